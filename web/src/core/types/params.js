@@ -6,7 +6,7 @@
  */
 
 /**
- * Formula name → numeric ID mapping (must match formula_id_from_u32 in lib.rs).
+ * Formula name → numeric ID mapping (must match the order of FormulaId::ALL in formulas/mod.rs).
  */
 const FORMULA_NAME_TO_ID = {
   '(none)': 0,
@@ -20,6 +20,14 @@ const FORMULA_NAME_TO_ID = {
   'Folding IntPow': 8,
   'Real Power': 9,
   'Aexion C': 10,
+  'ABoxMod1': 11,
+  'ABoxMod2': 12,
+  'ASurfMod1': 13,
+  'Power-N Bulb': 14,
+  'Lambdabulb': 15,
+  'Heightfield': 16,
+  'Text': 17,
+  'ASurfMod2': 18,
 };
 
 const HYBRID_MODE_TO_ID = {
@@ -58,7 +66,7 @@ export function hexToRgb(hex) {
  * [16] max_ray_length
  * [17] max_iterations
  * [18] bailout
- * [19] fov_factor
+ * [19] cone_scale (hit threshold in pixel-footprint radii, 0 = fixed de_stop)
  * [20] julia (0 or 1)
  * [21-23] julia_c (x, y, z)
  * [24] cut_enabled (0 or 1)
@@ -103,7 +111,7 @@ export function buildRenderParams(header) {
   params[16] = 50.0;
   params[17] = header.iterations;
   params[18] = 16.0;
-  params[19] = header.fov > 0 ? header.fov * 0.001 : 0.0;
+  params[19] = header.coneScale ?? 1.0;
   params[20] = header.julia ? 1.0 : 0.0;
   params[21] = header.juliaX;
   params[22] = header.juliaY;
@@ -0,0 +1,586 @@
+//! Keyframe animation: camera paths and animated render parameters.
+//!
+//! A timeline is a list of keyframes at increasing times. Positions, zoom and
+//! the extra parameters follow a Catmull-Rom spline whose tangents are scaled
+//! by the key spacing, so unevenly spaced keys do not change speed abruptly;
+//! orientations are slerped. Each segment can be eased.
+//!
+//! Positions are interpolated as offsets from the segment's start key, which
+//! keeps full f64 precision for cameras far from the origin or deep inside a
+//! zoom, where absolute coordinates cancel and the camera jitters.
+//!
+//! Timelines are described in JSON (see `TimelineDescription`):
+//!
+//! ```json
+//! { "fov": 53.13, "param_slots": [18],
+//!   "keyframes": [
+//!     { "time": 0, "position": [0, 0, -3], "target": [0, 0, 0], "params": [16] },
+//!     { "time": 4, "position": [2, 0, -2], "target": [0, 0, 0], "zoom": 2,
+//!       "params": [4], "z0_offset": [0.1, 0, 0], "easing": "ease_in_out" } ] }
+//! ```
+//!
+//! The formula's z0 offset lives in formula_ids rather than render_params,
+//! so it is animated by `frame_formula_ids`.
+//!
+//! Deep zooms get their own helper, `ZoomAnimation`: zoom changes by the
+//! same factor every frame and the march settings follow it, so the detail
+//! level stays put instead of popping between hand-tuned keys.
+
+use std::fmt;
+
+use serde::Deserialize;
+
+use crate::engine::camera::{self, CameraRays};
+use crate::engine::raymarcher;
+use crate::engine::types::{Matrix3, Vec3D};
+use crate::math::math3d::{self, Quaternion};
+use crate::math::strict;
+
+/// Why a timeline could not be built.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AnimationError {
+    /// Not valid JSON, or a field has the wrong type or name
+    Json(String),
+    /// No keyframes
+    Empty,
+    /// Keyframe time not after the previous one (index)
+    Time(usize),
+    /// Keyframe with a different number of params than the first (index)
+    ParamCount(usize),
+    /// Zoom factors must be positive and finite
+    Zoom,
+    /// Param slot outside the render_params layout
+    Slot(usize),
+}
+
+impl fmt::Display for AnimationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AnimationError::Json(e) => write!(f, "invalid timeline: {e}"),
+            AnimationError::Empty => write!(f, "timeline has no keyframes"),
+            AnimationError::Time(i) => write!(f, "keyframe {i} is not later than the one before"),
+            AnimationError::ParamCount(i) => write!(f, "keyframe {i} has a different number of params"),
+            AnimationError::Zoom => write!(f, "zoom factors must be positive and finite"),
+            AnimationError::Slot(slot) => write!(
+                f,
+                "param slot {slot} is outside the render_params layout (0..{})",
+                raymarcher::LAYOUT_MAGIC_INDEX
+            ),
+        }
+    }
+}
+
+impl std::error::Error for AnimationError {}
+
+/// Progress curve of a segment, applied before interpolating.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Easing {
+    #[default]
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+    /// Keep the start key's values until the next key
+    Hold,
+}
+
+impl Easing {
+    /// Eased progress for linear progress `u` in [0, 1].
+    pub fn apply(self, u: f64) -> f64 {
+        match self {
+            Easing::Linear => u,
+            Easing::EaseIn => u * u,
+            Easing::EaseOut => u * (2.0 - u),
+            Easing::EaseInOut => u * u * (3.0 - 2.0 * u),
+            Easing::Hold => {
+                if u >= 1.0 { 1.0 } else { 0.0 }
+            }
+        }
+    }
+}
+
+/// Camera and parameter state at one time.
+#[derive(Clone, Debug)]
+pub struct Keyframe {
+    pub time: f64,
+    pub position: Vec3D,
+    /// Camera to world rotation: camera x right, y up, z forward (the image
+    /// y axis points down, so this is `ray_dy` negated)
+    pub orientation: Quaternion,
+    /// Magnification of the timeline's field of view
+    pub zoom: f64,
+    /// Values for the timeline's `param_slots`
+    pub params: Vec<f64>,
+    /// Initial orbit offset of the formula (see `HybridFormula::z0_offset`)
+    pub z0_offset: Vec3D,
+    /// Easing of the segment from this key to the next
+    pub easing: Easing,
+}
+
+/// Sorted keyframes plus how they map onto the render parameters.
+#[derive(Clone, Debug)]
+pub struct Timeline {
+    pub keyframes: Vec<Keyframe>,
+    /// Vertical field of view at zoom 1, radians (0 = params.js default)
+    pub fov: f64,
+    /// render_params slot of each keyframe param
+    pub param_slots: Vec<usize>,
+}
+
+/// JSON form of one keyframe. The orientation is a quaternion `[w, x, y, z]`
+/// or, if absent, looks from `position` at `target`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeyframeDescription {
+    pub time: f64,
+    pub position: [f64; 3],
+    pub orientation: Option<[f64; 4]>,
+    pub target: [f64; 3],
+    pub up: [f64; 3],
+    pub zoom: f64,
+    pub params: Vec<f64>,
+    pub z0_offset: [f64; 3],
+    pub easing: Easing,
+}
+
+impl Default for KeyframeDescription {
+    fn default() -> Self {
+        Self {
+            time: 0.0,
+            position: [0.0, 0.0, -3.0],
+            orientation: None,
+            target: [0.0; 3],
+            up: [0.0, 1.0, 0.0],
+            zoom: 1.0,
+            params: Vec::new(),
+            z0_offset: [0.0; 3],
+            easing: Easing::Linear,
+        }
+    }
+}
+
+/// JSON form of a timeline.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TimelineDescription {
+    /// Vertical field of view at zoom 1, degrees
+    pub fov: f64,
+    pub param_slots: Vec<usize>,
+    pub keyframes: Vec<KeyframeDescription>,
+}
+
+/// Camera orientation of a ray basis (the inverse of `rays`).
+pub fn orientation_from_rays(rays: &CameraRays) -> Quaternion {
+    let right = math3d::vec3d_normalized(&rays.dx);
+    let up = math3d::vec3d_normalized(&math3d::vec3d_scale(&rays.dy, -1.0));
+    let forward = math3d::vec3d_normalized(&rays.dir_base);
+    let m = Matrix3 {
+        m: [[right.x, up.x, forward.x], [right.y, up.y, forward.y], [right.z, up.z, forward.z]],
+    };
+    Quaternion::from_matrix3(&m)
+}
+
+/// Ray basis of a camera orientation, with the vertical field of view `fov`
+/// (radians, 0 = params.js default) narrowed by `zoom`.
+pub fn rays(orientation: &Quaternion, fov: f64, zoom: f64, width: u32, height: u32) -> CameraRays {
+    let mut q = *orientation;
+    q.normalize();
+    let m = q.to_matrix3().m;
+    let column = |j: usize| Vec3D { x: m[0][j], y: m[1][j], z: m[2][j] };
+    let fov_scale = if fov > 0.0 { strict::tan(fov * 0.5) } else { 0.5 } / zoom.max(1e-300);
+    let aspect = width.max(1) as f64 / height.max(1) as f64;
+    CameraRays {
+        dir_base: column(2),
+        dx: math3d::vec3d_scale(&column(0), fov_scale * aspect),
+        dy: math3d::vec3d_scale(&column(1), -fov_scale),
+    }
+}
+
+/// Hermite segment from `p1` to `p2` with tangents `m1`, `m2` (already
+/// scaled to the segment length).
+fn hermite(p1: f64, p2: f64, m1: f64, m2: f64, u: f64) -> f64 {
+    let (u2, u3) = (u * u, u * u * u);
+    (2.0 * u3 - 3.0 * u2 + 1.0) * p1 + (u3 - 2.0 * u2 + u) * m1 + (-2.0 * u3 + 3.0 * u2) * p2 + (u3 - u2) * m2
+}
+
+impl Timeline {
+    /// Check the keyframes: at least one, strictly increasing times and the
+    /// same number of params each, and param slots inside the render_params
+    /// layout.
+    pub fn new(keyframes: Vec<Keyframe>, fov: f64, param_slots: Vec<usize>) -> Result<Self, AnimationError> {
+        let first = keyframes.first().ok_or(AnimationError::Empty)?;
+        for (i, pair) in keyframes.windows(2).enumerate() {
+            if pair[1].time.partial_cmp(&pair[0].time) != Some(std::cmp::Ordering::Greater) {
+                return Err(AnimationError::Time(i + 1));
+            }
+        }
+        if let Some(i) = keyframes.iter().position(|k| k.params.len() != first.params.len()) {
+            return Err(AnimationError::ParamCount(i));
+        }
+        if let Some(&slot) = param_slots.iter().find(|&&slot| slot >= raymarcher::LAYOUT_MAGIC_INDEX) {
+            return Err(AnimationError::Slot(slot));
+        }
+        Ok(Self { keyframes, fov, param_slots })
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, AnimationError> {
+        let desc: TimelineDescription = serde_json::from_str(json).map_err(|e| AnimationError::Json(e.to_string()))?;
+        let keyframes = desc
+            .keyframes
+            .into_iter()
+            .map(|k| {
+                let position = Vec3D { x: k.position[0], y: k.position[1], z: k.position[2] };
+                let orientation = match k.orientation {
+                    Some([w, x, y, z]) => Quaternion { w, x, y, z },
+                    None => {
+                        let target = Vec3D { x: k.target[0], y: k.target[1], z: k.target[2] };
+                        let up = Vec3D { x: k.up[0], y: k.up[1], z: k.up[2] };
+                        orientation_from_rays(&camera::compute_camera_rays(&position, &target, &up, 0.0, 1, 1))
+                    }
+                };
+                let [x, y, z] = k.z0_offset;
+                Keyframe {
+                    time: k.time,
+                    position,
+                    orientation,
+                    zoom: k.zoom,
+                    params: k.params,
+                    z0_offset: Vec3D { x, y, z },
+                    easing: k.easing,
+                }
+            })
+            .collect();
+        Self::new(keyframes, desc.fov.to_radians(), desc.param_slots)
+    }
+
+    pub fn start(&self) -> f64 {
+        self.keyframes[0].time
+    }
+
+    pub fn end(&self) -> f64 {
+        self.keyframes[self.keyframes.len() - 1].time
+    }
+
+    /// Segment start key and eased progress at time `t` (clamped to the timeline).
+    fn locate(&self, t: f64) -> (usize, f64) {
+        let keys = &self.keyframes;
+        let t = t.clamp(self.start(), self.end());
+        let i = keys.partition_point(|k| k.time <= t).saturating_sub(1).min(keys.len().saturating_sub(2));
+        if keys.len() < 2 {
+            return (0, 0.0);
+        }
+        let u = ((t - keys[i].time) / (keys[i + 1].time - keys[i].time)).clamp(0.0, 1.0);
+        (i, keys[i].easing.apply(u))
+    }
+
+    /// Catmull-Rom value of channel `value` on segment `i` at progress `u`,
+    /// relative to the start key's value.
+    fn spline(&self, i: usize, u: f64, value: impl Fn(&Keyframe) -> f64) -> f64 {
+        let keys = &self.keyframes;
+        let n = keys.len();
+        let (k1, k2) = (&keys[i], &keys[i + 1]);
+        let (p1, p2) = (0.0, value(k2) - value(k1));
+        let h = k2.time - k1.time;
+        // Finite-difference tangents per unit time; one-sided at the ends
+        let tangent = |j: usize| {
+            let (a, b) = (j.saturating_sub(1), (j + 1).min(n - 1));
+            (value(&keys[b]) - value(&keys[a])) / (keys[b].time - keys[a].time)
+        };
+        hermite(p1, p2, tangent(i) * h, tangent(i + 1) * h, u)
+    }
+
+    /// Interpolated keyframe at time `t` (clamped to the timeline).
+    pub fn sample(&self, t: f64) -> Keyframe {
+        let (i, u) = self.locate(t);
+        let k1 = &self.keyframes[i];
+        if self.keyframes.len() < 2 || u == 0.0 {
+            return Keyframe { time: t, ..k1.clone() };
+        }
+        let k2 = &self.keyframes[i + 1];
+        let offset = Vec3D {
+            x: self.spline(i, u, |k| k.position.x),
+            y: self.spline(i, u, |k| k.position.y),
+            z: self.spline(i, u, |k| k.position.z),
+        };
+        let mut orientation = k1.orientation.slerp(&k2.orientation, u);
+        orientation.normalize();
+        Keyframe {
+            time: t,
+            position: math3d::vec3d_add(&k1.position, &offset),
+            orientation,
+            zoom: (k1.zoom + self.spline(i, u, |k| k.zoom)).max(1e-300),
+            params: (0..k1.params.len()).map(|p| k1.params[p] + self.spline(i, u, |k| k.params[p])).collect(),
+            z0_offset: Vec3D {
+                x: k1.z0_offset.x + self.spline(i, u, |k| k.z0_offset.x),
+                y: k1.z0_offset.y + self.spline(i, u, |k| k.z0_offset.y),
+                z: k1.z0_offset.z + self.spline(i, u, |k| k.z0_offset.z),
+            },
+            easing: k1.easing,
+        }
+    }
+
+    /// `render_params` with the camera and params of time `t` written in.
+    pub fn frame_render_params(&self, t: f64, render_params: &[f64]) -> Vec<f64> {
+        let mut out = render_params.to_vec();
+        if out.len() < 14 {
+            out.resize(14, 0.0);
+        }
+        let key = self.sample(t);
+        let rays = rays(&key.orientation, self.fov, key.zoom, out[0] as u32, out[1] as u32);
+        out[2..5].copy_from_slice(&[key.position.x, key.position.y, key.position.z]);
+        out[5..14].copy_from_slice(&rays.to_array());
+        for (&slot, &v) in self.param_slots.iter().zip(&key.params) {
+            if slot >= out.len() {
+                // Slots a short buffer leaves out take their defaults
+                out.extend_from_slice(&raymarcher::default_params_buffer()[out.len()..=slot]);
+            }
+            out[slot] = v;
+        }
+        out
+    }
+
+    /// `formula_ids` with the z0 offset of time `t` written in; unchanged
+    /// when no keyframe sets one.
+    pub fn frame_formula_ids(&self, t: f64, formula_ids: &[u32]) -> Vec<u32> {
+        if self.keyframes.iter().all(|k| k.z0_offset == Vec3D::default()) {
+            return formula_ids.to_vec();
+        }
+        crate::with_z0_offset_section(formula_ids, &self.sample(t).z0_offset)
+    }
+}
+
+/// Exponential zoom toward a fixed point, relative to the view of a base
+/// `render_params` buffer (zoom 1).
+///
+/// Zoom `z` moves the camera to `1 / z` of its base distance from `target`
+/// along the same view, and scales `max_ray_length` by `1 / z`. `de_stop`
+/// is kept at `detail` pixels on the focus plane through `target`, and the
+/// step width moves geometrically from `step_width_start` to
+/// `step_width_end` as the zoom progresses.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ZoomAnimation {
+    pub target: [f64; 3],
+    pub zoom_start: f64,
+    pub zoom_end: f64,
+    /// `de_stop` in pixels (0 = the base buffer's automatic detail, or if
+    /// that is off its `de_stop` scaled with the zoom)
+    pub detail: f64,
+    /// Step width multipliers at the ends (0 = the base buffer's)
+    pub step_width_start: f64,
+    pub step_width_end: f64,
+    /// Applied to time before the zoom is interpolated
+    pub easing: Easing,
+}
+
+impl Default for ZoomAnimation {
+    fn default() -> Self {
+        Self {
+            target: [0.0; 3],
+            zoom_start: 1.0,
+            zoom_end: 1.0,
+            detail: 0.0,
+            step_width_start: 0.0,
+            step_width_end: 0.0,
+            easing: Easing::Linear,
+        }
+    }
+}
+
+/// Geometric interpolation from `a` to `b` (both positive), exact at the ends.
+fn log_lerp(a: f64, b: f64, u: f64) -> f64 {
+    if u >= 1.0 { b } else { a * strict::exp(u * strict::ln(b / a)) }
+}
+
+impl ZoomAnimation {
+    pub fn from_json(json: &str) -> Result<Self, AnimationError> {
+        let zoom: ZoomAnimation = serde_json::from_str(json).map_err(|e| AnimationError::Json(e.to_string()))?;
+        let valid = |z: f64| z > 0.0 && z.is_finite();
+        if !valid(zoom.zoom_start) || !valid(zoom.zoom_end) {
+            return Err(AnimationError::Zoom);
+        }
+        Ok(zoom)
+    }
+
+    /// Eased progress at time `t` in [0, 1] (clamped).
+    fn progress(&self, t: f64) -> f64 {
+        self.easing.apply(t.clamp(0.0, 1.0))
+    }
+
+    /// Zoom factor at time `t` in [0, 1].
+    pub fn zoom_at(&self, t: f64) -> f64 {
+        log_lerp(self.zoom_start, self.zoom_end, self.progress(t))
+    }
+
+    /// `render_params` of the frame at time `t` in [0, 1]: camera position,
+    /// `de_stop`, `step_width` and `max_ray_length` written in, and automatic
+    /// detail (slot 109) cleared so the explicit `de_stop` is used.
+    pub fn frame_render_params(&self, t: f64, render_params: &[f64]) -> Vec<f64> {
+        let mut out = render_params.to_vec();
+        if out.len() < 32 {
+            out.resize(32, 0.0);
+        }
+        let base = raymarcher::params_from_buffer(&out);
+        let target = Vec3D { x: self.target[0], y: self.target[1], z: self.target[2] };
+        let zoom = self.zoom_at(t);
+
+        // Offsets from the target keep precision deep in the zoom
+        let offset = math3d::vec3d_sub(&base.camera_pos, &target);
+        let position = math3d::vec3d_add(&target, &math3d::vec3d_scale(&offset, 1.0 / zoom));
+        // A pixel on the focus plane shrinks with the camera distance
+        let pixel = 2.0 * base.pixel_footprint(math3d::vec3d_length(&offset));
+        let detail = if self.detail > 0.0 { self.detail } else { base.auto_detail };
+        let de_stop = if detail > 0.0 && pixel > 0.0 { detail * pixel } else { base.de_stop } / zoom;
+
+        let or_base = |w: f64| if w > 0.0 { w } else { base.step_width };
+        let step_width = log_lerp(or_base(self.step_width_start), or_base(self.step_width_end), self.progress(t));
+
+        out[2..5].copy_from_slice(&[position.x, position.y, position.z]);
+        out[14] = de_stop;
+        out[15] = step_width;
+        out[16] = base.max_ray_length / zoom;
+        if let Some(auto_detail) = out.get_mut(109) {
+            *auto_detail = 0.0;
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(time: f64, x: f64, params: Vec<f64>) -> Keyframe {
+        Keyframe {
+            time,
+            position: Vec3D { x, y: 0.0, z: 0.0 },
+            orientation: Quaternion::identity(),
+            zoom: 1.0,
+            params,
+            z0_offset: Vec3D::default(),
+            easing: Easing::Linear,
+        }
+    }
+
+    #[test]
+    fn test_spline_hits_keys_and_keeps_even_motion_straight() {
+        let timeline = Timeline::new(vec![key(0.0, 0.0, vec![1.0]), key(1.0, 1.0, vec![2.0]), key(3.0, 3.0, vec![4.0])], 0.0, vec![18]).unwrap();
+        for (t, x) in [(0.0, 0.0), (1.0, 1.0), (3.0, 3.0), (-5.0, 0.0), (9.0, 3.0)] {
+            assert_eq!(timeline.sample(t).position.x, x, "t = {t}");
+        }
+        // Constant speed across unevenly spaced keys stays constant
+        for t in [0.25, 0.5, 1.5, 2.2, 2.9] {
+            let k = timeline.sample(t);
+            assert!((k.position.x - t).abs() < 1e-12 && (k.params[0] - (t + 1.0)).abs() < 1e-12, "t = {t}");
+        }
+
+        // Far from the origin the step between frames is still resolved
+        let far = 1e9;
+        let timeline = Timeline::new(vec![key(0.0, far, vec![]), key(1.0, far + 1e-6, vec![])], 0.0, vec![]).unwrap();
+        let gap = (far + 1e-6) - far;
+        assert_eq!(timeline.sample(0.5).position.x - far, gap * 0.5);
+    }
+
+    #[test]
+    fn test_orientation_and_render_params() {
+        // Looking along +z, then turned 90° to look along +x
+        let json = r#"{ "fov": 90, "param_slots": [18], "keyframes": [
+            { "time": 0, "position": [0, 0, 0], "target": [0, 0, 1], "params": [16] },
+            { "time": 2, "position": [0, 0, 0], "target": [1, 0, 0], "zoom": 2, "params": [8], "easing": "ease_in_out" } ] }"#;
+        let timeline = Timeline::from_json(json).unwrap();
+        let buffer = timeline.frame_render_params(1.0, &[200.0, 100.0]);
+        let dir = Vec3D { x: buffer[5], y: buffer[6], z: buffer[7] };
+        let half = std::f64::consts::FRAC_1_SQRT_2;
+        assert!((dir.x - half).abs() < 1e-12 && dir.y.abs() < 1e-12 && (dir.z - half).abs() < 1e-12, "{dir:?}");
+        // Halfway zoom 1.5 at fov 90: tan 45° / 1.5, times the 2:1 aspect horizontally
+        let dy = math3d::vec3d_length(&Vec3D { x: buffer[11], y: buffer[12], z: buffer[13] });
+        let dx = math3d::vec3d_length(&Vec3D { x: buffer[8], y: buffer[9], z: buffer[10] });
+        assert!((dy - 1.0 / 1.5).abs() < 1e-12 && (dx - 2.0 * dy).abs() < 1e-12);
+        assert_eq!(buffer[18], 12.0);
+
+        // The key camera matches a look-at camera
+        let end = timeline.frame_render_params(2.0, &[200.0, 100.0]);
+        let look = camera::compute_camera_rays(
+            &Vec3D::default(), &Vec3D { x: 1.0, y: 0.0, z: 0.0 }, &Vec3D { x: 0.0, y: 1.0, z: 0.0 },
+            std::f64::consts::FRAC_PI_2, 200, 100,
+        );
+        let expected = rays(&orientation_from_rays(&look), std::f64::consts::FRAC_PI_2, 2.0, 200, 100);
+        for (a, b) in end[5..14].iter().zip(expected.to_array()) {
+            assert!((a - b).abs() < 1e-12);
+        }
+        for (a, b) in look.to_array()[..3].iter().zip(&end[5..8]) {
+            assert!((a - b).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_z0_offset_goes_to_formula_ids() {
+        use crate::formulas::FormulaId;
+        let json = r#"{ "keyframes": [ { "time": 0 }, { "time": 1, "z0_offset": [1, 0, -2] } ] }"#;
+        let timeline = Timeline::from_json(json).unwrap();
+        let ids = [1, FormulaId::MandelbulbPower8 as u32, 8, 0];
+        let mid = crate::build_formula_from_ids(&timeline.frame_formula_ids(0.5, &ids), 8, 16.0).z0_offset;
+        assert!((mid.x - 0.5).abs() < 1e-12 && mid.y == 0.0 && (mid.z + 1.0).abs() < 1e-12, "{mid:?}");
+        // A zero offset leaves no section behind
+        assert_eq!(timeline.frame_formula_ids(0.0, &ids), ids);
+        let still = Timeline::from_json(r#"{ "keyframes": [ { "time": 0 } ] }"#).unwrap();
+        let with_section = crate::with_z0_offset_section(&ids, &Vec3D { x: 1.0, y: 2.0, z: 3.0 });
+        assert_eq!(still.frame_formula_ids(0.0, &with_section), with_section);
+    }
+
+    #[test]
+    fn test_easing_and_errors() {
+        let mut keys = vec![key(0.0, 0.0, vec![]), key(1.0, 1.0, vec![])];
+        keys[0].easing = Easing::Hold;
+        let held = Timeline::new(keys.clone(), 0.0, vec![]).unwrap();
+        assert_eq!((held.sample(0.99).position.x, held.sample(1.0).position.x), (0.0, 1.0));
+        assert_eq!(Easing::EaseInOut.apply(0.5), 0.5);
+        assert!(Easing::EaseIn.apply(0.5) < 0.5 && Easing::EaseOut.apply(0.5) > 0.5);
+
+        keys[1].time = 0.0;
+        assert_eq!(Timeline::new(keys, 0.0, vec![]).err(), Some(AnimationError::Time(1)));
+        assert_eq!(Timeline::new(vec![], 0.0, vec![]).err(), Some(AnimationError::Empty));
+        let mixed = vec![key(0.0, 0.0, vec![1.0]), key(1.0, 0.0, vec![])];
+        assert_eq!(Timeline::new(mixed, 0.0, vec![]).err(), Some(AnimationError::ParamCount(1)));
+        assert!(matches!(Timeline::from_json(r#"{ "frames": [] }"#), Err(AnimationError::Json(_))));
+        let json = r#"{ "param_slots": [1000000], "keyframes": [ { "time": 0, "params": [1] } ] }"#;
+        assert_eq!(Timeline::from_json(json).err(), Some(AnimationError::Slot(1000000)));
+    }
+
+    #[test]
+    fn test_zoom_tracks_detail() {
+        let zoom = ZoomAnimation::from_json(
+            r#"{ "target": [1, 0, 0], "zoom_start": 1, "zoom_end": 1e6, "step_width_start": 0.8, "step_width_end": 0.2 }"#,
+        )
+        .unwrap();
+        assert!((zoom.zoom_at(0.5) - 1e3).abs() < 1e-9 && zoom.zoom_at(2.0) == 1e6);
+
+        let mut buffer = vec![0.0; 110];
+        let rays = camera::compute_camera_rays(
+            &Vec3D { x: 1.0, y: 0.0, z: -2.0 }, &Vec3D { x: 1.0, y: 0.0, z: 0.0 }, &Vec3D { x: 0.0, y: 1.0, z: 0.0 },
+            0.0, 200, 100,
+        );
+        buffer[..5].copy_from_slice(&[200.0, 100.0, 1.0, 0.0, -2.0]);
+        buffer[5..14].copy_from_slice(&rays.to_array());
+        buffer[14..18].copy_from_slice(&[0.004, 0.8, 40.0, 12.0]);
+
+        let frame = zoom.frame_render_params(0.5, &buffer);
+        assert_eq!(&frame[2..4], &[1.0, 0.0]);
+        assert!((frame[4] + 2e-3).abs() < 1e-15);
+        assert!((frame[15] - 0.4).abs() < 1e-12 && (frame[16] - 0.04).abs() < 1e-12);
+        // The base de_stop is kept in pixels: it scales with the zoom
+        assert!((frame[14] - 4e-6).abs() < 1e-18);
+
+        // Automatic detail is measured at the target and written out explicitly
+        buffer[109] = 3.0;
+        let frame = zoom.frame_render_params(0.5, &buffer);
+        let pixel = 2.0 * raymarcher::params_from_buffer(&frame).pixel_footprint(2e-3);
+        assert!((frame[14] - 3.0 * pixel).abs() < 1e-18);
+        assert_eq!(frame[109], 0.0);
+        let detailed = ZoomAnimation { detail: 1.5, ..zoom.clone() }.frame_render_params(0.5, &buffer);
+        assert!((detailed[14] - 1.5 * pixel).abs() < 1e-18);
+
+        assert_eq!(ZoomAnimation::from_json(r#"{ "zoom_end": 0 }"#), Err(AnimationError::Zoom));
+    }
+}
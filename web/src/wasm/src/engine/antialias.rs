@@ -0,0 +1,194 @@
+//! Adaptive antialiasing — supersample only where the G-buffer has edges.
+//!
+//! After the primary march and paint, pixels whose neighbours differ in
+//! hit/miss state, depth or normal direction are re-rendered on an n × n
+//! sub-pixel grid (4× / 9×) and their shaded colors averaged into the RGBA
+//! output. Flat regions keep their single sample, so the cost scales with the
+//! amount of edge detail instead of the image size.
+
+use crate::engine::raymarcher::{self, RenderParams};
+use crate::engine::types::SiLight5;
+use crate::formulas::hybrid::HybridFormula;
+use crate::lighting::paint::{PaintConfig, PaintLayers, PixelPainter};
+
+/// Edge detection and supersampling settings.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AaSettings {
+    /// Sub-samples per axis for edge pixels (2 = 4×, 3 = 9×)
+    pub samples_per_axis: u32,
+    /// Normalized depth difference that counts as an edge
+    pub depth_threshold: f64,
+    /// Minimum cosine between neighbour normals before it counts as an edge
+    pub normal_threshold: f64,
+}
+
+impl Default for AaSettings {
+    fn default() -> Self {
+        Self { samples_per_axis: 2, depth_threshold: 0.002, normal_threshold: 0.9 }
+    }
+}
+
+#[inline]
+fn is_hit(p: &SiLight5) -> bool {
+    p.z_pos < 65534
+}
+
+/// Do two G-buffer entries differ enough to form an edge?
+fn differs(a: &SiLight5, b: &SiLight5, settings: &AaSettings) -> bool {
+    if is_hit(a) != is_hit(b) {
+        return true;
+    }
+    if !is_hit(a) {
+        return false;
+    }
+    let dz = (a.z_pos as f64 - b.z_pos as f64).abs() / 65535.0;
+    if dz > settings.depth_threshold {
+        return true;
+    }
+    let na = (a.sn_x as f64, a.sn_y as f64, a.sn_z as f64);
+    let nb = (b.sn_x as f64, b.sn_y as f64, b.sn_z as f64);
+    let la = (na.0 * na.0 + na.1 * na.1 + na.2 * na.2).sqrt();
+    let lb = (nb.0 * nb.0 + nb.1 * nb.1 + nb.2 * nb.2).sqrt();
+    if la < 1e-9 || lb < 1e-9 {
+        return false;
+    }
+    (na.0 * nb.0 + na.1 * nb.1 + na.2 * nb.2) / (la * lb) < settings.normal_threshold
+}
+
+/// Is pixel (x, y) on a depth / normal / silhouette discontinuity?
+pub fn is_edge(gbuffer: &[SiLight5], width: u32, height: u32, x: u32, y: u32, settings: &AaSettings) -> bool {
+    let idx = (y * width + x) as usize;
+    let Some(center) = gbuffer.get(idx) else { return false };
+    let neighbours = [
+        (x > 0).then(|| idx - 1),
+        (x + 1 < width).then_some(idx + 1),
+        (y > 0).then(|| idx - width as usize),
+        (y + 1 < height).then_some(idx + width as usize),
+    ];
+    neighbours.iter()
+        .flatten()
+        .filter_map(|&n| gbuffer.get(n))
+        .any(|n| differs(center, n, settings))
+}
+
+/// Re-render the edge pixels of this worker's scanlines with supersampling.
+///
+/// `rgba_out` must already hold the image painted from `gbuffer` and
+/// `layers`. Sub-samples go through the same per-pixel paint as the rest of
+/// the image, with the secondary layers and neighbourhood maps (SSAO,
+/// exposure, shafts, outlines) of the pixel they belong to; extended records
+/// are ignored. Returns the number of pixels that were supersampled.
+#[allow(clippy::too_many_arguments)]
+pub fn supersample_edges(
+    params: &RenderParams,
+    formula: &HybridFormula,
+    config: &PaintConfig,
+    gbuffer: &[SiLight5],
+    layers: PaintLayers,
+    rgba_out: &mut [u8],
+    worker_id: u32,
+    worker_count: u32,
+) -> u32 {
+    let settings = &params.aa;
+    let (w, h) = (params.width, params.height);
+    let n = settings.samples_per_axis.clamp(1, 8);
+    let inv = 1.0 / (n * n) as f64;
+    let mut resampled = 0;
+    let mut painter = None;
+
+    let mut y = worker_id;
+    while y < h {
+        for x in 0..w {
+            let ri = ((y * w + x) * 4) as usize;
+            if ri + 3 >= rgba_out.len() || !is_edge(gbuffer, w, h, x, y, settings) {
+                continue;
+            }
+
+            let painter = painter.get_or_insert_with(|| {
+                let layers = PaintLayers { extended: None, ..layers };
+                PixelPainter::new(gbuffer, layers, w, h, 0..h, config)
+            });
+            let i = (y * w + x) as usize;
+            let mut sum = (0.0, 0.0, 0.0);
+            for sy in 0..n {
+                for sx in 0..n {
+                    // Regular sub-pixel grid centred on the pixel
+                    let fx = x as f64 + (sx as f64 + 0.5) / n as f64 - 0.5;
+                    let fy = y as f64 + (sy as f64 + 0.5) / n as f64 - 0.5;
+                    let entry = raymarcher::render_subpixel(params, formula, fx, fy);
+                    let c = painter.color(i, fx, fy, &entry);
+                    sum = (sum.0 + c.0, sum.1 + c.1, sum.2 + c.2);
+                }
+            }
+
+            rgba_out[ri] = config.encode(sum.0 * inv);
+            rgba_out[ri + 1] = config.encode(sum.1 * inv);
+            rgba_out[ri + 2] = config.encode(sum.2 * inv);
+            resampled += 1;
+        }
+        y += worker_count;
+    }
+
+    resampled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hit(z: u16, nx: i16) -> SiLight5 {
+        SiLight5 { z_pos: z, sn_x: nx, sn_z: 32767, ..Default::default() }
+    }
+
+    #[test]
+    fn test_edges_on_silhouette_only() {
+        let miss = SiLight5 { z_pos: 65535, ..Default::default() };
+        // 3×1: hit, hit, miss
+        let row = [hit(1000, 0), hit(1000, 0), miss];
+        let s = AaSettings::default();
+        assert!(!is_edge(&row, 3, 1, 0, 0, &s));
+        assert!(is_edge(&row, 3, 1, 1, 0, &s));
+        assert!(is_edge(&row, 3, 1, 2, 0, &s));
+    }
+
+    #[test]
+    fn test_normal_and_depth_discontinuities() {
+        let s = AaSettings::default();
+        assert!(is_edge(&[hit(1000, 0), hit(1000, 32767)], 2, 1, 0, 0, &s));
+        assert!(is_edge(&[hit(1000, 0), hit(5000, 0)], 2, 1, 0, 0, &s));
+        assert!(!is_edge(&[hit(1000, 0), hit(1010, 100)], 2, 1, 0, 0, &s));
+    }
+
+    #[test]
+    fn test_single_sample_matches_paint() {
+        use crate::formulas::{hybrid::HybridMode, FormulaId};
+        use crate::lighting::paint::{self, PaintView};
+        use crate::lighting::post::{ExposureSettings, GlowSettings};
+        use crate::lighting::sky::SkySettings;
+        use crate::lighting::ssao::SsaoSettings;
+
+        // One sub-sample per pixel re-marches the primary ray, so the
+        // resampled edges must come out exactly as painted
+        let mut params = RenderParams { width: 16, height: 16, ..Default::default() };
+        params.aa.samples_per_axis = 1;
+        let formula = HybridFormula::new(&[(FormulaId::MandelbulbPower8, 1)], HybridMode::Alternating, 8, 16.0);
+        let mut gbuffer = vec![SiLight5::default(); 256];
+        raymarcher::render_scanlines(&params, &formula, &mut gbuffer, 0, 1);
+
+        let config = PaintConfig {
+            view: Some(PaintView::from_render_params(&params)),
+            sky: Some(SkySettings::default()),
+            glow: Some(GlowSettings::default()),
+            ssao: SsaoSettings { strength: 0.8, ..Default::default() },
+            exposure: ExposureSettings { strength: 1.0, ..Default::default() },
+            ..Default::default()
+        };
+        let mut painted = vec![0u8; 256 * 4];
+        paint::paint_gbuffer(&gbuffer, &mut painted, 16, 16, &config);
+        let mut resampled = painted.clone();
+        let n = supersample_edges(&params, &formula, &config, &gbuffer, PaintLayers::default(), &mut resampled, 0, 1);
+
+        assert!(n > 0);
+        assert_eq!(painted, resampled);
+    }
+}
@@ -0,0 +1,116 @@
+//! Ambient occlusion by hemispheric DE sampling — port of CalcAmbShadowDE.pas.
+//!
+//! Instead of deriving occlusion from the ray-march step count, this samples
+//! the distance field along several directions in the normal hemisphere at
+//! increasing distances (MB3D's multi-level DEAO). Wherever the DE is smaller
+//! than the distance travelled from the surface, nearby geometry occludes.
+
+use crate::engine::types::Vec3D;
+use crate::formulas::DistanceField;
+use crate::math::{math3d, strict, utils};
+
+/// Quality settings for the DE-sampled AO pass.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AoSettings {
+    /// Number of hemisphere directions (0 disables the pass)
+    pub samples: u32,
+    /// Number of distance levels per direction
+    pub levels: u32,
+    /// Maximum sampling distance in world units
+    pub radius: f64,
+}
+
+impl Default for AoSettings {
+    fn default() -> Self {
+        Self { samples: 0, levels: 4, radius: 0.1 }
+    }
+}
+
+impl AoSettings {
+    pub fn enabled(&self) -> bool {
+        self.samples > 0 && self.levels > 0 && self.radius > 0.0
+    }
+}
+
+/// Direction `i` of `count` in the hemisphere around `normal`.
+///
+/// Golden-angle spiral over a cosine-weighted hemisphere, so the samples are
+/// deterministic and evenly spread without needing an RNG.
+pub fn hemisphere_direction(normal: &Vec3D, i: u32, count: u32) -> Vec3D {
+    let (t, b) = math3d::vec3d_orthonormal_basis(normal);
+    let golden = std::f64::consts::PI * (3.0 - 5.0f64.sqrt());
+    let u = (i as f64 + 0.5) / count.max(1) as f64;
+    let r = u.sqrt();
+    let phi = i as f64 * golden;
+    let (sp, cp) = strict::sin_cos(phi);
+    let lx = r * cp;
+    let ly = r * sp;
+    let lz = (1.0 - u).max(0.0).sqrt();
+    Vec3D {
+        x: t.x * lx + b.x * ly + normal.x * lz,
+        y: t.y * lx + b.y * ly + normal.y * lz,
+        z: t.z * lx + b.z * ly + normal.z * lz,
+    }
+}
+
+/// Occlusion in [0, 1] at a surface point (0 = open, 1 = fully occluded).
+///
+/// The result is written to the G-buffer `ambient` channel.
+pub fn hemisphere_ao<F: DistanceField + ?Sized>(
+    hit_pos: &Vec3D,
+    normal: &Vec3D,
+    formula: &F,
+    settings: &AoSettings,
+) -> f64 {
+    if !settings.enabled() {
+        return 0.0;
+    }
+
+    let levels = settings.levels as f64;
+    let mut occlusion = 0.0;
+
+    for i in 0..settings.samples {
+        let dir = hemisphere_direction(normal, i, settings.samples);
+        let mut dir_occ = 0.0f64;
+        for level in 1..=settings.levels {
+            // Quadratic level spacing: dense close to the surface
+            let f = level as f64 / levels;
+            let dist = settings.radius * f * f;
+            let p = Vec3D {
+                x: hit_pos.x + dir.x * dist,
+                y: hit_pos.y + dir.y * dist,
+                z: hit_pos.z + dir.z * dist,
+            };
+            let de = formula.compute_de(&p).de;
+            dir_occ = dir_occ.max(utils::clamp(1.0 - de / dist, 0.0, 1.0));
+        }
+        occlusion += dir_occ;
+    }
+
+    occlusion / settings.samples as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formulas::{hybrid::{HybridFormula, HybridMode}, FormulaId};
+
+    #[test]
+    fn test_hemisphere_directions_face_normal() {
+        let n = Vec3D { x: 0.0, y: 1.0, z: 0.0 };
+        for i in 0..16 {
+            let d = hemisphere_direction(&n, i, 16);
+            assert!(math3d::vec3d_dot(&d, &n) > 0.0);
+            assert!((math3d::vec3d_length(&d) - 1.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_ao_open_space_is_unoccluded() {
+        let formula = HybridFormula::new(&[(FormulaId::MandelbulbPower8, 1)], HybridMode::Alternating, 12, 16.0);
+        let settings = AoSettings { samples: 8, levels: 3, radius: 0.05 };
+        let pos = Vec3D { x: 3.0, y: 0.0, z: 0.0 };
+        let n = Vec3D { x: 1.0, y: 0.0, z: 0.0 };
+        assert!(hemisphere_ao(&pos, &n, &formula, &settings) < 1e-9);
+    }
+}
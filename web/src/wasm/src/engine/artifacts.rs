@@ -0,0 +1,128 @@
+//! Scene-compiled artifact cache shared across render calls and threads.
+//!
+//! Animation frames with static geometry and the many worker calls of one
+//! frame all rebuild the same scene-derived data (the compiled hybrid
+//! formula, and later precomputed tables such as lighting CDFs or occupancy
+//! grids). Artifacts are stored per scene hash and artifact type behind
+//! `Arc`, so any thread can hold one while the cache evicts it; the least
+//! recently used entries are dropped once `capacity` is exceeded.
+
+use std::any::{Any, TypeId};
+use std::sync::{Arc, Mutex};
+
+/// Default number of cached artifacts.
+pub const DEFAULT_CAPACITY: usize = 32;
+
+type Entry = ((u64, TypeId), Arc<dyn Any + Send + Sync>);
+
+/// Thread-safe LRU cache of scene artifacts; most recently used at the back.
+pub struct ArtifactCache {
+    entries: Mutex<Vec<Entry>>,
+    capacity: usize,
+}
+
+impl ArtifactCache {
+    pub const fn new(capacity: usize) -> Self {
+        Self { entries: Mutex::new(Vec::new()), capacity }
+    }
+
+    /// Cached artifact of type `T` for `scene`, if present.
+    pub fn get<T: Any + Send + Sync>(&self, scene: u64) -> Option<Arc<T>> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let key = (scene, TypeId::of::<T>());
+        let pos = entries.iter().position(|(k, _)| *k == key)?;
+        let entry = entries.remove(pos);
+        let value = entry.1.clone();
+        entries.push(entry);
+        value.downcast().ok()
+    }
+
+    /// Cached artifact of type `T` for `scene`, building it on a miss.
+    ///
+    /// `build` runs without holding the lock, so concurrent misses may build
+    /// twice; the first one stored wins and every caller gets that one.
+    pub fn get_or_build<T: Any + Send + Sync>(&self, scene: u64, build: impl FnOnce() -> T) -> Arc<T> {
+        if let Some(value) = self.get(scene) {
+            return value;
+        }
+        let built: Arc<T> = Arc::new(build());
+
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let key = (scene, TypeId::of::<T>());
+        if let Some((_, existing)) = entries.iter().find(|(k, _)| *k == key) {
+            if let Ok(existing) = existing.clone().downcast() {
+                return existing;
+            }
+        }
+        if self.capacity > 0 {
+            entries.push((key, built.clone()));
+            let excess = entries.len().saturating_sub(self.capacity);
+            entries.drain(..excess);
+        }
+        built
+    }
+
+    /// Number of cached artifacts.
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    }
+}
+
+impl Default for ArtifactCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[test]
+    fn test_artifacts_are_built_once_and_shared() {
+        let cache = ArtifactCache::new(4);
+        let builds = AtomicU32::new(0);
+        let results: Vec<Arc<Vec<u32>>> = std::thread::scope(|s| {
+            let handles: Vec<_> = (0..4)
+                .map(|_| s.spawn(|| cache.get_or_build(7, || {
+                    builds.fetch_add(1, Ordering::Relaxed);
+                    vec![1u32, 2, 3]
+                })))
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert!(results.windows(2).all(|w| Arc::ptr_eq(&w[0], &w[1])));
+        assert!(builds.load(Ordering::Relaxed) >= 1);
+
+        // Later calls hit the cache
+        let before = builds.load(Ordering::Relaxed);
+        cache.get_or_build(7, || -> Vec<u32> { unreachable!() });
+        assert_eq!(builds.load(Ordering::Relaxed), before);
+
+        // Same scene, different artifact type
+        let text = cache.get_or_build(7, || String::from("grid"));
+        assert_eq!(text.as_str(), "grid");
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn test_least_recently_used_is_evicted() {
+        let cache = ArtifactCache::new(2);
+        cache.get_or_build(1, || 1u8);
+        cache.get_or_build(2, || 2u8);
+        cache.get::<u8>(1);
+        cache.get_or_build(3, || 3u8);
+        assert!(cache.get::<u8>(1).is_some());
+        assert!(cache.get::<u8>(2).is_none());
+        assert_eq!(cache.len(), 2);
+    }
+}
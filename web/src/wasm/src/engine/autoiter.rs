@@ -0,0 +1,108 @@
+//! Automatic iteration count ("auto maxiter").
+//!
+//! Renders a coarse grid of primary rays at increasing iteration counts and
+//! stops once the fraction of pixels hitting the fractal settles: too few
+//! iterations leave a blobby shape covering far more of the frame, and the
+//! coverage shrinks toward its final value as detail appears.
+
+use crate::engine::raymarcher::{self, RenderParams};
+use crate::formulas::hybrid::HybridFormula;
+
+/// Search settings.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AutoIterSettings {
+    /// First iteration count tried
+    pub start: u32,
+    /// Upper limit for the search
+    pub max: u32,
+    /// Coarse grid size (grid × grid rays, spread over the full frame)
+    pub grid: u32,
+    /// Converged when the hit fraction changes by less than this between rounds
+    pub tolerance: f64,
+    /// Iteration growth factor per round
+    pub growth: f64,
+}
+
+impl Default for AutoIterSettings {
+    fn default() -> Self {
+        Self { start: 4, max: 250, grid: 32, tolerance: 0.005, growth: 1.5 }
+    }
+}
+
+/// Outcome of the search.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AutoIterResult {
+    pub iterations: u32,
+    /// Fraction of coarse rays that hit the surface at `iterations`
+    pub hit_fraction: f64,
+    /// Number of coarse renders performed
+    pub rounds: u32,
+    /// False if `max` was reached before the coverage settled
+    pub converged: bool,
+}
+
+/// Fraction of a coarse grid of primary rays that hits the surface.
+pub fn hit_fraction(params: &RenderParams, formula: &HybridFormula, grid: u32) -> f64 {
+    let grid = grid.max(1);
+    let mut hits = 0u32;
+    for gy in 0..grid {
+        for gx in 0..grid {
+            let fx = (gx as f64 + 0.5) / grid as f64 * params.width as f64;
+            let fy = (gy as f64 + 0.5) / grid as f64 * params.height as f64;
+            let dir = raymarcher::pixel_direction(params, fx, fy);
+            if raymarcher::march_ray(&params.camera_pos, &dir, params, formula).hit {
+                hits += 1;
+            }
+        }
+    }
+    hits as f64 / (grid * grid) as f64
+}
+
+/// Find the iteration count where the structure converges.
+///
+/// `formula.total_iterations` is left at the returned value.
+pub fn auto_iterations(params: &RenderParams, formula: &mut HybridFormula, settings: &AutoIterSettings) -> AutoIterResult {
+    let mut iterations = settings.start.max(1).min(settings.max);
+    let mut previous: Option<f64> = None;
+    let mut rounds = 0;
+    loop {
+        formula.total_iterations = iterations;
+        let mut coarse = params.clone();
+        coarse.max_iterations = iterations;
+        let fraction = hit_fraction(&coarse, formula, settings.grid);
+        rounds += 1;
+
+        let settled = previous.is_some_and(|p| (p - fraction).abs() <= settings.tolerance);
+        if settled || iterations >= settings.max {
+            return AutoIterResult { iterations, hit_fraction: fraction, rounds, converged: settled };
+        }
+        previous = Some(fraction);
+        let next = (iterations as f64 * settings.growth.max(1.01)).ceil() as u32;
+        iterations = next.max(iterations + 1).min(settings.max);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::types::Vec3D;
+    use crate::formulas::{hybrid::HybridMode, FormulaId};
+
+    #[test]
+    fn test_search_grows_and_stops() {
+        let params = RenderParams {
+            width: 64,
+            height: 64,
+            ray_dx: Vec3D { x: 0.6, y: 0.0, z: 0.0 },
+            ray_dy: Vec3D { x: 0.0, y: 0.6, z: 0.0 },
+            ..Default::default()
+        };
+        let mut formula = HybridFormula::new(&[(FormulaId::MandelbulbPower8, 1)], HybridMode::Alternating, 1, 16.0);
+        let settings = AutoIterSettings { grid: 8, max: 24, ..Default::default() };
+        let result = auto_iterations(&params, &mut formula, &settings);
+        assert!(result.iterations >= settings.start && result.iterations <= settings.max);
+        assert!(result.rounds >= 2);
+        assert_eq!(formula.total_iterations, result.iterations);
+        assert!(result.hit_fraction > 0.0 && result.hit_fraction < 1.0);
+    }
+}
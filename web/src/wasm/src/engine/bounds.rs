@@ -0,0 +1,77 @@
+//! Bounding volumes for early ray skipping.
+//!
+//! When the fractal is known to lie inside a sphere or box, the ray marcher
+//! starts each ray at its entry into the volume and gives up at the exit, so
+//! rays that miss it cost no DE evaluations at all. The volume reuses the cut
+//! shapes (`CutShape::ray_span`).
+
+use crate::engine::cutting::CutShape;
+use crate::engine::types::Vec3D;
+
+/// f64 values of the bounds block in the render parameter buffer.
+pub const BOUNDS_STRIDE: usize = 7;
+
+/// Parse `[kind, a0..a5]`: kind 0 = none, 1 = sphere (cx, cy, cz, r),
+/// 2 = box (min xyz, max xyz), 3 = sphere of the bailout radius at the origin.
+///
+/// The bailout sphere is conservative for escape-time formulas that start the
+/// orbit at the sample position (any point outside escapes at once); do not
+/// use it with z0 offsets or non-iterable slots that extend further.
+pub fn bounds_from_slice(v: &[f64], bailout: f64) -> Option<CutShape> {
+    if v.len() < BOUNDS_STRIDE {
+        return None;
+    }
+    let a = |i: usize| Vec3D { x: v[i], y: v[i + 1], z: v[i + 2] };
+    match v[0] as u32 {
+        1 => Some(CutShape::Sphere { center: a(1), radius: v[4].abs() }),
+        2 => Some(CutShape::Box { min: a(1), max: a(4) }),
+        3 if bailout > 0.0 => Some(CutShape::Sphere { center: Vec3D::default(), radius: bailout.sqrt() }),
+        _ => None,
+    }
+}
+
+/// Part of the ray `[0, max_len]` inside the bounds, as (start, end).
+/// None if the ray misses the volume within that length.
+pub fn clip_ray(bounds: &CutShape, origin: &Vec3D, dir: &Vec3D, max_len: f64) -> Option<(f64, f64)> {
+    let (t0, t1) = bounds.ray_span(origin, dir)?;
+    let (start, end) = (t0.max(0.0), t1.min(max_len));
+    (start < end).then_some((start, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::raymarcher::{self, RenderParams};
+    use crate::formulas::hybrid::{HybridFormula, HybridMode};
+    use crate::formulas::FormulaId;
+
+    #[test]
+    fn test_clip_ray() {
+        let sphere = CutShape::Sphere { center: Vec3D::default(), radius: 1.0 };
+        let dir = Vec3D { x: 0.0, y: 0.0, z: 1.0 };
+        let outside = Vec3D { x: 0.0, y: 0.0, z: -3.0 };
+        assert_eq!(clip_ray(&sphere, &outside, &dir, 50.0), Some((2.0, 4.0)));
+        assert_eq!(clip_ray(&sphere, &Vec3D::default(), &dir, 50.0), Some((0.0, 1.0)));
+        assert_eq!(clip_ray(&sphere, &outside, &dir, 1.5), None);
+        let away = Vec3D { x: 0.0, y: 0.0, z: -1.0 };
+        assert_eq!(clip_ray(&sphere, &outside, &away, 50.0), None);
+    }
+
+    #[test]
+    fn test_bounds_keep_hits_and_save_steps() {
+        let formula = HybridFormula::new(&[(FormulaId::MandelbulbPower8, 1)], HybridMode::Alternating, 8, 4.0);
+        let free = RenderParams { camera_pos: Vec3D { x: 0.0, y: 0.0, z: -6.0 }, bailout: 4.0, ..Default::default() };
+        let bounded = RenderParams { bounds: bounds_from_slice(&[3.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0], 4.0), ..free.clone() };
+        let center = raymarcher::pixel_direction(&free, 400.0, 300.0);
+        let a = raymarcher::march_ray(&free.camera_pos, &center, &free, &formula);
+        let b = raymarcher::march_ray(&bounded.camera_pos, &center, &bounded, &formula);
+        assert!(a.hit && b.hit);
+        assert!((a.total_distance - b.total_distance).abs() < 1e-3);
+
+        // A ray passing beside the sphere is rejected without marching
+        let side = Vec3D { x: 0.6, y: 0.0, z: 0.8 };
+        let miss = raymarcher::march_ray(&bounded.camera_pos, &side, &bounded, &formula);
+        assert!(!miss.hit);
+        assert_eq!(miss.steps, 0);
+    }
+}
@@ -0,0 +1,119 @@
+//! Camera ray basis from position / target / field of view.
+//!
+//! The ray marcher takes the view as three vectors: the center ray
+//! `ray_dir_base` and the offsets `ray_dx` / `ray_dy` from the center to the
+//! right and bottom image edges (`pixel_direction` scales them by the pixel's
+//! position in [-1, 1]). This module derives them from a look-at camera with
+//! the same conventions as `buildRenderParams` in params.js, so frontends do
+//! not need to re-implement the math.
+
+use crate::engine::raymarcher::RenderParams;
+use crate::engine::types::Vec3D;
+use crate::math::{math3d, strict};
+
+/// View ray basis in the layout of `RenderParams`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CameraRays {
+    /// Unit direction of the image center
+    pub dir_base: Vec3D,
+    /// Center-to-right-edge offset
+    pub dx: Vec3D,
+    /// Center-to-bottom-edge offset
+    pub dy: Vec3D,
+}
+
+impl CameraRays {
+    /// Store the basis (and camera position) in `params`.
+    pub fn apply(&self, params: &mut RenderParams, pos: &Vec3D) {
+        params.camera_pos = *pos;
+        params.ray_dir_base = self.dir_base;
+        params.ray_dx = self.dx;
+        params.ray_dy = self.dy;
+    }
+
+    /// Flat `[dir_base, dx, dy]` for render_params slots 5..14.
+    pub fn to_array(&self) -> [f64; 9] {
+        let (b, x, y) = (&self.dir_base, &self.dx, &self.dy);
+        [b.x, b.y, b.z, x.x, x.y, x.z, y.x, y.y, y.z]
+    }
+}
+
+/// Ray basis of a camera at `pos` looking at `target`.
+///
+/// `fov` is the vertical field of view in radians (0 or less = params.js
+/// default of tan(fov/2) = 0.5). `up` only needs to be roughly up; if it is
+/// parallel to the view direction an arbitrary perpendicular is used.
+pub fn compute_camera_rays(pos: &Vec3D, target: &Vec3D, up: &Vec3D, fov: f64, width: u32, height: u32) -> CameraRays {
+    let mut forward = math3d::vec3d_sub(target, pos);
+    if math3d::vec3d_length_sqr(&forward) == 0.0 {
+        forward = Vec3D { x: 0.0, y: 0.0, z: 1.0 };
+    }
+    let forward = math3d::vec3d_normalized(&forward);
+
+    let right = math3d::vec3d_cross(up, &forward);
+    let right = if math3d::vec3d_length_sqr(&right) > 1e-24 {
+        math3d::vec3d_normalized(&right)
+    } else {
+        math3d::vec3d_orthonormal_basis(&forward).0
+    };
+    let true_up = math3d::vec3d_cross(&forward, &right);
+
+    let fov_scale = if fov > 0.0 { strict::tan(fov * 0.5) } else { 0.5 };
+    let aspect = width.max(1) as f64 / height.max(1) as f64;
+    CameraRays {
+        dir_base: forward,
+        dx: math3d::vec3d_scale(&right, fov_scale * aspect),
+        // Image rows grow downwards
+        dy: math3d::vec3d_scale(&true_up, -fov_scale),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::raymarcher;
+
+    fn angle(a: &Vec3D, b: &Vec3D) -> f64 {
+        math3d::vec3d_dot(a, b).clamp(-1.0, 1.0).acos()
+    }
+
+    #[test]
+    fn test_camera_rays_span_the_field_of_view() {
+        let pos = Vec3D { x: 1.0, y: 2.0, z: -3.0 };
+        let target = Vec3D { x: 0.0, y: 0.0, z: 0.0 };
+        let up = Vec3D { x: 0.0, y: 1.0, z: 0.0 };
+        let fov = 50f64.to_radians();
+        let rays = compute_camera_rays(&pos, &target, &up, fov, 320, 200);
+
+        let mut params = RenderParams { width: 320, height: 200, ..Default::default() };
+        rays.apply(&mut params, &pos);
+        let center = raymarcher::pixel_direction(&params, 160.0, 100.0);
+        let to_target = math3d::vec3d_normalized(&math3d::vec3d_sub(&target, &pos));
+        assert!(angle(&center, &to_target) < 1e-12);
+
+        // Top edge is half the vertical FOV away and above the center
+        let top = raymarcher::pixel_direction(&params, 160.0, 0.0);
+        assert!((angle(&top, &center) - fov * 0.5).abs() < 1e-12);
+        assert!(top.y > center.y);
+
+        // Same handedness as the default view (+Z forward, +X right, +Y up)
+        let right = raymarcher::pixel_direction(&params, 320.0, 100.0);
+        assert!(math3d::vec3d_cross(&center, &right).y > 0.0);
+    }
+
+    #[test]
+    fn test_degenerate_up_still_gives_a_basis() {
+        let rays = compute_camera_rays(
+            &Vec3D::default(),
+            &Vec3D { x: 0.0, y: 5.0, z: 0.0 },
+            &Vec3D { x: 0.0, y: 1.0, z: 0.0 },
+            1.0,
+            10,
+            10,
+        );
+        assert!(rays.to_array().iter().all(|v| v.is_finite()));
+        assert!(math3d::vec3d_dot(&rays.dx, &rays.dir_base).abs() < 1e-12);
+        assert!(math3d::vec3d_dot(&rays.dy, &rays.dir_base).abs() < 1e-12);
+        assert!(math3d::vec3d_length(&rays.dx) > 0.0);
+    }
+}
@@ -0,0 +1,251 @@
+//! Multi-scene compositing.
+//!
+//! Two ways to put several fractals into one frame:
+//! - `merge_by_depth` folds G-buffers rendered from different formula stacks
+//!   with the same camera (so workers can render them independently).
+//! - `Scene` is a small scene graph of formulas with their own transforms and
+//!   materials, traced as one distance field so shadows fall across objects.
+//!
+//! Either way the pixel's source object is tagged with its material id (high
+//! byte of `roughness`) so the paint pass can shade it with its own material.
+
+use crate::engine::raymarcher::{self, RenderParams};
+use crate::engine::types::{Matrix3, SiLight5, Vec3D};
+use crate::formulas::hybrid::HybridFormula;
+use crate::formulas::{DistanceField, FormulaResult};
+use crate::lighting::paint::LightConfig;
+use crate::math::math3d;
+
+/// Merge `src` into `dst`, keeping the nearer surface for each pixel.
+///
+/// Pixels taken from `src` are tagged with `src_material`. Ties keep `dst`,
+/// so two misses stay background. Returns the number of pixels taken from `src`.
+pub fn merge_by_depth(dst: &mut [SiLight5], src: &[SiLight5], src_material: u8) -> u32 {
+    let mut taken = 0;
+    for (d, s) in dst.iter_mut().zip(src) {
+        if s.z_pos < d.z_pos {
+            *d = *s;
+            d.set_material_id(src_material);
+            taken += 1;
+        }
+    }
+    taken
+}
+
+/// Placement of an object in the world: uniform scale, then rotation, then translation.
+#[derive(Clone, Copy, Debug)]
+pub struct ObjectTransform {
+    pub position: Vec3D,
+    /// Local → world rotation
+    pub rotation: Matrix3,
+    pub scale: f64,
+}
+
+impl Default for ObjectTransform {
+    fn default() -> Self {
+        Self { position: Vec3D::default(), rotation: math3d::mat3_identity(), scale: 1.0 }
+    }
+}
+
+impl ObjectTransform {
+    /// Parse `[px, py, pz, r00, r01, r02, r10, r11, r12, r20, r21, r22, scale]`.
+    ///
+    /// Missing trailing values keep the identity defaults.
+    pub fn from_slice(v: &[f64]) -> Self {
+        let mut t = Self::default();
+        if v.len() >= 3 {
+            t.position = Vec3D { x: v[0], y: v[1], z: v[2] };
+        }
+        if v.len() >= 12 {
+            for (i, row) in t.rotation.m.iter_mut().enumerate() {
+                row.copy_from_slice(&v[3 + i * 3..6 + i * 3]);
+            }
+        }
+        if let Some(&scale) = v.get(12) {
+            if scale > 0.0 {
+                t.scale = scale;
+            }
+        }
+        t
+    }
+
+    /// World-space point in the object's local frame.
+    pub fn to_local(&self, pos: &Vec3D) -> Vec3D {
+        let rel = math3d::vec3d_sub(pos, &self.position);
+        let local = math3d::mat3_mul_vec(&math3d::mat3_transpose(&self.rotation), &rel);
+        math3d::vec3d_scale(&local, 1.0 / self.scale)
+    }
+}
+
+/// One formula stack placed in a composite scene.
+pub struct SceneObject {
+    pub formula: HybridFormula,
+    pub transform: ObjectTransform,
+    /// Material id written to the G-buffer for this object's pixels
+    pub material: u8,
+}
+
+/// A set of objects traced as the union of their distance fields.
+#[derive(Default)]
+pub struct Scene {
+    pub objects: Vec<SceneObject>,
+}
+
+impl Scene {
+    /// Nearest object at a world-space position and its (world-scaled) DE result.
+    pub fn nearest(&self, pos: &Vec3D) -> Option<(usize, FormulaResult)> {
+        self.objects
+            .iter()
+            .enumerate()
+            .map(|(i, obj)| {
+                let mut fr = obj.formula.compute_de(&obj.transform.to_local(pos));
+                fr.de *= obj.transform.scale;
+                (i, fr)
+            })
+            .min_by(|a, b| a.1.de.total_cmp(&b.1.de))
+    }
+}
+
+impl DistanceField for Scene {
+    fn compute_de(&self, pos: &Vec3D) -> FormulaResult {
+        self.nearest(pos).map(|(_, fr)| fr).unwrap_or_default()
+    }
+}
+
+/// Per-light shadow flags for a surface point; shadow rays test every object.
+///
+/// At most 6 lights are used; occluders beyond a point or spot light do not
+/// shadow it.
+pub fn shadow_flags(
+    params: &RenderParams,
+    scene: &Scene,
+    hit_pos: &Vec3D,
+    normal: &Vec3D,
+    lights: &[LightConfig],
+) -> SiLight5 {
+    let mut flags = SiLight5::default();
+    let origin = math3d::vec3d_add(hit_pos, &math3d::vec3d_scale(normal, params.de_stop * 4.0));
+    for (i, light) in lights.iter().take(6).enumerate() {
+        let (dir, distance, _) = light.incidence(Some(hit_pos));
+        if math3d::vec3d_dot(normal, &dir) <= 0.0 {
+            continue; // facing away, unlit anyway
+        }
+        let dir = math3d::vec3d_normalized(&dir);
+        let mr = raymarcher::march_ray(&origin, &dir, params, scene);
+        if mr.hit && mr.total_distance < distance {
+            flags.set_in_shadow(i);
+        }
+    }
+    flags
+}
+
+/// Render interleaved scanlines of a composite scene into the G-buffer,
+/// with material ids and per-light shadow flags.
+pub fn render_scene_scanlines(
+    params: &RenderParams,
+    scene: &Scene,
+    lights: &[LightConfig],
+    gbuffer: &mut [SiLight5],
+    worker_id: u32,
+    worker_count: u32,
+) -> u32 {
+    let mut rows = 0;
+    let mut y = worker_id;
+    while y < params.height {
+        for x in 0..params.width {
+            let idx = (y * params.width + x) as usize;
+            if idx >= gbuffer.len() {
+                break;
+            }
+            let dir = raymarcher::pixel_direction(params, x as f64, y as f64);
+            let mr = raymarcher::march_ray(&params.camera_pos, &dir, params, scene);
+            let mut entry = raymarcher::gbuffer_entry(&mr, params, scene);
+            if mr.hit {
+                if let Some((obj, _)) = scene.nearest(&mr.hit_pos) {
+                    entry.set_material_id(scene.objects[obj].material);
+                }
+                entry.shadow |= shadow_flags(params, scene, &mr.hit_pos, &mr.normal, lights).shadow;
+            }
+            gbuffer[idx] = entry;
+        }
+        rows += 1;
+        y += worker_count.max(1);
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lighting::paint::LightKind;
+    use crate::formulas::{hybrid::HybridMode, FormulaId};
+
+    fn pixel(z: u16) -> SiLight5 {
+        SiLight5 { z_pos: z, roughness: 0x0040, ..Default::default() }
+    }
+
+    fn bulb() -> HybridFormula {
+        HybridFormula::new(&[(FormulaId::MandelbulbPower8, 1)], HybridMode::Alternating, 12, 16.0)
+    }
+
+    #[test]
+    fn test_merge_keeps_nearest() {
+        let mut dst = vec![pixel(100), pixel(65535), pixel(300), pixel(65535)];
+        let src = vec![pixel(200), pixel(500), pixel(300), pixel(65535)];
+        assert_eq!(merge_by_depth(&mut dst, &src, 2), 1);
+        let z: Vec<u16> = dst.iter().map(|p| p.z_pos).collect();
+        assert_eq!(z, vec![100, 500, 300, 65535]);
+        assert_eq!(dst[0].material_id(), 0);
+        assert_eq!(dst[1].material_id(), 2);
+        assert_eq!(dst[1].roughness & 0xFF, 0x40);
+    }
+
+    #[test]
+    fn test_transform_round_trip() {
+        let t = ObjectTransform::from_slice(&[
+            1.0, 2.0, 3.0,
+            0.0, -1.0, 0.0,
+            1.0, 0.0, 0.0,
+            0.0, 0.0, 1.0,
+            2.0,
+        ]);
+        // Local (1, 0, 0) → rotated to (0, 1, 0), scaled by 2, moved by the position
+        let world = Vec3D { x: 1.0, y: 4.0, z: 3.0 };
+        let local = t.to_local(&world);
+        assert!((local.x - 1.0).abs() < 1e-12 && local.y.abs() < 1e-12 && local.z.abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_scene_picks_nearest_object_and_casts_shadows() {
+        let far = ObjectTransform { position: Vec3D { x: 0.0, y: 5.0, z: 0.0 }, ..Default::default() };
+        let scene = Scene {
+            objects: vec![
+                SceneObject { formula: bulb(), transform: ObjectTransform::default(), material: 1 },
+                SceneObject { formula: bulb(), transform: far, material: 2 },
+            ],
+        };
+        let (obj, _) = scene.nearest(&Vec3D { x: 0.0, y: 4.0, z: 0.0 }).unwrap();
+        assert_eq!(obj, 1);
+
+        // A point between the two bulbs looking up at the far one is shadowed
+        let params = RenderParams::default();
+        let sun = |direction| [LightConfig { direction, ..Default::default() }];
+        let up = Vec3D { x: 0.0, y: 1.0, z: 0.0 };
+        let p = Vec3D { x: 0.0, y: 2.5, z: 0.0 };
+        assert!(shadow_flags(&params, &scene, &p, &up, &sun(up)).in_shadow(0));
+        let down = Vec3D { x: 0.0, y: -1.0, z: 0.0 };
+        let p = Vec3D { x: 0.0, y: 7.5, z: 0.0 };
+        assert!(!shadow_flags(&params, &scene, &p, &up, &sun(up)).in_shadow(0));
+        assert!(shadow_flags(&params, &scene, &p, &down, &sun(down)).in_shadow(0));
+
+        // A point light in front of the far bulb is not shadowed by it
+        let lamp = |y| [LightConfig {
+            kind: LightKind::Point,
+            position: Vec3D { x: 0.0, y, z: 0.0 },
+            ..Default::default()
+        }];
+        let p = Vec3D { x: 0.0, y: 2.5, z: 0.0 };
+        assert!(!shadow_flags(&params, &scene, &p, &up, &lamp(3.0)).in_shadow(0));
+        assert!(shadow_flags(&params, &scene, &p, &up, &lamp(9.0)).in_shadow(0));
+    }
+}
@@ -0,0 +1,213 @@
+//! Cutting planes and cut volumes.
+//!
+//! A cut removes part of space from the render; the ray marcher jumps over
+//! removed regions, so the fractal interior shows on the cut faces. Several
+//! cuts remove the union of their regions (use a box cut for corner cutaways).
+
+use crate::engine::types::Vec3D;
+use crate::math::math3d;
+
+/// Maximum number of cuts read from the render parameter buffer.
+pub const MAX_CUTS: usize = 6;
+/// f64 values per cut in the render parameter buffer.
+pub const CUT_STRIDE: usize = 8;
+
+/// Cut geometry. "Inside" is the half-space behind a plane, or the interior of a volume.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CutShape {
+    /// Points with `dot(p, normal) < d` are inside
+    Plane { normal: Vec3D, d: f64 },
+    Sphere { center: Vec3D, radius: f64 },
+    Box { min: Vec3D, max: Vec3D },
+}
+
+impl CutShape {
+    /// Ray parameter range (t_enter, t_exit) over which the ray is inside the shape.
+    pub fn ray_span(&self, p: &Vec3D, dir: &Vec3D) -> Option<(f64, f64)> {
+        match *self {
+            CutShape::Plane { normal, d } => {
+                let dist = d - math3d::vec3d_dot(p, &normal);
+                let cos = math3d::vec3d_dot(dir, &normal);
+                if cos.abs() < 1e-12 {
+                    return (dist > 0.0).then_some((f64::NEG_INFINITY, f64::INFINITY));
+                }
+                let t = dist / cos;
+                Some(if cos > 0.0 { (f64::NEG_INFINITY, t) } else { (t, f64::INFINITY) })
+            }
+            CutShape::Sphere { center, radius } => {
+                let oc = math3d::vec3d_sub(p, &center);
+                let b = math3d::vec3d_dot(&oc, dir);
+                let c = math3d::vec3d_length_sqr(&oc) - radius * radius;
+                let disc = b * b - c;
+                if disc < 0.0 {
+                    return None;
+                }
+                let s = disc.sqrt();
+                Some((-b - s, -b + s))
+            }
+            CutShape::Box { min, max } => {
+                let mut t0 = f64::NEG_INFINITY;
+                let mut t1 = f64::INFINITY;
+                for (o, d, lo, hi) in [(p.x, dir.x, min.x, max.x), (p.y, dir.y, min.y, max.y), (p.z, dir.z, min.z, max.z)] {
+                    if d.abs() < 1e-12 {
+                        if o <= lo || o >= hi {
+                            return None;
+                        }
+                        continue;
+                    }
+                    let (a, b) = ((lo - o) / d, (hi - o) / d);
+                    t0 = t0.max(a.min(b));
+                    t1 = t1.min(a.max(b));
+                }
+                (t0 < t1).then_some((t0, t1))
+            }
+        }
+    }
+}
+
+/// One cut and which side of it is removed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Cut {
+    pub shape: CutShape,
+    /// Remove the inside (true) or keep only the inside (false)
+    pub removes_inside: bool,
+}
+
+impl Cut {
+    /// Parse `[kind, removes_inside, a0..a5]`: kind 0 = plane (nx, ny, nz, d),
+    /// 1 = sphere (cx, cy, cz, r), 2 = box (min xyz, max xyz).
+    pub fn from_slice(v: &[f64]) -> Option<Cut> {
+        if v.len() < CUT_STRIDE {
+            return None;
+        }
+        let a = |i: usize| Vec3D { x: v[i], y: v[i + 1], z: v[i + 2] };
+        let shape = match v[0] as u32 {
+            0 => CutShape::Plane { normal: math3d::vec3d_normalized(&a(2)), d: v[5] },
+            1 => CutShape::Sphere { center: a(2), radius: v[5].abs() },
+            2 => CutShape::Box { min: a(2), max: a(5) },
+            _ => return None,
+        };
+        Some(Cut { shape, removes_inside: v[1] != 0.0 })
+    }
+
+    fn inside(&self, p: &Vec3D) -> bool {
+        match self.shape {
+            CutShape::Plane { normal, d } => math3d::vec3d_dot(p, &normal) < d,
+            CutShape::Sphere { center, radius } => {
+                math3d::vec3d_length_sqr(&math3d::vec3d_sub(p, &center)) < radius * radius
+            }
+            CutShape::Box { min, max } => {
+                p.x > min.x && p.x < max.x && p.y > min.y && p.y < max.y && p.z > min.z && p.z < max.z
+            }
+        }
+    }
+
+    /// Does this cut remove point `p`?
+    pub fn removes(&self, p: &Vec3D) -> bool {
+        self.inside(p) == self.removes_inside
+    }
+
+    /// Distance along the ray to leave the removed region, starting at a
+    /// removed point. None if the ray never leaves it.
+    pub fn skip_distance(&self, p: &Vec3D, dir: &Vec3D) -> Option<f64> {
+        let span = self.shape.ray_span(p, dir);
+        if self.removes_inside {
+            span.map(|(_, t1)| t1).filter(|t| t.is_finite() && *t > 0.0)
+        } else {
+            span.map(|(t0, _)| t0).filter(|t| t.is_finite() && *t > 0.0)
+        }
+    }
+}
+
+/// Read the cut table at `data[offset]`: `[count, (cut × CUT_STRIDE) × MAX_CUTS]`.
+pub fn cuts_from_buffer(data: &[f64], offset: usize) -> Vec<Cut> {
+    let count = data.get(offset).map_or(0, |&c| (c.max(0.0) as usize).min(MAX_CUTS));
+    (0..count)
+        .filter_map(|i| {
+            let start = offset + 1 + i * CUT_STRIDE;
+            data.get(start..start + CUT_STRIDE).and_then(Cut::from_slice)
+        })
+        .collect()
+}
+
+/// Result of checking a march position against the cuts.
+#[derive(Debug, PartialEq)]
+pub enum CutSkip {
+    /// Not removed; march normally
+    Keep,
+    /// Removed; jump this far along the ray
+    Advance(f64),
+    /// The rest of the ray is removed
+    Miss,
+}
+
+/// Where to continue marching from `p`, leaving every removed region.
+pub fn skip_removed(cuts: &[Cut], p: &Vec3D, dir: &Vec3D, epsilon: f64) -> CutSkip {
+    let mut pos = *p;
+    let mut total = 0.0;
+    // Leaving one region may land in another; each cut is passed at most twice
+    for _ in 0..cuts.len() * 2 + 1 {
+        let Some(cut) = cuts.iter().find(|c| c.removes(&pos)) else {
+            return if total > 0.0 { CutSkip::Advance(total) } else { CutSkip::Keep };
+        };
+        match cut.skip_distance(&pos, dir) {
+            Some(t) => {
+                let step = t + epsilon;
+                pos = math3d::vec3d_add(&pos, &math3d::vec3d_scale(dir, step));
+                total += step;
+            }
+            None => return CutSkip::Miss,
+        }
+    }
+    CutSkip::Miss
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const Z: Vec3D = Vec3D { x: 0.0, y: 0.0, z: 1.0 };
+
+    fn at(z: f64) -> Vec3D {
+        Vec3D { x: 0.0, y: 0.0, z }
+    }
+
+    #[test]
+    fn test_sphere_hole_is_skipped() {
+        let hole = Cut { shape: CutShape::Sphere { center: at(0.0), radius: 1.0 }, removes_inside: true };
+        assert_eq!(skip_removed(&[hole], &at(-2.0), &Z, 1e-9), CutSkip::Keep);
+        match skip_removed(&[hole], &at(-0.5), &Z, 1e-9) {
+            CutSkip::Advance(t) => assert!((t - 1.5).abs() < 1e-6),
+            other => panic!("{other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_keep_inside_box_and_miss() {
+        let keep = Cut {
+            shape: CutShape::Box { min: Vec3D { x: -1.0, y: -1.0, z: -1.0 }, max: Vec3D { x: 1.0, y: 1.0, z: 1.0 } },
+            removes_inside: false,
+        };
+        match skip_removed(&[keep], &at(-3.0), &Z, 1e-9) {
+            CutSkip::Advance(t) => assert!((t - 2.0).abs() < 1e-6),
+            other => panic!("{other:?}"),
+        }
+        assert_eq!(skip_removed(&[keep], &at(3.0), &Z, 1e-9), CutSkip::Miss);
+    }
+
+    #[test]
+    fn test_chained_planes_and_buffer_layout() {
+        let data = [
+            2.0,
+            0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, // remove z < 0
+            0.0, 1.0, 0.0, 0.0, -1.0, -2.0, 0.0, 0.0, // remove z > 2
+        ];
+        let cuts = cuts_from_buffer(&data, 0);
+        assert_eq!(cuts.len(), 2);
+        assert!(cuts[0].removes(&at(-1.0)) && !cuts[0].removes(&at(1.0)));
+        assert!(cuts[1].removes(&at(3.0)) && !cuts[1].removes(&at(1.0)));
+        assert_eq!(skip_removed(&cuts, &at(1.0), &Z, 1e-9), CutSkip::Keep);
+        assert!(matches!(skip_removed(&cuts, &at(-1.0), &Z, 1e-9), CutSkip::Advance(t) if (t - 1.0).abs() < 1e-6));
+        assert_eq!(skip_removed(&cuts, &at(3.0), &Z, 1e-9), CutSkip::Miss);
+    }
+}
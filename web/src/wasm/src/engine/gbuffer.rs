@@ -0,0 +1,148 @@
+//! Checked views of G-buffers handed over from JS as raw bytes.
+//!
+//! Workers pass G-buffers as `Uint8Array`s, usually over a
+//! SharedArrayBuffer. Before the bytes are reinterpreted as records, the
+//! view checks that the buffer holds every pixel, is a whole number of
+//! records (so a buffer of the other record layout is caught) and is
+//! aligned for the record type. A mismatch is reported as a `ViewError`
+//! instead of rendering into, or painting from, the wrong bytes.
+
+use std::fmt;
+
+use super::types::{SiLight5, SiLight6};
+
+/// Plain-data G-buffer record.
+///
+/// # Safety
+/// Implementors must be `repr(C)` or `repr(C, packed)` structs of integer
+/// and float fields only, so every bit pattern is a valid value.
+pub unsafe trait GBufferRecord: Copy {
+    /// Name used in error messages.
+    const NAME: &'static str;
+}
+
+// SAFETY: repr(C, packed) plain data
+unsafe impl GBufferRecord for SiLight5 {
+    const NAME: &'static str = "SiLight5";
+}
+
+// SAFETY: repr(C, packed) plain data
+unsafe impl GBufferRecord for SiLight6 {
+    const NAME: &'static str = "SiLight6";
+}
+
+/// Why a byte buffer cannot be viewed as G-buffer records.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ViewError {
+    /// Fewer bytes than `pixels` records need
+    Length { layer: &'static str, record: &'static str, pixels: usize, expected: usize, actual: usize },
+    /// Length is not a multiple of the record size
+    Stride { layer: &'static str, record: &'static str, size: usize, actual: usize },
+    /// Start address is not aligned for the record type
+    Alignment { layer: &'static str, record: &'static str, align: usize },
+}
+
+impl fmt::Display for ViewError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ViewError::Length { layer, record, pixels, expected, actual } => write!(
+                f,
+                "{layer}: {actual} bytes is too short for {pixels} {record} records ({expected} bytes)"
+            ),
+            ViewError::Stride { layer, record, size, actual } => write!(
+                f,
+                "{layer}: {actual} bytes is not a multiple of the {size}-byte {record} record"
+            ),
+            ViewError::Alignment { layer, record, align } => {
+                write!(f, "{layer}: buffer is not {align}-byte aligned for {record} records")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ViewError {}
+
+/// Validate `bytes` as at least `pixels` records of `T`.
+fn check<T: GBufferRecord>(layer: &'static str, ptr: *const u8, len: usize, pixels: usize) -> Result<(), ViewError> {
+    let size = std::mem::size_of::<T>();
+    let align = std::mem::align_of::<T>();
+    let expected = pixels.saturating_mul(size);
+    if len < expected {
+        return Err(ViewError::Length { layer, record: T::NAME, pixels, expected, actual: len });
+    }
+    if !len.is_multiple_of(size) {
+        return Err(ViewError::Stride { layer, record: T::NAME, size, actual: len });
+    }
+    if !(ptr as usize).is_multiple_of(align) {
+        return Err(ViewError::Alignment { layer, record: T::NAME, align });
+    }
+    Ok(())
+}
+
+/// The first `pixels` records of `bytes`. `layer` names the buffer in errors.
+pub fn view<'a, T: GBufferRecord>(layer: &'static str, bytes: &'a [u8], pixels: usize) -> Result<&'a [T], ViewError> {
+    check::<T>(layer, bytes.as_ptr(), bytes.len(), pixels)?;
+    // SAFETY: length and alignment checked above; T is plain data
+    Ok(unsafe { std::slice::from_raw_parts(bytes.as_ptr() as *const T, pixels) })
+}
+
+/// Mutable variant of `view`.
+pub fn view_mut<'a, T: GBufferRecord>(
+    layer: &'static str,
+    bytes: &'a mut [u8],
+    pixels: usize,
+) -> Result<&'a mut [T], ViewError> {
+    check::<T>(layer, bytes.as_ptr(), bytes.len(), pixels)?;
+    // SAFETY: length and alignment checked above; T is plain data and the
+    // exclusive borrow of `bytes` moves to the result
+    Ok(unsafe { std::slice::from_raw_parts_mut(bytes.as_mut_ptr() as *mut T, pixels) })
+}
+
+/// `view` for an optional layer: an empty buffer means "not requested".
+pub fn optional_view<'a, T: GBufferRecord>(
+    layer: &'static str,
+    bytes: &'a [u8],
+    pixels: usize,
+) -> Result<Option<&'a [T]>, ViewError> {
+    if bytes.is_empty() { Ok(None) } else { view(layer, bytes, pixels).map(Some) }
+}
+
+/// `view_mut` for an optional layer: an empty buffer means "not requested".
+pub fn optional_view_mut<'a, T: GBufferRecord>(
+    layer: &'static str,
+    bytes: &'a mut [u8],
+    pixels: usize,
+) -> Result<Option<&'a mut [T]>, ViewError> {
+    if bytes.is_empty() { Ok(None) } else { view_mut(layer, bytes, pixels).map(Some) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_views_cover_exactly_the_pixels() {
+        let mut bytes = vec![0u8; 18 * 6];
+        bytes[18 * 2 + 6] = 0x34; // z_pos of pixel 2 (little endian)
+        bytes[18 * 2 + 7] = 0x12;
+        let pixels = view::<SiLight5>("gbuffer", &bytes, 4).ok().unwrap();
+        assert_eq!(pixels.len(), 4, "a longer buffer is viewed up to the pixel count");
+        assert_eq!({ pixels[2].z_pos }, 0x1234);
+
+        view_mut::<SiLight5>("gbuffer", &mut bytes, 6).ok().unwrap()[0].z_pos = 65535;
+        assert_eq!(&bytes[6..8], &[0xff, 0xff]);
+        assert!(matches!(optional_view::<SiLight5>("reflect", &[], 6), Ok(None)));
+    }
+
+    #[test]
+    fn test_mismatched_buffers_are_rejected() {
+        let bytes = vec![0u8; 18 * 4];
+        let short = view::<SiLight5>("gbuffer", &bytes, 5).err().unwrap();
+        assert!(matches!(short, ViewError::Length { expected: 90, actual: 72, .. }), "{short:?}");
+        // A packed buffer read as extended records: long enough, wrong stride
+        let stride = view::<SiLight6>("gbuffer", &bytes, 2).err().unwrap();
+        assert!(matches!(stride, ViewError::Stride { size: 34, actual: 72, .. }), "{stride:?}");
+        assert!(stride.to_string().starts_with("gbuffer: 72 bytes"));
+        assert!(optional_view::<SiLight5>("reflect", &bytes[..17], 1).is_err());
+    }
+}
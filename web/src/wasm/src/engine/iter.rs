@@ -0,0 +1,113 @@
+//! Pull-based rendering for streaming output.
+//!
+//! `RenderIter` marches one pixel per `next()` in row-major order, so native
+//! callers can feed encoders, sockets or tiled file writers without holding a
+//! full-frame G-buffer. `PaintedRows` wraps it and yields painted RGBA rows.
+
+use crate::engine::raymarcher::{self, RenderParams};
+use crate::engine::types::SiLight5;
+use crate::formulas::DistanceField;
+use crate::lighting::paint::{self, PaintConfig};
+
+/// Iterator over `(x, y, entry)` for every pixel of the image.
+pub struct RenderIter<'a, F: DistanceField + ?Sized> {
+    params: &'a RenderParams,
+    formula: &'a F,
+    next: u64,
+}
+
+impl<'a, F: DistanceField + ?Sized> RenderIter<'a, F> {
+    pub fn new(params: &'a RenderParams, formula: &'a F) -> Self {
+        Self { params, formula, next: 0 }
+    }
+
+    /// Start at row `y` (e.g. to resume an interrupted stream).
+    pub fn from_row(params: &'a RenderParams, formula: &'a F, y: u32) -> Self {
+        Self { params, formula, next: y as u64 * params.width as u64 }
+    }
+
+    fn total(&self) -> u64 {
+        self.params.width as u64 * self.params.height as u64
+    }
+}
+
+impl<F: DistanceField + ?Sized> Iterator for RenderIter<'_, F> {
+    type Item = (u32, u32, SiLight5);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next >= self.total() {
+            return None;
+        }
+        let w = self.params.width as u64;
+        let (x, y) = ((self.next % w) as u32, (self.next / w) as u32);
+        self.next += 1;
+        Some((x, y, raymarcher::render_pixel(self.params, self.formula, x, y)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let left = self.total().saturating_sub(self.next) as usize;
+        (left, Some(left))
+    }
+}
+
+impl<F: DistanceField + ?Sized> ExactSizeIterator for RenderIter<'_, F> {}
+
+/// Iterator over painted RGBA rows (`width × 4` bytes each).
+///
+/// Each row is painted on its own, so neighbourhood effects of the paint
+/// pass (the local exposure map) only see that row.
+pub struct PaintedRows<'a, F: DistanceField + ?Sized> {
+    pixels: RenderIter<'a, F>,
+    config: &'a PaintConfig,
+    row: Vec<SiLight5>,
+}
+
+impl<'a, F: DistanceField + ?Sized> PaintedRows<'a, F> {
+    pub fn new(params: &'a RenderParams, formula: &'a F, config: &'a PaintConfig) -> Self {
+        Self { pixels: RenderIter::new(params, formula), config, row: Vec::with_capacity(params.width as usize) }
+    }
+}
+
+impl<F: DistanceField + ?Sized> Iterator for PaintedRows<'_, F> {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Vec<u8>> {
+        let width = self.pixels.params.width;
+        self.row.clear();
+        self.row.extend(self.pixels.by_ref().take(width as usize).map(|(_, _, entry)| entry));
+        if self.row.is_empty() {
+            return None;
+        }
+        let mut rgba = vec![0u8; self.row.len() * 4];
+        paint::paint_gbuffer(&self.row, &mut rgba, self.row.len() as u32, 1, self.config);
+        Some(rgba)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formulas::hybrid::{HybridFormula, HybridMode};
+    use crate::formulas::FormulaId;
+
+    #[test]
+    fn test_iterator_matches_scanline_render() {
+        let formula = HybridFormula::new(&[(FormulaId::MandelbulbPower8, 1)], HybridMode::Alternating, 8, 16.0);
+        let params = RenderParams { width: 7, height: 5, ..Default::default() };
+        let mut full = vec![SiLight5::default(); 35];
+        raymarcher::render_scanlines(&params, &formula, &mut full, 0, 1);
+
+        let iter = RenderIter::new(&params, &formula);
+        assert_eq!(iter.len(), 35);
+        for (x, y, entry) in iter {
+            let expected = full[(y * 7 + x) as usize];
+            assert_eq!((entry.z_pos, entry.sn_x), (expected.z_pos, expected.sn_x));
+        }
+        assert_eq!(RenderIter::from_row(&params, &formula, 3).len(), 14);
+
+        let config = PaintConfig::default();
+        let rows: Vec<Vec<u8>> = PaintedRows::new(&params, &formula, &config).collect();
+        assert_eq!(rows.len(), 5);
+        assert!(rows.iter().all(|r| r.len() == 28));
+    }
+}
@@ -0,0 +1,573 @@
+//! Surface meshing: adaptive octree voxelization + dual contouring.
+//!
+//! The octree only descends where the DE says the surface may pass (|d| no
+//! more than the cell's half diagonal), so empty space costs one DE per
+//! pruned cell instead of a dense voxel grid. Each finest cell whose corners
+//! change sign gets one vertex, placed at the minimum of the QEF built from
+//! the edge crossings and their normals (Ju et al. 2002). Marching cubes puts
+//! its vertices on cell edges and rounds off the creases of box-folded
+//! surfaces; the QEF vertex lands on the crease or corner itself.
+//!
+//! With a triangle budget, octree nodes whose merged QEF error stays under a
+//! threshold collapse to one vertex — flat regions first, creases last — and
+//! the threshold is searched so the mesh fits the budget.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::engine::types::Vec3D;
+use crate::formulas::DistanceField;
+use crate::math::math3d;
+
+/// Deepest supported octree level (512 cells per axis).
+pub const MAX_DEPTH: u32 = 9;
+
+/// Refinement steps for each edge crossing.
+const CROSSING_STEPS: usize = 4;
+
+/// What to mesh and how finely.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MeshSettings {
+    /// Box sampled by the octree
+    pub min: Vec3D,
+    pub max: Vec3D,
+    /// Finest octree level: 2^depth cells per axis (1..=MAX_DEPTH)
+    pub depth: u32,
+    /// Maximum triangle count (0 = unlimited)
+    pub triangle_budget: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MeshError {
+    /// The box is empty or not finite
+    Bounds,
+    /// Depth outside 1..=MAX_DEPTH
+    Depth(u32),
+}
+
+impl fmt::Display for MeshError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MeshError::Bounds => write!(f, "mesh bounds must be a finite, non-empty box"),
+            MeshError::Depth(d) => write!(f, "mesh depth {d} is outside 1..={MAX_DEPTH}"),
+        }
+    }
+}
+
+impl std::error::Error for MeshError {}
+
+/// Indexed triangle mesh, counter-clockwise seen from outside.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Mesh {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub triangles: Vec<[u32; 3]>,
+}
+
+impl Mesh {
+    /// Binary STL (facet normals from the winding).
+    pub fn to_stl(&self) -> Vec<u8> {
+        let mut out = vec![0u8; 80];
+        out.extend_from_slice(&(self.triangles.len() as u32).to_le_bytes());
+        let vec = |i: u32| {
+            let p = self.positions[i as usize];
+            Vec3D { x: p[0] as f64, y: p[1] as f64, z: p[2] as f64 }
+        };
+        for t in &self.triangles {
+            let (a, b, c) = (vec(t[0]), vec(t[1]), vec(t[2]));
+            let n = math3d::vec3d_normalized(&math3d::vec3d_cross(&math3d::vec3d_sub(&b, &a), &math3d::vec3d_sub(&c, &a)));
+            for v in [n, a, b, c] {
+                for x in [v.x, v.y, v.z] {
+                    out.extend_from_slice(&(x as f32).to_le_bytes());
+                }
+            }
+            out.extend_from_slice(&[0, 0]);
+        }
+        out
+    }
+}
+
+/// Grid coordinates (in finest cells) of a corner or cell.
+type Key = (u32, u32, u32);
+
+/// Quadric error function of a cell: squared distances to the tangent
+/// planes of its edge crossings.
+#[derive(Clone, Copy, Default)]
+struct Qef {
+    /// Upper triangle of AᵀA: xx, xy, xz, yy, yz, zz
+    ata: [f64; 6],
+    atb: [f64; 3],
+    btb: f64,
+    mass: Vec3D,
+    normal: Vec3D,
+    count: u32,
+}
+
+impl Qef {
+    fn add(&mut self, p: &Vec3D, n: &Vec3D) {
+        let d = math3d::vec3d_dot(n, p);
+        let [xx, xy, xz, yy, yz, zz] = &mut self.ata;
+        *xx += n.x * n.x;
+        *xy += n.x * n.y;
+        *xz += n.x * n.z;
+        *yy += n.y * n.y;
+        *yz += n.y * n.z;
+        *zz += n.z * n.z;
+        self.atb[0] += n.x * d;
+        self.atb[1] += n.y * d;
+        self.atb[2] += n.z * d;
+        self.btb += d * d;
+        self.mass = math3d::vec3d_add(&self.mass, p);
+        self.normal = math3d::vec3d_add(&self.normal, n);
+        self.count += 1;
+    }
+
+    fn merge(&mut self, other: &Qef) {
+        for (a, b) in self.ata.iter_mut().zip(other.ata) {
+            *a += b;
+        }
+        for (a, b) in self.atb.iter_mut().zip(other.atb) {
+            *a += b;
+        }
+        self.btb += other.btb;
+        self.mass = math3d::vec3d_add(&self.mass, &other.mass);
+        self.normal = math3d::vec3d_add(&self.normal, &other.normal);
+        self.count += other.count;
+    }
+
+    fn matrix(&self) -> [[f64; 3]; 3] {
+        let [xx, xy, xz, yy, yz, zz] = self.ata;
+        [[xx, xy, xz], [xy, yy, yz], [xz, yz, zz]]
+    }
+
+    /// Minimizer, kept inside `[lo, hi]` (the mass point otherwise), and its error.
+    fn solve(&self, lo: &Vec3D, hi: &Vec3D) -> (Vec3D, f64) {
+        let c = math3d::vec3d_scale(&self.mass, 1.0 / self.count.max(1) as f64);
+        let a = self.matrix();
+        let mul = |v: [f64; 3]| [0, 1, 2].map(|i| a[i][0] * v[0] + a[i][1] * v[1] + a[i][2] * v[2]);
+        let ac = mul([c.x, c.y, c.z]);
+        let rhs = [0, 1, 2].map(|i| self.atb[i] - ac[i]);
+
+        // Pseudo-inverse around the mass point, dropping directions the
+        // normals do not constrain (flat or single-crease cells)
+        let (values, vectors) = jacobi_eigen(a);
+        let largest = values.iter().fold(0.0f64, |m, v| m.max(v.abs()));
+        let mut offset = [0.0; 3];
+        for (k, &value) in values.iter().enumerate() {
+            if value.abs() <= largest * 1e-2 || value == 0.0 {
+                continue;
+            }
+            let v = [vectors[0][k], vectors[1][k], vectors[2][k]];
+            let s = (v[0] * rhs[0] + v[1] * rhs[1] + v[2] * rhs[2]) / value;
+            for i in 0..3 {
+                offset[i] += v[i] * s;
+            }
+        }
+        let mut x = Vec3D { x: c.x + offset[0], y: c.y + offset[1], z: c.z + offset[2] };
+        let inside = |p: &Vec3D| p.x >= lo.x && p.x <= hi.x && p.y >= lo.y && p.y <= hi.y && p.z >= lo.z && p.z <= hi.z;
+        if !inside(&x) {
+            x = c;
+        }
+        (x, self.error(&x))
+    }
+
+    /// |Ax − b|² at `x`.
+    fn error(&self, x: &Vec3D) -> f64 {
+        let a = self.matrix();
+        let v = [x.x, x.y, x.z];
+        let quad: f64 = (0..3).map(|i| v[i] * (a[i][0] * v[0] + a[i][1] * v[1] + a[i][2] * v[2])).sum();
+        let lin: f64 = (0..3).map(|i| v[i] * self.atb[i]).sum();
+        (quad - 2.0 * lin + self.btb).max(0.0)
+    }
+}
+
+/// Eigenvalues and eigenvectors (columns) of a symmetric 3×3 matrix by
+/// cyclic Jacobi rotations.
+fn jacobi_eigen(mut a: [[f64; 3]; 3]) -> ([f64; 3], [[f64; 3]; 3]) {
+    let mut v = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+    for _ in 0..16 {
+        let off = a[0][1].abs() + a[0][2].abs() + a[1][2].abs();
+        if off < 1e-30 {
+            break;
+        }
+        for (p, q) in [(0, 1), (0, 2), (1, 2)] {
+            if a[p][q].abs() < 1e-300 {
+                continue;
+            }
+            let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+            let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+            let c = 1.0 / (t * t + 1.0).sqrt();
+            let s = t * c;
+            for row in &mut a {
+                let (akp, akq) = (row[p], row[q]);
+                row[p] = c * akp - s * akq;
+                row[q] = s * akp + c * akq;
+            }
+            let (rp, rq) = (a[p], a[q]);
+            a[p] = [0, 1, 2].map(|k| c * rp[k] - s * rq[k]);
+            a[q] = [0, 1, 2].map(|k| s * rp[k] + c * rq[k]);
+            for row in &mut v {
+                let (vp, vq) = (row[p], row[q]);
+                row[p] = c * vp - s * vq;
+                row[q] = s * vp + c * vq;
+            }
+        }
+    }
+    ([a[0][0], a[1][1], a[2][2]], v)
+}
+
+/// Signed DE samples on the finest grid, cached per corner.
+struct Sampler<'a, F: DistanceField + ?Sized> {
+    field: &'a F,
+    min: Vec3D,
+    cell: Vec3D,
+    corners: HashMap<Key, f64>,
+}
+
+impl<F: DistanceField + ?Sized> Sampler<'_, F> {
+    /// World position of fractional grid coordinates.
+    fn point(&self, x: f64, y: f64, z: f64) -> Vec3D {
+        Vec3D { x: self.min.x + x * self.cell.x, y: self.min.y + y * self.cell.y, z: self.min.z + z * self.cell.z }
+    }
+
+    fn corner(&mut self, k: Key) -> f64 {
+        if let Some(&d) = self.corners.get(&k) {
+            return d;
+        }
+        let d = self.field.signed_de(&self.point(k.0 as f64, k.1 as f64, k.2 as f64));
+        self.corners.insert(k, d);
+        d
+    }
+
+    /// Surface crossing on the edge from corner `k` along `axis`, with the
+    /// DE gradient there.
+    fn crossing(&mut self, k: Key, axis: usize) -> (Vec3D, Vec3D) {
+        let (mut a, mut b) = ((0.0, self.corner(k)), (1.0, self.corner(step(k, axis, 1))));
+        let start_inside = a.1 < 0.0;
+        let at = |t: f64| {
+            let mut c = [k.0 as f64, k.1 as f64, k.2 as f64];
+            c[axis] += t;
+            self.point(c[0], c[1], c[2])
+        };
+        // False position, falling back to bisection when it stalls at an end
+        let mut best = if a.1.abs() < b.1.abs() { a } else { b };
+        for _ in 0..CROSSING_STEPS {
+            let mut t = a.0 - a.1 * (b.0 - a.0) / (b.1 - a.1);
+            if !(t > a.0 && t < b.0) {
+                t = 0.5 * (a.0 + b.0);
+            }
+            let d = self.field.signed_de(&at(t));
+            if d.abs() < best.1.abs() {
+                best = (t, d);
+            }
+            if d == 0.0 {
+                break;
+            }
+            if (d < 0.0) == (a.1 < 0.0) { a = (t, d) } else { b = (t, d) }
+        }
+        let p = at(best.0);
+
+        let h = 0.05 * self.cell.x.min(self.cell.y).min(self.cell.z);
+        let diff = |dx: f64, dy: f64, dz: f64| {
+            self.field.signed_de(&Vec3D { x: p.x + dx, y: p.y + dy, z: p.z + dz })
+                - self.field.signed_de(&Vec3D { x: p.x - dx, y: p.y - dy, z: p.z - dz })
+        };
+        let mut n = Vec3D { x: diff(h, 0.0, 0.0), y: diff(0.0, h, 0.0), z: diff(0.0, 0.0, h) };
+        if !(n.x.is_finite() && n.y.is_finite() && n.z.is_finite()) || math3d::vec3d_length_sqr(&n) == 0.0 {
+            // Degenerate gradient: the edge direction, pointing outward
+            n = Vec3D::default();
+            let sign = if start_inside { 1.0 } else { -1.0 };
+            match axis {
+                0 => n.x = sign,
+                1 => n.y = sign,
+                _ => n.z = sign,
+            }
+        }
+        (p, math3d::vec3d_normalized(&n))
+    }
+}
+
+fn step(k: Key, axis: usize, by: i64) -> Key {
+    let mut c = [k.0 as i64, k.1 as i64, k.2 as i64];
+    c[axis] += by;
+    (c[0] as u32, c[1] as u32, c[2] as u32)
+}
+
+fn parent(k: Key) -> Key {
+    (k.0 >> 1, k.1 >> 1, k.2 >> 1)
+}
+
+/// Octree node after solving its QEF.
+struct Node {
+    qef: Qef,
+    vertex: Vec3D,
+    error: f64,
+}
+
+/// Mesh the zero set of `field.signed_de` inside the settings box.
+pub fn dual_contour<F: DistanceField + ?Sized>(field: &F, settings: &MeshSettings) -> Result<Mesh, MeshError> {
+    let (min, max) = (settings.min, settings.max);
+    let finite = [min.x, min.y, min.z, max.x, max.y, max.z].iter().all(|v| v.is_finite());
+    if !finite || max.x <= min.x || max.y <= min.y || max.z <= min.z {
+        return Err(MeshError::Bounds);
+    }
+    let depth = settings.depth;
+    if !(1..=MAX_DEPTH).contains(&depth) {
+        return Err(MeshError::Depth(depth));
+    }
+    let n = 1u32 << depth;
+    let cell = Vec3D { x: (max.x - min.x) / n as f64, y: (max.y - min.y) / n as f64, z: (max.z - min.z) / n as f64 };
+    let mut sampler = Sampler { field, min, cell, corners: HashMap::new() };
+
+    // Adaptive descent: keep only cells the surface may pass through
+    let cell_diagonal = math3d::vec3d_length(&cell);
+    let mut active = Vec::new();
+    let mut stack = vec![((0, 0, 0), n)];
+    while let Some((k, size)) = stack.pop() {
+        if size > 1 {
+            let half = size as f64 * 0.5;
+            let center = sampler.point(k.0 as f64 + half, k.1 as f64 + half, k.2 as f64 + half);
+            if field.signed_de(&center).abs() > half * cell_diagonal {
+                continue;
+            }
+            let h = size / 2;
+            for i in 0..8 {
+                stack.push(((k.0 + (i & 1) * h, k.1 + (i >> 1 & 1) * h, k.2 + (i >> 2) * h), h));
+            }
+            continue;
+        }
+        let inside: Vec<bool> = (0..8).map(|i| sampler.corner((k.0 + (i & 1), k.1 + (i >> 1 & 1), k.2 + (i >> 2))) < 0.0).collect();
+        if inside.iter().any(|&s| s != inside[0]) {
+            active.push(k);
+        }
+    }
+
+    // Hermite data on the sign-changing edges of the active cells
+    let mut leaves: HashMap<Key, Qef> = HashMap::new();
+    let mut edges: HashMap<(Key, usize), (Vec3D, Vec3D)> = HashMap::new();
+    for &k in &active {
+        let mut qef = Qef::default();
+        for axis in 0..3 {
+            let (b, c) = ((axis + 1) % 3, (axis + 2) % 3);
+            for (i, j) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                let start = step(step(k, b, i), c, j);
+                if (sampler.corner(start) < 0.0) == (sampler.corner(step(start, axis, 1)) < 0.0) {
+                    continue;
+                }
+                let (p, normal) = match edges.get(&(start, axis)) {
+                    Some(&h) => h,
+                    None => {
+                        let h = sampler.crossing(start, axis);
+                        edges.insert((start, axis), h);
+                        h
+                    }
+                };
+                qef.add(&p, &normal);
+            }
+        }
+        leaves.insert(k, qef);
+    }
+
+    // One quad per crossing edge, joining the four cells around it
+    let mut quads = Vec::with_capacity(edges.len());
+    for &(start, axis) in edges.keys() {
+        let (b, c) = ((axis + 1) % 3, (axis + 2) % 3);
+        let coord = |k: Key, a: usize| [k.0, k.1, k.2][a];
+        if coord(start, b) == 0 || coord(start, c) == 0 {
+            continue;
+        }
+        let cells = [start, step(start, b, -1), step(step(start, b, -1), c, -1), step(start, c, -1)];
+        if !cells.iter().all(|k| leaves.contains_key(k)) {
+            continue;
+        }
+        // Counter-clockwise around +axis, so facing outward when the start is inside
+        if sampler.corner(start) < 0.0 {
+            quads.push(cells);
+        } else {
+            quads.push([cells[3], cells[2], cells[1], cells[0]]);
+        }
+    }
+    quads.sort_unstable();
+
+    // Merge the QEFs up the octree; level `depth` holds the leaves
+    let mut levels: Vec<HashMap<Key, Node>> = (0..=depth).map(|_| HashMap::new()).collect();
+    let bounds = |level: u32, k: Key| {
+        let size = (1u32 << (depth - level)) as f64;
+        let lo = sampler.point(k.0 as f64 * size, k.1 as f64 * size, k.2 as f64 * size);
+        let hi = sampler.point((k.0 + 1) as f64 * size, (k.1 + 1) as f64 * size, (k.2 + 1) as f64 * size);
+        (lo, hi)
+    };
+    let mut merged = leaves;
+    for level in (0..=depth).rev() {
+        let mut up: HashMap<Key, Qef> = HashMap::new();
+        for (&k, qef) in &merged {
+            up.entry(parent(k)).or_default().merge(qef);
+            let (lo, hi) = bounds(level, k);
+            let (vertex, error) = qef.solve(&lo, &hi);
+            levels[level as usize].insert(k, Node { qef: *qef, vertex, error });
+        }
+        merged = up;
+    }
+
+    // Collapse threshold: the smallest one that fits the budget
+    let budget = settings.triangle_budget;
+    let mut threshold = -1.0;
+    if budget > 0 && count_triangles(&quads, &levels, depth, threshold) > budget {
+        let mut errors: Vec<f64> = levels[..depth as usize].iter().flat_map(|l| l.values().map(|n| n.error)).collect();
+        errors.sort_by(f64::total_cmp);
+        errors.dedup();
+        let (mut lo, mut hi) = (0, errors.len());
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            if count_triangles(&quads, &levels, depth, errors[mid]) <= budget { hi = mid } else { lo = mid + 1 }
+        }
+        threshold = errors.get(lo).copied().unwrap_or(f64::INFINITY);
+    }
+
+    let collapsed = collapsed_nodes(&levels, depth, threshold);
+    let mut mesh = Mesh::default();
+    let mut index: HashMap<(u32, Key), u32> = HashMap::new();
+    let mut emitted = HashSet::new();
+    for quad in &quads {
+        let reps = quad.map(|k| representative(&collapsed, depth, k));
+        for tri in [[reps[0], reps[1], reps[2]], [reps[0], reps[2], reps[3]]] {
+            // Collapsed quads repeat vertices and triangles
+            if tri[0] == tri[1] || tri[1] == tri[2] || tri[0] == tri[2] || !emitted.insert(tri) {
+                continue;
+            }
+            let ids = tri.map(|(level, k)| {
+                *index.entry((level, k)).or_insert_with(|| {
+                    let node = &levels[level as usize][&k];
+                    let n = math3d::vec3d_normalized(&node.qef.normal);
+                    mesh.positions.push([node.vertex.x as f32, node.vertex.y as f32, node.vertex.z as f32]);
+                    mesh.normals.push([n.x as f32, n.y as f32, n.z as f32]);
+                    (mesh.positions.len() - 1) as u32
+                })
+            });
+            mesh.triangles.push(ids);
+        }
+    }
+    Ok(mesh)
+}
+
+/// Per level, the nodes collapsed at `threshold`: every leaf, and inner
+/// nodes within the error whose children all collapsed.
+fn collapsed_nodes(levels: &[HashMap<Key, Node>], depth: u32, threshold: f64) -> Vec<HashSet<Key>> {
+    let mut collapsed: Vec<HashSet<Key>> = vec![HashSet::new(); depth as usize + 1];
+    collapsed[depth as usize] = levels[depth as usize].keys().copied().collect();
+    for level in (0..depth as usize).rev() {
+        let blocked: HashSet<Key> =
+            levels[level + 1].keys().filter(|k| !collapsed[level + 1].contains(k)).map(|&k| parent(k)).collect();
+        collapsed[level] =
+            levels[level].iter().filter(|(k, n)| n.error <= threshold && !blocked.contains(k)).map(|(&k, _)| k).collect();
+    }
+    collapsed
+}
+
+/// Coarsest collapsed ancestor of leaf `k`, as (level, key).
+fn representative(collapsed: &[HashSet<Key>], depth: u32, k: Key) -> (u32, Key) {
+    (0..=depth)
+        .map(|level| (level, (k.0 >> (depth - level), k.1 >> (depth - level), k.2 >> (depth - level))))
+        .find(|(level, a)| collapsed[*level as usize].contains(a))
+        .unwrap_or((depth, k))
+}
+
+fn count_triangles(quads: &[[Key; 4]], levels: &[HashMap<Key, Node>], depth: u32, threshold: f64) -> usize {
+    let collapsed = collapsed_nodes(levels, depth, threshold);
+    let mut triangles = HashSet::new();
+    for quad in quads {
+        let r = quad.map(|k| representative(&collapsed, depth, k));
+        for tri in [[r[0], r[1], r[2]], [r[0], r[2], r[3]]] {
+            if tri[0] != tri[1] && tri[1] != tri[2] && tri[0] != tri[2] {
+                triangles.insert(tri);
+            }
+        }
+    }
+    triangles.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formulas::FormulaResult;
+
+    /// Axis-aligned box with half size `h`: the sharp-edged test shape.
+    struct Cube(f64);
+
+    impl DistanceField for Cube {
+        fn compute_de(&self, p: &Vec3D) -> FormulaResult {
+            let q = [p.x.abs() - self.0, p.y.abs() - self.0, p.z.abs() - self.0];
+            let outside = q.iter().map(|v| v.max(0.0).powi(2)).sum::<f64>().sqrt();
+            let inside = q[0].max(q[1]).max(q[2]).min(0.0);
+            let de = outside + inside;
+            FormulaResult { de: de.max(0.0), inside: de < 0.0, interior_de: Some(-de.min(0.0)), ..Default::default() }
+        }
+    }
+
+    struct Sphere(f64);
+
+    impl DistanceField for Sphere {
+        fn compute_de(&self, p: &Vec3D) -> FormulaResult {
+            let de = math3d::vec3d_length(p) - self.0;
+            FormulaResult { de: de.max(0.0), inside: de < 0.0, interior_de: Some(-de.min(0.0)), ..Default::default() }
+        }
+    }
+
+    fn settings(depth: u32, triangle_budget: usize) -> MeshSettings {
+        let (a, b) = (-0.5, 0.5);
+        MeshSettings { min: Vec3D { x: a, y: a, z: a }, max: Vec3D { x: b, y: b, z: b }, depth, triangle_budget }
+    }
+
+    #[test]
+    fn test_sphere_is_closed_and_faces_out() {
+        let mesh = dual_contour(&Sphere(0.3), &settings(4, 0)).unwrap();
+        assert!(mesh.triangles.len() > 100);
+
+        // Every directed edge is matched by its reverse exactly once
+        let mut edges = HashMap::new();
+        for t in &mesh.triangles {
+            for i in 0..3 {
+                *edges.entry((t[i], t[(i + 1) % 3])).or_insert(0) += 1;
+            }
+        }
+        assert!(edges.iter().all(|(&(a, b), &n)| n == 1 && edges.get(&(b, a)) == Some(&1)));
+
+        for t in &mesh.triangles {
+            let p = t.map(|i| {
+                let p = mesh.positions[i as usize];
+                Vec3D { x: p[0] as f64, y: p[1] as f64, z: p[2] as f64 }
+            });
+            let n = math3d::vec3d_cross(&math3d::vec3d_sub(&p[1], &p[0]), &math3d::vec3d_sub(&p[2], &p[0]));
+            assert!(math3d::vec3d_dot(&n, &p[0]) > 0.0);
+            assert!((math3d::vec3d_length(&p[0]) - 0.3).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn test_cube_keeps_sharp_corners() {
+        // Corners at ±0.3 fall inside cells of the 16³ grid, not on its lines
+        let mesh = dual_contour(&Cube(0.3), &settings(4, 0)).unwrap();
+        let corner_hit = |c: [f32; 3]| mesh.positions.iter().any(|p| (0..3).all(|i| (p[i] - c[i]).abs() < 1e-4));
+        for sx in [-0.3, 0.3] {
+            for sy in [-0.3, 0.3] {
+                for sz in [-0.3, 0.3] {
+                    assert!(corner_hit([sx, sy, sz]), "{sx} {sy} {sz}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_budget_collapses_flat_regions_first() {
+        let full = dual_contour(&Cube(0.3), &settings(5, 0)).unwrap();
+        let mesh = dual_contour(&Cube(0.3), &settings(5, 200)).unwrap();
+        assert!(full.triangles.len() > 1000);
+        assert!(!mesh.triangles.is_empty() && mesh.triangles.len() <= 200, "{}", mesh.triangles.len());
+        // Collapsed vertices stay on the cube
+        for p in &mesh.positions {
+            let d = Cube(0.3).signed_de(&Vec3D { x: p[0] as f64, y: p[1] as f64, z: p[2] as f64 });
+            assert!(d.abs() < 1e-3, "{p:?}");
+        }
+        assert_eq!(dual_contour(&Cube(0.3), &settings(0, 0)), Err(MeshError::Depth(0)));
+    }
+}
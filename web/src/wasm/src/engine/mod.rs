@@ -1,2 +1,28 @@
 pub mod types;
 pub mod raymarcher;
+pub mod ao;
+pub mod refraction;
+pub mod montecarlo;
+pub mod progressive;
+pub mod antialias;
+pub mod preview;
+pub mod composite;
+pub mod stereo;
+pub mod cutting;
+pub mod autoiter;
+pub mod volumetric;
+pub mod bounds;
+pub mod prepass;
+pub mod repro;
+pub mod reproject;
+pub mod iter;
+pub mod camera;
+pub mod artifacts;
+pub mod replay;
+pub mod scene;
+pub mod validate;
+pub mod zones;
+pub mod mesh;
+pub mod animation;
+pub mod morph;
+pub mod gbuffer;
@@ -0,0 +1,206 @@
+//! Monte Carlo render mode — MB3D's "MC render".
+//!
+//! Each call traces one stochastic path per pixel (jittered sub-pixel position,
+//! soft shadows from cone-sampled lights, cosine-weighted diffuse bounces) and
+//! adds it to a float accumulation buffer. Repeated calls converge toward a
+//! noise-free global-illumination image; `resolve` turns the running average
+//! into displayable RGBA.
+//!
+//! Accumulation buffer: f32 × 4 per pixel — summed R, G, B and the sample count.
+
+use crate::engine::raymarcher::{self, RenderParams};
+use crate::engine::types::Vec3D;
+use crate::formulas::hybrid::HybridFormula;
+use crate::lighting::paint::PaintConfig;
+use crate::math::rng::Pcg32;
+use crate::math::{math3d, strict, utils};
+
+/// Floats per accumulation-buffer pixel.
+pub const ACCUM_CHANNELS: usize = 4;
+
+/// Path tracing settings.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct McSettings {
+    /// Diffuse bounces after the primary hit (0 = direct light only)
+    pub bounces: u32,
+    /// Angular radius of the lights in radians (0 = hard shadows)
+    pub light_radius: f64,
+}
+
+impl Default for McSettings {
+    fn default() -> Self {
+        Self { bounces: 1, light_radius: 0.02 }
+    }
+}
+
+/// Cosine-weighted random direction in the hemisphere around `normal`.
+pub fn cosine_direction(normal: &Vec3D, rng: &mut Pcg32) -> Vec3D {
+    let (t, b) = math3d::vec3d_orthonormal_basis(normal);
+    let u = rng.next_f64();
+    let (sp, cp) = strict::sin_cos(std::f64::consts::TAU * rng.next_f64());
+    let r = u.sqrt();
+    let lz = (1.0 - u).max(0.0).sqrt();
+    Vec3D {
+        x: t.x * r * cp + b.x * r * sp + normal.x * lz,
+        y: t.y * r * cp + b.y * r * sp + normal.y * lz,
+        z: t.z * r * cp + b.z * r * sp + normal.z * lz,
+    }
+}
+
+/// Random direction within a cone of angular `radius` around `axis`.
+fn cone_direction(axis: &Vec3D, radius: f64, rng: &mut Pcg32) -> Vec3D {
+    if radius <= 0.0 {
+        return *axis;
+    }
+    let (t, b) = math3d::vec3d_orthonormal_basis(axis);
+    let r = rng.next_f64().sqrt() * strict::tan(radius);
+    let (sp, cp) = strict::sin_cos(std::f64::consts::TAU * rng.next_f64());
+    math3d::vec3d_normalized(&Vec3D {
+        x: axis.x + (t.x * cp + b.x * sp) * r,
+        y: axis.y + (t.y * cp + b.y * sp) * r,
+        z: axis.z + (t.z * cp + b.z * sp) * r,
+    })
+}
+
+/// Trace one path and return its radiance.
+pub fn trace_path(
+    origin: &Vec3D,
+    direction: &Vec3D,
+    params: &RenderParams,
+    formula: &HybridFormula,
+    config: &PaintConfig,
+    rng: &mut Pcg32,
+) -> (f64, f64, f64) {
+    let mut radiance = (0.0, 0.0, 0.0);
+    let mut throughput = (1.0, 1.0, 1.0);
+    let mut origin = *origin;
+    let mut dir = *direction;
+    let offset = params.de_stop * 4.0;
+
+    for bounce in 0..=params.mc.bounces {
+        let mr = raymarcher::march_ray(&origin, &dir, params, formula);
+        if !mr.hit {
+            // Camera rays see the background, bounced rays the ambient sky
+            let sky = if bounce == 0 {
+                config.bg_color
+            } else {
+                let a = config.ambient_intensity;
+                (config.ambient_color.0 * a, config.ambient_color.1 * a, config.ambient_color.2 * a)
+            };
+            radiance.0 += throughput.0 * sky.0;
+            radiance.1 += throughput.1 * sky.1;
+            radiance.2 += throughput.2 * sky.2;
+            break;
+        }
+
+        let albedo = config.gradient.sample((mr.smooth_iteration % 256.0) / 256.0);
+        let n = mr.normal;
+        let surface = math3d::vec3d_add(&mr.hit_pos, &math3d::vec3d_scale(&n, offset));
+
+        // Direct light with one soft shadow ray per light
+        for light in &config.lights {
+            if !light.enabled || light.amplitude < 0.001 { continue; }
+            let l = cone_direction(&light.direction, params.mc.light_radius, rng);
+            let n_dot_l = math3d::vec3d_dot(&n, &l);
+            if n_dot_l <= 0.0 { continue; }
+            if raymarcher::march_ray(&surface, &l, params, formula).hit { continue; }
+            let e = n_dot_l * light.amplitude;
+            radiance.0 += throughput.0 * albedo.0 * light.color.0 * e;
+            radiance.1 += throughput.1 * albedo.1 * light.color.1 * e;
+            radiance.2 += throughput.2 * albedo.2 * light.color.2 * e;
+        }
+
+        // Diffuse bounce: cosine sampling cancels the cosine term and the pdf
+        throughput = (throughput.0 * albedo.0, throughput.1 * albedo.1, throughput.2 * albedo.2);
+        dir = cosine_direction(&n, rng);
+        origin = surface;
+    }
+
+    radiance
+}
+
+/// Add one sample per pixel for this worker's scanlines.
+///
+/// Returns the sample count of the pixels after this pass.
+pub fn render_pass(
+    params: &RenderParams,
+    formula: &HybridFormula,
+    config: &PaintConfig,
+    accum: &mut [f32],
+    worker_id: u32,
+    worker_count: u32,
+) -> u32 {
+    let w = params.width;
+    let h = params.height;
+    let mut samples = 0u32;
+
+    let mut y = worker_id;
+    while y < h {
+        for x in 0..w {
+            let idx = (y * w + x) as usize;
+            let ai = idx * ACCUM_CHANNELS;
+            if ai + 3 >= accum.len() { return samples; }
+
+            let sample = accum[ai + 3] as u32;
+            let mut rng = Pcg32::for_pixel(idx as u32, sample);
+
+            // Jittered sub-pixel position
+            let fx = x as f64 + rng.next_f64() - 0.5;
+            let fy = y as f64 + rng.next_f64() - 0.5;
+            let dir = raymarcher::pixel_direction(params, fx, fy);
+
+            let (r, g, b) = trace_path(&params.camera_pos, &dir, params, formula, config, &mut rng);
+            accum[ai] += r as f32;
+            accum[ai + 1] += g as f32;
+            accum[ai + 2] += b as f32;
+            accum[ai + 3] += 1.0;
+            samples = sample + 1;
+        }
+        y += worker_count;
+    }
+
+    samples
+}
+
+/// Convert the accumulation buffer into RGBA bytes (running average),
+/// sRGB-encoded when `srgb` is set.
+pub fn resolve(accum: &[f32], rgba_out: &mut [u8], srgb: bool) {
+    let encode = |v: f64| utils::float_to_byte(if srgb { utils::linear_to_srgb(v) } else { v });
+    for (px, out) in accum.chunks_exact(ACCUM_CHANNELS).zip(rgba_out.chunks_exact_mut(4)) {
+        let inv = if px[3] > 0.0 { 1.0 / px[3] as f64 } else { 0.0 };
+        out[0] = encode(px[0] as f64 * inv);
+        out[1] = encode(px[1] as f64 * inv);
+        out[2] = encode(px[2] as f64 * inv);
+        out[3] = 255;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formulas::{hybrid::HybridMode, FormulaId};
+
+    #[test]
+    fn test_cosine_directions_in_hemisphere() {
+        let n = math3d::vec3d_normalized(&Vec3D { x: 0.3, y: -1.0, z: 0.2 });
+        let mut rng = Pcg32::new(1, 1);
+        for _ in 0..64 {
+            let d = cosine_direction(&n, &mut rng);
+            assert!(math3d::vec3d_dot(&d, &n) >= 0.0);
+            assert!((math3d::vec3d_length(&d) - 1.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_accumulation_counts_samples() {
+        let params = RenderParams { width: 4, height: 4, ..Default::default() };
+        let formula = HybridFormula::new(&[(FormulaId::MandelbulbPower8, 1)], HybridMode::Alternating, 8, 16.0);
+        let config = PaintConfig::default();
+        let mut accum = vec![0.0f32; 16 * ACCUM_CHANNELS];
+        assert_eq!(render_pass(&params, &formula, &config, &mut accum, 0, 1), 1);
+        assert_eq!(render_pass(&params, &formula, &config, &mut accum, 0, 1), 2);
+        let mut rgba = vec![0u8; 16 * 4];
+        resolve(&accum, &mut rgba, false);
+        assert!(rgba.chunks(4).all(|p| p[3] == 255));
+    }
+}
@@ -0,0 +1,284 @@
+//! Parameter morphing between two scene descriptions.
+//!
+//! Every number the two scenes share — formula parameters, the julia
+//! constant, the z0 offset, light and ambient settings, gradient stops, fog,
+//! march settings — is interpolated. Settings are addressed by JSON pointer into the
+//! description (`/formulas/0/params/1`, `/lights/0/color`, `/gradient`), and
+//! each pointer prefix can have its own easing; the longest matching prefix
+//! wins and `""` sets the default:
+//!
+//! ```json
+//! { "": "ease_in_out", "/julia": "linear", "/lights/0/color": "hold" }
+//! ```
+//!
+//! The camera flies along an `animation::Timeline` from one view to the
+//! other (position interpolated, orientation slerped) under the `/camera`
+//! easing. Integer settings are rounded. Anything that cannot be
+//! interpolated — names, kinds, flags, lists of different lengths, a julia
+//! constant on one side only — comes from the nearer scene.
+
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+
+use crate::engine::animation::{self, Easing, Keyframe, Timeline};
+use crate::engine::camera;
+use crate::engine::scene::{CameraDescription, SceneDescription, SceneError};
+use crate::engine::types::Vec3D;
+use crate::math::math3d;
+
+/// Pointers the morph handles itself rather than as plain numbers.
+const SKIPPED: [&str; 4] = ["/version", "/camera/position", "/camera/target", "/camera/up"];
+
+/// One number present in both scenes.
+#[derive(Clone, Debug)]
+struct Leaf {
+    pointer: String,
+    integer: bool,
+    easing: Easing,
+}
+
+/// Two scenes and how to blend between them.
+#[derive(Clone, Debug)]
+pub struct SceneMorph {
+    from: Value,
+    to: Value,
+    leaves: Vec<Leaf>,
+    /// Keys at 0 and 1: camera path, then the leaf values in `leaves` order
+    /// followed by the camera's target distance
+    timeline: Timeline,
+    camera_easing: Easing,
+}
+
+fn vec3(v: [f64; 3]) -> Vec3D {
+    Vec3D { x: v[0], y: v[1], z: v[2] }
+}
+
+/// Easing of the longest prefix of `pointer` in `easings`.
+fn easing_for(easings: &BTreeMap<String, Easing>, pointer: &str) -> Easing {
+    easings
+        .iter()
+        .filter(|(prefix, _)| {
+            pointer == prefix.as_str()
+                || prefix.is_empty()
+                || pointer.strip_prefix(prefix.as_str()).is_some_and(|rest| rest.starts_with('/'))
+        })
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map_or(Easing::Linear, |(_, &easing)| easing)
+}
+
+/// Numbers found at the same place in both values.
+fn collect(a: &Value, b: &Value, pointer: &mut String, out: &mut Vec<(String, f64, f64, bool)>) {
+    if SKIPPED.contains(&pointer.as_str()) {
+        return;
+    }
+    let len = pointer.len();
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => {
+            if let (Some(fx), Some(fy)) = (x.as_f64(), y.as_f64()) {
+                let integer = !x.is_f64() && !y.is_f64();
+                out.push((pointer.clone(), fx, fy, integer));
+            }
+        }
+        (Value::Object(x), Value::Object(y)) => {
+            for (key, xv) in x {
+                if let Some(yv) = y.get(key) {
+                    pointer.push('/');
+                    pointer.push_str(key);
+                    collect(xv, yv, pointer, out);
+                    pointer.truncate(len);
+                }
+            }
+        }
+        (Value::Array(x), Value::Array(y)) if x.len() == y.len() => {
+            for (i, (xv, yv)) in x.iter().zip(y).enumerate() {
+                pointer.push_str(&format!("/{i}"));
+                collect(xv, yv, pointer, out);
+                pointer.truncate(len);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Camera key of a look-at camera.
+fn camera_key(c: &CameraDescription, time: f64, params: Vec<f64>) -> Keyframe {
+    let (position, target) = (vec3(c.position), vec3(c.target));
+    let rays = camera::compute_camera_rays(&position, &target, &vec3(c.up), 0.0, 1, 1);
+    Keyframe {
+        time,
+        position,
+        orientation: animation::orientation_from_rays(&rays),
+        zoom: 1.0,
+        params,
+        z0_offset: Vec3D::default(),
+        easing: Easing::Linear,
+    }
+}
+
+impl SceneMorph {
+    /// Morph from `from` (t = 0) to `to` (t = 1) with per-pointer `easings`.
+    pub fn new(from: &SceneDescription, to: &SceneDescription, easings: &BTreeMap<String, Easing>) -> Self {
+        let value = |s: &SceneDescription| serde_json::to_value(s).expect("scene descriptions always serialize");
+        let (a, b) = (value(from), value(to));
+        let mut found = Vec::new();
+        collect(&a, &b, &mut String::new(), &mut found);
+
+        let distance = |c: &CameraDescription| math3d::vec3d_length(&math3d::vec3d_sub(&vec3(c.target), &vec3(c.position)));
+        let mut from_params: Vec<f64> = found.iter().map(|f| f.1).collect();
+        let mut to_params: Vec<f64> = found.iter().map(|f| f.2).collect();
+        from_params.push(distance(&from.camera));
+        to_params.push(distance(&to.camera));
+        let keys = vec![camera_key(&from.camera, 0.0, from_params), camera_key(&to.camera, 1.0, to_params)];
+
+        let leaves = found
+            .into_iter()
+            .map(|(pointer, _, _, integer)| {
+                let easing = easing_for(easings, &pointer);
+                Leaf { pointer, integer, easing }
+            })
+            .collect();
+        Self {
+            from: a,
+            to: b,
+            leaves,
+            timeline: Timeline::new(keys, 0.0, Vec::new()).expect("two keys with equal param counts"),
+            camera_easing: easing_for(easings, "/camera"),
+        }
+    }
+
+    /// Parse both scenes and the easing map (pointer to easing name; empty =
+    /// linear everywhere).
+    pub fn from_json(from: &str, to: &str, easings: &str) -> Result<Self, SceneError> {
+        let easings = if easings.trim().is_empty() {
+            BTreeMap::new()
+        } else {
+            serde_json::from_str(easings).map_err(|e| SceneError::Json(e.to_string()))?
+        };
+        Ok(Self::new(&SceneDescription::from_json(from)?, &SceneDescription::from_json(to)?, &easings))
+    }
+
+    /// The scene at `t` in [0, 1] (clamped).
+    pub fn scene(&self, t: f64) -> SceneDescription {
+        let t = t.clamp(0.0, 1.0);
+        let mut out = if t < 0.5 { self.from.clone() } else { self.to.clone() };
+
+        // One timeline sample per easing in use
+        let mut samples: Vec<(Easing, Keyframe)> = Vec::new();
+        for easing in self.leaves.iter().map(|l| l.easing).chain([self.camera_easing]) {
+            if !samples.iter().any(|(e, _)| *e == easing) {
+                samples.push((easing, self.timeline.sample(easing.apply(t))));
+            }
+        }
+        let sample = |easing: Easing| &samples.iter().find(|(e, _)| *e == easing).expect("sampled above").1;
+
+        for (i, leaf) in self.leaves.iter().enumerate() {
+            let v = sample(leaf.easing).params[i];
+            let number = if leaf.integer {
+                Some(serde_json::Number::from(v.round() as i64))
+            } else {
+                serde_json::Number::from_f64(v)
+            };
+            if let (Some(slot), Some(number)) = (out.pointer_mut(&leaf.pointer), number) {
+                *slot = Value::Number(number);
+            }
+        }
+
+        let key = sample(self.camera_easing);
+        let m = key.orientation.to_matrix3().m;
+        let up = [m[0][1], m[1][1], m[2][1]];
+        let forward = Vec3D { x: m[0][2], y: m[1][2], z: m[2][2] };
+        let distance = key.params[self.leaves.len()];
+        let target = math3d::vec3d_add(&key.position, &math3d::vec3d_scale(&forward, distance));
+        let camera = &mut out["camera"];
+        camera["position"] = serde_json::json!([key.position.x, key.position.y, key.position.z]);
+        camera["target"] = serde_json::json!([target.x, target.y, target.z]);
+        camera["up"] = serde_json::json!(up);
+
+        serde_json::from_value(out).expect("blended values keep the description's shape")
+    }
+
+    /// Pointers of the interpolated numbers, for editors listing what morphs.
+    pub fn pointers(&self) -> impl Iterator<Item = &str> {
+        self.leaves.iter().map(|l| l.pointer.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::math3d::Quaternion;
+
+    fn forward(q: &Quaternion) -> Vec3D {
+        let m = q.to_matrix3().m;
+        Vec3D { x: m[0][2], y: m[1][2], z: m[2][2] }
+    }
+
+    fn scenes() -> (SceneDescription, SceneDescription) {
+        let from = SceneDescription::from_json(
+            r#"{ "camera": { "position": [0, 0, -2], "target": [0, 0, 0] },
+                 "formulas": [{ "name": "Amazing Box", "iterations": 10, "params": [2.0, 0.5] }],
+                 "julia": [0, 0, 0],
+                 "lights": [{ "color": [1, 0, 0], "amplitude": 1 }],
+                 "gradient": [{ "position": 0, "color": [0, 0, 0] }, { "position": 1, "color": [1, 1, 1] }] }"#,
+        )
+        .unwrap();
+        let to = SceneDescription::from_json(
+            r#"{ "camera": { "position": [2, 0, 0], "target": [0, 0, 0] },
+                 "formulas": [{ "name": "Amazing Box", "iterations": 13, "params": [-2.0, 0.5] }],
+                 "julia": [1, 0.5, -1],
+                 "z0_offset": [1, 0, -0.5],
+                 "lights": [{ "color": [0, 0, 1], "amplitude": 3, "kind": "point" }],
+                 "gradient": [{ "position": 0.5, "color": [1, 0, 0] }] }"#,
+        )
+        .unwrap();
+        (from, to)
+    }
+
+    #[test]
+    fn test_numbers_blend_and_the_rest_switches_halfway() {
+        let (from, to) = scenes();
+        let morph = SceneMorph::new(&from, &to, &BTreeMap::new());
+        assert_eq!(SceneDescription { camera: from.camera.clone(), ..morph.scene(0.0) }, from);
+
+        let mid = morph.scene(0.5);
+        assert_eq!(mid.formulas[0].params, vec![0.0, 0.5]);
+        assert_eq!(mid.formulas[0].iterations, 12); // 11.5 rounded
+        assert_eq!(mid.julia, Some([0.5, 0.25, -0.5]));
+        assert_eq!(mid.z0_offset, [0.5, 0.0, -0.25]);
+        assert_eq!((mid.lights[0].color, mid.lights[0].amplitude), ([0.5, 0.0, 0.5], 2.0));
+        // Not interpolatable: taken from the nearer scene
+        assert_eq!(mid.lights[0].kind, to.lights[0].kind);
+        assert_eq!(mid.gradient, to.gradient);
+        assert_eq!(morph.scene(0.49).gradient, from.gradient);
+
+        // The camera moves along the chord while turning halfway
+        let p = mid.camera.position;
+        let half = std::f64::consts::FRAC_1_SQRT_2;
+        assert!((p[0] - 1.0).abs() < 1e-12 && (p[2] + 1.0).abs() < 1e-12, "{p:?}");
+        let dir = math3d::vec3d_normalized(&math3d::vec3d_sub(&vec3(mid.camera.target), &vec3(p)));
+        assert!((dir.x + half).abs() < 1e-9 && (dir.z - half).abs() < 1e-9, "{dir:?}");
+        let target = morph.scene(1.0).camera.target;
+        assert!(target.iter().all(|v| v.abs() < 1e-12));
+    }
+
+    #[test]
+    fn test_easing_by_pointer_prefix() {
+        let (from, to) = scenes();
+        let easings = r#"{ "": "ease_in", "/lights/0/color": "hold", "/julia/0": "linear", "/camera": "hold" }"#;
+        let morph = SceneMorph::from_json(&from.to_json(), &to.to_json(), easings).unwrap();
+        // Different stop counts: the gradient switches instead
+        assert!(morph.pointers().any(|p| p == "/julia/2") && !morph.pointers().any(|p| p.starts_with("/gradient")));
+
+        let mid = morph.scene(0.5);
+        assert_eq!(mid.lights[0].color, [1.0, 0.0, 0.0]);
+        assert_eq!(mid.lights[0].amplitude, 1.5); // ease in: 0.25 of the way
+        assert_eq!(mid.julia, Some([0.5, 0.125, -0.25]));
+        let start = camera_key(&from.camera, 0.0, Vec::new());
+        let held = camera_key(&mid.camera, 0.0, Vec::new());
+        let (a, b) = (forward(&start.orientation), forward(&held.orientation));
+        assert!(math3d::vec3d_length(&math3d::vec3d_sub(&a, &b)) < 1e-12);
+
+        assert!(matches!(SceneMorph::from_json("{}", "{}", "{ \"\": \"bounce\" }"), Err(SceneError::Json(_))));
+    }
+}
@@ -0,0 +1,150 @@
+//! Low-resolution depth pre-pass for priming primary rays.
+//!
+//! One fat ray per `block × block` cell is cone-marched with a radius that
+//! covers every pixel ray of the cell, stopping as soon as the surface may
+//! touch the cone. The stopping distance is therefore a lower bound for the
+//! hit distance of all those rays, and full-resolution rays can start there
+//! instead of at the camera. Cells whose cone reaches the end of the ray
+//! without touching anything are known misses.
+
+use crate::engine::raymarcher::{self, RenderParams};
+use crate::engine::types::Vec3D;
+use crate::formulas::DistanceField;
+
+/// Depth stored for cells whose cone never touched the surface.
+pub const PREPASS_MISS: f32 = f32::INFINITY;
+
+/// Pre-pass grid size for an image: (columns, rows).
+pub fn prepass_size(params: &RenderParams) -> (u32, u32) {
+    let block = params.prepass_block.max(1);
+    (params.width.div_ceil(block), params.height.div_ceil(block))
+}
+
+/// Conservative first-contact distance of the cone through cell (bx, by).
+pub fn cone_depth<F: DistanceField + ?Sized>(params: &RenderParams, formula: &F, bx: u32, by: u32) -> f32 {
+    let block = params.prepass_block.max(1) as f64;
+    let center = raymarcher::pixel_direction(params, (bx as f64 + 0.5) * block, (by as f64 + 0.5) * block);
+    // Half the cell diagonal plus half a pixel, per unit of distance
+    let spread = params.pixel_footprint(1.0) * 2.0 * (block * std::f64::consts::FRAC_1_SQRT_2 + 0.5);
+
+    let mut t = 0.0f64;
+    for _ in 0..params.max_steps {
+        let pos = Vec3D {
+            x: params.camera_pos.x + center.x * t,
+            y: params.camera_pos.y + center.y * t,
+            z: params.camera_pos.z + center.z * t,
+        };
+        let de = formula.compute_de(&pos).de;
+        let radius = spread * t + params.de_stop;
+        if de.is_nan() || de < radius {
+            return t as f32;
+        }
+        // The unbounding sphere still contains the whole cone this far ahead
+        t += (de - radius) * params.step_width.min(1.0);
+        if t > params.max_ray_length {
+            return PREPASS_MISS;
+        }
+    }
+    t as f32
+}
+
+/// Fill interleaved pre-pass rows (row-major `prepass_size` grid).
+pub fn render_prepass_rows<F: DistanceField + ?Sized>(
+    params: &RenderParams,
+    formula: &F,
+    depths: &mut [f32],
+    worker_id: u32,
+    worker_count: u32,
+) -> u32 {
+    let (cols, rows) = prepass_size(params);
+    let mut rendered = 0;
+    let mut by = worker_id;
+    while by < rows {
+        for bx in 0..cols {
+            if let Some(d) = depths.get_mut((by * cols + bx) as usize) {
+                *d = cone_depth(params, formula, bx, by);
+            }
+        }
+        rendered += 1;
+        by += worker_count.max(1);
+    }
+    rendered
+}
+
+/// A finished pre-pass grid, used to prime full-resolution rays.
+#[derive(Clone, Copy)]
+pub struct DepthPrepass<'a> {
+    pub depths: &'a [f32],
+    pub block: u32,
+    pub cols: u32,
+    pub rows: u32,
+}
+
+impl<'a> DepthPrepass<'a> {
+    pub fn new(params: &RenderParams, depths: &'a [f32]) -> Option<Self> {
+        let (cols, rows) = prepass_size(params);
+        (depths.len() >= (cols * rows) as usize)
+            .then_some(Self { depths, block: params.prepass_block.max(1), cols, rows })
+    }
+
+    /// Distance at which the ray of pixel (x, y) may start, or None if it
+    /// certainly misses. Takes the minimum over the neighbouring cells so
+    /// rays near cell borders stay conservative.
+    pub fn start_distance(&self, x: u32, y: u32) -> Option<f64> {
+        let (bx, by) = ((x / self.block).min(self.cols - 1), (y / self.block).min(self.rows - 1));
+        let mut nearest = PREPASS_MISS;
+        for cy in by.saturating_sub(1)..=(by + 1).min(self.rows - 1) {
+            for cx in bx.saturating_sub(1)..=(bx + 1).min(self.cols - 1) {
+                nearest = nearest.min(self.depths[(cy * self.cols + cx) as usize]);
+            }
+        }
+        nearest.is_finite().then_some(nearest as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::raymarcher::GBufferLayers;
+    use crate::engine::types::SiLight5;
+    use crate::formulas::hybrid::{HybridFormula, HybridMode};
+    use crate::formulas::FormulaId;
+
+    #[test]
+    fn test_primed_render_matches_and_starts_before_hits() {
+        let formula = HybridFormula::new(&[(FormulaId::MandelbulbPower8, 1)], HybridMode::Alternating, 8, 16.0);
+        let params = RenderParams {
+            width: 96,
+            height: 72,
+            ray_dx: Vec3D { x: 0.6, y: 0.0, z: 0.0 },
+            ray_dy: Vec3D { x: 0.0, y: 0.45, z: 0.0 },
+            ..Default::default()
+        };
+        let (cols, rows) = prepass_size(&params);
+        assert_eq!((cols, rows), (24, 18));
+        let mut depths = vec![0.0f32; (cols * rows) as usize];
+        render_prepass_rows(&params, &formula, &mut depths, 0, 1);
+        assert!(depths.iter().any(|d| d.is_finite()) && depths.iter().any(|d| !d.is_finite()));
+
+        let mut plain = vec![SiLight5::default(); 96 * 72];
+        raymarcher::render_scanlines(&params, &formula, &mut plain, 0, 1);
+
+        let prepass = DepthPrepass::new(&params, &depths).unwrap();
+        let mut primed = vec![SiLight5::default(); 96 * 72];
+        let layers = GBufferLayers { prepass: Some(prepass), ..Default::default() };
+        raymarcher::render_scanlines_layers(&params, &formula, &mut primed, layers, 0, 1);
+
+        for (i, (a, b)) in plain.iter().zip(&primed).enumerate() {
+            let (za, zb) = (a.z_pos, b.z_pos);
+            assert_eq!(za == 65535, zb == 65535, "pixel {i}");
+            if za != 65535 {
+                // Different starting points may settle on the surface up to a pixel apart
+                let hit = za as f64 / 65535.0 * params.max_ray_length;
+                let diff = (za as f64 - zb as f64).abs() / 65535.0 * params.max_ray_length;
+                assert!(diff <= 2.0 * params.pixel_footprint(hit), "pixel {i}: {za} vs {zb}");
+                let start = prepass.start_distance(i as u32 % 96, i as u32 / 96).unwrap();
+                assert!(start <= hit);
+            }
+        }
+    }
+}
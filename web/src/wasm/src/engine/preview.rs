@@ -0,0 +1,186 @@
+//! Preview thumbnail cache keyed by scene hash.
+//!
+//! Preset browsers and history panels request the same small previews over and
+//! over; this keeps rendered RGBA thumbnails in WASM memory under a byte budget
+//! and evicts the least recently used ones. `render_frame` is the plain
+//! single-threaded march + paint used for thumbnails and session replays.
+
+use crate::engine::raymarcher::{self, GBufferLayers, RenderParams};
+use crate::engine::types::SiLight5;
+use crate::formulas::hybrid::HybridFormula;
+use crate::lighting::paint::{self, PaintConfig, PaintLayers, PaintView};
+
+/// Default memory budget for cached thumbnails (bytes).
+pub const DEFAULT_BUDGET: usize = 16 * 1024 * 1024;
+
+/// FNV-1a 64-bit hash over the scene buffers and thumbnail size.
+pub fn scene_hash(render_params: &[f64], formula_ids: &[u32], paint_params: &[f64], width: u32, height: u32) -> u64 {
+    const PRIME: u64 = 0x100000001b3;
+    let mut hash: u64 = 0xcbf29ce484222325;
+    let mut feed = |bytes: &[u8]| {
+        for &b in bytes {
+            hash ^= b as u64;
+            hash = hash.wrapping_mul(PRIME);
+        }
+    };
+    // Length prefixes keep e.g. ([a], [b, c]) and ([a, b], [c]) apart
+    feed(&(render_params.len() as u32).to_le_bytes());
+    render_params.iter().for_each(|v| feed(&v.to_bits().to_le_bytes()));
+    feed(&(formula_ids.len() as u32).to_le_bytes());
+    formula_ids.iter().for_each(|v| feed(&v.to_le_bytes()));
+    feed(&(paint_params.len() as u32).to_le_bytes());
+    paint_params.iter().for_each(|v| feed(&v.to_bits().to_le_bytes()));
+    feed(&width.to_le_bytes());
+    feed(&height.to_le_bytes());
+    hash
+}
+
+/// Single-threaded march + paint of a whole frame (with any secondary layers
+/// the paint config needs). Returns the primary G-buffer.
+pub fn render_frame(
+    params: &RenderParams,
+    formula: &HybridFormula,
+    config: &PaintConfig,
+    rgba_out: &mut [u8],
+) -> Vec<SiLight5> {
+    march_and_paint(params, formula, config, |gbuffer, layers, config| {
+        paint::paint_gbuffer_layers(gbuffer, layers, rgba_out, params.width, params.height, config)
+    })
+}
+
+/// `render_frame` with unquantized float output (see `paint::paint_gbuffer_f32`).
+pub fn render_frame_f32(
+    params: &RenderParams,
+    formula: &HybridFormula,
+    config: &PaintConfig,
+    rgba_out: &mut [f32],
+) -> Vec<SiLight5> {
+    march_and_paint(params, formula, config, |gbuffer, layers, config| {
+        paint::paint_gbuffer_f32(gbuffer, layers, rgba_out, params.width, params.height, config)
+    })
+}
+
+fn march_and_paint(
+    params: &RenderParams,
+    formula: &HybridFormula,
+    config: &PaintConfig,
+    paint: impl FnOnce(&[SiLight5], PaintLayers, &PaintConfig),
+) -> Vec<SiLight5> {
+    let pixel_count = params.width as usize * params.height as usize;
+    let mut gbuffer = vec![SiLight5::default(); pixel_count];
+    // Secondary layers only when the paint config will use them
+    let layer = |used: bool| used.then(|| vec![SiLight5::default(); pixel_count]);
+    let mut reflect = layer(config.reflectivity > 0.0 || config.material_reflectivity.iter().any(|(_, r)| *r > 0.0));
+    let mut transmit = layer(config.transparency > 0.0);
+
+    // Render all scanlines (single worker)
+    let layers = GBufferLayers {
+        reflect: reflect.as_deref_mut(),
+        transmit: transmit.as_deref_mut(),
+        ..Default::default()
+    };
+    raymarcher::render_scanlines_layers(params, formula, &mut gbuffer, layers, 0, 1);
+
+    // Paint
+    let layers = PaintLayers {
+        reflect: reflect.as_deref(),
+        transmit: transmit.as_deref(),
+        ..Default::default()
+    };
+    // The camera is known here, so view-dependent effects work without a view section
+    let with_view;
+    let config = if config.view.is_none() && config.needs_view() {
+        with_view = PaintConfig { view: Some(PaintView::from_render_params(params)), ..config.clone() };
+        &with_view
+    } else {
+        config
+    };
+    paint(&gbuffer, layers, config);
+    gbuffer
+}
+
+/// LRU cache of RGBA thumbnails; most recently used entries are at the back.
+pub struct PreviewCache {
+    entries: Vec<(u64, Vec<u8>)>,
+    budget: usize,
+    used: usize,
+}
+
+impl PreviewCache {
+    pub const fn new(budget: usize) -> Self {
+        Self { entries: Vec::new(), budget, used: 0 }
+    }
+
+    /// Look up a thumbnail and mark it as recently used.
+    pub fn get(&mut self, key: u64) -> Option<&[u8]> {
+        let pos = self.entries.iter().position(|(k, _)| *k == key)?;
+        let entry = self.entries.remove(pos);
+        self.entries.push(entry);
+        self.entries.last().map(|(_, rgba)| rgba.as_slice())
+    }
+
+    /// Store a thumbnail, evicting old ones to stay within the budget.
+    /// Thumbnails larger than the whole budget are not cached.
+    pub fn insert(&mut self, key: u64, rgba: Vec<u8>) {
+        if let Some(pos) = self.entries.iter().position(|(k, _)| *k == key) {
+            self.used -= self.entries.remove(pos).1.len();
+        }
+        if rgba.len() > self.budget {
+            return;
+        }
+        self.used += rgba.len();
+        self.entries.push((key, rgba));
+        self.evict();
+    }
+
+    /// Change the budget, evicting as needed.
+    pub fn set_budget(&mut self, budget: usize) {
+        self.budget = budget;
+        self.evict();
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.used = 0;
+    }
+
+    /// Bytes currently held.
+    pub fn used(&self) -> usize {
+        self.used
+    }
+
+    fn evict(&mut self) {
+        while self.used > self.budget && !self.entries.is_empty() {
+            self.used -= self.entries.remove(0).1.len();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lru_eviction_order() {
+        let mut cache = PreviewCache::new(8);
+        cache.insert(1, vec![0; 4]);
+        cache.insert(2, vec![0; 4]);
+        assert!(cache.get(1).is_some()); // 1 is now most recent
+        cache.insert(3, vec![0; 4]);
+        assert!(cache.get(2).is_none());
+        assert!(cache.get(1).is_some() && cache.get(3).is_some());
+        assert_eq!(cache.used(), 8);
+        cache.insert(4, vec![0; 16]); // larger than the budget — ignored
+        assert!(cache.get(4).is_none());
+    }
+
+    #[test]
+    fn test_hash_separates_buffers() {
+        let a = scene_hash(&[1.0], &[2, 3], &[], 64, 64);
+        let b = scene_hash(&[1.0], &[2], &[], 64, 64);
+        let c = scene_hash(&[1.0], &[2, 3], &[], 64, 32);
+        assert_ne!(a, b);
+        assert_ne!(a, c);
+        assert_eq!(a, scene_hash(&[1.0], &[2, 3], &[], 64, 64));
+    }
+}
@@ -0,0 +1,184 @@
+//! Progressive refinement rendering for interactive navigation.
+//!
+//! The first pass marches one ray per `block × block` cell and fills the whole
+//! cell with it; each following pass halves the block size and renders only
+//! the pixels not covered by earlier passes, until every pixel has its own ray.
+//! The cursor is kept in `ProgressiveState`, so rendering can stop after any
+//! pixel budget and resume later while JS paints the intermediate G-buffer.
+//!
+//! `advance` converts a frame time budget into rays with a fixed cost model
+//! instead of reading the clock, so recordings and tests see the same
+//! sequence of intermediate images on every machine.
+
+use crate::engine::raymarcher::{self, RenderParams};
+use crate::engine::types::SiLight5;
+use crate::formulas::hybrid::HybridFormula;
+
+/// Resumable progressive render cursor.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProgressiveState {
+    /// Block size of the pass in progress (power of two, 1 = final pass)
+    pub block: u32,
+    /// Block size of the first pass
+    start_block: u32,
+    /// Next pixel to render in the current pass
+    row: u32,
+    col: u32,
+    /// All passes finished
+    pub done: bool,
+    /// Rays marched since the last reset (one per pixel over all passes)
+    pub rays_done: u32,
+    /// Fractional ray budget carried over between `advance` calls
+    budget_carry: f64,
+}
+
+impl ProgressiveState {
+    /// Start with blocks of `start_block` pixels (rounded up to a power of two).
+    pub fn new(start_block: u32) -> Self {
+        let block = start_block.max(1).next_power_of_two();
+        Self { block, start_block: block, row: 0, col: 0, done: false, rays_done: 0, budget_carry: 0.0 }
+    }
+
+    /// Restart from the coarsest pass (e.g. after the camera moved).
+    pub fn reset(&mut self) {
+        *self = Self::new(self.start_block);
+    }
+
+    /// Was (x, y) already rendered by a coarser pass?
+    #[inline]
+    fn covered(&self, x: u32, y: u32) -> bool {
+        let coarse = self.block * 2;
+        self.block < self.start_block && x.is_multiple_of(coarse) && y.is_multiple_of(coarse)
+    }
+
+    /// Completion in [0, 1] for an image of `pixel_count` pixels.
+    pub fn fraction(&self, pixel_count: u32) -> f64 {
+        if self.done || pixel_count == 0 {
+            1.0
+        } else {
+            (self.rays_done as f64 / pixel_count as f64).min(1.0)
+        }
+    }
+
+    /// Deterministic frame step: spend `ms_budget` at a fixed cost of
+    /// `rays_per_ms`, independent of actual wall time.
+    ///
+    /// Returns the completion fraction afterwards.
+    pub fn advance(
+        &mut self,
+        params: &RenderParams,
+        formula: &HybridFormula,
+        gbuffer: &mut [SiLight5],
+        ms_budget: f64,
+        rays_per_ms: f64,
+    ) -> f64 {
+        let budget = self.budget_carry + ms_budget.max(0.0) * rays_per_ms.max(0.0);
+        let rays = budget.floor();
+        self.budget_carry = budget - rays;
+        self.step(params, formula, gbuffer, rays.min(u32::MAX as f64) as u32);
+        self.fraction(params.width * params.height)
+    }
+
+    /// Render up to `max_pixels` rays, writing into `gbuffer`.
+    ///
+    /// Returns the number of rays marched; check `done` afterwards.
+    pub fn step(
+        &mut self,
+        params: &RenderParams,
+        formula: &HybridFormula,
+        gbuffer: &mut [SiLight5],
+        max_pixels: u32,
+    ) -> u32 {
+        let (w, h) = (params.width, params.height);
+        let mut rendered = 0;
+
+        while !self.done && rendered < max_pixels {
+            if self.row >= h {
+                // Pass finished — refine
+                if self.block == 1 {
+                    self.done = true;
+                    break;
+                }
+                self.block /= 2;
+                self.row = 0;
+                self.col = 0;
+                continue;
+            }
+
+            let (x, y, b) = (self.col, self.row, self.block);
+            if !self.covered(x, y) {
+                let entry = raymarcher::render_pixel(params, formula, x, y);
+                // Fill the block this sample stands for
+                for fy in y..(y + b).min(h) {
+                    for fx in x..(x + b).min(w) {
+                        if let Some(px) = gbuffer.get_mut((fy * w + fx) as usize) {
+                            *px = entry;
+                        }
+                    }
+                }
+                rendered += 1;
+                self.rays_done += 1;
+            }
+
+            self.col += b;
+            if self.col >= w {
+                self.col = 0;
+                self.row += b;
+            }
+        }
+
+        rendered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formulas::{hybrid::HybridMode, FormulaId};
+
+    #[test]
+    fn test_progressive_matches_full_render() {
+        let params = RenderParams { width: 9, height: 7, ..Default::default() };
+        let formula = HybridFormula::new(&[(FormulaId::MandelbulbPower8, 1)], HybridMode::Alternating, 8, 16.0);
+
+        let mut full = vec![SiLight5::default(); 63];
+        raymarcher::render_scanlines(&params, &formula, &mut full, 0, 1);
+
+        let mut progressive = vec![SiLight5::default(); 63];
+        let mut state = ProgressiveState::new(4);
+        let mut total = 0;
+        while !state.done {
+            total += state.step(&params, &formula, &mut progressive, 5);
+        }
+
+        // Every pixel is marched exactly once across all passes
+        assert_eq!(total, 63);
+        for (a, b) in full.iter().zip(&progressive) {
+            assert_eq!(
+                (a.z_pos, a.sn_x, a.sn_y, a.sn_z),
+                (b.z_pos, b.sn_x, b.sn_y, b.sn_z)
+            );
+        }
+    }
+
+    #[test]
+    fn test_advance_is_deterministic_and_reaches_one() {
+        let params = RenderParams { width: 8, height: 8, ..Default::default() };
+        let formula = HybridFormula::new(&[(FormulaId::MandelbulbPower8, 1)], HybridMode::Alternating, 8, 16.0);
+        let run = || {
+            let mut gbuffer = vec![SiLight5::default(); 64];
+            let mut state = ProgressiveState::new(4);
+            let mut fractions = Vec::new();
+            while fractions.last() != Some(&1.0) {
+                // 2.5 rays per frame: carried budget yields 2, 3, 2, 3, ...
+                fractions.push(state.advance(&params, &formula, &mut gbuffer, 16.0, 2.5 / 16.0));
+            }
+            fractions
+        };
+        let a = run();
+        assert_eq!(a, run());
+        assert_eq!(a[0], 2.0 / 64.0);
+        assert_eq!(a[1], 5.0 / 64.0);
+        assert!(a.windows(2).all(|w| w[0] <= w[1]));
+    }
+}
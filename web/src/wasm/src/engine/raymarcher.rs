@@ -7,11 +7,44 @@
 /// - Dynamic fog accumulation
 /// - Cutting plane support
 /// - G-buffer output (SiLight5 packed format)
+/// - Optional reflection and transmission (refraction) layers
 
+use crate::engine::antialias::AaSettings;
+use crate::engine::ao::{self, AoSettings};
+use crate::engine::bounds;
+use crate::engine::cutting::{self, Cut, CutShape, CutSkip};
+use crate::engine::montecarlo::McSettings;
+use crate::engine::prepass::DepthPrepass;
+use crate::engine::refraction::{self, RefractionSettings};
+use crate::engine::reproject;
+use crate::engine::stereo::StereoSettings;
+use crate::engine::volumetric::VolumeSettings;
+use crate::engine::zones::{self, MaterialZone};
 use crate::engine::types::*;
 use crate::math::math3d;
+use crate::math::strict;
 use crate::math::utils;
 use crate::formulas::hybrid::HybridFormula;
+use crate::formulas::{DistanceField, FormulaResult};
+
+/// What miss pixels store besides the `z_pos = 65535` sentinel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MissEncoding {
+    /// Nothing (flat background)
+    Sentinel,
+    /// Closest approach to the surface in pixel widths (for glow/halo painting)
+    ClosestApproach,
+}
+
+/// Step strategy of the sphere tracer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MarchMode {
+    /// MB3D's RSFmul step regulation
+    Regulated,
+    /// Enhanced sphere tracing: steps of `omega` × DE; each detected
+    /// overshoot retreats and halves the over-relaxation for the rest of the ray
+    OverRelaxed { omega: f64 },
+}
 
 /// Complete render parameters deserialized from the JS side.
 #[derive(Clone)]
@@ -34,19 +67,47 @@ pub struct RenderParams {
     pub max_ray_length: f64,
     /// Maximum fractal iterations
     pub max_iterations: u32,
+    /// Maximum ray-march steps per ray
+    pub max_steps: u32,
+    /// Ray-march steps allowed per frame over all pixels (0 = unlimited)
+    pub step_budget: f64,
+    /// Sphere tracing step strategy
+    pub march_mode: MarchMode,
+    /// Background data stored for miss pixels
+    pub miss_encoding: MissEncoding,
+    /// Cell size in pixels of the depth pre-pass (see `prepass`)
+    pub prepass_block: u32,
+    /// Automatic detail level in pixels; when > 0, `params_from_buffer`
+    /// replaces `de_stop` with `auto_de_stop(auto_detail)`
+    pub auto_detail: f64,
     /// Bailout radius squared
     pub bailout: f64,
-    /// FOV factor for distance-dependent DE scaling
-    pub fov_factor: f64,
-    /// Julia mode
-    pub julia: bool,
-    pub julia_c: Vec3D,
-    /// Cutting plane
-    pub cut_enabled: bool,
-    pub cut_normal: Vec3D,
-    pub cut_d: f64,
+    /// Hit threshold in pixel-footprint radii (0 = fixed `de_stop`)
+    pub cone_scale: f64,
+    /// Cutting planes and volumes; removed regions are jumped over
+    pub cuts: Vec<Cut>,
+    /// Volume known to contain the fractal; rays are clipped to it
+    pub bounds: Option<CutShape>,
+    /// Material ids by orbit-trap or iteration range (first match wins)
+    pub material_zones: Vec<MaterialZone>,
+    /// Curvature probe radius in hit thresholds (0 = no curvature channel)
+    pub curvature_radius: f64,
+    /// G-buffer record layout written by the wasm `render_scanlines`
+    pub gbuffer_format: GBufferFormat,
     /// Binary search refinement steps
     pub bin_search_steps: u32,
+    /// Hemispheric DE-sampled AO (disabled = legacy step-count ambient)
+    pub ao: AoSettings,
+    /// Transparent material settings (used when a transmission layer is rendered)
+    pub refraction: RefractionSettings,
+    /// Monte Carlo render mode settings
+    pub mc: McSettings,
+    /// Adaptive edge antialiasing settings
+    pub aa: AaSettings,
+    /// Stereo pair settings (used by the stereo render entry points)
+    pub stereo: StereoSettings,
+    /// Density integration settings for the volumetric mode
+    pub volume: VolumeSettings,
 }
 
 impl Default for RenderParams {
@@ -62,16 +123,84 @@ impl Default for RenderParams {
             step_width: 0.8,
             max_ray_length: 50.0,
             max_iterations: 12,
+            max_steps: 8000,
+            step_budget: 0.0,
+            march_mode: MarchMode::Regulated,
+            miss_encoding: MissEncoding::Sentinel,
+            prepass_block: 4,
+            auto_detail: 0.0,
             bailout: 16.0,
-            fov_factor: 0.0,
-            julia: false,
-            julia_c: Vec3D::default(),
-            cut_enabled: false,
-            cut_normal: Vec3D { x: 0.0, y: 1.0, z: 0.0 },
-            cut_d: 0.0,
+            cone_scale: 0.0,
+            cuts: Vec::new(),
+            bounds: None,
+            material_zones: Vec::new(),
+            curvature_radius: 0.0,
+            gbuffer_format: GBufferFormat::Packed,
             bin_search_steps: 3,
+            ao: AoSettings::default(),
+            refraction: RefractionSettings::default(),
+            mc: McSettings::default(),
+            aa: AaSettings::default(),
+            stereo: StereoSettings::default(),
+            volume: VolumeSettings::default(),
+        }
+    }
+}
+
+impl RenderParams {
+    /// Radius of a pixel's view cone at distance `t` along the ray.
+    ///
+    /// Adjacent pixel rays differ by `ray_dx / (width / 2)` before
+    /// normalization, so the cone half-angle follows from resolution and FOV.
+    pub fn pixel_footprint(&self, t: f64) -> f64 {
+        let base = math3d::vec3d_length(&self.ray_dir_base);
+        if base <= 0.0 || self.width == 0 || self.height == 0 {
+            return 0.0;
         }
+        let per_px_x = math3d::vec3d_length(&self.ray_dx) / (self.width as f64 * 0.5);
+        let per_px_y = math3d::vec3d_length(&self.ray_dy) / (self.height as f64 * 0.5);
+        0.5 * t * per_px_x.max(per_px_y) / base
+    }
+
+    /// `de_stop` matching MB3D's automatic detail level: `detail` pixels on
+    /// the focus plane through the origin (where the fractal is centered).
+    ///
+    /// Falls back to `de_stop` when the camera sits at the origin.
+    pub fn auto_de_stop(&self, detail: f64) -> f64 {
+        let focus = math3d::vec3d_length(&self.camera_pos);
+        let pixel = 2.0 * self.pixel_footprint(focus);
+        if pixel > 0.0 { detail * pixel } else { self.de_stop }
+    }
+
+    /// Length of a buffer with `channels` values per pixel, or None when it
+    /// overflows or the pixels do not fit the u32 indices (as in
+    /// `CameraDescription::rgba_len`).
+    pub fn buffer_len(&self, channels: usize) -> Option<usize> {
+        (self.width as usize)
+            .checked_mul(self.height as usize)
+            .filter(|&pixels| pixels <= u32::MAX as usize)
+            .and_then(|pixels| pixels.checked_mul(channels))
+    }
+
+    /// Surface hit threshold at distance `t`: the pixel footprint scaled by
+    /// `cone_scale`, but never below `de_stop`.
+    pub fn hit_threshold(&self, t: f64) -> f64 {
+        if self.cone_scale > 0.0 {
+            self.de_stop.max(self.cone_scale * self.pixel_footprint(t))
+        } else {
+            self.de_stop
+        }
+    }
+}
+
+/// Automatic `de_stop` from MB3D-style view settings: `detail` times the
+/// size of one pixel on the focus plane at distance `1 / zoom`, for an image
+/// `width` pixels wide with horizontal field of view `fov` (radians).
+pub fn auto_de_stop(zoom: f64, fov: f64, width: u32, detail: f64) -> f64 {
+    if zoom <= 0.0 || width == 0 {
+        return 0.0;
     }
+    detail * 2.0 * strict::tan(fov * 0.5) / (zoom * width as f64)
 }
 
 /// Result of a single ray march.
@@ -85,82 +214,228 @@ pub struct RayMarchResult {
     pub normal: Vec3D,
     /// Smooth iteration value for coloring
     pub smooth_iteration: f64,
+    /// The hit sample did not escape (interior of the set)
+    pub inside: bool,
     /// Orbit trap value
     pub orbit_trap: f64,
+    /// Second (point) orbit trap value
+    pub orbit_trap2: f64,
     /// Number of ray marching steps taken (for ambient occlusion)
     pub steps: u32,
     /// Dynamic fog accumulation
     pub fog: f64,
     /// Hit position in world space
     pub hit_pos: Vec3D,
+    /// Smallest DE along the ray measured in pixel widths at that distance
+    pub closest_approach: f64,
+    /// Distance estimator evaluations, including refinement and the normal
+    pub de_evals: u32,
 }
 
 /// March a single ray using sphere tracing with adaptive step regulation.
 ///
 /// Full port of the MandCalc procedure from CalcThread.pas.
-pub fn march_ray(
+pub fn march_ray<F: DistanceField + ?Sized>(
     origin: &Vec3D,
     direction: &Vec3D,
     params: &RenderParams,
-    formula: &HybridFormula,
+    formula: &F,
+) -> RayMarchResult {
+    march_ray_limited(origin, direction, params, formula, params.max_steps)
+}
+
+/// Like `march_ray` with an explicit step limit (e.g. from a `StepBudget`).
+///
+/// A ray that runs out of steps counts as a miss.
+pub fn march_ray_limited<F: DistanceField + ?Sized>(
+    origin: &Vec3D,
+    direction: &Vec3D,
+    params: &RenderParams,
+    formula: &F,
+    max_steps: u32,
+) -> RayMarchResult {
+    march_ray_from(origin, direction, params, formula, max_steps, 0.0)
+}
+
+/// Like `march_ray_limited`, skipping the first `start` units of the ray
+/// (known to be empty, e.g. from a depth pre-pass).
+pub fn march_ray_from<F: DistanceField + ?Sized>(
+    origin: &Vec3D,
+    direction: &Vec3D,
+    params: &RenderParams,
+    formula: &F,
+    max_steps: u32,
+    start: f64,
+) -> RayMarchResult {
+    march_core(origin, direction, params, formula, max_steps, start, None)
+}
+
+/// One recorded ray-march step (see `march_ray_traced`).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MarchStep {
+    /// Distance along the ray
+    pub t: f64,
+    /// Raw distance estimate there
+    pub de: f64,
+}
+
+/// Like `march_ray`, recording every DE evaluation into `trace` (for diagnostics).
+pub fn march_ray_traced<F: DistanceField + ?Sized>(
+    origin: &Vec3D,
+    direction: &Vec3D,
+    params: &RenderParams,
+    formula: &F,
+    trace: &mut Vec<MarchStep>,
+) -> RayMarchResult {
+    march_core(origin, direction, params, formula, params.max_steps, 0.0, Some(trace))
+}
+
+fn march_core<F: DistanceField + ?Sized>(
+    origin: &Vec3D,
+    direction: &Vec3D,
+    params: &RenderParams,
+    formula: &F,
+    max_steps: u32,
+    start: f64,
+    mut trace: Option<&mut Vec<MarchStep>>,
 ) -> RayMarchResult {
-    let mut result = RayMarchResult::default();
-    let mut pos = *origin;
-    let mut total_dist = 0.0f64;
-    let max_steps = 8000u32;
+    let mut result = RayMarchResult { closest_approach: f64::INFINITY, ..Default::default() };
+    let mut total_dist = start.max(0.0);
+    let mut pos = Vec3D {
+        x: origin.x + direction.x * total_dist,
+        y: origin.y + direction.y * total_dist,
+        z: origin.z + direction.z * total_dist,
+    };
 
     // Adaptive step regulation state (port of RSFmul from CalcThread.pas)
     let mut last_de = f64::MAX;
     let mut last_step = 0.0f64;
     let mut rsf_mul = 1.0f64; // Step regulation factor
 
+    // Over-relaxation factor (decays toward 1 with each overshoot)
+    let mut omega = match params.march_mode {
+        MarchMode::Regulated => 1.0,
+        MarchMode::OverRelaxed { omega } => omega.max(1.0),
+    };
+
     // Dynamic fog accumulation
     let mut fog_accum = 0.0f64;
 
-    let julia_c = if params.julia { Some(&params.julia_c) } else { None };
+    // Retries left after a non-finite DE, and the step width factor while
+    // recovering from one (restored gradually by the steps that follow)
+    let mut retries = MAX_DE_RETRIES;
+    let mut recover = 1.0f64;
+
+    // Pixel width per unit of ray distance, for the closest approach
+    let pixel_width = params.pixel_footprint(1.0) * 2.0;
+
+    // Start at the bounding volume and stop at its far side
+    let mut ray_end = params.max_ray_length;
+    if let Some(bounds) = &params.bounds {
+        match bounds::clip_ray(bounds, origin, direction, params.max_ray_length) {
+            Some((enter, end)) => {
+                if enter > total_dist {
+                    pos.x += direction.x * (enter - total_dist);
+                    pos.y += direction.y * (enter - total_dist);
+                    pos.z += direction.z * (enter - total_dist);
+                    total_dist = enter;
+                }
+                ray_end = end;
+            }
+            None => return result,
+        }
+    }
+
 
     for step in 0..max_steps {
-        // Check cutting plane
-        if params.cut_enabled {
-            let plane_dist = math3d::vec3d_dot(&pos, &params.cut_normal) - params.cut_d;
-            if plane_dist < 0.0 {
-                // Behind the cutting plane — skip forward
-                let cos_angle = math3d::vec3d_dot(direction, &params.cut_normal);
-                if cos_angle.abs() > 1e-10 {
-                    let t = -plane_dist / cos_angle;
-                    if t > 0.0 {
-                        pos.x += direction.x * t;
-                        pos.y += direction.y * t;
-                        pos.z += direction.z * t;
-                        total_dist += t;
-                    }
+        // Jump over regions removed by the cuts
+        if !params.cuts.is_empty() {
+            match cutting::skip_removed(&params.cuts, &pos, direction, params.de_stop * 1e-3) {
+                CutSkip::Keep => {}
+                CutSkip::Advance(t) => {
+                    pos.x += direction.x * t;
+                    pos.y += direction.y * t;
+                    pos.z += direction.z * t;
+                    total_dist += t;
+                }
+                CutSkip::Miss => {
+                    result.total_distance = total_dist;
+                    result.steps = step;
+                    result.fog = fog_accum;
+                    return result;
                 }
             }
         }
 
-        // Distance-dependent DE threshold (like FOV-scaled DEstop in MB3D)
-        let de_threshold = if params.fov_factor > 0.0 {
-            params.de_stop * (1.0 + total_dist * params.fov_factor)
-        } else {
-            params.de_stop
-        };
+        // Pixel-cone DE threshold: detail stops at the pixel footprint
+        let de_threshold = params.hit_threshold(total_dist);
 
         // Evaluate the distance estimator at current position
-        let fr = formula.compute_de(&pos, julia_c);
+        let mut fr = formula.compute_de(&pos);
+        result.de_evals += 1;
+        if !fr.de.is_finite() {
+            // Singular point (NaN/Inf from the formula): bound the DE from
+            // the neighbourhood, or re-sample closer to the last good point
+            result.de_evals += 6;
+            match fallback_de(&pos, de_threshold, formula) {
+                Some(bounded) => fr = bounded,
+                None if retries > 0 && step > 0 => {
+                    retries -= 1;
+                    let back = last_step * 0.5;
+                    pos.x -= direction.x * back;
+                    pos.y -= direction.y * back;
+                    pos.z -= direction.z * back;
+                    total_dist -= back;
+                    last_step -= back;
+                    recover *= 0.5;
+                    continue;
+                }
+                None => {}
+            }
+        }
+        if let Some(trace) = trace.as_deref_mut() {
+            trace.push(MarchStep { t: total_dist, de: fr.de });
+        }
 
         let mut de = fr.de;
+        if total_dist > 0.0 && pixel_width > 0.0 {
+            result.closest_approach = result.closest_approach.min(de / (total_dist * pixel_width));
+        }
 
-        // Adaptive step regulation — port from CalcThread.pas MandCalc
-        // Prevents overstepping by capping the step based on previous DE estimate
-        if step > 0 {
-            let max_allowed = last_de + last_step;
-            if de > max_allowed {
-                de = max_allowed;
-                // Reduce the regulation factor when DE jumps
-                rsf_mul = (rsf_mul * 0.9).max(0.5);
-            } else {
-                // Slowly restore regulation factor
-                rsf_mul = (rsf_mul * 1.01).min(1.0);
+        match params.march_mode {
+            // Adaptive step regulation — port from CalcThread.pas MandCalc
+            // Prevents overstepping by capping the step based on previous DE estimate
+            MarchMode::Regulated => {
+                if step > 0 {
+                    let max_allowed = last_de + last_step;
+                    if de > max_allowed {
+                        de = max_allowed;
+                        // Reduce the regulation factor when DE jumps
+                        rsf_mul = (rsf_mul * 0.9).max(0.5);
+                    } else {
+                        // Slowly restore regulation factor
+                        rsf_mul = (rsf_mul * 1.01).min(1.0);
+                    }
+                }
+            }
+            // If the unbounding spheres of the last two points do not overlap,
+            // the over-relaxed step may have skipped the surface: go back to a
+            // plain step from the previous point and relax less from now on
+            MarchMode::OverRelaxed { .. } => {
+                let safe_last = last_de * params.step_width;
+                if omega > 1.0 && step > 0 && de * params.step_width + safe_last < last_step {
+                    let back = last_step - safe_last;
+                    pos.x -= direction.x * back;
+                    pos.y -= direction.y * back;
+                    pos.z -= direction.z * back;
+                    total_dist -= back;
+                    last_step = safe_last;
+                    omega = 1.0 + (omega - 1.0) * 0.5;
+                    if omega < 1.05 {
+                        omega = 1.0;
+                    }
+                    continue;
+                }
             }
         }
 
@@ -170,7 +445,9 @@ pub fn march_ray(
             result.total_distance = total_dist;
             result.hit_pos = pos;
             result.smooth_iteration = fr.smooth_it;
+            result.inside = fr.inside;
             result.orbit_trap = fr.orbit_trap;
+            result.orbit_trap2 = fr.orbit_trap2;
             result.steps = step;
             result.fog = fog_accum;
 
@@ -180,19 +457,21 @@ pub fn march_ray(
                     &mut result.hit_pos,
                     direction,
                     &last_step,
+                    de_threshold,
                     params,
                     formula,
                 );
             }
 
             // Calculate surface normal via central differences
-            result.normal = calculate_normal(&result.hit_pos, params, formula);
+            result.normal = calculate_normal(&result.hit_pos, total_dist, params, formula);
+            result.de_evals += params.bin_search_steps + 6;
 
             return result;
         }
 
         // Check if we exceeded maximum ray length
-        if total_dist > params.max_ray_length || de.is_nan() || de.is_infinite() {
+        if total_dist > ray_end || de.is_nan() || de.is_infinite() {
             result.hit = false;
             result.total_distance = total_dist;
             result.steps = step;
@@ -200,8 +479,8 @@ pub fn march_ray(
             return result;
         }
 
-        // Compute step size with regulation
-        let step_size = de * params.step_width * rsf_mul;
+        // Compute step size with regulation (rsf_mul and omega are 1 in the other mode)
+        let step_size = de * params.step_width * rsf_mul * omega * recover;
 
         // Advance along the ray
         pos.x += direction.x * step_size;
@@ -212,6 +491,7 @@ pub fn march_ray(
         // Update regulation state
         last_de = de;
         last_step = step_size;
+        recover = (recover * 1.25).min(1.0);
 
         // Accumulate fog (based on proximity to surface)
         fog_accum += 1.0 / (1.0 + de * de * 100.0);
@@ -222,16 +502,35 @@ pub fn march_ray(
     result
 }
 
+/// Non-finite DE samples a ray may re-sample around before it gives up.
+const MAX_DE_RETRIES: u32 = 4;
+
+/// Numerical fallback for a NaN/Inf distance estimate at `pos`.
+///
+/// DEs are 1-Lipschitz, so a finite estimate `d` at distance `eps` is a
+/// valid (conservative) `d - eps` here. Samples the six axis neighbours at
+/// `eps` and keeps the tightest bound, with that neighbour's coloring data.
+fn fallback_de<F: DistanceField + ?Sized>(pos: &Vec3D, eps: f64, formula: &F) -> Option<FormulaResult> {
+    let eps = eps.max(1e-12);
+    let offsets = [(eps, 0.0, 0.0), (-eps, 0.0, 0.0), (0.0, eps, 0.0), (0.0, -eps, 0.0), (0.0, 0.0, eps), (0.0, 0.0, -eps)];
+    offsets
+        .iter()
+        .map(|&(dx, dy, dz)| formula.compute_de(&Vec3D { x: pos.x + dx, y: pos.y + dy, z: pos.z + dz }))
+        .filter(|fr| fr.de.is_finite())
+        .min_by(|a, b| a.de.total_cmp(&b.de))
+        .map(|fr| FormulaResult { de: (fr.de - eps).max(0.0), ..fr })
+}
+
 /// Binary search refinement — port of RMdoBinSearch from CalcThread.pas.
 /// Refines the hit position by binary searching along the last step.
-fn binary_search_refine(
+fn binary_search_refine<F: DistanceField + ?Sized>(
     hit_pos: &mut Vec3D,
     direction: &Vec3D,
     last_step: &f64,
+    threshold: f64,
     params: &RenderParams,
-    formula: &HybridFormula,
+    formula: &F,
 ) {
-    let julia_c = if params.julia { Some(&params.julia_c) } else { None };
     let mut step = *last_step;
     let mut pos = *hit_pos;
 
@@ -247,8 +546,8 @@ fn binary_search_refine(
             y: pos.y + direction.y * step,
             z: pos.z + direction.z * step,
         };
-        let fr = formula.compute_de(&test_pos, julia_c);
-        if fr.de < params.de_stop {
+        let fr = formula.compute_de(&test_pos);
+        if fr.de < threshold {
             // Still hitting — don't move forward
         } else {
             // Not hitting — move forward
@@ -266,38 +565,209 @@ fn binary_search_refine(
 
 /// Calculate surface normal via central differences on the DE function.
 ///
-/// Port of RMCalculateNormals from CalcThread.pas.
-fn calculate_normal(
+/// Port of RMCalculateNormals from CalcThread.pas. The epsilon is half the
+/// hit threshold at distance `t`, so normals are as smooth as a pixel.
+pub(crate) fn calculate_normal<F: DistanceField + ?Sized>(
     pos: &Vec3D,
+    t: f64,
     params: &RenderParams,
-    formula: &HybridFormula,
+    formula: &F,
 ) -> Vec3D {
-    let eps = params.de_stop * 0.5;
-    let julia_c = if params.julia { Some(&params.julia_c) } else { None };
-
-    let dx = formula.compute_de(
-        &Vec3D { x: pos.x + eps, y: pos.y, z: pos.z }, julia_c,
-    ).de - formula.compute_de(
-        &Vec3D { x: pos.x - eps, y: pos.y, z: pos.z }, julia_c,
-    ).de;
-
-    let dy = formula.compute_de(
-        &Vec3D { x: pos.x, y: pos.y + eps, z: pos.z }, julia_c,
-    ).de - formula.compute_de(
-        &Vec3D { x: pos.x, y: pos.y - eps, z: pos.z }, julia_c,
-    ).de;
-
-    let dz = formula.compute_de(
-        &Vec3D { x: pos.x, y: pos.y, z: pos.z + eps }, julia_c,
-    ).de - formula.compute_de(
-        &Vec3D { x: pos.x, y: pos.y, z: pos.z - eps }, julia_c,
-    ).de;
-
-    let mut normal = Vec3D { x: dx, y: dy, z: dz };
+    let eps = params.hit_threshold(t) * 0.5;
+    let at = |dx: f64, dy: f64, dz: f64| Vec3D { x: pos.x + dx, y: pos.y + dy, z: pos.z + dz };
+
+    let samples = [
+        at(eps, 0.0, 0.0), at(-eps, 0.0, 0.0),
+        at(0.0, eps, 0.0), at(0.0, -eps, 0.0),
+        at(0.0, 0.0, eps), at(0.0, 0.0, -eps),
+    ];
+    let mut de = [0.0; 6];
+    formula.compute_de_batch(&samples, &mut de);
+
+    let mut normal = Vec3D { x: de[0] - de[1], y: de[2] - de[3], z: de[4] - de[5] };
     math3d::vec3d_normalize(&mut normal);
     normal
 }
 
+/// Normalized surface curvature at a hit, in [-1, 1].
+///
+/// The Laplacian of the distance field is the divergence of the normal, so
+/// half of it is the mean curvature. It is measured over a probe radius of
+/// `curvature_radius` hit thresholds and scaled by that radius, so features
+/// about the probe size saturate and flat areas read 0. Convex ridges are
+/// positive, crevices negative.
+pub fn curvature_at<F: DistanceField + ?Sized>(pos: &Vec3D, t: f64, params: &RenderParams, formula: &F) -> f64 {
+    let r = params.hit_threshold(t) * params.curvature_radius;
+    if r <= 0.0 {
+        return 0.0;
+    }
+    let at = |dx: f64, dy: f64, dz: f64| Vec3D { x: pos.x + dx, y: pos.y + dy, z: pos.z + dz };
+    let samples = [
+        *pos,
+        at(r, 0.0, 0.0), at(-r, 0.0, 0.0),
+        at(0.0, r, 0.0), at(0.0, -r, 0.0),
+        at(0.0, 0.0, r), at(0.0, 0.0, -r),
+    ];
+    let mut de = [0.0; 7];
+    formula.compute_de_batch(&samples, &mut de);
+    let laplacian = (de[1..].iter().sum::<f64>() - 6.0 * de[0]) / (r * r);
+    (0.5 * laplacian * r).tanh()
+}
+
+/// Normal of the distance field at an arbitrary world position.
+///
+/// The differencing epsilon is the hit threshold at the point's distance
+/// from the camera, so probes agree with the normals of rendered pixels.
+pub fn normal_at_point<F: DistanceField + ?Sized>(params: &RenderParams, formula: &F, pos: &Vec3D) -> Vec3D {
+    let t = math3d::vec3d_length(&math3d::vec3d_sub(pos, &params.camera_pos));
+    calculate_normal(pos, t, params, formula)
+}
+
+/// G-buffer entry for pixels whose ray missed the surface.
+const MISS_PIXEL: SiLight5 = SiLight5 {
+    sn_x: 0,
+    sn_y: 0,
+    sn_z: 0,
+    z_pos: 65535,
+    shadow: 0,
+    ambient: 0,
+    color_gradient: 0,
+    orbit_trap: 0,
+    roughness: 0,
+};
+
+/// Pack a march result into a G-buffer entry.
+pub(crate) fn gbuffer_entry<F: DistanceField + ?Sized>(mr: &RayMarchResult, params: &RenderParams, formula: &F) -> SiLight5 {
+    if !mr.hit {
+        let mut entry = MISS_PIXEL;
+        if params.miss_encoding == MissEncoding::ClosestApproach {
+            entry.set_miss_distance(mr.closest_approach);
+        }
+        return entry;
+    }
+    let ambient = if params.ao.enabled() {
+        ao::hemisphere_ao(&mr.hit_pos, &mr.normal, formula, &params.ao)
+    } else {
+        (mr.steps as f64 / 200.0).min(1.0)
+    };
+    let mut entry = SiLight5 {
+        sn_x: utils::min_max_clip_15bit(mr.normal.x),
+        sn_y: utils::min_max_clip_15bit(mr.normal.y),
+        sn_z: utils::min_max_clip_15bit(mr.normal.z),
+        z_pos: utils::min_max_clip_16bit(
+            utils::clamp(mr.total_distance / params.max_ray_length, 0.0, 1.0)
+        ),
+        shadow: 0,
+        ambient: utils::min_max_clip_16bit(ambient),
+        color_gradient: 0,
+        orbit_trap: utils::min_max_clip_16bit(
+            utils::clamp(1.0 - mr.orbit_trap.min(1.0), 0.0, 1.0)
+        ),
+        roughness: 0,
+    };
+    entry.set_gradient((mr.smooth_iteration % 256.0) / 256.0, mr.inside);
+    if params.curvature_radius > 0.0 {
+        entry.set_curvature(curvature_at(&mr.hit_pos, mr.total_distance, params, formula));
+    }
+    let trap = entry.orbit_trap as f64 / 65535.0;
+    if let Some(material) = zones::material_for(&params.material_zones, trap, mr.smooth_iteration) {
+        entry.set_material_id(material);
+    }
+    entry
+}
+
+/// Extend a packed entry with the full-precision data of its march result.
+pub(crate) fn extended_entry(entry: SiLight5, mr: &RayMarchResult) -> SiLight6 {
+    if !mr.hit {
+        return SiLight6 { base: entry, ..Default::default() };
+    }
+    let p = mr.hit_pos;
+    SiLight6 {
+        base: entry,
+        position: [p.x as f32, p.y as f32, p.z as f32],
+        // Orbit radii rarely drop below 1 on the surface, so map r / (1 + r)
+        orbit_trap2: utils::min_max_clip_16bit(mr.orbit_trap2.max(0.0) / (1.0 + mr.orbit_trap2.max(0.0))),
+        material: entry.material_id(),
+        flags: 0,
+    }
+}
+
+/// March one reflection bounce from a primary hit.
+///
+/// The secondary ray starts slightly above the surface so it does not
+/// immediately re-hit the point it left.
+pub fn march_reflection(
+    primary: &RayMarchResult,
+    direction: &Vec3D,
+    params: &RenderParams,
+    formula: &HybridFormula,
+) -> RayMarchResult {
+    let n = &primary.normal;
+    let reflected = math3d::vec3d_normalized(&math3d::vec3d_reflect(direction, n));
+    let origin = math3d::vec3d_add(&primary.hit_pos, &math3d::vec3d_scale(n, params.de_stop * 4.0));
+    march_ray(&origin, &reflected, params, formula)
+}
+
+/// Normalized view ray direction through (fractional) pixel coordinates.
+pub fn pixel_direction(params: &RenderParams, fx: f64, fy: f64) -> Vec3D {
+    let hw = params.width as f64 * 0.5;
+    let hh = params.height as f64 * 0.5;
+    let px = (fx - hw) / hw;
+    let py = (fy - hh) / hh;
+    let mut dir = Vec3D {
+        x: params.ray_dir_base.x + px * params.ray_dx.x + py * params.ray_dy.x,
+        y: params.ray_dir_base.y + px * params.ray_dx.y + py * params.ray_dy.y,
+        z: params.ray_dir_base.z + px * params.ray_dx.z + py * params.ray_dy.z,
+    };
+    math3d::vec3d_normalize(&mut dir);
+    dir
+}
+
+/// March the primary ray of pixel (x, y) and pack it into a G-buffer entry.
+pub fn render_pixel<F: DistanceField + ?Sized>(params: &RenderParams, formula: &F, x: u32, y: u32) -> SiLight5 {
+    render_subpixel(params, formula, x as f64, y as f64)
+}
+
+/// Like `render_pixel` at fractional pixel coordinates (for supersampling).
+pub fn render_subpixel<F: DistanceField + ?Sized>(params: &RenderParams, formula: &F, fx: f64, fy: f64) -> SiLight5 {
+    let dir = pixel_direction(params, fx, fy);
+    gbuffer_entry(&march_ray(&params.camera_pos, &dir, params, formula), params, formula)
+}
+
+/// Full-precision surface query under a pixel, for click-to-center and
+/// picking a Julia seed from the surface.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PixelPick {
+    /// The ray hit the fractal (otherwise all other fields are zero)
+    pub hit: bool,
+    /// World-space hit position
+    pub position: Vec3D,
+    /// Distance from the camera along the ray
+    pub distance: f64,
+    /// Surface normal at the hit
+    pub normal: Vec3D,
+    /// Distance estimate at the hit position
+    pub de: f64,
+}
+
+/// March the ray through (fractional) pixel coordinates and report the hit
+/// without the quantization of the G-buffer.
+pub fn pick_pixel<F: DistanceField + ?Sized>(params: &RenderParams, formula: &F, fx: f64, fy: f64) -> PixelPick {
+    let dir = pixel_direction(params, fx, fy);
+    let result = march_ray(&params.camera_pos, &dir, params, formula);
+    if !result.hit {
+        return PixelPick::default();
+    }
+    PixelPick {
+        hit: true,
+        position: result.hit_pos,
+        // Measured to the refined hit, not the last march step
+        distance: math3d::vec3d_length(&math3d::vec3d_sub(&result.hit_pos, &params.camera_pos)),
+        normal: result.normal,
+        de: formula.compute_de(&result.hit_pos).de,
+    }
+}
+
 /// Render a complete image region (set of scanlines).
 ///
 /// This is the main entry point called from WASM, rendering interleaved
@@ -309,72 +779,358 @@ pub fn render_scanlines(
     worker_id: u32,
     worker_count: u32,
 ) -> u32 {
+    render_scanlines_layers(params, formula, gbuffer, GBufferLayers::default(), worker_id, worker_count)
+}
+
+/// Render the rectangle [x0, x1) × [y0, y1) into a full-frame G-buffer.
+///
+/// The rectangle is clipped to the image. Returns the number of pixels rendered.
+pub fn render_tile(
+    params: &RenderParams,
+    formula: &HybridFormula,
+    gbuffer: &mut [SiLight5],
+    x0: u32,
+    y0: u32,
+    x1: u32,
+    y1: u32,
+) -> u32 {
+    let (x1, y1) = (x1.min(params.width), y1.min(params.height));
+    let mut rendered = 0;
+    for y in y0..y1 {
+        for x in x0..x1 {
+            let idx = (y * params.width + x) as usize;
+            if idx < gbuffer.len() {
+                gbuffer[idx] = render_pixel(params, formula, x, y);
+                rendered += 1;
+            }
+        }
+    }
+    rendered
+}
+
+/// Optional secondary G-buffer layers, each the same size as the primary one.
+#[derive(Default)]
+pub struct GBufferLayers<'a> {
+    /// One reflection bounce per hit (misses stay background)
+    pub reflect: Option<&'a mut [SiLight5]>,
+    /// What is seen through a transparent surface; `ambient` holds the
+    /// interior path length (see `SiLight5::interior_path`) instead of AO
+    pub transmit: Option<&'a mut [SiLight5]>,
+    /// Input: start depths from a depth pre-pass
+    pub prepass: Option<DepthPrepass<'a>>,
+    /// Input: per-pixel start depths reprojected from the previous frame
+    /// (0 = unknown; validated before use, see `reproject`)
+    pub start_depths: Option<&'a [f32]>,
+    /// Extended records of the primary hits (the primary buffer may then
+    /// be empty)
+    pub extended: Option<&'a mut [SiLight6]>,
+}
+
+/// Like `render_scanlines`, additionally filling the requested secondary layers.
+pub fn render_scanlines_layers(
+    params: &RenderParams,
+    formula: &HybridFormula,
+    gbuffer: &mut [SiLight5],
+    layers: GBufferLayers,
+    worker_id: u32,
+    worker_count: u32,
+) -> u32 {
+    render_scanlines_reporting(params, formula, gbuffer, layers, worker_id, worker_count, &mut |_, _| {}).rows
+}
+
+/// Number of scanlines assigned to `worker_id` by the interleaved scheme.
+pub fn worker_row_count(height: u32, worker_id: u32, worker_count: u32) -> u32 {
+    height.saturating_sub(worker_id).div_ceil(worker_count.max(1))
+}
+
+/// Frame-wide step budget, spread over the pixels of one render call.
+///
+/// Each pixel may use up to `BURST` times its fair share of what is left, so
+/// a few pathological pixels cannot stall the frame; once the budget runs
+/// low, remaining pixels get short marches and fall back to background.
+#[derive(Clone, Debug)]
+pub struct StepBudget {
+    remaining: f64,
+    pixels_left: u64,
+}
+
+impl StepBudget {
+    const BURST: f64 = 4.0;
+    const MIN_STEPS: u32 = 16;
+
+    /// Budget of `total` steps for `pixels` pixels; None if `total` is not positive.
+    pub fn new(total: f64, pixels: u64) -> Option<Self> {
+        (total > 0.0).then_some(Self { remaining: total, pixels_left: pixels.max(1) })
+    }
+
+    /// Step limit for the next pixel.
+    pub fn pixel_limit(&self, max_steps: u32) -> u32 {
+        let share = self.remaining.max(0.0) / self.pixels_left.max(1) as f64 * Self::BURST;
+        (share as u32).clamp(Self::MIN_STEPS.min(max_steps), max_steps)
+    }
+
+    /// Record the steps a pixel used.
+    pub fn spend(&mut self, steps: u32) {
+        self.remaining -= steps as f64;
+        self.pixels_left = self.pixels_left.saturating_sub(1);
+    }
+}
+
+/// Per-call ray-march statistics of a render worker.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RenderStats {
+    pub rows: u32,
+    pub pixels: u64,
+    pub hits: u64,
+    /// March steps over all primary rays
+    pub steps: u64,
+    /// Most steps any primary ray took
+    pub max_steps: u32,
+    /// Distance estimator evaluations of the primary rays
+    pub de_evals: u64,
+}
+
+impl RenderStats {
+    fn add(&mut self, mr: &RayMarchResult) {
+        self.pixels += 1;
+        self.hits += mr.hit as u64;
+        self.steps += mr.steps as u64;
+        self.max_steps = self.max_steps.max(mr.steps);
+        self.de_evals += mr.de_evals as u64;
+    }
+
+    pub fn mean_steps(&self) -> f64 {
+        if self.pixels == 0 { 0.0 } else { self.steps as f64 / self.pixels as f64 }
+    }
+
+    pub fn hit_ratio(&self) -> f64 {
+        if self.pixels == 0 { 0.0 } else { self.hits as f64 / self.pixels as f64 }
+    }
+}
+
+/// Like `render_scanlines_layers`, calling `progress(rows_done, rows_total)`
+/// after every completed scanline of this worker. Returns the worker's statistics.
+pub fn render_scanlines_reporting(
+    params: &RenderParams,
+    formula: &HybridFormula,
+    gbuffer: &mut [SiLight5],
+    mut layers: GBufferLayers,
+    worker_id: u32,
+    worker_count: u32,
+    progress: &mut dyn FnMut(u32, u32),
+) -> RenderStats {
     let w = params.width;
     let h = params.height;
-    let hw = w as f64 * 0.5;
-    let hh = h as f64 * 0.5;
-    let mut rows_rendered = 0u32;
-
-    let julia_c = if params.julia { Some(&params.julia_c) } else { None };
-    let _ = julia_c; // used inside march_ray via params
+    let rows_total = worker_row_count(h, worker_id, worker_count);
+    let mut stats = RenderStats::default();
+    // Each worker gets its share of the frame budget
+    let mut budget = StepBudget::new(
+        params.step_budget / worker_count.max(1) as f64,
+        rows_total as u64 * w as u64,
+    );
 
     let mut y = worker_id;
     while y < h {
-        for x in 0..w {
-            let px = (x as f64 - hw) / hw;
-            let py = (y as f64 - hh) / hh;
-
-            // Compute ray direction for this pixel
-            let mut dir = Vec3D {
-                x: params.ray_dir_base.x + px * params.ray_dx.x + py * params.ray_dy.x,
-                y: params.ray_dir_base.y + px * params.ray_dx.y + py * params.ray_dy.y,
-                z: params.ray_dir_base.z + px * params.ray_dx.z + py * params.ray_dy.z,
-            };
-            math3d::vec3d_normalize(&mut dir);
-
-            // March the ray
-            let mr = march_ray(&params.camera_pos, &dir, params, formula);
-
-            // Write to G-buffer
-            let idx = (y * w + x) as usize;
-            if idx < gbuffer.len() {
-                if mr.hit {
-                    gbuffer[idx] = SiLight5 {
-                        sn_x: utils::min_max_clip_15bit(mr.normal.x),
-                        sn_y: utils::min_max_clip_15bit(mr.normal.y),
-                        sn_z: utils::min_max_clip_15bit(mr.normal.z),
-                        z_pos: utils::min_max_clip_16bit(
-                            utils::clamp(mr.total_distance / params.max_ray_length, 0.0, 1.0)
-                        ),
-                        shadow: 0,
-                        ambient: ((mr.steps as f64 / 200.0).min(1.0) * 65535.0) as u16,
-                        color_gradient: ((mr.smooth_iteration % 256.0) / 256.0 * 65535.0) as u16,
-                        orbit_trap: utils::min_max_clip_16bit(
-                            utils::clamp(1.0 - mr.orbit_trap.min(1.0), 0.0, 1.0)
-                        ),
-                        roughness: 0,
-                    };
+        render_row(params, formula, gbuffer, &mut layers, &mut budget, y, &mut stats);
+        stats.rows += 1;
+        progress(stats.rows, rows_total);
+        y += worker_count;
+    }
+
+    stats
+}
+
+/// March every pixel of scanline `y` into the G-buffer and requested layers.
+fn render_row(
+    params: &RenderParams,
+    formula: &HybridFormula,
+    gbuffer: &mut [SiLight5],
+    layers: &mut GBufferLayers,
+    budget: &mut Option<StepBudget>,
+    y: u32,
+    stats: &mut RenderStats,
+) {
+    let w = params.width;
+    for x in 0..w {
+        // Compute ray direction for this pixel
+        let dir = pixel_direction(params, x as f64, y as f64);
+
+        // March the ray, from the pre-pass depth if there is one. Cells
+        // the pre-pass saw miss are skipped unless misses carry data.
+        let limit = budget.as_ref().map_or(params.max_steps, |b| b.pixel_limit(params.max_steps));
+        let start = match &layers.prepass {
+            Some(prepass) => prepass.start_distance(x, y)
+                .or((params.miss_encoding == MissEncoding::ClosestApproach).then_some(0.0)),
+            None => Some(0.0),
+        };
+        let idx = (y * w + x) as usize;
+        let start = match (start, layers.start_depths.and_then(|d| d.get(idx))) {
+            (Some(t), Some(&reprojected)) if reprojected as f64 > t => {
+                Some(reproject::validated_start(params, formula, &dir, reprojected as f64).max(t))
+            }
+            (start, _) => start,
+        };
+        let mr = match start {
+            Some(t) => march_ray_from(&params.camera_pos, &dir, params, formula, limit, t),
+            None => RayMarchResult::default(),
+        };
+        if let Some(b) = budget.as_mut() {
+            b.spend(mr.steps);
+        }
+        stats.add(&mr);
+
+        // Write to G-buffer
+        let entry = gbuffer_entry(&mr, params, formula);
+        if idx < gbuffer.len() {
+            gbuffer[idx] = entry;
+        }
+        if let Some(layer) = layers.extended.as_deref_mut() {
+            if idx < layer.len() {
+                layer[idx] = extended_entry(entry, &mr);
+            }
+        }
+
+        // Reflection layer
+        if let Some(layer) = layers.reflect.as_deref_mut() {
+            if idx < layer.len() {
+                layer[idx] = if mr.hit {
+                    let rr = march_reflection(&mr, &dir, params, formula);
+                    gbuffer_entry(&rr, params, formula)
                 } else {
-                    // Background — mark as no-hit
-                    gbuffer[idx] = SiLight5 {
-                        sn_x: 0,
-                        sn_y: 0,
-                        sn_z: 0,
-                        z_pos: 65535,
-                        shadow: 0,
-                        ambient: 0,
-                        color_gradient: 0,
-                        orbit_trap: 0,
-                        roughness: 0,
-                    };
-                }
+                    MISS_PIXEL
+                };
             }
         }
-        rows_rendered += 1;
-        y += worker_count;
+
+        // Transmission layer
+        if let Some(layer) = layers.transmit.as_deref_mut() {
+            if idx < layer.len() {
+                layer[idx] = if mr.hit {
+                    let tr = refraction::march_refraction(&mr, &dir, params, formula);
+                    let mut entry = gbuffer_entry(&tr.exit, params, formula);
+                    entry.set_interior_path(tr.interior_distance);
+                    entry
+                } else {
+                    MISS_PIXEL
+                };
+            }
+        }
+    }
+}
+
+/// Render rows claimed from a shared counter until the image is exhausted.
+///
+/// `claim(n)` atomically reserves the next `n` rows and returns the first of
+/// them (a fetch-add on a counter shared by all workers), so
+/// fast workers keep taking rows while slow ones finish heavy ones. The step
+/// budget is split as if each worker got an equal share of the rows.
+pub fn render_claimed_rows(
+    params: &RenderParams,
+    formula: &HybridFormula,
+    gbuffer: &mut [SiLight5],
+    mut layers: GBufferLayers,
+    worker_count: u32,
+    rows_per_claim: u32,
+    claim: &mut dyn FnMut(u32) -> u32,
+) -> u32 {
+    let chunk = rows_per_claim.max(1);
+    let (w, h) = (params.width, params.height);
+    let mut budget = StepBudget::new(
+        params.step_budget / worker_count.max(1) as f64,
+        h.div_ceil(worker_count.max(1)) as u64 * w as u64,
+    );
+    let mut stats = RenderStats::default();
+    loop {
+        let first = claim(chunk);
+        if first >= h {
+            return stats.rows;
+        }
+        for y in first..first.saturating_add(chunk).min(h) {
+            render_row(params, formula, gbuffer, &mut layers, &mut budget, y, &mut stats);
+            stats.rows += 1;
+        }
+    }
+}
+
+/// Read an optional trailing parameter, falling back to `default` for older buffers.
+#[inline]
+fn param_or(data: &[f64], idx: usize, default: f64) -> f64 {
+    data.get(idx).copied().unwrap_or(default)
+}
+
+/// Global julia constant from the render parameter buffer (None = mandelbrot).
+///
+/// Julia mode lives in `HybridFormula` (with per-slot overrides); this reads
+/// the hybrid-wide fallback from its legacy position in the buffer.
+pub fn julia_from_buffer(data: &[f64]) -> Option<Vec3D> {
+    if data.len() < 32 || data[20] == 0.0 {
+        return None;
+    }
+    Some(Vec3D { x: data[21], y: data[22], z: data[23] })
+}
+
+/// First index of the bounding volume in the render parameter buffer.
+pub(crate) const BOUNDS_OFFSET: usize = 100;
+
+/// First index of the cut table in the render parameter buffer.
+pub(crate) const CUTS_OFFSET: usize = 42;
+
+/// First index of the material zone table in the render parameter buffer.
+pub(crate) const ZONES_OFFSET: usize = 110;
+
+/// Index of the curvature probe radius (after the zone table).
+const CURVATURE_RADIUS_INDEX: usize = ZONES_OFFSET + 1 + zones::MAX_ZONES * zones::ZONE_STRIDE;
+
+/// Index of the G-buffer format flag.
+pub(crate) const GBUFFER_FORMAT_INDEX: usize = CURVATURE_RADIUS_INDEX + 1;
+
+/// Index of the layout magic, followed by the layout version (see `validate`).
+pub(crate) const LAYOUT_MAGIC_INDEX: usize = GBUFFER_FORMAT_INDEX + 1;
+
+/// Legacy cutting plane (indices 24..28) followed by the cut table.
+fn cuts_from_buffer(data: &[f64]) -> Vec<Cut> {
+    let mut cuts = Vec::new();
+    if data[24] != 0.0 {
+        cuts.push(Cut {
+            shape: CutShape::Plane { normal: Vec3D { x: data[25], y: data[26], z: data[27] }, d: data[28] },
+            removes_inside: true,
+        });
     }
+    cuts.extend(cutting::cuts_from_buffer(data, CUTS_OFFSET));
+    cuts
+}
 
-    rows_rendered
+/// Every slot of the parameter buffer at its default, up to the layout
+/// stamp. Buffer writers take the slots they do not set from here.
+pub(crate) fn default_params_buffer() -> Vec<f64> {
+    let d = RenderParams::default();
+    let (c, b, x, y) = (d.camera_pos, d.ray_dir_base, d.ray_dx, d.ray_dy);
+    let mut p = vec![d.width as f64, d.height as f64, c.x, c.y, c.z, b.x, b.y, b.z, x.x, x.y, x.z, y.x, y.y, y.z];
+    p.extend([d.de_stop, d.step_width, d.max_ray_length, d.max_iterations as f64, d.bailout, d.cone_scale]);
+    p.extend([0.0; 9]); // julia, legacy cutting plane
+    p.push(d.bin_search_steps as f64);
+    p.extend([
+        d.ao.samples as f64,
+        d.ao.levels as f64,
+        d.ao.radius,
+        d.refraction.ior,
+        d.refraction.interior_step,
+        d.mc.bounces as f64,
+        d.mc.light_radius,
+        d.aa.samples_per_axis as f64,
+        d.aa.depth_threshold,
+        d.aa.normal_threshold,
+        d.stereo.eye_distance,
+        d.stereo.convergence,
+    ]);
+    p.resize(CUTS_OFFSET + 1 + cutting::MAX_CUTS * cutting::CUT_STRIDE, 0.0);
+    let v = &d.volume;
+    p.extend([v.density, v.falloff, v.step, v.absorption, v.emission]);
+    p.extend([d.max_steps as f64, d.step_budget, 0.0, 1.6]);
+    p.resize(BOUNDS_OFFSET + bounds::BOUNDS_STRIDE, 0.0);
+    p.extend([0.0, d.prepass_block as f64, 0.0]);
+    p.resize(LAYOUT_MAGIC_INDEX, 0.0);
+    p
 }
 
 /// Build RenderParams from the serialized parameter buffer.
@@ -387,8 +1143,25 @@ pub fn params_from_buffer(data: &[f64]) -> RenderParams {
 
     // Layout: [width, height, camera xyz, base_dir xyz, dx xyz, dy xyz,
     //          de_stop, step_width, max_ray_length, max_iter, bailout,
-    //          fov_factor, julia, julia xyz, cut_enabled, cut_normal xyz, cut_d, bin_search]
-    RenderParams {
+    //          cone_scale, julia, julia xyz (see julia_from_buffer),
+    //          cut_enabled, cut_normal xyz, cut_d, bin_search,
+    //          (30) ao_samples, ao_levels, ao_radius, (33) ior, interior_step,
+    //          (35) mc_bounces, mc_light_radius,
+    //          (37) aa_samples_per_axis, aa_depth_threshold, aa_normal_threshold,
+    //          (40) stereo_eye_distance, stereo_convergence,
+    //          (42) cut_count, 6 × [kind, removes_inside, a0..a5] (see cutting::Cut),
+    //          (91) volume density, falloff, step, absorption, emission,
+    //          (96) max_steps, step_budget, (98) march_mode (0 regulated, 1 over-relaxed), omega,
+    //          (100) bounds [kind, a0..a5] (see bounds::bounds_from_slice),
+    //          (107) miss_encoding (0 sentinel, 1 closest approach), (108) prepass_block,
+    //          (109) auto_detail (pixels, 0 = use de_stop as given),
+    //          (110) zone_count, 8 × [source, min, max, material_id] (see zones::MaterialZone),
+    //          (143) curvature_radius (hit thresholds, 0 = off),
+    //          (144) gbuffer_format (0 packed SiLight5, 1 extended SiLight6),
+    //          (145) layout magic, layout version (see validate)]
+    // Fields from index 30 on are optional; missing ones keep their defaults.
+    let defaults = RenderParams::default();
+    let mut params = RenderParams {
         width: data[0] as u32,
         height: data[1] as u32,
         camera_pos: Vec3D { x: data[2], y: data[3], z: data[4] },
@@ -399,14 +1172,391 @@ pub fn params_from_buffer(data: &[f64]) -> RenderParams {
         step_width: data[15],
         max_ray_length: data[16],
         max_iterations: data[17] as u32,
+        max_steps: param_or(data, 96, defaults.max_steps as f64).max(1.0) as u32,
+        step_budget: param_or(data, 97, defaults.step_budget),
+        march_mode: if param_or(data, 98, 0.0) == 1.0 {
+            MarchMode::OverRelaxed { omega: param_or(data, 99, 1.6) }
+        } else {
+            MarchMode::Regulated
+        },
+        miss_encoding: if param_or(data, 107, 0.0) == 1.0 {
+            MissEncoding::ClosestApproach
+        } else {
+            MissEncoding::Sentinel
+        },
+        prepass_block: param_or(data, 108, defaults.prepass_block as f64).max(1.0) as u32,
+        auto_detail: param_or(data, 109, 0.0).max(0.0),
         bailout: data[18],
-        fov_factor: data[19],
-        julia: data[20] != 0.0,
-        julia_c: Vec3D { x: data[21], y: data[22], z: data[23] },
-        cut_enabled: data[24] != 0.0,
-        cut_normal: Vec3D { x: data[25], y: data[26], z: data[27] },
-        cut_d: data[28],
+        cone_scale: data[19],
+        cuts: cuts_from_buffer(data),
+        bounds: data.get(BOUNDS_OFFSET..BOUNDS_OFFSET + bounds::BOUNDS_STRIDE)
+            .and_then(|v| bounds::bounds_from_slice(v, data[18])),
+        material_zones: zones::zones_from_buffer(data, ZONES_OFFSET),
+        curvature_radius: param_or(data, CURVATURE_RADIUS_INDEX, 0.0).max(0.0),
+        gbuffer_format: GBufferFormat::from_u32(param_or(data, GBUFFER_FORMAT_INDEX, 0.0) as u32),
         bin_search_steps: data[29] as u32,
+        ao: AoSettings {
+            samples: param_or(data, 30, defaults.ao.samples as f64) as u32,
+            levels: param_or(data, 31, defaults.ao.levels as f64) as u32,
+            radius: param_or(data, 32, defaults.ao.radius),
+        },
+        refraction: RefractionSettings {
+            ior: param_or(data, 33, defaults.refraction.ior),
+            interior_step: param_or(data, 34, defaults.refraction.interior_step),
+            ..defaults.refraction
+        },
+        mc: McSettings {
+            bounces: param_or(data, 35, defaults.mc.bounces as f64) as u32,
+            light_radius: param_or(data, 36, defaults.mc.light_radius),
+        },
+        aa: AaSettings {
+            samples_per_axis: param_or(data, 37, defaults.aa.samples_per_axis as f64) as u32,
+            depth_threshold: param_or(data, 38, defaults.aa.depth_threshold),
+            normal_threshold: param_or(data, 39, defaults.aa.normal_threshold),
+        },
+        stereo: StereoSettings {
+            eye_distance: param_or(data, 40, defaults.stereo.eye_distance),
+            convergence: param_or(data, 41, defaults.stereo.convergence),
+        },
+        volume: VolumeSettings {
+            density: param_or(data, 91, defaults.volume.density),
+            falloff: param_or(data, 92, defaults.volume.falloff),
+            step: param_or(data, 93, defaults.volume.step),
+            absorption: param_or(data, 94, defaults.volume.absorption),
+            emission: param_or(data, 95, defaults.volume.emission),
+        },
+    };
+    if params.auto_detail > 0.0 {
+        params.de_stop = params.auto_de_stop(params.auto_detail);
     }
+    params
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formulas::{hybrid::HybridMode, FormulaId};
+
+    #[test]
+    fn test_buffer_len_is_checked() {
+        let params = RenderParams { width: 40, height: 30, ..Default::default() };
+        assert_eq!(params.buffer_len(4), Some(4800));
+        // 65536² pixels do not fit u32 indices
+        let huge = RenderParams { width: 65536, height: 65536, ..Default::default() };
+        assert_eq!(huge.buffer_len(1), None);
+        let wide = RenderParams { width: 65536, height: 32768, ..Default::default() };
+        assert_eq!(wide.buffer_len(1), Some(1 << 31));
+    }
+
+    #[test]
+    fn test_worker_row_count() {
+        assert_eq!(worker_row_count(10, 0, 3), 4);
+        assert_eq!(worker_row_count(10, 2, 3), 3);
+        assert_eq!(worker_row_count(2, 3, 4), 0);
+    }
+
+    #[test]
+    fn test_progress_reported_per_row() {
+        let params = RenderParams { width: 3, height: 5, ..Default::default() };
+        let formula = HybridFormula::new(&[(FormulaId::MandelbulbPower8, 1)], HybridMode::Alternating, 8, 16.0);
+        let mut gbuffer = vec![SiLight5::default(); 15];
+        let mut reports = Vec::new();
+        let stats = render_scanlines_reporting(
+            &params, &formula, &mut gbuffer, GBufferLayers::default(), 1, 2,
+            &mut |done, total| reports.push((done, total)),
+        );
+        assert_eq!(stats.rows, 2);
+        assert_eq!(reports, vec![(1, 2), (2, 2)]);
+        assert_eq!(stats.pixels, 6);
+        assert_eq!(stats.hits, 6); // narrow default view of the bulb
+        assert!(stats.max_steps as f64 >= stats.mean_steps());
+        // Every primary ray evaluates the DE at least once per step plus refinement and normal
+        assert!(stats.de_evals >= stats.steps + 6 * (3 + 6));
+    }
+
+    #[test]
+    fn test_step_budget_limits_pixels() {
+        let mut budget = StepBudget::new(1000.0, 10).unwrap();
+        assert_eq!(budget.pixel_limit(8000), 400);
+        assert_eq!(budget.pixel_limit(100), 100);
+        budget.spend(990);
+        assert_eq!(budget.pixel_limit(8000), StepBudget::MIN_STEPS);
+        assert!(StepBudget::new(0.0, 10).is_none());
+    }
+
+    #[test]
+    fn test_tiny_step_budget_still_renders_every_pixel() {
+        let params = RenderParams { width: 4, height: 4, step_budget: 16.0, ..Default::default() };
+        let formula = HybridFormula::new(&[(FormulaId::MandelbulbPower8, 1)], HybridMode::Alternating, 8, 16.0);
+        let mut gbuffer = vec![SiLight5::default(); 16];
+        assert_eq!(render_scanlines(&params, &formula, &mut gbuffer, 0, 1), 4);
+        assert!(gbuffer.iter().all(|p| { let z = p.z_pos; z != 0 }));
+    }
+
+    #[test]
+    fn test_over_relaxation_takes_fewer_steps_to_the_same_hits() {
+        let formula = HybridFormula::new(&[(FormulaId::MandelbulbPower8, 1)], HybridMode::Alternating, 8, 16.0);
+        let regulated = RenderParams::default();
+        let relaxed = RenderParams { march_mode: MarchMode::OverRelaxed { omega: 1.6 }, ..Default::default() };
+        let (mut steps_a, mut steps_b) = (0, 0);
+        for i in 0..16 {
+            let dir = math3d::vec3d_normalized(&Vec3D { x: i as f64 * 0.03, y: 0.1, z: 1.0 });
+            let a = march_ray(&regulated.camera_pos, &dir, &regulated, &formula);
+            let b = march_ray(&relaxed.camera_pos, &dir, &relaxed, &formula);
+            assert_eq!(a.hit, b.hit);
+            if a.hit {
+                assert!((a.total_distance - b.total_distance).abs() < 0.01);
+            }
+            steps_a += a.steps;
+            steps_b += b.steps;
+        }
+        assert!(steps_b < steps_a, "{steps_b} vs {steps_a}");
+    }
+
+    #[test]
+    fn test_hit_threshold_follows_pixel_cone() {
+        let low = RenderParams { cone_scale: 1.0, de_stop: 1e-6, ..Default::default() };
+        let high = RenderParams { width: 1600, height: 1200, ..low.clone() };
+        // Linear in distance, halves when the resolution doubles
+        let t = 2.0;
+        assert!((low.hit_threshold(2.0 * t) - 2.0 * low.hit_threshold(t)).abs() < 1e-15);
+        assert!((high.hit_threshold(t) - 0.5 * low.hit_threshold(t)).abs() < 1e-15);
+        // 600 rows across ±1/300: adjacent rays are 1/90000 apart, radius half that
+        assert!((low.pixel_footprint(1.0) - 0.5 / 90_000.0).abs() < 1e-15);
+        // Never finer than de_stop; disabled cone keeps de_stop
+        assert_eq!(low.hit_threshold(0.0), 1e-6);
+        assert_eq!(RenderParams::default().hit_threshold(t), 0.0005);
+    }
+
+    #[test]
+    fn test_miss_pixels_store_closest_approach() {
+        let formula = HybridFormula::new(&[(FormulaId::MandelbulbPower8, 1)], HybridMode::Alternating, 8, 16.0);
+        let params = RenderParams {
+            miss_encoding: MissEncoding::ClosestApproach,
+            ray_dx: Vec3D { x: 0.6, y: 0.0, z: 0.0 },
+            ray_dy: Vec3D { x: 0.0, y: 0.45, z: 0.0 },
+            ..Default::default()
+        };
+        // Scan a row outward from the center: the first misses graze the silhouette
+        let distances: Vec<f64> = (400..800)
+            .map(|x| render_pixel(&params, &formula, x, 300))
+            .filter(|e| e.z_pos == 65535)
+            .map(|e| e.miss_distance())
+            .collect();
+        assert!(!distances.is_empty());
+        assert!(distances[0] < 4.0);
+        assert!(distances[0] < *distances.last().unwrap());
+
+        // Sentinel misses read as far away
+        let plain = RenderParams { miss_encoding: MissEncoding::Sentinel, ..params };
+        let edge = render_pixel(&plain, &formula, 799, 300);
+        assert_eq!({ edge.z_pos }, 65535);
+        assert_eq!(edge.miss_distance(), 65535.0 / SiLight5::MISS_DISTANCE_SCALE);
+    }
+
+    #[test]
+    fn test_claimed_rows_cover_image_once() {
+        use std::sync::atomic::{AtomicU32, Ordering};
+        let params = RenderParams { width: 6, height: 11, ..Default::default() };
+        let formula = HybridFormula::new(&[(FormulaId::MandelbulbPower8, 1)], HybridMode::Alternating, 8, 16.0);
+        let mut full = vec![SiLight5::default(); 66];
+        render_scanlines(&params, &formula, &mut full, 0, 1);
+
+        let counter = AtomicU32::new(0);
+        let mut claim = |n: u32| counter.fetch_add(n, Ordering::Relaxed);
+        let mut claimed = vec![SiLight5::default(); 66];
+        let rows = render_claimed_rows(&params, &formula, &mut claimed, GBufferLayers::default(), 2, 4, &mut claim);
+        assert_eq!(rows, 11);
+        // A late worker finds nothing left
+        let late = render_claimed_rows(&params, &formula, &mut claimed, GBufferLayers::default(), 2, 4, &mut claim);
+        assert_eq!(late, 0);
+        for (a, b) in full.iter().zip(&claimed) {
+            assert_eq!((a.z_pos, a.sn_z), (b.z_pos, b.sn_z));
+        }
+    }
+
+    #[test]
+    fn test_tile_matches_full_render() {
+        let params = RenderParams { width: 12, height: 10, ..Default::default() };
+        let formula = HybridFormula::new(&[(FormulaId::MandelbulbPower8, 1)], HybridMode::Alternating, 8, 16.0);
+        let mut full = vec![SiLight5::default(); 120];
+        render_scanlines(&params, &formula, &mut full, 0, 1);
+
+        // The tile end is clamped to the image
+        let mut tiled = vec![SiLight5::default(); 120];
+        assert_eq!(render_tile(&params, &formula, &mut tiled, 3, 2, 20, 7), 9 * 5);
+        let fields = |p: &SiLight5| {
+            (p.sn_x, p.sn_y, p.sn_z, p.z_pos, p.shadow, p.ambient, p.color_gradient, p.orbit_trap, p.roughness)
+        };
+        for (i, (a, b)) in full.iter().zip(&tiled).enumerate() {
+            let (x, y) = (i as u32 % 12, i as u32 / 12);
+            if (3..12).contains(&x) && (2..7).contains(&y) {
+                assert_eq!(fields(a), fields(b), "pixel ({x}, {y})");
+            } else {
+                assert_eq!(fields(b), fields(&SiLight5::default()));
+            }
+        }
+        assert!(tiled.iter().any(|p| p.z_pos < 65534));
+    }
+
+    #[test]
+    fn test_extended_records_match_packed_entries() {
+        assert_eq!(GBufferFormat::Extended.record_size(), 34);
+        let params = RenderParams { width: 8, height: 8, ..Default::default() };
+        let formula = HybridFormula::new(&[(FormulaId::MandelbulbPower8, 1)], HybridMode::Alternating, 8, 16.0);
+        let mut packed = vec![SiLight5::default(); 64];
+        render_scanlines(&params, &formula, &mut packed, 0, 1);
+        let mut records = vec![SiLight6::default(); 64];
+        let layers = GBufferLayers { extended: Some(&mut records), ..Default::default() };
+        render_scanlines_layers(&params, &formula, &mut [], layers, 0, 1);
+
+        let mut hits = 0;
+        for (i, (a, r)) in packed.iter().zip(&records).enumerate() {
+            let b = r.base;
+            assert_eq!((a.z_pos, a.sn_x, a.orbit_trap), (b.z_pos, b.sn_x, b.orbit_trap));
+            if a.z_pos < 65535 {
+                hits += 1;
+                // The stored position lies on the pixel's ray at the stored depth
+                let dir = pixel_direction(&params, (i % 8) as f64, (i / 8) as f64);
+                let t = a.z_pos as f64 / 65535.0 * params.max_ray_length;
+                let expected = math3d::vec3d_add(&params.camera_pos, &math3d::vec3d_scale(&dir, t));
+                let d = math3d::vec3d_length(&math3d::vec3d_sub(&r.world_position(), &expected));
+                assert!(d < params.max_ray_length / 65535.0 + 1e-5, "{d}");
+                assert!(r.orbit_trap2 > 0);
+            }
+        }
+        assert!(hits > 0);
+    }
+
+    #[test]
+    fn test_normal_at_point_points_away_from_bulb() {
+        let params = RenderParams::default();
+        let formula = HybridFormula::new(&[(FormulaId::MandelbulbPower8, 1)], HybridMode::Alternating, 8, 16.0);
+        let n = normal_at_point(&params, &formula, &Vec3D { x: 1.5, y: 0.0, z: 0.0 });
+        assert!(n.x > 0.9, "{n:?}");
+    }
+
+    #[test]
+    fn test_auto_de_stop_matches_view_geometry() {
+        use crate::engine::camera;
+        let pos = Vec3D { x: 0.0, y: 0.0, z: -4.0 };
+        let fov = 0.8f64;
+        let rays = camera::compute_camera_rays(&pos, &Vec3D::default(), &Vec3D { x: 0.0, y: 1.0, z: 0.0 }, fov, 640, 480);
+        let mut params = RenderParams { width: 640, height: 480, ..Default::default() };
+        rays.apply(&mut params, &pos);
+
+        let fov_h = 2.0 * ((fov * 0.5).tan() * 640.0 / 480.0).atan();
+        let expected = auto_de_stop(0.25, fov_h, 640, 1.5);
+        assert!((params.auto_de_stop(1.5) - expected).abs() < 1e-12 * expected);
+        // Twice the resolution halves the threshold
+        assert!((auto_de_stop(0.25, fov_h, 1280, 1.5) - expected * 0.5).abs() < 1e-15);
+
+        let mut buffer = vec![0.0; 110];
+        buffer[..2].copy_from_slice(&[640.0, 480.0]);
+        buffer[2..14].copy_from_slice(&[pos.x, pos.y, pos.z, rays.dir_base.x, rays.dir_base.y, rays.dir_base.z,
+            rays.dx.x, rays.dx.y, rays.dx.z, rays.dy.x, rays.dy.y, rays.dy.z]);
+        buffer[14] = 0.01;
+        buffer[109] = 1.5;
+        assert_eq!(params_from_buffer(&buffer).de_stop, params.auto_de_stop(1.5));
+    }
+
+    /// Unit sphere whose DE is NaN on the plane x = 0 (like axis
+    /// singularities of some hybrids).
+    struct SingularSphere;
+
+    impl DistanceField for SingularSphere {
+        fn compute_de(&self, pos: &Vec3D) -> FormulaResult {
+            let de = if pos.x == 0.0 { f64::NAN } else { math3d::vec3d_length(pos) - 1.0 };
+            FormulaResult { de, ..Default::default() }
+        }
+    }
+
+    #[test]
+    fn test_nan_de_recovers_instead_of_missing() {
+        let params = RenderParams::default();
+        let dir = Vec3D { x: 0.0, y: 0.0, z: 1.0 };
+        let result = march_ray(&params.camera_pos, &dir, &params, &SingularSphere);
+        assert!(result.hit);
+        assert!((result.total_distance - 1.5).abs() < 0.01, "{}", result.total_distance);
+    }
+
+    /// Unit sphere wrapped in a NaN shell at radius 1.3, thicker than the
+    /// fallback's sampling distance.
+    struct NanShell;
+
+    impl DistanceField for NanShell {
+        fn compute_de(&self, pos: &Vec3D) -> FormulaResult {
+            let r = math3d::vec3d_length(pos);
+            let de = if (r - 1.3).abs() < 0.01 { f64::NAN } else { r - 1.0 };
+            FormulaResult { de, ..Default::default() }
+        }
+    }
+
+    #[test]
+    fn test_nan_shell_is_crossed_by_retrying() {
+        let params = RenderParams::default();
+        let dir = Vec3D { x: 0.0, y: 0.0, z: 1.0 };
+        // The first step (0.8 × 1.5) lands inside the shell, where no
+        // neighbour bounds the DE either
+        let landing = Vec3D { x: 0.0, y: 0.0, z: params.camera_pos.z + 1.2 };
+        assert!(NanShell.compute_de(&landing).de.is_nan());
+        assert!(fallback_de(&landing, params.hit_threshold(1.2), &NanShell).is_none());
+
+        let result = march_ray(&params.camera_pos, &dir, &params, &NanShell);
+        assert!(result.hit);
+        assert!((result.total_distance - 1.5).abs() < 0.01, "{}", result.total_distance);
+    }
+
+    /// Inside of a hollow unit sphere (a concave surface).
+    struct Hollow;
+
+    impl DistanceField for Hollow {
+        fn compute_de(&self, pos: &Vec3D) -> FormulaResult {
+            FormulaResult { de: 1.0 - math3d::vec3d_length(pos), ..Default::default() }
+        }
+    }
+
+    #[test]
+    fn test_curvature_sign_and_storage() {
+        let params = RenderParams { de_stop: 0.01, curvature_radius: 10.0, ..Default::default() };
+        let p = math3d::vec3d_normalized(&Vec3D { x: 0.6, y: 0.2, z: -0.7 });
+        let convex = curvature_at(&p, 1.0, &params, &SingularSphere);
+        // Mean curvature 1 of the unit sphere times the 0.1 probe radius
+        assert!((convex - 0.1f64.tanh()).abs() < 1e-3, "{convex}");
+        assert!((curvature_at(&p, 1.0, &params, &Hollow) + convex).abs() < 1e-3);
+
+        let mut entry = SiLight5::default();
+        entry.set_in_shadow(2);
+        assert_eq!(entry.curvature(), 0.0);
+        entry.set_curvature(-0.5);
+        assert!((entry.curvature() + 0.5).abs() < 2e-3);
+        assert!(entry.in_shadow(2));
+    }
+
+    #[test]
+    fn test_interior_hits_are_flagged() {
+        let params = RenderParams::default();
+        let outside = RayMarchResult { hit: true, smooth_iteration: 64.0, ..Default::default() };
+        let inside = RayMarchResult { inside: true, ..outside.clone() };
+        let (a, b) = (gbuffer_entry(&outside, &params, &SingularSphere), gbuffer_entry(&inside, &params, &SingularSphere));
+        assert!(!a.is_inside() && b.is_inside());
+        assert!((a.gradient_position() - 0.25).abs() < 1e-4);
+        assert_eq!(a.gradient_position(), b.gradient_position());
+    }
+
+    #[test]
+    fn test_pick_pixel_reports_surface_point() {
+        let params = RenderParams { width: 16, height: 12, ..Default::default() };
+        let formula = HybridFormula::new(&[(FormulaId::MandelbulbPower8, 1)], HybridMode::Alternating, 8, 16.0);
+        let pick = pick_pixel(&params, &formula, 8.0, 6.0);
+        assert!(pick.hit);
+        let offset = math3d::vec3d_sub(&pick.position, &params.camera_pos);
+        assert!((math3d::vec3d_length(&offset) - pick.distance).abs() < 1e-9);
+        assert!(pick.de.abs() < params.hit_threshold(pick.distance) * 2.0);
+        assert!((math3d::vec3d_length(&pick.normal) - 1.0).abs() < 1e-6);
+        // Agrees with the quantized G-buffer entry of the same pixel
+        let entry = render_pixel(&params, &formula, 8, 6);
+        assert_ne!({ entry.z_pos }, 65535);
+    }
+}
@@ -0,0 +1,213 @@
+//! Transparent material rendering — refraction through the fractal interior.
+//!
+//! A primary hit is refracted into the surface, marched through the interior
+//! with fixed steps (fractal DEs are not signed inside, so sphere tracing
+//! cannot be used there), refracted out at the exit point and then traced as
+//! a normal ray. Total internal reflection bounces the ray back inside.
+//! The interior path length is reported for Beer–Lambert absorption at paint time.
+
+use crate::engine::raymarcher::{self, RayMarchResult, RenderParams};
+use crate::engine::types::Vec3D;
+use crate::formulas::DistanceField;
+use crate::math::math3d;
+
+/// Settings for the transmission layer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RefractionSettings {
+    /// Index of refraction of the material
+    pub ior: f64,
+    /// Interior march step in world units (0 = 8 × de_stop)
+    pub interior_step: f64,
+    /// Maximum internal reflections before giving up
+    pub max_bounces: u32,
+}
+
+impl Default for RefractionSettings {
+    fn default() -> Self {
+        Self { ior: 1.5, interior_step: 0.0, max_bounces: 4 }
+    }
+}
+
+/// Result of a transmitted ray.
+#[derive(Clone, Default)]
+pub struct RefractionResult {
+    /// What the ray sees after leaving the material
+    pub exit: RayMarchResult,
+    /// World distance travelled inside the material
+    pub interior_distance: f64,
+}
+
+/// Upper bound on interior steps per ray.
+const MAX_INTERIOR_STEPS: u32 = 4096;
+
+/// Is `pos` inside the material (fractal not escaped, or negative SDF)?
+#[inline]
+fn is_inside<F: DistanceField + ?Sized>(pos: &Vec3D, params: &RenderParams, formula: &F) -> bool {
+    let fr = formula.compute_de(pos);
+    fr.inside || fr.de < params.de_stop
+}
+
+/// Trace a ray through a transparent surface hit by `primary` along `direction`.
+pub fn march_refraction<F: DistanceField + ?Sized>(
+    primary: &RayMarchResult,
+    direction: &Vec3D,
+    params: &RenderParams,
+    formula: &F,
+) -> RefractionResult {
+    let settings = &params.refraction;
+    let ior = settings.ior.max(1e-3);
+    let step = if settings.interior_step > 0.0 { settings.interior_step } else { params.de_stop * 8.0 };
+    let offset = params.de_stop * 4.0;
+    let mut result = RefractionResult::default();
+
+    // Enter the material
+    let n = &primary.normal;
+    let mut dir = match math3d::vec3d_refract(direction, n, 1.0 / ior) {
+        Some(t) => math3d::vec3d_normalized(&t),
+        None => return result,
+    };
+    let mut pos = math3d::vec3d_sub(&primary.hit_pos, &math3d::vec3d_scale(n, offset));
+    let mut bounces = 0;
+    let mut steps = 0;
+
+    while steps < MAX_INTERIOR_STEPS {
+        // Fixed-step march until the ray leaves the material
+        let mut next = math3d::vec3d_add(&pos, &math3d::vec3d_scale(&dir, step));
+        steps += 1;
+        if is_inside(&next, params, formula) {
+            pos = next;
+            result.interior_distance += step;
+            continue;
+        }
+
+        // Bisect the boundary between the last inside and first outside point
+        let mut lo = 0.0;
+        let mut hi = step;
+        for _ in 0..params.bin_search_steps.max(4) {
+            let mid = 0.5 * (lo + hi);
+            let p = math3d::vec3d_add(&pos, &math3d::vec3d_scale(&dir, mid));
+            if is_inside(&p, params, formula) { lo = mid } else { hi = mid }
+        }
+        next = math3d::vec3d_add(&pos, &math3d::vec3d_scale(&dir, hi));
+        result.interior_distance += hi;
+
+        // Outward normal at the exit; refract from material into air
+        let exit_n = raymarcher::calculate_normal(&next, 0.0, params, formula);
+        let inward = math3d::vec3d_scale(&exit_n, -1.0);
+        match math3d::vec3d_refract(&dir, &inward, ior) {
+            Some(t) => {
+                let out_dir = math3d::vec3d_normalized(&t);
+                let origin = math3d::vec3d_add(&next, &math3d::vec3d_scale(&exit_n, offset));
+                result.exit = raymarcher::march_ray(&origin, &out_dir, params, formula);
+                result.exit.total_distance += primary.total_distance + result.interior_distance;
+                return result;
+            }
+            None => {
+                // Total internal reflection — stay inside
+                bounces += 1;
+                if bounces > settings.max_bounces {
+                    return result;
+                }
+                dir = math3d::vec3d_normalized(&math3d::vec3d_reflect(&dir, &exit_n));
+                pos = math3d::vec3d_sub(&next, &math3d::vec3d_scale(&exit_n, offset));
+            }
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formulas::FormulaResult;
+
+    /// Unit sphere at the origin, as an exact SDF.
+    struct Sphere;
+
+    impl DistanceField for Sphere {
+        fn compute_de(&self, pos: &Vec3D) -> FormulaResult {
+            FormulaResult { de: math3d::vec3d_length(pos) - 1.0, ..Default::default() }
+        }
+    }
+
+    /// Cube of half-size 1 at the origin above a floor at y = −3.
+    struct CubeOverFloor;
+
+    impl DistanceField for CubeOverFloor {
+        fn compute_de(&self, pos: &Vec3D) -> FormulaResult {
+            let q = [pos.x.abs() - 1.0, pos.y.abs() - 1.0, pos.z.abs() - 1.0];
+            let outside = q.iter().map(|v| v.max(0.0).powi(2)).sum::<f64>().sqrt();
+            let cube = outside + q[0].max(q[1]).max(q[2]).min(0.0);
+            FormulaResult { de: cube.min(pos.y + 3.0), ..Default::default() }
+        }
+    }
+
+    fn params(max_bounces: u32) -> RenderParams {
+        RenderParams {
+            de_stop: 1e-4,
+            refraction: RefractionSettings { ior: 1.5, interior_step: 0.01, max_bounces },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_sphere_chord_and_exit_direction() {
+        // Hit at height 0.5 going along +z: 30° incidence
+        let hit_pos = Vec3D { x: 0.0, y: 0.5, z: -(0.75f64).sqrt() };
+        let primary = RayMarchResult { hit: true, hit_pos, normal: hit_pos, ..Default::default() };
+        let dir = Vec3D { x: 0.0, y: 0.0, z: 1.0 };
+        // A wall at z = 3 behind the sphere shows where the ray leaves to
+        struct Scene;
+        impl DistanceField for Scene {
+            fn compute_de(&self, pos: &Vec3D) -> FormulaResult {
+                FormulaResult { de: Sphere.compute_de(pos).de.min(3.0 - pos.z), ..Default::default() }
+            }
+        }
+        let result = march_refraction(&primary, &dir, &params(4), &Scene);
+
+        let theta_i = 0.5f64.asin();
+        let theta_t = (0.5 / 1.5f64).asin();
+        let chord = 2.0 * theta_t.cos();
+        assert!((result.interior_distance - chord).abs() < 1e-2, "{}", result.interior_distance);
+
+        // The ray leaves deviated by 2 (θi − θt) towards the axis
+        let inside = math3d::vec3d_normalized(&math3d::vec3d_refract(&dir, &hit_pos, 1.0 / 1.5).unwrap());
+        let exit = math3d::vec3d_add(&hit_pos, &math3d::vec3d_scale(&inside, chord));
+        let deviation = 2.0 * (theta_i - theta_t);
+        let expected_y = exit.y - deviation.tan() * (3.0 - exit.z);
+        assert!(result.exit.hit);
+        assert!((result.exit.hit_pos.z - 3.0).abs() < 1e-2);
+        assert!((result.exit.hit_pos.y - expected_y).abs() < 1e-2, "{} vs {expected_y}", result.exit.hit_pos.y);
+    }
+
+    #[test]
+    fn test_total_internal_reflection_gives_up_after_max_bounces() {
+        // 45° into the top face; the refracted ray meets the side face past
+        // the critical angle, reflects, and leaves through the bottom
+        let primary = RayMarchResult {
+            hit: true,
+            hit_pos: Vec3D { x: 0.5, y: 1.0, z: 0.0 },
+            normal: Vec3D { x: 0.0, y: 1.0, z: 0.0 },
+            ..Default::default()
+        };
+        let dir = math3d::vec3d_normalized(&Vec3D { x: 1.0, y: -1.0, z: 0.0 });
+        let sin_t = std::f64::consts::FRAC_1_SQRT_2 / 1.5;
+        let cos_t = (1.0 - sin_t * sin_t).sqrt();
+        let to_side = 0.5 / sin_t;
+
+        let trapped = march_refraction(&primary, &dir, &params(0), &CubeOverFloor);
+        assert!(!trapped.exit.hit);
+        assert!((trapped.interior_distance - to_side).abs() < 1e-2, "{}", trapped.interior_distance);
+
+        let escaped = march_refraction(&primary, &dir, &params(1), &CubeOverFloor);
+        let side_y = 1.0 - to_side * cos_t;
+        let to_bottom = (side_y + 1.0) / cos_t;
+        assert!((escaped.interior_distance - to_side - to_bottom).abs() < 1e-2, "{}", escaped.interior_distance);
+        // Out at 45° again, down and back towards −x
+        let bottom_x = 1.0 - to_bottom * sin_t;
+        assert!(escaped.exit.hit);
+        assert!((escaped.exit.hit_pos.y + 3.0).abs() < 1e-2);
+        assert!((escaped.exit.hit_pos.x - (bottom_x - 2.0)).abs() < 1e-2, "{}", escaped.exit.hit_pos.x);
+    }
+}
@@ -0,0 +1,277 @@
+//! Scene replay: recorded sessions of scene mutations with render checkpoints.
+//!
+//! A session is a JSON document holding the starting scene buffers and a
+//! list of steps. Each step applies a JSON Patch (RFC 6902 subset: `add`,
+//! `remove`, `replace`, `test`) to the scene and may request a checkpoint,
+//! which renders and paints the whole frame and records CRC-32s of the
+//! G-buffer and the RGBA image:
+//!
+//! ```json
+//! { "version": 1,
+//!   "scene": { "render": [...], "formula": [...], "paint": [...] },
+//!   "steps": [
+//!     { "patch": [{ "op": "replace", "path": "/render/14", "value": 0.0002 }],
+//!       "checkpoint": { "name": "zoom", "width": 64, "height": 48,
+//!                       "gbuffer_crc": 1234, "image_crc": 5678 } } ] }
+//! ```
+//!
+//! Recorded CRCs are compared against the replay, so a session doubles as an
+//! end-to-end test of the pipeline and as a shareable exploration history.
+
+use std::fmt;
+
+use serde_json::{json, Value};
+
+use crate::engine::{preview, raymarcher, repro, scene};
+use crate::export::png;
+use crate::lighting::paint;
+
+/// Session format version written by recorders.
+pub const SESSION_VERSION: u64 = 1;
+
+/// Why a session could not be replayed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReplayError {
+    /// Step index (None = session document itself)
+    pub step: Option<usize>,
+    pub message: String,
+}
+
+impl ReplayError {
+    fn new(step: Option<usize>, message: impl Into<String>) -> Self {
+        Self { step, message: message.into() }
+    }
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.step {
+            Some(step) => write!(f, "step {step}: {}", self.message),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+/// Result of one replayed checkpoint.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Checkpoint {
+    pub step: usize,
+    pub name: String,
+    pub gbuffer_crc: u32,
+    pub image_crc: u32,
+    /// Whether the recorded CRCs match (None = nothing recorded)
+    pub matches: Option<bool>,
+}
+
+impl Checkpoint {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "step": self.step,
+            "name": self.name,
+            "gbuffer_crc": self.gbuffer_crc,
+            "image_crc": self.image_crc,
+            "matches": self.matches,
+        })
+    }
+}
+
+/// Replay a session document, rendering every checkpoint.
+pub fn replay(session: &str) -> Result<Vec<Checkpoint>, ReplayError> {
+    let doc: Value = serde_json::from_str(session).map_err(|e| ReplayError::new(None, e.to_string()))?;
+    let version = doc.get("version").and_then(Value::as_u64).unwrap_or(SESSION_VERSION);
+    if version > SESSION_VERSION {
+        return Err(ReplayError::new(None, format!("unsupported session version {version}")));
+    }
+    let mut scene = doc.get("scene").cloned().ok_or_else(|| ReplayError::new(None, "missing scene"))?;
+    let steps = doc.get("steps").and_then(Value::as_array).cloned().unwrap_or_default();
+
+    let mut checkpoints = Vec::new();
+    for (i, step) in steps.iter().enumerate() {
+        if let Some(ops) = step.get("patch") {
+            apply_patch(&mut scene, ops).map_err(|e| ReplayError::new(Some(i), e))?;
+        }
+        if let Some(request) = step.get("checkpoint") {
+            let checkpoint = render_checkpoint(&scene, request, i).map_err(|e| ReplayError::new(Some(i), e))?;
+            checkpoints.push(checkpoint);
+        }
+    }
+    Ok(checkpoints)
+}
+
+/// Render the scene for one checkpoint request and compare recorded CRCs.
+fn render_checkpoint(scene: &Value, request: &Value, step: usize) -> Result<Checkpoint, String> {
+    let render = number_array(scene, "render")?;
+    let formula: Vec<u32> = number_array(scene, "formula")?.iter().map(|&v| v as u32).collect();
+    let paint_params = number_array(scene, "paint").unwrap_or_default();
+
+    let mut render = render;
+    if render.len() < 32 {
+        return Err("render buffer shorter than 32 values".into());
+    }
+    for (slot, key) in [(0, "width"), (1, "height")] {
+        if let Some(v) = request.get(key).and_then(Value::as_f64) {
+            render[slot] = v;
+        }
+    }
+
+    let params = raymarcher::params_from_buffer(&render);
+    let formula = crate::build_formula(&render, &formula, &params);
+    let config = paint::paint_config_from_buffer(&paint_params);
+    let len = params.buffer_len(4).ok_or_else(|| scene::SceneError::ImageTooLarge(params.width, params.height).to_string())?;
+    let mut rgba = vec![0u8; len];
+    let gbuffer = preview::render_frame(&params, &formula, &config, &mut rgba);
+
+    let mut bytes = Vec::with_capacity(gbuffer.len() * 18);
+    for e in &gbuffer {
+        repro::push_entry_bytes(&mut bytes, e);
+    }
+    let gbuffer_crc = png::crc32(&bytes);
+    let image_crc = png::crc32(&rgba);
+
+    let recorded = |key: &str| request.get(key).and_then(Value::as_u64).map(|v| v as u32);
+    let matches = match (recorded("gbuffer_crc"), recorded("image_crc")) {
+        (None, None) => None,
+        (g, i) => Some(g.is_none_or(|g| g == gbuffer_crc) && i.is_none_or(|i| i == image_crc)),
+    };
+    let name = request.get("name").and_then(Value::as_str).unwrap_or_default().to_string();
+    Ok(Checkpoint { step, name, gbuffer_crc, image_crc, matches })
+}
+
+fn number_array(scene: &Value, key: &str) -> Result<Vec<f64>, String> {
+    let values = scene.get(key).and_then(Value::as_array).ok_or_else(|| format!("scene has no `{key}` array"))?;
+    values.iter().map(|v| v.as_f64().ok_or_else(|| format!("non-numeric value in `{key}`"))).collect()
+}
+
+/// Apply a JSON Patch (add / remove / replace / test) to `doc`.
+pub fn apply_patch(doc: &mut Value, ops: &Value) -> Result<(), String> {
+    let ops = ops.as_array().ok_or("patch must be an array")?;
+    for op in ops {
+        let kind = op.get("op").and_then(Value::as_str).ok_or("patch operation without `op`")?;
+        let path = op.get("path").and_then(Value::as_str).ok_or("patch operation without `path`")?;
+        let value = || op.get("value").cloned().ok_or_else(|| format!("`{kind}` without `value`"));
+        let (parent, last) = split_path(path)?;
+        match kind {
+            "test" => {
+                let current = doc.pointer(path).ok_or_else(|| format!("no value at {path}"))?;
+                if *current != value()? {
+                    return Err(format!("test failed at {path}"));
+                }
+            }
+            "replace" => {
+                let slot = doc.pointer_mut(path).ok_or_else(|| format!("no value at {path}"))?;
+                *slot = value()?;
+            }
+            "add" => {
+                let value = value()?;
+                match doc.pointer_mut(&parent).ok_or_else(|| format!("no container at {parent}"))? {
+                    Value::Array(items) => {
+                        let index = if last == "-" { items.len() } else { array_index(&last, items.len() + 1)? };
+                        items.insert(index, value);
+                    }
+                    Value::Object(map) => {
+                        map.insert(last, value);
+                    }
+                    _ => return Err(format!("{parent} is not a container")),
+                }
+            }
+            "remove" => match doc.pointer_mut(&parent).ok_or_else(|| format!("no container at {parent}"))? {
+                Value::Array(items) => {
+                    let index = array_index(&last, items.len())?;
+                    items.remove(index);
+                }
+                Value::Object(map) => {
+                    map.remove(&last).ok_or_else(|| format!("no value at {path}"))?;
+                }
+                _ => return Err(format!("{parent} is not a container")),
+            },
+            other => return Err(format!("unsupported patch operation `{other}`")),
+        }
+    }
+    Ok(())
+}
+
+/// Split a JSON pointer into its parent pointer and unescaped last token.
+fn split_path(path: &str) -> Result<(String, String), String> {
+    let cut = path.rfind('/').ok_or_else(|| format!("invalid path `{path}`"))?;
+    let last = path[cut + 1..].replace("~1", "/").replace("~0", "~");
+    Ok((path[..cut].to_string(), last))
+}
+
+fn array_index(token: &str, len: usize) -> Result<usize, String> {
+    match token.parse::<usize>() {
+        Ok(i) if i < len => Ok(i),
+        _ => Err(format!("array index `{token}` out of range")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(steps: Value) -> String {
+        let mut render = vec![0.0; 32];
+        render[..20].copy_from_slice(&[
+            16.0, 12.0, 0.0, 0.0, -2.5, 0.0, 0.0, 1.0, 0.3, 0.0, 0.0, 0.0, 0.3, 0.0,
+            0.001, 0.8, 50.0, 8.0, 16.0, 0.0,
+        ]);
+        render[29] = 3.0;
+        json!({ "version": 1, "scene": { "render": render, "formula": [1, 2, 1, 0], "paint": [] }, "steps": steps })
+            .to_string()
+    }
+
+    #[test]
+    fn test_patch_operations() {
+        let mut doc = json!({ "render": [1.0, 2.0], "meta": { "a~b": 1 } });
+        let ops = json!([
+            { "op": "test", "path": "/render/1", "value": 2.0 },
+            { "op": "replace", "path": "/render/0", "value": 5.0 },
+            { "op": "add", "path": "/render/-", "value": 3.0 },
+            { "op": "remove", "path": "/meta/a~0b" },
+            { "op": "add", "path": "/meta/note", "value": "x" },
+        ]);
+        apply_patch(&mut doc, &ops).unwrap();
+        assert_eq!(doc, json!({ "render": [5.0, 2.0, 3.0], "meta": { "note": "x" } }));
+
+        let failing = json!([{ "op": "test", "path": "/render/0", "value": 1.0 }]);
+        assert!(apply_patch(&mut doc, &failing).is_err());
+    }
+
+    #[test]
+    fn test_recorded_session_replays_identically() {
+        let steps = json!([
+            { "checkpoint": { "name": "start" } },
+            { "patch": [{ "op": "replace", "path": "/render/4", "value": -2.0 }],
+              "checkpoint": { "name": "closer", "width": 8, "height": 6 } },
+        ]);
+        let first = replay(&session(steps.clone())).unwrap();
+        assert_eq!(first.len(), 2);
+        assert_eq!(first[1].step, 1);
+        assert!(first.iter().all(|c| c.matches.is_none()));
+        assert_ne!(first[0].gbuffer_crc, first[1].gbuffer_crc);
+
+        // Record the CRCs and replay: every checkpoint matches
+        let mut recorded = steps;
+        for (step, c) in recorded.as_array_mut().unwrap().iter_mut().zip(&first) {
+            step["checkpoint"]["gbuffer_crc"] = json!(c.gbuffer_crc);
+            step["checkpoint"]["image_crc"] = json!(c.image_crc);
+        }
+        let again = replay(&session(recorded.clone())).unwrap();
+        assert!(again.iter().all(|c| c.matches == Some(true)));
+
+        // A diverging mutation is caught at its checkpoint
+        recorded[1]["patch"][0]["value"] = json!(-2.2);
+        let diverged = replay(&session(recorded)).unwrap();
+        assert_eq!(diverged[0].matches, Some(true));
+        assert_eq!(diverged[1].matches, Some(false));
+    }
+
+    #[test]
+    fn test_errors_name_the_step() {
+        let steps = json!([{ "patch": [{ "op": "replace", "path": "/render/999", "value": 1 }] }]);
+        let err = replay(&session(steps)).unwrap_err();
+        assert_eq!(err.step, Some(0));
+        assert!(replay("{").is_err());
+    }
+}
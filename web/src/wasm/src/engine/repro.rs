@@ -0,0 +1,141 @@
+//! Single-tile reproduction reports for rendering-artifact bug reports.
+//!
+//! `repro_tile` re-renders one tile exactly as the workers would and returns
+//! a short text report: a hash of the scene buffers, per-tile step
+//! statistics, a checksum of the resulting G-buffer entries and the full
+//! step trace of the most expensive pixel. Two machines rendering the same
+//! scene must produce identical reports, so a mismatch pinpoints the tile.
+
+use std::fmt::Write;
+
+use crate::engine::raymarcher::{self, MarchStep, RenderParams};
+use crate::engine::types::SiLight5;
+use crate::export::png;
+use crate::formulas::DistanceField;
+
+/// Steps of the worst pixel included in the report.
+pub const MAX_TRACE_STEPS: usize = 256;
+
+/// Statistics of a re-rendered tile.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TileStats {
+    pub pixels: u32,
+    pub hits: u32,
+    pub total_steps: u64,
+    /// Pixel with the most steps and its step count
+    pub worst: (u32, u32),
+    pub worst_steps: u32,
+    /// Pixels whose ray ended on a NaN or infinite DE
+    pub bad_de: u32,
+    /// CRC-32 of the tile's G-buffer entries (row-major)
+    pub gbuffer_crc: u32,
+}
+
+/// FNV-1a hash of the scene buffers (render params by bit pattern).
+pub fn scene_hash(render_params: &[f64], formula_ids: &[u32]) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+    let words = render_params.iter().map(|v| v.to_bits()).chain(formula_ids.iter().map(|&v| v as u64));
+    for word in words {
+        for byte in word.to_le_bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+/// Append the fields of a G-buffer entry as little-endian u16s (the byte
+/// order used for checksums, independent of the host).
+pub(crate) fn push_entry_bytes(bytes: &mut Vec<u8>, e: &SiLight5) {
+    for v in [e.sn_x as u16, e.sn_y as u16, e.sn_z as u16, e.z_pos, e.shadow, e.ambient,
+              e.color_gradient, e.orbit_trap, e.roughness] {
+        bytes.extend_from_slice(&v.to_le_bytes());
+    }
+}
+
+/// Render tile [x0, x1) × [y0, y1) and collect its statistics and the step
+/// trace of its worst pixel.
+pub fn render_tile_stats<F: DistanceField + ?Sized>(
+    params: &RenderParams,
+    formula: &F,
+    tile: (u32, u32, u32, u32),
+) -> (TileStats, Vec<MarchStep>) {
+    let (x0, y0) = (tile.0.min(params.width), tile.1.min(params.height));
+    let (x1, y1) = (tile.2.min(params.width), tile.3.min(params.height));
+    let mut stats = TileStats::default();
+    let mut bytes = Vec::with_capacity((x1.saturating_sub(x0) * y1.saturating_sub(y0)) as usize * std::mem::size_of::<SiLight5>());
+
+    for y in y0..y1 {
+        for x in x0..x1 {
+            let dir = raymarcher::pixel_direction(params, x as f64, y as f64);
+            let mut trace = Vec::new();
+            let mr = raymarcher::march_ray_traced(&params.camera_pos, &dir, params, formula, &mut trace);
+            let e = raymarcher::gbuffer_entry(&mr, params, formula);
+
+            stats.pixels += 1;
+            stats.hits += mr.hit as u32;
+            stats.total_steps += mr.steps as u64;
+            if trace.last().is_some_and(|s| !s.de.is_finite()) {
+                stats.bad_de += 1;
+            }
+            if stats.pixels == 1 || mr.steps > stats.worst_steps {
+                stats.worst = (x, y);
+                stats.worst_steps = mr.steps;
+            }
+            push_entry_bytes(&mut bytes, &e);
+        }
+    }
+    stats.gbuffer_crc = png::crc32(&bytes);
+
+    let mut worst_trace = Vec::new();
+    if stats.pixels > 0 {
+        let (x, y) = stats.worst;
+        let dir = raymarcher::pixel_direction(params, x as f64, y as f64);
+        raymarcher::march_ray_traced(&params.camera_pos, &dir, params, formula, &mut worst_trace);
+        worst_trace.truncate(MAX_TRACE_STEPS);
+    }
+    (stats, worst_trace)
+}
+
+/// Text report for a tile: `key=value` header lines, then one `t de` line
+/// per traced step of the worst pixel.
+pub fn repro_report(scene_hash: u64, tile: (u32, u32, u32, u32), stats: &TileStats, trace: &[MarchStep]) -> String {
+    let mut out = String::new();
+    let mean = if stats.pixels > 0 { stats.total_steps as f64 / stats.pixels as f64 } else { 0.0 };
+    let _ = writeln!(out, "mb3d-repro 1");
+    let _ = writeln!(out, "scene={scene_hash:016x}");
+    let _ = writeln!(out, "tile={},{},{},{}", tile.0, tile.1, tile.2, tile.3);
+    let _ = writeln!(out, "pixels={} hits={} bad_de={}", stats.pixels, stats.hits, stats.bad_de);
+    let _ = writeln!(out, "steps_total={} steps_mean={mean:.2}", stats.total_steps);
+    let _ = writeln!(out, "worst={},{} worst_steps={}", stats.worst.0, stats.worst.1, stats.worst_steps);
+    let _ = writeln!(out, "gbuffer_crc={:08x}", stats.gbuffer_crc);
+    let _ = writeln!(out, "trace={}", trace.len());
+    for step in trace {
+        let _ = writeln!(out, "{:e} {:e}", step.t, step.de);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formulas::hybrid::{HybridFormula, HybridMode};
+    use crate::formulas::FormulaId;
+
+    #[test]
+    fn test_report_is_deterministic() {
+        let formula = HybridFormula::new(&[(FormulaId::MandelbulbPower8, 1)], HybridMode::Alternating, 8, 16.0);
+        let params = RenderParams { width: 16, height: 16, ..Default::default() };
+        let tile = (4, 4, 8, 8);
+        let (stats, trace) = render_tile_stats(&params, &formula, tile);
+        assert_eq!(stats.pixels, 16);
+        // The trace covers every DE evaluation of the worst ray
+        assert_eq!(trace.len() as u32, stats.worst_steps + 1);
+
+        let report = repro_report(scene_hash(&[1.0], &[2]), tile, &stats, &trace);
+        let again = render_tile_stats(&params, &formula, tile);
+        assert_eq!(report, repro_report(scene_hash(&[1.0], &[2]), tile, &again.0, &again.1));
+        assert!(report.starts_with("mb3d-repro 1\n"));
+        assert_ne!(scene_hash(&[1.0], &[2]), scene_hash(&[1.0], &[3]));
+    }
+}
@@ -0,0 +1,148 @@
+//! Temporal reprojection of the previous frame's depths.
+//!
+//! While the camera moves, most surfaces visible in the new frame were also
+//! visible in the previous one. Their hit points are projected into the new
+//! view and splatted into a per-pixel start-depth buffer (nearest wins, then
+//! spread over a 3×3 neighbourhood to close small cracks). The marcher starts
+//! rays slightly before those depths; `validated_start` falls back to the
+//! camera whenever the start point is already at or inside the surface.
+
+use crate::engine::raymarcher::{self, RenderParams};
+use crate::engine::types::{SiLight5, Vec3D};
+use crate::formulas::DistanceField;
+use crate::math::math3d;
+
+/// Fraction of the reprojected depth backed off before marching.
+pub const DEPTH_MARGIN: f64 = 0.02;
+
+/// Project a world-space point into `params`' image: (x, y, distance).
+pub fn project(params: &RenderParams, p: &Vec3D) -> Option<(f64, f64, f64)> {
+    let v = math3d::vec3d_sub(p, &params.camera_pos);
+    // Solve v = s · (base + px·dx + py·dy) for (s, s·px, s·py) by Cramer's rule
+    let (b, dx, dy) = (&params.ray_dir_base, &params.ray_dx, &params.ray_dy);
+    let det = math3d::vec3d_dot(b, &math3d::vec3d_cross(dx, dy));
+    if det.abs() < 1e-300 {
+        return None;
+    }
+    let s = math3d::vec3d_dot(&v, &math3d::vec3d_cross(dx, dy)) / det;
+    if s <= 0.0 {
+        return None;
+    }
+    let px = math3d::vec3d_dot(b, &math3d::vec3d_cross(&v, dy)) / det / s;
+    let py = math3d::vec3d_dot(b, &math3d::vec3d_cross(dx, &v)) / det / s;
+    let (hw, hh) = (params.width as f64 * 0.5, params.height as f64 * 0.5);
+    Some((px * hw + hw, py * hh + hh, math3d::vec3d_length(&v)))
+}
+
+/// Start depths for the new view from the previous frame's G-buffer.
+///
+/// Returns one distance per pixel of `params`; 0 where nothing reprojected.
+pub fn reproject_depths(prev_params: &RenderParams, prev_gbuffer: &[SiLight5], params: &RenderParams) -> Vec<f32> {
+    let (w, h) = (params.width as usize, params.height as usize);
+    let mut splat = vec![f32::INFINITY; w * h];
+    for (i, px) in prev_gbuffer.iter().enumerate().take((prev_params.width * prev_params.height) as usize) {
+        if px.z_pos >= 65534 || (px.sn_x, px.sn_y, px.sn_z) == (0, 0, 0) {
+            continue;
+        }
+        let (x, y) = ((i as u32 % prev_params.width) as f64, (i as u32 / prev_params.width) as f64);
+        let dir = raymarcher::pixel_direction(prev_params, x, y);
+        let t = px.z_pos as f64 / 65535.0 * prev_params.max_ray_length;
+        let world = math3d::vec3d_add(&prev_params.camera_pos, &math3d::vec3d_scale(&dir, t));
+        if let Some((nx, ny, d)) = project(params, &world) {
+            let (nx, ny) = (nx.round(), ny.round());
+            if nx >= 0.0 && ny >= 0.0 && (nx as usize) < w && (ny as usize) < h {
+                let cell = &mut splat[ny as usize * w + nx as usize];
+                *cell = cell.min(d as f32);
+            }
+        }
+    }
+
+    // Nearest splat in the 3×3 neighbourhood, backed off by the margin and
+    // two depth quantization steps of the previous frame
+    let quantum = 2.0 * prev_params.max_ray_length / 65535.0;
+    let mut out = vec![0.0f32; w * h];
+    for y in 0..h {
+        for x in 0..w {
+            let mut nearest = f32::INFINITY;
+            for sy in y.saturating_sub(1)..(y + 2).min(h) {
+                for sx in x.saturating_sub(1)..(x + 2).min(w) {
+                    nearest = nearest.min(splat[sy * w + sx]);
+                }
+            }
+            if nearest.is_finite() {
+                out[y * w + x] = (nearest as f64 * (1.0 - DEPTH_MARGIN) - quantum).max(0.0) as f32;
+            }
+        }
+    }
+    out
+}
+
+/// Start distance `t` if the point there is clearly outside the surface,
+/// otherwise 0 (march from the camera).
+pub fn validated_start<F: DistanceField + ?Sized>(params: &RenderParams, formula: &F, dir: &Vec3D, t: f64) -> f64 {
+    if t <= 0.0 {
+        return 0.0;
+    }
+    let p = math3d::vec3d_add(&params.camera_pos, &math3d::vec3d_scale(dir, t));
+    let fr = formula.compute_de(&p);
+    if fr.inside || !fr.de.is_finite() || fr.de < params.hit_threshold(t) * 2.0 {
+        0.0
+    } else {
+        t
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::raymarcher::GBufferLayers;
+    use crate::formulas::hybrid::{HybridFormula, HybridMode};
+    use crate::formulas::FormulaId;
+
+    fn view(camera_x: f64) -> RenderParams {
+        RenderParams {
+            width: 48,
+            height: 36,
+            camera_pos: Vec3D { x: camera_x, y: 0.0, z: -2.5 },
+            ray_dx: Vec3D { x: 0.6, y: 0.0, z: 0.0 },
+            ray_dy: Vec3D { x: 0.0, y: 0.45, z: 0.0 },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_project_inverts_pixel_direction() {
+        let params = view(0.3);
+        let dir = raymarcher::pixel_direction(&params, 10.0, 7.0);
+        let p = math3d::vec3d_add(&params.camera_pos, &math3d::vec3d_scale(&dir, 2.0));
+        let (x, y, d) = project(&params, &p).unwrap();
+        assert!((x - 10.0).abs() < 1e-9 && (y - 7.0).abs() < 1e-9 && (d - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_reprojected_render_matches_full_render() {
+        let formula = HybridFormula::new(&[(FormulaId::MandelbulbPower8, 1)], HybridMode::Alternating, 8, 16.0);
+        let (prev, next) = (view(0.0), view(0.05));
+        let mut prev_gbuffer = vec![SiLight5::default(); 48 * 36];
+        raymarcher::render_scanlines(&prev, &formula, &mut prev_gbuffer, 0, 1);
+
+        let starts = reproject_depths(&prev, &prev_gbuffer, &next);
+        assert!(starts.iter().filter(|&&d| d > 0.0).count() > 48 * 36 / 4);
+
+        let mut full = vec![SiLight5::default(); 48 * 36];
+        raymarcher::render_scanlines(&next, &formula, &mut full, 0, 1);
+        let mut reprojected = vec![SiLight5::default(); 48 * 36];
+        let layers = GBufferLayers { start_depths: Some(&starts), ..Default::default() };
+        raymarcher::render_scanlines_layers(&next, &formula, &mut reprojected, layers, 0, 1);
+
+        for (i, (a, b)) in full.iter().zip(&reprojected).enumerate() {
+            let (za, zb) = (a.z_pos, b.z_pos);
+            assert_eq!(za == 65535, zb == 65535, "pixel {i}");
+            if za != 65535 {
+                let hit = za as f64 / 65535.0 * next.max_ray_length;
+                let diff = (za as f64 - zb as f64).abs() / 65535.0 * next.max_ray_length;
+                assert!(diff <= 2.0 * next.pixel_footprint(hit), "pixel {i}: {za} vs {zb}");
+            }
+        }
+    }
+}
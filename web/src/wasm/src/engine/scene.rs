@@ -0,0 +1,699 @@
+//! JSON scene descriptions.
+//!
+//! The render, formula and paint buffers address every setting by position,
+//! which is compact for workers but easy to get out of step with the
+//! TypeScript side. `SceneDescription` names the settings instead:
+//!
+//! ```json
+//! { "version": 1,
+//!   "camera": { "width": 640, "height": 480, "position": [0, 0, -2.5],
+//!               "target": [0, 0, 0], "up": [0, 1, 0], "fov": 53.13 },
+//!   "formulas": [{ "name": "Amazing Box", "iterations": 12, "params": [2.0] }],
+//!   "z0_offset": [0.1, 0, 0],
+//!   "lights": [{ "direction": [0.5, 0.5, -0.7], "color": [1, 0.9, 0.8] }],
+//!   "gradient": [{ "position": 0, "color": [0, 0, 0.3] }, { "position": 1, "color": [1, 1, 1] }],
+//!   "fog": { "density": 0.2, "color": [0.6, 0.7, 0.8] },
+//!   "cuts": [{ "shape": "plane", "normal": [0, 0, 1], "d": 0 }] }
+//! ```
+//!
+//! Every field is optional and defaults to the renderer's defaults; unknown
+//! fields are rejected so a renamed setting fails loudly. A description
+//! converts to the three buffers (`to_buffers`), so it drives every entry
+//! point, and buffers convert back (`from_buffers`) for settings it covers.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::engine::camera;
+use crate::engine::cutting::{self, Cut, CutShape};
+use crate::engine::raymarcher::{self, RenderParams};
+use crate::engine::types::Vec3D;
+use crate::engine::validate;
+use crate::formulas::{self, FormulaId};
+use crate::lighting::paint::{self, LightConfig, LightKind, PaintConfig};
+use crate::math::math3d;
+
+/// Description format version written by `to_json`.
+pub const SCENE_VERSION: u32 = 1;
+/// Most formula slots and lights the buffers carry.
+const MAX_LIGHTS: usize = 6;
+
+/// Why a description could not be used.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SceneError {
+    /// Not valid JSON, or a field has the wrong type or name
+    Json(String),
+    /// Written by a newer version
+    Version(u32),
+    /// No formula with this name
+    UnknownFormula(String),
+    /// More than the buffers can carry: (what, limit)
+    TooMany(&'static str, usize),
+    /// Zero-sized image
+    EmptyImage,
+    /// More pixels than the renderer can address: (width, height)
+    ImageTooLarge(u32, u32),
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Json(e) => write!(f, "invalid scene: {e}"),
+            SceneError::Version(v) => write!(f, "unsupported scene version {v}"),
+            SceneError::UnknownFormula(name) => write!(f, "unknown formula \"{name}\""),
+            SceneError::TooMany(what, limit) => write!(f, "a scene has at most {limit} {what}"),
+            SceneError::EmptyImage => f.write_str("camera width and height must be positive"),
+            SceneError::ImageTooLarge(w, h) => write!(f, "a {w} × {h} image is too large to render"),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+/// Look-at camera (same conventions as `camera::compute_camera_rays`).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CameraDescription {
+    pub width: u32,
+    pub height: u32,
+    pub position: [f64; 3],
+    pub target: [f64; 3],
+    pub up: [f64; 3],
+    /// Vertical field of view in degrees
+    pub fov: f64,
+}
+
+impl Default for CameraDescription {
+    fn default() -> Self {
+        let d = RenderParams::default();
+        Self {
+            width: d.width,
+            height: d.height,
+            position: vec_axes(&d.camera_pos),
+            target: [0.0; 3],
+            up: [0.0, 1.0, 0.0],
+            // params.js default: tan(fov / 2) = 0.5
+            fov: 2.0 * 0.5f64.atan().to_degrees(),
+        }
+    }
+}
+
+impl CameraDescription {
+    /// Size in bytes of the RGBA image; pixels are indexed with u32.
+    pub fn rgba_len(&self) -> Result<usize, SceneError> {
+        if self.width == 0 || self.height == 0 {
+            return Err(SceneError::EmptyImage);
+        }
+        (self.width as usize)
+            .checked_mul(self.height as usize)
+            .filter(|&pixels| pixels <= u32::MAX as usize)
+            .and_then(|pixels| pixels.checked_mul(4))
+            .ok_or(SceneError::ImageTooLarge(self.width, self.height))
+    }
+}
+
+/// Ray marching accuracy and iteration limits.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MarchDescription {
+    pub de_stop: f64,
+    pub step_width: f64,
+    pub max_ray_length: f64,
+    pub max_iterations: u32,
+    /// Squared escape radius
+    pub bailout: f64,
+    /// Grow the DE stop with the pixel cone (0 = fixed `de_stop`)
+    pub cone_scale: f64,
+    pub bin_search_steps: u32,
+}
+
+impl Default for MarchDescription {
+    fn default() -> Self {
+        let d = RenderParams::default();
+        Self {
+            de_stop: d.de_stop,
+            step_width: d.step_width,
+            max_ray_length: d.max_ray_length,
+            max_iterations: d.max_iterations,
+            bailout: d.bailout,
+            cone_scale: d.cone_scale,
+            bin_search_steps: d.bin_search_steps,
+        }
+    }
+}
+
+/// One hybrid slot.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FormulaDescription {
+    /// UI name (see `FormulaId::from_name`)
+    pub name: String,
+    #[serde(default = "one")]
+    pub iterations: u32,
+    /// Formula parameters in declaration order (empty = the formula's defaults)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub params: Vec<f64>,
+}
+
+/// How the slots combine.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HybridDescription {
+    #[default]
+    Alternating,
+    Interpolated,
+    #[serde(rename = "4d")]
+    FourD,
+}
+
+/// A cut; `removes_inside: false` keeps only the inside.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "shape", rename_all = "lowercase", deny_unknown_fields)]
+pub enum CutDescription {
+    /// Inside is `dot(p, normal) < d`
+    Plane {
+        normal: [f64; 3],
+        d: f64,
+        #[serde(default = "yes")]
+        removes_inside: bool,
+    },
+    Sphere {
+        center: [f64; 3],
+        radius: f64,
+        #[serde(default = "yes")]
+        removes_inside: bool,
+    },
+    Box {
+        min: [f64; 3],
+        max: [f64; 3],
+        #[serde(default = "yes")]
+        removes_inside: bool,
+    },
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LightKindDescription {
+    #[default]
+    Directional,
+    Point,
+    Spot,
+}
+
+/// A paint light (see `paint::LightConfig`).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LightDescription {
+    pub kind: LightKindDescription,
+    /// Toward the light; for spots the reversed cone axis
+    pub direction: [f64; 3],
+    pub color: [f64; 3],
+    pub amplitude: f64,
+    pub specular_size: f64,
+    pub specular_intensity: f64,
+    /// Point and spot lights only
+    pub position: [f64; 3],
+    pub falloff_linear: f64,
+    pub falloff_quadratic: f64,
+    /// Spot cone half-angles in degrees
+    pub spot_inner: f64,
+    pub spot_outer: f64,
+    pub enabled: bool,
+}
+
+impl Default for LightDescription {
+    fn default() -> Self {
+        Self::from_config(&LightConfig::default())
+    }
+}
+
+impl LightDescription {
+    fn from_config(l: &LightConfig) -> Self {
+        Self {
+            kind: match l.kind {
+                LightKind::Directional => LightKindDescription::Directional,
+                LightKind::Point => LightKindDescription::Point,
+                LightKind::Spot => LightKindDescription::Spot,
+            },
+            direction: vec_axes(&l.direction),
+            color: rgb_axes(l.color),
+            amplitude: l.amplitude,
+            specular_size: l.specular_size,
+            specular_intensity: l.specular_intensity,
+            position: vec_axes(&l.position),
+            falloff_linear: l.falloff_linear,
+            falloff_quadratic: l.falloff_quadratic,
+            spot_inner: l.spot_inner.to_degrees(),
+            spot_outer: l.spot_outer.to_degrees(),
+            enabled: l.enabled,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AmbientDescription {
+    pub color: [f64; 3],
+    pub intensity: f64,
+    /// How strongly ambient occlusion darkens
+    pub occlusion: f64,
+}
+
+impl Default for AmbientDescription {
+    fn default() -> Self {
+        let d = PaintConfig::default();
+        Self { color: rgb_axes(d.ambient_color), intensity: d.ambient_intensity, occlusion: d.ao_strength }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FogDescription {
+    /// 0 = no fog
+    pub density: f64,
+    pub color: [f64; 3],
+}
+
+impl Default for FogDescription {
+    fn default() -> Self {
+        let d = PaintConfig::default();
+        Self { density: d.fog_density, color: rgb_axes(d.fog_color) }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GradientStopDescription {
+    pub position: f64,
+    pub color: [f64; 3],
+}
+
+/// A whole scene. See the module docs for the JSON form.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SceneDescription {
+    pub version: u32,
+    pub camera: CameraDescription,
+    pub march: MarchDescription,
+    /// Empty = Mandelbulb Power 8
+    pub formulas: Vec<FormulaDescription>,
+    pub hybrid: HybridDescription,
+    /// Julia constant for every slot (None = Mandelbrot mode)
+    pub julia: Option<[f64; 3]>,
+    /// Initial orbit offset added to z0 (zero = off)
+    pub z0_offset: [f64; 3],
+    pub cuts: Vec<CutDescription>,
+    pub lights: Vec<LightDescription>,
+    pub ambient: AmbientDescription,
+    /// Surface color stops (empty = default gradient)
+    pub gradient: Vec<GradientStopDescription>,
+    pub fog: FogDescription,
+    pub background: [f64; 3],
+}
+
+impl Default for SceneDescription {
+    fn default() -> Self {
+        let d = PaintConfig::default();
+        Self {
+            version: SCENE_VERSION,
+            camera: CameraDescription::default(),
+            march: MarchDescription::default(),
+            formulas: Vec::new(),
+            hybrid: HybridDescription::default(),
+            julia: None,
+            z0_offset: [0.0; 3],
+            cuts: Vec::new(),
+            lights: d.lights.iter().map(LightDescription::from_config).collect(),
+            ambient: AmbientDescription::default(),
+            gradient: Vec::new(),
+            fog: FogDescription::default(),
+            background: rgb_axes(d.bg_color),
+        }
+    }
+}
+
+/// The positional buffers the render and paint entry points take.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SceneBuffers {
+    pub render_params: Vec<f64>,
+    pub formula_ids: Vec<u32>,
+    pub paint_params: Vec<f64>,
+}
+
+fn one() -> u32 {
+    1
+}
+
+fn yes() -> bool {
+    true
+}
+
+fn vec3(v: [f64; 3]) -> Vec3D {
+    Vec3D { x: v[0], y: v[1], z: v[2] }
+}
+
+fn vec_axes(v: &Vec3D) -> [f64; 3] {
+    [v.x, v.y, v.z]
+}
+
+fn rgb_axes(c: (f64, f64, f64)) -> [f64; 3] {
+    [c.0, c.1, c.2]
+}
+
+impl SceneDescription {
+    /// Parse and validate a JSON description.
+    pub fn from_json(json: &str) -> Result<Self, SceneError> {
+        let scene: SceneDescription = serde_json::from_str(json).map_err(|e| SceneError::Json(e.to_string()))?;
+        if scene.version > SCENE_VERSION {
+            return Err(SceneError::Version(scene.version));
+        }
+        Ok(scene)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(&SceneDescription { version: SCENE_VERSION, ..self.clone() })
+            .expect("scene descriptions always serialize")
+    }
+
+    fn rays(&self) -> camera::CameraRays {
+        let c = &self.camera;
+        let (pos, target, up) = (vec3(c.position), vec3(c.target), vec3(c.up));
+        camera::compute_camera_rays(&pos, &target, &up, c.fov.to_radians(), c.width, c.height)
+    }
+
+    /// The three buffers, stamped with their layout version (see
+    /// `raymarcher::params_from_buffer`, `build_formula_from_ids` in lib.rs
+    /// and `paint::paint_config_from_buffer`).
+    pub fn to_buffers(&self) -> Result<SceneBuffers, SceneError> {
+        self.camera.rgba_len()?;
+        for (what, len, limit) in [
+            ("formulas", self.formulas.len(), formulas::MAX_HYBRID_SLOTS),
+            ("lights", self.lights.len(), MAX_LIGHTS),
+            ("cuts", self.cuts.len(), cutting::MAX_CUTS),
+        ] {
+            if len > limit {
+                return Err(SceneError::TooMany(what, limit));
+            }
+        }
+        let mut buffers = SceneBuffers {
+            render_params: self.render_params(),
+            formula_ids: self.formula_ids()?,
+            paint_params: self.paint_params(),
+        };
+        validate::stamp_render_params(&mut buffers.render_params);
+        validate::stamp_formula_ids(&mut buffers.formula_ids);
+        validate::stamp_paint_params(&mut buffers.paint_params);
+        Ok(buffers)
+    }
+
+    fn render_params(&self) -> Vec<f64> {
+        let (c, m) = (&self.camera, &self.march);
+        let rays = self.rays();
+        let julia = self.julia.unwrap_or([0.0; 3]);
+        let mut p = vec![c.width as f64, c.height as f64];
+        p.extend(c.position);
+        p.extend(rays.to_array());
+        p.extend([
+            m.de_stop,
+            m.step_width,
+            m.max_ray_length,
+            m.max_iterations as f64,
+            m.bailout,
+            m.cone_scale,
+            self.julia.is_some() as u8 as f64,
+        ]);
+        p.extend(julia);
+        // Legacy cutting plane off: cuts go in the table
+        p.extend([0.0; 5]);
+        p.push(m.bin_search_steps as f64);
+
+        // Slots 30..41 at their defaults, so the cut table can follow
+        p.extend_from_slice(&raymarcher::default_params_buffer()[p.len()..raymarcher::CUTS_OFFSET]);
+        p.push(self.cuts.len() as f64);
+        for cut in &self.cuts {
+            let (kind, removes_inside, a, b) = match *cut {
+                CutDescription::Plane { normal, d, removes_inside } => (0.0, removes_inside, normal, [d, 0.0, 0.0]),
+                CutDescription::Sphere { center, radius, removes_inside } => (1.0, removes_inside, center, [radius, 0.0, 0.0]),
+                CutDescription::Box { min, max, removes_inside } => (2.0, removes_inside, min, max),
+            };
+            p.extend([kind, removes_inside as u8 as f64]);
+            p.extend(a);
+            p.extend(b);
+        }
+        p
+    }
+
+    fn formula_ids(&self) -> Result<Vec<u32>, SceneError> {
+        let mut slots = Vec::new();
+        for f in &self.formulas {
+            let id = FormulaId::from_name(&f.name);
+            if id == FormulaId::None {
+                return Err(SceneError::UnknownFormula(f.name.clone()));
+            }
+            slots.push((id, f.iterations));
+        }
+        let ids = formulas::FormulaIds {
+            slots,
+            mode: Some(match self.hybrid {
+                HybridDescription::Alternating => 0,
+                HybridDescription::Interpolated => 1,
+                HybridDescription::FourD => 2,
+            }),
+            sections: self
+                .formulas
+                .iter()
+                .enumerate()
+                .filter(|(_, f)| !f.params.is_empty())
+                .map(|(slot, f)| formulas::ParamSection {
+                    tag: crate::SECTION_FORMULA_PARAMS,
+                    slot: slot as u32,
+                    values: f.params.clone(),
+                })
+                .collect(),
+        };
+        Ok(crate::with_z0_offset_section(&ids.encode(), &vec3(self.z0_offset)))
+    }
+
+    fn paint_params(&self) -> Vec<f64> {
+        let rays = self.rays();
+        let mut p = vec![self.lights.len() as f64];
+        let mut sections = Vec::new();
+        for (i, l) in self.lights.iter().enumerate() {
+            p.extend(l.direction);
+            p.extend(l.color);
+            p.extend([l.amplitude, l.specular_size, l.specular_intensity]);
+            if !l.enabled {
+                sections.extend([paint::SECTION_LIGHT_ENABLED as f64, 2.0, i as f64, 0.0]);
+            }
+            if l.kind != LightKindDescription::Directional {
+                sections.extend([paint::SECTION_LIGHT_SOURCE as f64, 9.0, i as f64, l.kind as u8 as f64]);
+                sections.extend(l.position);
+                sections.extend([l.falloff_linear, l.falloff_quadratic, l.spot_inner.to_radians(), l.spot_outer.to_radians()]);
+            }
+        }
+        let a = &self.ambient;
+        p.extend(a.color);
+        p.push(a.intensity);
+        p.push(self.fog.density);
+        p.extend(self.fog.color);
+        p.extend(self.background);
+        p.extend(vec_axes(&rays.dir_base));
+        p.push(a.occlusion);
+        p.push(self.gradient.len() as f64);
+        for stop in &self.gradient {
+            p.push(stop.position);
+            p.extend(stop.color);
+        }
+        // Positional lights and height fog need the view
+        sections.extend([paint::SECTION_VIEW as f64, 13.0]);
+        sections.extend(self.camera.position);
+        sections.extend(rays.to_array());
+        sections.push(self.march.max_ray_length);
+        p.extend(sections);
+        p
+    }
+
+    /// Describe existing buffers. Settings the description does not cover
+    /// (formula sections other than parameters, AO, paint sections other
+    /// than lights and the view, ...) are left out.
+    pub fn from_buffers(render_params: &[f64], formula_ids: &[u32], paint_params: &[f64]) -> Self {
+        let r = raymarcher::params_from_buffer(render_params);
+        let config = paint::paint_config_from_buffer(paint_params);
+
+        let forward = math3d::vec3d_normalized(&r.ray_dir_base);
+        let up = math3d::vec3d_normalized(&math3d::vec3d_scale(&r.ray_dy, -1.0));
+        let half = math3d::vec3d_length(&r.ray_dy) / math3d::vec3d_length(&r.ray_dir_base).max(1e-300);
+        let camera = CameraDescription {
+            width: r.width,
+            height: r.height,
+            position: vec_axes(&r.camera_pos),
+            target: vec_axes(&math3d::vec3d_add(&r.camera_pos, &forward)),
+            up: vec_axes(&up),
+            fov: (2.0 * half.atan()).to_degrees(),
+        };
+        let march = MarchDescription {
+            de_stop: render_params.get(14).copied().unwrap_or(r.de_stop),
+            step_width: r.step_width,
+            max_ray_length: r.max_ray_length,
+            max_iterations: r.max_iterations,
+            bailout: r.bailout,
+            cone_scale: r.cone_scale,
+            bin_search_steps: r.bin_search_steps,
+        };
+        let cuts = r.cuts.iter().map(|cut: &Cut| match cut.shape {
+            CutShape::Plane { normal, d } => CutDescription::Plane { normal: vec_axes(&normal), d, removes_inside: cut.removes_inside },
+            CutShape::Sphere { center, radius } => {
+                CutDescription::Sphere { center: vec_axes(&center), radius, removes_inside: cut.removes_inside }
+            }
+            CutShape::Box { min, max } => {
+                CutDescription::Box { min: vec_axes(&min), max: vec_axes(&max), removes_inside: cut.removes_inside }
+            }
+        });
+
+        let decoded = formulas::FormulaIds::decode(formula_ids);
+        let mut formulas: Vec<_> = decoded
+            .slots
+            .iter()
+            .map(|(id, iterations)| FormulaDescription { name: id.info().name.into(), iterations: *iterations, params: Vec::new() })
+            .collect();
+        let hybrid = match decoded.mode {
+            Some(1) => HybridDescription::Interpolated,
+            Some(2) => HybridDescription::FourD,
+            _ => HybridDescription::Alternating,
+        };
+        let mut z0_offset = [0.0; 3];
+        for section in decoded.sections {
+            match (section.tag, formulas.get_mut(section.slot as usize)) {
+                (crate::SECTION_FORMULA_PARAMS, Some(f)) => f.params = section.values,
+                (crate::SECTION_Z0_OFFSET, _) if section.values.len() >= 3 => {
+                    z0_offset = [section.values[0], section.values[1], section.values[2]];
+                }
+                _ => {}
+            }
+        }
+
+        let default_stops = paint::PaintConfig::default().gradient.stops;
+        let stops = &config.gradient.stops;
+        let is_default = stops.len() == default_stops.len()
+            && stops.iter().zip(&default_stops).all(|(a, b)| (a.position, a.r, a.g, a.b) == (b.position, b.r, b.g, b.b));
+        let gradient = if is_default {
+            Vec::new()
+        } else {
+            stops.iter().map(|s| GradientStopDescription { position: s.position, color: [s.r, s.g, s.b] }).collect()
+        };
+
+        SceneDescription {
+            version: SCENE_VERSION,
+            camera,
+            march,
+            formulas,
+            hybrid,
+            julia: raymarcher::julia_from_buffer(render_params).map(|c| vec_axes(&c)),
+            z0_offset,
+            cuts: cuts.collect(),
+            lights: config.lights.iter().map(LightDescription::from_config).collect(),
+            ambient: AmbientDescription {
+                color: rgb_axes(config.ambient_color),
+                intensity: config.ambient_intensity,
+                occlusion: config.ao_strength,
+            },
+            gradient,
+            fog: FogDescription { density: config.fog_density, color: rgb_axes(config.fog_color) },
+            background: rgb_axes(config.bg_color),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCENE: &str = r#"{
+        "version": 1,
+        "camera": { "width": 64, "height": 48, "position": [0, 0.5, -3], "fov": 40 },
+        "march": { "max_iterations": 10, "de_stop": 0.001 },
+        "formulas": [
+            { "name": "Amazing Box", "iterations": 2, "params": [-1.5] },
+            { "name": "Mandelbulb Power 8" }
+        ],
+        "hybrid": "interpolated",
+        "julia": [0.1, 0.2, 0.3],
+        "z0_offset": [0.25, 0, -0.5],
+        "cuts": [{ "shape": "box", "min": [-1, -1, -1], "max": [1, 1, 0], "removes_inside": false }],
+        "lights": [
+            { "direction": [0, 0, -1], "color": [1, 0.5, 0.25] },
+            { "kind": "point", "position": [1, 2, 3], "falloff_quadratic": 0.5, "enabled": false }
+        ],
+        "gradient": [{ "position": 0, "color": [0, 0, 0] }, { "position": 1, "color": [1, 1, 1] }],
+        "fog": { "density": 0.25, "color": [0.5, 0.6, 0.7] }
+    }"#;
+
+    #[test]
+    fn test_json_converts_to_buffers() {
+        let buffers = SceneDescription::from_json(SCENE).unwrap().to_buffers().unwrap();
+
+        let r = raymarcher::params_from_buffer(&buffers.render_params);
+        assert_eq!((r.width, r.height, r.max_iterations, r.de_stop), (64, 48, 10, 0.001));
+        assert_eq!(r.camera_pos, Vec3D { x: 0.0, y: 0.5, z: -3.0 });
+        let half = math3d::vec3d_length(&r.ray_dy) / math3d::vec3d_length(&r.ray_dir_base);
+        assert!((half - 20f64.to_radians().tan()).abs() < 1e-12);
+        assert_eq!(raymarcher::julia_from_buffer(&buffers.render_params), Some(Vec3D { x: 0.1, y: 0.2, z: 0.3 }));
+        assert_eq!(r.cuts.len(), 1);
+        assert!(!r.cuts[0].removes_inside);
+
+        let ids = &buffers.formula_ids;
+        assert_eq!(ids[..6], [2, FormulaId::AmazingBox as u32, 2, FormulaId::MandelbulbPower8 as u32, 1, 1]);
+        let sections = formulas::parse_param_sections(&ids[6..]);
+        assert_eq!((sections[0].tag, sections[0].slot, sections[0].values.clone()), (crate::SECTION_FORMULA_PARAMS, 0, vec![-1.5]));
+        let formula = crate::build_formula_from_ids(ids, 10, 16.0);
+        assert_eq!(formula.z0_offset, Vec3D { x: 0.25, y: 0.0, z: -0.5 });
+
+        let config = paint::paint_config_from_buffer(&buffers.paint_params);
+        assert_eq!(config.lights.len(), 2);
+        assert_eq!(config.lights[0].color, (1.0, 0.5, 0.25));
+        assert_eq!((config.lights[1].kind, config.lights[1].enabled), (LightKind::Point, false));
+        assert_eq!(config.lights[1].position, Vec3D { x: 1.0, y: 2.0, z: 3.0 });
+        assert_eq!((config.fog_density, config.fog_color), (0.25, (0.5, 0.6, 0.7)));
+        assert_eq!(config.gradient.stops.len(), 2);
+        assert_eq!(config.view.as_ref().unwrap().camera_pos, r.camera_pos);
+        assert_eq!(validate::validate(&buffers.render_params, ids, &buffers.paint_params), []);
+    }
+
+    #[test]
+    fn test_buffers_describe_the_same_scene() {
+        let scene = SceneDescription::from_json(SCENE).unwrap();
+        let b = scene.to_buffers().unwrap();
+        let back = SceneDescription::from_buffers(&b.render_params, &b.formula_ids, &b.paint_params);
+        assert_eq!((&back.formulas, back.hybrid, back.julia), (&scene.formulas, scene.hybrid, scene.julia));
+        assert_eq!(back.z0_offset, [0.25, 0.0, -0.5]);
+        assert_eq!((&back.cuts, &back.gradient, &back.fog), (&scene.cuts, &scene.gradient, &scene.fog));
+        assert_eq!(back.lights[1].kind, LightKindDescription::Point);
+        assert!(!back.lights[1].enabled);
+        // The look-at target is only known as a direction
+        assert!((back.camera.fov - 40.0).abs() < 1e-9);
+        let forward = math3d::vec3d_sub(&vec3(back.camera.target), &vec3(back.camera.position));
+        let expected = math3d::vec3d_normalized(&Vec3D { x: 0.0, y: -0.5, z: 3.0 });
+        assert!(math3d::vec3d_length(&math3d::vec3d_sub(&forward, &expected)) < 1e-12);
+        assert_eq!(SceneDescription::from_json(&scene.to_json()).unwrap(), scene);
+
+        // The empty description is the renderer's default scene
+        let empty = SceneDescription::from_json("{}").unwrap();
+        assert_eq!(empty, SceneDescription::default());
+        let d = empty.to_buffers().unwrap();
+        assert_eq!(SceneDescription::from_buffers(&d.render_params, &d.formula_ids, &d.paint_params).gradient, []);
+    }
+
+    #[test]
+    fn test_invalid_descriptions_are_rejected() {
+        let err = |json: &str| SceneDescription::from_json(json).and_then(|s| s.to_buffers()).unwrap_err();
+        assert!(matches!(err(r#"{ "camera": { "zoom": 2 } }"#), SceneError::Json(e) if e.contains("zoom")));
+        assert_eq!(err(r#"{ "version": 2 }"#), SceneError::Version(2));
+        assert_eq!(err(r#"{ "formulas": [{ "name": "Mandelbox" }] }"#), SceneError::UnknownFormula("Mandelbox".into()));
+        assert_eq!(err(r#"{ "camera": { "width": 0 } }"#), SceneError::EmptyImage);
+        let huge = format!(r#"{{ "camera": {{ "width": {0}, "height": {0} }} }}"#, u32::MAX);
+        assert_eq!(err(&huge), SceneError::ImageTooLarge(u32::MAX, u32::MAX));
+        let lights = format!(r#"{{ "lights": [{}] }}"#, ["{}"; 7].join(","));
+        assert_eq!(err(&lights), SceneError::TooMany("lights", 6));
+    }
+}
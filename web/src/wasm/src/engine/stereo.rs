@@ -0,0 +1,130 @@
+//! Stereo pairs — parallel off-axis cameras with a convergence distance.
+//!
+//! Each eye is moved half the inter-ocular distance along the camera's right
+//! vector and its frustum is shifted so that objects at the convergence
+//! distance have zero parallax (no toe-in, so no vertical parallax).
+
+use crate::engine::raymarcher::{self, RenderParams};
+use crate::engine::types::SiLight5;
+use crate::formulas::hybrid::HybridFormula;
+use crate::math::math3d;
+
+/// Stereo camera settings.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StereoSettings {
+    /// Distance between the eyes in world units
+    pub eye_distance: f64,
+    /// Distance of the zero-parallax plane (0 = parallel, everything in front of the screen)
+    pub convergence: f64,
+}
+
+impl Default for StereoSettings {
+    fn default() -> Self {
+        Self { eye_distance: 0.05, convergence: 2.5 }
+    }
+}
+
+/// Which eye to render.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Eye {
+    Left,
+    Right,
+}
+
+impl Eye {
+    pub fn from_u32(v: u32) -> Self {
+        if v == 0 { Eye::Left } else { Eye::Right }
+    }
+
+    fn sign(self) -> f64 {
+        match self {
+            Eye::Left => -1.0,
+            Eye::Right => 1.0,
+        }
+    }
+}
+
+/// Render parameters for one eye of the pair described by `params.stereo`.
+pub fn eye_params(params: &RenderParams, eye: Eye) -> RenderParams {
+    let settings = params.stereo;
+    let right = math3d::vec3d_normalized(&params.ray_dx);
+    let half = settings.eye_distance * 0.5 * eye.sign();
+
+    let mut out = params.clone();
+    out.camera_pos = math3d::vec3d_add(&params.camera_pos, &math3d::vec3d_scale(&right, half));
+    if settings.convergence > 0.0 {
+        // Shift the frustum back toward the centre line so the image planes coincide at the convergence distance
+        let shift = -half / settings.convergence * math3d::vec3d_length(&params.ray_dir_base);
+        out.ray_dir_base = math3d::vec3d_add(&params.ray_dir_base, &math3d::vec3d_scale(&right, shift));
+    }
+    out
+}
+
+/// Render interleaved scanlines of both eyes side by side into a double-wide
+/// G-buffer (2 * width * height entries, left eye in the left half).
+pub fn render_side_by_side(
+    params: &RenderParams,
+    formula: &HybridFormula,
+    gbuffer: &mut [SiLight5],
+    worker_id: u32,
+    worker_count: u32,
+) -> u32 {
+    let eyes = [eye_params(params, Eye::Left), eye_params(params, Eye::Right)];
+    let w = params.width;
+    let mut rows = 0;
+    let mut y = worker_id;
+    while y < params.height {
+        for (e, eye) in eyes.iter().enumerate() {
+            for x in 0..w {
+                let idx = (y * w * 2 + e as u32 * w + x) as usize;
+                if let Some(px) = gbuffer.get_mut(idx) {
+                    *px = raymarcher::render_pixel(eye, formula, x, y);
+                }
+            }
+        }
+        rows += 1;
+        y += worker_count.max(1);
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::types::Vec3D;
+
+    fn center_direction(params: &RenderParams) -> Vec3D {
+        raymarcher::pixel_direction(params, params.width as f64 * 0.5, params.height as f64 * 0.5)
+    }
+
+    #[test]
+    fn test_eyes_converge_at_convergence_distance() {
+        let params = RenderParams {
+            stereo: StereoSettings { eye_distance: 0.1, convergence: 3.0 },
+            ..Default::default()
+        };
+        let left = eye_params(&params, Eye::Left);
+        let right = eye_params(&params, Eye::Right);
+        assert!((left.camera_pos.x + 0.05).abs() < 1e-12);
+        assert!((right.camera_pos.x - 0.05).abs() < 1e-12);
+
+        // Both centre rays reach the same point on the convergence plane
+        let hit = |p: &RenderParams| {
+            let d = center_direction(p);
+            let t = 3.0 / d.z;
+            p.camera_pos.x + d.x * t
+        };
+        assert!(hit(&left).abs() < 1e-9);
+        assert!(hit(&right).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_parallel_eyes_keep_direction() {
+        let params = RenderParams {
+            stereo: StereoSettings { eye_distance: 0.1, convergence: 0.0 },
+            ..Default::default()
+        };
+        let left = eye_params(&params, Eye::Left);
+        assert_eq!(left.ray_dir_base, params.ray_dir_base);
+    }
+}
@@ -18,9 +18,10 @@ pub struct SiLight5 {
     pub z_pos: u16,
     /// Hard shadow bitfield
     pub shadow: u16,
-    /// Ambient occlusion value
+    /// Ambient occlusion value (misses: closest approach, see `miss_distance`)
     pub ambient: u16,
-    /// Smooth iteration gradient for coloring
+    /// Smooth iteration gradient for coloring (low 15 bits); bit 15 flags
+    /// interior hits (MB3D SIgradient convention)
     pub color_gradient: u16,
     /// Orbit trap color index
     pub orbit_trap: u16,
@@ -28,9 +29,182 @@ pub struct SiLight5 {
     pub roughness: u16,
 }
 
+impl SiLight5 {
+    /// Bit of the first per-light hard shadow flag in `shadow` (one bit per light).
+    pub const SHADOW_SHIFT: u32 = 10;
+
+    /// Material id stored in the high byte of `roughness`.
+    pub fn material_id(&self) -> u8 {
+        (self.roughness >> 8) as u8
+    }
+
+    /// Replace the material id, keeping the roughness byte.
+    pub fn set_material_id(&mut self, id: u8) {
+        self.roughness = (self.roughness & 0x00FF) | ((id as u16) << 8);
+    }
+
+    /// Is light `index` (0..6) occluded at this pixel?
+    pub fn in_shadow(&self, index: usize) -> bool {
+        index < 6 && self.shadow & (1 << (Self::SHADOW_SHIFT as usize + index)) != 0
+    }
+
+    /// Flag light `index` (0..6) as occluded.
+    pub fn set_in_shadow(&mut self, index: usize) {
+        if index < 6 {
+            self.shadow |= 1 << (Self::SHADOW_SHIFT as usize + index);
+        }
+    }
+
+    /// Bit of `color_gradient` flagging hits on the inside of the set.
+    pub const INSIDE_FLAG: u16 = 0x8000;
+
+    /// Store the gradient position in [0, 1] and the interior flag.
+    pub fn set_gradient(&mut self, position: f64, inside: bool) {
+        let steps = (position.clamp(0.0, 1.0) * 32767.0) as u16;
+        self.color_gradient = steps | if inside { Self::INSIDE_FLAG } else { 0 };
+    }
+
+    /// Gradient position in [0, 1].
+    pub fn gradient_position(&self) -> f64 {
+        (self.color_gradient & !Self::INSIDE_FLAG) as f64 / 32767.0
+    }
+
+    /// Was the surface hit on the inside of the set (orbit never escaped)?
+    pub fn is_inside(&self) -> bool {
+        self.color_gradient & Self::INSIDE_FLAG != 0
+    }
+
+    /// Resolution of the stored miss distance (steps per pixel width).
+    pub const MISS_DISTANCE_SCALE: f64 = 256.0;
+
+    /// Store a miss ray's closest approach to the surface, in pixel widths.
+    ///
+    /// Kept inverted in `ambient`, so plain sentinel misses (ambient 0) read
+    /// as the farthest representable distance (256 px).
+    pub fn set_miss_distance(&mut self, pixels: f64) {
+        let steps = (pixels.max(0.0) * Self::MISS_DISTANCE_SCALE).min(65535.0);
+        self.ambient = 65535 - steps as u16;
+    }
+
+    /// Closest approach of a miss ray to the surface, in pixel widths.
+    pub fn miss_distance(&self) -> f64 {
+        (65535 - self.ambient) as f64 / Self::MISS_DISTANCE_SCALE
+    }
+
+    /// Bits of `shadow` below the light flags holding the curvature.
+    pub const CURVATURE_MASK: u16 = (1 << Self::SHADOW_SHIFT) - 1;
+
+    /// Store normalized curvature in [-1, 1] (negative = crevice). Code 0
+    /// is reserved for "not computed".
+    pub fn set_curvature(&mut self, curvature: f64) {
+        let code = (512.0 + curvature.clamp(-1.0, 1.0) * 511.0).round() as u16;
+        self.shadow = (self.shadow & !Self::CURVATURE_MASK) | code;
+    }
+
+    /// Normalized curvature in [-1, 1] (0 when not computed).
+    pub fn curvature(&self) -> f64 {
+        match self.shadow & Self::CURVATURE_MASK {
+            0 => 0.0,
+            code => (code as f64 - 512.0) / 511.0,
+        }
+    }
+
+    /// Transmission-layer entries keep the interior path length in
+    /// `ambient`, so `roughness` still holds the seen surface's material;
+    /// the seen surface is shaded unoccluded. Path in world units, stored
+    /// as IEEE half-float bits.
+    pub fn interior_path(&self) -> f64 {
+        let exp = (self.ambient >> 10) as i32 & 0x1F;
+        let mant = (self.ambient & 0x3FF) as f64;
+        match exp {
+            0 => mant * 2f64.powi(-24),
+            _ => (1.0 + mant / 1024.0) * 2f64.powi(exp - 15),
+        }
+    }
+
+    /// Store the interior path length in world units (clamped to the
+    /// largest finite half float, 65504).
+    pub fn set_interior_path(&mut self, distance: f64) {
+        if distance.is_nan() || distance <= 0.0 {
+            self.ambient = 0;
+            return;
+        }
+        let bits = (distance as f32).to_bits();
+        let exp = ((bits >> 23) & 0xFF) as i32 - 127 + 15;
+        let mant = bits & 0x7F_FFFF;
+        self.ambient = if exp >= 31 {
+            0x7BFF
+        } else if exp <= 0 {
+            // Subnormal half; anything below 2^-24 flushes to zero
+            if exp < -10 { 0 } else { ((mant | 0x80_0000) >> (14 - exp)) as u16 }
+        } else {
+            ((exp as u16) << 10) | (mant >> 13) as u16
+        };
+    }
+}
+
+/// Layout of the per-pixel G-buffer records.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GBufferFormat {
+    /// `SiLight5`, 18 bytes
+    #[default]
+    Packed,
+    /// `SiLight6`, 34 bytes
+    Extended,
+}
+
+impl GBufferFormat {
+    pub fn from_u32(v: u32) -> Self {
+        if v == 1 { GBufferFormat::Extended } else { GBufferFormat::Packed }
+    }
+
+    /// Bytes per pixel.
+    pub fn record_size(self) -> usize {
+        match self {
+            GBufferFormat::Packed => std::mem::size_of::<SiLight5>(),
+            GBufferFormat::Extended => std::mem::size_of::<SiLight6>(),
+        }
+    }
+}
+
+/// Extended per-pixel G-buffer entry (34 bytes): the packed entry plus the
+/// world position at f32 precision, a second orbit-trap channel and a full
+/// material id byte.
+///
+/// The packed part is filled exactly as in the 18-byte format (its
+/// `roughness` high byte repeats `material`), so either layout paints the
+/// same; the extra fields replace the depth-reconstructed position and add
+/// a coloring source.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Default)]
+pub struct SiLight6 {
+    pub base: SiLight5,
+    /// World-space hit position (zero for misses)
+    pub position: [f32; 3],
+    /// Point trap: smallest orbit radius r, stored as r / (1 + r)
+    pub orbit_trap2: u16,
+    /// Material id
+    pub material: u8,
+    /// Reserved, zero
+    pub flags: u8,
+}
+
+impl SiLight6 {
+    /// World-space hit position.
+    pub fn world_position(&self) -> Vec3D {
+        let p = self.position;
+        Vec3D { x: p[0] as f64, y: p[1] as f64, z: p[2] as f64 }
+    }
+
+    /// Second trap channel in [0, 1].
+    pub fn trap2(&self) -> f64 {
+        self.orbit_trap2 as f64 / 65535.0
+    }
+}
+
 /// 3D vector with f64 precision — port of TVec3D.
 #[repr(C, align(16))]
-#[derive(Clone, Copy, Default, Debug)]
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
 pub struct Vec3D {
     pub x: f64,
     pub y: f64,
@@ -44,96 +218,307 @@ pub struct Matrix3 {
     pub m: [[f64; 3]; 3],
 }
 
-/// Light source definition — port of TLight8 (32 bytes).
+/// MB3D's 7-byte double: the top 7 bytes of a little-endian f64 (the low
+/// mantissa byte is dropped).
+pub type Double7B = [u8; 7];
+
+/// MB3D's 2-byte float: mantissa and decimal exponent as signed bytes,
+/// value = mantissa · 10^(exponent − 1).
+pub type ShortFloat = [i8; 2];
+
+/// Decode a 7-byte packed double.
+pub fn d7b_to_f64(d: &Double7B) -> f64 {
+    let mut bytes = [0u8; 8];
+    bytes[1..].copy_from_slice(d);
+    f64::from_le_bytes(bytes)
+}
+
+/// Encode a 7-byte packed double (the lowest mantissa byte is dropped).
+pub fn f64_to_d7b(v: f64) -> Double7B {
+    v.to_le_bytes()[1..].try_into().unwrap()
+}
+
+/// Decode a 2-byte short float.
+pub fn short_float_to_f64(sf: ShortFloat) -> f64 {
+    sf[0] as f64 * 10f64.powi((sf[1] as i32).clamp(-25, 25) - 1)
+}
+
+/// Encode a 2-byte short float: two significant digits and a decimal
+/// exponent (MB3D's `SingleToShortFloat`).
+pub fn f64_to_short_float(v: f64) -> ShortFloat {
+    if v.abs() < 1e-45 {
+        return [0, 0];
+    }
+    if v.abs() > 1e38 {
+        return [99, 38];
+    }
+    let (mut m, mut e) = (v, 0i8);
+    while m.abs() >= 9.95 {
+        m *= 0.1;
+        e += 1;
+    }
+    while m.abs() <= 0.995 {
+        m *= 10.0;
+        e -= 1;
+    }
+    [(m * 10.0).round_ties_even() as i8, e]
+}
+
+/// Light source definition — port of TLight8 (32 bytes packed).
+///
+/// For global lights `x_pos` and `y_pos` hold the light angles in radians;
+/// positional lights store their world position in all three.
 #[repr(C, packed)]
 #[derive(Clone, Copy, Default)]
 pub struct Light8 {
-    /// Position encoded as Double7B (7-byte packed double) × 3
-    pub pos: [u8; 21],
+    /// Bits 0-1: 0 on, 1 off, 2 lightmap; bits 2-4: positional light and
+    /// its visibility; bit 5: angles relative to the object; bit 6: no hard shadow
+    pub option: u8,
+    /// Bits 0-2: specular exponent 8 << n; bits 4-5: diffuse function
+    pub function: u8,
+    /// Amplitude
+    pub amp: ShortFloat,
     /// Light color (RGB bytes)
     pub color: [u8; 3],
-    /// Amplitude (u16)
-    pub amplitude: u16,
-    /// Function type
-    pub func_type: u8,
-    /// Lightmap number
-    pub lightmap: u8,
-    /// Specular size
-    pub spec_size: u8,
-    /// Reserved
-    pub reserved: [u8; 3],
-}
-
-/// Lighting parameters — port of TLightingParas9 (~408 bytes packed).
+    /// Lightmap number (0 = none)
+    pub lightmap: u16,
+    pub x_pos: Double7B,
+    pub additional_byte_ex: u8,
+    pub y_pos: Double7B,
+    pub free_byte: u8,
+    pub z_pos: Double7B,
+}
+
+/// Surface color stop — port of TLCol8 (10 bytes packed).
 #[repr(C, packed)]
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Default)]
+pub struct LCol8 {
+    /// Position on the gradient, 0..32767
+    pub position: u16,
+    /// Diffuse color, 0x00BBGGRR
+    pub color_dif: u32,
+    /// Specular color, 0x00BBGGRR; transparency in the high byte
+    pub color_spe: u32,
+}
+
+/// Interior color stop — port of TICol8 (6 bytes packed).
+#[repr(C, packed)]
+#[derive(Clone, Copy, Default)]
+pub struct ICol8 {
+    /// Position on the gradient, 0..32767
+    pub position: u16,
+    /// Color, 0x00BBGGRR
+    pub color: u32,
+}
+
+/// Lighting parameters — port of TLightingParas9 (408 bytes packed).
+#[repr(C, packed)]
+#[derive(Clone, Copy, Default)]
 pub struct LightingParas9 {
-    /// 6 light sources
+    pub var_col_z_pos: i16,
+    pub roughness_factor: u8,
+    pub color_map: u8,
+    pub dyn_fog_col2: [u8; 3],
+    pub additional_options: u8,
+    /// Trackbar positions 3..11 (fog, ambient and depth settings)
+    pub tb_pos: [i32; 9],
+    pub tb_options: u32,
+    pub fine_col_adj: [u8; 2],
+    pub pic_offset_xy: [u8; 2],
+    /// Ambient color, top
+    pub amb_col: [u8; 3],
+    pub dyn_fog_r: u8,
+    /// Ambient color, bottom
+    pub amb_col2: [u8; 3],
+    pub dyn_fog_g: u8,
+    /// Background / depth color, top
+    pub depth_col: [u8; 3],
+    pub dyn_fog_b: u8,
+    /// Background / depth color, bottom
+    pub depth_col2: [u8; 3],
+    pub pic_offset_z: u8,
     pub lights: [Light8; 6],
-    /// 10 surface color stops (RGBA × 10 = 40 bytes)
-    pub surface_colors: [u8; 40],
-    /// 4 interior color stops
-    pub interior_colors: [u8; 16],
-    /// Fog parameters
-    pub fog_density: f64,
-    pub fog_color: [u8; 3],
-    /// Background image name (64 bytes)
-    pub bg_image: [u8; 64],
-    /// Remaining lighting params (ambient, diffuse multipliers, etc.)
-    pub ambient_intensity: f64,
-    pub ambient_color: [u8; 3],
-    /// Padding to match Delphi layout
-    pub _pad: [u8; 99],
-}
-
-impl Default for LightingParas9 {
-    fn default() -> Self {
-        unsafe { std::mem::zeroed() }
-    }
+    /// Surface gradient
+    pub lcols: [LCol8; 10],
+    /// Interior gradient
+    pub icols: [ICol8; 4],
+    /// Background image file name
+    pub bg_bmp: [u8; 24],
 }
 
-/// Master scene parameter record — port of TMandHeader10 (~840 bytes packed).
+/// Master scene parameter record — port of TMandHeader10 (840 bytes packed).
 ///
-/// This is the primary serialization format for .m3p files.
+/// This is the primary serialization format for .m3p files. Offsets in the
+/// field comments are the Delphi ones.
 #[repr(C, packed)]
 #[derive(Clone, Copy)]
 pub struct MandHeader10 {
-    /// Image dimensions
+    /// File format id (20 and up for this layout)
+    pub mand_id: i32,
     pub width: i32,
     pub height: i32,
-    /// Maximum iterations
     pub iterations: i32,
-    /// 3D position (center of view)
-    pub dx_mid: f64,
-    pub dy_mid: f64,
-    pub dz_mid: f64,
-    /// Zoom level
+    /// Bits 6-9: smooth normals
+    pub options: u16,
+    pub new_options: u8,
+    pub color_on_it: u8,
+    /// #20 view range along the forward axis
+    pub z_start: f64,
+    pub z_end: f64,
+    /// #36 view center
+    pub x_mid: f64,
+    pub y_mid: f64,
+    pub z_mid: f64,
+    /// #60 4D rotation
+    pub xw_rot: f64,
+    pub yw_rot: f64,
+    pub zw_rot: f64,
+    /// #84
     pub zoom: f64,
-    /// Rotation matrix (3×3 as 9 doubles = 72 bytes)
-    pub rotation: [f64; 9],
-    /// Field of view
-    pub fov: f64,
-    /// Distance estimator stop threshold
-    pub de_stop: f64,
-    /// Julia mode flag
-    pub julia: i32,
-    /// Julia constants
-    pub julia_x: f64,
-    pub julia_y: f64,
-    pub julia_z: f64,
-    /// Lighting parameters
-    pub lighting: LightingParas9,
-    /// Remaining header fields (cutting planes, DOF, MC settings, etc.)
-    /// Placeholder — will be fully mapped as fields are needed
-    pub _remaining: [u8; 256],
+    /// Bailout radius (not squared)
+    pub r_stop: f64,
+    pub reflects_calc_time: i32,
+    pub fmix_pow: f32,
+    /// #108 vertical field of view in degrees
+    pub fov_y: f64,
+    pub tr_index: f32,
+    pub tr_scattering: f32,
+    pub mc_options: u8,
+    pub mc_diff_reflects: u8,
+    pub stereo_mode: u8,
+    pub ssao24_border_mirror_size: u8,
+    pub amb_calc_time: i32,
+    pub normals_on_de: u8,
+    pub calculate_hard_shadow: u8,
+    /// #134 binary search steps after the DE stop
+    pub steps_after_de_stop: u8,
+    pub minimum_iterations: u16,
+    pub mc_last_y: u16,
+    pub calc_1hs_soft: u8,
+    pub avrg_de_steps: i32,
+    pub avrg_its: i32,
+    /// #148 0 = MB3D lens, 1 = planar, 2 = spherical panorama
+    pub planar_optic: u8,
+    pub calc_amb_shadow_automatic: u8,
+    pub navi_min_dist: f32,
+    /// #154 world size of a pixel at the view plane, 2.1345 / (zoom · width)
+    pub step_width: f64,
+    pub vary_de_stop_on_fov: u8,
+    pub hs_calculated: u8,
+    pub dof_z_sharp: f32,
+    pub dof_clip_r: f32,
+    pub dof_aperture: f32,
+    /// #176 bits 0-2: cut at x, y, z; bits 4-6: cut below instead of above
+    pub cut_option: u8,
+    /// #177 DE stop in pixels
+    pub de_stop: f32,
+    pub calc_dof_type: u8,
+    /// #182 ray step multiplier
+    pub z_step_div: f32,
+    pub mc_depth: u8,
+    pub ssao_r_count: u8,
+    pub ao_de_dithering: u8,
+    pub image_scale: u8,
+    /// #190
+    pub is_julia: u8,
+    pub jx: f64,
+    pub jy: f64,
+    pub jz: f64,
+    pub jw: f64,
+    pub d_fog_it: u8,
+    pub mc_soft_shadow_radius: ShortFloat,
+    pub hs_max_length_multiplier: f32,
+    pub stereo_screen_width: f32,
+    pub stereo_screen_distance: f32,
+    pub stereo_min_distance: f32,
+    pub raystep_limiter: f32,
+    /// #246 view rotation scaled to `step_width`; rows: right, down, forward
+    pub vgrads: [[f64; 3]; 3],
+    pub mc_saturation: u8,
+    pub amb_shadow_threshold: f32,
+    pub calc_time: i32,
+    pub calc_hs_time: i32,
+    pub calc_ns_on_zbuf_auto: u8,
+    pub sr_amount: f32,
+    pub calc_sr_automatic: u8,
+    pub sr_reflection_count: u8,
+    pub color_mul: f32,
+    pub color2_option: u8,
+    pub vol_light_nr: u8,
+    pub calc_3d: u8,
+    pub slice_calc: u8,
+    /// #346 cutting plane positions
+    pub cut_x: f64,
+    pub cut_y: f64,
+    pub cut_z: f64,
+    pub transmission_absorption: f32,
+    pub deao_max_l: f32,
+    pub de_comb_s: f32,
+    /// #382 Delphi pointers, garbage on disk (author names in newer files)
+    pub ph_custom_f: [u32; 6],
+    pub pcf_addon: u32,
+    pub dof_z_sharp2: f32,
+    pub max_its: i32,
+    pub max_its_f2: i32,
+    pub de_mix_color_option: u8,
+    pub mc_contrast: u8,
+    pub m3d_version: f32,
+    pub tiling_options: i32,
+    /// #432
+    pub light: LightingParas9,
 }
 
 impl Default for MandHeader10 {
     fn default() -> Self {
+        // SAFETY: plain integer/float data, all-zero is a valid value
         unsafe { std::mem::zeroed() }
     }
 }
 
+/// One hybrid formula slot — port of THAformula (188 bytes packed).
+#[repr(C, packed)]
+#[derive(Clone, Copy, Default)]
+pub struct HAFormula {
+    /// Iteration count (interpolated hybrids: weight)
+    pub it_count: i32,
+    /// Internal formula number below 20, custom formula (by name) from 20
+    pub fnr: i32,
+    pub option_count: i32,
+    /// Formula name, zero padded
+    pub custom_fname: [u8; 32],
+    pub option_type: [u8; 16],
+    pub option_value: [f64; 16],
+}
+
+/// Hybrid formula block following the header — port of THeaderCustomAddon
+/// (1136 bytes packed).
+#[repr(C, packed)]
+#[derive(Clone, Copy)]
+pub struct HeaderCustomAddon {
+    /// 16 for this layout
+    pub version: u8,
+    /// Hybrid type: 0 alternating, 1 interpolated, 2 DE combined
+    pub options1: u8,
+    pub options2: u8,
+    pub options3: u8,
+    pub f_count: u8,
+    pub hyb_opt1: u8,
+    pub hyb_opt2: u16,
+    pub formulas: [HAFormula; 6],
+}
+
+impl Default for HeaderCustomAddon {
+    fn default() -> Self {
+        // SAFETY: plain integer/float data, all-zero is a valid value
+        unsafe { std::mem::zeroed() }
+    }
+}
+
+const _: () = assert!(std::mem::size_of::<Light8>() == 32);
+const _: () = assert!(std::mem::size_of::<LightingParas9>() == 408);
+const _: () = assert!(std::mem::size_of::<MandHeader10>() == 840);
+const _: () = assert!(std::mem::size_of::<HeaderCustomAddon>() == 1136);
+
 /// Calculation thread parameters — port of TMCTparameter (~700+ bytes packed).
 ///
 /// Everything a single render thread needs to march rays.
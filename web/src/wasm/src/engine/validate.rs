@@ -0,0 +1,377 @@
+//! Parameter buffer validation and layout stamps.
+//!
+//! The buffer parsers never fail: missing values take their defaults and
+//! nonsense values are clamped or ignored, so a frontend whose layout has
+//! drifted renders something plausible but wrong. `validate` reports those
+//! cases instead, naming the buffer and index.
+//!
+//! Each buffer can also carry a layout stamp, the magic `MB3D` (as a number)
+//! and the layout version:
+//!
+//! | buffer        | where |
+//! |---------------|-------|
+//! | render_params | slots 145 (magic) and 146 (version) |
+//! | formula_ids   | section 6 `[magic, version]` |
+//! | paint_params  | section 39 `[magic, version]` |
+//!
+//! Unstamped buffers are accepted as the current layout. A render_params
+//! stamp found away from its slot, or a stamp with the wrong magic, means the
+//! buffer was laid out differently.
+
+use serde::Serialize;
+
+use crate::engine::cutting;
+use crate::engine::raymarcher;
+use crate::engine::zones;
+use crate::formulas::{self, FormulaId};
+use crate::lighting::paint::{self, PaintConfig};
+
+/// `MB3D` as a big-endian u32.
+pub const LAYOUT_MAGIC: f64 = 0x4D42_3344 as f64;
+/// Layout version of all three buffers.
+pub const LAYOUT_VERSION: u32 = 1;
+
+const RENDER: &str = "render_params";
+const FORMULA: &str = "formula_ids";
+const PAINT: &str = "paint_params";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Renders, but probably not as intended
+    Warning,
+    /// The value is replaced by a default or ignored
+    Error,
+}
+
+/// One finding.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Issue {
+    pub severity: Severity,
+    pub buffer: &'static str,
+    /// Offending index (None = the buffer as a whole)
+    pub index: Option<usize>,
+    pub message: String,
+}
+
+struct Report(Vec<Issue>);
+
+impl Report {
+    fn push(&mut self, severity: Severity, buffer: &'static str, index: Option<usize>, message: String) {
+        self.0.push(Issue { severity, buffer, index, message });
+    }
+
+    fn error(&mut self, buffer: &'static str, index: Option<usize>, message: impl Into<String>) {
+        self.push(Severity::Error, buffer, index, message.into());
+    }
+
+    fn warning(&mut self, buffer: &'static str, index: Option<usize>, message: impl Into<String>) {
+        self.push(Severity::Warning, buffer, index, message.into());
+    }
+
+    /// Check a `[magic, version]` stamp.
+    fn stamp(&mut self, buffer: &'static str, index: usize, values: &[f64]) {
+        match values {
+            [magic, ..] if *magic != LAYOUT_MAGIC => {
+                self.error(buffer, Some(index), "layout magic missing: the buffer layout is out of step")
+            }
+            [_, version, ..] if *version > LAYOUT_VERSION as f64 => self.error(
+                buffer,
+                Some(index + 1),
+                format!("layout version {version} is newer than this build ({LAYOUT_VERSION})"),
+            ),
+            [_] => self.error(buffer, Some(index), "layout stamp without a version"),
+            _ => {}
+        }
+    }
+}
+
+/// All findings for the three buffers, errors and warnings in buffer order.
+pub fn validate(render_params: &[f64], formula_ids: &[u32], paint_params: &[f64]) -> Vec<Issue> {
+    let mut report = Report(Vec::new());
+    check_render(&mut report, render_params);
+    check_formula(&mut report, formula_ids);
+    check_paint(&mut report, paint_params);
+    report.0
+}
+
+fn check_render(report: &mut Report, data: &[f64]) {
+    if data.len() < 32 {
+        report.error(RENDER, None, format!("{} values; at least 32 are required, defaults are used", data.len()));
+        return;
+    }
+    match data.iter().position(|&v| v == LAYOUT_MAGIC) {
+        Some(i) if i == raymarcher::LAYOUT_MAGIC_INDEX => report.stamp(RENDER, i, &data[i..]),
+        Some(i) => report.error(
+            RENDER,
+            Some(i),
+            format!("layout stamp at slot {i} instead of {}: the buffer layout is out of step", raymarcher::LAYOUT_MAGIC_INDEX),
+        ),
+        None => {}
+    }
+    if let Some(i) = data.iter().position(|v| !v.is_finite()) {
+        report.error(RENDER, Some(i), format!("{} is not a finite number", data[i]));
+    }
+
+    for (i, name) in [(0, "width"), (1, "height")] {
+        if data[i] < 1.0 {
+            report.error(RENDER, Some(i), format!("{name} {} is not positive", data[i]));
+        }
+    }
+    for (i, name) in [(5, "view direction"), (8, "right edge offset"), (11, "bottom edge offset")] {
+        if data[i..i + 3].iter().all(|v| *v == 0.0) {
+            report.error(RENDER, Some(i), format!("zero-length ray basis vector ({name})"));
+        }
+    }
+    let (de_stop, max_ray_length) = (data[14], data[16]);
+    let auto_detail = data.get(109).copied().unwrap_or(0.0);
+    if de_stop <= 0.0 && auto_detail <= 0.0 {
+        report.error(RENDER, Some(14), format!("DE stop {de_stop} is not positive; rays never hit"));
+    } else if de_stop > 0.01 * max_ray_length {
+        report.warning(RENDER, Some(14), format!("DE stop {de_stop} is coarse for a ray length of {max_ray_length}"));
+    }
+    let step_width = data[15];
+    if step_width <= 0.0 {
+        report.error(RENDER, Some(15), format!("step width {step_width} is not positive; rays never advance"));
+    } else if step_width > 1.0 {
+        report.warning(RENDER, Some(15), format!("step width {step_width} above 1 oversteps the surface"));
+    }
+    if max_ray_length <= 0.0 {
+        report.error(RENDER, Some(16), format!("ray length {max_ray_length} is not positive"));
+    }
+    if data[17] < 1.0 {
+        report.error(RENDER, Some(17), "max iterations must be at least 1");
+    }
+    if data[18] <= 0.0 {
+        report.error(RENDER, Some(18), format!("bailout {} is not positive", data[18]));
+    }
+
+    let tables = [
+        (raymarcher::CUTS_OFFSET, "cuts", cutting::MAX_CUTS, cutting::CUT_STRIDE),
+        (raymarcher::ZONES_OFFSET, "material zones", zones::MAX_ZONES, zones::ZONE_STRIDE),
+    ];
+    for (offset, name, max, stride) in tables {
+        let Some(&count) = data.get(offset) else { continue };
+        if count > max as f64 {
+            report.warning(RENDER, Some(offset), format!("{count} {name}; only the first {max} are used"));
+        }
+        let end = offset + 1 + (count.max(0.0) as usize).min(max) * stride;
+        if end > data.len() {
+            report.error(RENDER, Some(offset), format!("{name} table needs {end} values, buffer has {}", data.len()));
+        }
+    }
+}
+
+fn check_formula(report: &mut Report, ids: &[u32]) {
+    if ids.is_empty() {
+        return;
+    }
+    let count = ids[0] as usize;
+    if count == 0 {
+        report.warning(FORMULA, Some(0), "no formula slots; Mandelbulb Power 8 is used");
+    } else if count > 6 {
+        report.warning(FORMULA, Some(0), format!("{count} slots; only the first 6 are used"));
+    }
+    let mode_index = 1 + count.min(6) * 2;
+    if mode_index > ids.len() {
+        report.error(FORMULA, None, format!("{count} slots need {} words, buffer has {}", mode_index + 1, ids.len()));
+        return;
+    }
+    for i in (1..mode_index).step_by(2) {
+        if ids[i] as usize >= FormulaId::ALL.len() {
+            report.error(FORMULA, Some(i), format!("unknown formula id {}; the slot is empty", ids[i]));
+        }
+    }
+    match ids.get(mode_index) {
+        None => report.warning(FORMULA, Some(mode_index), "hybrid mode missing; alternating is used"),
+        Some(&mode) if mode > 2 => {
+            report.warning(FORMULA, Some(mode_index), format!("unknown hybrid mode {mode}; alternating is used"))
+        }
+        _ => {}
+    }
+
+    // Sections: [tag, slot, count, count × (lo, hi)]
+    let mut idx = mode_index + 1;
+    while idx < ids.len() {
+        let end = ids.get(idx + 2).and_then(|&n| (n as usize).checked_mul(2)?.checked_add(idx + 3));
+        match end {
+            Some(end) if end <= ids.len() => {
+                if ids[idx] == crate::SECTION_LAYOUT_VERSION {
+                    let values: Vec<f64> = crate::formulas::parse_param_sections(&ids[idx..end])[0].values.clone();
+                    report.stamp(FORMULA, idx + 3, &values);
+                }
+                idx = end;
+            }
+            _ => {
+                report.error(FORMULA, Some(idx), format!("section {} runs past the end of the buffer", ids[idx]));
+                return;
+            }
+        }
+    }
+}
+
+fn check_paint(report: &mut Report, data: &[f64]) {
+    if data.is_empty() {
+        return;
+    }
+    let lights = data[0].max(0.0) as usize;
+    if lights > 6 {
+        report.warning(PAINT, Some(0), format!("{lights} lights; only the first 6 are used"));
+    }
+    // Lights, then ambient, fog, background, view direction, AO strength
+    let stops_index = 1 + lights.min(6) * 9 + 4 + 4 + 3 + 3 + 1;
+    let Some(&stops) = data.get(stops_index) else {
+        report.error(PAINT, None, format!("{} values end before the gradient at {stops_index}", data.len()));
+        return;
+    };
+    let Some(mut idx) = (stops.max(0.0) as usize)
+        .checked_mul(4)
+        .and_then(|n| n.checked_add(stops_index + 1))
+        .filter(|&end| end <= data.len())
+    else {
+        report.error(PAINT, Some(stops_index), format!("{stops} gradient stops run past the end of the buffer"));
+        return;
+    };
+
+    // Sections: [tag, count, values]
+    while idx < data.len() {
+        let (tag, count) = (data[idx], data.get(idx + 1).copied().unwrap_or(0.0));
+        let end = (count.max(0.0) as usize).checked_add(idx + 2).filter(|&end| idx + 1 < data.len() && end <= data.len());
+        let Some(end) = end else {
+            report.error(PAINT, Some(idx), format!("section {tag} runs past the end of the buffer"));
+            return;
+        };
+        if tag == paint::SECTION_LAYOUT_VERSION as f64 {
+            report.stamp(PAINT, idx + 2, &data[idx + 2..end]);
+        } else if tag < 1.0 || tag > paint::SECTION_LAYOUT_VERSION as f64 {
+            report.warning(PAINT, Some(idx), format!("unknown section {tag} is ignored"));
+        }
+        idx = end;
+    }
+}
+
+/// Fill missing render_params slots with their defaults and stamp the layout.
+pub fn stamp_render_params(data: &mut Vec<f64>) {
+    if data.len() < raymarcher::LAYOUT_MAGIC_INDEX {
+        let defaults = raymarcher::default_params_buffer();
+        data.extend_from_slice(&defaults[data.len()..]);
+    }
+    data.truncate(raymarcher::LAYOUT_MAGIC_INDEX);
+    data.extend([LAYOUT_MAGIC, LAYOUT_VERSION as f64]);
+}
+
+/// Append the layout section to a complete formula_ids buffer.
+pub fn stamp_formula_ids(ids: &mut Vec<u32>) {
+    if formulas::FormulaIds::decode(ids).mode.is_none() {
+        return;
+    }
+    formulas::ParamSection { tag: crate::SECTION_LAYOUT_VERSION, slot: 0, values: vec![LAYOUT_MAGIC, LAYOUT_VERSION as f64] }
+        .encode(ids);
+}
+
+/// Append the layout section to a paint_params buffer; an empty buffer
+/// first gets the default lighting.
+pub fn stamp_paint_params(data: &mut Vec<f64>) {
+    if data.is_empty() {
+        let d = PaintConfig::default();
+        data.push(d.lights.len() as f64);
+        for l in &d.lights {
+            data.extend([l.direction.x, l.direction.y, l.direction.z, l.color.0, l.color.1, l.color.2]);
+            data.extend([l.amplitude, l.specular_size, l.specular_intensity]);
+        }
+        data.extend([d.ambient_color.0, d.ambient_color.1, d.ambient_color.2, d.ambient_intensity]);
+        data.extend([d.fog_density, d.fog_color.0, d.fog_color.1, d.fog_color.2]);
+        data.extend([d.bg_color.0, d.bg_color.1, d.bg_color.2]);
+        data.extend([d.view_dir.x, d.view_dir.y, d.view_dir.z, d.ao_strength, 0.0]);
+    }
+    data.extend([paint::SECTION_LAYOUT_VERSION as f64, 2.0, LAYOUT_MAGIC, LAYOUT_VERSION as f64]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stamped() -> (Vec<f64>, Vec<u32>, Vec<f64>) {
+        let (mut render, mut formula, mut paint) = (Vec::new(), vec![1, 2, 1, 0], Vec::new());
+        stamp_render_params(&mut render);
+        stamp_formula_ids(&mut formula);
+        stamp_paint_params(&mut paint);
+        (render, formula, paint)
+    }
+
+    fn errors(issues: &[Issue]) -> Vec<(&'static str, Option<usize>)> {
+        issues.iter().filter(|i| i.severity == Severity::Error).map(|i| (i.buffer, i.index)).collect()
+    }
+
+    #[test]
+    fn test_stamped_defaults_are_valid() {
+        let (render, formula, paint) = stamped();
+        assert_eq!(render.len(), raymarcher::LAYOUT_MAGIC_INDEX + 2);
+        assert_eq!(validate(&render, &formula, &paint), []);
+        // Stamps do not change what the buffers mean
+        let params = raymarcher::params_from_buffer(&render);
+        assert_eq!((params.max_steps, params.prepass_block, params.volume.density), (8000, 4, 1.0));
+        assert_eq!(paint::paint_config_from_buffer(&paint).lights.len(), 1);
+    }
+
+    #[test]
+    fn test_out_of_range_values_are_reported() {
+        let (mut render, formula, paint) = stamped();
+        render[14] = 0.0;
+        render[8..11].fill(0.0);
+        render[20] = f64::NAN;
+        let issues = validate(&render, &formula, &paint);
+        assert_eq!(errors(&issues), [(RENDER, Some(20)), (RENDER, Some(8)), (RENDER, Some(14))]);
+
+        render = stamped().0;
+        render[15] = 1.5;
+        let issues = validate(&render, &formula, &paint);
+        assert_eq!((issues.len(), issues[0].severity, issues[0].index), (1, Severity::Warning, Some(15)));
+        assert_eq!(errors(&validate(&render[..20], &formula, &paint)), [(RENDER, None)]);
+    }
+
+    #[test]
+    fn test_layout_mismatches_are_reported() {
+        let (render, formula, paint) = stamped();
+        // A frontend that inserted a slot somewhere in the middle
+        let mut shifted = render.clone();
+        shifted.insert(100, 0.0);
+        assert_eq!(errors(&validate(&shifted, &formula, &paint)), [(RENDER, Some(raymarcher::LAYOUT_MAGIC_INDEX + 1))]);
+        // Unstamped buffers, even with trailing slots, are taken as the current layout
+        let mut unstamped = render[..raymarcher::LAYOUT_MAGIC_INDEX].to_vec();
+        unstamped.extend([0.0; 4]);
+        assert_eq!(errors(&validate(&unstamped, &formula, &paint)), []);
+
+        let mut newer = render.clone();
+        newer[raymarcher::LAYOUT_MAGIC_INDEX + 1] = 2.0;
+        assert_eq!(errors(&validate(&newer, &formula, &paint)), [(RENDER, Some(raymarcher::LAYOUT_MAGIC_INDEX + 1))]);
+
+        assert_eq!(errors(&validate(&render, &formula[..formula.len() - 1], &paint)), [(FORMULA, Some(4))]);
+        assert_eq!(errors(&validate(&render, &[3, 2, 1], &paint)), [(FORMULA, None)]);
+        assert_eq!(errors(&validate(&render, &formula, &paint[..10])), [(PAINT, None)]);
+        let mut wrong_magic = paint.clone();
+        *wrong_magic.last_mut().unwrap() = 1.0;
+        let n = wrong_magic.len();
+        wrong_magic[n - 2] = 7.0;
+        assert_eq!(errors(&validate(&render, &formula, &wrong_magic)), [(PAINT, Some(n - 2))]);
+    }
+
+    #[test]
+    fn test_huge_counts_are_reported() {
+        let (render, formula, paint) = stamped();
+        let stops_index = 1 + 9 + 4 + 4 + 3 + 3 + 1;
+        for huge in [1e300, f64::INFINITY, usize::MAX as f64] {
+            let mut stops = paint.clone();
+            stops[stops_index] = huge;
+            assert_eq!(errors(&validate(&render, &formula, &stops)), [(PAINT, Some(stops_index))]);
+
+            let mut section = paint.clone();
+            let n = section.len();
+            section[n - 3] = huge;
+            assert_eq!(errors(&validate(&render, &formula, &section)), [(PAINT, Some(n - 4))]);
+        }
+        let mut ids = formula.clone();
+        ids.extend([crate::SECTION_FORMULA_PARAMS, 0, u32::MAX]);
+        assert_eq!(errors(&validate(&render, &ids, &paint)), [(FORMULA, Some(formula.len()))]);
+    }
+}
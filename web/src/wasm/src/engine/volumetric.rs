@@ -0,0 +1,135 @@
+//! Volumetric density rendering — nebula/cloud style fractals.
+//!
+//! Instead of stopping at the surface, each ray integrates a density derived
+//! from the distance estimate (dense near and inside the set, fading with
+//! distance) front to back, emitting gradient color and absorbing light as it
+//! goes. Far from the set the step grows with the DE, so empty space is cheap.
+//!
+//! Output is straight RGBA: accumulated color and alpha = 1 − transmittance.
+
+use crate::engine::raymarcher::{self, RenderParams};
+use crate::engine::types::Vec3D;
+use crate::formulas::hybrid::HybridFormula;
+use crate::lighting::paint::PaintConfig;
+use crate::math::{strict, utils};
+
+/// Volume integration settings.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VolumeSettings {
+    /// Density at the surface (DE = 0) and inside the set
+    pub density: f64,
+    /// DE distance over which density falls by a factor e
+    pub falloff: f64,
+    /// Base integration step (0 = max_ray_length / 512)
+    pub step: f64,
+    /// Extinction per unit density and distance
+    pub absorption: f64,
+    /// Emitted gradient color per unit density and distance
+    pub emission: f64,
+}
+
+impl Default for VolumeSettings {
+    fn default() -> Self {
+        Self { density: 1.0, falloff: 0.05, step: 0.0, absorption: 4.0, emission: 4.0 }
+    }
+}
+
+/// Stop integrating once this little light gets through.
+const MIN_TRANSMITTANCE: f64 = 0.005;
+
+/// Integrate one ray; returns (r, g, b, alpha) with color not premultiplied.
+pub fn integrate_ray(
+    origin: &Vec3D,
+    dir: &Vec3D,
+    params: &RenderParams,
+    formula: &HybridFormula,
+    config: &PaintConfig,
+    settings: &VolumeSettings,
+) -> (f64, f64, f64, f64) {
+    let base_step = if settings.step > 0.0 { settings.step } else { params.max_ray_length / 512.0 };
+    let falloff = settings.falloff.max(1e-9);
+    let mut transmittance = 1.0;
+    let mut color = (0.0, 0.0, 0.0);
+    let mut t = 0.0;
+
+    while t < params.max_ray_length && transmittance > MIN_TRANSMITTANCE {
+        let p = Vec3D { x: origin.x + dir.x * t, y: origin.y + dir.y * t, z: origin.z + dir.z * t };
+        let fr = formula.compute_de(&p);
+        let de = if fr.inside { 0.0 } else { fr.de.max(0.0) };
+        if !de.is_finite() {
+            break;
+        }
+        // Skip empty space where density is negligible (e^-4 ≈ 2%)
+        let step = base_step.max(de - 4.0 * falloff);
+        let density = settings.density * strict::exp(-de / falloff);
+        if density > 1e-6 {
+            let (r, g, b) = config.gradient.sample((fr.smooth_it % 256.0) / 256.0);
+            let absorbed = 1.0 - strict::exp(-settings.absorption * density * step);
+            let emitted = settings.emission * density * step * transmittance;
+            color.0 += r * emitted;
+            color.1 += g * emitted;
+            color.2 += b * emitted;
+            transmittance *= 1.0 - absorbed;
+        }
+        t += step;
+    }
+
+    let alpha = 1.0 - transmittance;
+    if alpha <= 0.0 {
+        return (0.0, 0.0, 0.0, 0.0);
+    }
+    (color.0 / alpha, color.1 / alpha, color.2 / alpha, alpha)
+}
+
+/// Render interleaved scanlines of the volume into `rgba_out` (straight alpha).
+pub fn render_scanlines(
+    params: &RenderParams,
+    formula: &HybridFormula,
+    config: &PaintConfig,
+    settings: &VolumeSettings,
+    rgba_out: &mut [u8],
+    worker_id: u32,
+    worker_count: u32,
+) -> u32 {
+    let mut rows = 0;
+    let mut y = worker_id;
+    while y < params.height {
+        for x in 0..params.width {
+            let i = ((y * params.width + x) * 4) as usize;
+            let Some(px) = rgba_out.get_mut(i..i + 4) else { break };
+            let dir = raymarcher::pixel_direction(params, x as f64, y as f64);
+            let (r, g, b, a) = integrate_ray(&params.camera_pos, &dir, params, formula, config, settings);
+            px.copy_from_slice(&[
+                config.encode(r),
+                config.encode(g),
+                config.encode(b),
+                utils::float_to_byte(a),
+            ]);
+        }
+        rows += 1;
+        y += worker_count.max(1);
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formulas::{hybrid::HybridMode, FormulaId};
+
+    #[test]
+    fn test_density_is_opaque_through_the_set_and_clear_beside_it() {
+        let params = RenderParams::default();
+        let formula = HybridFormula::new(&[(FormulaId::MandelbulbPower8, 1)], HybridMode::Alternating, 8, 16.0);
+        let config = PaintConfig::default();
+        let settings = VolumeSettings::default();
+        let origin = params.camera_pos;
+
+        let through = integrate_ray(&origin, &Vec3D { x: 0.0, y: 0.0, z: 1.0 }, &params, &formula, &config, &settings);
+        assert!(through.3 > 0.9);
+
+        let beside = Vec3D { x: 0.0, y: 3.0, z: -2.5 };
+        let away = integrate_ray(&beside, &Vec3D { x: 0.0, y: 1.0, z: 0.0 }, &params, &formula, &config, &settings);
+        assert!(away.3 < 0.01);
+    }
+}
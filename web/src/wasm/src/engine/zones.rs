@@ -0,0 +1,97 @@
+//! Material zones — material ids from orbit-trap or iteration ranges.
+//!
+//! A zone claims every hit whose normalized orbit trap (as stored in
+//! `SiLight5::orbit_trap`) or smooth iteration count lies in its range and
+//! writes its material id into the G-buffer. The paint pass then looks up
+//! the gradient, highlight tint and reflectivity of that id, so a single
+//! fractal can carry several materials.
+
+/// Maximum number of zones read from the render parameter buffer.
+pub const MAX_ZONES: usize = 8;
+/// f64 values per zone in the render parameter buffer.
+pub const ZONE_STRIDE: usize = 4;
+
+/// Value a zone's range is tested against.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ZoneSource {
+    /// Normalized orbit trap [0, 1] (1 = orbit passed through the trap)
+    OrbitTrap,
+    /// Smooth iteration count
+    Iteration,
+}
+
+/// One material zone.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MaterialZone {
+    pub source: ZoneSource,
+    /// Half-open range [min, max)
+    pub min: f64,
+    pub max: f64,
+    /// Material id written for hits in range
+    pub material: u8,
+}
+
+impl MaterialZone {
+    /// Parse `[source (0 orbit trap, 1 iteration), min, max, material_id]`.
+    pub fn from_slice(v: &[f64]) -> Option<MaterialZone> {
+        if v.len() < ZONE_STRIDE {
+            return None;
+        }
+        let source = match v[0] as u32 {
+            0 => ZoneSource::OrbitTrap,
+            1 => ZoneSource::Iteration,
+            _ => return None,
+        };
+        Some(MaterialZone { source, min: v[1], max: v[2], material: v[3].clamp(0.0, 255.0) as u8 })
+    }
+
+    fn contains(&self, trap: f64, iteration: f64) -> bool {
+        let v = match self.source {
+            ZoneSource::OrbitTrap => trap,
+            ZoneSource::Iteration => iteration,
+        };
+        v >= self.min && v < self.max
+    }
+}
+
+/// Read the zone table at `data[offset]`: `[count, (zone × ZONE_STRIDE) × MAX_ZONES]`.
+pub fn zones_from_buffer(data: &[f64], offset: usize) -> Vec<MaterialZone> {
+    let count = data.get(offset).map_or(0, |&c| (c.max(0.0) as usize).min(MAX_ZONES));
+    (0..count)
+        .filter_map(|i| {
+            let start = offset + 1 + i * ZONE_STRIDE;
+            data.get(start..start + ZONE_STRIDE).and_then(MaterialZone::from_slice)
+        })
+        .collect()
+}
+
+/// Material of the first zone containing the hit (None = keep the default).
+pub fn material_for(zones: &[MaterialZone], trap: f64, iteration: f64) -> Option<u8> {
+    zones.iter().find(|z| z.contains(trap, iteration)).map(|z| z.material)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_matching_zone_wins() {
+        let zones = [
+            MaterialZone { source: ZoneSource::OrbitTrap, min: 0.8, max: 1.1, material: 2 },
+            MaterialZone { source: ZoneSource::Iteration, min: 0.0, max: 5.0, material: 1 },
+        ];
+        assert_eq!(material_for(&zones, 0.9, 3.0), Some(2));
+        assert_eq!(material_for(&zones, 0.5, 3.0), Some(1));
+        assert_eq!(material_for(&zones, 0.5, 5.0), None);
+    }
+
+    #[test]
+    fn test_table_parsing() {
+        let mut data = vec![0.0; 10];
+        data.extend([2.0, 1.0, 2.0, 4.0, 3.0, 7.0, 0.0, 0.0, 0.0, 0.0]);
+        let zones = zones_from_buffer(&data, 10);
+        assert_eq!(zones.len(), 1, "unknown sources are skipped");
+        assert_eq!(zones[0], MaterialZone { source: ZoneSource::Iteration, min: 2.0, max: 4.0, material: 3 });
+        assert!(zones_from_buffer(&data, 40).is_empty());
+    }
+}
@@ -0,0 +1,198 @@
+//! Parameter-stamped exports — a footer band with caption text drawn in an
+//! embedded 5×7 bitmap font (uppercase ASCII, digits and common punctuation;
+//! lowercase is drawn as uppercase, anything else as a blank).
+
+use crate::engine::raymarcher::RenderParams;
+use crate::formulas::hybrid::HybridFormula;
+
+/// Glyph cell size in font pixels (5×7 glyph plus one column/row of spacing).
+pub const CELL_WIDTH: u32 = 6;
+pub const CELL_HEIGHT: u32 = 8;
+
+/// 5×7 glyphs sorted by character; each row is 5 bits, MSB = leftmost column.
+const FONT: &[(char, [u8; 7])] = &[
+    (' ', [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]),
+    ('!', [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04]),
+    ('#', [0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A]),
+    ('%', [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03]),
+    ('\'', [0x04, 0x04, 0x08, 0x00, 0x00, 0x00, 0x00]),
+    ('(', [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02]),
+    (')', [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08]),
+    ('*', [0x00, 0x04, 0x15, 0x0E, 0x15, 0x04, 0x00]),
+    ('+', [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00]),
+    (',', [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08]),
+    ('-', [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00]),
+    ('.', [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C]),
+    ('/', [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00]),
+    ('0', [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E]),
+    ('1', [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E]),
+    ('2', [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F]),
+    ('3', [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E]),
+    ('4', [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02]),
+    ('5', [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E]),
+    ('6', [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E]),
+    ('7', [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08]),
+    ('8', [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E]),
+    ('9', [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C]),
+    (':', [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00]),
+    ('=', [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00]),
+    ('?', [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04]),
+    ('A', [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11]),
+    ('B', [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E]),
+    ('C', [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E]),
+    ('D', [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C]),
+    ('E', [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F]),
+    ('F', [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10]),
+    ('G', [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F]),
+    ('H', [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11]),
+    ('I', [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E]),
+    ('J', [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C]),
+    ('K', [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11]),
+    ('L', [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F]),
+    ('M', [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11]),
+    ('N', [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11]),
+    ('O', [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E]),
+    ('P', [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10]),
+    ('Q', [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D]),
+    ('R', [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11]),
+    ('S', [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E]),
+    ('T', [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04]),
+    ('U', [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E]),
+    ('V', [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04]),
+    ('W', [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A]),
+    ('X', [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11]),
+    ('Y', [0x11, 0x11, 0x0A, 0x04, 0x04, 0x04, 0x04]),
+    ('Z', [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F]),
+    ('_', [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F]),
+    ('|', [0x04, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04]),
+];
+
+fn glyph(c: char) -> [u8; 7] {
+    let c = c.to_ascii_uppercase();
+    FONT.binary_search_by_key(&c, |&(k, _)| k).map_or([0; 7], |i| FONT[i].1)
+}
+
+/// Footer band appearance.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FooterSettings {
+    /// Integer upscale of the font
+    pub scale: u32,
+    /// Padding around the text in output pixels
+    pub padding: u32,
+    pub background: [u8; 3],
+    pub text: [u8; 3],
+}
+
+impl Default for FooterSettings {
+    fn default() -> Self {
+        Self { scale: 2, padding: 6, background: [16, 16, 20], text: [230, 230, 230] }
+    }
+}
+
+/// Height in pixels of the band for `lines` lines of text.
+pub fn footer_height(lines: u32, settings: &FooterSettings) -> u32 {
+    lines.max(1) * CELL_HEIGHT * settings.scale.max(1) + settings.padding * 2
+}
+
+/// Draw `text` into an RGBA image with its top-left corner at `origin`;
+/// text is clipped at the edges.
+pub fn draw_text(rgba: &mut [u8], width: u32, height: u32, origin: (u32, u32), text: &str, scale: u32, color: [u8; 3]) {
+    let (x, y) = origin;
+    let scale = scale.max(1);
+    for (ci, c) in text.chars().enumerate() {
+        let rows = glyph(c);
+        let gx = x + ci as u32 * CELL_WIDTH * scale;
+        for (ry, bits) in rows.iter().enumerate() {
+            for rx in 0..5u32 {
+                if bits & (0x10 >> rx) == 0 {
+                    continue;
+                }
+                for sy in 0..scale {
+                    for sx in 0..scale {
+                        let px = gx + rx * scale + sx;
+                        let py = y + ry as u32 * scale + sy;
+                        if px >= width || py >= height {
+                            continue;
+                        }
+                        let i = ((py * width + px) * 4) as usize;
+                        rgba[i..i + 4].copy_from_slice(&[color[0], color[1], color[2], 255]);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Copy of the image with a caption band appended below it.
+///
+/// `text` may contain several lines separated by `\n`. Returns the new image
+/// and its height.
+pub fn append_footer(rgba: &[u8], width: u32, height: u32, text: &str, settings: &FooterSettings) -> (Vec<u8>, u32) {
+    let lines: Vec<&str> = text.lines().collect();
+    let band = footer_height(lines.len() as u32, settings);
+    let total = height + band;
+    let image_bytes = (width * height * 4) as usize;
+
+    let mut out = Vec::with_capacity((width * total * 4) as usize);
+    out.extend_from_slice(&rgba[..image_bytes.min(rgba.len())]);
+    out.resize(image_bytes, 0);
+    let bg = [settings.background[0], settings.background[1], settings.background[2], 255];
+    for _ in 0..width * band {
+        out.extend_from_slice(&bg);
+    }
+
+    let scale = settings.scale.max(1);
+    for (li, line) in lines.iter().enumerate() {
+        let y = height + settings.padding + li as u32 * CELL_HEIGHT * scale;
+        draw_text(&mut out, width, total, (settings.padding, y), line, scale, settings.text);
+    }
+    (out, total)
+}
+
+/// Standard parameter caption: formula stack, iterations, zoom and position.
+pub fn parameter_caption(params: &RenderParams, formula: &HybridFormula, zoom: f64) -> String {
+    let stack: Vec<String> = formula
+        .slots
+        .iter()
+        .filter(|s| s.active)
+        .map(|s| format!("{} x{}", s.formula.name(), s.iterations))
+        .collect();
+    let p = &params.camera_pos;
+    format!(
+        "{}\nITER {}  ZOOM {:.4e}  POS {:.6} {:.6} {:.6}",
+        stack.join(" + "),
+        params.max_iterations,
+        zoom,
+        p.x,
+        p.y,
+        p.z,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_font_is_sorted_and_known_glyphs_exist() {
+        assert!(FONT.windows(2).all(|w| w[0].0 < w[1].0));
+        assert_eq!(glyph('a'), glyph('A'));
+        assert_ne!(glyph('A'), [0; 7]);
+        assert_eq!(glyph('~'), [0; 7]);
+    }
+
+    #[test]
+    fn test_footer_appended_below_image() {
+        let rgba = vec![200u8; 40 * 10 * 4];
+        let settings = FooterSettings { scale: 1, padding: 2, ..Default::default() };
+        let (out, h) = append_footer(&rgba, 40, 10, "I", &settings);
+        assert_eq!(h, 10 + CELL_HEIGHT + 4);
+        assert_eq!(out.len(), (40 * h * 4) as usize);
+        assert_eq!(&out[..4], &[200, 200, 200, 200]);
+        // Top bar of the "I" glyph: columns 1..4 of its first row
+        let at = |x: u32, y: u32| &out[((y * 40 + x) * 4) as usize..][..3];
+        assert_eq!(at(3, 12), &settings.text);
+        assert_eq!(at(5, 12), &settings.text);
+        assert_eq!(at(2, 12), &settings.background);
+    }
+}
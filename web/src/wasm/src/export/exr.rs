@@ -0,0 +1,241 @@
+//! OpenEXR writer for render passes (single-part scanline, 32-bit float).
+//!
+//! Compositors want the data behind the picture: unclamped color, camera
+//! distance, normals, occlusion and the orbit trap as separate channels.
+//! Every channel is FLOAT so nothing is lost to half precision; ZIP blocks
+//! of 16 scanlines keep the files reasonably small.
+//!
+//! Channel names follow the EXR layer convention (`layer.channel`), so
+//! `N.X`, `N.Y`, `N.Z` show up as one normals layer next to RGBA and `Z`.
+
+use crate::engine::types::SiLight5;
+
+/// File magic.
+pub const MAGIC: [u8; 4] = [0x76, 0x2F, 0x31, 0x01];
+
+/// Scanline block compression.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExrCompression {
+    None,
+    /// zlib over 16-line blocks with the EXR byte split and delta predictor
+    Zip,
+}
+
+impl ExrCompression {
+    fn code(self) -> u8 {
+        match self {
+            ExrCompression::None => 0,
+            ExrCompression::Zip => 3,
+        }
+    }
+
+    fn lines_per_block(self) -> u32 {
+        match self {
+            ExrCompression::None => 1,
+            ExrCompression::Zip => 16,
+        }
+    }
+}
+
+/// One named FLOAT channel, `width * height` samples top row first.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Channel {
+    pub name: String,
+    pub samples: Vec<f32>,
+}
+
+impl Channel {
+    pub fn new(name: &str, samples: Vec<f32>) -> Self {
+        Channel { name: name.to_string(), samples }
+    }
+}
+
+/// Split a packed G-buffer (and optional float RGBA paint output, see
+/// `paint::paint_gbuffer_f32`) into passes:
+///
+/// - `R`, `G`, `B`, `A`: the paint output, when given
+/// - `Z`: distance along the ray (world units), infinite for misses
+/// - `N.X`, `N.Y`, `N.Z`: world-space surface normal, zero for misses
+/// - `AO.Y`: occlusion factor, 1 unoccluded (and for misses)
+/// - `trap.Y`: orbit trap in [0, 1], zero for misses
+pub fn passes(gbuffer: &[SiLight5], color: Option<&[f32]>, max_ray_length: f64) -> Vec<Channel> {
+    let mut channels = Vec::new();
+    if let Some(color) = color {
+        for (c, name) in ["R", "G", "B", "A"].into_iter().enumerate() {
+            let samples = color.chunks_exact(4).take(gbuffer.len()).map(|p| p[c]).collect();
+            channels.push(Channel::new(name, samples));
+        }
+    }
+    let pass = |name: &str, miss: f32, hit: &dyn Fn(&SiLight5) -> f32| {
+        let samples = gbuffer.iter().map(|p| if p.z_pos >= 65534 { miss } else { hit(p) }).collect();
+        Channel::new(name, samples)
+    };
+    channels.push(pass("Z", f32::INFINITY, &|p| (p.z_pos as f64 / 65535.0 * max_ray_length) as f32));
+    channels.push(pass("N.X", 0.0, &|p| p.sn_x as f32 / 32767.0));
+    channels.push(pass("N.Y", 0.0, &|p| p.sn_y as f32 / 32767.0));
+    channels.push(pass("N.Z", 0.0, &|p| p.sn_z as f32 / 32767.0));
+    channels.push(pass("AO.Y", 1.0, &|p| 1.0 - p.ambient as f32 / 65535.0));
+    channels.push(pass("trap.Y", 0.0, &|p| p.orbit_trap as f32 / 65535.0));
+    channels
+}
+
+/// Encode `channels` as a `width` × `height` EXR file. Channels are sorted
+/// by name as the format requires; returns an empty Vec if any channel is
+/// shorter than the image.
+pub fn encode(width: u32, height: u32, channels: &[Channel], compression: ExrCompression) -> Vec<u8> {
+    let pixels = width as usize * height as usize;
+    if width == 0 || height == 0 || channels.iter().any(|c| c.samples.len() < pixels) {
+        return Vec::new();
+    }
+    let mut channels: Vec<&Channel> = channels.iter().collect();
+    channels.sort_by(|a, b| a.name.as_bytes().cmp(b.name.as_bytes()));
+
+    let mut out = MAGIC.to_vec();
+    out.extend_from_slice(&2u32.to_le_bytes()); // version 2, single-part scanline
+
+    let mut chlist = Vec::new();
+    for c in &channels {
+        chlist.extend_from_slice(c.name.as_bytes());
+        chlist.push(0);
+        chlist.extend_from_slice(&2i32.to_le_bytes()); // FLOAT
+        chlist.extend_from_slice(&[0, 0, 0, 0]); // pLinear, reserved
+        chlist.extend_from_slice(&1i32.to_le_bytes()); // x sampling
+        chlist.extend_from_slice(&1i32.to_le_bytes()); // y sampling
+    }
+    chlist.push(0);
+    let window: Vec<u8> =
+        [0, 0, width as i32 - 1, height as i32 - 1].iter().flat_map(|v| v.to_le_bytes()).collect();
+
+    attribute(&mut out, "channels", "chlist", &chlist);
+    attribute(&mut out, "compression", "compression", &[compression.code()]);
+    attribute(&mut out, "dataWindow", "box2i", &window);
+    attribute(&mut out, "displayWindow", "box2i", &window);
+    attribute(&mut out, "lineOrder", "lineOrder", &[0]); // increasing y
+    attribute(&mut out, "pixelAspectRatio", "float", &1f32.to_le_bytes());
+    attribute(&mut out, "screenWindowCenter", "v2f", &[0; 8]);
+    attribute(&mut out, "screenWindowWidth", "float", &1f32.to_le_bytes());
+    out.push(0);
+
+    // Offset table, patched as the blocks are written
+    let lines = compression.lines_per_block();
+    let blocks = height.div_ceil(lines) as usize;
+    let table = out.len();
+    out.resize(table + blocks * 8, 0);
+
+    let w = width as usize;
+    for block in 0..blocks {
+        let y0 = block as u32 * lines;
+        let y1 = (y0 + lines).min(height);
+        // Per scanline, every channel's row in turn
+        let mut raw = Vec::with_capacity((y1 - y0) as usize * channels.len() * w * 4);
+        for y in y0 as usize..y1 as usize {
+            for c in &channels {
+                raw.extend(c.samples[y * w..(y + 1) * w].iter().flat_map(|v| v.to_le_bytes()));
+            }
+        }
+        let data = match compression {
+            ExrCompression::None => raw,
+            ExrCompression::Zip => {
+                // Stored raw when compression does not pay, as readers expect
+                let packed = zip_block(&raw);
+                if packed.len() < raw.len() { packed } else { raw }
+            }
+        };
+
+        let offset = out.len() as u64;
+        out[table + block * 8..table + block * 8 + 8].copy_from_slice(&offset.to_le_bytes());
+        out.extend_from_slice(&(y0 as i32).to_le_bytes());
+        out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        out.extend_from_slice(&data);
+    }
+    out
+}
+
+/// Header attribute: name, type name, size, value.
+fn attribute(out: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    out.extend_from_slice(name.as_bytes());
+    out.push(0);
+    out.extend_from_slice(kind.as_bytes());
+    out.push(0);
+    out.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    out.extend_from_slice(value);
+}
+
+/// EXR ZIP: even bytes then odd bytes, byte deltas (+128), zlib.
+fn zip_block(raw: &[u8]) -> Vec<u8> {
+    let mut split: Vec<u8> = raw.iter().step_by(2).chain(raw.iter().skip(1).step_by(2)).copied().collect();
+    for i in (1..split.len()).rev() {
+        split[i] = split[i].wrapping_sub(split[i - 1]).wrapping_add(128);
+    }
+    miniz_oxide::deflate::compress_to_vec_zlib(&split, 4)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Inverse of `zip_block`.
+    fn unzip_block(data: &[u8]) -> Vec<u8> {
+        let mut split = miniz_oxide::inflate::decompress_to_vec_zlib(data).unwrap();
+        for i in 1..split.len() {
+            split[i] = split[i].wrapping_add(split[i - 1]).wrapping_sub(128);
+        }
+        let half = split.len().div_ceil(2);
+        (0..split.len()).map(|i| if i % 2 == 0 { split[i / 2] } else { split[half + i / 2] }).collect()
+    }
+
+    fn u32_at(b: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes(b[at..at + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn test_header_and_zip_blocks_round_trip() {
+        let (width, height) = (3, 20);
+        let ramp = |k: f32| (0..60).map(|i| i as f32 * k).collect::<Vec<_>>();
+        let channels = [Channel::new("Z", ramp(0.5)), Channel::new("B", ramp(-1.0)), Channel::new("AO.Y", ramp(0.0))];
+        let exr = encode(width, height, &channels, ExrCompression::Zip);
+        assert_eq!(&exr[..8], &[0x76, 0x2F, 0x31, 0x01, 2, 0, 0, 0]);
+
+        // Channel list is sorted: AO.Y, B, Z
+        let list = &exr[8 + b"channels\0chlist\0".len() + 4..];
+        assert!(list.starts_with(b"AO.Y\0"));
+        assert_eq!(&list[5 + 16..5 + 16 + 2], b"B\0");
+
+        // Header ends after the last attribute (name, "float", size, value) and a null
+        let last = exr.windows(18).position(|w| w == b"screenWindowWidth\0").unwrap();
+        let table = last + 18 + 6 + 4 + 4 + 1;
+        assert_eq!(exr[table - 1], 0);
+
+        // Two blocks, of 16 and 4 lines
+        let first = u32_at(&exr, table) as usize;
+        let second = u32_at(&exr, table + 8) as usize;
+        assert_eq!(first, table + 16);
+        assert_eq!(u32_at(&exr, second), 16); // y of the second block
+
+        let size = u32_at(&exr, first + 4) as usize;
+        let raw = unzip_block(&exr[first + 8..first + 8 + size]);
+        assert_eq!(raw.len(), 16 * 3 * 3 * 4);
+        // Line 1, channel B (second), sample 2 = pixel 5
+        let at = (3 * 3 + 3 + 2) * 4;
+        assert_eq!(f32::from_le_bytes(raw[at..at + 4].try_into().unwrap()), -5.0);
+
+        assert!(encode(width, height + 1, &channels, ExrCompression::None).is_empty());
+    }
+
+    #[test]
+    fn test_passes_decode_the_gbuffer() {
+        let hit = SiLight5 { sn_z: -32767, z_pos: 32768, ambient: 16384, orbit_trap: 65535, ..Default::default() };
+        let miss = SiLight5 { z_pos: 65535, ambient: 1234, ..Default::default() };
+        let color = [0.5, 2.0, 0.25, 1.0, 0.0, 0.0, 0.0, 1.0];
+        let passes = passes(&[hit, miss], Some(&color), 10.0);
+        let get = |name: &str| &passes.iter().find(|c| c.name == name).unwrap().samples;
+
+        assert_eq!(get("G"), &[2.0, 0.0]);
+        assert!((get("Z")[0] - 5.0).abs() < 1e-3 && get("Z")[1].is_infinite());
+        assert_eq!(get("N.Z"), &[-1.0, 0.0]);
+        assert_eq!(get("AO.Y")[1], 1.0);
+        assert!((get("AO.Y")[0] - 0.75).abs() < 1e-4);
+        assert_eq!(get("trap.Y"), &[1.0, 0.0]);
+        assert_eq!(passes.len(), 10);
+    }
+}
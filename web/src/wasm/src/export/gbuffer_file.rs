@@ -0,0 +1,220 @@
+//! Saved renders — the G-buffer plus the buffers that produced it, in one
+//! compressed blob (the web counterpart of MB3D's .m3i files).
+//!
+//! Reloading a blob gives back the render, formula and paint parameters and
+//! the G-buffer, so a saved render can be relit and repainted without
+//! marching it again.
+//!
+//! Layout (little endian):
+//!
+//! | offset | size | field |
+//! |-------:|-----:|-------|
+//! | 0      | 4    | magic `MBGB` |
+//! | 4      | 2    | version (1) |
+//! | 6      | 1    | G-buffer format (0 packed, 1 extended) |
+//! | 7      | 1    | reserved, 0 |
+//! | 8      | 8    | width, height |
+//! | 16     | 12   | render_params, formula_ids and paint_params counts |
+//! | 28     | 4    | CRC-32 of the uncompressed payload |
+//! | 32     |      | zlib payload |
+//!
+//! The payload holds the three parameter arrays followed by the G-buffer
+//! split into byte planes (byte 0 of every record, then byte 1, ...). Depth,
+//! normals and gradients change slowly across the image, so each plane is
+//! far more regular than the interleaved records and compresses better.
+
+use std::fmt;
+
+use crate::engine::types::GBufferFormat;
+use super::png::crc32;
+
+/// File magic.
+pub const MAGIC: [u8; 4] = *b"MBGB";
+/// Version written by `encode`.
+pub const VERSION: u16 = 1;
+const HEADER_LEN: usize = 32;
+
+/// A render with everything needed to repaint it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SavedRender {
+    pub width: u32,
+    pub height: u32,
+    pub format: GBufferFormat,
+    pub render_params: Vec<f64>,
+    pub formula_ids: Vec<u32>,
+    pub paint_params: Vec<f64>,
+    /// Raw G-buffer records, width * height * format.record_size() bytes
+    pub gbuffer: Vec<u8>,
+}
+
+/// Why a blob could not be loaded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LoadError {
+    /// Not a saved render
+    Magic,
+    /// Written by a newer version
+    Version(u16),
+    /// Header or payload shorter than the header promises
+    Truncated,
+    /// Payload does not inflate, or inflates past the size the header declares
+    Corrupt,
+    /// Payload checksum mismatch
+    Checksum,
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Magic => f.write_str("not a saved render"),
+            LoadError::Version(v) => write!(f, "unsupported saved render version {v}"),
+            LoadError::Truncated => f.write_str("saved render is truncated"),
+            LoadError::Corrupt => f.write_str("saved render payload is corrupt"),
+            LoadError::Checksum => f.write_str("saved render checksum mismatch"),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+/// Compress `render` at zlib `level` (0–10).
+pub fn encode(render: &SavedRender, level: u8) -> Vec<u8> {
+    let size = render.format.record_size();
+    let records = render.gbuffer.len() / size;
+    let mut payload = Vec::with_capacity(
+        (render.render_params.len() + render.paint_params.len()) * 8 + render.formula_ids.len() * 4 + records * size,
+    );
+    payload.extend(render.render_params.iter().flat_map(|v| v.to_le_bytes()));
+    payload.extend(render.formula_ids.iter().flat_map(|v| v.to_le_bytes()));
+    payload.extend(render.paint_params.iter().flat_map(|v| v.to_le_bytes()));
+    for k in 0..size {
+        payload.extend(render.gbuffer.chunks_exact(size).map(|record| record[k]));
+    }
+
+    let mut out = Vec::with_capacity(HEADER_LEN + payload.len() / 4);
+    out.extend_from_slice(&MAGIC);
+    out.extend_from_slice(&VERSION.to_le_bytes());
+    out.push(render.format as u8);
+    out.push(0);
+    for v in [
+        render.width,
+        render.height,
+        render.render_params.len() as u32,
+        render.formula_ids.len() as u32,
+        render.paint_params.len() as u32,
+        crc32(&payload),
+    ] {
+        out.extend_from_slice(&v.to_le_bytes());
+    }
+    out.extend_from_slice(&miniz_oxide::deflate::compress_to_vec_zlib(&payload, level.min(10)));
+    out
+}
+
+/// Reload a blob written by `encode`.
+pub fn decode(bytes: &[u8]) -> Result<SavedRender, LoadError> {
+    if bytes.len() < HEADER_LEN {
+        return Err(if bytes.starts_with(&MAGIC) { LoadError::Truncated } else { LoadError::Magic });
+    }
+    if bytes[..4] != MAGIC {
+        return Err(LoadError::Magic);
+    }
+    let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+    if version > VERSION {
+        return Err(LoadError::Version(version));
+    }
+    let format = GBufferFormat::from_u32(bytes[6] as u32);
+    let word = |i: usize| u32::from_le_bytes(bytes[8 + i * 4..12 + i * 4].try_into().unwrap());
+    let (width, height) = (word(0), word(1));
+    let counts = [word(2) as usize, word(3) as usize, word(4) as usize];
+
+    // Payload size the header declares; a header that overflows it is corrupt
+    let size = format.record_size();
+    let records = (width as usize).checked_mul(height as usize).ok_or(LoadError::Corrupt)?;
+    let expected = counts[0]
+        .checked_add(counts[2])
+        .and_then(|n| n.checked_mul(8))
+        .and_then(|n| n.checked_add(counts[1].checked_mul(4)?))
+        .and_then(|n| n.checked_add(records.checked_mul(size)?))
+        .ok_or(LoadError::Corrupt)?;
+
+    // Never inflate past the declared size
+    let payload = miniz_oxide::inflate::decompress_to_vec_zlib_with_limit(&bytes[HEADER_LEN..], expected)
+        .map_err(|_| LoadError::Corrupt)?;
+    if crc32(&payload) != word(5) {
+        return Err(LoadError::Checksum);
+    }
+    if payload.len() != expected {
+        return Err(LoadError::Truncated);
+    }
+
+    let (render, rest) = payload.split_at(counts[0] * 8);
+    let (formula, rest) = rest.split_at(counts[1] * 4);
+    let (paint, planes) = rest.split_at(counts[2] * 8);
+    let f64s = |b: &[u8]| b.chunks_exact(8).map(|c| f64::from_le_bytes(c.try_into().unwrap())).collect();
+    let mut gbuffer = vec![0u8; records * size];
+    for (k, plane) in planes.chunks_exact(records.max(1)).enumerate().take(size) {
+        for (record, &b) in gbuffer.chunks_exact_mut(size).zip(plane) {
+            record[k] = b;
+        }
+    }
+    Ok(SavedRender {
+        width,
+        height,
+        format,
+        render_params: f64s(render),
+        formula_ids: formula.chunks_exact(4).map(|c| u32::from_le_bytes(c.try_into().unwrap())).collect(),
+        paint_params: f64s(paint),
+        gbuffer,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render() -> SavedRender {
+        let (width, height) = (7, 5);
+        SavedRender {
+            width,
+            height,
+            format: GBufferFormat::Packed,
+            render_params: vec![7.0, 5.0, -1.25, f64::MAX],
+            formula_ids: vec![1, 0, 12, 3],
+            paint_params: vec![0.5; 9],
+            gbuffer: (0..width * height * 18).map(|i| (i * 7 % 251) as u8).collect(),
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let saved = render();
+        let blob = encode(&saved, 6);
+        assert_eq!(decode(&blob), Ok(saved));
+
+        let extended = SavedRender { format: GBufferFormat::Extended, gbuffer: vec![9; 7 * 5 * 34], ..render() };
+        assert_eq!(decode(&encode(&extended, 1)), Ok(extended));
+    }
+
+    #[test]
+    fn test_damaged_blobs_are_rejected() {
+        let blob = encode(&render(), 6);
+        assert_eq!(decode(b"PNG"), Err(LoadError::Magic));
+        assert_eq!(decode(&blob[..20]), Err(LoadError::Truncated));
+        assert_eq!(decode(&blob[..blob.len() - 4]), Err(LoadError::Corrupt));
+
+        let mut newer = blob.clone();
+        newer[4] = 9;
+        assert_eq!(decode(&newer), Err(LoadError::Version(9)));
+        let mut flipped = blob;
+        flipped[28] ^= 1;
+        assert_eq!(decode(&flipped), Err(LoadError::Checksum));
+
+        // Sizes that overflow, or that the payload exceeds, are rejected before inflating
+        let mut huge = encode(&render(), 6);
+        huge[8..16].copy_from_slice(&[0xFF; 8]);
+        huge[16..20].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(decode(&huge), Err(LoadError::Corrupt));
+        let mut smaller = encode(&render(), 6);
+        smaller[12] = 1;
+        assert_eq!(decode(&smaller), Err(LoadError::Corrupt));
+    }
+}
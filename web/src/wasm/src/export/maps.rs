@@ -0,0 +1,112 @@
+//! Depth and normal maps from the G-buffer, for external depth of field,
+//! relighting and upscaling tools.
+//!
+//! Both come out as samples ready for `png::encode_samples`: one gray value
+//! per pixel for depth, RGB for normals, scaled to 8 or 16 bits.
+
+use crate::engine::types::{SiLight5, Vec3D};
+use crate::lighting::paint::PaintView;
+use crate::math::math3d;
+
+/// Largest sample value at `depth` bits (8 or 16).
+fn full_scale(depth: u8) -> f64 {
+    if depth == 16 { 65535.0 } else { 255.0 }
+}
+
+fn is_miss(p: &SiLight5) -> bool {
+    p.z_pos >= 65534
+}
+
+/// Gray depth map of the distance along each view ray: `near` is white and
+/// `far` black (swap them for black near the camera), clamped outside.
+/// `near == far` uses the nearest and farthest hit. Misses are black.
+pub fn depth_map(gbuffer: &[SiLight5], max_ray_length: f64, near: f64, far: f64, depth: u8) -> Vec<u16> {
+    let distance = |p: &SiLight5| p.z_pos as f64 / 65535.0 * max_ray_length;
+    let (near, far) = if near == far {
+        let hits = gbuffer.iter().filter(|p| !is_miss(p)).map(distance);
+        hits.fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), d| (lo.min(d), hi.max(d)))
+    } else {
+        (near, far)
+    };
+    let scale = full_scale(depth);
+    gbuffer
+        .iter()
+        .map(|p| {
+            if is_miss(p) {
+                return 0;
+            }
+            // A single depth (flat image) maps to white
+            let v = if far == near { 1.0 } else { (far - distance(p)) / (far - near) };
+            (v.clamp(0.0, 1.0) * scale).round() as u16
+        })
+        .collect()
+}
+
+/// RGB normal map, `n * 0.5 + 0.5` per channel. World space, or with `view`
+/// camera space: x right, y up, z toward the camera (the usual normal map
+/// convention). Misses are black.
+pub fn normal_map(gbuffer: &[SiLight5], view: Option<&PaintView>, depth: u8) -> Vec<u16> {
+    let basis = view.map(|v| {
+        (
+            math3d::vec3d_normalized(&v.ray_dx),
+            math3d::vec3d_normalized(&v.ray_dy),
+            math3d::vec3d_normalized(&v.ray_dir_base),
+        )
+    });
+    let scale = full_scale(depth);
+    let mut out = Vec::with_capacity(gbuffer.len() * 3);
+    for p in gbuffer {
+        if is_miss(p) {
+            out.extend_from_slice(&[0, 0, 0]);
+            continue;
+        }
+        let n = math3d::vec3d_normalized(&Vec3D {
+            x: p.sn_x as f64 / 32767.0,
+            y: p.sn_y as f64 / 32767.0,
+            z: p.sn_z as f64 / 32767.0,
+        });
+        let n = match &basis {
+            Some((right, down, forward)) => Vec3D {
+                x: math3d::vec3d_dot(&n, right),
+                y: -math3d::vec3d_dot(&n, down),
+                z: -math3d::vec3d_dot(&n, forward),
+            },
+            None => n,
+        };
+        out.extend([n.x, n.y, n.z].map(|c| ((c * 0.5 + 0.5).clamp(0.0, 1.0) * scale).round() as u16));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hit(z_pos: u16, normal: [i16; 3]) -> SiLight5 {
+        SiLight5 { sn_x: normal[0], sn_y: normal[1], sn_z: normal[2], z_pos, ..Default::default() }
+    }
+
+    #[test]
+    fn test_depth_remap() {
+        let miss = SiLight5 { z_pos: 65535, ..Default::default() };
+        let gbuffer = [hit(0, [0; 3]), hit(32768, [0; 3]), hit(16384, [0; 3]), miss];
+        // Auto range: the hits span 0..0.5 of the ray length
+        assert_eq!(depth_map(&gbuffer, 2.0, 0.0, 0.0, 8), vec![255, 0, 128, 0]);
+        // Explicit and inverted range, 16 bit
+        assert_eq!(depth_map(&gbuffer, 2.0, 2.0, 0.0, 16), vec![0, 32768, 16384, 0]);
+    }
+
+    #[test]
+    fn test_normal_spaces() {
+        let gbuffer = [hit(100, [0, 0, -32767])];
+        assert_eq!(normal_map(&gbuffer, None, 8), vec![128, 128, 0]);
+        // Facing a camera that looks along +z: toward the viewer is +z in camera space
+        let view = PaintView {
+            ray_dir_base: Vec3D { x: 0.0, y: 0.0, z: 1.0 },
+            ray_dx: Vec3D { x: 1.0, y: 0.0, z: 0.0 },
+            ray_dy: Vec3D { x: 0.0, y: 1.0, z: 0.0 },
+            ..Default::default()
+        };
+        assert_eq!(normal_map(&gbuffer, Some(&view), 16), vec![32768, 32768, 65535]);
+    }
+}
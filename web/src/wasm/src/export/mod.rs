@@ -0,0 +1,10 @@
+//! Image export — encoders for finished and in-progress renders.
+
+pub mod annotate;
+pub mod exr;
+pub mod gbuffer_file;
+pub mod maps;
+pub mod png;
+pub mod snapshot;
+pub mod tiff;
+pub mod volume;
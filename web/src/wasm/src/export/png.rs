@@ -0,0 +1,160 @@
+//! Minimal PNG encoder (8- or 16-bit gray, RGB or RGBA, zlib via miniz_oxide).
+
+/// PNG file signature.
+pub const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+/// CRC-32 (ISO-HDLC) as used by PNG chunks.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// Append a chunk (length, type, data, CRC over type + data).
+pub fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let crc = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// Encode an RGBA8 image. `level` is the zlib level (0–10; 1 is fast and
+/// usually good enough for previews). Returns an empty Vec if `rgba` is too short.
+pub fn encode_rgba(rgba: &[u8], width: u32, height: u32, level: u8) -> Vec<u8> {
+    encode(rgba, width, height, ColorType::Rgba, 8, level)
+}
+
+/// Encode an RGBA16 image (samples are written big-endian as PNG requires).
+/// Same `level` and short-input behavior as `encode_rgba`.
+pub fn encode_rgba16(rgba: &[u16], width: u32, height: u32, level: u8) -> Vec<u8> {
+    encode_samples(rgba, width, height, ColorType::Rgba, 16, level)
+}
+
+/// PNG color types written by this encoder.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorType {
+    Gray = 0,
+    Rgb = 2,
+    Rgba = 6,
+}
+
+impl ColorType {
+    pub fn channels(self) -> usize {
+        match self {
+            ColorType::Gray => 1,
+            ColorType::Rgb => 3,
+            ColorType::Rgba => 4,
+        }
+    }
+}
+
+/// Encode interleaved samples of `depth` bits (8, values 0–255, or 16).
+/// Returns an empty Vec if `samples` is too short.
+pub fn encode_samples(samples: &[u16], width: u32, height: u32, color: ColorType, depth: u8, level: u8) -> Vec<u8> {
+    let count = width as usize * height as usize * color.channels();
+    if samples.len() < count {
+        return Vec::new();
+    }
+    let depth = if depth == 16 { 16 } else { 8 };
+    let bytes: Vec<u8> = if depth == 16 {
+        samples[..count].iter().flat_map(|v| v.to_be_bytes()).collect()
+    } else {
+        samples[..count].iter().map(|&v| v.min(255) as u8).collect()
+    };
+    encode(&bytes, width, height, color, depth, level)
+}
+
+/// Image from big-endian sample bytes.
+fn encode(data: &[u8], width: u32, height: u32, color: ColorType, depth: u8, level: u8) -> Vec<u8> {
+    let bpp = color.channels() * depth as usize / 8; // bytes per pixel
+    let stride = width as usize * bpp;
+    if width == 0 || height == 0 || data.len() < stride * height as usize {
+        return Vec::new();
+    }
+
+    // Sub filter on every row: cheap, and fractal gradients compress much better
+    let mut raw = Vec::with_capacity((stride + 1) * height as usize);
+    for row in data.chunks_exact(stride).take(height as usize) {
+        raw.push(1);
+        raw.extend(row.iter().enumerate().map(|(i, &b)| {
+            if i < bpp { b } else { b.wrapping_sub(row[i - bpp]) }
+        }));
+    }
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&width.to_be_bytes());
+    ihdr.extend_from_slice(&height.to_be_bytes());
+    ihdr.extend_from_slice(&[depth, color as u8, 0, 0, 0]); // deflate, adaptive filters, no interlace
+
+    let mut out = SIGNATURE.to_vec();
+    write_chunk(&mut out, b"IHDR", &ihdr);
+    write_chunk(&mut out, b"IDAT", &miniz_oxide::deflate::compress_to_vec_zlib(&raw, level.min(10)));
+    write_chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn test_encode_round_trips_pixels() {
+        let rgba: Vec<u8> = (0..3 * 2 * 4).map(|i| (i * 11) as u8).collect();
+        let png = encode_rgba(&rgba, 3, 2, 1);
+        assert_eq!(&png[..8], &SIGNATURE);
+        assert_eq!(&png[12..16], b"IHDR");
+
+        // IDAT starts after signature + IHDR chunk (8 + 12 + 13)
+        let idat = 8 + 25;
+        let len = u32::from_be_bytes(png[idat..idat + 4].try_into().unwrap()) as usize;
+        assert_eq!(&png[idat + 4..idat + 8], b"IDAT");
+        let raw = miniz_oxide::inflate::decompress_to_vec_zlib(&png[idat + 8..idat + 8 + len]).unwrap();
+
+        // Undo the Sub filter
+        let mut decoded = Vec::new();
+        for row in raw.chunks_exact(13) {
+            assert_eq!(row[0], 1);
+            let start = decoded.len();
+            for (i, &b) in row[1..].iter().enumerate() {
+                let left = if i < 4 { 0 } else { decoded[start + i - 4] };
+                decoded.push(b.wrapping_add(left));
+            }
+        }
+        assert_eq!(decoded, rgba);
+        assert!(png.ends_with(&[0xAE, 0x42, 0x60, 0x82])); // IEND CRC
+    }
+
+    #[test]
+    fn test_encode_16_bit() {
+        let rgba: Vec<u16> = (0..2 * 2 * 4).map(|i| (i * 4099) as u16).collect();
+        let png = encode_rgba16(&rgba, 2, 2, 6);
+        assert_eq!(&png[24..26], &[16, 6]); // bit depth, color type
+
+        let idat = 8 + 25;
+        let len = u32::from_be_bytes(png[idat..idat + 4].try_into().unwrap()) as usize;
+        let raw = miniz_oxide::inflate::decompress_to_vec_zlib(&png[idat + 8..idat + 8 + len]).unwrap();
+        let mut decoded = Vec::new();
+        for row in raw.chunks_exact(17) {
+            let start = decoded.len();
+            for (i, &b) in row[1..].iter().enumerate() {
+                let left = if i < 8 { 0 } else { decoded[start + i - 8] };
+                decoded.push(b.wrapping_add(left));
+            }
+        }
+        let samples: Vec<u16> = decoded.chunks_exact(2).map(|b| u16::from_be_bytes([b[0], b[1]])).collect();
+        assert_eq!(samples, rgba);
+        assert!(encode_rgba16(&rgba[1..], 2, 2, 6).is_empty());
+    }
+}
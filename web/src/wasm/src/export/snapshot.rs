@@ -0,0 +1,79 @@
+//! Progress snapshots of partially rendered G-buffers.
+//!
+//! Relies on the G-buffer being zero-initialized: an all-zero entry has not
+//! been rendered yet (hits always carry a unit normal, misses z_pos = 65535).
+
+use crate::engine::types::SiLight5;
+use crate::lighting::paint::{self, PaintConfig};
+
+/// Has this G-buffer entry been written by a render call?
+pub fn is_rendered(pixel: &SiLight5) -> bool {
+    let SiLight5 { sn_x, sn_y, sn_z, z_pos, .. } = *pixel;
+    sn_x != 0 || sn_y != 0 || sn_z != 0 || z_pos != 0
+}
+
+/// A painted, possibly downscaled, preview image.
+pub struct Snapshot {
+    pub rgba: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Paint the G-buffer with pending pixels left transparent, shrunk by an
+/// integer box filter until both sides fit `max_dim` (0 = full size).
+pub fn snapshot(gbuffer: &[SiLight5], width: u32, height: u32, config: &PaintConfig, max_dim: u32) -> Snapshot {
+    let mut rgba = vec![0u8; (width * height * 4) as usize];
+    paint::paint_gbuffer(gbuffer, &mut rgba, width, height, config);
+    for (px, pixel) in rgba.chunks_exact_mut(4).zip(gbuffer) {
+        if !is_rendered(pixel) {
+            px.fill(0);
+        }
+    }
+
+    let factor = if max_dim == 0 { 1 } else { width.max(height).div_ceil(max_dim).max(1) };
+    if factor == 1 {
+        return Snapshot { rgba, width, height };
+    }
+    let (sw, sh) = (width / factor, height / factor);
+    let mut small = vec![0u8; (sw * sh * 4) as usize];
+    for y in 0..sh {
+        for x in 0..sw {
+            let mut sum = [0u32; 4];
+            for dy in 0..factor {
+                for dx in 0..factor {
+                    let i = (((y * factor + dy) * width + x * factor + dx) * 4) as usize;
+                    for (s, &v) in sum.iter_mut().zip(&rgba[i..i + 4]) {
+                        *s += v as u32;
+                    }
+                }
+            }
+            let o = ((y * sw + x) * 4) as usize;
+            for (d, s) in small[o..o + 4].iter_mut().zip(sum) {
+                *d = (s / (factor * factor)) as u8;
+            }
+        }
+    }
+    Snapshot { rgba: small, width: sw, height: sh }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pending_pixels_are_transparent_and_downscaled() {
+        let miss = SiLight5 { z_pos: 65535, ..Default::default() };
+        let mut gbuffer = vec![SiLight5::default(); 4 * 4];
+        gbuffer[..8].fill(miss); // top two rows done
+
+        let full = snapshot(&gbuffer, 4, 4, &PaintConfig::default(), 0);
+        assert_eq!((full.width, full.height), (4, 4));
+        assert_eq!(full.rgba[3], 255);
+        assert_eq!(full.rgba[15 * 4 + 3], 0);
+
+        let small = snapshot(&gbuffer, 4, 4, &PaintConfig::default(), 2);
+        assert_eq!((small.width, small.height), (2, 2));
+        assert_eq!(small.rgba[3], 255);
+        assert_eq!(small.rgba[2 * 4 + 3], 0);
+    }
+}
@@ -0,0 +1,216 @@
+//! Streaming tiled BigTIFF writer (8-bit RGBA) for gigapixel renders.
+//!
+//! Tiles are written as soon as they are finished, in any order, so only one
+//! tile has to be in memory at a time. The tile offset tables and the single
+//! IFD go at the end of the file; `finish` then patches the header to point
+//! at the IFD. BigTIFF (64-bit offsets) keeps files past 4 GiB valid.
+
+use std::io::{self, Seek, SeekFrom, Write};
+
+/// Tile compression.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TiffCompression {
+    None,
+    /// zlib ("Adobe Deflate", compression 8) at the fast level
+    Deflate,
+}
+
+// TIFF field types
+const SHORT: u16 = 3;
+const LONG: u16 = 4;
+const LONG8: u16 = 16;
+
+/// Writer for one tiled RGBA image.
+pub struct TiledTiffWriter<W: Write + Seek> {
+    out: W,
+    width: u32,
+    height: u32,
+    tile_size: u32,
+    compression: TiffCompression,
+    /// Per tile: (file offset, byte count); 0 count = not written yet
+    tiles: Vec<(u64, u64)>,
+    /// Current end of file
+    end: u64,
+}
+
+impl<W: Write + Seek> TiledTiffWriter<W> {
+    /// Start a file. `tile_size` is rounded up to a multiple of 16 (TIFF requirement).
+    pub fn new(mut out: W, width: u32, height: u32, tile_size: u32, compression: TiffCompression) -> io::Result<Self> {
+        if width == 0 || height == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "empty image"));
+        }
+        let tile_size = tile_size.max(16).next_multiple_of(16);
+        // Header: byte order, version 43, offset size 8, reserved, IFD offset (patched later)
+        out.write_all(b"II")?;
+        out.write_all(&43u16.to_le_bytes())?;
+        out.write_all(&8u16.to_le_bytes())?;
+        out.write_all(&0u16.to_le_bytes())?;
+        out.write_all(&0u64.to_le_bytes())?;
+        let tiles = vec![(0, 0); (width.div_ceil(tile_size) * height.div_ceil(tile_size)) as usize];
+        Ok(Self { out, width, height, tile_size, compression, tiles, end: 16 })
+    }
+
+    pub fn tile_size(&self) -> u32 {
+        self.tile_size
+    }
+
+    /// Tiles across and down.
+    pub fn tile_grid(&self) -> (u32, u32) {
+        (self.width.div_ceil(self.tile_size), self.height.div_ceil(self.tile_size))
+    }
+
+    /// Write tile (tx, ty). `rgba` holds the tile's visible pixels, `w × h`
+    /// (smaller than the tile size at the right and bottom edges); the rest
+    /// of the tile is padded with zeros.
+    pub fn write_tile(&mut self, tx: u32, ty: u32, rgba: &[u8], w: u32, h: u32) -> io::Result<()> {
+        let (across, down) = self.tile_grid();
+        if tx >= across || ty >= down || rgba.len() < (w * h * 4) as usize {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "tile out of range"));
+        }
+        let ts = self.tile_size as usize;
+        let mut tile = vec![0u8; ts * ts * 4];
+        let (w, h) = ((w as usize).min(ts), (h as usize).min(ts));
+        for (dst, src) in tile.chunks_exact_mut(ts * 4).zip(rgba.chunks_exact(w * 4)).take(h) {
+            dst[..w * 4].copy_from_slice(src);
+        }
+        let data = match self.compression {
+            TiffCompression::None => tile,
+            TiffCompression::Deflate => miniz_oxide::deflate::compress_to_vec_zlib(&tile, 1),
+        };
+
+        self.out.seek(SeekFrom::Start(self.end))?;
+        self.out.write_all(&data)?;
+        self.tiles[(ty * across + tx) as usize] = (self.end, data.len() as u64);
+        self.end += data.len() as u64;
+        Ok(())
+    }
+
+    /// Write the tile tables and IFD and return the underlying writer.
+    /// Fails if any tile was never written.
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.tiles.iter().any(|&(_, count)| count == 0) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "missing tiles"));
+        }
+        self.out.seek(SeekFrom::Start(self.end))?;
+        // The tables and the IFD must start on a word boundary
+        if self.end % 2 == 1 {
+            self.out.write_all(&[0])?;
+            self.end += 1;
+        }
+        let offsets_at = self.end;
+        for &(offset, _) in &self.tiles {
+            self.out.write_all(&offset.to_le_bytes())?;
+        }
+        let counts_at = offsets_at + self.tiles.len() as u64 * 8;
+        for &(_, count) in &self.tiles {
+            self.out.write_all(&count.to_le_bytes())?;
+        }
+        let ifd_at = counts_at + self.tiles.len() as u64 * 8;
+
+        let n = self.tiles.len() as u64;
+        let compression = match self.compression {
+            TiffCompression::None => 1,
+            TiffCompression::Deflate => 8,
+        };
+        // (tag, type, count, value or offset); values fit the 8-byte field
+        let entries: [(u16, u16, u64, u64); 12] = [
+            (256, LONG, 1, self.width as u64),         // ImageWidth
+            (257, LONG, 1, self.height as u64),        // ImageLength
+            (258, SHORT, 4, 0x0008_0008_0008_0008),    // BitsPerSample 8,8,8,8
+            (259, SHORT, 1, compression),              // Compression
+            (262, SHORT, 1, 2),                        // Photometric RGB
+            (277, SHORT, 1, 4),                        // SamplesPerPixel
+            (284, SHORT, 1, 1),                        // PlanarConfiguration chunky
+            (322, LONG, 1, self.tile_size as u64),     // TileWidth
+            (323, LONG, 1, self.tile_size as u64),     // TileLength
+            (324, LONG8, n, if n == 1 { self.tiles[0].0 } else { offsets_at }), // TileOffsets
+            (325, LONG8, n, if n == 1 { self.tiles[0].1 } else { counts_at }),  // TileByteCounts
+            (338, SHORT, 1, 2),                        // ExtraSamples: unassociated alpha
+        ];
+        self.out.write_all(&(entries.len() as u64).to_le_bytes())?;
+        for (tag, kind, count, value) in entries {
+            self.out.write_all(&tag.to_le_bytes())?;
+            self.out.write_all(&kind.to_le_bytes())?;
+            self.out.write_all(&count.to_le_bytes())?;
+            self.out.write_all(&value.to_le_bytes())?;
+        }
+        self.out.write_all(&0u64.to_le_bytes())?; // no next IFD
+
+        self.out.seek(SeekFrom::Start(8))?;
+        self.out.write_all(&ifd_at.to_le_bytes())?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn u64_at(b: &[u8], at: usize) -> u64 {
+        u64::from_le_bytes(b[at..at + 8].try_into().unwrap())
+    }
+
+    #[test]
+    fn test_tiles_out_of_order_and_ifd() {
+        let mut writer = TiledTiffWriter::new(Cursor::new(Vec::new()), 20, 16, 16, TiffCompression::None).unwrap();
+        assert_eq!(writer.tile_grid(), (2, 1));
+        let right = vec![7u8; 4 * 16 * 4];
+        writer.write_tile(1, 0, &right, 4, 16).unwrap();
+        let left = vec![9u8; 16 * 16 * 4];
+        writer.write_tile(0, 0, &left, 16, 16).unwrap();
+        let bytes = writer.finish().unwrap().into_inner();
+
+        assert_eq!(&bytes[..4], &[b'I', b'I', 43, 0]);
+        let ifd = u64_at(&bytes, 8) as usize;
+        assert_eq!(u64_at(&bytes, ifd), 12);
+        // Entry 10 is TileOffsets → table of two offsets
+        let entry = ifd + 8 + 9 * 20;
+        assert_eq!(u16::from_le_bytes([bytes[entry], bytes[entry + 1]]), 324);
+        let table = u64_at(&bytes, entry + 12) as usize;
+        let (first, second) = (u64_at(&bytes, table) as usize, u64_at(&bytes, table + 8) as usize);
+        // The right tile came first; padded past its 4 visible columns
+        assert_eq!(second, 16);
+        assert_eq!(&bytes[second..second + 16], &[7; 16]);
+        assert_eq!(bytes[second + 16], 0);
+        assert_eq!(bytes[first], 9);
+    }
+
+    #[test]
+    fn test_deflate_round_trip_on_a_word_boundary() {
+        let mut odd_data = false;
+        for seed in 0..8u8 {
+            let mut writer = TiledTiffWriter::new(Cursor::new(Vec::new()), 32, 16, 16, TiffCompression::Deflate).unwrap();
+            let tiles: Vec<Vec<u8>> =
+                (0..2u8).map(|t| (0..16 * 16 * 4).map(|i| (i as u8 / 7).wrapping_mul(seed + t)).collect()).collect();
+            writer.write_tile(0, 0, &tiles[0], 16, 16).unwrap();
+            writer.write_tile(1, 0, &tiles[1], 16, 16).unwrap();
+            odd_data |= writer.end % 2 == 1;
+            let bytes = writer.finish().unwrap().into_inner();
+
+            let ifd = u64_at(&bytes, 8) as usize;
+            assert_eq!(ifd % 2, 0);
+            let entry = |tag: u16| {
+                let at = ifd + 8 + (0..12).find(|k| bytes[ifd + 8 + k * 20..][..2] == tag.to_le_bytes()).unwrap() * 20;
+                u64_at(&bytes, at + 12) as usize
+            };
+            assert_eq!(entry(259), 8);
+            let (offsets, counts) = (entry(324), entry(325));
+            assert_eq!(offsets % 2, 0);
+            for (t, tile) in tiles.iter().enumerate() {
+                let (at, len) = (u64_at(&bytes, offsets + t * 8) as usize, u64_at(&bytes, counts + t * 8) as usize);
+                let decoded = miniz_oxide::inflate::decompress_to_vec_zlib(&bytes[at..at + len]).unwrap();
+                assert_eq!(&decoded, tile);
+            }
+        }
+        assert!(odd_data, "no tile data of odd length was tried");
+    }
+
+    #[test]
+    fn test_missing_tile_is_an_error() {
+        let mut writer = TiledTiffWriter::new(Cursor::new(Vec::new()), 40, 40, 16, TiffCompression::Deflate).unwrap();
+        writer.write_tile(0, 0, &[0; 16 * 16 * 4], 16, 16).unwrap();
+        assert!(writer.finish().is_err());
+    }
+}
@@ -0,0 +1,260 @@
+//! Volume export: the distance field or occupancy sampled on a regular grid.
+//!
+//! Two outputs:
+//!
+//! - raw little-endian `f32` voxels (x fastest, then y, then z) with a JSON
+//!   header naming the grid, for Houdini/Blender volume importers and scripts
+//! - MagicaVoxel `.vox` (occupancy only, at most 256 voxels per axis)
+//!
+//! Voxel values are taken at voxel centers. Distances are signed, negative
+//! inside, as SDF volumes expect.
+
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::engine::types::Vec3D;
+use crate::formulas::DistanceField;
+
+/// Largest grid size per axis.
+pub const MAX_RESOLUTION: u32 = 1024;
+/// Largest grid size per axis in a .vox file.
+pub const VOX_MAX_SIZE: u32 = 256;
+/// Header format version.
+pub const HEADER_VERSION: u32 = 1;
+
+/// What each voxel holds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VolumeKind {
+    /// Signed distance estimate, negative inside
+    Distance,
+    /// 1 inside the fractal (orbit never escapes, or DE ≤ 0), 0 outside
+    Occupancy,
+}
+
+impl VolumeKind {
+    pub fn from_u32(v: u32) -> Self {
+        if v == 1 { VolumeKind::Occupancy } else { VolumeKind::Distance }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VolumeError {
+    /// The box is empty or not finite
+    Bounds,
+    /// A grid size outside 1..=MAX_RESOLUTION
+    Resolution(u32),
+    /// .vox needs occupancy and at most VOX_MAX_SIZE voxels per axis
+    Vox,
+}
+
+impl fmt::Display for VolumeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VolumeError::Bounds => write!(f, "volume bounds must be a finite, non-empty box"),
+            VolumeError::Resolution(r) => write!(f, "volume resolution {r} is outside 1..={MAX_RESOLUTION}"),
+            VolumeError::Vox => write!(f, ".vox export needs an occupancy grid of at most {VOX_MAX_SIZE}³ voxels"),
+        }
+    }
+}
+
+impl std::error::Error for VolumeError {}
+
+/// A sampled grid.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Volume {
+    pub min: Vec3D,
+    pub max: Vec3D,
+    /// Voxels per axis
+    pub size: [u32; 3],
+    pub kind: VolumeKind,
+    /// x fastest, then y, then z
+    pub voxels: Vec<f32>,
+}
+
+/// JSON header of a raw volume.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Header {
+    version: u32,
+    kind: VolumeKind,
+    size: [u32; 3],
+    min: [f64; 3],
+    max: [f64; 3],
+    voxel_size: [f64; 3],
+    data_type: &'static str,
+    byte_order: &'static str,
+    order: &'static str,
+}
+
+/// Sample `field` at the voxel centers of a `size` grid over `[min, max]`.
+pub fn sample<F: DistanceField + ?Sized>(
+    field: &F,
+    min: Vec3D,
+    max: Vec3D,
+    size: [u32; 3],
+    kind: VolumeKind,
+) -> Result<Volume, VolumeError> {
+    let finite = [min.x, min.y, min.z, max.x, max.y, max.z].iter().all(|v| v.is_finite());
+    if !finite || max.x <= min.x || max.y <= min.y || max.z <= min.z {
+        return Err(VolumeError::Bounds);
+    }
+    if let Some(&bad) = size.iter().find(|&&n| n == 0 || n > MAX_RESOLUTION) {
+        return Err(VolumeError::Resolution(bad));
+    }
+
+    let mut volume = Volume { min, max, size, kind, voxels: Vec::with_capacity(size.iter().product::<u32>() as usize) };
+    let step = volume.voxel_size();
+    for z in 0..size[2] {
+        for y in 0..size[1] {
+            for x in 0..size[0] {
+                let p = Vec3D {
+                    x: min.x + (x as f64 + 0.5) * step[0],
+                    y: min.y + (y as f64 + 0.5) * step[1],
+                    z: min.z + (z as f64 + 0.5) * step[2],
+                };
+                let v = match kind {
+                    VolumeKind::Distance => field.signed_de(&p),
+                    VolumeKind::Occupancy => {
+                        let fr = field.compute_de(&p);
+                        if fr.inside || fr.de <= 0.0 { 1.0 } else { 0.0 }
+                    }
+                };
+                volume.voxels.push(v as f32);
+            }
+        }
+    }
+    Ok(volume)
+}
+
+impl Volume {
+    /// Edge lengths of one voxel.
+    pub fn voxel_size(&self) -> [f64; 3] {
+        [
+            (self.max.x - self.min.x) / self.size[0] as f64,
+            (self.max.y - self.min.y) / self.size[1] as f64,
+            (self.max.z - self.min.z) / self.size[2] as f64,
+        ]
+    }
+
+    /// Header describing `raw`.
+    pub fn header_json(&self) -> String {
+        let header = Header {
+            version: HEADER_VERSION,
+            kind: self.kind,
+            size: self.size,
+            min: [self.min.x, self.min.y, self.min.z],
+            max: [self.max.x, self.max.y, self.max.z],
+            voxel_size: self.voxel_size(),
+            data_type: "float32",
+            byte_order: "little",
+            order: "xyz",
+        };
+        serde_json::to_string(&header).expect("volume headers always serialize")
+    }
+
+    /// Voxels as little-endian f32 bytes.
+    pub fn raw(&self) -> Vec<u8> {
+        self.voxels.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    /// MagicaVoxel file of the occupied voxels (palette index 1).
+    pub fn to_vox(&self) -> Result<Vec<u8>, VolumeError> {
+        if self.kind != VolumeKind::Occupancy || self.size.iter().any(|&n| n > VOX_MAX_SIZE) {
+            return Err(VolumeError::Vox);
+        }
+        let [sx, sy, _] = self.size.map(|n| n as usize);
+        let filled: Vec<[u8; 4]> = self
+            .voxels
+            .iter()
+            .enumerate()
+            .filter(|(_, &v)| v > 0.5)
+            .map(|(i, _)| [(i % sx) as u8, (i / sx % sy) as u8, (i / (sx * sy)) as u8, 1])
+            .collect();
+
+        let mut size = Vec::new();
+        for n in self.size {
+            size.extend_from_slice(&n.to_le_bytes());
+        }
+        let mut xyzi = (filled.len() as u32).to_le_bytes().to_vec();
+        xyzi.extend(filled.iter().flatten());
+
+        let mut children = Vec::new();
+        vox_chunk(&mut children, b"SIZE", &size, &[]);
+        vox_chunk(&mut children, b"XYZI", &xyzi, &[]);
+        let mut out = b"VOX ".to_vec();
+        out.extend_from_slice(&150u32.to_le_bytes());
+        vox_chunk(&mut out, b"MAIN", &[], &children);
+        Ok(out)
+    }
+}
+
+/// Chunk: id, content size, children size, content, children.
+fn vox_chunk(out: &mut Vec<u8>, id: &[u8; 4], content: &[u8], children: &[u8]) {
+    out.extend_from_slice(id);
+    out.extend_from_slice(&(content.len() as u32).to_le_bytes());
+    out.extend_from_slice(&(children.len() as u32).to_le_bytes());
+    out.extend_from_slice(content);
+    out.extend_from_slice(children);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formulas::FormulaResult;
+    use crate::math::math3d;
+
+    struct Sphere(f64);
+
+    impl DistanceField for Sphere {
+        fn compute_de(&self, p: &Vec3D) -> FormulaResult {
+            let de = math3d::vec3d_length(p) - self.0;
+            FormulaResult { de: de.max(0.0), inside: de < 0.0, interior_de: Some(-de.min(0.0)), ..Default::default() }
+        }
+    }
+
+    fn unit_box() -> (Vec3D, Vec3D) {
+        (Vec3D { x: -1.0, y: -1.0, z: -1.0 }, Vec3D { x: 1.0, y: 1.0, z: 1.0 })
+    }
+
+    #[test]
+    fn test_distance_grid_and_header() {
+        let (min, max) = unit_box();
+        let volume = sample(&Sphere(0.5), min, max, [4, 2, 2], VolumeKind::Distance).unwrap();
+        assert_eq!(volume.voxels.len(), 16);
+        // Voxel (1, 0, 0) is centered at (-0.25, -0.5, -0.5)
+        let expected = (0.25f64 * 0.25 + 0.5).sqrt() - 0.5;
+        assert!((volume.voxels[1] as f64 - expected).abs() < 1e-6);
+        assert_eq!(volume.raw().len(), 64);
+
+        let header: serde_json::Value = serde_json::from_str(&volume.header_json()).unwrap();
+        assert_eq!(header["kind"], "distance");
+        assert_eq!(header["size"], serde_json::json!([4, 2, 2]));
+        assert_eq!(header["voxelSize"], serde_json::json!([0.5, 1.0, 1.0]));
+        assert_eq!(header["dataType"], "float32");
+
+        assert_eq!(volume.to_vox(), Err(VolumeError::Vox));
+        assert_eq!(sample(&Sphere(0.5), min, max, [0, 2, 2], VolumeKind::Distance), Err(VolumeError::Resolution(0)));
+    }
+
+    #[test]
+    fn test_occupancy_vox() {
+        let (min, max) = unit_box();
+        let volume = sample(&Sphere(0.5), min, max, [4, 4, 4], VolumeKind::Occupancy).unwrap();
+        // The eight voxels around the origin are inside
+        assert_eq!(volume.voxels.iter().filter(|&&v| v == 1.0).count(), 8);
+
+        let vox = volume.to_vox().unwrap();
+        assert_eq!(&vox[..8], b"VOX \x96\0\0\0");
+        assert_eq!(&vox[8..12], b"MAIN");
+        let size = 20;
+        assert_eq!(&vox[size..size + 4], b"SIZE");
+        assert_eq!(&vox[size + 12..size + 24], &[4, 0, 0, 0, 4, 0, 0, 0, 4, 0, 0, 0]);
+        let xyzi = size + 24;
+        assert_eq!(&vox[xyzi..xyzi + 4], b"XYZI");
+        assert_eq!(u32::from_le_bytes(vox[xyzi + 12..xyzi + 16].try_into().unwrap()), 8);
+        assert_eq!(&vox[xyzi + 16..xyzi + 20], &[1, 1, 1, 1]);
+        assert_eq!(vox.len(), xyzi + 16 + 8 * 4);
+    }
+}
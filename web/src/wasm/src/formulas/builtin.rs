@@ -3,7 +3,9 @@
 /// Each formula implements the Formula trait providing both
 /// full DE computation and single-step iteration for hybrid mode.
 
-use crate::engine::types::Vec3D;
+use crate::engine::types::{Matrix3, Vec3D};
+use crate::math::{math3d, strict};
+use super::simd::Kernel;
 use super::{Formula, FormulaResult, IterationState};
 
 // ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
@@ -40,14 +42,16 @@ impl Formula for MandelbulbPower2 {
             if self.iterate_once(&mut state, bailout) {
                 // Escaped — compute DE
                 let r = state.r_sqr.sqrt();
-                let de = 0.5 * r * r.ln() / state.dr;
-                let smooth = (i as f64) + 1.0 - (state.r_sqr.ln().ln() / std::f64::consts::LN_2);
+                let de = 0.5 * r * strict::ln(r) / state.dr;
+                let smooth = (i as f64) + 1.0 - (strict::ln(strict::ln(state.r_sqr)) / std::f64::consts::LN_2);
                 return FormulaResult {
                     de: de.max(0.0),
                     smooth_it: smooth,
                     orbit_trap: state.orbit_trap,
+                    orbit_trap2: state.orbit_trap2,
                     inside: false,
                     iterations: i,
+                    interior_de: None,
                 };
             }
         }
@@ -69,22 +73,22 @@ impl Formula for MandelbulbPower2 {
 
         // Track orbit trap
         let otrap = x.abs().min(y.abs()).min(z.abs());
-        if otrap < state.orbit_trap { state.orbit_trap = otrap; }
+        state.track_traps(otrap);
 
         // Power 2 Mandelbulb: spherical coordinates method
-        let theta = (z / r).acos();
-        let phi = y.atan2(x);
+        let theta = strict::acos(z / r);
+        let phi = strict::atan2(y, x);
         let power = 2.0;
 
-        state.dr = r.powf(power - 1.0) * power * state.dr + 1.0;
+        state.dr = strict::powf(r, power - 1.0) * power * state.dr + 1.0;
 
-        let zr = r.powf(power);
+        let zr = strict::powf(r, power);
         let new_theta = theta * power;
         let new_phi = phi * power;
 
-        state.x = zr * new_theta.sin() * new_phi.cos() + state.c1;
-        state.y = zr * new_theta.sin() * new_phi.sin() + state.c2;
-        state.z = zr * new_theta.cos() + state.c3;
+        state.x = zr * strict::sin(new_theta) * strict::cos(new_phi) + state.c1;
+        state.y = zr * strict::sin(new_theta) * strict::sin(new_phi) + state.c2;
+        state.z = zr * strict::cos(new_theta) + state.c3;
 
         false
     }
@@ -105,14 +109,16 @@ impl Formula for MandelbulbPower8 {
             state.iteration = i;
             if self.iterate_once(&mut state, bailout) {
                 let r = state.r_sqr.sqrt();
-                let de = 0.5 * r * r.ln() / state.dr;
-                let smooth = (i as f64) + 1.0 - (state.r_sqr.ln().ln() / (8.0f64.ln()));
+                let de = 0.5 * r * strict::ln(r) / state.dr;
+                let smooth = (i as f64) + 1.0 - (strict::ln(strict::ln(state.r_sqr)) / (strict::ln(8.0f64)));
                 return FormulaResult {
                     de: de.max(0.0),
                     smooth_it: smooth,
                     orbit_trap: state.orbit_trap,
+                    orbit_trap2: state.orbit_trap2,
                     inside: false,
                     iterations: i,
+                    interior_de: None,
                 };
             }
         }
@@ -132,11 +138,11 @@ impl Formula for MandelbulbPower8 {
 
         let r = r_sqr.sqrt();
         let otrap = x.abs().min(y.abs()).min(z.abs());
-        if otrap < state.orbit_trap { state.orbit_trap = otrap; }
+        state.track_traps(otrap);
 
         // Optimized power-8 using trig identities
-        let theta = (z / r).acos();
-        let phi = y.atan2(x);
+        let theta = strict::acos(z / r);
+        let phi = strict::atan2(y, x);
 
         // dr = r^7 * 8 * dr + 1
         let r7 = r_sqr * r_sqr * r_sqr * r; // r^7
@@ -145,14 +151,18 @@ impl Formula for MandelbulbPower8 {
         let r8 = r7 * r; // r^8
         let theta8 = theta * 8.0;
         let phi8 = phi * 8.0;
-        let st = theta8.sin();
+        let st = strict::sin(theta8);
 
-        state.x = r8 * st * phi8.cos() + state.c1;
-        state.y = r8 * st * phi8.sin() + state.c2;
-        state.z = r8 * theta8.cos() + state.c3;
+        state.x = r8 * st * strict::cos(phi8) + state.c1;
+        state.y = r8 * st * strict::sin(phi8) + state.c2;
+        state.z = r8 * strict::cos(theta8) + state.c3;
 
         false
     }
+
+    fn kernel(&self) -> Option<Kernel> {
+        Some(Kernel::Mandelbulb8)
+    }
 }
 
 // ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
@@ -177,6 +187,17 @@ impl Default for AmazingBox {
     }
 }
 
+impl AmazingBox {
+    /// Parameter order: [scale, fold_limit, min_radius_sq, fixed_radius_sq]
+    pub fn from_params(params: &[f64]) -> Self {
+        let mut f = Self::default();
+        apply_params(&mut [
+            &mut f.scale, &mut f.fold_limit, &mut f.min_radius_sq, &mut f.fixed_radius_sq,
+        ], params);
+        f
+    }
+}
+
 impl Formula for AmazingBox {
     fn name(&self) -> &str { "Amazing Box" }
 
@@ -190,10 +211,12 @@ impl Formula for AmazingBox {
                 let de = r / state.dr.abs();
                 return FormulaResult {
                     de,
-                    smooth_it: i as f64 + (bailout.ln() - state.r_sqr.ln()) / (2.0 * self.scale.abs().ln()),
+                    smooth_it: i as f64 + (strict::ln(bailout) - strict::ln(state.r_sqr)) / (2.0 * strict::ln(self.scale.abs())),
                     orbit_trap: state.orbit_trap,
+                    orbit_trap2: state.orbit_trap2,
                     inside: false,
                     iterations: i,
+                    interior_de: None,
                 };
             }
         }
@@ -229,10 +252,19 @@ impl Formula for AmazingBox {
         state.r_sqr = state.x * state.x + state.y * state.y + state.z * state.z;
 
         let otrap = state.x.abs().min(state.y.abs()).min(state.z.abs());
-        if otrap < state.orbit_trap { state.orbit_trap = otrap; }
+        state.track_traps(otrap);
 
         state.r_sqr > bailout
     }
+
+    fn kernel(&self) -> Option<Kernel> {
+        Some(Kernel::AmazingBox {
+            scale: self.scale,
+            fold_limit: self.fold_limit,
+            min_radius_sq: self.min_radius_sq,
+            fixed_radius_sq: self.fixed_radius_sq,
+        })
+    }
 }
 
 // ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
@@ -251,6 +283,15 @@ impl Default for AmazingSurf {
     }
 }
 
+impl AmazingSurf {
+    /// Parameter order: [scale, fold_x, fold_y]
+    pub fn from_params(params: &[f64]) -> Self {
+        let mut f = Self::default();
+        apply_params(&mut [&mut f.scale, &mut f.fold_x, &mut f.fold_y], params);
+        f
+    }
+}
+
 impl Formula for AmazingSurf {
     fn name(&self) -> &str { "Amazing Surf" }
 
@@ -265,8 +306,10 @@ impl Formula for AmazingSurf {
                     de,
                     smooth_it: i as f64,
                     orbit_trap: state.orbit_trap,
+                    orbit_trap2: state.orbit_trap2,
                     inside: false,
                     iterations: i,
+                    interior_de: None,
                 };
             }
         }
@@ -297,7 +340,7 @@ impl Formula for AmazingSurf {
         state.r_sqr = state.x * state.x + state.y * state.y + state.z * state.z;
 
         let otrap = state.x.abs().min(state.y.abs()).min(state.z.abs());
-        if otrap < state.orbit_trap { state.orbit_trap = otrap; }
+        state.track_traps(otrap);
 
         state.r_sqr > bailout
     }
@@ -319,13 +362,15 @@ impl Formula for QuaternionJulia {
             state.iteration = i;
             if self.iterate_once(&mut state, bailout) {
                 let r = state.r_sqr.sqrt();
-                let de = 0.5 * r * r.ln() / state.dr;
+                let de = 0.5 * r * strict::ln(r) / state.dr;
                 return FormulaResult {
                     de: de.max(0.0),
                     smooth_it: i as f64,
                     orbit_trap: state.orbit_trap,
+                    orbit_trap2: state.orbit_trap2,
                     inside: false,
                     iterations: i,
+                    interior_de: None,
                 };
             }
         }
@@ -345,7 +390,7 @@ impl Formula for QuaternionJulia {
         state.dr = 2.0 * r * state.dr + 1.0;
 
         let otrap = x.abs().min(y.abs()).min(z.abs());
-        if otrap < state.orbit_trap { state.orbit_trap = otrap; }
+        state.track_traps(otrap);
 
         // Quaternion squaring: q^2 = (a^2 - |v|^2, 2*a*v)
         // where q = (a, v) = (x, y, z, w) mapped to quaternion
@@ -373,13 +418,15 @@ impl Formula for Tricorn {
             state.iteration = i;
             if self.iterate_once(&mut state, bailout) {
                 let r = state.r_sqr.sqrt();
-                let de = 0.5 * r * r.ln() / state.dr;
+                let de = 0.5 * r * strict::ln(r) / state.dr;
                 return FormulaResult {
                     de: de.max(0.0),
                     smooth_it: i as f64,
                     orbit_trap: state.orbit_trap,
+                    orbit_trap2: state.orbit_trap2,
                     inside: false,
                     iterations: i,
+                    interior_de: None,
                 };
             }
         }
@@ -397,20 +444,20 @@ impl Formula for Tricorn {
         state.dr = r * 2.0 * state.dr + 1.0;
 
         let otrap = x.abs().min(y.abs()).min(z.abs());
-        if otrap < state.orbit_trap { state.orbit_trap = otrap; }
+        state.track_traps(otrap);
 
         // Tricorn uses conjugate (negate y) before squaring in spherical coords
-        let theta = (z / r).acos();
-        let phi = (-y).atan2(x); // conjugate — negate y
+        let theta = strict::acos(z / r);
+        let phi = strict::atan2(-y, x); // conjugate — negate y
 
         let r2 = state.r_sqr;
         let theta2 = theta * 2.0;
         let phi2 = phi * 2.0;
-        let st = theta2.sin();
+        let st = strict::sin(theta2);
 
-        state.x = r2 * st * phi2.cos() + state.c1;
-        state.y = r2 * st * phi2.sin() + state.c2;
-        state.z = r2 * theta2.cos() + state.c3;
+        state.x = r2 * st * strict::cos(phi2) + state.c1;
+        state.y = r2 * st * strict::sin(phi2) + state.c2;
+        state.z = r2 * strict::cos(theta2) + state.c3;
 
         false
     }
@@ -431,13 +478,15 @@ impl Formula for Bulbox {
             state.iteration = i;
             if self.iterate_once(&mut state, bailout) {
                 let r = state.r_sqr.sqrt();
-                let de = 0.5 * r * r.ln() / state.dr;
+                let de = 0.5 * r * strict::ln(r) / state.dr;
                 return FormulaResult {
                     de: de.max(0.0),
                     smooth_it: i as f64,
                     orbit_trap: state.orbit_trap,
+                    orbit_trap2: state.orbit_trap2,
                     inside: false,
                     iterations: i,
+                    interior_de: None,
                 };
             }
         }
@@ -465,16 +514,16 @@ impl Formula for Bulbox {
         state.dr = 2.0 * r * state.dr + 1.0;
 
         let otrap = x.abs().min(y.abs()).min(z.abs());
-        if otrap < state.orbit_trap { state.orbit_trap = otrap; }
+        state.track_traps(otrap);
 
-        let theta = (z / r).acos();
-        let phi = y.atan2(x);
+        let theta = strict::acos(z / r);
+        let phi = strict::atan2(y, x);
 
         let zr = state.r_sqr; // r^2
-        let st = (theta * 2.0).sin();
-        state.x = zr * st * (phi * 2.0).cos() + state.c1;
-        state.y = zr * st * (phi * 2.0).sin() + state.c2;
-        state.z = zr * (theta * 2.0).cos() + state.c3;
+        let st = strict::sin(theta * 2.0);
+        state.x = zr * st * strict::cos(phi * 2.0) + state.c1;
+        state.y = zr * st * strict::sin(phi * 2.0) + state.c2;
+        state.z = zr * strict::cos(theta * 2.0) + state.c3;
 
         false
     }
@@ -504,13 +553,15 @@ impl Formula for FoldingIntPow {
             state.iteration = i;
             if self.iterate_once(&mut state, bailout) {
                 let r = state.r_sqr.sqrt();
-                let de = 0.5 * r * r.ln() / state.dr;
+                let de = 0.5 * r * strict::ln(r) / state.dr;
                 return FormulaResult {
                     de: de.max(0.0),
                     smooth_it: i as f64,
                     orbit_trap: state.orbit_trap,
+                    orbit_trap2: state.orbit_trap2,
                     inside: false,
                     iterations: i,
+                    interior_de: None,
                 };
             }
         }
@@ -535,21 +586,21 @@ impl Formula for FoldingIntPow {
 
         let r = state.r_sqr.sqrt();
         let p = self.power as f64;
-        state.dr = r.powf(p - 1.0) * p * state.dr + 1.0;
+        state.dr = strict::powf(r, p - 1.0) * p * state.dr + 1.0;
 
         let otrap = x.abs().min(y.abs()).min(z.abs());
-        if otrap < state.orbit_trap { state.orbit_trap = otrap; }
+        state.track_traps(otrap);
 
-        let theta = (z / r).acos();
-        let phi = y.atan2(x);
-        let rp = r.powf(p);
+        let theta = strict::acos(z / r);
+        let phi = strict::atan2(y, x);
+        let rp = strict::powf(r, p);
         let tp = theta * p;
         let pp = phi * p;
-        let st = tp.sin();
+        let st = strict::sin(tp);
 
-        state.x = rp * st * pp.cos() + state.c1;
-        state.y = rp * st * pp.sin() + state.c2;
-        state.z = rp * tp.cos() + state.c3;
+        state.x = rp * st * strict::cos(pp) + state.c1;
+        state.y = rp * st * strict::sin(pp) + state.c2;
+        state.z = rp * strict::cos(tp) + state.c3;
 
         false
     }
@@ -578,14 +629,16 @@ impl Formula for RealPower {
             state.iteration = i;
             if self.iterate_once(&mut state, bailout) {
                 let r = state.r_sqr.sqrt();
-                let de = 0.5 * r * r.ln() / state.dr;
-                let smooth = (i as f64) + 1.0 - (state.r_sqr.ln().ln() / self.power.ln());
+                let de = 0.5 * r * strict::ln(r) / state.dr;
+                let smooth = (i as f64) + 1.0 - (strict::ln(strict::ln(state.r_sqr)) / strict::ln(self.power));
                 return FormulaResult {
                     de: de.max(0.0),
                     smooth_it: smooth,
                     orbit_trap: state.orbit_trap,
+                    orbit_trap2: state.orbit_trap2,
                     inside: false,
                     iterations: i,
+                    interior_de: None,
                 };
             }
         }
@@ -602,21 +655,21 @@ impl Formula for RealPower {
         let r = state.r_sqr.sqrt();
         let p = self.power;
 
-        state.dr = r.powf(p - 1.0) * p * state.dr + 1.0;
+        state.dr = strict::powf(r, p - 1.0) * p * state.dr + 1.0;
 
         let otrap = x.abs().min(y.abs()).min(z.abs());
-        if otrap < state.orbit_trap { state.orbit_trap = otrap; }
+        state.track_traps(otrap);
 
-        let theta = (z / r).acos();
-        let phi = y.atan2(x);
-        let rp = r.powf(p);
+        let theta = strict::acos(z / r);
+        let phi = strict::atan2(y, x);
+        let rp = strict::powf(r, p);
         let tp = theta * p;
         let pp = phi * p;
-        let st = tp.sin();
+        let st = strict::sin(tp);
 
-        state.x = rp * st * pp.cos() + state.c1;
-        state.y = rp * st * pp.sin() + state.c2;
-        state.z = rp * tp.cos() + state.c3;
+        state.x = rp * st * strict::cos(pp) + state.c1;
+        state.y = rp * st * strict::sin(pp) + state.c2;
+        state.z = rp * strict::cos(tp) + state.c3;
 
         false
     }
@@ -638,13 +691,15 @@ impl Formula for AexionC {
             state.iteration = i;
             if self.iterate_once(&mut state, bailout) {
                 let r = state.r_sqr.sqrt();
-                let de = 0.5 * r * r.ln() / state.dr;
+                let de = 0.5 * r * strict::ln(r) / state.dr;
                 return FormulaResult {
                     de: de.max(0.0),
                     smooth_it: i as f64,
                     orbit_trap: state.orbit_trap,
+                    orbit_trap2: state.orbit_trap2,
                     inside: false,
                     iterations: i,
+                    interior_de: None,
                 };
             }
         }
@@ -664,7 +719,7 @@ impl Formula for AexionC {
         state.dr = 2.0 * r * state.dr + 1.0;
 
         let otrap = x.abs().min(y.abs()).min(z.abs());
-        if otrap < state.orbit_trap { state.orbit_trap = otrap; }
+        state.track_traps(otrap);
 
         // 4D octahedral / bicomplex squaring
         let xx = x * x - y * y - z * z + w * w;
@@ -681,6 +736,587 @@ impl Formula for AexionC {
     }
 }
 
+// ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
+// Shared helpers for the ABox modification family (ABoxMod1/2, ASurfMod1)
+// ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
+
+/// Kali-style offset fold: `Fold - |(|v + m| - Fold)| - |m|`.
+#[inline(always)]
+fn kali_fold(v: f64, fold: f64, offset: f64) -> f64 {
+    fold - ((v + offset).abs() - fold).abs() - offset.abs()
+}
+
+/// Classic box fold: `|v + f| - |v - f| - v`.
+#[inline(always)]
+fn box_fold(v: f64, fold: f64) -> f64 {
+    (v + fold).abs() - (v - fold).abs() - v
+}
+
+/// ABox sphere fold multiplier: `Scale/Min_R²`, `Scale/rr` or `Scale`.
+#[inline(always)]
+fn sphere_fold_scale(rr: f64, min_r: f64, scale: f64) -> f64 {
+    let min_r2 = min_r * min_r;
+    if rr < min_r2 {
+        if min_r2 > 1e-30 { scale / min_r2 } else { scale }
+    } else if rr < 1.0 {
+        scale / rr
+    } else {
+        scale
+    }
+}
+
+/// Scale for this call: `scale` on the first, then `Scale += Scale_vary * (|Scale| - 1)`
+/// applied to the value carried in `state`.
+#[inline]
+fn varied_scale(state: &mut IterationState, scale: f64, scale_vary: f64) -> f64 {
+    if scale_vary == 0.0 {
+        return scale;
+    }
+    let s = match state.varied_scale {
+        Some(s) => s + scale_vary * (s.abs() - 1.0),
+        None => scale,
+    };
+    state.varied_scale = Some(s);
+    s
+}
+
+/// Squared capsule distance: spherical caps on top/bottom, cylinder on the body.
+#[inline(always)]
+fn capsule_r_sqr(state: &IterationState, half_size: f64) -> f64 {
+    let zc = state.z.abs() - half_size;
+    let rr = state.x * state.x + state.y * state.y;
+    if zc > 0.0 { rr + zc * zc } else { rr }
+}
+
+/// Shared escape loop for the ABox family (DE = r / |dr|).
+fn abox_family_de(
+    formula: &dyn Formula,
+    scale: f64,
+    pos: &Vec3D,
+    max_iter: u32,
+    bailout: f64,
+    julia_c: Option<&Vec3D>,
+) -> FormulaResult {
+    let mut state = IterationState::new(pos, julia_c);
+    for i in 0..max_iter {
+        state.iteration = i;
+        if formula.iterate_once(&mut state, bailout) {
+            let r = state.r_sqr.sqrt();
+            let log_scale = strict::ln(scale.abs().max(1.0 + 1e-6));
+            return FormulaResult {
+                de: r / state.dr.abs(),
+                smooth_it: i as f64 + (strict::ln(bailout) - strict::ln(state.r_sqr)) / (2.0 * log_scale),
+                orbit_trap: state.orbit_trap,
+                orbit_trap2: state.orbit_trap2,
+                inside: false,
+                iterations: i,
+                interior_de: None,
+            };
+        }
+    }
+    let r = state.r_sqr.sqrt();
+    FormulaResult { de: r / state.dr.abs(), smooth_it: max_iter as f64, inside: true, iterations: max_iter, ..Default::default() }
+}
+
+/// Finish an ABox-family iteration: scale by `m` (Z also by `z_mul`), add C,
+/// update dr, trap and r².
+#[inline(always)]
+fn abox_apply_scale(state: &mut IterationState, m: f64, z_mul: f64, bailout: f64) -> bool {
+    state.x = state.x * m + state.c1;
+    state.y = state.y * m + state.c2;
+    state.z = state.z * m * z_mul + state.c3;
+    state.dr = state.dr * m.abs() + 1.0;
+
+    state.r_sqr = state.x * state.x + state.y * state.y + state.z * state.z;
+
+    let otrap = state.x.abs().min(state.y.abs()).min(state.z.abs());
+    state.track_traps(otrap);
+
+    state.r_sqr > bailout
+}
+
+/// Override `fields` in order with the leading entries of `params`.
+#[inline]
+fn apply_params(fields: &mut [&mut f64], params: &[f64]) {
+    for (field, value) in fields.iter_mut().zip(params) {
+        **field = *value;
+    }
+}
+
+// ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
+// ABoxMod1 — Kali offset folds with per-axis asymmetry and varying scale
+// ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
+
+pub struct ABoxMod1 {
+    pub scale: f64,
+    pub min_r: f64,
+    pub fold: f64,
+    pub scale_vary: f64,
+    pub fold_x_mod: f64,
+    pub fold_y_mod: f64,
+    pub fold_z_mod: f64,
+    /// Z multiplier applied after scaling (MB3D "Z multiplier")
+    pub z_mul: f64,
+}
+
+impl Default for ABoxMod1 {
+    fn default() -> Self {
+        Self {
+            scale: 2.0,
+            min_r: 0.0,
+            fold: 2.0,
+            scale_vary: 0.0,
+            fold_x_mod: 0.0,
+            fold_y_mod: 0.0,
+            fold_z_mod: 0.0,
+            z_mul: 1.0,
+        }
+    }
+}
+
+impl ABoxMod1 {
+    /// Parameter order: [scale, min_r, fold, scale_vary, fold_x_mod, fold_y_mod, fold_z_mod, z_mul]
+    pub fn from_params(params: &[f64]) -> Self {
+        let mut f = Self::default();
+        apply_params(&mut [
+            &mut f.scale, &mut f.min_r, &mut f.fold, &mut f.scale_vary,
+            &mut f.fold_x_mod, &mut f.fold_y_mod, &mut f.fold_z_mod, &mut f.z_mul,
+        ], params);
+        f
+    }
+}
+
+impl Formula for ABoxMod1 {
+    fn name(&self) -> &str { "ABoxMod1" }
+
+    fn compute_de(&self, pos: &Vec3D, max_iter: u32, bailout: f64, julia_c: Option<&Vec3D>) -> FormulaResult {
+        abox_family_de(self, self.scale, pos, max_iter, bailout, julia_c)
+    }
+
+    fn iterate_once(&self, state: &mut IterationState, bailout: f64) -> bool {
+        let scale = varied_scale(state, self.scale, self.scale_vary);
+
+        state.x = kali_fold(state.x, self.fold, self.fold_x_mod);
+        state.y = kali_fold(state.y, self.fold, self.fold_y_mod);
+        state.z = kali_fold(state.z, self.fold, self.fold_z_mod);
+
+        let rr = state.x * state.x + state.y * state.y + state.z * state.z;
+        let m = sphere_fold_scale(rr, self.min_r, scale);
+        abox_apply_scale(state, m, self.z_mul, bailout)
+    }
+}
+
+// ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
+// ABoxMod2 — separate Z fold and cylindrical (non-conformal) inversion
+// ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
+
+pub struct ABoxMod2 {
+    pub scale: f64,
+    pub min_r: f64,
+    pub fold_xy: f64,
+    pub fold_z: f64,
+    pub cyl_half_size: f64,
+    /// Z multiplier applied after scaling
+    pub z_mul: f64,
+}
+
+impl Default for ABoxMod2 {
+    fn default() -> Self {
+        Self { scale: 2.0, min_r: 0.5, fold_xy: 1.0, fold_z: 1.5, cyl_half_size: 0.5, z_mul: 1.0 }
+    }
+}
+
+impl ABoxMod2 {
+    /// Parameter order: [scale, min_r, fold_xy, fold_z, cyl_half_size, z_mul]
+    pub fn from_params(params: &[f64]) -> Self {
+        let mut f = Self::default();
+        apply_params(&mut [
+            &mut f.scale, &mut f.min_r, &mut f.fold_xy, &mut f.fold_z, &mut f.cyl_half_size, &mut f.z_mul,
+        ], params);
+        f
+    }
+}
+
+impl Formula for ABoxMod2 {
+    fn name(&self) -> &str { "ABoxMod2" }
+
+    fn compute_de(&self, pos: &Vec3D, max_iter: u32, bailout: f64, julia_c: Option<&Vec3D>) -> FormulaResult {
+        abox_family_de(self, self.scale, pos, max_iter, bailout, julia_c)
+    }
+
+    fn iterate_once(&self, state: &mut IterationState, bailout: f64) -> bool {
+        state.x = box_fold(state.x, self.fold_xy);
+        state.y = box_fold(state.y, self.fold_xy);
+        state.z = box_fold(state.z, self.fold_z);
+
+        let rr = capsule_r_sqr(state, self.cyl_half_size);
+        let m = sphere_fold_scale(rr, self.min_r, self.scale);
+        abox_apply_scale(state, m, self.z_mul, bailout)
+    }
+}
+
+// ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
+// ASurfMod1 — Amazing Surface folds combined with ABoxMod1 offsets and rotation
+// ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
+
+pub struct ASurfMod1 {
+    pub scale: f64,
+    pub min_r: f64,
+    pub fold: f64,
+    pub scale_vary: f64,
+    pub fold_x_mod: f64,
+    pub fold_y_mod: f64,
+    /// Multiplier on r² before the sphere fold ("1/Radius")
+    pub inv_radius: f64,
+    /// Rotation applied after scaling (precomputed from three angles in degrees)
+    pub rotation: Matrix3,
+    /// Z multiplier applied after scaling
+    pub z_mul: f64,
+}
+
+impl Default for ASurfMod1 {
+    fn default() -> Self {
+        Self {
+            scale: 2.0,
+            min_r: 0.0,
+            fold: 2.0,
+            scale_vary: 0.0,
+            fold_x_mod: 0.0,
+            fold_y_mod: 0.0,
+            inv_radius: 1.0,
+            rotation: math3d::mat3_identity(),
+            z_mul: 1.0,
+        }
+    }
+}
+
+impl ASurfMod1 {
+    /// Parameter order: [scale, min_r, fold, scale_vary, fold_x_mod, fold_y_mod,
+    ///                   inv_radius, rot_x_deg, rot_y_deg, rot_z_deg, z_mul]
+    pub fn from_params(params: &[f64]) -> Self {
+        let mut f = Self::default();
+        apply_params(&mut [
+            &mut f.scale, &mut f.min_r, &mut f.fold, &mut f.scale_vary,
+            &mut f.fold_x_mod, &mut f.fold_y_mod, &mut f.inv_radius,
+        ], params);
+        if params.len() >= 10 {
+            f.rotation = math3d::mat3_from_euler(
+                params[7].to_radians(), params[8].to_radians(), params[9].to_radians(),
+            );
+        }
+        if let Some(z_mul) = params.get(10) {
+            f.z_mul = *z_mul;
+        }
+        f
+    }
+}
+
+impl Formula for ASurfMod1 {
+    fn name(&self) -> &str { "ASurfMod1" }
+
+    fn compute_de(&self, pos: &Vec3D, max_iter: u32, bailout: f64, julia_c: Option<&Vec3D>) -> FormulaResult {
+        abox_family_de(self, self.scale, pos, max_iter, bailout, julia_c)
+    }
+
+    fn iterate_once(&self, state: &mut IterationState, bailout: f64) -> bool {
+        let scale = varied_scale(state, self.scale, self.scale_vary);
+
+        // Surface variant: only X and Y are folded
+        state.x = kali_fold(state.x, self.fold, self.fold_x_mod);
+        state.y = kali_fold(state.y, self.fold, self.fold_y_mod);
+
+        let rr = (state.x * state.x + state.y * state.y + state.z * state.z) * self.inv_radius;
+        let m = sphere_fold_scale(rr, self.min_r, scale);
+
+        let rotated = math3d::mat3_mul_vec(
+            &self.rotation,
+            &Vec3D { x: state.x, y: state.y, z: state.z },
+        );
+        state.x = rotated.x;
+        state.y = rotated.y;
+        state.z = rotated.z;
+
+        abox_apply_scale(state, m, self.z_mul, bailout)
+    }
+}
+
+// ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
+// ASurfMod2 — Amazing Surf folds with separate X/Y limits and cylindrical inversion
+// ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
+
+pub struct ASurfMod2 {
+    pub scale: f64,
+    pub min_r: f64,
+    pub fold_x: f64,
+    pub fold_y: f64,
+    /// Half height of the capsule used for the inversion (0 = sphere)
+    pub cyl_half_size: f64,
+    /// Rotation applied after scaling (precomputed from three angles in degrees)
+    pub rotation: Matrix3,
+    /// Z multiplier applied after scaling
+    pub z_mul: f64,
+}
+
+impl Default for ASurfMod2 {
+    fn default() -> Self {
+        Self {
+            scale: 2.0,
+            min_r: 0.5,
+            fold_x: 1.0,
+            fold_y: 1.0,
+            cyl_half_size: 0.5,
+            rotation: math3d::mat3_identity(),
+            z_mul: 1.0,
+        }
+    }
+}
+
+impl ASurfMod2 {
+    /// Parameter order: [scale, min_r, fold_x, fold_y, cyl_half_size,
+    ///                   rot_x_deg, rot_y_deg, rot_z_deg, z_mul]
+    pub fn from_params(params: &[f64]) -> Self {
+        let mut f = Self::default();
+        apply_params(&mut [
+            &mut f.scale, &mut f.min_r, &mut f.fold_x, &mut f.fold_y, &mut f.cyl_half_size,
+        ], params);
+        if params.len() >= 8 {
+            f.rotation = math3d::mat3_from_euler(
+                params[5].to_radians(), params[6].to_radians(), params[7].to_radians(),
+            );
+        }
+        if let Some(z_mul) = params.get(8) {
+            f.z_mul = *z_mul;
+        }
+        f
+    }
+}
+
+impl Formula for ASurfMod2 {
+    fn name(&self) -> &str { "ASurfMod2" }
+
+    fn compute_de(&self, pos: &Vec3D, max_iter: u32, bailout: f64, julia_c: Option<&Vec3D>) -> FormulaResult {
+        abox_family_de(self, self.scale, pos, max_iter, bailout, julia_c)
+    }
+
+    fn iterate_once(&self, state: &mut IterationState, bailout: f64) -> bool {
+        // Surface variant: only X and Y are folded, each with its own limit
+        state.x = box_fold(state.x, self.fold_x);
+        state.y = box_fold(state.y, self.fold_y);
+
+        let rr = capsule_r_sqr(state, self.cyl_half_size);
+        let m = sphere_fold_scale(rr, self.min_r, self.scale);
+
+        let rotated = math3d::mat3_mul_vec(
+            &self.rotation,
+            &Vec3D { x: state.x, y: state.y, z: state.z },
+        );
+        state.x = rotated.x;
+        state.y = rotated.y;
+        state.z = rotated.z;
+
+        abox_apply_scale(state, m, self.z_mul, bailout)
+    }
+}
+
+// ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
+// Triplex algebra with selectable angle convention (SinePow / CosinePow family)
+// ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
+
+/// Spherical angle convention for triplex powers.
+///
+/// `Sine` measures theta from the Z axis (White/Nylander, MB3D's default bulbs);
+/// `Cosine` measures it as elevation from the XY plane (MB3D's CosinePow formulas).
+/// Imported scenes frequently differ only by this choice.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BulbConvention {
+    Sine,
+    Cosine,
+}
+
+impl BulbConvention {
+    pub fn from_f64(v: f64) -> Self {
+        if v >= 0.5 { BulbConvention::Cosine } else { BulbConvention::Sine }
+    }
+}
+
+/// Decompose (x, y, z) into (r, theta, phi) under `conv`.
+#[inline]
+fn triplex_angles(x: f64, y: f64, z: f64, conv: BulbConvention) -> (f64, f64, f64) {
+    let r = (x * x + y * y + z * z).sqrt();
+    if r < 1e-30 {
+        return (0.0, 0.0, 0.0);
+    }
+    let theta = match conv {
+        BulbConvention::Sine => strict::acos(z / r),
+        BulbConvention::Cosine => strict::asin(z / r),
+    };
+    (r, theta, strict::atan2(y, x))
+}
+
+/// Rebuild a triplex from (r, theta, phi) under `conv`.
+#[inline]
+fn triplex_from_angles(r: f64, theta: f64, phi: f64, conv: BulbConvention) -> (f64, f64, f64) {
+    let (st, ct) = strict::sin_cos(theta);
+    let (sp, cp) = strict::sin_cos(phi);
+    match conv {
+        BulbConvention::Sine => (r * st * cp, r * st * sp, r * ct),
+        BulbConvention::Cosine => (r * ct * cp, r * ct * sp, r * st),
+    }
+}
+
+/// Triplex power: (r, theta, phi) → (r^n, n·theta, n·phi).
+#[inline]
+fn triplex_pow(x: f64, y: f64, z: f64, n: f64, conv: BulbConvention) -> (f64, f64, f64) {
+    let (r, theta, phi) = triplex_angles(x, y, z, conv);
+    triplex_from_angles(strict::powf(r, n), theta * n, phi * n, conv)
+}
+
+/// Triplex product: radii multiply, angles add.
+#[inline]
+fn triplex_mul(a: (f64, f64, f64), b: (f64, f64, f64), conv: BulbConvention) -> (f64, f64, f64) {
+    let (ra, ta, pa) = triplex_angles(a.0, a.1, a.2, conv);
+    let (rb, tb, pb) = triplex_angles(b.0, b.1, b.2, conv);
+    triplex_from_angles(ra * rb, ta + tb, pa + pb, conv)
+}
+
+/// Escape loop shared by the triplex bulbs (DE = 0.5·r·ln r / dr).
+fn triplex_bulb_de(
+    formula: &dyn Formula,
+    power: f64,
+    pos: &Vec3D,
+    max_iter: u32,
+    bailout: f64,
+    julia_c: Option<&Vec3D>,
+) -> FormulaResult {
+    let mut state = IterationState::new(pos, julia_c);
+    for i in 0..max_iter {
+        state.iteration = i;
+        if formula.iterate_once(&mut state, bailout) {
+            let r = state.r_sqr.sqrt();
+            let de = 0.5 * r * strict::ln(r) / state.dr;
+            let smooth = (i as f64) + 1.0 - (strict::ln(strict::ln(state.r_sqr)) / strict::ln(power.abs().max(1.0 + 1e-6)));
+            return FormulaResult {
+                de: de.max(0.0),
+                smooth_it: smooth,
+                orbit_trap: state.orbit_trap,
+                orbit_trap2: state.orbit_trap2,
+                inside: false,
+                iterations: i,
+                interior_de: None,
+            };
+        }
+    }
+    FormulaResult { de: 0.0, smooth_it: max_iter as f64, inside: true, iterations: max_iter, ..Default::default() }
+}
+
+// ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
+// Power-N Bulb — sine/cosine convention Mandelbulb, port of _SinePow2 / CosinePow8
+// ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
+
+pub struct PowerNBulb {
+    pub power: f64,
+    pub convention: BulbConvention,
+    /// Z multiplier applied to the new Z component (MB3D "Z multiplier")
+    pub z_mul: f64,
+}
+
+impl Default for PowerNBulb {
+    fn default() -> Self {
+        Self { power: 8.0, convention: BulbConvention::Sine, z_mul: 1.0 }
+    }
+}
+
+impl PowerNBulb {
+    /// Parameter order: [power, convention (0 = sine, 1 = cosine), z_mul]
+    pub fn from_params(params: &[f64]) -> Self {
+        let mut f = Self::default();
+        let mut convention = 0.0;
+        apply_params(&mut [&mut f.power, &mut convention, &mut f.z_mul], params);
+        f.convention = BulbConvention::from_f64(convention);
+        f
+    }
+}
+
+impl Formula for PowerNBulb {
+    fn name(&self) -> &str { "Power-N Bulb" }
+
+    fn compute_de(&self, pos: &Vec3D, max_iter: u32, bailout: f64, julia_c: Option<&Vec3D>) -> FormulaResult {
+        triplex_bulb_de(self, self.power, pos, max_iter, bailout, julia_c)
+    }
+
+    fn iterate_once(&self, state: &mut IterationState, bailout: f64) -> bool {
+        let (x, y, z) = (state.x, state.y, state.z);
+        state.r_sqr = x * x + y * y + z * z;
+        if state.r_sqr > bailout { return true; }
+
+        let r = state.r_sqr.sqrt();
+        let otrap = x.abs().min(y.abs()).min(z.abs());
+        state.track_traps(otrap);
+
+        state.dr = strict::powf(r, self.power - 1.0) * self.power * state.dr + 1.0;
+
+        let (nx, ny, nz) = triplex_pow(x, y, z, self.power, self.convention);
+        state.x = nx + state.c1;
+        state.y = ny + state.c2;
+        state.z = nz * self.z_mul + state.c3;
+
+        false
+    }
+}
+
+// ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
+// Lambdabulb — triplex logistic map z' = c·z·(1 − z), port of Lambda4Dc/Lambda4Dnc
+// ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
+
+pub struct Lambdabulb {
+    pub convention: BulbConvention,
+}
+
+impl Default for Lambdabulb {
+    fn default() -> Self {
+        Self { convention: BulbConvention::Sine }
+    }
+}
+
+impl Lambdabulb {
+    /// Parameter order: [convention (0 = sine, 1 = cosine)]
+    pub fn from_params(params: &[f64]) -> Self {
+        Self { convention: BulbConvention::from_f64(params.first().copied().unwrap_or(0.0)) }
+    }
+}
+
+impl Formula for Lambdabulb {
+    fn name(&self) -> &str { "Lambdabulb" }
+
+    fn compute_de(&self, pos: &Vec3D, max_iter: u32, bailout: f64, julia_c: Option<&Vec3D>) -> FormulaResult {
+        triplex_bulb_de(self, 2.0, pos, max_iter, bailout, julia_c)
+    }
+
+    fn iterate_once(&self, state: &mut IterationState, bailout: f64) -> bool {
+        let (x, y, z) = (state.x, state.y, state.z);
+        state.r_sqr = x * x + y * y + z * z;
+        if state.r_sqr > bailout { return true; }
+
+        let otrap = x.abs().min(y.abs()).min(z.abs());
+        state.track_traps(otrap);
+
+        // z·(1 − z) = z − z²
+        let (sx, sy, sz) = triplex_pow(x, y, z, 2.0, self.convention);
+        let w = (x - sx, y - sy, z - sz);
+
+        // |d/dz c·z·(1 − z)| = |c|·|1 − 2z|
+        let c_len = (state.c1 * state.c1 + state.c2 * state.c2 + state.c3 * state.c3).sqrt();
+        let one_minus_2z = (strict::powi(1.0 - 2.0 * x, 2) + 4.0 * (y * y + z * z)).sqrt();
+        state.dr = c_len * one_minus_2z * state.dr + 1.0;
+
+        let (nx, ny, nz) = triplex_mul((state.c1, state.c2, state.c3), w, self.convention);
+        state.x = nx;
+        state.y = ny;
+        state.z = nz;
+
+        false
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -737,4 +1373,97 @@ mod tests {
         let result = f.compute_de(&pos, 20, 16.0, None);
         assert!(result.de > 0.0);
     }
+
+    #[test]
+    fn test_kali_fold_zero_offset_is_box_like() {
+        // With no offset the Kali fold mirrors |v| > fold back inside
+        assert!((kali_fold(0.5, 2.0, 0.0) - 0.5).abs() < 1e-12);
+        assert!((kali_fold(3.0, 2.0, 0.0) - 1.0).abs() < 1e-12);
+        assert!((kali_fold(-3.0, 2.0, 0.0) - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_abox_mods_from_params() {
+        let f = ABoxMod1::from_params(&[2.5, 0.5, 1.0]);
+        assert_eq!(f.scale, 2.5);
+        assert_eq!(f.min_r, 0.5);
+        assert_eq!(f.fold, 1.0);
+        assert_eq!(f.fold_z_mod, 0.0);
+
+        let g = ASurfMod1::from_params(&[2.0, 0.0, 2.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 90.0]);
+        let v = math3d::mat3_mul_vec(&g.rotation, &Vec3D { x: 1.0, y: 0.0, z: 0.0 });
+        assert!(v.x.abs() < 1e-9);
+        assert!((v.y.abs() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_abox_mods_escape_far_points() {
+        let far = Vec3D { x: 30.0, y: 0.0, z: 0.0 };
+        for f in [
+            Box::new(ABoxMod1::default()) as Box<dyn Formula>,
+            Box::new(ABoxMod2::default()),
+            Box::new(ASurfMod1::default()),
+            Box::new(ASurfMod2::default()),
+        ] {
+            let result = f.compute_de(&far, 30, 1024.0, None);
+            assert!(!result.inside, "{} should escape", f.name());
+            assert!(result.de > 0.0);
+        }
+    }
+
+    #[test]
+    fn test_varied_scale_advances_once_per_call() {
+        let mut state = IterationState::new(&Vec3D::default(), None);
+        let mut expected = 2.0;
+        for _ in 0..5 {
+            assert_eq!(varied_scale(&mut state, 2.0, 0.1), expected);
+            expected += 0.1 * (expected - 1.0);
+        }
+    }
+
+    #[test]
+    fn test_abox_mods_z_mul() {
+        assert_eq!(ABoxMod1::from_params(&[2.0, 0.0, 2.0, 0.0, 0.0, 0.0, 0.0, 0.5]).z_mul, 0.5);
+        assert_eq!(ABoxMod2::from_params(&[2.0, 0.5, 1.0, 1.5, 0.5, 0.5]).z_mul, 0.5);
+        assert_eq!(ASurfMod1::from_params(&[2.0; 11]).z_mul, 2.0);
+        assert_eq!(ASurfMod2::from_params(&[2.0]).z_mul, 1.0);
+
+        // Only Z is affected
+        let pos = Vec3D { x: 0.3, y: 0.2, z: 0.4 };
+        let run = |f: &dyn Formula| {
+            let mut state = IterationState::new(&pos, None);
+            f.iterate_once(&mut state, 1024.0);
+            state
+        };
+        let plain = run(&ASurfMod2::default());
+        let squashed = run(&ASurfMod2::from_params(&[2.0, 0.5, 1.0, 1.0, 0.5, 0.0, 0.0, 0.0, 0.5]));
+        assert_eq!(plain.x, squashed.x);
+        assert!((squashed.z - pos.z - 0.5 * (plain.z - pos.z)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_bulb_conventions_differ_off_axis() {
+        let pos = Vec3D { x: 0.6, y: 0.3, z: 0.5 };
+        let sine = PowerNBulb::from_params(&[8.0, 0.0]).compute_de(&pos, 20, 16.0, None);
+        let cosine = PowerNBulb::from_params(&[8.0, 1.0]).compute_de(&pos, 20, 16.0, None);
+        assert!((sine.de - cosine.de).abs() > 1e-9);
+    }
+
+    #[test]
+    fn test_power_n_sine_matches_mandelbulb8() {
+        let pos = Vec3D { x: 0.9, y: 0.4, z: 0.3 };
+        let a = PowerNBulb::default().compute_de(&pos, 20, 16.0, None);
+        let b = MandelbulbPower8.compute_de(&pos, 20, 16.0, None);
+        assert_eq!(a.iterations, b.iterations);
+        assert!((a.de - b.de).abs() < 1e-9 * b.de.max(1.0));
+    }
+
+    #[test]
+    fn test_lambdabulb_julia_escapes_far() {
+        let c = Vec3D { x: 1.0, y: 0.2, z: 0.0 };
+        let far = Vec3D { x: 3.0, y: 0.0, z: 0.0 };
+        let result = Lambdabulb::default().compute_de(&far, 20, 16.0, Some(&c));
+        assert!(!result.inside);
+        assert!(result.de > 0.0);
+    }
 }
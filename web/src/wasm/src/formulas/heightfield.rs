@@ -0,0 +1,262 @@
+//! Heightfield pseudo-formula — turns a grayscale image into a DE object.
+//!
+//! The image covers a square footprint in the XY plane centred on the origin
+//! and is extruded along +Z by its (optionally smoothed) gray values. It is not
+//! iterable; use it standalone or as a combine slot (see `hybrid::CombineMode`)
+//! to embed logos or terrain into fractal scenes.
+//!
+//! Images (8-bit, or 16-bit as terrain heightmaps usually are) are uploaded
+//! once into a registry and referenced by handle. An offset places the
+//! footprint anywhere in the scene, e.g. under a fractal combined by union.
+
+use std::sync::Arc;
+
+use crate::engine::types::Vec3D;
+use crate::math::utils;
+use super::{Formula, FormulaResult, IterationState};
+use super::resources::Registry;
+
+/// Grayscale height image with values normalized to [0, 1].
+#[derive(Clone, Debug)]
+pub struct HeightImage {
+    pub width: u32,
+    pub height: u32,
+    pub values: Vec<f32>,
+}
+
+impl HeightImage {
+    /// Build from 8-bit grayscale (1 byte/pixel) or RGBA (4 bytes/pixel, luma used) data.
+    pub fn from_bytes(pixels: &[u8], width: u32, height: u32) -> Option<Self> {
+        let count = (width as usize) * (height as usize);
+        if count == 0 {
+            return None;
+        }
+        let values = if pixels.len() >= count * 4 {
+            pixels.chunks_exact(4).take(count)
+                .map(|p| (0.299 * p[0] as f32 + 0.587 * p[1] as f32 + 0.114 * p[2] as f32) / 255.0)
+                .collect()
+        } else if pixels.len() >= count {
+            pixels[..count].iter().map(|&v| v as f32 / 255.0).collect()
+        } else {
+            return None;
+        };
+        Some(Self { width, height, values })
+    }
+
+    /// Build from 16-bit grayscale samples (1 per pixel).
+    pub fn from_u16(samples: &[u16], width: u32, height: u32) -> Option<Self> {
+        let count = (width as usize) * (height as usize);
+        if count == 0 || samples.len() < count {
+            return None;
+        }
+        let values = samples[..count].iter().map(|&v| v as f32 / 65535.0).collect();
+        Some(Self { width, height, values })
+    }
+
+    #[inline]
+    fn at(&self, x: u32, y: u32) -> f32 {
+        self.values[(y.min(self.height - 1) * self.width + x.min(self.width - 1)) as usize]
+    }
+
+    /// Bilinear sample at normalized coordinates (u, v) ∈ [0, 1]².
+    pub fn sample(&self, u: f64, v: f64) -> f64 {
+        let fx = utils::clamp(u, 0.0, 1.0) * (self.width - 1) as f64;
+        let fy = utils::clamp(v, 0.0, 1.0) * (self.height - 1) as f64;
+        let x0 = fx.floor() as u32;
+        let y0 = fy.floor() as u32;
+        let tx = fx - x0 as f64;
+        let ty = fy - y0 as f64;
+        let top = utils::lerp(self.at(x0, y0) as f64, self.at(x0 + 1, y0) as f64, tx);
+        let bottom = utils::lerp(self.at(x0, y0 + 1) as f64, self.at(x0 + 1, y0 + 1) as f64, tx);
+        utils::lerp(top, bottom, ty)
+    }
+
+    /// Separable box blur with the given radius in pixels.
+    pub fn smoothed(&self, radius: u32) -> Self {
+        if radius == 0 {
+            return self.clone();
+        }
+        let (w, h) = (self.width as i64, self.height as i64);
+        let r = radius as i64;
+        let blur = |src: &[f32], horizontal: bool| -> Vec<f32> {
+            let mut out = vec![0.0f32; src.len()];
+            for y in 0..h {
+                for x in 0..w {
+                    let mut sum = 0.0f32;
+                    for k in -r..=r {
+                        let (sx, sy) = if horizontal {
+                            ((x + k).clamp(0, w - 1), y)
+                        } else {
+                            (x, (y + k).clamp(0, h - 1))
+                        };
+                        sum += src[(sy * w + sx) as usize];
+                    }
+                    out[(y * w + x) as usize] = sum / (2 * r + 1) as f32;
+                }
+            }
+            out
+        };
+        let values = blur(&blur(&self.values, true), false);
+        Self { width: self.width, height: self.height, values }
+    }
+
+    /// Largest height difference between neighbouring pixels (for DE scaling).
+    pub fn max_slope(&self) -> f64 {
+        let mut max = 0.0f32;
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let v = self.at(x, y);
+                max = max.max((self.at(x + 1, y) - v).abs()).max((self.at(x, y + 1) - v).abs());
+            }
+        }
+        max as f64
+    }
+}
+
+static IMAGES: Registry<HeightImage> = Registry::new();
+
+/// Store an image in the registry, returning its handle.
+pub fn register_image(image: HeightImage) -> u32 {
+    IMAGES.insert(image)
+}
+
+/// Look up a registered image.
+pub fn image(handle: u32) -> Option<Arc<HeightImage>> {
+    IMAGES.get(handle)
+}
+
+/// Release a registered image; its handle may be reused.
+pub fn release_image(handle: u32) {
+    IMAGES.remove(handle);
+}
+
+/// Heightfield DE object.
+pub struct Heightfield {
+    pub image: Option<Arc<HeightImage>>,
+    /// Edge length of the square XY footprint in world units
+    pub size: f64,
+    /// World height of a white pixel
+    pub height_scale: f64,
+    /// Depth of the solid block below the base plane
+    pub thickness: f64,
+    /// World position of the footprint center on the base plane
+    pub offset: Vec3D,
+    /// Lipschitz factor keeping the DE conservative on steep slopes
+    de_factor: f64,
+}
+
+impl Default for Heightfield {
+    fn default() -> Self {
+        Self { image: None, size: 2.0, height_scale: 0.25, thickness: 0.1, offset: Vec3D::default(), de_factor: 1.0 }
+    }
+}
+
+impl Heightfield {
+    /// Parameter order: [image_handle, size, height_scale, smoothing_radius_px,
+    /// thickness, offset_x, offset_y, offset_z]
+    pub fn from_params(params: &[f64]) -> Self {
+        let mut f = Self::default();
+        let get = |i: usize, d: f64| params.get(i).copied().unwrap_or(d);
+        f.size = get(1, f.size).max(1e-9);
+        f.height_scale = get(2, f.height_scale);
+        f.thickness = get(4, f.thickness).max(0.0);
+        f.offset = Vec3D { x: get(5, 0.0), y: get(6, 0.0), z: get(7, 0.0) };
+        let smoothing = get(3, 0.0).max(0.0).round() as u32;
+        f.image = params.first()
+            .and_then(|&h| image(h as u32))
+            .map(|img| if smoothing > 0 { Arc::new(img.smoothed(smoothing)) } else { img });
+        if let Some(img) = &f.image {
+            // Slope per world unit = pixel slope × pixels per unit × height scale
+            let px_per_unit = (img.width.max(img.height) as f64 - 1.0).max(1.0) / f.size;
+            let g = img.max_slope() * px_per_unit * f.height_scale.abs();
+            f.de_factor = 1.0 / (1.0 + g * g).sqrt();
+        }
+        f
+    }
+
+    /// Signed distance to the heightfield block and the normalized height below `pos`.
+    pub fn signed_distance(&self, pos: &Vec3D) -> (f64, f64) {
+        let pos = &Vec3D { x: pos.x - self.offset.x, y: pos.y - self.offset.y, z: pos.z - self.offset.z };
+        let half = self.size * 0.5;
+        let h_norm = match &self.image {
+            Some(img) => img.sample((pos.x + half) / self.size, (pos.y + half) / self.size),
+            None => 0.0,
+        };
+        let d_surface = (pos.z - h_norm * self.height_scale) * self.de_factor;
+        let footprint = (pos.x.abs() - half).max(pos.y.abs() - half);
+        let bottom = -self.thickness - pos.z;
+        (d_surface.max(footprint).max(bottom), h_norm)
+    }
+}
+
+impl Formula for Heightfield {
+    fn name(&self) -> &str { "Heightfield" }
+
+    fn compute_de(&self, pos: &Vec3D, _max_iter: u32, _bailout: f64, _julia_c: Option<&Vec3D>) -> FormulaResult {
+        let (de, h) = self.signed_distance(pos);
+        FormulaResult {
+            de,
+            smooth_it: h * 255.0,
+            orbit_trap: h,
+            orbit_trap2: h,
+            inside: de < 0.0,
+            iterations: 0,
+            interior_de: None,
+        }
+    }
+
+    fn iterate_once(&self, _state: &mut IterationState, _bailout: f64) -> bool {
+        true // Pseudo-formula — not iterable
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ramp_image() -> HeightImage {
+        // 4×4 image rising along X
+        let pixels: Vec<u8> = (0..16).map(|i| ((i % 4) * 85) as u8).collect();
+        HeightImage::from_bytes(&pixels, 4, 4).unwrap()
+    }
+
+    #[test]
+    fn test_registry_roundtrip() {
+        let handle = register_image(ramp_image());
+        assert!(image(handle).is_some());
+        release_image(handle);
+        assert!(image(handle).is_none());
+    }
+
+    #[test]
+    fn test_heightfield_de_sign() {
+        let handle = register_image(ramp_image());
+        let hf = Heightfield::from_params(&[handle as f64, 2.0, 1.0]);
+        // Above the high edge → outside, below the low edge's surface → inside
+        let above = hf.compute_de(&Vec3D { x: 0.9, y: 0.0, z: 2.0 }, 0, 0.0, None);
+        assert!(above.de > 0.0 && !above.inside);
+        let below = hf.compute_de(&Vec3D { x: 0.9, y: 0.0, z: 0.5 }, 0, 0.0, None);
+        assert!(below.de < 0.0 && below.inside);
+        release_image(handle);
+    }
+
+    #[test]
+    fn test_16_bit_image_with_offset() {
+        // Flat terrain at half height, moved to (10, 0, -1)
+        let handle = register_image(HeightImage::from_u16(&[32768; 9], 3, 3).unwrap());
+        let hf = Heightfield::from_params(&[handle as f64, 2.0, 1.0, 0.0, 0.1, 10.0, 0.0, -1.0]);
+        let surface = -1.0 + 32768.0 / 65535.0;
+        let (de, h) = hf.signed_distance(&Vec3D { x: 10.5, y: 0.0, z: surface + 0.25 });
+        assert!((de - 0.25).abs() < 1e-6 && (h - 32768.0 / 65535.0).abs() < 1e-6);
+        // The original footprint at the origin is empty now
+        assert!(hf.signed_distance(&Vec3D { x: 0.0, y: 0.0, z: 0.0 }).0 > 8.0);
+        assert!(HeightImage::from_u16(&[0; 8], 3, 3).is_none());
+        release_image(handle);
+    }
+
+    #[test]
+    fn test_smoothing_preserves_constant_image() {
+        let img = HeightImage::from_bytes(&[128; 25], 5, 5).unwrap().smoothed(2);
+        assert!(img.values.iter().all(|&v| (v - 128.0 / 255.0).abs() < 1e-6));
+    }
+}
@@ -4,9 +4,13 @@
 /// - Alternating: cycles through formulas, each running its iteration count
 /// - Interpolated: blends between formula results
 /// - 4D: extends to 4-dimensional hybrid iteration
+///
+/// Slots may instead be marked as boolean combine slots: they are not iterated
+/// but their DE is merged with the hybrid result (union, intersection, ...).
 
 use crate::engine::types::Vec3D;
-use super::{Formula, FormulaId, FormulaResult, IterationState};
+use crate::math::{math3d, strict, utils};
+use super::{simd, DistanceField, Formula, FormulaId, FormulaResult, IterationState};
 
 /// Hybrid mode matching the UI radio buttons.
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -17,6 +21,7 @@ pub enum HybridMode {
 }
 
 impl HybridMode {
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Self {
         match s {
             "interpolated" => HybridMode::Interpolated,
@@ -26,14 +31,110 @@ impl HybridMode {
     }
 }
 
+/// Per-iteration weight curve for interpolated hybrids — MB3D's "DEmixer".
+///
+/// The weight of the second formula moves from `start` at the first iteration
+/// to `end` at the last one, shaped by `exponent` (1 = linear).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DeMixerCurve {
+    pub start: f64,
+    pub end: f64,
+    pub exponent: f64,
+}
+
+impl Default for DeMixerCurve {
+    fn default() -> Self {
+        Self { start: 0.0, end: 1.0, exponent: 1.0 }
+    }
+}
+
+impl DeMixerCurve {
+    /// Weight of the second formula at `iteration` out of `total` iterations.
+    pub fn weight(&self, iteration: u32, total: u32) -> f64 {
+        let t = if total > 1 {
+            iteration as f64 / (total - 1) as f64
+        } else {
+            0.0
+        };
+        let shaped = if self.exponent > 0.0 { strict::powf(t, self.exponent) } else { t };
+        utils::clamp(utils::lerp(self.start, self.end, shaped), 0.0, 1.0)
+    }
+}
+
+/// Boolean combine mode for DE objects merged with the fractal DE.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CombineMode {
+    /// min(a, b)
+    Union,
+    /// max(a, b)
+    Intersection,
+    /// max(a, -b) — carve the object out of the fractal
+    Subtraction,
+    /// Polynomial smooth minimum with blend radius k
+    SmoothUnion(f64),
+}
+
+impl CombineMode {
+    /// Decode from the u32/f64 protocol: 0 = union, 1 = intersection, 2 = subtraction, 3 = smooth union.
+    pub fn from_u32(mode: u32, k: f64) -> Self {
+        match mode {
+            1 => CombineMode::Intersection,
+            2 => CombineMode::Subtraction,
+            3 if k > 0.0 => CombineMode::SmoothUnion(k),
+            _ => CombineMode::Union,
+        }
+    }
+
+    /// Merge object result `b` into accumulated result `a`. Coloring follows the
+    /// operand that determines the resulting distance.
+    pub fn apply(&self, a: FormulaResult, b: FormulaResult) -> FormulaResult {
+        match *self {
+            CombineMode::Union => if b.de < a.de { b } else { a },
+            CombineMode::Intersection => if b.de > a.de { b } else { a },
+            CombineMode::Subtraction => {
+                if -b.de > a.de {
+                    FormulaResult { de: -b.de, inside: false, ..b }
+                } else {
+                    a
+                }
+            }
+            CombineMode::SmoothUnion(k) => {
+                let h = utils::clamp(0.5 + 0.5 * (b.de - a.de) / k, 0.0, 1.0);
+                let de = utils::lerp(b.de, a.de, h) - k * h * (1.0 - h);
+                let inside = a.inside || b.inside || de < 0.0;
+                let base = if h > 0.5 { a } else { b };
+                FormulaResult { de, inside, ..base }
+            }
+        }
+    }
+}
+
+/// Per-slot julia setting — MB3D allows mixing mandel and julia slots.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SlotJulia {
+    /// Follow the hybrid-wide julia setting
+    #[default]
+    Global,
+    /// Mandelbrot mode: c = initial position
+    Mandel,
+    /// Julia mode with this slot's own constant
+    Julia(Vec3D),
+}
+
 /// A single slot in the hybrid formula configuration.
 pub struct HybridSlot {
+    /// Formula identifier (used to rebuild the formula with new parameters)
+    pub id: FormulaId,
     /// Formula for this slot
     pub formula: Box<dyn Formula>,
     /// Number of iterations to run this formula per cycle
     pub iterations: u32,
     /// Whether this slot is active
     pub active: bool,
+    /// If set, the slot is a DE object merged with the hybrid result instead of iterated
+    pub combine: Option<CombineMode>,
+    /// Julia override for this slot
+    pub julia: SlotJulia,
 }
 
 /// Hybrid formula combiner — runs multiple formulas in sequence.
@@ -42,6 +143,12 @@ pub struct HybridFormula {
     pub mode: HybridMode,
     pub total_iterations: u32,
     pub bailout: f64,
+    /// Optional per-iteration weight curve used by the interpolated mode
+    pub mixer: Option<DeMixerCurve>,
+    /// Global julia constant, used by slots set to `SlotJulia::Global`
+    pub julia: Option<Vec3D>,
+    /// Initial orbit offset (z0 += offset), used to morph sets
+    pub z0_offset: Vec3D,
 }
 
 impl HybridFormula {
@@ -55,25 +162,90 @@ impl HybridFormula {
         let slots: Vec<HybridSlot> = slot_configs
             .iter()
             .map(|(id, iters)| HybridSlot {
+                id: *id,
                 formula: id.create(),
                 iterations: *iters,
                 active: *id != FormulaId::None && *iters > 0,
+                combine: None,
+                julia: SlotJulia::Global,
             })
             .collect();
 
-        Self { slots, mode, total_iterations, bailout }
+        Self { slots, mode, total_iterations, bailout, mixer: None, julia: None, z0_offset: Vec3D::default() }
+    }
+
+    /// Enable the per-iteration DEmixer curve for the interpolated mode.
+    pub fn with_mixer(mut self, curve: DeMixerCurve) -> Self {
+        self.mixer = Some(curve);
+        self
+    }
+
+    /// Set the global julia constant (None = mandelbrot mode).
+    pub fn with_julia(mut self, julia: Option<Vec3D>) -> Self {
+        self.julia = julia;
+        self
+    }
+
+    /// Set the initial orbit offset.
+    pub fn with_z0_offset(mut self, offset: Vec3D) -> Self {
+        self.z0_offset = offset;
+        self
+    }
+
+    /// Override julia mode for one slot.
+    pub fn set_slot_julia(&mut self, slot: usize, julia: SlotJulia) {
+        if let Some(s) = self.slots.get_mut(slot) {
+            s.julia = julia;
+        }
+    }
+
+    /// Effective julia constant of a slot (None = mandelbrot mode).
+    fn slot_julia<'a>(&'a self, slot: &'a HybridSlot) -> Option<&'a Vec3D> {
+        match &slot.julia {
+            SlotJulia::Global => self.julia.as_ref(),
+            SlotJulia::Mandel => None,
+            SlotJulia::Julia(c) => Some(c),
+        }
+    }
+
+    /// Rebuild a slot's formula with user parameters (see `FormulaId::create_with_params`).
+    pub fn set_slot_params(&mut self, slot: usize, params: &[f64]) {
+        if let Some(s) = self.slots.get_mut(slot) {
+            s.formula = s.id.create_with_params(params);
+        }
+    }
+
+    /// Turn a slot into a boolean combine slot (see `CombineMode`).
+    pub fn set_slot_combine(&mut self, slot: usize, mode: CombineMode) {
+        if let Some(s) = self.slots.get_mut(slot) {
+            s.combine = Some(mode);
+            s.active = s.id != FormulaId::None;
+        }
     }
 
     /// Get the active slot count.
-    fn active_count(&self) -> usize {
+    pub fn active_count(&self) -> usize {
         self.slots.iter().filter(|s| s.active).count()
     }
 
-    /// Compute DE using the hybrid system.
-    pub fn compute_de(&self, pos: &Vec3D, julia_c: Option<&Vec3D>) -> FormulaResult {
+    /// Compute DE using the hybrid system, then merge any combine slots in order.
+    pub fn compute_de(&self, pos: &Vec3D) -> FormulaResult {
+        let mut result = self.compute_fractal_de(pos);
+        for slot in self.slots.iter().filter(|s| s.active) {
+            if let Some(mode) = slot.combine {
+                let julia_c = self.slot_julia(slot);
+                let object = slot.formula.compute_de(pos, self.total_iterations, self.bailout, julia_c);
+                result = mode.apply(result, object);
+            }
+        }
+        result
+    }
+
+    /// DE of the iterated slots only.
+    fn compute_fractal_de(&self, pos: &Vec3D) -> FormulaResult {
         let active: Vec<usize> = self.slots.iter()
             .enumerate()
-            .filter(|(_, s)| s.active)
+            .filter(|(_, s)| s.active && s.combine.is_none())
             .map(|(i, _)| i)
             .collect();
 
@@ -83,22 +255,32 @@ impl HybridFormula {
 
         // Single formula — delegate directly
         if active.len() == 1 {
-            let slot = &self.slots[active[0]];
-            return slot.formula.compute_de(pos, self.total_iterations, self.bailout, julia_c);
+            return self.compute_slot_de(&self.slots[active[0]], pos);
         }
 
         // Multi-formula hybrid
         match self.mode {
-            HybridMode::Alternating => self.compute_alternating(pos, julia_c, &active),
-            HybridMode::Interpolated => self.compute_interpolated(pos, julia_c, &active),
-            HybridMode::FourD => self.compute_4d(pos, julia_c, &active),
+            HybridMode::Alternating => self.compute_alternating(pos, &active),
+            HybridMode::Interpolated => self.compute_interpolated(pos, &active),
+            HybridMode::FourD => self.compute_4d(pos, &active),
         }
     }
 
+    /// Run one slot's own DE with the slot julia and the z0 offset applied.
+    fn compute_slot_de(&self, slot: &HybridSlot, pos: &Vec3D) -> FormulaResult {
+        let julia_c = self.slot_julia(slot);
+        if self.z0_offset == Vec3D::default() {
+            return slot.formula.compute_de(pos, self.total_iterations, self.bailout, julia_c);
+        }
+        // Formulas start at their `pos` argument, so shift it and pin the constant
+        let start = math3d::vec3d_add(pos, &self.z0_offset);
+        slot.formula.compute_de(&start, self.total_iterations, self.bailout, Some(julia_c.unwrap_or(pos)))
+    }
+
     /// Alternating mode: cycle through formulas, each running its slot's iteration count.
     /// Port of doHybridPasDE from formulas.pas.
-    fn compute_alternating(&self, pos: &Vec3D, julia_c: Option<&Vec3D>, active: &[usize]) -> FormulaResult {
-        let mut state = IterationState::new(pos, julia_c);
+    fn compute_alternating(&self, pos: &Vec3D, active: &[usize]) -> FormulaResult {
+        let mut state = IterationState::new(pos, None).with_z0_offset(&self.z0_offset);
         let mut total_iters = 0u32;
         let mut slot_idx = 0usize;
 
@@ -106,17 +288,19 @@ impl HybridFormula {
             let si = active[slot_idx % active.len()];
             let slot = &self.slots[si];
             let slot_iters = slot.iterations.max(1);
+            set_julia_c(&mut state, self.slot_julia(slot).unwrap_or(pos));
 
             for _ in 0..slot_iters {
                 if total_iters >= self.total_iterations {
                     break 'outer;
                 }
 
+                state.iteration = total_iters;
                 if slot.formula.iterate_once(&mut state, self.bailout) {
                     // Escaped
                     let r = state.r_sqr.sqrt();
                     let de = if state.dr.abs() > 1e-30 {
-                        0.5 * r * r.ln() / state.dr
+                        0.5 * r * strict::ln(r) / state.dr
                     } else {
                         r * 0.5
                     };
@@ -124,8 +308,10 @@ impl HybridFormula {
                         de: de.max(0.0),
                         smooth_it: total_iters as f64,
                         orbit_trap: state.orbit_trap,
+                        orbit_trap2: state.orbit_trap2,
                         inside: false,
                         iterations: total_iters,
+                        interior_de: None,
                     };
                 }
 
@@ -140,39 +326,144 @@ impl HybridFormula {
             de: 0.0,
             smooth_it: self.total_iterations as f64,
             orbit_trap: state.orbit_trap,
+            orbit_trap2: state.orbit_trap2,
             inside: true,
             iterations: self.total_iterations,
+            interior_de: None,
         }
     }
 
     /// Interpolated mode: blend iteration results from two formulas.
-    fn compute_interpolated(&self, pos: &Vec3D, julia_c: Option<&Vec3D>, active: &[usize]) -> FormulaResult {
+    fn compute_interpolated(&self, pos: &Vec3D, active: &[usize]) -> FormulaResult {
         if active.len() < 2 {
-            return self.compute_alternating(pos, julia_c, active);
+            return self.compute_alternating(pos, active);
+        }
+
+        if let Some(curve) = self.mixer {
+            return self.compute_mixed(pos, active, &curve);
         }
 
         // Run both formulas independently and blend the DEs
-        let r1 = self.slots[active[0]].formula.compute_de(
-            pos, self.total_iterations, self.bailout, julia_c
-        );
-        let r2 = self.slots[active[1]].formula.compute_de(
-            pos, self.total_iterations, self.bailout, julia_c
-        );
+        let r1 = self.compute_slot_de(&self.slots[active[0]], pos);
+        let r2 = self.compute_slot_de(&self.slots[active[1]], pos);
 
         let blend = 0.5;
         FormulaResult {
             de: r1.de * (1.0 - blend) + r2.de * blend,
             smooth_it: r1.smooth_it * (1.0 - blend) + r2.smooth_it * blend,
             orbit_trap: r1.orbit_trap.min(r2.orbit_trap),
+            orbit_trap2: r1.orbit_trap2.min(r2.orbit_trap2),
             inside: r1.inside && r2.inside,
             iterations: r1.iterations.max(r2.iterations),
+            interior_de: None,
+        }
+    }
+
+    /// DEmixer mode: iterate both formulas from the same state every iteration and
+    /// blend the resulting states with a weight that follows the mixer curve.
+    fn compute_mixed(
+        &self,
+        pos: &Vec3D,
+        active: &[usize],
+        curve: &DeMixerCurve,
+    ) -> FormulaResult {
+        let (slot_a, slot_b) = (&self.slots[active[0]], &self.slots[active[1]]);
+        let (fa, fb) = (&slot_a.formula, &slot_b.formula);
+        let ca = *self.slot_julia(slot_a).unwrap_or(pos);
+        let cb = *self.slot_julia(slot_b).unwrap_or(pos);
+        let mut state = IterationState::new(pos, None).with_z0_offset(&self.z0_offset);
+
+        for i in 0..self.total_iterations {
+            state.iteration = i;
+            let w = curve.weight(i, self.total_iterations);
+
+            let mut sa = state.clone();
+            let mut sb = state.clone();
+            set_julia_c(&mut sa, &ca);
+            set_julia_c(&mut sb, &cb);
+            fa.iterate_once(&mut sa, self.bailout);
+            fb.iterate_once(&mut sb, self.bailout);
+
+            state.x = utils::lerp(sa.x, sb.x, w);
+            state.y = utils::lerp(sa.y, sb.y, w);
+            state.z = utils::lerp(sa.z, sb.z, w);
+            state.w = utils::lerp(sa.w, sb.w, w);
+            state.dr = utils::lerp(sa.dr, sb.dr, w);
+            state.orbit_trap = sa.orbit_trap.min(sb.orbit_trap);
+            state.orbit_trap2 = sa.orbit_trap2.min(sb.orbit_trap2);
+            state.r_sqr = state.x * state.x + state.y * state.y + state.z * state.z;
+
+            if state.r_sqr > self.bailout {
+                let r = state.r_sqr.sqrt();
+                let de = if state.dr.abs() > 1e-30 {
+                    0.5 * r * strict::ln(r) / state.dr
+                } else {
+                    r * 0.5
+                };
+                return FormulaResult {
+                    de: de.max(0.0),
+                    smooth_it: i as f64,
+                    orbit_trap: state.orbit_trap,
+                    orbit_trap2: state.orbit_trap2,
+                    inside: false,
+                    iterations: i,
+                    interior_de: None,
+                };
+            }
+        }
+
+        FormulaResult {
+            de: 0.0,
+            smooth_it: self.total_iterations as f64,
+            orbit_trap: state.orbit_trap,
+            orbit_trap2: state.orbit_trap2,
+            inside: true,
+            iterations: self.total_iterations,
+            interior_de: None,
         }
     }
 
     /// 4D hybrid mode: extend iteration to 4D space.
-    fn compute_4d(&self, pos: &Vec3D, julia_c: Option<&Vec3D>, active: &[usize]) -> FormulaResult {
+    fn compute_4d(&self, pos: &Vec3D, active: &[usize]) -> FormulaResult {
         // For now, delegate to alternating; 4D extension requires formula-specific 4D support
-        self.compute_alternating(pos, julia_c, active)
+        self.compute_alternating(pos, active)
+    }
+}
+
+/// Point the iteration's additive constant at `c` (julia constant or start position).
+#[inline]
+fn set_julia_c(state: &mut IterationState, c: &Vec3D) {
+    state.c1 = c.x;
+    state.c2 = c.y;
+    state.c3 = c.z;
+}
+
+impl DistanceField for HybridFormula {
+    fn compute_de(&self, pos: &Vec3D) -> FormulaResult {
+        HybridFormula::compute_de(self, pos)
+    }
+
+    /// Uses the slot's lane kernel when the hybrid is a single plain formula.
+    fn compute_de_batch(&self, points: &[Vec3D], out: &mut [f64]) {
+        let mut active = self.slots.iter().filter(|s| s.active);
+        let single = match (active.next(), active.next()) {
+            (Some(slot), None) if slot.combine.is_none() && self.z0_offset == Vec3D::default() => Some(slot),
+            _ => None,
+        };
+        let Some((slot, kernel)) = single.and_then(|slot| Some((slot, slot.formula.kernel()?))) else {
+            for (p, de) in points.iter().zip(out) {
+                *de = HybridFormula::compute_de(self, p).de;
+            }
+            return;
+        };
+        let level = simd::SimdLevel::current();
+        for (chunk, out) in points.chunks(simd::LANES).zip(out.chunks_mut(simd::LANES)) {
+            // Pad a short final chunk with its first point
+            let lanes: [Vec3D; simd::LANES] = std::array::from_fn(|l| *chunk.get(l).unwrap_or(&chunk[0]));
+            let des = kernel.de4(level, &lanes, self.total_iterations, self.bailout, self.slot_julia(slot));
+            let n = out.len().min(chunk.len());
+            out[..n].copy_from_slice(&des[..n]);
+        }
     }
 }
 
@@ -189,7 +480,7 @@ mod tests {
             16.0,
         );
         let pos = Vec3D { x: 2.0, y: 0.0, z: 0.0 };
-        let result = hybrid.compute_de(&pos, None);
+        let result = hybrid.compute_de(&pos);
         assert!(result.de > 0.0);
         assert!(!result.inside);
     }
@@ -206,7 +497,121 @@ mod tests {
             16.0,
         );
         let pos = Vec3D { x: 0.0, y: 0.0, z: 0.0 };
-        let result = hybrid.compute_de(&pos, None);
+        let result = hybrid.compute_de(&pos);
         assert!(result.inside);
     }
+
+    #[test]
+    fn test_mixer_curve_weights() {
+        let curve = DeMixerCurve { start: 0.0, end: 1.0, exponent: 2.0 };
+        assert!((curve.weight(0, 11) - 0.0).abs() < 1e-12);
+        assert!((curve.weight(5, 11) - 0.25).abs() < 1e-12);
+        assert!((curve.weight(10, 11) - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_mixer_constant_weight_matches_single_formula() {
+        let mixed = HybridFormula::new(
+            &[
+                (FormulaId::MandelbulbPower8, 1),
+                (FormulaId::MandelbulbPower2, 1),
+            ],
+            HybridMode::Interpolated,
+            20,
+            16.0,
+        )
+        .with_mixer(DeMixerCurve { start: 0.0, end: 0.0, exponent: 1.0 });
+        let single = HybridFormula::new(
+            &[(FormulaId::MandelbulbPower8, 1)],
+            HybridMode::Alternating,
+            20,
+            16.0,
+        );
+        let pos = Vec3D { x: 1.3, y: 0.2, z: 0.1 };
+        let a = mixed.compute_de(&pos);
+        let b = single.compute_de(&pos);
+        assert_eq!(a.inside, b.inside);
+        assert!((a.de - b.de).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_combine_modes() {
+        let a = FormulaResult { de: 0.5, ..Default::default() };
+        let b = FormulaResult { de: -0.2, inside: true, ..Default::default() };
+        assert_eq!(CombineMode::Union.apply(a.clone(), b.clone()).de, -0.2);
+        assert_eq!(CombineMode::Intersection.apply(a.clone(), b.clone()).de, 0.5);
+        assert_eq!(CombineMode::Subtraction.apply(a.clone(), b.clone()).de, 0.5);
+        let smooth = CombineMode::SmoothUnion(0.5).apply(a, b);
+        assert!(smooth.de <= -0.2);
+    }
+
+    #[test]
+    fn test_combine_slot_not_iterated() {
+        let mut hybrid = HybridFormula::new(
+            &[(FormulaId::MandelbulbPower8, 1), (FormulaId::Heightfield, 0)],
+            HybridMode::Alternating,
+            20,
+            16.0,
+        );
+        hybrid.set_slot_combine(1, CombineMode::Union);
+        // Default heightfield is a flat slab at z ∈ [-0.1, 0] over |x|,|y| ≤ 1
+        let pos = Vec3D { x: 0.0, y: 0.0, z: -0.05 };
+        assert!(hybrid.compute_de(&pos).de < 0.0);
+        let far = Vec3D { x: 3.0, y: 0.0, z: 0.0 };
+        let bulb_only = HybridFormula::new(
+            &[(FormulaId::MandelbulbPower8, 1)],
+            HybridMode::Alternating,
+            20,
+            16.0,
+        );
+        assert!(hybrid.compute_de(&far).de <= bulb_only.compute_de(&far).de);
+    }
+
+    #[test]
+    fn test_slot_julia_overrides_global() {
+        let c = Vec3D { x: 0.3, y: -0.2, z: 0.1 };
+        let pos = Vec3D { x: 0.9, y: 0.4, z: -0.3 };
+        let bulb = || HybridFormula::new(&[(FormulaId::MandelbulbPower8, 1)], HybridMode::Alternating, 20, 16.0);
+
+        let global = bulb().with_julia(Some(c));
+        let mut per_slot = bulb();
+        per_slot.set_slot_julia(0, SlotJulia::Julia(c));
+        assert!((global.compute_de(&pos).de - per_slot.compute_de(&pos).de).abs() < 1e-12);
+
+        let mut mandel = bulb().with_julia(Some(c));
+        mandel.set_slot_julia(0, SlotJulia::Mandel);
+        assert!((mandel.compute_de(&pos).de - bulb().compute_de(&pos).de).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_z0_offset_shifts_start_not_constant() {
+        let pos = Vec3D { x: 0.6, y: 0.3, z: -0.2 };
+        let offset = Vec3D { x: 0.1, y: 0.0, z: 0.05 };
+        let single = HybridFormula::new(&[(FormulaId::MandelbulbPower8, 1)], HybridMode::Alternating, 20, 16.0)
+            .with_z0_offset(offset);
+        let hybrid = HybridFormula::new(
+            &[(FormulaId::MandelbulbPower8, 1), (FormulaId::MandelbulbPower8, 1)],
+            HybridMode::Alternating,
+            20,
+            16.0,
+        )
+        .with_z0_offset(offset);
+        // Direct delegation and the generic hybrid loop must agree
+        let a = single.compute_de(&pos);
+        let b = hybrid.compute_de(&pos);
+        assert_eq!(a.iterations, b.iterations);
+        assert!((a.de - b.de).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_batch_de_uses_kernel_with_same_result() {
+        let hybrid = HybridFormula::new(&[(FormulaId::AmazingBox, 1)], HybridMode::Alternating, 12, 16.0)
+            .with_julia(Some(Vec3D { x: 0.2, y: 0.1, z: -0.3 }));
+        let points: Vec<Vec3D> = (0..6).map(|i| Vec3D { x: 0.4 * i as f64 - 1.0, y: 0.3, z: -0.2 }).collect();
+        let mut batch = [0.0; 6];
+        hybrid.compute_de_batch(&points, &mut batch);
+        for (p, de) in points.iter().zip(batch) {
+            assert_eq!(de, hybrid.compute_de(p).de);
+        }
+    }
 }
@@ -7,7 +7,11 @@
 /// interpolated, or 4D modes — matching the original Mandelbulb3D approach.
 
 pub mod builtin;
+pub mod heightfield;
 pub mod hybrid;
+pub mod resources;
+pub mod simd;
+pub mod text;
 
 use crate::engine::types::Vec3D;
 
@@ -20,10 +24,15 @@ pub struct FormulaResult {
     pub smooth_it: f64,
     /// Orbit trap minimum distance (for alternative coloring)
     pub orbit_trap: f64,
+    /// Second trap: smallest orbit radius (point trap at the origin)
+    pub orbit_trap2: f64,
     /// Whether the point is inside the fractal
     pub inside: bool,
     /// Raw iteration count at escape
     pub iterations: u32,
+    /// Distance to the surface from an inside point (positive), when the
+    /// formula can estimate it; see `DistanceField::signed_de` otherwise
+    pub interior_de: Option<f64>,
 }
 
 impl Default for FormulaResult {
@@ -32,12 +41,68 @@ impl Default for FormulaResult {
             de: f64::MAX,
             smooth_it: 0.0,
             orbit_trap: f64::MAX,
+            orbit_trap2: f64::MAX,
             inside: false,
             iterations: 0,
+            interior_de: None,
         }
     }
 }
 
+impl FormulaResult {
+    /// Signed distance: `de` outside, minus the interior distance inside
+    /// (0 when no interior estimate is attached).
+    pub fn signed_de(&self) -> f64 {
+        if self.inside {
+            -self.interior_de.unwrap_or(0.0)
+        } else {
+            self.de
+        }
+    }
+}
+
+/// Smallest probe step of `interior_distance`.
+pub const INTERIOR_PROBE_EPS: f64 = 1e-4;
+/// Interior distances are clamped to this radius.
+pub const INTERIOR_PROBE_RADIUS: f64 = 4.0;
+
+/// Approximate distance from an inside point to the surface.
+///
+/// Escape-time DEs are 0 everywhere inside, so this probes 14 directions
+/// (axes and cube diagonals) for the nearest outside point: doubling steps
+/// from `eps` find a bracket, bisection narrows it. The result is an upper
+/// bound of the true distance (exact when the nearest boundary point lies
+/// along a probe direction), clamped to `max_radius`.
+pub fn interior_distance<F: DistanceField + ?Sized>(field: &F, pos: &Vec3D, eps: f64, max_radius: f64) -> f64 {
+    const D: f64 = 0.577_350_269_189_625_8; // 1 / sqrt(3)
+    const DIRECTIONS: [(f64, f64, f64); 14] = [
+        (1.0, 0.0, 0.0), (-1.0, 0.0, 0.0), (0.0, 1.0, 0.0), (0.0, -1.0, 0.0), (0.0, 0.0, 1.0), (0.0, 0.0, -1.0),
+        (D, D, D), (D, D, -D), (D, -D, D), (D, -D, -D), (-D, D, D), (-D, D, -D), (-D, -D, D), (-D, -D, -D),
+    ];
+    let inside = |dir: &(f64, f64, f64), t: f64| {
+        let p = Vec3D { x: pos.x + dir.0 * t, y: pos.y + dir.1 * t, z: pos.z + dir.2 * t };
+        field.compute_de(&p).inside
+    };
+
+    let mut best = max_radius;
+    for dir in &DIRECTIONS {
+        let (mut lo, mut hi) = (0.0, eps.max(1e-12));
+        while hi < best && inside(dir, hi) {
+            lo = hi;
+            hi *= 2.0;
+        }
+        if hi >= best {
+            continue;
+        }
+        for _ in 0..12 {
+            let mid = 0.5 * (lo + hi);
+            if inside(dir, mid) { lo = mid } else { hi = mid }
+        }
+        best = best.min(hi);
+    }
+    best
+}
+
 /// Iteration state passed to each formula — port of TIteration3Dext.
 #[derive(Clone, Debug)]
 pub struct IterationState {
@@ -58,8 +123,12 @@ pub struct IterationState {
     pub smooth: f64,
     /// Orbit trap tracking
     pub orbit_trap: f64,
+    /// Point trap tracking (smallest orbit radius)
+    pub orbit_trap2: f64,
     /// Current iteration number
     pub iteration: u32,
+    /// Scale of varying-scale formulas, advanced once per call (None until the first)
+    pub varied_scale: Option<f64>,
 }
 
 impl IterationState {
@@ -73,9 +142,131 @@ impl IterationState {
             r_sqr: 0.0,
             smooth: 0.0,
             orbit_trap: f64::MAX,
+            orbit_trap2: f64::MAX,
             iteration: 0,
+            varied_scale: None,
+        }
+    }
+
+    /// Record the plane trap `plane_trap` of the current orbit point, and
+    /// its radius for the point trap.
+    #[inline]
+    pub fn track_traps(&mut self, plane_trap: f64) {
+        if plane_trap < self.orbit_trap { self.orbit_trap = plane_trap; }
+        let r_sqr = self.x * self.x + self.y * self.y + self.z * self.z;
+        if r_sqr < self.orbit_trap2 * self.orbit_trap2 { self.orbit_trap2 = r_sqr.sqrt(); }
+    }
+
+    /// Shift the initial orbit value (z0 += offset) without touching the constant.
+    pub fn with_z0_offset(mut self, offset: &Vec3D) -> Self {
+        self.x += offset.x;
+        self.y += offset.y;
+        self.z += offset.z;
+        self
+    }
+}
+
+/// Optional tagged parameter block carried in the formula_ids array.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParamSection {
+    /// Section kind (see the `SECTION_*` constants in lib.rs)
+    pub tag: u32,
+    /// Hybrid slot the section applies to (ignored by hybrid-wide sections)
+    pub slot: u32,
+    /// Decoded f64 payload
+    pub values: Vec<f64>,
+}
+
+impl ParamSection {
+    /// Append the section in the `parse_param_sections` word layout.
+    pub fn encode(&self, words: &mut Vec<u32>) {
+        words.extend([self.tag, self.slot, self.values.len() as u32]);
+        for v in &self.values {
+            let bits = v.to_bits();
+            words.extend([bits as u32, (bits >> 32) as u32]);
+        }
+    }
+}
+
+/// Most hybrid slots a formula_ids buffer carries.
+pub const MAX_HYBRID_SLOTS: usize = 6;
+
+/// Decoded formula_ids buffer.
+///
+/// Layout: `[num_slots, id1, iters1, ..., hybrid_mode, sections...]`, with ids
+/// in `FormulaId::ALL` order and sections as in `parse_param_sections`. At
+/// most `MAX_HYBRID_SLOTS` slots are read; a buffer that ends inside the slot
+/// list or before the mode has no mode and no sections.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FormulaIds {
+    /// (formula, iterations) per slot
+    pub slots: Vec<(FormulaId, u32)>,
+    /// Hybrid mode: 0 = alternating, 1 = interpolated, 2 = 4D
+    pub mode: Option<u32>,
+    pub sections: Vec<ParamSection>,
+}
+
+impl FormulaIds {
+    /// Read a buffer; it never fails, missing parts are left empty.
+    pub fn decode(words: &[u32]) -> Self {
+        let count = words.first().map_or(0, |&n| (n as usize).min(MAX_HYBRID_SLOTS));
+        let slots: Vec<_> = words
+            .get(1..)
+            .unwrap_or_default()
+            .chunks_exact(2)
+            .take(count)
+            .map(|pair| (FormulaId::from_u32(pair[0]), pair[1]))
+            .collect();
+        let mode_index = 1 + count * 2;
+        if slots.len() < count || words.len() <= mode_index {
+            return Self { slots, ..Default::default() };
+        }
+        Self {
+            slots,
+            mode: Some(words[mode_index]),
+            sections: parse_param_sections(&words[mode_index + 1..]),
+        }
+    }
+
+    /// Words of the buffer; the mode defaults to alternating.
+    pub fn encode(&self) -> Vec<u32> {
+        let mut words = vec![self.slots.len() as u32];
+        for (id, iterations) in &self.slots {
+            words.extend([id.to_u32(), *iterations]);
+        }
+        words.push(self.mode.unwrap_or(0));
+        for section in &self.sections {
+            section.encode(&mut words);
         }
+        words
+    }
+}
+
+/// Parse tagged parameter sections from a u32 word stream.
+///
+/// Each section is `[tag, slot, count, lo0, hi0, lo1, hi1, ...]`, carrying `count`
+/// f64 values as little-endian u32 word pairs (a Float64Array viewed as Uint32Array).
+/// A truncated trailing section is dropped.
+pub fn parse_param_sections(words: &[u32]) -> Vec<ParamSection> {
+    let mut sections = Vec::new();
+    let mut idx = 0;
+    while idx + 2 < words.len() {
+        let tag = words[idx];
+        let slot = words[idx + 1];
+        let count = words[idx + 2] as usize;
+        idx += 3;
+        if idx + count * 2 > words.len() { break; }
+        let values = (0..count)
+            .map(|i| {
+                let lo = words[idx + i * 2] as u64;
+                let hi = words[idx + i * 2 + 1] as u64;
+                f64::from_bits((hi << 32) | lo)
+            })
+            .collect();
+        idx += count * 2;
+        sections.push(ParamSection { tag, slot, values });
     }
+    sections
 }
 
 /// Formula trait — each fractal formula implements this.
@@ -91,6 +282,50 @@ pub trait Formula: Send + Sync {
     /// Perform a single iteration step (for hybrid systems).
     /// Returns true if the point has escaped (r_sqr > bailout).
     fn iterate_once(&self, state: &mut IterationState, bailout: f64) -> bool;
+
+    /// Specialized lane kernel for batched evaluation, if the formula has one.
+    fn kernel(&self) -> Option<simd::Kernel> {
+        None
+    }
+}
+
+/// Anything the ray marcher can trace: a hybrid formula or a composite scene.
+pub trait DistanceField: Send + Sync {
+    /// Distance estimate (plus coloring data) at a world-space position.
+    fn compute_de(&self, pos: &Vec3D) -> FormulaResult;
+
+    /// Distance estimates of several points at once, written to `out`
+    /// (overridden where lane kernels apply, see `simd`).
+    fn compute_de_batch(&self, points: &[Vec3D], out: &mut [f64]) {
+        for (p, de) in points.iter().zip(out) {
+            *de = self.compute_de(p).de;
+        }
+    }
+
+    /// Signed distance at `pos`, negative inside. Inside points without an
+    /// interior estimate from the formula are probed with `interior_distance`.
+    fn signed_de(&self, pos: &Vec3D) -> f64 {
+        let fr = self.compute_de(pos);
+        match (fr.inside, fr.interior_de) {
+            (false, _) | (true, Some(_)) => fr.signed_de(),
+            (true, None) => -interior_distance(self, pos, INTERIOR_PROBE_EPS, INTERIOR_PROBE_RADIUS),
+        }
+    }
+}
+
+/// Static documentation for a formula, surfaced to the UI as in-context help.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FormulaInfo {
+    /// UI name (as accepted by `FormulaId::from_name`)
+    pub name: &'static str,
+    /// Name of the formula (or formula file) in the original Mandelbulb3D
+    pub mb3d_name: &'static str,
+    /// Short usage notes
+    pub notes: &'static str,
+    /// Recommended bailout (squared escape radius)
+    pub bailout: f64,
+    /// Recommended ray step width
+    pub step_width: f64,
 }
 
 /// Formula identifier matching the TypeScript/UI formula names.
@@ -107,9 +342,137 @@ pub enum FormulaId {
     FoldingIntPow,
     RealPower,
     AexionC,
+    ABoxMod1,
+    ABoxMod2,
+    ASurfMod1,
+    PowerNBulb,
+    Lambdabulb,
+    Heightfield,
+    Text,
+    ASurfMod2,
 }
 
 impl FormulaId {
+    /// Every formula, in formula-ID order (index 0 = `None`).
+    pub const ALL: [FormulaId; 19] = [
+        FormulaId::None,
+        FormulaId::MandelbulbPower2,
+        FormulaId::MandelbulbPower8,
+        FormulaId::AmazingBox,
+        FormulaId::AmazingSurf,
+        FormulaId::QuaternionJulia,
+        FormulaId::Tricorn,
+        FormulaId::Bulbox,
+        FormulaId::FoldingIntPow,
+        FormulaId::RealPower,
+        FormulaId::AexionC,
+        FormulaId::ABoxMod1,
+        FormulaId::ABoxMod2,
+        FormulaId::ASurfMod1,
+        FormulaId::PowerNBulb,
+        FormulaId::Lambdabulb,
+        FormulaId::Heightfield,
+        FormulaId::Text,
+        FormulaId::ASurfMod2,
+    ];
+
+    /// Formula with wire id `id` (its index in `ALL`); unknown ids are `None`.
+    pub fn from_u32(id: u32) -> Self {
+        FormulaId::ALL.get(id as usize).copied().unwrap_or(FormulaId::None)
+    }
+
+    /// Wire id of this formula (its index in `ALL`).
+    pub fn to_u32(self) -> u32 {
+        FormulaId::ALL.iter().position(|id| *id == self).unwrap_or(0) as u32
+    }
+
+    /// Usage notes and recommended settings for this formula.
+    pub fn info(&self) -> FormulaInfo {
+        let (name, mb3d_name, notes, bailout, step_width) = match self {
+            FormulaId::None => ("(none)", "", "Empty slot; skipped by the hybrid.", 16.0, 1.0),
+            FormulaId::MandelbulbPower2 => (
+                "Mandelbulb Power 2", "Integer Power 2",
+                "Smooth, blobby bulb. Good as a softening slot in hybrids.", 16.0, 0.8,
+            ),
+            FormulaId::MandelbulbPower8 => (
+                "Mandelbulb Power 8", "Integer Power 8",
+                "The classic Mandelbulb. Raise iterations for deep zooms.", 16.0, 0.8,
+            ),
+            FormulaId::AmazingBox => (
+                "Amazing Box", "Amazing Box",
+                "Mandelbox. Scale -1.5 to -2 gives the classic box; positive scales give solid shapes. Needs a larger bailout.",
+                1024.0, 0.5,
+            ),
+            FormulaId::AmazingSurf => (
+                "Amazing Surf", "Amazing Surf",
+                "2D-folded box variant producing surf-like sheets. Lower the step width if surfaces tear.",
+                1024.0, 0.4,
+            ),
+            FormulaId::QuaternionJulia => (
+                "Quaternion Julia", "Quaternion",
+                "Use with Julia mode enabled; the Julia constant shapes the set.", 16.0, 0.8,
+            ),
+            FormulaId::Tricorn => (
+                "Tricorn", "Tricorn",
+                "Conjugated bulb with sharp ridges; DE is rough, so keep the step width low.", 16.0, 0.5,
+            ),
+            FormulaId::Bulbox => (
+                "Bulbox", "Bulbox",
+                "Box fold followed by a bulb step. Mixes boxy and organic detail.", 64.0, 0.6,
+            ),
+            FormulaId::FoldingIntPow => (
+                "Folding IntPow", "Folding Int Pow",
+                "Box fold with an integer power. Parameters: power, fold limit.", 64.0, 0.6,
+            ),
+            FormulaId::RealPower => (
+                "Real Power", "Real Power",
+                "Mandelbulb with a real-valued power. Parameter: power.", 16.0, 0.8,
+            ),
+            FormulaId::AexionC => (
+                "Aexion C", "AexionC",
+                "Aexion's octonion-style variant. Works best in hybrids with a bulb.", 16.0, 0.6,
+            ),
+            FormulaId::ABoxMod1 => (
+                "ABoxMod1", "ABoxMod1",
+                "Amazing Box with Kali offset folds and per-axis asymmetry.", 1024.0, 0.5,
+            ),
+            FormulaId::ABoxMod2 => (
+                "ABoxMod2", "ABoxMod2",
+                "Amazing Box with a separate Z fold and cylindrical inversion. Non-conformal; lower the step width.",
+                1024.0, 0.4,
+            ),
+            FormulaId::ASurfMod1 => (
+                "ASurfMod1", "_ASurfMod1",
+                "Amazing Surf folds combined with ABoxMod1 offsets and rotation.", 1024.0, 0.4,
+            ),
+            FormulaId::PowerNBulb => (
+                "Power-N Bulb", "_SinePow2 / CosinePow8",
+                "Bulb with a real power and a selectable sine/cosine angle convention. Parameters: power, convention, z multiplier.",
+                16.0, 0.8,
+            ),
+            FormulaId::Lambdabulb => (
+                "Lambdabulb", "Lambda4Dc / Lambda4Dnc",
+                "Logistic-map bulb; use with Julia mode for the classic Lambdabulb.", 16.0, 0.6,
+            ),
+            FormulaId::Heightfield => (
+                "Heightfield", "",
+                "Extrudes an uploaded grayscale image (8- or 16-bit terrain), scaled and offset by its \
+                 parameters. Not iterable; use standalone or as a combine slot.", 16.0, 1.0,
+            ),
+            FormulaId::Text => (
+                "Text", "",
+                "Extruded glyph outlines from uploaded paths. Not iterable; use standalone or as a combine slot.",
+                16.0, 1.0,
+            ),
+            FormulaId::ASurfMod2 => (
+                "ASurfMod2", "ASurfMod2",
+                "Amazing Surf with separate X/Y folds, cylindrical inversion and rotation. Non-conformal; lower the step width.",
+                1024.0, 0.4,
+            ),
+        };
+        FormulaInfo { name, mb3d_name, notes, bailout, step_width }
+    }
+
     /// Parse from a string name (matching UI dropdown values).
     pub fn from_name(name: &str) -> Self {
         match name {
@@ -123,6 +486,14 @@ impl FormulaId {
             "Folding IntPow" => FormulaId::FoldingIntPow,
             "Real Power" => FormulaId::RealPower,
             "Aexion C" => FormulaId::AexionC,
+            "ABoxMod1" => FormulaId::ABoxMod1,
+            "ABoxMod2" => FormulaId::ABoxMod2,
+            "ASurfMod1" => FormulaId::ASurfMod1,
+            "Power-N Bulb" => FormulaId::PowerNBulb,
+            "Lambdabulb" => FormulaId::Lambdabulb,
+            "Heightfield" => FormulaId::Heightfield,
+            "Text" => FormulaId::Text,
+            "ASurfMod2" => FormulaId::ASurfMod2,
             _ => FormulaId::None,
         }
     }
@@ -141,6 +512,101 @@ impl FormulaId {
             FormulaId::FoldingIntPow => Box::new(builtin::FoldingIntPow::default()),
             FormulaId::RealPower => Box::new(builtin::RealPower::new(8.0)),
             FormulaId::AexionC => Box::new(builtin::AexionC),
+            FormulaId::ABoxMod1 => Box::new(builtin::ABoxMod1::default()),
+            FormulaId::ABoxMod2 => Box::new(builtin::ABoxMod2::default()),
+            FormulaId::ASurfMod1 => Box::new(builtin::ASurfMod1::default()),
+            FormulaId::PowerNBulb => Box::new(builtin::PowerNBulb::default()),
+            FormulaId::Lambdabulb => Box::new(builtin::Lambdabulb::default()),
+            FormulaId::Heightfield => Box::new(heightfield::Heightfield::default()),
+            FormulaId::Text => Box::new(text::Text::default()),
+            FormulaId::ASurfMod2 => Box::new(builtin::ASurfMod2::default()),
         }
     }
+
+    /// Create a formula instance with user parameters.
+    ///
+    /// `params` overrides the formula's defaults in declaration order; missing
+    /// trailing values keep their defaults. Formulas without parameters ignore it.
+    pub fn create_with_params(&self, params: &[f64]) -> Box<dyn Formula> {
+        if params.is_empty() {
+            return self.create();
+        }
+        match self {
+            FormulaId::AmazingBox => Box::new(builtin::AmazingBox::from_params(params)),
+            FormulaId::AmazingSurf => Box::new(builtin::AmazingSurf::from_params(params)),
+            FormulaId::FoldingIntPow => Box::new(builtin::FoldingIntPow {
+                power: params[0].round().max(1.0) as u32,
+                fold_limit: params.get(1).copied().unwrap_or(1.0),
+            }),
+            FormulaId::RealPower => Box::new(builtin::RealPower::new(params[0])),
+            FormulaId::ABoxMod1 => Box::new(builtin::ABoxMod1::from_params(params)),
+            FormulaId::ABoxMod2 => Box::new(builtin::ABoxMod2::from_params(params)),
+            FormulaId::ASurfMod1 => Box::new(builtin::ASurfMod1::from_params(params)),
+            FormulaId::ASurfMod2 => Box::new(builtin::ASurfMod2::from_params(params)),
+            FormulaId::PowerNBulb => Box::new(builtin::PowerNBulb::from_params(params)),
+            FormulaId::Lambdabulb => Box::new(builtin::Lambdabulb::from_params(params)),
+            FormulaId::Heightfield => Box::new(heightfield::Heightfield::from_params(params)),
+            FormulaId::Text => Box::new(text::Text::from_params(params)),
+            _ => self.create(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_info_names_match_registry() {
+        for (idx, id) in FormulaId::ALL.iter().enumerate() {
+            let info = id.info();
+            if idx > 0 {
+                assert_eq!(FormulaId::from_name(info.name), *id);
+                assert_eq!(id.create().name(), info.name);
+            }
+            assert!(info.bailout > 0.0 && info.step_width > 0.0);
+        }
+    }
+
+    #[test]
+    fn test_formula_ids_round_trip() {
+        let section = ParamSection { tag: 2, slot: 1, values: vec![0.5, -3.0] };
+        let ids = FormulaIds {
+            slots: vec![(FormulaId::AmazingBox, 3), (FormulaId::ASurfMod2, 1)],
+            mode: Some(1),
+            sections: vec![section],
+        };
+        let words = ids.encode();
+        assert_eq!(words[..6], [2, 3, 3, 18, 1, 1]);
+        assert_eq!(FormulaIds::decode(&words), ids);
+
+        // Cut inside the slot list: no mode, no sections
+        let cut = FormulaIds::decode(&words[..4]);
+        assert_eq!((cut.slots.len(), cut.mode), (1, None));
+        // Unknown ids read as None
+        assert_eq!(FormulaIds::decode(&[1, 99, 4, 0]).slots, [(FormulaId::None, 4)]);
+    }
+
+    /// Escape-time style unit ball: DE 0 and `inside` within radius 1.
+    struct Ball;
+
+    impl DistanceField for Ball {
+        fn compute_de(&self, pos: &Vec3D) -> FormulaResult {
+            let r = (pos.x * pos.x + pos.y * pos.y + pos.z * pos.z).sqrt();
+            FormulaResult { de: (r - 1.0).max(0.0), inside: r < 1.0, ..Default::default() }
+        }
+    }
+
+    #[test]
+    fn test_signed_de_is_negative_inside() {
+        assert!((Ball.signed_de(&Vec3D { x: 0.0, y: 0.0, z: 1.5 }) - 0.5).abs() < 1e-12);
+        let center = Ball.signed_de(&Vec3D::default());
+        assert!((center + 1.0).abs() < 1e-3, "{center}");
+        let off = Ball.signed_de(&Vec3D { x: 0.3, y: 0.4, z: 0.0 });
+        // Upper bound of the true 0.5, off the probe directions
+        assert!((-0.65..=-0.5).contains(&off), "{off}");
+
+        let estimated = FormulaResult { inside: true, interior_de: Some(0.25), ..Default::default() };
+        assert_eq!(estimated.signed_de(), -0.25);
+    }
 }
@@ -0,0 +1,51 @@
+//! Handle-based registry for bulk data uploaded from JS (images, glyph paths).
+//!
+//! Pseudo-formulas cannot receive large buffers through the u32 formula_ids
+//! protocol, so the data is registered once and referenced by handle in the
+//! formula's parameter list.
+
+use std::sync::{Arc, Mutex};
+
+/// Thread-safe slot map of shared resources; released handles are reused.
+pub struct Registry<T> {
+    slots: Mutex<Vec<Option<Arc<T>>>>,
+}
+
+impl<T> Registry<T> {
+    pub const fn new() -> Self {
+        Self { slots: Mutex::new(Vec::new()) }
+    }
+
+    /// Store a resource, returning its handle.
+    pub fn insert(&self, value: T) -> u32 {
+        let mut slots = self.slots.lock().unwrap_or_else(|e| e.into_inner());
+        let value = Some(Arc::new(value));
+        if let Some(free) = slots.iter().position(|slot| slot.is_none()) {
+            slots[free] = value;
+            free as u32
+        } else {
+            slots.push(value);
+            (slots.len() - 1) as u32
+        }
+    }
+
+    /// Look up a resource by handle.
+    pub fn get(&self, handle: u32) -> Option<Arc<T>> {
+        let slots = self.slots.lock().unwrap_or_else(|e| e.into_inner());
+        slots.get(handle as usize).cloned().flatten()
+    }
+
+    /// Release a handle.
+    pub fn remove(&self, handle: u32) {
+        let mut slots = self.slots.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(slot) = slots.get_mut(handle as usize) {
+            *slot = None;
+        }
+    }
+}
+
+impl<T> Default for Registry<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
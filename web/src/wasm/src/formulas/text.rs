@@ -0,0 +1,204 @@
+//! Text pseudo-formula — extruded 2D glyph paths as a DE object.
+//!
+//! JS converts text to path polygons (e.g. from opentype.js outlines or a
+//! single-stroke font) and registers them here. Closed outlines are filled
+//! with the even-odd rule; with a stroke width the paths are treated as open
+//! polylines instead. The shape lies in the XY plane, extruded symmetrically
+//! along Z, and is combined with the fractal via `hybrid::CombineMode`.
+
+use std::sync::Arc;
+
+use crate::engine::types::Vec3D;
+use super::{Formula, FormulaResult, IterationState};
+use super::resources::Registry;
+
+/// A set of 2D paths in font units.
+#[derive(Clone, Debug, Default)]
+pub struct GlyphPaths {
+    pub paths: Vec<Vec<(f64, f64)>>,
+}
+
+impl GlyphPaths {
+    /// Parse `[num_paths, (num_points, x0, y0, x1, y1, ...)*]`.
+    pub fn from_buffer(data: &[f64]) -> Option<Self> {
+        let num_paths = *data.first()? as usize;
+        let mut paths = Vec::with_capacity(num_paths.min(4096));
+        let mut idx = 1;
+        for _ in 0..num_paths {
+            let count = *data.get(idx)? as usize;
+            idx += 1;
+            let end = count.checked_mul(2).and_then(|n| idx.checked_add(n))?;
+            let coords = data.get(idx..end)?;
+            paths.push(coords.chunks_exact(2).map(|c| (c[0], c[1])).collect());
+            idx = end;
+        }
+        Some(Self { paths })
+    }
+
+    /// Signed 2D distance: negative inside filled outlines (even-odd rule).
+    pub fn outline_distance(&self, px: f64, py: f64) -> f64 {
+        let mut min_sq = f64::MAX;
+        let mut inside = false;
+        for path in self.paths.iter().filter(|p| p.len() >= 2) {
+            let mut j = path.len() - 1;
+            for i in 0..path.len() {
+                let (a, b) = (path[j], path[i]);
+                min_sq = min_sq.min(segment_distance_sq(px, py, a, b));
+                // Ray crossing toward +X
+                if (b.1 > py) != (a.1 > py) {
+                    let x = b.0 + (py - b.1) / (a.1 - b.1) * (a.0 - b.0);
+                    if px < x {
+                        inside = !inside;
+                    }
+                }
+                j = i;
+            }
+        }
+        let d = min_sq.sqrt();
+        if inside { -d } else { d }
+    }
+
+    /// Unsigned distance to the paths as open polylines.
+    pub fn stroke_distance(&self, px: f64, py: f64) -> f64 {
+        let mut min_sq = f64::MAX;
+        for path in &self.paths {
+            for seg in path.windows(2) {
+                min_sq = min_sq.min(segment_distance_sq(px, py, seg[0], seg[1]));
+            }
+            if path.len() == 1 {
+                let (dx, dy) = (px - path[0].0, py - path[0].1);
+                min_sq = min_sq.min(dx * dx + dy * dy);
+            }
+        }
+        min_sq.sqrt()
+    }
+}
+
+/// Squared distance from (px, py) to segment a–b.
+fn segment_distance_sq(px: f64, py: f64, a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (ex, ey) = (b.0 - a.0, b.1 - a.1);
+    let (wx, wy) = (px - a.0, py - a.1);
+    let len_sq = ex * ex + ey * ey;
+    let t = if len_sq > 0.0 { ((wx * ex + wy * ey) / len_sq).clamp(0.0, 1.0) } else { 0.0 };
+    let (dx, dy) = (wx - ex * t, wy - ey * t);
+    dx * dx + dy * dy
+}
+
+static GLYPHS: Registry<GlyphPaths> = Registry::new();
+
+/// Store glyph paths, returning their handle.
+pub fn register_glyphs(glyphs: GlyphPaths) -> u32 {
+    GLYPHS.insert(glyphs)
+}
+
+/// Look up registered glyph paths.
+pub fn glyphs(handle: u32) -> Option<Arc<GlyphPaths>> {
+    GLYPHS.get(handle)
+}
+
+/// Release registered glyph paths.
+pub fn release_glyphs(handle: u32) {
+    GLYPHS.remove(handle);
+}
+
+/// Extruded text DE object.
+pub struct Text {
+    pub glyphs: Option<Arc<GlyphPaths>>,
+    /// World units per font unit
+    pub scale: f64,
+    /// Total extrusion depth along Z
+    pub depth: f64,
+    /// Stroke width in world units (0 = filled outlines)
+    pub stroke_width: f64,
+}
+
+impl Default for Text {
+    fn default() -> Self {
+        Self { glyphs: None, scale: 1.0, depth: 0.1, stroke_width: 0.0 }
+    }
+}
+
+impl Text {
+    /// Parameter order: [glyph_handle, scale, depth, stroke_width]
+    pub fn from_params(params: &[f64]) -> Self {
+        let mut f = Self::default();
+        let get = |i: usize, d: f64| params.get(i).copied().unwrap_or(d);
+        f.glyphs = params.first().and_then(|&h| glyphs(h as u32));
+        f.scale = get(1, f.scale).max(1e-12);
+        f.depth = get(2, f.depth).max(0.0);
+        f.stroke_width = get(3, f.stroke_width).max(0.0);
+        f
+    }
+
+    /// Signed distance to the extruded shape.
+    pub fn signed_distance(&self, pos: &Vec3D) -> f64 {
+        let Some(glyphs) = &self.glyphs else {
+            return f64::MAX;
+        };
+        let (px, py) = (pos.x / self.scale, pos.y / self.scale);
+        let d2 = if self.stroke_width > 0.0 {
+            glyphs.stroke_distance(px, py) * self.scale - self.stroke_width * 0.5
+        } else {
+            glyphs.outline_distance(px, py) * self.scale
+        };
+        // Extrusion of a 2D SDF
+        let dz = pos.z.abs() - self.depth * 0.5;
+        let (ox, oz) = (d2.max(0.0), dz.max(0.0));
+        d2.max(dz).min(0.0) + (ox * ox + oz * oz).sqrt()
+    }
+}
+
+impl Formula for Text {
+    fn name(&self) -> &str { "Text" }
+
+    fn compute_de(&self, pos: &Vec3D, _max_iter: u32, _bailout: f64, _julia_c: Option<&Vec3D>) -> FormulaResult {
+        let de = self.signed_distance(pos);
+        FormulaResult {
+            de,
+            smooth_it: 0.0,
+            orbit_trap: 0.0,
+            orbit_trap2: 0.0,
+            inside: de < 0.0,
+            iterations: 0,
+            interior_de: None,
+        }
+    }
+
+    fn iterate_once(&self, _state: &mut IterationState, _bailout: f64) -> bool {
+        true // Pseudo-formula — not iterable
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_square() -> GlyphPaths {
+        GlyphPaths::from_buffer(&[1.0, 4.0, -1.0, -1.0, 1.0, -1.0, 1.0, 1.0, -1.0, 1.0]).unwrap()
+    }
+
+    #[test]
+    fn test_parse_rejects_truncated_buffer() {
+        assert!(GlyphPaths::from_buffer(&[1.0, 4.0, 0.0, 0.0]).is_none());
+        for count in [f64::INFINITY, 1e300, usize::MAX as f64] {
+            assert!(GlyphPaths::from_buffer(&[1.0, count, 0.0, 0.0]).is_none());
+        }
+    }
+
+    #[test]
+    fn test_outline_distance_sign() {
+        let sq = unit_square();
+        assert!((sq.outline_distance(0.0, 0.0) + 1.0).abs() < 1e-12);
+        assert!((sq.outline_distance(3.0, 0.0) - 2.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_extruded_text() {
+        let handle = register_glyphs(unit_square());
+        let text = Text::from_params(&[handle as f64, 0.5, 0.2]);
+        assert!(text.signed_distance(&Vec3D { x: 0.0, y: 0.0, z: 0.0 }) < 0.0);
+        let above = text.signed_distance(&Vec3D { x: 0.0, y: 0.0, z: 1.1 });
+        assert!((above - 1.0).abs() < 1e-12);
+        release_glyphs(handle);
+    }
+}
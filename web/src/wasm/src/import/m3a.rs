@@ -0,0 +1,310 @@
+//! MB3D animation projects (.m3a).
+//!
+//! Layout of version 5, the only one written since MB3D 1.7 (little endian):
+//!
+//! | size | field |
+//! |-----:|-------|
+//! | 4    | version (5) |
+//! | 16   | frame width, height, image scale, 3D flag |
+//! | 256  | output folder (Pascal short string) |
+//! | 4    | options: bits 0-1 interpolation, bit 2 loop, bits 3-5 output format, bit 6 stereo, bit 7 left eye only, bit 8 keep existing images, bit 9 save Z-buffer |
+//! | 4    | keyframe count (1..4095) |
+//! |      | per keyframe: frames to the next key, preview time (ms), unused smoothing (4 bytes each), `MandHeader10`, `HeaderCustomAddon` |
+//! |      | per keyframe: preview width, height (4 bytes each), then BGRX pixels, bottom row first |
+//!
+//! Each keyframe is a full parameter set, so it converts to the wasm
+//! buffers like an .m3p file (`M3pFile::to_scene`).
+
+use std::fmt;
+
+use super::m3p::{self, M3pFile};
+
+/// Version written by MB3D 1.7 and later; older files use `MandHeader9`.
+pub const VERSION: i32 = 5;
+/// Most keyframes MB3D accepts.
+pub const MAX_KEYFRAMES: usize = 4095;
+
+/// Why a buffer is not a loadable animation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// Shorter than the fields it announces
+    Truncated,
+    /// Version other than 5
+    Version(i32),
+    /// Keyframe count outside 1..=4095
+    KeyframeCount(i32),
+    /// A keyframe's parameters could not be read
+    Keyframe(usize, m3p::ParseError),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Truncated => f.write_str(".m3a file is truncated"),
+            ParseError::Version(v) if *v < VERSION => {
+                write!(f, ".m3a version {v} is too old; resave it in Mandelbulb3D 1.7 or later")
+            }
+            ParseError::Version(v) => write!(f, "unsupported .m3a version {v}"),
+            ParseError::KeyframeCount(n) => write!(f, "invalid keyframe count {n}"),
+            ParseError::Keyframe(i, e) => write!(f, "keyframe {}: {e}", i + 1),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// How subframes between keyframes are interpolated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interpolation {
+    /// Straight from one key to the next
+    Linear,
+    /// Quadratic Bézier through the neighbouring keys (smooth camera paths)
+    Bezier,
+}
+
+/// Image format MB3D writes the frames in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    Bmp,
+    Png,
+    Jpg,
+    /// A parameter file per frame
+    M3p,
+}
+
+impl OutputFormat {
+    fn from_u32(v: u32) -> Self {
+        match v {
+            1 => OutputFormat::Png,
+            2 => OutputFormat::Jpg,
+            3 => OutputFormat::M3p,
+            _ => OutputFormat::Bmp,
+        }
+    }
+}
+
+/// Keyframe thumbnail, RGBA8 top row first.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Preview {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+/// One key of the animation.
+#[derive(Clone)]
+pub struct Keyframe {
+    /// Parameters at this key
+    pub file: M3pFile,
+    /// Frames from this key to the next (0 = the key is passed over)
+    pub frames: u32,
+    /// Time MB3D took to render the preview, in milliseconds (for estimates)
+    pub preview_ms: u32,
+    pub preview: Preview,
+}
+
+/// A parsed animation project.
+#[derive(Clone)]
+pub struct Animation {
+    /// Frame size; keys are rendered at `image_scale` times this and downsampled
+    pub width: u32,
+    pub height: u32,
+    pub image_scale: u32,
+    pub output_folder: String,
+    pub interpolation: Interpolation,
+    /// The last key runs back into the first
+    pub looped: bool,
+    pub output_format: OutputFormat,
+    pub stereo: bool,
+    pub left_eye_only: bool,
+    pub overwrite: bool,
+    pub save_zbuffer: bool,
+    pub keyframes: Vec<Keyframe>,
+}
+
+impl Animation {
+    /// Frames in the whole animation (MB3D's `TotalBMPsToRender`). Without
+    /// a loop the last key adds its own frame.
+    pub fn frame_count(&self) -> u32 {
+        let keys = if self.looped { self.keyframes.len() } else { self.keyframes.len().saturating_sub(1) };
+        let between: u32 = self.keyframes[..keys].iter().map(|k| k.frames).sum();
+        let last = self.keyframes.last().is_some_and(|k| k.frames > 0);
+        between + u32::from(!self.looped && last)
+    }
+
+    /// Key that `frame` starts from and the position [0, 1) toward the next key.
+    pub fn keyframe_at(&self, frame: u32) -> Option<(usize, f64)> {
+        if frame >= self.frame_count() {
+            return None;
+        }
+        let mut start = 0;
+        for (i, key) in self.keyframes.iter().enumerate() {
+            if frame < start + key.frames {
+                return Some((i, (frame - start) as f64 / key.frames as f64));
+            }
+            start += key.frames;
+        }
+        // The closing frame of an animation without a loop
+        Some((self.keyframes.len() - 1, 0.0))
+    }
+}
+
+/// Little-endian reader over the file.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], ParseError> {
+        let end = self.pos.checked_add(n).filter(|&end| end <= self.bytes.len()).ok_or(ParseError::Truncated)?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn i32(&mut self) -> Result<i32, ParseError> {
+        Ok(i32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, ParseError> {
+        Ok(self.i32()?.max(0) as u32)
+    }
+}
+
+/// Parse an .m3a byte buffer.
+pub fn parse(bytes: &[u8]) -> Result<Animation, ParseError> {
+    let mut r = Reader { bytes, pos: 0 };
+    let version = r.i32()?;
+    if version != VERSION {
+        return Err(ParseError::Version(version));
+    }
+    let (width, height, image_scale) = (r.u32()?, r.u32()?, r.u32()?.max(1));
+    r.take(4)?; // 3D flag, taken from the keyframes
+    let folder = r.take(256)?;
+    let output_folder = String::from_utf8_lossy(&folder[1..1 + folder[0] as usize]).into_owned();
+    let options = r.u32()?;
+    let count = r.i32()?;
+    if !(1..=MAX_KEYFRAMES as i32).contains(&count) {
+        return Err(ParseError::KeyframeCount(count));
+    }
+
+    let mut keyframes = Vec::with_capacity(count as usize);
+    for i in 0..count as usize {
+        let (frames, preview_ms) = (r.u32()?, r.u32()?);
+        r.take(4)?;
+        let (file, len) = m3p::parse_records(&bytes[r.pos..]).map_err(|e| match e {
+            m3p::ParseError::Truncated(_) => ParseError::Truncated,
+            e => ParseError::Keyframe(i, e),
+        })?;
+        if len == m3p::HEADER_LEN {
+            return Err(ParseError::Truncated);
+        }
+        r.take(len)?;
+        keyframes.push(Keyframe { file, frames, preview_ms, preview: Preview::default() });
+    }
+    for key in &mut keyframes {
+        let (w, h) = (r.u32()?, r.u32()?);
+        let size = (w as usize).checked_mul(h as usize).and_then(|n| n.checked_mul(4)).ok_or(ParseError::Truncated)?;
+        let bgrx = r.take(size)?;
+        let mut rgba = Vec::with_capacity(size);
+        for row in bgrx.chunks_exact(w.max(1) as usize * 4).rev() {
+            rgba.extend(row.chunks_exact(4).flat_map(|p| [p[2], p[1], p[0], 255]));
+        }
+        key.preview = Preview { width: w, height: h, rgba };
+    }
+
+    Ok(Animation {
+        width,
+        height,
+        image_scale,
+        output_folder,
+        interpolation: if options & 3 == 1 { Interpolation::Bezier } else { Interpolation::Linear },
+        looped: options & 4 != 0,
+        output_format: OutputFormat::from_u32((options >> 3) & 7),
+        stereo: options & 64 != 0,
+        left_eye_only: options & 128 != 0,
+        overwrite: options & 256 == 0,
+        save_zbuffer: options & 512 != 0,
+        keyframes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ABOX: &[u8] = include_bytes!("../../../../../M3Parameter/ABoxScale2Start.m3p");
+    const QUAT_HYBRID: &[u8] = include_bytes!("../../../../../M3Parameter/QuatP4hybridJulia.m3p");
+
+    /// An animation as MB3D's "Save Ani pars" writes it.
+    fn project(options: u32, keys: &[(&[u8], u32)]) -> Vec<u8> {
+        let mut out = Vec::new();
+        for v in [VERSION, 320, 240, 2, 0] {
+            out.extend(v.to_le_bytes());
+        }
+        let mut folder = [0u8; 256];
+        folder[0] = 6;
+        folder[1..7].copy_from_slice(b"C:\\ani");
+        out.extend(folder);
+        out.extend(options.to_le_bytes());
+        out.extend((keys.len() as i32).to_le_bytes());
+        for (params, frames) in keys {
+            for v in [*frames, 1500, 0] {
+                out.extend(v.to_le_bytes());
+            }
+            out.extend_from_slice(params);
+        }
+        for _ in keys {
+            // 2x1: blue then red, stored as BGRX
+            out.extend([2u32, 1].iter().flat_map(|v| v.to_le_bytes()));
+            out.extend([255, 0, 0, 0, 0, 0, 255, 0]);
+        }
+        out
+    }
+
+    #[test]
+    fn test_keyframes_and_settings() {
+        let bytes = project(1 | 4 | (1 << 3) | 256, &[(ABOX, 30), (QUAT_HYBRID, 0), (ABOX, 20)]);
+        let ani = parse(&bytes).unwrap();
+        assert_eq!((ani.width, ani.height, ani.image_scale), (320, 240, 2));
+        assert_eq!(ani.output_folder, "C:\\ani");
+        assert_eq!((ani.interpolation, ani.looped, ani.output_format), (Interpolation::Bezier, true, OutputFormat::Png));
+        assert!(!ani.overwrite && !ani.stereo);
+
+        assert_eq!(ani.keyframes.len(), 3);
+        assert_eq!({ ani.keyframes[1].file.header.is_julia }, 1);
+        assert_eq!((ani.keyframes[0].frames, ani.keyframes[0].preview_ms), (30, 1500));
+        assert_eq!(ani.keyframes[2].preview.rgba, [0, 0, 255, 255, 255, 0, 0, 255]);
+        assert_eq!(ani.keyframes[0].file.to_scene(), m3p::load(ABOX).unwrap());
+    }
+
+    #[test]
+    fn test_frame_positions() {
+        let looped = parse(&project(4, &[(ABOX, 10), (ABOX, 0), (ABOX, 5)])).unwrap();
+        assert_eq!(looped.frame_count(), 15);
+        assert_eq!(looped.keyframe_at(4), Some((0, 0.4)));
+        // The zero-frame key is passed over
+        assert_eq!(looped.keyframe_at(10), Some((2, 0.0)));
+        assert_eq!(looped.keyframe_at(15), None);
+
+        let open = parse(&project(0, &[(ABOX, 10), (ABOX, 5)])).unwrap();
+        assert_eq!(open.frame_count(), 11);
+        assert_eq!(open.keyframe_at(10), Some((1, 0.0)));
+    }
+
+    #[test]
+    fn test_rejects_damaged_projects() {
+        let bytes = project(0, &[(ABOX, 10)]);
+        assert_eq!(parse(&bytes[..bytes.len() - 1]).err(), Some(ParseError::Truncated));
+        let mut old = bytes.clone();
+        old[0] = 4;
+        assert_eq!(parse(&old).err(), Some(ParseError::Version(4)));
+        let mut empty = bytes.clone();
+        empty[280..284].copy_from_slice(&0i32.to_le_bytes());
+        assert_eq!(parse(&empty).err(), Some(ParseError::KeyframeCount(0)));
+        let mut key = bytes;
+        key[296] = 9;
+        assert_eq!(parse(&key).err(), Some(ParseError::Keyframe(0, m3p::ParseError::OldFormat(9))));
+    }
+}
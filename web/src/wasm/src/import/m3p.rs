@@ -0,0 +1,1004 @@
+//! Classic MB3D parameter files (.m3p).
+//!
+//! An .m3p file is a `MandHeader10` record (840 bytes, lighting included)
+//! followed by the hybrid formula block `HeaderCustomAddon` (1136 bytes, or
+//! 1016 bytes in files from before version 1.6). Files with a format id
+//! below 20 predate `MandHeader10` and are rejected.
+//!
+//! `M3pFile::to_scene` converts the records into the three wasm parameter
+//! buffers. The conversion is approximate where the engines differ:
+//! - MB3D's default lens maps pixels to view angles linearly; the ray
+//!   marcher uses a planar projection with the same vertical field of view.
+//! - Only the built-in formulas with a port in `formulas` are loaded;
+//!   custom .m3f formulas are skipped with a warning.
+//! - Lightmaps, background images and DE-combined hybrids are not supported.
+//!
+//! `save` goes the other way: it writes the buffers into a copy of a
+//! template file, so settings the buffers do not carry (MB3D's AO, DOF,
+//! lightmaps, custom formulas) survive a load/save cycle. A new scene uses
+//! `M3pFile::default()` as the template.
+
+use std::fmt;
+
+use crate::engine::camera::CameraRays;
+use crate::engine::cutting::{Cut, CutShape};
+use crate::engine::raymarcher;
+use crate::engine::types::{
+    f64_to_d7b, f64_to_short_float, HAFormula, HeaderCustomAddon, LightingParas9, MandHeader10, Vec3D,
+};
+use crate::formulas::{self, FormulaId};
+use crate::lighting::gradient::ColorGradient;
+use crate::lighting::mb3d::{self, mix, rgb_from_bytes};
+use crate::lighting::paint::{self, LightKind, PaintConfig, PaintView};
+use crate::math::math3d;
+
+/// Size of the `MandHeader10` record.
+pub const HEADER_LEN: usize = std::mem::size_of::<MandHeader10>();
+/// Size of the `HeaderCustomAddon` record.
+pub const ADDON_LEN: usize = std::mem::size_of::<HeaderCustomAddon>();
+/// Size of the formula block written before `HeaderCustomAddon` existed.
+const OLD_ADDON_LEN: usize = 1016;
+/// First format id using `MandHeader10`.
+pub const MIN_MAND_ID: i32 = 20;
+
+/// Why a buffer is not a loadable .m3p file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// Shorter than the header
+    Truncated(usize),
+    /// Not an MB3D parameter file
+    NotM3p,
+    /// Format id of a pre-`MandHeader10` file
+    OldFormat(i32),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Truncated(len) => write!(f, "{len} bytes is too short for an .m3p header ({HEADER_LEN} bytes)"),
+            ParseError::NotM3p => f.write_str("not an MB3D parameter file"),
+            ParseError::OldFormat(id) => write!(f, ".m3p format {id} is too old; resave it in Mandelbulb3D 1.7 or later"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Trailing-zero-padded name bytes as a string.
+fn name_from_bytes(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+fn rgb_to_bytes(c: (f64, f64, f64)) -> [u8; 3] {
+    [c.0, c.1, c.2].map(|v| (v * 255.0).round().clamp(0.0, 255.0) as u8)
+}
+
+/// `c` as `0x00BBGGRR`, keeping the top byte of `old`.
+fn color_to_u32(c: (f64, f64, f64), old: u32) -> u32 {
+    let [r, g, b] = rgb_to_bytes(c);
+    u32::from_le_bytes([r, g, b, (old >> 24) as u8])
+}
+
+fn same_color(a: (f64, f64, f64), b: (f64, f64, f64)) -> bool {
+    (a.0 - b.0).abs() < 1e-9 && (a.1 - b.1).abs() < 1e-9 && (a.2 - b.2).abs() < 1e-9
+}
+
+/// Up to `n` gradient stops as (position, color), padded with the last
+/// stop. Longer or procedural gradients are resampled evenly.
+fn gradient_stops(gradient: &ColorGradient, n: usize) -> Vec<(f64, (f64, f64, f64))> {
+    let flat = gradient.to_flat();
+    if flat.is_empty() || flat.len() / 4 > n {
+        return (0..n).map(|i| i as f64 / (n - 1) as f64).map(|t| (t, gradient.sample(t))).collect();
+    }
+    let mut stops: Vec<_> = flat.chunks_exact(4).map(|s| (s[0], (s[1], s[2], s[3]))).collect();
+    stops.resize(n, *stops.last().unwrap());
+    stops
+}
+
+fn gradient_position(t: f64) -> u16 {
+    (t * 32767.0).round().clamp(0.0, 32767.0) as u16
+}
+
+/// Light angles (LX, LY) for a view-space direction; inverse of the mapping
+/// in `mb3d::light_direction`.
+fn light_angles(local: &Vec3D) -> (f64, f64) {
+    let n = math3d::vec3d_normalized(local);
+    let (a, b) = (-n.x, -n.y);
+    // (sin LX, sin LY, cos LX · cos LY) is n scaled by k, with
+    // a²b²k⁴ - k² + 1 = 0; take the root with k = 1 when a or b is 0
+    let k = (2.0 / (1.0 + (1.0 - 4.0 * a * a * b * b).max(0.0).sqrt())).sqrt();
+    let lx = (k * a).clamp(-1.0, 1.0).asin();
+    let ly = (k * b).clamp(-1.0, 1.0).asin();
+    // A light behind the view plane needs cos LX < 0
+    (if n.z > 0.0 { std::f64::consts::PI - lx } else { lx }, ly)
+}
+
+fn vec_axes(v: &Vec3D) -> [f64; 3] {
+    [v.x, v.y, v.z]
+}
+
+/// Packed plain-data records of the file format.
+///
+/// # Safety
+///
+/// Implementors must be `repr(C, packed)` and built only from integers,
+/// floats and arrays or records of them: no padding bytes, and every bit
+/// pattern is a valid value.
+unsafe trait PlainRecord: Copy {}
+
+// SAFETY: repr(C, packed) records of integers, floats and byte arrays (see
+// the size assertions in engine::types)
+unsafe impl PlainRecord for MandHeader10 {}
+unsafe impl PlainRecord for HeaderCustomAddon {}
+unsafe impl PlainRecord for LightingParas9 {}
+
+/// Bytes of a packed plain-data record.
+fn record_bytes<T: PlainRecord>(record: &T) -> &[u8] {
+    // SAFETY: PlainRecord types have no padding, so every byte is initialized
+    unsafe { std::slice::from_raw_parts(record as *const T as *const u8, std::mem::size_of::<T>()) }
+}
+
+/// Read a plain-data record from the start of `bytes` (length checked by the caller).
+fn read_record<T: PlainRecord>(bytes: &[u8]) -> T {
+    assert!(bytes.len() >= std::mem::size_of::<T>());
+    // SAFETY: length checked; every bit pattern is a valid PlainRecord
+    unsafe { std::ptr::read_unaligned(bytes.as_ptr() as *const T) }
+}
+
+/// Convert the formula block written by MB3D before version 1.6.
+fn addon_from_old(bytes: &[u8]) -> HeaderCustomAddon {
+    let int = |i: usize| i32::from_le_bytes(bytes[i * 4..i * 4 + 4].try_into().unwrap());
+    let mut addon = HeaderCustomAddon { version: 16, ..Default::default() };
+    for (k, f) in addon.formulas.iter_mut().enumerate() {
+        f.it_count = int(2 + k);
+        f.fnr = int(8 + k);
+        f.option_count = 16;
+        let name = 56 + k * 32;
+        f.custom_fname.copy_from_slice(&bytes[name..name + 32]);
+        let values = 248 + k * 128;
+        let mut option_value = [0.0; 16];
+        for (j, v) in option_value.iter_mut().enumerate() {
+            *v = f64::from_le_bytes(bytes[values + j * 8..values + j * 8 + 8].try_into().unwrap());
+        }
+        f.option_value = option_value;
+    }
+    addon
+}
+
+/// Parsed .m3p records.
+#[derive(Clone, Copy)]
+pub struct M3pFile {
+    pub header: MandHeader10,
+    pub addon: HeaderCustomAddon,
+}
+
+/// The wasm parameter buffers for a loaded file.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Scene {
+    pub render_params: Vec<f64>,
+    pub formula_ids: Vec<u32>,
+    pub paint_params: Vec<f64>,
+    /// Settings that could not be carried over
+    pub warnings: Vec<String>,
+}
+
+/// Parse an .m3p byte buffer.
+pub fn parse(bytes: &[u8]) -> Result<M3pFile, ParseError> {
+    parse_records(bytes).map(|(file, _)| file)
+}
+
+/// Parse the records at the start of `bytes`, also returning how many
+/// bytes they took (just the header when the formula block is missing).
+pub(crate) fn parse_records(bytes: &[u8]) -> Result<(M3pFile, usize), ParseError> {
+    if bytes.len() < HEADER_LEN {
+        return Err(ParseError::Truncated(bytes.len()));
+    }
+    let header: MandHeader10 = read_record(bytes);
+    let id = header.mand_id;
+    if !(0..=250).contains(&id) {
+        return Err(ParseError::NotM3p);
+    }
+    if id < MIN_MAND_ID {
+        return Err(ParseError::OldFormat(id));
+    }
+    let rest = &bytes[HEADER_LEN..];
+    // Same test as MB3D's LoadHAddon: current blocks start with version 16..99
+    let (addon, addon_len) = match rest.first() {
+        Some(16..=99) if rest.len() >= ADDON_LEN => (read_record(rest), ADDON_LEN),
+        Some(_) if rest.len() >= OLD_ADDON_LEN => (addon_from_old(rest), OLD_ADDON_LEN),
+        _ => (HeaderCustomAddon::default(), 0),
+    };
+    Ok((M3pFile { header, addon }, HEADER_LEN + addon_len))
+}
+
+/// Map an MB3D formula slot to a ported formula and its parameters (in
+/// `FormulaId::create_with_params` order).
+fn map_formula(f: &HAFormula) -> Result<(FormulaId, Vec<f64>), String> {
+    let (v, fnr) = (f.option_value, f.fnr);
+    let name = name_from_bytes(&f.custom_fname);
+    let mapped = match fnr {
+        // Integer Power: power, Z multiplier
+        0 => Some((FormulaId::PowerNBulb, vec![v[0].round(), 0.0, v[1]])),
+        1 => Some((FormulaId::RealPower, vec![v[0]])),
+        2 => Some((FormulaId::QuaternionJulia, Vec::new())),
+        3 => Some((FormulaId::Tricorn, Vec::new())),
+        // Scale, Min R, Fold; MB3D's fixed radius is 1
+        4 => Some((FormulaId::AmazingBox, vec![v[0], v[2], v[1] * v[1], 1.0])),
+        5 => Some((FormulaId::Bulbox, Vec::new())),
+        // Power, Z multiplier, R fold
+        6 => Some((FormulaId::FoldingIntPow, vec![v[0], v[2]])),
+        9 => Some((FormulaId::AexionC, Vec::new())),
+        fnr if fnr >= 20 => match name.as_str() {
+            // Scale, Min R, Fold, rotation; folds are symmetric in MB3D
+            "Amazing Surf" => Some((FormulaId::AmazingSurf, vec![v[0], v[2], v[2]])),
+            "ABoxMod1" => Some((FormulaId::ABoxMod1, v[..7].to_vec())),
+            "ABoxMod2" => Some((FormulaId::ABoxMod2, v[..5].to_vec())),
+            "ASurfMod1" | "_ASurfMod1" => Some((FormulaId::ASurfMod1, v[..10].to_vec())),
+            "ASurfMod2" => Some((FormulaId::ASurfMod2, v[..9].to_vec())),
+            _ => None,
+        },
+        _ => None,
+    };
+    mapped.ok_or(if name.is_empty() { format!("formula #{fnr}") } else { name })
+}
+
+/// Option values MB3D gives a new slot of each formula this module maps
+/// (`GetHAddOnFromInternFormula` and the .m3f defaults).
+const MB3D_DEFAULTS: [(&str, &[f64]); 13] = [
+    ("Integer Power", &[8.0, -1.0]),
+    ("Real Power", &[8.0, -1.0]),
+    ("Quaternion", &[-1.0, 0.0]),
+    ("Tricorn", &[-2.0, 1.0]),
+    ("Amazing Box", &[2.0, 0.5, 1.0]),
+    ("Bulbox", &[2.0, 0.5, 1.0, 1.0, 2.0, 2.0]),
+    ("Folding Int Pow", &[2.0, -1.0, 2.0]),
+    ("Aexion C", &[8.0, 1.0, 1.0, 1.0, 8.0, 1.0]),
+    ("Amazing Surf", &[1.5, 0.5, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0]),
+    ("ABoxMod1", &[2.0, 0.0, 2.0, 0.0, 0.0, 0.0, 0.0]),
+    ("ABoxMod2", &[2.0, 0.5, 1.0, 1.5, 0.5]),
+    ("ASurfMod1", &[2.0, 0.0, 2.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0]),
+    ("ASurfMod2", &[2.0, 0.5, 1.0, 1.0, 0.5, 0.0, 0.0, 0.0, 1.0]),
+];
+
+/// Parameters of the ported formulas before any formula-params section
+/// (the defaults of `FormulaId::create`).
+fn default_params(id: FormulaId) -> &'static [f64] {
+    match id {
+        FormulaId::AmazingBox => &[2.0, 1.0, 0.25, 1.0],
+        FormulaId::AmazingSurf => &[1.5, 1.0, 1.0],
+        FormulaId::FoldingIntPow => &[2.0, 1.0],
+        FormulaId::RealPower => &[8.0],
+        FormulaId::ABoxMod1 => &[2.0, 0.0, 2.0, 0.0, 0.0, 0.0, 0.0, 1.0],
+        FormulaId::ABoxMod2 => &[2.0, 0.5, 1.0, 1.5, 0.5, 1.0],
+        FormulaId::ASurfMod1 => &[2.0, 0.0, 2.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+        FormulaId::ASurfMod2 => &[2.0, 0.5, 1.0, 1.0, 0.5, 0.0, 0.0, 0.0, 1.0],
+        FormulaId::PowerNBulb => &[8.0, 0.0, 1.0],
+        _ => &[],
+    }
+}
+
+/// Option values to set in an MB3D slot as (index, value); the other
+/// options keep their values.
+type OptionValues = Vec<(usize, f64)>;
+
+/// Inverse of `map_formula`: MB3D formula number, name and option values.
+fn unmap_formula(id: FormulaId, p: &[f64], warnings: &mut Vec<String>) -> Option<(i32, &'static str, OptionValues)> {
+    let all = |n: usize| p[..n].iter().copied().enumerate().collect();
+    Some(match id {
+        FormulaId::MandelbulbPower2 => (0, "Integer Power", vec![(0, 2.0), (1, 1.0)]),
+        FormulaId::MandelbulbPower8 => (0, "Integer Power", vec![(0, 8.0), (1, 1.0)]),
+        FormulaId::PowerNBulb => {
+            if p[1] != 0.0 {
+                warnings.push("Power-N Bulb: the cosine convention is saved as sine".into());
+            }
+            let fnr = if p[0].fract() == 0.0 { 0 } else { 1 };
+            (fnr, ["Integer Power", "Real Power"][fnr as usize], vec![(0, p[0]), (1, p[2])])
+        }
+        FormulaId::RealPower => (1, "Real Power", vec![(0, p[0])]),
+        FormulaId::QuaternionJulia => (2, "Quaternion", Vec::new()),
+        FormulaId::Tricorn => (3, "Tricorn", Vec::new()),
+        FormulaId::AmazingBox => {
+            if p[3] != 1.0 {
+                warnings.push("Amazing Box: MB3D's fixed radius is 1".into());
+            }
+            (4, "Amazing Box", vec![(0, p[0]), (1, p[2].max(0.0).sqrt()), (2, p[1])])
+        }
+        FormulaId::Bulbox => (5, "Bulbox", Vec::new()),
+        FormulaId::FoldingIntPow => (6, "Folding Int Pow", vec![(0, p[0]), (2, p[1])]),
+        FormulaId::AexionC => (9, "Aexion C", Vec::new()),
+        FormulaId::AmazingSurf => {
+            if p[1] != p[2] {
+                warnings.push("Amazing Surf: MB3D folds x and y by the same amount".into());
+            }
+            (20, "Amazing Surf", vec![(0, p[0]), (2, p[1])])
+        }
+        FormulaId::ABoxMod1 | FormulaId::ABoxMod2 | FormulaId::ASurfMod1 => {
+            // The trailing Z multiplier is a port extension
+            let (name, n) = match id {
+                FormulaId::ABoxMod1 => ("ABoxMod1", 7),
+                FormulaId::ABoxMod2 => ("ABoxMod2", 5),
+                _ => ("ASurfMod1", 10),
+            };
+            if p[n] != 1.0 {
+                warnings.push(format!("{name}: MB3D has no Z multiplier; it is saved as 1"));
+            }
+            (20, name, all(n))
+        }
+        FormulaId::ASurfMod2 => (20, "ASurfMod2", all(9)),
+        _ => return None,
+    })
+}
+
+impl M3pFile {
+    /// World size of a pixel at the view plane (MB3D's `CalcStepWidth`).
+    pub fn step_width(&self) -> f64 {
+        let h = &self.header;
+        2.1345 / ({ h.zoom } * { h.width }.max(1) as f64)
+    }
+
+    /// Tangent of half the vertical field of view.
+    fn half_fov_tan(&self) -> f64 {
+        ({ self.header.fov_y }.abs().min(179.0).to_radians() * 0.5).tan()
+    }
+
+    /// Distance from the camera to the view plane, where a pixel is `step_width` wide.
+    fn view_plane_offset(&self) -> f64 {
+        let h = &self.header;
+        if h.stereo_mode == 2 { 0.0 } else { self.step_width() * { h.height } as f64 * 0.5 / self.half_fov_tan().max(0.01) }
+    }
+
+    /// Camera position, ray basis and maximum ray length (MB3D's `CalcCamPos`).
+    pub fn camera(&self) -> (Vec3D, CameraRays, f64) {
+        let h = &self.header;
+        let rows = { h.vgrads };
+        let row = |i: usize| math3d::vec3d_normalized(&Vec3D { x: rows[i][0], y: rows[i][1], z: rows[i][2] });
+        let (right, down, forward) = (row(0), row(1), row(2));
+
+        let half = self.half_fov_tan();
+        let vp_off = self.view_plane_offset();
+        let mid = Vec3D { x: h.x_mid, y: h.y_mid, z: h.z_mid };
+        let pos = math3d::vec3d_add(&mid, &math3d::vec3d_scale(&forward, { h.z_start } - { h.z_mid } - vp_off));
+
+        let aspect = { h.width }.max(1) as f64 / { h.height }.max(1) as f64;
+        let rays = CameraRays {
+            dir_base: forward,
+            dx: math3d::vec3d_scale(&right, half * aspect),
+            dy: math3d::vec3d_scale(&down, half),
+        };
+        (pos, rays, { h.z_end } - { h.z_start } + vp_off)
+    }
+
+    /// render_params buffer (see `raymarcher::params_from_buffer`).
+    pub fn render_params(&self) -> Vec<f64> {
+        let h = &self.header;
+        let (pos, rays, max_ray_length) = self.camera();
+        let step = self.step_width();
+        let de_stop = { h.de_stop } as f64;
+        let julia = h.is_julia != 0;
+
+        let mut p = vec![{ h.width } as f64, { h.height } as f64, pos.x, pos.y, pos.z];
+        p.extend(rays.to_array());
+        p.extend([
+            de_stop * step,
+            { h.z_step_div } as f64,
+            max_ray_length,
+            { h.iterations } as f64,
+            { h.r_stop } * { h.r_stop },
+            // MB3D grows the DE stop with distance when this is set
+            if h.vary_de_stop_on_fov != 0 { de_stop } else { 0.0 },
+            julia as u8 as f64,
+            { h.jx },
+            { h.jy },
+            { h.jz },
+            0.0,
+            0.0,
+            0.0,
+            0.0,
+            0.0,
+            h.steps_after_de_stop as f64,
+        ]);
+
+        // Slots 30..41 at their defaults, so the cut table can follow
+        p.extend_from_slice(&raymarcher::default_params_buffer()[p.len()..raymarcher::CUTS_OFFSET]);
+
+        // MB3D removes the union of "beyond the cut" half-spaces: keep a box instead
+        let axes = h.cut_option & 7;
+        if axes == 0 {
+            p.push(0.0);
+        } else {
+            const FAR: f64 = 1e30;
+            let (mut min, mut max) = ([-FAR; 3], [FAR; 3]);
+            for (axis, at) in [{ h.cut_x }, { h.cut_y }, { h.cut_z }].into_iter().enumerate() {
+                if axes & (1 << axis) == 0 {
+                    continue;
+                }
+                if h.cut_option & (16 << axis) == 0 { max[axis] = at } else { min[axis] = at }
+            }
+            p.extend([1.0, 2.0, 0.0]);
+            p.extend(min);
+            p.extend(max);
+        }
+        p
+    }
+
+    /// formula_ids buffer with a formula-params section per slot. Slots
+    /// without a port are skipped and named in `warnings`.
+    pub fn formula_ids(&self, warnings: &mut Vec<String>) -> Vec<u32> {
+        let addon = &self.addon;
+        // Interpolated hybrids only use the first two slots
+        let used = if addon.options1 & 3 == 1 { 2 } else { addon.formulas.len() };
+        let mut slots = Vec::new();
+        for f in addon.formulas[..used].iter().filter(|f| f.it_count > 0) {
+            match map_formula(f) {
+                Ok((id, params)) => slots.push((id, f.it_count as u32, params)),
+                Err(name) => warnings.push(format!("formula \"{name}\" is not supported and was skipped")),
+            }
+        }
+        if slots.is_empty() {
+            warnings.push("no supported formula; using Mandelbulb Power 8".into());
+            slots.push((FormulaId::MandelbulbPower8, 1, Vec::new()));
+        }
+
+        let mode = match addon.options1 & 3 {
+            1 => 1,
+            2 => {
+                warnings.push("DE-combined hybrids are not supported; loaded as alternating".into());
+                0
+            }
+            _ => 0,
+        };
+        formulas::FormulaIds {
+            slots: slots.iter().map(|(id, iters, _)| (*id, *iters)).collect(),
+            mode: Some(mode),
+            sections: slots
+                .iter()
+                .enumerate()
+                .filter(|(_, s)| !s.2.is_empty())
+                .map(|(slot, (_, _, params))| formulas::ParamSection {
+                    tag: crate::SECTION_FORMULA_PARAMS,
+                    slot: slot as u32,
+                    values: params.clone(),
+                })
+                .collect(),
+        }
+        .encode()
+    }
+
+    /// The view the lighting refers to.
+    fn paint_view(&self) -> PaintView {
+        let (camera_pos, rays, max_ray_length) = self.camera();
+        PaintView { camera_pos, ray_dir_base: rays.dir_base, ray_dx: rays.dx, ray_dy: rays.dy, max_ray_length }
+    }
+
+    /// paint_params buffer (see `paint::paint_config_from_buffer`) for the
+    /// settings `mb3d::paint_config` decodes.
+    pub fn paint_params(&self, warnings: &mut Vec<String>) -> Vec<f64> {
+        let light = &self.header.light;
+        let view = self.paint_view();
+        let config = mb3d::paint_config(light, &view);
+
+        let mut p = vec![config.lights.len() as f64];
+        let mut sections = Vec::new();
+        for (i, (l, c)) in { light.lights }.iter().zip(&config.lights).enumerate() {
+            let d = c.direction;
+            p.extend([d.x, d.y, d.z, c.color.0, c.color.1, c.color.2, c.amplitude, c.specular_size, c.specular_intensity]);
+            if l.option & 3 == 2 {
+                warnings.push(format!("light {}: lightmaps are not supported; switched off", i + 1));
+            }
+            if !c.enabled {
+                sections.extend([paint::SECTION_LIGHT_ENABLED as f64, 2.0, i as f64, 0.0]);
+            }
+            if c.kind.is_positional() {
+                let pos = c.position;
+                sections.extend([paint::SECTION_LIGHT_SOURCE as f64, 5.0, i as f64, c.kind as u8 as f64, pos.x, pos.y, pos.z]);
+            }
+        }
+
+        let (a, f, b) = (config.ambient_color, config.fog_color, config.bg_color);
+        p.extend([a.0, a.1, a.2, config.ambient_intensity]);
+        p.extend([config.fog_density, f.0, f.1, f.2]);
+        p.extend([b.0, b.1, b.2]);
+        p.extend([config.view_dir.x, config.view_dir.y, config.view_dir.z]);
+        p.push(config.ao_strength);
+        p.push(config.gradient.stops.len() as f64);
+        p.extend(config.gradient.to_flat());
+
+        let interior = config.interior_gradient.to_flat();
+        sections.extend([paint::SECTION_INTERIOR_GRADIENT as f64, 1.0 + interior.len() as f64, (interior.len() / 4) as f64]);
+        sections.extend(interior);
+        sections.extend([paint::SECTION_VIEW as f64, 13.0, view.camera_pos.x, view.camera_pos.y, view.camera_pos.z]);
+        sections.extend(CameraRays { dir_base: view.ray_dir_base, dx: view.ray_dx, dy: view.ray_dy }.to_array());
+        sections.push(view.max_ray_length);
+
+        if light.bg_bmp[0] != 0 {
+            warnings.push(format!("background image \"{}\" is not loaded", name_from_bytes(&light.bg_bmp)));
+        }
+        p.extend(sections);
+        p
+    }
+
+    /// All three buffers plus the conversion warnings.
+    pub fn to_scene(&self) -> Scene {
+        let mut warnings = Vec::new();
+        if self.header.planar_optic == 2 {
+            warnings.push("spherical panorama projection is not supported; rendered as planar".into());
+        }
+        let formula_ids = self.formula_ids(&mut warnings);
+        let paint_params = self.paint_params(&mut warnings);
+        Scene { render_params: self.render_params(), formula_ids, paint_params, warnings }
+    }
+
+    /// The records as file bytes (inverse of `parse`).
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(HEADER_LEN + ADDON_LEN);
+        out.extend_from_slice(record_bytes(&self.header));
+        out.extend_from_slice(record_bytes(&self.addon));
+        out
+    }
+
+    /// Copy with the scene replaced by the wasm buffers (inverse of
+    /// `to_scene`). Settings MB3D cannot store are named in `warnings`.
+    pub fn with_scene(
+        &self,
+        render_params: &[f64],
+        formula_ids: &[u32],
+        paint_params: &[f64],
+        warnings: &mut Vec<String>,
+    ) -> M3pFile {
+        let mut file = *self;
+        file.set_render_params(render_params, warnings);
+        file.set_formulas(formula_ids, warnings);
+        // After the camera: view-relative lights are stored against it
+        file.set_paint(&paint::paint_config_from_buffer(paint_params), warnings);
+        file
+    }
+
+    fn set_render_params(&mut self, buffer: &[f64], warnings: &mut Vec<String>) {
+        let p = raymarcher::params_from_buffer(buffer);
+        // Kept from the template: how far the view plane sits in front of
+        // the mid point, and the zoom unless the DE stop sets it
+        let offset = { self.header.z_start } - { self.header.z_mid };
+        let h = &mut self.header;
+        h.width = p.width as i32;
+        h.height = p.height as i32;
+        h.iterations = p.max_iterations as i32;
+        h.r_stop = p.bailout.max(0.0).sqrt();
+        h.z_step_div = p.step_width as f32;
+        h.steps_after_de_stop = p.bin_search_steps.min(255) as u8;
+        match raymarcher::julia_from_buffer(buffer) {
+            Some(c) => (h.is_julia, h.jx, h.jy, h.jz) = (1, c.x, c.y, c.z),
+            None => h.is_julia = 0,
+        }
+        if h.planar_optic == 2 {
+            h.planar_optic = 1;
+        }
+        // A distance-scaled DE stop is stored in pixels at the view plane:
+        // pick the zoom that makes both stops match
+        if p.cone_scale > 0.0 && p.de_stop > 0.0 {
+            h.zoom = 2.1345 * p.cone_scale / (p.de_stop * p.width.max(1) as f64);
+        }
+        let half = math3d::vec3d_length(&p.ray_dy) / math3d::vec3d_length(&p.ray_dir_base).max(1e-300);
+        h.fov_y = (2.0 * half.atan()).to_degrees();
+
+        let step = self.step_width();
+        let vp_off = self.view_plane_offset();
+        let forward = math3d::vec3d_normalized(&p.ray_dir_base);
+        let mid = math3d::vec3d_sub(&p.camera_pos, &math3d::vec3d_scale(&forward, offset - vp_off));
+        let h = &mut self.header;
+        h.step_width = step;
+        h.vgrads = [p.ray_dx, p.ray_dy, p.ray_dir_base]
+            .map(|v| vec_axes(&math3d::vec3d_scale(&math3d::vec3d_normalized(&v), step)));
+        (h.x_mid, h.y_mid, h.z_mid) = (mid.x, mid.y, mid.z);
+        h.z_start = mid.z + offset;
+        h.z_end = { h.z_start } + p.max_ray_length - vp_off;
+        if p.cone_scale > 0.0 {
+            (h.vary_de_stop_on_fov, h.de_stop) = (1, p.cone_scale as f32);
+        } else {
+            (h.vary_de_stop_on_fov, h.de_stop) = (0, (p.de_stop / step) as f32);
+        }
+        self.set_cuts(&p.cuts, warnings);
+    }
+
+    /// MB3D cuts away everything beyond a position on up to three axes, so
+    /// only keep-inside boxes and axis-aligned planes can be saved.
+    fn set_cuts(&mut self, cuts: &[Cut], warnings: &mut Vec<String>) {
+        const FAR: f64 = 1e29;
+        // (axis, position, remove below)
+        let mut wanted = Vec::new();
+        for cut in cuts {
+            match cut.shape {
+                CutShape::Box { min, max } if !cut.removes_inside => {
+                    for (axis, (lo, hi)) in vec_axes(&min).into_iter().zip(vec_axes(&max)).enumerate() {
+                        if hi < FAR {
+                            wanted.push((axis, hi, false));
+                        }
+                        if lo > -FAR {
+                            wanted.push((axis, lo, true));
+                        }
+                    }
+                }
+                CutShape::Plane { normal, d } => match vec_axes(&normal).iter().position(|n| n.abs() > 1.0 - 1e-9) {
+                    // Inside is n·p < d: for +axis that keeps p < d
+                    Some(axis) => {
+                        let positive = vec_axes(&normal)[axis] > 0.0;
+                        wanted.push((axis, if positive { d } else { -d }, positive == cut.removes_inside));
+                    }
+                    None => warnings.push("only axis-aligned cutting planes can be saved".into()),
+                },
+                _ => warnings.push("sphere cuts and remove-inside boxes cannot be saved".into()),
+            }
+        }
+
+        let h = &mut self.header;
+        let mut option = h.cut_option & !0x77;
+        let mut at = [h.cut_x, h.cut_y, h.cut_z];
+        for (axis, value, below) in wanted {
+            if option & (1 << axis) != 0 {
+                let name = ["x", "y", "z"][axis];
+                warnings.push(format!("only one cut per axis can be saved; the second {name} cut was dropped"));
+                continue;
+            }
+            option |= (1 << axis) | if below { 16 << axis } else { 0 };
+            at[axis] = value;
+        }
+        h.cut_option = option;
+        (h.cut_x, h.cut_y, h.cut_z) = (at[0], at[1], at[2]);
+    }
+
+    fn set_formulas(&mut self, ids: &[u32], warnings: &mut Vec<String>) {
+        let decoded = formulas::FormulaIds::decode(ids);
+        let mut slots: Vec<_> =
+            decoded.slots.iter().map(|&(id, iters)| (id, iters, default_params(id).to_vec())).collect();
+        if ids.is_empty() {
+            slots.push((FormulaId::MandelbulbPower8, 1, Vec::new()));
+        }
+        let mode = decoded.mode.unwrap_or(0);
+        for section in decoded.sections {
+            match (section.tag, slots.get_mut(section.slot as usize)) {
+                (crate::SECTION_FORMULA_PARAMS, Some((_, _, params))) => {
+                    for (p, v) in params.iter_mut().zip(&section.values) {
+                        *p = *v;
+                    }
+                }
+                (crate::SECTION_FORMULA_PARAMS, None) | (crate::SECTION_LAYOUT_VERSION, _) => {}
+                (tag, _) => warnings.push(format!("formula section {tag} has no .m3p equivalent and was dropped")),
+            }
+        }
+
+        let mut template = self.addon.formulas;
+        let mut out = Vec::new();
+        for (id, iters, params) in slots.iter().filter(|s| s.0 != FormulaId::None && s.1 > 0) {
+            let Some((fnr, name, values)) = unmap_formula(*id, params, warnings) else {
+                warnings.push(format!("formula \"{}\" has no MB3D equivalent and was skipped", id.info().name));
+                continue;
+            };
+            // Reuse the template slot when it holds the same formula, so
+            // options this renderer does not model keep their values
+            let reused = template.get(out.len()).copied();
+            let mut f = reused.filter(|t| { t.fnr } == fnr && name_from_bytes(&t.custom_fname) == name).unwrap_or_else(|| {
+                let defaults = MB3D_DEFAULTS.iter().find(|d| d.0 == name).map_or(&[][..], |d| d.1);
+                let mut f = HAFormula { fnr, option_count: defaults.len() as i32, ..Default::default() };
+                f.custom_fname[..name.len()].copy_from_slice(name.as_bytes());
+                let mut option_value = [0.0; 16];
+                option_value[..defaults.len()].copy_from_slice(defaults);
+                f.option_value = option_value;
+                f
+            });
+            let mut option_value = f.option_value;
+            for (i, v) in values {
+                option_value[i] = v;
+            }
+            f.option_value = option_value;
+            f.it_count = *iters as i32;
+            out.push(f);
+        }
+        if out.len() > 2 && mode == 1 {
+            warnings.push("MB3D interpolates between the first two formulas only".into());
+        }
+        if mode == 2 {
+            warnings.push("4D hybrids have no .m3p equivalent; saved as alternating".into());
+        }
+
+        let addon = &mut self.addon;
+        addon.f_count = out.len() as u8;
+        addon.options1 = (addon.options1 & !3) | if mode == 1 { 1 } else { 0 };
+        // Leftover template slots are switched off, as MB3D leaves them
+        for f in template[out.len()..].iter_mut() {
+            f.it_count = 0;
+        }
+        template[..out.len()].copy_from_slice(&out);
+        addon.formulas = template;
+    }
+
+    fn set_paint(&mut self, config: &PaintConfig, warnings: &mut Vec<String>) {
+        let (_, rays, _) = self.camera();
+        let right = math3d::vec3d_normalized(&rays.dx);
+        let down = math3d::vec3d_normalized(&rays.dy);
+        let mut light = self.header.light;
+
+        let mut lights = { light.lights };
+        for (i, l) in lights.iter_mut().enumerate() {
+            let Some(c) = config.lights.get(i) else {
+                if l.option & 3 == 0 {
+                    l.option |= 1;
+                }
+                continue;
+            };
+            // Lightmap lights stay as they are
+            if l.option & 3 != 2 {
+                l.option = (l.option & !3) | if c.enabled { 0 } else { 1 };
+            }
+            l.color = rgb_to_bytes(c.color);
+            l.amp = f64_to_short_float(c.amplitude);
+            l.function = (l.function & !7) | (c.specular_size / 8.0).max(1.0).log2().round().min(7.0) as u8;
+            if c.kind != LightKind::Directional {
+                if c.kind == LightKind::Spot {
+                    warnings.push(format!("light {}: spot lights are saved as point lights", i + 1));
+                }
+                l.option |= 4;
+                (l.x_pos, l.y_pos, l.z_pos) = (f64_to_d7b(c.position.x), f64_to_d7b(c.position.y), f64_to_d7b(c.position.z));
+                continue;
+            }
+            // Unchanged directions keep their stored angles exactly
+            let unchanged = l.option & 4 == 0 && math3d::vec3d_dot(&mb3d::light_direction(l, &rays), &c.direction) > 1.0 - 1e-12;
+            l.option &= !4;
+            if !unchanged {
+                let d = c.direction;
+                let local = if l.option & 0x20 != 0 {
+                    d
+                } else {
+                    let along = |axis: &Vec3D| math3d::vec3d_dot(&d, axis);
+                    Vec3D { x: along(&right), y: along(&down), z: along(&rays.dir_base) }
+                };
+                let (lx, ly) = light_angles(&local);
+                (l.x_pos, l.y_pos) = (f64_to_d7b(lx), f64_to_d7b(ly));
+            }
+        }
+        light.lights = lights;
+
+        if !same_color(mix(rgb_from_bytes(light.amb_col), rgb_from_bytes(light.amb_col2)), config.ambient_color) {
+            light.amb_col = rgb_to_bytes(config.ambient_color);
+            light.amb_col2 = light.amb_col;
+        }
+        let mut tb_pos = light.tb_pos;
+        tb_pos[5] = (tb_pos[5] & !0xFFF) | (config.ambient_intensity * 90.0).round().clamp(0.0, 4095.0) as i32;
+        light.tb_pos = tb_pos;
+        let depth = mix(rgb_from_bytes(light.depth_col), rgb_from_bytes(light.depth_col2));
+        if !same_color(depth, config.fog_color) || !same_color(depth, config.bg_color) {
+            let far = |fog: f64, bg: f64| 2.0 * bg - fog;
+            let (f, b) = (config.fog_color, config.bg_color);
+            light.depth_col = rgb_to_bytes(f);
+            light.depth_col2 = rgb_to_bytes((far(f.0, b.0), far(f.1, b.1), far(f.2, b.2)));
+        }
+        if config.fog_density > 0.0 {
+            warnings.push("fog density has no .m3p equivalent".into());
+        }
+
+        let mut lcols = { light.lcols };
+        for (c, (t, color)) in lcols.iter_mut().zip(gradient_stops(&config.gradient, 10)) {
+            c.position = gradient_position(t);
+            c.color_dif = color_to_u32(color, c.color_dif);
+        }
+        light.lcols = lcols;
+        let mut icols = { light.icols };
+        for (c, (t, color)) in icols.iter_mut().zip(gradient_stops(&config.interior_gradient, 4)) {
+            c.position = gradient_position(t);
+            c.color = color_to_u32(color, c.color);
+        }
+        light.icols = icols;
+        self.header.light = light;
+    }
+}
+
+impl Default for M3pFile {
+    /// Template for new files: MB3D 1.9's defaults for everything the
+    /// wasm buffers do not set.
+    fn default() -> Self {
+        let header = MandHeader10 {
+            mand_id: 44,
+            options: 1,
+            zoom: 1.0,
+            z_start: -2.0,
+            z_end: 30.0,
+            minimum_iterations: 1,
+            calc_amb_shadow_automatic: 21,
+            normals_on_de: 1,
+            image_scale: 1,
+            raystep_limiter: 1.0,
+            m3d_version: 1.89,
+            ..Default::default()
+        };
+        let empty = HAFormula { fnr: -1, ..Default::default() };
+        let addon = HeaderCustomAddon { version: 16, formulas: [empty; 6], ..Default::default() };
+        M3pFile { header, addon }
+    }
+}
+
+/// Parse and convert an .m3p byte buffer.
+pub fn load(bytes: &[u8]) -> Result<Scene, ParseError> {
+    parse(bytes).map(|file| file.to_scene())
+}
+
+/// Write the buffers as .m3p bytes, taking everything they do not carry
+/// from `template`. Also returns the settings that could not be saved.
+pub fn save(template: &M3pFile, render_params: &[f64], formula_ids: &[u32], paint_params: &[f64]) -> (Vec<u8>, Vec<String>) {
+    let mut warnings = Vec::new();
+    let file = template.with_scene(render_params, formula_ids, paint_params, &mut warnings);
+    (file.to_bytes(), warnings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::raymarcher;
+    use crate::engine::types::{d7b_to_f64, short_float_to_f64};
+    use crate::formulas;
+
+    const ABOX: &[u8] = include_bytes!("../../../../../M3Parameter/ABoxScale2Start.m3p");
+    const QUAT_HYBRID: &[u8] = include_bytes!("../../../../../M3Parameter/QuatP4hybridJulia.m3p");
+
+    #[test]
+    fn test_packed_numbers() {
+        let packed = f64_to_d7b(0.9424);
+        assert_eq!(packed, 0.9424f64.to_le_bytes()[1..]);
+        assert!((d7b_to_f64(&packed) - 0.9424).abs() < 1e-12, "low mantissa byte dropped");
+        assert_eq!(short_float_to_f64([10, 0]), 1.0);
+        assert_eq!(short_float_to_f64([-25, 2]), -250.0);
+        for (v, sf) in [(1.0, [10, 0]), (-250.0, [-25, 2]), (0.05, [50, -2]), (0.0, [0, 0])] {
+            assert_eq!(f64_to_short_float(v), sf, "{v}");
+        }
+
+        // Light angles survive the trip through a view-space direction
+        let (lx, ly) = (0.4f64, -1.1f64);
+        for lx in [lx, std::f64::consts::PI - lx] {
+            let local = Vec3D { x: -lx.sin(), y: -ly.sin(), z: -lx.cos() * ly.cos() };
+            let (ax, ay) = light_angles(&local);
+            assert!((ax - lx).abs() < 1e-12 && (ay - ly).abs() < 1e-12, "{ax} {ay}");
+        }
+    }
+
+    #[test]
+    fn test_header_converts_to_render_params() {
+        let file = parse(ABOX).unwrap();
+        assert_eq!({ file.header.width }, 400);
+        assert!((file.step_width() - { file.header.step_width }).abs() < 1e-12);
+
+        let scene = file.to_scene();
+        let params = raymarcher::params_from_buffer(&scene.render_params);
+        assert_eq!((params.width, params.height, params.max_iterations), (400, 400, 60));
+        assert_eq!(params.bailout, 1024.0 * 1024.0);
+        assert!((params.de_stop - 0.8 * file.step_width()).abs() < 1e-9);
+        assert_eq!(params.bin_search_steps, 4);
+        assert!(params.cuts.is_empty());
+        // Rows of hVGrads are the camera axes: forward matches the center ray
+        let dir = raymarcher::pixel_direction(&params, 200.0, 200.0);
+        let fwd = { file.header.vgrads }[2];
+        let cos = math3d::vec3d_dot(&dir, &math3d::vec3d_normalized(&Vec3D { x: fwd[0], y: fwd[1], z: fwd[2] }));
+        assert!(cos > 1.0 - 1e-12);
+        // Vertical field of view is 30 degrees
+        let top = raymarcher::pixel_direction(&params, 200.0, 0.0);
+        assert!((math3d::vec3d_dot(&dir, &top).acos().to_degrees() - 15.0).abs() < 1e-9);
+        assert!(scene.warnings.is_empty(), "{:?}", scene.warnings);
+    }
+
+    #[test]
+    fn test_addon_converts_to_hybrid() {
+        let scene = load(QUAT_HYBRID).unwrap();
+        // Quaternion ×1, Integer Power 4 ×2; the zero-count Amazing Box slot is unused
+        assert_eq!(&scene.formula_ids[..6], &[2, 5, 1, 14, 2, 0]);
+        let sections = formulas::parse_param_sections(&scene.formula_ids[6..]);
+        assert_eq!(sections.len(), 1);
+        assert_eq!((sections[0].slot, sections[0].values.as_slice()), (1, &[4.0, 0.0, -1.0][..]));
+        assert_eq!(raymarcher::julia_from_buffer(&scene.render_params), Some(Vec3D { x: -1.0, y: 0.0, z: 0.0 }));
+
+        let abox = load(ABOX).unwrap();
+        let sections = formulas::parse_param_sections(&abox.formula_ids[4..]);
+        assert_eq!(&abox.formula_ids[..4], &[1, 3, 1, 0]);
+        assert_eq!(sections[0].values, vec![2.0, 1.0, 0.25, 1.0]);
+    }
+
+    #[test]
+    fn test_lighting_converts_to_paint_config() {
+        let file = parse(ABOX).unwrap();
+        let scene = file.to_scene();
+        let config = paint::paint_config_from_buffer(&scene.paint_params);
+        assert_eq!(config.lights.len(), 6);
+        let enabled: Vec<_> = config.lights.iter().map(|l| l.enabled).collect();
+        assert_eq!(enabled, [true, true, false, false, false, false]);
+        let key = &config.lights[0];
+        assert_eq!((key.color, key.amplitude, key.specular_size), ((1.0, 1.0, 1.0), 1.0, 16.0));
+        assert!((math3d::vec3d_length(&key.direction) - 1.0).abs() < 1e-12);
+        assert!((config.ambient_intensity - 30.0 / 90.0).abs() < 1e-12);
+        // First surface color 0x1D85F8 is orange
+        let c = config.gradient.sample(0.0);
+        assert!((c.0 - 248.0 / 255.0).abs() < 1e-9 && (c.2 - 29.0 / 255.0).abs() < 1e-9, "{c:?}");
+        assert!(config.view.is_some());
+    }
+
+    #[test]
+    fn test_records_round_trip_exactly() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/../../../M3Parameter");
+        let mut checked = 0;
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let bytes = std::fs::read(entry.unwrap().path()).unwrap();
+            // Files with the pre-1.6 formula block are saved in the current layout
+            if bytes.len() != HEADER_LEN + ADDON_LEN {
+                continue;
+            }
+            assert_eq!(parse(&bytes).unwrap().to_bytes(), bytes);
+            checked += 1;
+        }
+        assert!(checked > 50, "{checked}");
+    }
+
+    #[test]
+    fn test_saved_scene_reloads_unchanged() {
+        let close = |a: &[f64], b: &[f64]| {
+            a.len() == b.len() && a.iter().zip(b).all(|(x, y)| (x - y).abs() <= 1e-9 * x.abs().max(1.0))
+        };
+        for bytes in [ABOX, QUAT_HYBRID] {
+            let file = parse(bytes).unwrap();
+            let scene = file.to_scene();
+            let (saved, warnings) = save(&file, &scene.render_params, &scene.formula_ids, &scene.paint_params);
+            assert!(warnings.is_empty(), "{warnings:?}");
+            let reloaded = load(&saved).unwrap();
+            assert!(close(&reloaded.render_params, &scene.render_params), "{:?}", reloaded.render_params);
+            assert_eq!(reloaded.formula_ids, scene.formula_ids);
+            assert!(close(&reloaded.paint_params, &scene.paint_params));
+            // Lighting is written back bit for bit
+            let lighting = parse(&saved).unwrap().header.light;
+            assert_eq!(record_bytes(&lighting), record_bytes(&file.header.light));
+        }
+        let file = parse(ABOX).unwrap();
+        let scene = file.to_scene();
+        let (saved, _) = save(&file, &scene.render_params, &scene.formula_ids, &scene.paint_params);
+        assert_eq!(saved[HEADER_LEN..], ABOX[HEADER_LEN..]);
+    }
+
+    #[test]
+    fn test_new_file_from_buffers() {
+        let scene = load(ABOX).unwrap();
+        let mut render = scene.render_params[..42].to_vec();
+        // Keep z < 1.5, and x > -0.25 via a plane with an outward -x normal
+        render.extend([2.0, 2.0, 0.0, -1e30, -1e30, -1e30, 1e30, 1e30, 1.5]);
+        render.extend([0.0, 0.0, -1.0, 0.0, 0.0, 0.25, 0.0, 0.0]);
+        // Power-N Bulb power 5 ×2, Amazing Box at its defaults ×1
+        let mut ids = vec![2, 14, 2, 3, 1, 0, crate::SECTION_FORMULA_PARAMS, 0, 3];
+        for v in [5.0f64, 0.0, 1.0] {
+            ids.extend([v.to_bits() as u32, (v.to_bits() >> 32) as u32]);
+        }
+
+        let (bytes, warnings) = save(&M3pFile::default(), &render, &ids, &scene.paint_params);
+        assert!(warnings.is_empty(), "{warnings:?}");
+        let file = parse(&bytes).unwrap();
+        let h = file.header;
+        assert_eq!(({ h.mand_id }, h.cut_option, { h.cut_x }, { h.cut_z }), (44, 0x15, -0.25, 1.5));
+        let slots = file.addon.formulas;
+        assert_eq!(({ slots[0].fnr }, { slots[0].it_count }, { slots[0].option_value }[..2].to_vec()), (0, 2, vec![5.0, 1.0]));
+        assert_eq!(name_from_bytes(&slots[1].custom_fname), "Amazing Box");
+        assert_eq!({ slots[1].option_value }[..3], [2.0, 0.5, 1.0]);
+        assert_eq!(({ slots[2].fnr }, file.addon.f_count), (-1, 2));
+
+        let reloaded = file.to_scene();
+        let (a, b) = (raymarcher::params_from_buffer(&reloaded.render_params), raymarcher::params_from_buffer(&render));
+        assert!(math3d::vec3d_length(&math3d::vec3d_sub(&a.camera_pos, &b.camera_pos)) < 1e-9);
+        assert!(math3d::vec3d_length(&math3d::vec3d_sub(&a.ray_dx, &b.ray_dx)) < 1e-12);
+        assert!((a.de_stop - b.de_stop).abs() < 1e-9 && (a.max_ray_length - b.max_ray_length).abs() < 1e-9);
+        let pa = paint::paint_config_from_buffer(&reloaded.paint_params);
+        let pb = paint::paint_config_from_buffer(&scene.paint_params);
+        for (la, lb) in pa.lights.iter().zip(&pb.lights) {
+            assert!(math3d::vec3d_dot(&la.direction, &lb.direction) > 1.0 - 1e-12);
+            assert_eq!((la.enabled, la.color, la.specular_size), (lb.enabled, lb.color, lb.specular_size));
+        }
+    }
+
+    #[test]
+    fn test_rejects_other_files() {
+        assert_eq!(parse(&ABOX[..100]).err(), Some(ParseError::Truncated(100)));
+        let mut old = ABOX.to_vec();
+        old[0] = 9;
+        assert_eq!(parse(&old).err(), Some(ParseError::OldFormat(9)));
+        assert_eq!(parse(&[0xff; HEADER_LEN]).err(), Some(ParseError::NotM3p));
+    }
+}
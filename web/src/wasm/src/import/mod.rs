@@ -0,0 +1,4 @@
+//! Import — readers for classic Mandelbulb3D files.
+
+pub mod m3a;
+pub mod m3p;
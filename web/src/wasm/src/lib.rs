@@ -1,8 +1,14 @@
+// Module headers are written as `///` comments on their first item.
+#![allow(clippy::empty_line_after_doc_comments)]
+
 use wasm_bindgen::prelude::*;
 
 pub mod engine;
+pub mod export;
 pub mod formulas;
+pub mod import;
 pub mod lighting;
+pub mod log;
 pub mod math;
 
 /// Initialize the WASM module (call once from JS).
@@ -18,8 +24,14 @@ pub fn init() {
 ///
 /// `render_params` — Float64Array of render parameters (see RenderParams layout)
 /// `formula_ids` — Uint32Array of [num_formulas, id1, iters1, id2, iters2, ..., hybrid_mode]
-/// `gbuffer` — Uint8Array view into SharedArrayBuffer (width * height * 18 bytes)
+/// `gbuffer` — Uint8Array view into SharedArrayBuffer (width * height * 18
+///   bytes, or 34 with the extended G-buffer format flag in `render_params`)
 /// `worker_id` / `worker_count` — interleaved scanline assignment
+///
+/// Like every entry point taking a G-buffer, throws if a buffer is too short
+/// for the image or is not a whole number of records (see `engine::gbuffer`).
+///
+/// Deprecated in favour of `render_scene_json`; warns once.
 #[wasm_bindgen]
 pub fn render_scanlines(
     render_params: &[f64],
@@ -27,138 +39,2025 @@ pub fn render_scanlines(
     gbuffer: &mut [u8],
     worker_id: u32,
     worker_count: u32,
-) -> u32 {
+) -> Result<u32, JsError> {
+    log::deprecated("render_scanlines", "render_scene_json");
+    scanlines_from_buffers(render_params, formula_ids, gbuffer, worker_id, worker_count)
+}
+
+/// `render_scanlines` without the deprecation warning, shared with `render_scene_json`.
+fn scanlines_from_buffers(
+    render_params: &[f64],
+    formula_ids: &[u32],
+    gbuffer: &mut [u8],
+    worker_id: u32,
+    worker_count: u32,
+) -> Result<u32, JsError> {
     // Parse render parameters
     let params = engine::raymarcher::params_from_buffer(render_params);
 
     // Build formula from IDs
-    let formula = build_formula_from_ids(formula_ids, params.max_iterations, params.bailout);
+    let formula = cached_formula(render_params, formula_ids, &params);
 
-    // Interpret gbuffer as slice of SiLight5 (18 bytes each)
+    // View gbuffer as SiLight5 (18 bytes) or SiLight6 (34 bytes) records
     let pixel_count = (params.width * params.height) as usize;
-    let gbuf_pixels = unsafe {
-        let ptr = gbuffer.as_mut_ptr() as *mut engine::types::SiLight5;
-        std::slice::from_raw_parts_mut(ptr, pixel_count.min(gbuffer.len() / 18))
-    };
+    if params.gbuffer_format == engine::types::GBufferFormat::Extended {
+        let layers = engine::raymarcher::GBufferLayers {
+            extended: Some(engine::gbuffer::view_mut("gbuffer", gbuffer, pixel_count)?),
+            ..Default::default()
+        };
+        return Ok(engine::raymarcher::render_scanlines_layers(&params, &formula, &mut [], layers, worker_id, worker_count));
+    }
+    let gbuf_pixels = engine::gbuffer::view_mut("gbuffer", gbuffer, pixel_count)?;
 
     // Render assigned scanlines
-    engine::raymarcher::render_scanlines(&params, &formula, gbuf_pixels, worker_id, worker_count)
+    Ok(engine::raymarcher::render_scanlines(&params, &formula, gbuf_pixels, worker_id, worker_count))
 }
 
-/// Paint the G-buffer into an RGBA pixel buffer for display.
+/// Render scanlines while publishing progress into a shared Float64Array.
 ///
-/// `gbuffer` — Uint8Array: the G-buffer from render_scanlines
-/// `rgba_out` — Uint8Array: output RGBA (width * height * 4 bytes)
-/// `paint_params` — Float64Array of paint/lighting parameters
+/// `progress` — typically backed by a SharedArrayBuffer so the UI sees updates
+/// while the worker is busy; each worker owns 4 slots at `worker_id * 4`:
+/// [rows_done, rows_total, elapsed_ms, eta_ms]. Updated every `report_every`
+/// rows and after the last row.
 #[wasm_bindgen]
-pub fn paint_gbuffer(
-    gbuffer: &[u8],
-    rgba_out: &mut [u8],
-    width: u32,
-    height: u32,
-    paint_params: &[f64],
-) {
-    let config = lighting::paint::paint_config_from_buffer(paint_params);
+pub fn render_scanlines_progress(
+    render_params: &[f64],
+    formula_ids: &[u32],
+    gbuffer: &mut [u8],
+    progress: &js_sys::Float64Array,
+    report_every: u32,
+    worker_id: u32,
+    worker_count: u32,
+) -> Result<u32, JsError> {
+    let params = engine::raymarcher::params_from_buffer(render_params);
+    let formula = cached_formula(render_params, formula_ids, &params);
+    let pixel_count = (params.width * params.height) as usize;
+    let gbuf_pixels = engine::gbuffer::view_mut("gbuffer", gbuffer, pixel_count)?;
 
-    // Interpret gbuffer as SiLight5 slice
-    let pixel_count = (width * height) as usize;
-    let gbuf_pixels = unsafe {
-        let ptr = gbuffer.as_ptr() as *const engine::types::SiLight5;
-        std::slice::from_raw_parts(ptr, pixel_count.min(gbuffer.len() / 18))
+    let base = worker_id * 4;
+    let start = js_sys::Date::now();
+    let every = report_every.max(1);
+    let mut report = |done: u32, total: u32| {
+        if !done.is_multiple_of(every) && done != total {
+            return;
+        }
+        let elapsed = js_sys::Date::now() - start;
+        let eta = if done > 0 { elapsed / done as f64 * (total - done) as f64 } else { 0.0 };
+        progress.set_index(base, done as f64);
+        progress.set_index(base + 1, total as f64);
+        progress.set_index(base + 2, elapsed);
+        progress.set_index(base + 3, eta);
     };
 
-    lighting::paint::paint_gbuffer(gbuf_pixels, rgba_out, width, height, &config);
+    Ok(engine::raymarcher::render_scanlines_reporting(
+        &params, &formula, gbuf_pixels, Default::default(), worker_id, worker_count, &mut report,
+    ).rows)
 }
 
-/// Quick render — combined ray march + paint in one call.
-/// Useful for single-threaded preview rendering.
+/// Render scanlines and write this call's statistics into `stats_out`:
+/// [rows, pixels, hit_ratio, mean_steps, max_steps, de_evals, wall_ms].
+/// Returns the number of rows rendered.
+#[wasm_bindgen]
+pub fn render_scanlines_stats(
+    render_params: &[f64],
+    formula_ids: &[u32],
+    gbuffer: &mut [u8],
+    stats_out: &mut [f64],
+    worker_id: u32,
+    worker_count: u32,
+) -> Result<u32, JsError> {
+    let start = js_sys::Date::now();
+    let params = engine::raymarcher::params_from_buffer(render_params);
+    let formula = cached_formula(render_params, formula_ids, &params);
+    let pixel_count = (params.width * params.height) as usize;
+    let gbuf_pixels = engine::gbuffer::view_mut("gbuffer", gbuffer, pixel_count)?;
+
+    let stats = engine::raymarcher::render_scanlines_reporting(
+        &params, &formula, gbuf_pixels, Default::default(), worker_id, worker_count, &mut |_, _| {},
+    );
+    let values = [
+        stats.rows as f64,
+        stats.pixels as f64,
+        stats.hit_ratio(),
+        stats.mean_steps(),
+        stats.max_steps as f64,
+        stats.de_evals as f64,
+        js_sys::Date::now() - start,
+    ];
+    let n = values.len().min(stats_out.len());
+    stats_out[..n].copy_from_slice(&values[..n]);
+    Ok(stats.rows)
+}
+
+/// Render a rectangular tile [x0, x1) × [y0, y1) into the full-frame G-buffer.
 ///
-/// Returns RGBA bytes directly (width * height * 4).
+/// Lets schedulers distribute arbitrary rectangles (cache-friendly tiles,
+/// dirty-rect re-render, distributed rendering) instead of interleaved scanlines.
+/// Returns the number of pixels rendered.
 #[wasm_bindgen]
-pub fn render_quick(
+pub fn render_tile(
+    render_params: &[f64],
+    formula_ids: &[u32],
+    gbuffer: &mut [u8],
+    x0: u32,
+    y0: u32,
+    x1: u32,
+    y1: u32,
+) -> Result<u32, JsError> {
+    let params = engine::raymarcher::params_from_buffer(render_params);
+    let formula = cached_formula(render_params, formula_ids, &params);
+    let pixel_count = (params.width * params.height) as usize;
+    let gbuf_pixels = engine::gbuffer::view_mut("gbuffer", gbuffer, pixel_count)?;
+    Ok(engine::raymarcher::render_tile(&params, &formula, gbuf_pixels, x0, y0, x1, y1))
+}
+
+/// Pick a max iteration count automatically ("auto maxiter").
+///
+/// Renders a `grid` × `grid` coarse preview at growing iteration counts from
+/// `start` until the fraction of hit pixels changes by less than `tolerance`,
+/// up to `max_iterations`. Returns [iterations, hit_fraction, rounds, converged (0/1)].
+#[wasm_bindgen]
+pub fn auto_max_iterations(
+    render_params: &[f64],
+    formula_ids: &[u32],
+    start: u32,
+    max_iterations: u32,
+    grid: u32,
+    tolerance: f64,
+) -> Vec<f64> {
+    let params = engine::raymarcher::params_from_buffer(render_params);
+    let mut formula = build_formula(render_params, formula_ids, &params);
+    let settings = engine::autoiter::AutoIterSettings {
+        start,
+        max: max_iterations,
+        grid,
+        tolerance,
+        ..Default::default()
+    };
+    let r = engine::autoiter::auto_iterations(&params, &mut formula, &settings);
+    vec![r.iterations as f64, r.hit_fraction, r.rounds as f64, if r.converged { 1.0 } else { 0.0 }]
+}
+
+/// Volumetric mode: integrate a DE-derived density along each ray instead of
+/// sphere tracing to the surface (nebula/cloud renders).
+///
+/// Writes straight-alpha RGBA (width * height * 4) directly — there is no
+/// G-buffer; the color comes from the paint gradient. Settings are
+/// `render_params` [91..95].
+#[wasm_bindgen]
+pub fn render_volume(
     render_params: &[f64],
     formula_ids: &[u32],
     paint_params: &[f64],
     rgba_out: &mut [u8],
-) {
+    worker_id: u32,
+    worker_count: u32,
+) -> u32 {
     let params = engine::raymarcher::params_from_buffer(render_params);
-    let formula = build_formula_from_ids(formula_ids, params.max_iterations, params.bailout);
+    let formula = cached_formula(render_params, formula_ids, &params);
+    let config = lighting::paint::paint_config_from_buffer(paint_params);
+    engine::volumetric::render_scanlines(&params, &formula, &config, &params.volume, rgba_out, worker_id, worker_count)
+}
 
-    let pixel_count = (params.width * params.height) as usize;
-    let mut gbuffer = vec![engine::types::SiLight5::default(); pixel_count];
+/// Render rows claimed from a shared counter instead of a fixed interleave.
+///
+/// `counter` is an Int32Array over a SharedArrayBuffer; slot 0 holds the next
+/// unclaimed row and must be reset to 0 before each frame. Every worker calls
+/// this with the same counter and keeps claiming `rows_per_claim` rows at a
+/// time until the image is done. Returns the rows this worker rendered.
+#[wasm_bindgen]
+pub fn render_scanlines_claimed(
+    render_params: &[f64],
+    formula_ids: &[u32],
+    gbuffer: &mut [u8],
+    counter: &js_sys::Int32Array,
+    worker_count: u32,
+    rows_per_claim: u32,
+) -> Result<u32, JsError> {
+    let params = engine::raymarcher::params_from_buffer(render_params);
+    let formula = cached_formula(render_params, formula_ids, &params);
 
-    // Render all scanlines (single worker)
-    engine::raymarcher::render_scanlines(&params, &formula, &mut gbuffer, 0, 1);
+    let pixel_count = (params.width * params.height) as usize;
+    let gbuf_pixels = engine::gbuffer::view_mut("gbuffer", gbuffer, pixel_count)?;
 
-    // Paint
-    let config = lighting::paint::paint_config_from_buffer(paint_params);
-    lighting::paint::paint_gbuffer(&gbuffer, rgba_out, params.width, params.height, &config);
+    let mut claim = |n: u32| match js_sys::Atomics::add(counter, 0, n as i32) {
+        Ok(first) if first >= 0 => first as u32,
+        _ => u32::MAX,
+    };
+    Ok(engine::raymarcher::render_claimed_rows(
+        &params,
+        &formula,
+        gbuf_pixels,
+        engine::raymarcher::GBufferLayers::default(),
+        worker_count,
+        rows_per_claim,
+        &mut claim,
+    ))
 }
 
-/// Build a HybridFormula from the formula_ids array.
+/// Render scanlines plus optional secondary layers.
 ///
-/// Layout: [num_slots, id1, iters1, id2, iters2, ..., hybrid_mode]
-/// hybrid_mode: 0 = alternating, 1 = interpolated, 2 = 4D
-fn build_formula_from_ids(
+/// `reflect_gbuffer` / `transmit_gbuffer` have the same size and layout as
+/// `gbuffer`; pass an empty array to skip a layer. Paint the result with
+/// `paint_gbuffer_layers`, which blends them by the paint reflectivity and
+/// transparency.
+#[wasm_bindgen]
+pub fn render_scanlines_layers(
+    render_params: &[f64],
     formula_ids: &[u32],
-    max_iterations: u32,
-    bailout: f64,
-) -> formulas::hybrid::HybridFormula {
-    use formulas::{FormulaId, hybrid::HybridMode};
+    gbuffer: &mut [u8],
+    reflect_gbuffer: &mut [u8],
+    transmit_gbuffer: &mut [u8],
+    worker_id: u32,
+    worker_count: u32,
+) -> Result<u32, JsError> {
+    let params = engine::raymarcher::params_from_buffer(render_params);
+    let formula = cached_formula(render_params, formula_ids, &params);
 
-    if formula_ids.is_empty() {
-        // Default: single Mandelbulb power 8
-        return formulas::hybrid::HybridFormula::new(
-            &[(FormulaId::MandelbulbPower8, 1)],
-            HybridMode::Alternating,
-            max_iterations,
-            bailout,
-        );
+    let pixel_count = (params.width * params.height) as usize;
+    let layers = engine::raymarcher::GBufferLayers {
+        reflect: engine::gbuffer::optional_view_mut("reflect_gbuffer", reflect_gbuffer, pixel_count)?,
+        transmit: engine::gbuffer::optional_view_mut("transmit_gbuffer", transmit_gbuffer, pixel_count)?,
+        ..Default::default()
+    };
+    let gbuf_pixels = engine::gbuffer::view_mut("gbuffer", gbuffer, pixel_count)?;
+
+    Ok(engine::raymarcher::render_scanlines_layers(&params, &formula, gbuf_pixels, layers, worker_id, worker_count))
+}
+
+/// Render scanlines plus a reflection layer (same size and layout as
+/// `gbuffer`). Paint the pair with `paint_gbuffer_reflect`.
+#[wasm_bindgen]
+pub fn render_scanlines_reflect(
+    render_params: &[f64],
+    formula_ids: &[u32],
+    gbuffer: &mut [u8],
+    reflect_gbuffer: &mut [u8],
+    worker_id: u32,
+    worker_count: u32,
+) -> Result<u32, JsError> {
+    render_scanlines_layers(render_params, formula_ids, gbuffer, reflect_gbuffer, &mut [], worker_id, worker_count)
+}
+
+/// Re-render one tile with instrumentation and return a diagnostic report.
+///
+/// The scene is a JSON scene description (see `engine::scene`); `tile` is
+/// `[x0, y0, x1, y1]` (exclusive end). The report holds a scene hash, step
+/// statistics, a G-buffer checksum and the step trace of the tile's most
+/// expensive pixel — paste it into rendering-artifact issues. Throws if the
+/// description is invalid.
+#[wasm_bindgen]
+pub fn repro_tile(scene: &str, tile: &[u32]) -> Result<String, JsError> {
+    let buffers = engine::scene::SceneDescription::from_json(scene)?.to_buffers()?;
+    let (render_params, formula_ids) = (&buffers.render_params, &buffers.formula_ids);
+    let params = engine::raymarcher::params_from_buffer(render_params);
+    let formula = cached_formula(render_params, formula_ids, &params);
+    let get = |i: usize, default: u32| tile.get(i).copied().unwrap_or(default);
+    let rect = (get(0, 0), get(1, 0), get(2, params.width), get(3, params.height));
+    let (stats, trace) = engine::repro::render_tile_stats(&params, &*formula, rect);
+    let hash = engine::repro::scene_hash(render_params, formula_ids);
+    Ok(engine::repro::repro_report(hash, rect, &stats, &trace))
+}
+
+/// Whether this build routes transcendentals through libm
+/// (`strict-determinism` feature).
+///
+/// Network render nodes only compare tile checksums with peers that report
+/// the same value.
+#[wasm_bindgen]
+pub fn strict_determinism() -> bool {
+    math::strict::STRICT
+}
+
+/// Replay a recorded session (see `engine::replay` for the format).
+///
+/// Returns JSON: `{ "checkpoints": [{ step, name, gbuffer_crc, image_crc,
+/// matches }] }`, or `{ "error": ..., "step": ... }` if the session is invalid.
+#[wasm_bindgen]
+pub fn replay_session(session: &str) -> String {
+    let report = match engine::replay::replay(session) {
+        Ok(checkpoints) => serde_json::json!({
+            "checkpoints": checkpoints.iter().map(|c| c.to_json()).collect::<Vec<_>>(),
+        }),
+        Err(e) => serde_json::json!({ "error": e.message, "step": e.step }),
+    };
+    report.to_string()
+}
+
+/// Render scanlines of a JSON scene description (see `engine::scene` for the
+/// format) instead of positional buffers. Throws if the description is invalid.
+#[wasm_bindgen]
+pub fn render_scene_json(scene: &str, gbuffer: &mut [u8], worker_id: u32, worker_count: u32) -> Result<u32, JsError> {
+    let buffers = engine::scene::SceneDescription::from_json(scene)?.to_buffers()?;
+    scanlines_from_buffers(&buffers.render_params, &buffers.formula_ids, gbuffer, worker_id, worker_count)
+}
+
+/// Paint a G-buffer rendered from a JSON scene description; the image size
+/// is the description's camera size.
+#[wasm_bindgen]
+pub fn paint_scene_json(scene: &str, gbuffer: &[u8], rgba_out: &mut [u8]) -> Result<(), JsError> {
+    let description = engine::scene::SceneDescription::from_json(scene)?;
+    let buffers = description.to_buffers()?;
+    let camera = &description.camera;
+    paint_from_buffers(gbuffer, rgba_out, camera.width, camera.height, &buffers.paint_params)
+}
+
+/// The positional buffers of a JSON scene description, for entry points
+/// that have no JSON variant.
+#[wasm_bindgen]
+pub struct SceneBuffers {
+    buffers: engine::scene::SceneBuffers,
+}
+
+#[wasm_bindgen]
+impl SceneBuffers {
+    /// Convert a JSON scene description; throws if it is invalid.
+    #[wasm_bindgen(constructor)]
+    pub fn new(scene: &str) -> Result<SceneBuffers, JsError> {
+        Ok(SceneBuffers { buffers: engine::scene::SceneDescription::from_json(scene)?.to_buffers()? })
     }
 
-    let num_slots = formula_ids[0] as usize;
-    let mut slots = Vec::new();
+    pub fn render_params(&self) -> Vec<f64> {
+        self.buffers.render_params.clone()
+    }
 
-    let mut idx = 1;
-    for _ in 0..num_slots.min(6) {
-        if idx + 1 >= formula_ids.len() { break; }
-        let id = formula_id_from_u32(formula_ids[idx]);
-        let iters = formula_ids[idx + 1];
-        slots.push((id, iters));
-        idx += 2;
+    pub fn formula_ids(&self) -> Vec<u32> {
+        self.buffers.formula_ids.clone()
     }
 
-    let hybrid_mode = if idx < formula_ids.len() {
-        match formula_ids[idx] {
-            1 => HybridMode::Interpolated,
-            2 => HybridMode::FourD,
-            _ => HybridMode::Alternating,
-        }
-    } else {
-        HybridMode::Alternating
+    pub fn paint_params(&self) -> Vec<f64> {
+        self.buffers.paint_params.clone()
+    }
+}
+
+/// Check the three parameter buffers for values the parsers would replace
+/// or ignore (see `engine::validate`).
+///
+/// Returns a JSON array of `{ severity: "error" | "warning", buffer, index,
+/// message }`; empty when the buffers are fine.
+#[wasm_bindgen]
+pub fn validate_params(render_params: &[f64], formula_ids: &[u32], paint_params: &[f64]) -> String {
+    let issues = engine::validate::validate(render_params, formula_ids, paint_params);
+    serde_json::to_string(&issues).unwrap_or_else(|_| "[]".into())
+}
+
+/// Describe existing buffers as a JSON scene description. Settings the
+/// description does not cover are left out.
+#[wasm_bindgen]
+pub fn scene_json_from_buffers(render_params: &[f64], formula_ids: &[u32], paint_params: &[f64]) -> String {
+    engine::scene::SceneDescription::from_buffers(render_params, formula_ids, paint_params).to_json()
+}
+
+/// Reproject the previous frame's hit depths into the current view.
+///
+/// `prev_render_params` / `prev_gbuffer` are the buffers of the last rendered
+/// frame; `start_depths_out` receives one f32 per pixel of the new view
+/// (0 = unknown) for `render_scanlines_reprojected`.
+#[wasm_bindgen]
+pub fn reproject_depths(
+    prev_render_params: &[f64],
+    prev_gbuffer: &[u8],
+    render_params: &[f64],
+    start_depths_out: &mut [f32],
+) -> Result<(), JsError> {
+    let prev = engine::raymarcher::params_from_buffer(prev_render_params);
+    let params = engine::raymarcher::params_from_buffer(render_params);
+    let prev_pixels = engine::gbuffer::view("prev_gbuffer", prev_gbuffer, (prev.width * prev.height) as usize)?;
+    let depths = engine::reproject::reproject_depths(&prev, prev_pixels, &params);
+    let n = depths.len().min(start_depths_out.len());
+    start_depths_out[..n].copy_from_slice(&depths[..n]);
+    Ok(())
+}
+
+/// Render scanlines with rays starting at validated reprojected depths
+/// (from `reproject_depths`).
+#[wasm_bindgen]
+pub fn render_scanlines_reprojected(
+    render_params: &[f64],
+    formula_ids: &[u32],
+    start_depths: &[f32],
+    gbuffer: &mut [u8],
+    worker_id: u32,
+    worker_count: u32,
+) -> Result<u32, JsError> {
+    let params = engine::raymarcher::params_from_buffer(render_params);
+    let formula = cached_formula(render_params, formula_ids, &params);
+
+    let pixel_count = (params.width * params.height) as usize;
+    let layers = engine::raymarcher::GBufferLayers {
+        start_depths: Some(start_depths),
+        ..Default::default()
     };
+    let gbuf_pixels = engine::gbuffer::view_mut("gbuffer", gbuffer, pixel_count)?;
 
-    if slots.is_empty() {
-        slots.push((FormulaId::MandelbulbPower8, 1));
-    }
+    Ok(engine::raymarcher::render_scanlines_layers(&params, &formula, gbuf_pixels, layers, worker_id, worker_count))
+}
+
+/// Render interleaved rows of the low-resolution depth pre-pass.
+///
+/// `depths_out` holds ceil(width / block) × ceil(height / block) f32 values
+/// (block = render param 108), typically in a SharedArrayBuffer filled by
+/// all workers before any of them calls `render_scanlines_primed`.
+#[wasm_bindgen]
+pub fn render_depth_prepass(
+    render_params: &[f64],
+    formula_ids: &[u32],
+    depths_out: &mut [f32],
+    worker_id: u32,
+    worker_count: u32,
+) -> u32 {
+    let params = engine::raymarcher::params_from_buffer(render_params);
+    let formula = cached_formula(render_params, formula_ids, &params);
+    engine::prepass::render_prepass_rows(&params, &*formula, depths_out, worker_id, worker_count)
+}
+
+/// Render scanlines with rays starting at the pre-pass depths
+/// (from `render_depth_prepass`). A too-short depth array renders unprimed.
+#[wasm_bindgen]
+pub fn render_scanlines_primed(
+    render_params: &[f64],
+    formula_ids: &[u32],
+    depths: &[f32],
+    gbuffer: &mut [u8],
+    worker_id: u32,
+    worker_count: u32,
+) -> Result<u32, JsError> {
+    let params = engine::raymarcher::params_from_buffer(render_params);
+    let formula = cached_formula(render_params, formula_ids, &params);
+
+    let pixel_count = (params.width * params.height) as usize;
+    let layers = engine::raymarcher::GBufferLayers {
+        prepass: engine::prepass::DepthPrepass::new(&params, depths),
+        ..Default::default()
+    };
+    let gbuf_pixels = engine::gbuffer::view_mut("gbuffer", gbuffer, pixel_count)?;
+
+    Ok(engine::raymarcher::render_scanlines_layers(&params, &formula, gbuf_pixels, layers, worker_id, worker_count))
+}
+
+/// Encode the current, possibly partial, render as a PNG progress snapshot.
+///
+/// Pixels not rendered yet (the G-buffer must start zeroed) are transparent.
+/// The image is box-downscaled so neither side exceeds `max_dim` (0 = full size),
+/// and compressed at the fast zlib level to keep snapshots cheap.
+#[wasm_bindgen]
+pub fn encode_progress_png(
+    gbuffer: &[u8],
+    width: u32,
+    height: u32,
+    paint_params: &[f64],
+    max_dim: u32,
+) -> Result<Vec<u8>, JsError> {
+    let config = lighting::paint::paint_config_from_buffer(paint_params);
+    let pixel_count = (width * height) as usize;
+    let gbuf_pixels = engine::gbuffer::view("gbuffer", gbuffer, pixel_count)?;
+    let snap = export::snapshot::snapshot(gbuf_pixels, width, height, &config, max_dim);
+    Ok(export::png::encode_rgba(&snap.rgba, snap.width, snap.height, 1))
+}
+
+/// Encode RGBA8 pixels (width * height * 4) as a PNG file, so large renders
+/// can be saved from a worker without a canvas. Returns an empty array if
+/// `rgba` is too short.
+#[wasm_bindgen]
+pub fn encode_rgba_png(rgba: &[u8], width: u32, height: u32) -> Vec<u8> {
+    export::png::encode_rgba(rgba, width, height, 6)
+}
 
-    formulas::hybrid::HybridFormula::new(&slots, hybrid_mode, max_iterations, bailout)
+/// 16-bit variant of `encode_rgba_png` (width * height * 4 samples).
+#[wasm_bindgen]
+pub fn encode_rgba16_png(rgba: &[u16], width: u32, height: u32) -> Vec<u8> {
+    export::png::encode_rgba16(rgba, width, height, 6)
 }
 
-/// Map a u32 formula ID to FormulaId enum.
-fn formula_id_from_u32(id: u32) -> formulas::FormulaId {
-    match id {
-        1 => formulas::FormulaId::MandelbulbPower2,
-        2 => formulas::FormulaId::MandelbulbPower8,
-        3 => formulas::FormulaId::AmazingBox,
-        4 => formulas::FormulaId::AmazingSurf,
-        5 => formulas::FormulaId::QuaternionJulia,
-        6 => formulas::FormulaId::Tricorn,
-        7 => formulas::FormulaId::Bulbox,
-        8 => formulas::FormulaId::FoldingIntPow,
-        9 => formulas::FormulaId::RealPower,
-        10 => formulas::FormulaId::AexionC,
-        _ => formulas::FormulaId::None,
+/// Render and paint a whole frame (single-threaded, like `render_quick`) and
+/// return it as a PNG file. With `sixteen_bit` the paint output is quantized
+/// to 16 bits per channel instead of 8, which keeps smooth gradients and fog
+/// free of banding. Throws if the image is too large to render.
+#[wasm_bindgen]
+pub fn render_to_png(
+    render_params: &[f64],
+    formula_ids: &[u32],
+    paint_params: &[f64],
+    sixteen_bit: bool,
+) -> Result<Vec<u8>, JsError> {
+    let params = engine::raymarcher::params_from_buffer(render_params);
+    let samples = params.buffer_len(4).ok_or(engine::scene::SceneError::ImageTooLarge(params.width, params.height))?;
+    let formula = cached_formula(render_params, formula_ids, &params);
+    let config = lighting::paint::paint_config_from_buffer(paint_params);
+    if sixteen_bit {
+        let mut color = vec![0f32; samples];
+        engine::preview::render_frame_f32(&params, &formula, &config, &mut color);
+        let rgba: Vec<u16> = color
+            .chunks_exact(4)
+            .flat_map(|c| [config.encode16(c[0] as f64), config.encode16(c[1] as f64), config.encode16(c[2] as f64), u16::MAX])
+            .collect();
+        Ok(export::png::encode_rgba16(&rgba, params.width, params.height, 6))
+    } else {
+        let mut rgba = vec![0u8; samples];
+        engine::preview::render_frame(&params, &formula, &config, &mut rgba);
+        Ok(export::png::encode_rgba(&rgba, params.width, params.height, 6))
     }
 }
 
+/// Append a caption band to an exported RGBA image.
+///
+/// `text` lines are separated by `\n` (see `parameter_caption`); returns the
+/// new image, which is `width` wide and taller by the band height.
+#[wasm_bindgen]
+pub fn append_caption_footer(rgba: &[u8], width: u32, height: u32, text: &str, scale: u32) -> Vec<u8> {
+    let settings = export::annotate::FooterSettings { scale, ..Default::default() };
+    export::annotate::append_footer(rgba, width, height, text, &settings).0
+}
+
+/// Standard caption text for `append_caption_footer`: formula stack,
+/// iterations, zoom and camera position.
+#[wasm_bindgen]
+pub fn parameter_caption(render_params: &[f64], formula_ids: &[u32], zoom: f64) -> String {
+    let params = engine::raymarcher::params_from_buffer(render_params);
+    let formula = cached_formula(render_params, formula_ids, &params);
+    export::annotate::parameter_caption(&params, &formula, zoom)
+}
+
+/// Paint the G-buffer and merge an externally rendered RGBA + depth layer by depth.
+///
+/// `layer_depth` holds ray distances in world units (converted with the
+/// `max_ray_length` in `render_params`); the layer is fogged like the fractal.
+/// Returns the number of pixels the layer covered.
+#[wasm_bindgen]
+pub fn paint_with_external_layer(
+    gbuffer: &[u8],
+    render_params: &[f64],
+    paint_params: &[f64],
+    layer_rgba: &[u8],
+    layer_depth: &[f32],
+    rgba_out: &mut [u8],
+) -> Result<u32, JsError> {
+    let params = engine::raymarcher::params_from_buffer(render_params);
+    let config = lighting::paint::paint_config_from_buffer(paint_params);
+    let pixel_count = (params.width * params.height) as usize;
+    let gbuf_pixels = engine::gbuffer::view("gbuffer", gbuffer, pixel_count)?;
+    lighting::paint::paint_gbuffer(gbuf_pixels, rgba_out, params.width, params.height, &config);
+
+    let scale = 1.0 / params.max_ray_length.max(f64::MIN_POSITIVE);
+    let depth: Vec<f32> = layer_depth.iter().map(|&d| (d as f64 * scale) as f32).collect();
+    let external = lighting::post::ExternalLayer { rgba: layer_rgba, depth: &depth };
+    Ok(lighting::post::composite_external(gbuf_pixels, &external, rgba_out, &config))
+}
+
+/// Merge a second scene's G-buffer into `gbuffer` by depth.
+///
+/// Render each scene (its own `formula_ids`, same camera `render_params`)
+/// into a separate G-buffer with `render_scanlines`, then fold them together
+/// here before painting. Pixels taken from `other_gbuffer` are tagged with
+/// `material_id`. Returns the number of pixels taken from `other_gbuffer`.
+#[wasm_bindgen]
+pub fn composite_gbuffers(
+    gbuffer: &mut [u8],
+    other_gbuffer: &[u8],
+    width: u32,
+    height: u32,
+    material_id: u8,
+) -> Result<u32, JsError> {
+    let pixel_count = (width * height) as usize;
+    let other = engine::gbuffer::view("other_gbuffer", other_gbuffer, pixel_count)?;
+    let dst = engine::gbuffer::view_mut("gbuffer", gbuffer, pixel_count)?;
+    Ok(engine::composite::merge_by_depth(dst, other, material_id))
+}
+
+/// Render one eye of a stereo pair (`eye`: 0 = left, 1 = right).
+///
+/// Eye distance and convergence come from `render_params` [40..41]; call once
+/// per eye with the same parameters to get two ordinary G-buffers.
+#[wasm_bindgen]
+pub fn render_eye_scanlines(
+    render_params: &[f64],
+    formula_ids: &[u32],
+    eye: u32,
+    gbuffer: &mut [u8],
+    worker_id: u32,
+    worker_count: u32,
+) -> Result<u32, JsError> {
+    let params = engine::raymarcher::params_from_buffer(render_params);
+    let formula = cached_formula(render_params, formula_ids, &params);
+    let eye_params = engine::stereo::eye_params(&params, engine::stereo::Eye::from_u32(eye));
+    let pixel_count = (params.width * params.height) as usize;
+    let gbuf_pixels = engine::gbuffer::view_mut("gbuffer", gbuffer, pixel_count)?;
+    Ok(engine::raymarcher::render_scanlines(&eye_params, &formula, gbuf_pixels, worker_id, worker_count))
+}
+
+/// Render both eyes side by side into a double-wide G-buffer
+/// (2 * width * height entries; paint it with `paint_gbuffer` at 2 * width).
+#[wasm_bindgen]
+pub fn render_stereo_scanlines(
+    render_params: &[f64],
+    formula_ids: &[u32],
+    gbuffer: &mut [u8],
+    worker_id: u32,
+    worker_count: u32,
+) -> Result<u32, JsError> {
+    let params = engine::raymarcher::params_from_buffer(render_params);
+    let formula = cached_formula(render_params, formula_ids, &params);
+    // Both eyes are indexed with u32 too
+    let pixel_count = params
+        .buffer_len(2)
+        .filter(|&pixels| pixels <= u32::MAX as usize)
+        .ok_or(engine::scene::SceneError::ImageTooLarge(params.width.saturating_mul(2), params.height))?;
+    let gbuf_pixels = engine::gbuffer::view_mut("gbuffer", gbuffer, pixel_count)?;
+    Ok(engine::stereo::render_side_by_side(&params, &formula, gbuf_pixels, worker_id, worker_count))
+}
+
+/// Red/cyan anaglyph from a painted side-by-side stereo image
+/// (`eye_width` is the width of one eye). `mode`: 0 color, 1 gray, 2 half-color.
+#[wasm_bindgen]
+pub fn anaglyph_side_by_side(sbs_rgba: &[u8], eye_width: u32, height: u32, mode: u32, rgba_out: &mut [u8]) {
+    let row = eye_width as usize * 4;
+    let mut left = Vec::with_capacity(row * height as usize);
+    let mut right = Vec::with_capacity(row * height as usize);
+    for line in sbs_rgba.chunks_exact(row * 2).take(height as usize) {
+        left.extend_from_slice(&line[..row]);
+        right.extend_from_slice(&line[row..]);
+    }
+    lighting::paint::anaglyph(&left, &right, rgba_out, lighting::paint::AnaglyphMode::from_u32(mode));
+}
+
+/// Paint the G-buffer into an RGBA pixel buffer for display.
+///
+/// `gbuffer` — Uint8Array: the G-buffer from render_scanlines, in the
+///   layout named by the G-buffer format section of `paint_params`
+/// `rgba_out` — Uint8Array: output RGBA (width * height * 4 bytes)
+/// `paint_params` — Float64Array of paint/lighting parameters
+///
+/// Deprecated in favour of `paint_scene_json`; warns once.
+#[wasm_bindgen]
+pub fn paint_gbuffer(
+    gbuffer: &[u8],
+    rgba_out: &mut [u8],
+    width: u32,
+    height: u32,
+    paint_params: &[f64],
+) -> Result<(), JsError> {
+    log::deprecated("paint_gbuffer", "paint_scene_json");
+    paint_from_buffers(gbuffer, rgba_out, width, height, paint_params)
+}
+
+/// `paint_gbuffer` without the deprecation warning, shared with `paint_scene_json`.
+fn paint_from_buffers(
+    gbuffer: &[u8],
+    rgba_out: &mut [u8],
+    width: u32,
+    height: u32,
+    paint_params: &[f64],
+) -> Result<(), JsError> {
+    let config = lighting::paint::paint_config_from_buffer(paint_params);
+
+    // Interpret gbuffer as SiLight5 slice
+    let pixel_count = (width * height) as usize;
+    if config.gbuffer_format == engine::types::GBufferFormat::Extended {
+        let records = engine::gbuffer::view("gbuffer", gbuffer, pixel_count)?;
+        lighting::paint::paint_gbuffer_extended(records, rgba_out, width, height, &config);
+        return Ok(());
+    }
+    let gbuf_pixels = engine::gbuffer::view("gbuffer", gbuffer, pixel_count)?;
+
+    lighting::paint::paint_gbuffer(gbuf_pixels, rgba_out, width, height, &config);
+    Ok(())
+}
+
+/// Repaint the image rows `row_start..row_end` of a full-size RGBA buffer.
+///
+/// Lighting tweaks can repaint only a dirty region, or split a repaint across
+/// workers sharing `rgba_out` (a view into a SharedArrayBuffer). The whole
+/// G-buffer is still read, so neighbourhood effects match a full repaint.
+#[wasm_bindgen]
+pub fn paint_gbuffer_range(
+    gbuffer: &[u8],
+    rgba_out: &mut [u8],
+    width: u32,
+    height: u32,
+    row_start: u32,
+    row_end: u32,
+    paint_params: &[f64],
+) -> Result<(), JsError> {
+    let config = lighting::paint::paint_config_from_buffer(paint_params);
+    let pixel_count = (width * height) as usize;
+    lighting::paint::paint_gbuffer_range(
+        engine::gbuffer::view("gbuffer", gbuffer, pixel_count)?,
+        Default::default(),
+        rgba_out,
+        width,
+        height,
+        row_start..row_end,
+        &config,
+    );
+    Ok(())
+}
+
+/// Draw the crop and safe-area guides from `paint_params` into a separate
+/// transparent RGBA overlay (width * height * 4). Without a safe-region
+/// section the overlay is just cleared.
+#[wasm_bindgen]
+pub fn paint_safe_regions(overlay_out: &mut [u8], width: u32, height: u32, paint_params: &[f64]) {
+    let config = lighting::paint::paint_config_from_buffer(paint_params);
+    match config.safe_regions {
+        Some(settings) => lighting::overlay::draw_safe_regions(overlay_out, width, height, &settings),
+        None => overlay_out.fill(0),
+    }
+}
+
+/// Paint the G-buffer with the reflection layer from `render_scanlines_reflect`.
+#[wasm_bindgen]
+pub fn paint_gbuffer_reflect(
+    gbuffer: &[u8],
+    reflect_gbuffer: &[u8],
+    rgba_out: &mut [u8],
+    width: u32,
+    height: u32,
+    paint_params: &[f64],
+) -> Result<(), JsError> {
+    paint_gbuffer_layers(gbuffer, reflect_gbuffer, &[], rgba_out, width, height, paint_params)
+}
+
+/// Paint the G-buffer with the secondary layers from `render_scanlines_layers`.
+///
+/// Empty layer arrays are ignored.
+#[wasm_bindgen]
+pub fn paint_gbuffer_layers(
+    gbuffer: &[u8],
+    reflect_gbuffer: &[u8],
+    transmit_gbuffer: &[u8],
+    rgba_out: &mut [u8],
+    width: u32,
+    height: u32,
+    paint_params: &[f64],
+) -> Result<(), JsError> {
+    let config = lighting::paint::paint_config_from_buffer(paint_params);
+
+    let pixel_count = (width * height) as usize;
+    let layers = lighting::paint::PaintLayers {
+        reflect: engine::gbuffer::optional_view("reflect_gbuffer", reflect_gbuffer, pixel_count)?,
+        transmit: engine::gbuffer::optional_view("transmit_gbuffer", transmit_gbuffer, pixel_count)?,
+        ..Default::default()
+    };
+
+    lighting::paint::paint_gbuffer_layers(
+        engine::gbuffer::view("gbuffer", gbuffer, pixel_count)?, layers, rgba_out, width, height, &config,
+    );
+    Ok(())
+}
+
+/// Paint the G-buffer (and optional secondary layers) into float RGBA
+/// (width * height * 4) without 8-bit quantization, for EXR export or further
+/// post-processing. Values are linear light under the linear workflow and
+/// are not clamped.
+///
+/// Empty layer arrays are ignored.
+#[wasm_bindgen]
+pub fn paint_gbuffer_f32(
+    gbuffer: &[u8],
+    reflect_gbuffer: &[u8],
+    transmit_gbuffer: &[u8],
+    rgba_out: &mut [f32],
+    width: u32,
+    height: u32,
+    paint_params: &[f64],
+) -> Result<(), JsError> {
+    let config = lighting::paint::paint_config_from_buffer(paint_params);
+
+    let pixel_count = (width * height) as usize;
+    let layers = lighting::paint::PaintLayers {
+        reflect: engine::gbuffer::optional_view("reflect_gbuffer", reflect_gbuffer, pixel_count)?,
+        transmit: engine::gbuffer::optional_view("transmit_gbuffer", transmit_gbuffer, pixel_count)?,
+        ..Default::default()
+    };
+
+    lighting::paint::paint_gbuffer_f32(
+        engine::gbuffer::view("gbuffer", gbuffer, pixel_count)?, layers, rgba_out, width, height, &config,
+    );
+    Ok(())
+}
+
+/// Export render passes from a packed G-buffer as an OpenEXR file (FLOAT
+/// channels, ZIP compressed): `Z` camera distance, `N.X/Y/Z` normals, `AO.Y`
+/// and `trap.Y`, plus `R/G/B/A` from `color` (the `paint_gbuffer_f32`
+/// output; empty to leave color out).
+#[wasm_bindgen]
+pub fn encode_exr_passes(
+    gbuffer: &[u8],
+    color: &[f32],
+    width: u32,
+    height: u32,
+    render_params: &[f64],
+) -> Result<Vec<u8>, JsError> {
+    let params = engine::raymarcher::params_from_buffer(render_params);
+    let pixel_count = (width * height) as usize;
+    let gbuf_pixels = engine::gbuffer::view("gbuffer", gbuffer, pixel_count)?;
+    let color = (!color.is_empty()).then_some(color);
+    let channels = export::exr::passes(gbuf_pixels, color, params.max_ray_length);
+    Ok(export::exr::encode(width, height, &channels, export::exr::ExrCompression::Zip))
+}
+
+/// Depth map of a packed G-buffer as a grayscale PNG (16-bit with
+/// `sixteen_bit`). Distance along the view ray is remapped so `near` is
+/// white and `far` black (swap them to invert); `near == far` spans the
+/// nearest to the farthest hit. Misses are black.
+#[wasm_bindgen]
+pub fn extract_depth_map(
+    gbuffer: &[u8],
+    width: u32,
+    height: u32,
+    render_params: &[f64],
+    near: f64,
+    far: f64,
+    sixteen_bit: bool,
+) -> Result<Vec<u8>, JsError> {
+    let params = engine::raymarcher::params_from_buffer(render_params);
+    let gbuf_pixels = engine::gbuffer::view("gbuffer", gbuffer, (width * height) as usize)?;
+    let depth = if sixteen_bit { 16 } else { 8 };
+    let samples = export::maps::depth_map(gbuf_pixels, params.max_ray_length, near, far, depth);
+    Ok(export::png::encode_samples(&samples, width, height, export::png::ColorType::Gray, depth, 6))
+}
+
+/// Normal map of a packed G-buffer as an RGB PNG (16-bit with
+/// `sixteen_bit`), `n * 0.5 + 0.5` per channel. World space, or camera
+/// space (x right, y up, z toward the viewer) with `camera_space`, using
+/// the view of `render_params`. Misses are black.
+#[wasm_bindgen]
+pub fn extract_normal_map(
+    gbuffer: &[u8],
+    width: u32,
+    height: u32,
+    render_params: &[f64],
+    camera_space: bool,
+    sixteen_bit: bool,
+) -> Result<Vec<u8>, JsError> {
+    let params = engine::raymarcher::params_from_buffer(render_params);
+    let gbuf_pixels = engine::gbuffer::view("gbuffer", gbuffer, (width * height) as usize)?;
+    let view = camera_space.then(|| lighting::paint::PaintView::from_render_params(&params));
+    let depth = if sixteen_bit { 16 } else { 8 };
+    let samples = export::maps::normal_map(gbuf_pixels, view.as_ref(), depth);
+    Ok(export::png::encode_samples(&samples, width, height, export::png::ColorType::Rgb, depth, 6))
+}
+
+/// Adaptive antialiasing pass over a rendered and painted frame.
+///
+/// Pixels on depth / normal / silhouette edges of `gbuffer` are re-rendered
+/// with n × n supersampling (render_params AA settings) and replaced in
+/// `rgba_out`. Returns the number of pixels resampled by this worker.
+#[wasm_bindgen]
+pub fn antialias_edges(
+    render_params: &[f64],
+    formula_ids: &[u32],
+    paint_params: &[f64],
+    gbuffer: &[u8],
+    rgba_out: &mut [u8],
+    worker_id: u32,
+    worker_count: u32,
+) -> Result<u32, JsError> {
+    antialias_edges_layers(render_params, formula_ids, paint_params, gbuffer, &[], &[], rgba_out, worker_id, worker_count)
+}
+
+/// `antialias_edges` for a frame painted with `paint_gbuffer_layers`; the
+/// resampled pixels blend the same reflection / transmission layers.
+/// Empty layer arrays are ignored.
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn antialias_edges_layers(
+    render_params: &[f64],
+    formula_ids: &[u32],
+    paint_params: &[f64],
+    gbuffer: &[u8],
+    reflect_gbuffer: &[u8],
+    transmit_gbuffer: &[u8],
+    rgba_out: &mut [u8],
+    worker_id: u32,
+    worker_count: u32,
+) -> Result<u32, JsError> {
+    let params = engine::raymarcher::params_from_buffer(render_params);
+    let formula = cached_formula(render_params, formula_ids, &params);
+    let config = lighting::paint::paint_config_from_buffer(paint_params);
+    let pixel_count = (params.width * params.height) as usize;
+    let layers = lighting::paint::PaintLayers {
+        reflect: engine::gbuffer::optional_view("reflect_gbuffer", reflect_gbuffer, pixel_count)?,
+        transmit: engine::gbuffer::optional_view("transmit_gbuffer", transmit_gbuffer, pixel_count)?,
+        ..Default::default()
+    };
+    Ok(engine::antialias::supersample_edges(
+        &params,
+        &formula,
+        &config,
+        engine::gbuffer::view("gbuffer", gbuffer, pixel_count)?,
+        layers,
+        rgba_out,
+        worker_id,
+        worker_count,
+    ))
+}
+
+/// Quick render — combined ray march + paint in one call.
+/// Useful for single-threaded preview rendering.
+///
+/// Returns RGBA bytes directly (width * height * 4).
+///
+/// Deprecated in favour of `render_scene_json` and `paint_scene_json`; warns once.
+#[wasm_bindgen]
+pub fn render_quick(
+    render_params: &[f64],
+    formula_ids: &[u32],
+    paint_params: &[f64],
+    rgba_out: &mut [u8],
+) {
+    log::deprecated("render_quick", "render_scene_json + paint_scene_json");
+    quick_from_buffers(render_params, formula_ids, paint_params, rgba_out);
+}
+
+/// `render_quick` without the deprecation warning.
+fn quick_from_buffers(render_params: &[f64], formula_ids: &[u32], paint_params: &[f64], rgba_out: &mut [u8]) {
+    let params = engine::raymarcher::params_from_buffer(render_params);
+    let formula = cached_formula(render_params, formula_ids, &params);
+    let config = lighting::paint::paint_config_from_buffer(paint_params);
+    engine::preview::render_frame(&params, &formula, &config, rgba_out);
+}
+
+static PREVIEW_CACHE: std::sync::Mutex<engine::preview::PreviewCache> =
+    std::sync::Mutex::new(engine::preview::PreviewCache::new(engine::preview::DEFAULT_BUDGET));
+
+/// Return a cached preview thumbnail of a JSON scene description (see
+/// `engine::scene`), rendering it on a cache miss.
+///
+/// The camera size is replaced by `width` × `height` (the view ray setup is
+/// resolution independent) and the scene is identified by a hash of its
+/// buffers and that size. Returns RGBA (width * height * 4); throws if the
+/// description is invalid or the thumbnail size is zero or too large.
+#[wasm_bindgen]
+pub fn get_or_render_preview(scene: &str, width: u32, height: u32) -> Result<Vec<u8>, JsError> {
+    let mut description = engine::scene::SceneDescription::from_json(scene)?;
+    description.camera.width = width;
+    description.camera.height = height;
+    let len = description.camera.rgba_len()?;
+    let buffers = description.to_buffers()?;
+    let (render_params, formula_ids, paint_params) = (&buffers.render_params, &buffers.formula_ids, &buffers.paint_params);
+
+    let key = engine::preview::scene_hash(render_params, formula_ids, paint_params, width, height);
+    if let Some(rgba) = PREVIEW_CACHE.lock().unwrap_or_else(|e| e.into_inner()).get(key) {
+        return Ok(rgba.to_vec());
+    }
+
+    let params = engine::raymarcher::params_from_buffer(render_params);
+    let formula = cached_formula(render_params, formula_ids, &params);
+    let config = lighting::paint::paint_config_from_buffer(paint_params);
+    let mut rgba = vec![0u8; len];
+    engine::preview::render_frame(&params, &formula, &config, &mut rgba);
+
+    PREVIEW_CACHE.lock().unwrap_or_else(|e| e.into_inner()).insert(key, rgba.clone());
+    Ok(rgba)
+}
+
+/// Set the preview cache memory budget in bytes (evicts as needed).
+#[wasm_bindgen]
+pub fn set_preview_cache_budget(bytes: u32) {
+    PREVIEW_CACHE.lock().unwrap_or_else(|e| e.into_inner()).set_budget(bytes as usize);
+}
+
+/// Drop all cached previews.
+#[wasm_bindgen]
+pub fn clear_preview_cache() {
+    PREVIEW_CACHE.lock().unwrap_or_else(|e| e.into_inner()).clear();
+}
+
+/// Monte Carlo render pass — adds one path-traced sample per pixel.
+///
+/// `accum` — Float32Array (width * height * 4): summed RGB + sample count per
+/// pixel; zero it to restart. Call repeatedly (from any number of workers with
+/// interleaved scanlines) and display with `mc_resolve`.
+/// Returns the sample count reached by this worker's pixels.
+#[wasm_bindgen]
+pub fn mc_render(
+    render_params: &[f64],
+    formula_ids: &[u32],
+    paint_params: &[f64],
+    accum: &mut [f32],
+    worker_id: u32,
+    worker_count: u32,
+) -> u32 {
+    let params = engine::raymarcher::params_from_buffer(render_params);
+    let formula = cached_formula(render_params, formula_ids, &params);
+    let config = lighting::paint::paint_config_from_buffer(paint_params);
+    engine::montecarlo::render_pass(&params, &formula, &config, accum, worker_id, worker_count)
+}
+
+/// Convert a Monte Carlo accumulation buffer into RGBA bytes.
+#[wasm_bindgen]
+pub fn mc_resolve(accum: &[f32], rgba_out: &mut [u8]) {
+    engine::montecarlo::resolve(accum, rgba_out, false);
+}
+
+/// Like `mc_resolve`, encoding the linear path-traced radiance as sRGB.
+#[wasm_bindgen]
+pub fn mc_resolve_srgb(accum: &[f32], rgba_out: &mut [u8]) {
+    engine::montecarlo::resolve(accum, rgba_out, true);
+}
+
+/// Relight session over one G-buffer: repaints in a weighted sum while
+/// lights are switched, recolored or dimmed (see `lighting::relight`).
+///
+/// Call `update_light` after moving or reshaping a light in `paint_params`,
+/// and create a new session when anything else in the paint parameters changes.
+#[wasm_bindgen]
+pub struct RelightSession {
+    gbuffer: Vec<engine::types::SiLight5>,
+    cache: lighting::relight::RelightCache,
+}
+
+#[wasm_bindgen]
+impl RelightSession {
+    /// Shade the G-buffer once and cache the per-light terms.
+    #[wasm_bindgen(constructor)]
+    pub fn new(gbuffer: &[u8], width: u32, height: u32, paint_params: &[f64]) -> Result<RelightSession, JsError> {
+        let config = lighting::paint::paint_config_from_buffer(paint_params);
+        let gbuffer = engine::gbuffer::view("gbuffer", gbuffer, (width * height) as usize)?.to_vec();
+        let cache = lighting::relight::RelightCache::build(&gbuffer, width, height, &config);
+        Ok(RelightSession { gbuffer, cache })
+    }
+
+    /// Recompute light `index` from `paint_params`.
+    pub fn update_light(&mut self, index: u32, paint_params: &[f64]) {
+        let config = lighting::paint::paint_config_from_buffer(paint_params);
+        self.cache.update_light(&self.gbuffer, index as usize, &config);
+    }
+
+    /// Paint into `rgba_out` with the light switches, colors and amplitudes
+    /// of `paint_params`.
+    pub fn paint(&self, rgba_out: &mut [u8], paint_params: &[f64]) {
+        let config = lighting::paint::paint_config_from_buffer(paint_params);
+        self.cache.paint(rgba_out, &config);
+    }
+}
+
+/// Save a render for relighting later: the G-buffer plus its render,
+/// formula and paint parameters in one compressed blob.
+///
+/// The G-buffer size and record format come from `render_params`; reload
+/// the blob with `LoadedRender`.
+#[wasm_bindgen]
+pub fn save_render(
+    render_params: &[f64],
+    formula_ids: &[u32],
+    paint_params: &[f64],
+    gbuffer: &[u8],
+) -> Result<Vec<u8>, JsError> {
+    let params = engine::raymarcher::params_from_buffer(render_params);
+    let pixel_count = (params.width * params.height) as usize;
+    match params.gbuffer_format {
+        engine::types::GBufferFormat::Packed => {
+            engine::gbuffer::view::<engine::types::SiLight5>("gbuffer", gbuffer, pixel_count)?;
+        }
+        engine::types::GBufferFormat::Extended => {
+            engine::gbuffer::view::<engine::types::SiLight6>("gbuffer", gbuffer, pixel_count)?;
+        }
+    }
+    let saved = export::gbuffer_file::SavedRender {
+        width: params.width,
+        height: params.height,
+        format: params.gbuffer_format,
+        render_params: render_params.to_vec(),
+        formula_ids: formula_ids.to_vec(),
+        paint_params: paint_params.to_vec(),
+        gbuffer: gbuffer[..pixel_count * params.gbuffer_format.record_size()].to_vec(),
+    };
+    Ok(export::gbuffer_file::encode(&saved, 6))
+}
+
+/// A render reloaded from a `save_render` blob.
+///
+/// Pass its buffers back to `paint_gbuffer`, `RelightSession` and friends
+/// to repaint without marching again.
+#[wasm_bindgen]
+pub struct LoadedRender {
+    saved: export::gbuffer_file::SavedRender,
+}
+
+#[wasm_bindgen]
+impl LoadedRender {
+    /// Decode a blob; throws if it is not a saved render or is damaged.
+    #[wasm_bindgen(constructor)]
+    pub fn new(bytes: &[u8]) -> Result<LoadedRender, JsError> {
+        Ok(LoadedRender { saved: export::gbuffer_file::decode(bytes)? })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.saved.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.saved.height
+    }
+
+    pub fn render_params(&self) -> Vec<f64> {
+        self.saved.render_params.clone()
+    }
+
+    pub fn formula_ids(&self) -> Vec<u32> {
+        self.saved.formula_ids.clone()
+    }
+
+    pub fn paint_params(&self) -> Vec<f64> {
+        self.saved.paint_params.clone()
+    }
+
+    /// G-buffer bytes in the format named by `render_params`.
+    pub fn gbuffer(&self) -> Vec<u8> {
+        self.saved.gbuffer.clone()
+    }
+}
+
+/// A classic MB3D parameter file (.m3p) converted to this renderer's buffers.
+///
+/// Settings without an equivalent here (custom formulas, lightmaps,
+/// DE-combined hybrids) are dropped and listed in `warnings`.
+#[wasm_bindgen]
+pub struct M3pScene {
+    file: import::m3p::M3pFile,
+    scene: import::m3p::Scene,
+}
+
+#[wasm_bindgen]
+impl M3pScene {
+    /// Parse and convert an .m3p file; throws if it is not one or predates MB3D 1.7.
+    #[wasm_bindgen(constructor)]
+    pub fn new(bytes: &[u8]) -> Result<M3pScene, JsError> {
+        let file = import::m3p::parse(bytes)?;
+        Ok(M3pScene { file, scene: file.to_scene() })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.scene.render_params[0] as u32
+    }
+
+    pub fn height(&self) -> u32 {
+        self.scene.render_params[1] as u32
+    }
+
+    pub fn render_params(&self) -> Vec<f64> {
+        self.scene.render_params.clone()
+    }
+
+    pub fn formula_ids(&self) -> Vec<u32> {
+        self.scene.formula_ids.clone()
+    }
+
+    pub fn paint_params(&self) -> Vec<f64> {
+        self.scene.paint_params.clone()
+    }
+
+    /// One line per setting that could not be carried over.
+    pub fn warnings(&self) -> Vec<String> {
+        self.scene.warnings.clone()
+    }
+
+    /// Save edited buffers back to .m3p, keeping this file's settings the
+    /// buffers do not carry. Settings MB3D cannot store are logged.
+    pub fn save(&self, render_params: &[f64], formula_ids: &[u32], paint_params: &[f64]) -> Vec<u8> {
+        save_m3p_with(&self.file, render_params, formula_ids, paint_params)
+    }
+}
+
+/// Save a scene as a classic MB3D parameter file (.m3p) for the desktop
+/// version. Settings MB3D cannot store are logged.
+#[wasm_bindgen]
+pub fn save_m3p(render_params: &[f64], formula_ids: &[u32], paint_params: &[f64]) -> Vec<u8> {
+    save_m3p_with(&import::m3p::M3pFile::default(), render_params, formula_ids, paint_params)
+}
+
+fn save_m3p_with(template: &import::m3p::M3pFile, render_params: &[f64], formula_ids: &[u32], paint_params: &[f64]) -> Vec<u8> {
+    let (bytes, warnings) = import::m3p::save(template, render_params, formula_ids, paint_params);
+    for warning in &warnings {
+        log::warn(&format!(".m3p: {warning}"));
+    }
+    bytes
+}
+
+/// A classic MB3D animation project (.m3a): keyframes as `M3pScene`s plus
+/// the frame counts and interpolation between them.
+#[wasm_bindgen]
+pub struct M3aAnimation {
+    animation: import::m3a::Animation,
+}
+
+#[wasm_bindgen]
+impl M3aAnimation {
+    /// Parse an .m3a file; throws if it is damaged or predates MB3D 1.7.
+    #[wasm_bindgen(constructor)]
+    pub fn new(bytes: &[u8]) -> Result<M3aAnimation, JsError> {
+        Ok(M3aAnimation { animation: import::m3a::parse(bytes)? })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.animation.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.animation.height
+    }
+
+    pub fn image_scale(&self) -> u32 {
+        self.animation.image_scale
+    }
+
+    /// 0 = linear, 1 = quadratic Bézier
+    pub fn interpolation(&self) -> u32 {
+        self.animation.interpolation as u32
+    }
+
+    pub fn looped(&self) -> bool {
+        self.animation.looped
+    }
+
+    pub fn keyframe_count(&self) -> usize {
+        self.animation.keyframes.len()
+    }
+
+    /// Frames in the whole animation.
+    pub fn frame_count(&self) -> u32 {
+        self.animation.frame_count()
+    }
+
+    /// Frames from keyframe `index` to the next one.
+    pub fn keyframe_frames(&self, index: usize) -> u32 {
+        self.animation.keyframes.get(index).map_or(0, |k| k.frames)
+    }
+
+    /// Keyframe `frame` starts from and the position toward the next one,
+    /// as [index, t]; empty past the end.
+    pub fn keyframe_at(&self, frame: u32) -> Vec<f64> {
+        self.animation.keyframe_at(frame).map_or(Vec::new(), |(i, t)| vec![i as f64, t])
+    }
+
+    /// Parameters at keyframe `index`.
+    pub fn keyframe(&self, index: usize) -> Option<M3pScene> {
+        let file = self.animation.keyframes.get(index)?.file;
+        Some(M3pScene { file, scene: file.to_scene() })
+    }
+
+    pub fn preview_width(&self, index: usize) -> u32 {
+        self.animation.keyframes.get(index).map_or(0, |k| k.preview.width)
+    }
+
+    pub fn preview_height(&self, index: usize) -> u32 {
+        self.animation.keyframes.get(index).map_or(0, |k| k.preview.height)
+    }
+
+    /// Keyframe thumbnail as RGBA8, top row first.
+    pub fn preview_rgba(&self, index: usize) -> Vec<u8> {
+        self.animation.keyframes.get(index).map_or(Vec::new(), |k| k.preview.rgba.clone())
+    }
+}
+
+/// Palette editor handle over the same `ColorGradient` the painter samples.
+///
+/// Stops are exchanged as flat [pos, r, g, b, ...] arrays, the layout used for
+/// the gradient section of paint_params.
+#[wasm_bindgen]
+pub struct GradientEditor {
+    gradient: lighting::gradient::ColorGradient,
+}
+
+#[wasm_bindgen]
+impl GradientEditor {
+    /// Create from flat stops (empty = default palette).
+    #[wasm_bindgen(constructor)]
+    pub fn new(stops: &[f64]) -> GradientEditor {
+        GradientEditor { gradient: lighting::gradient::ColorGradient::from_flat(stops) }
+    }
+
+    /// Current stops as a flat array.
+    pub fn stops(&self) -> Vec<f64> {
+        self.gradient.to_flat()
+    }
+
+    /// Sampled color at `t` as [r, g, b].
+    pub fn sample(&self, t: f64) -> Vec<f64> {
+        let (r, g, b) = self.gradient.sample(t);
+        vec![r, g, b]
+    }
+
+    /// Insert a stop at `t` with the sampled color; returns its index.
+    pub fn insert_stop(&mut self, t: f64) -> u32 {
+        self.gradient.insert_stop(t) as u32
+    }
+
+    /// Remove the stop nearest to `t`; returns false if only one stop is left.
+    pub fn remove_nearest(&mut self, t: f64) -> bool {
+        self.gradient.remove_nearest(t)
+    }
+
+    pub fn distribute_evenly(&mut self) {
+        self.gradient.distribute_evenly();
+    }
+
+    pub fn reverse(&mut self) {
+        self.gradient.reverse();
+    }
+
+    /// Blending space for `sample`: 0 RGB, 1 OKLab, 2 HSV.
+    pub fn set_interpolation(&mut self, space: u32) {
+        self.gradient.interpolation = lighting::gradient::Interpolation::from_u32(space);
+    }
+}
+
+/// Import a .ugr, .map or .gpl palette file (format detected from the
+/// contents) as flat gradient stops [pos, r, g, b, ...].
+///
+/// `entry` selects the gradient of a .ugr collection. Returns an empty array
+/// if the file holds no colors.
+#[wasm_bindgen]
+pub fn import_palette(bytes: &[u8], entry: u32) -> Vec<f64> {
+    lighting::gradient::ColorGradient::from_palette_file(bytes, entry as usize)
+        .map(|g| g.to_flat())
+        .unwrap_or_default()
+}
+
+/// Resumable progressive renderer for interactive navigation.
+///
+/// Holds the parsed scene and a refinement cursor; call `step` repeatedly with
+/// a pixel budget and paint the G-buffer between calls.
+#[wasm_bindgen]
+pub struct ProgressiveRender {
+    params: engine::raymarcher::RenderParams,
+    formula: formulas::hybrid::HybridFormula,
+    state: engine::progressive::ProgressiveState,
+    /// Cost model for `advance_render`
+    rays_per_ms: f64,
+}
+
+#[wasm_bindgen]
+impl ProgressiveRender {
+    /// `start_block` — pixel size of the first, coarsest pass (e.g. 8)
+    #[wasm_bindgen(constructor)]
+    pub fn new(render_params: &[f64], formula_ids: &[u32], start_block: u32) -> ProgressiveRender {
+        let params = engine::raymarcher::params_from_buffer(render_params);
+        let formula = build_formula(render_params, formula_ids, &params);
+        let state = engine::progressive::ProgressiveState::new(start_block);
+        ProgressiveRender { params, formula, state, rays_per_ms: 200.0 }
+    }
+
+    /// Render up to `max_pixels` rays into `gbuffer`. Returns true when finished.
+    pub fn step(&mut self, gbuffer: &mut [u8], max_pixels: u32) -> Result<bool, JsError> {
+        let pixel_count = (self.params.width * self.params.height) as usize;
+        let gbuf_pixels = engine::gbuffer::view_mut("gbuffer", gbuffer, pixel_count)?;
+        self.state.step(&self.params, &self.formula, gbuf_pixels, max_pixels);
+        Ok(self.state.done)
+    }
+
+    /// Spend a frame budget of `ms_budget` and return the completion fraction.
+    ///
+    /// The budget is converted to rays with the fixed `rays_per_ms` cost model
+    /// (not the clock), so progression is identical on every run.
+    pub fn advance_render(&mut self, gbuffer: &mut [u8], ms_budget: f64) -> Result<f64, JsError> {
+        let pixel_count = (self.params.width * self.params.height) as usize;
+        let gbuf_pixels = engine::gbuffer::view_mut("gbuffer", gbuffer, pixel_count)?;
+        Ok(self.state.advance(&self.params, &self.formula, gbuf_pixels, ms_budget, self.rays_per_ms))
+    }
+
+    /// Set the cost model used by `advance_render` (default 200 rays per ms).
+    pub fn set_rays_per_ms(&mut self, rays_per_ms: f64) {
+        self.rays_per_ms = rays_per_ms.max(0.0);
+    }
+
+    /// Completion fraction in [0, 1].
+    pub fn progress(&self) -> f64 {
+        self.state.fraction(self.params.width * self.params.height)
+    }
+
+    /// Block size of the pass in progress (1 = full resolution).
+    pub fn block_size(&self) -> u32 {
+        self.state.block
+    }
+
+    /// Restart from the coarsest pass.
+    pub fn reset(&mut self) {
+        self.state.reset();
+    }
+}
+
+/// Scene graph of formula stacks, each with its own transform and material id.
+///
+/// Objects are traced as one distance field, so shadow rays from one object
+/// test all the others. Pair with paint material gradients (paint section 5).
+#[wasm_bindgen]
+pub struct CompositeScene {
+    scene: engine::composite::Scene,
+}
+
+#[wasm_bindgen]
+impl CompositeScene {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> CompositeScene {
+        CompositeScene { scene: engine::composite::Scene::default() }
+    }
+
+    /// Add an object; returns its index.
+    ///
+    /// `render_params` supplies the global julia settings for this object's
+    /// formula; `transform` is `[px, py, pz, rotation (9, row-major), scale]`.
+    pub fn add_object(&mut self, render_params: &[f64], formula_ids: &[u32], transform: &[f64], material_id: u8) -> u32 {
+        let params = engine::raymarcher::params_from_buffer(render_params);
+        self.scene.objects.push(engine::composite::SceneObject {
+            formula: build_formula(render_params, formula_ids, &params),
+            transform: engine::composite::ObjectTransform::from_slice(transform),
+            material: material_id,
+        });
+        self.scene.objects.len() as u32 - 1
+    }
+
+    pub fn object_count(&self) -> u32 {
+        self.scene.objects.len() as u32
+    }
+
+    /// Render interleaved scanlines; shadow rays go toward the lights in `paint_params`.
+    pub fn render_scanlines(
+        &self,
+        render_params: &[f64],
+        paint_params: &[f64],
+        gbuffer: &mut [u8],
+        worker_id: u32,
+        worker_count: u32,
+    ) -> Result<u32, JsError> {
+        let params = engine::raymarcher::params_from_buffer(render_params);
+        let lights = lighting::paint::paint_config_from_buffer(paint_params).lights;
+        let pixel_count = (params.width * params.height) as usize;
+        let gbuf_pixels = engine::gbuffer::view_mut("gbuffer", gbuffer, pixel_count)?;
+        Ok(engine::composite::render_scene_scanlines(&params, &self.scene, &lights, gbuf_pixels, worker_id, worker_count))
+    }
+}
+
+impl Default for CompositeScene {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Upload a grayscale (1 byte/pixel) or RGBA image for the Heightfield formula.
+///
+/// Returns the handle to pass as the formula's first parameter, or u32::MAX if
+/// the pixel data does not match the dimensions. Must be called in every worker.
+#[wasm_bindgen]
+pub fn register_height_image(pixels: &[u8], width: u32, height: u32) -> u32 {
+    match formulas::heightfield::HeightImage::from_bytes(pixels, width, height) {
+        Some(image) => formulas::heightfield::register_image(image),
+        None => u32::MAX,
+    }
+}
+
+/// Upload a 16-bit grayscale heightmap (1 sample/pixel) for the Heightfield
+/// formula; terrain exports are usually 16-bit, which 8-bit images terrace.
+///
+/// Returns the handle, or u32::MAX if `samples` is too short. Must be called in every worker.
+#[wasm_bindgen]
+pub fn register_height_image16(samples: &[u16], width: u32, height: u32) -> u32 {
+    match formulas::heightfield::HeightImage::from_u16(samples, width, height) {
+        Some(image) => formulas::heightfield::register_image(image),
+        None => u32::MAX,
+    }
+}
+
+/// Release an image uploaded with `register_height_image` or `register_height_image16`.
+#[wasm_bindgen]
+pub fn release_height_image(handle: u32) {
+    formulas::heightfield::release_image(handle);
+    // The handle may be reused for different data
+    ARTIFACTS.clear();
+    PREVIEW_CACHE.lock().unwrap_or_else(|e| e.into_inner()).clear();
+}
+
+/// Upload an 8-bit RGBA equirectangular environment map for image-based lighting.
+///
+/// Returns the handle for the paint environment section, or u32::MAX if the
+/// pixel data does not match the dimensions. Must be called in every worker
+/// that paints.
+#[wasm_bindgen]
+pub fn register_environment_rgba8(pixels: &[u8], width: u32, height: u32) -> u32 {
+    match lighting::envmap::EnvironmentMap::from_rgba8(pixels, width, height) {
+        Some(map) => lighting::envmap::register_map(map),
+        None => u32::MAX,
+    }
+}
+
+/// Upload an HDR equirectangular environment map as float RGB triples.
+#[wasm_bindgen]
+pub fn register_environment_hdr(rgb: &[f32], width: u32, height: u32) -> u32 {
+    match lighting::envmap::EnvironmentMap::from_rgb_f32(rgb, width, height) {
+        Some(map) => lighting::envmap::register_map(map),
+        None => u32::MAX,
+    }
+}
+
+/// Release an environment map uploaded with `register_environment_*`.
+#[wasm_bindgen]
+pub fn release_environment(handle: u32) {
+    lighting::envmap::release_map(handle);
+    // The handle may be reused for different data
+    PREVIEW_CACHE.lock().unwrap_or_else(|e| e.into_inner()).clear();
+}
+
+/// Upload an 8-bit RGBA image for triplanar surface texturing.
+///
+/// Returns the handle for the paint texture section, or u32::MAX if the
+/// pixel data does not match the dimensions. Must be called in every worker
+/// that paints.
+#[wasm_bindgen]
+pub fn register_texture_rgba8(pixels: &[u8], width: u32, height: u32) -> u32 {
+    match lighting::texture::TextureImage::from_rgba8(pixels, width, height) {
+        Some(image) => lighting::texture::register_texture(image),
+        None => u32::MAX,
+    }
+}
+
+/// Release a texture uploaded with `register_texture_rgba8`.
+#[wasm_bindgen]
+pub fn release_texture(handle: u32) {
+    lighting::texture::release_texture(handle);
+    // The handle may be reused for different data
+    PREVIEW_CACHE.lock().unwrap_or_else(|e| e.into_inner()).clear();
+}
+
+/// Upload glyph paths for the Text formula.
+///
+/// `paths` layout: [num_paths, (num_points, x0, y0, x1, y1, ...)*] in font units.
+/// Returns the handle to pass as the formula's first parameter, or u32::MAX if
+/// the buffer is truncated. Must be called in every worker.
+#[wasm_bindgen]
+pub fn register_text_paths(paths: &[f64]) -> u32 {
+    match formulas::text::GlyphPaths::from_buffer(paths) {
+        Some(glyphs) => formulas::text::register_glyphs(glyphs),
+        None => u32::MAX,
+    }
+}
+
+/// Release paths uploaded with `register_text_paths`.
+#[wasm_bindgen]
+pub fn release_text_paths(handle: u32) {
+    formulas::text::release_glyphs(handle);
+    // The handle may be reused for different data
+    ARTIFACTS.clear();
+    PREVIEW_CACHE.lock().unwrap_or_else(|e| e.into_inner()).clear();
+}
+
+/// Section tag: DEmixer weight curve `[start, end, exponent]` (slot ignored).
+const SECTION_MIXER_CURVE: u32 = 1;
+/// Section tag: formula parameters for `slot`, in the formula's declaration order.
+const SECTION_FORMULA_PARAMS: u32 = 2;
+/// Section tag: merge `slot` as a DE object `[mode, smooth_k]` (see `CombineMode::from_u32`).
+const SECTION_SLOT_COMBINE: u32 = 3;
+/// Section tag: julia override for `slot` `[enabled, cx, cy, cz]` (0 = mandel, 1 = julia).
+const SECTION_SLOT_JULIA: u32 = 4;
+/// Section tag: initial orbit offset `[x, y, z]` added to z0 (slot ignored).
+const SECTION_Z0_OFFSET: u32 = 5;
+/// Section tag: buffer layout stamp `[magic, version]` (slot ignored, see `engine::validate`).
+const SECTION_LAYOUT_VERSION: u32 = 6;
+
+/// `formula_ids` with its z0 offset section replaced by `offset` (left out
+/// when the offset is zero).
+pub(crate) fn with_z0_offset_section(formula_ids: &[u32], offset: &engine::types::Vec3D) -> Vec<u32> {
+    let mut decoded = formulas::FormulaIds::decode(formula_ids);
+    if decoded.mode.is_none() {
+        return formula_ids.to_vec();
+    }
+    decoded.sections.retain(|section| section.tag != SECTION_Z0_OFFSET);
+    if *offset != engine::types::Vec3D::default() {
+        decoded.sections.push(formulas::ParamSection {
+            tag: SECTION_Z0_OFFSET,
+            slot: 0,
+            values: vec![offset.x, offset.y, offset.z],
+        });
+    }
+    decoded.encode()
+}
+
+/// Build the scene formula: formula_ids plus the global julia setting from render_params.
+fn build_formula(
+    render_params: &[f64],
+    formula_ids: &[u32],
+    params: &engine::raymarcher::RenderParams,
+) -> formulas::hybrid::HybridFormula {
+    build_formula_from_ids(formula_ids, params.max_iterations, params.bailout)
+        .with_julia(engine::raymarcher::julia_from_buffer(render_params))
+}
+
+static ARTIFACTS: engine::artifacts::ArtifactCache =
+    engine::artifacts::ArtifactCache::new(engine::artifacts::DEFAULT_CAPACITY);
+
+/// `build_formula` through the artifact cache, keyed by the buffers the
+/// formula depends on (so camera moves keep the compiled formula).
+fn cached_formula(
+    render_params: &[f64],
+    formula_ids: &[u32],
+    params: &engine::raymarcher::RenderParams,
+) -> std::sync::Arc<formulas::hybrid::HybridFormula> {
+    // max_iterations, bailout and the julia block (slots 17..24)
+    let formula_params = render_params.get(17..24).unwrap_or(&[]);
+    let key = engine::repro::scene_hash(formula_params, formula_ids);
+    ARTIFACTS.get_or_build(key, || build_formula(render_params, formula_ids, params))
+}
+
+/// Drop all cached scene artifacts (compiled formulas, ...).
+#[wasm_bindgen]
+pub fn clear_artifact_cache() {
+    ARTIFACTS.clear();
+}
+
+/// Build a HybridFormula from the formula_ids array (see `formulas::FormulaIds`
+/// for the layout). Hybrid mode 0 = alternating, 1 = interpolated, 2 = 4D;
+/// no slots give a single Mandelbulb power 8.
+fn build_formula_from_ids(
+    formula_ids: &[u32],
+    max_iterations: u32,
+    bailout: f64,
+) -> formulas::hybrid::HybridFormula {
+    use formulas::{FormulaId, hybrid::HybridMode};
+
+    let decoded = formulas::FormulaIds::decode(formula_ids);
+    let mut slots = decoded.slots;
+    if slots.is_empty() {
+        slots.push((FormulaId::MandelbulbPower8, 1));
+    }
+    let hybrid_mode = match decoded.mode {
+        Some(1) => HybridMode::Interpolated,
+        Some(2) => HybridMode::FourD,
+        _ => HybridMode::Alternating,
+    };
+
+    let mut formula = formulas::hybrid::HybridFormula::new(&slots, hybrid_mode, max_iterations, bailout);
+
+    for section in &decoded.sections {
+        match section.tag {
+            SECTION_MIXER_CURVE if section.values.len() >= 3 => {
+                formula = formula.with_mixer(formulas::hybrid::DeMixerCurve {
+                    start: section.values[0],
+                    end: section.values[1],
+                    exponent: section.values[2],
+                });
+            }
+            SECTION_FORMULA_PARAMS => {
+                formula.set_slot_params(section.slot as usize, &section.values);
+            }
+            SECTION_SLOT_COMBINE if !section.values.is_empty() => {
+                let mode = formulas::hybrid::CombineMode::from_u32(
+                    section.values[0] as u32,
+                    section.values.get(1).copied().unwrap_or(0.0),
+                );
+                formula.set_slot_combine(section.slot as usize, mode);
+            }
+            SECTION_SLOT_JULIA if !section.values.is_empty() => {
+                let julia = match section.values.as_slice() {
+                    [enabled, x, y, z, ..] if *enabled != 0.0 => {
+                        formulas::hybrid::SlotJulia::Julia(engine::types::Vec3D { x: *x, y: *y, z: *z })
+                    }
+                    _ => formulas::hybrid::SlotJulia::Mandel,
+                };
+                formula.set_slot_julia(section.slot as usize, julia);
+            }
+            SECTION_Z0_OFFSET if section.values.len() >= 3 => {
+                formula = formula.with_z0_offset(engine::types::Vec3D {
+                    x: section.values[0],
+                    y: section.values[1],
+                    z: section.values[2],
+                });
+            }
+            _ => {}
+        }
+    }
+
+    formula
+}
+
+/// Documentation for formula `id` as a plain object:
+/// `{ name, mb3dName, notes, bailout, stepWidth }`.
+#[wasm_bindgen]
+pub fn formula_info(id: u32) -> js_sys::Object {
+    let info = formulas::FormulaId::from_u32(id).info();
+    let obj = js_sys::Object::new();
+    let fields: [(&str, JsValue); 5] = [
+        ("name", info.name.into()),
+        ("mb3dName", info.mb3d_name.into()),
+        ("notes", info.notes.into()),
+        ("bailout", info.bailout.into()),
+        ("stepWidth", info.step_width.into()),
+    ];
+    for (key, value) in fields {
+        let _ = js_sys::Reflect::set(&obj, &key.into(), &value);
+    }
+    obj
+}
+
+/// Pick the surface under pixel (x, y) at full precision.
+///
+/// Returns `{ hit, position: [x, y, z], distance, normal: [x, y, z], de }`
+/// for click-to-center navigation and setting the Julia seed from a surface
+/// point; fractional coordinates address sub-pixel positions.
+#[wasm_bindgen]
+pub fn pick_pixel(render_params: &[f64], formula_ids: &[u32], x: f64, y: f64) -> js_sys::Object {
+    let params = engine::raymarcher::params_from_buffer(render_params);
+    let formula = cached_formula(render_params, formula_ids, &params);
+    let pick = engine::raymarcher::pick_pixel(&params, &*formula, x, y);
+    let vec = |v: engine::types::Vec3D| JsValue::from(js_sys::Float64Array::from(&[v.x, v.y, v.z][..]));
+    let obj = js_sys::Object::new();
+    let fields: [(&str, JsValue); 5] = [
+        ("hit", pick.hit.into()),
+        ("position", vec(pick.position)),
+        ("distance", pick.distance.into()),
+        ("normal", vec(pick.normal)),
+        ("de", pick.de.into()),
+    ];
+    for (key, value) in fields {
+        let _ = js_sys::Reflect::set(&obj, &key.into(), &value);
+    }
+    obj
+}
+
+/// Distance estimate of the configured hybrid formula at a world position.
+///
+/// Used for autofocus, collision-aware fly-through and external tooling.
+#[wasm_bindgen]
+pub fn de_at_point(render_params: &[f64], formula_ids: &[u32], x: f64, y: f64, z: f64) -> f64 {
+    let params = engine::raymarcher::params_from_buffer(render_params);
+    let formula = cached_formula(render_params, formula_ids, &params);
+    formulas::DistanceField::compute_de(&*formula, &engine::types::Vec3D { x, y, z }).de
+}
+
+/// Signed distance at a world position: negative inside the fractal, where
+/// the interior distance is estimated by probing for the nearest outside point.
+#[wasm_bindgen]
+pub fn signed_de_at_point(render_params: &[f64], formula_ids: &[u32], x: f64, y: f64, z: f64) -> f64 {
+    let params = engine::raymarcher::params_from_buffer(render_params);
+    let formula = cached_formula(render_params, formula_ids, &params);
+    formulas::DistanceField::signed_de(&*formula, &engine::types::Vec3D { x, y, z })
+}
+
+/// Unit gradient of the distance field at a world position, as `[x, y, z]`.
+#[wasm_bindgen]
+pub fn normal_at_point(render_params: &[f64], formula_ids: &[u32], x: f64, y: f64, z: f64) -> Vec<f64> {
+    let params = engine::raymarcher::params_from_buffer(render_params);
+    let formula = cached_formula(render_params, formula_ids, &params);
+    let n = engine::raymarcher::normal_at_point(&params, &*formula, &engine::types::Vec3D { x, y, z });
+    vec![n.x, n.y, n.z]
+}
+
+/// Triangle mesh of the fractal surface for printing or DCC tools.
+#[wasm_bindgen]
+pub struct SurfaceMesh {
+    mesh: engine::mesh::Mesh,
+}
+
+#[wasm_bindgen]
+impl SurfaceMesh {
+    /// Mesh the surface inside `bounds` (`[min xyz, max xyz]`) by dual
+    /// contouring on an adaptive octree with 2^`depth` cells per axis
+    /// (1..=9). A non-zero `triangle_budget` collapses flat regions until the
+    /// mesh fits, keeping creases and corners for last.
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        render_params: &[f64],
+        formula_ids: &[u32],
+        bounds: &[f64],
+        depth: u32,
+        triangle_budget: u32,
+    ) -> Result<SurfaceMesh, JsError> {
+        let params = engine::raymarcher::params_from_buffer(render_params);
+        let formula = cached_formula(render_params, formula_ids, &params);
+        let corner = |i: usize| engine::types::Vec3D {
+            x: bounds.get(i).copied().unwrap_or(f64::NAN),
+            y: bounds.get(i + 1).copied().unwrap_or(f64::NAN),
+            z: bounds.get(i + 2).copied().unwrap_or(f64::NAN),
+        };
+        let settings = engine::mesh::MeshSettings {
+            min: corner(0),
+            max: corner(3),
+            depth,
+            triangle_budget: triangle_budget as usize,
+        };
+        Ok(SurfaceMesh { mesh: engine::mesh::dual_contour(&*formula, &settings)? })
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.mesh.positions.len()
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.mesh.triangles.len()
+    }
+
+    /// Vertex positions, `[x, y, z]` per vertex.
+    pub fn positions(&self) -> Vec<f32> {
+        self.mesh.positions.concat()
+    }
+
+    /// Unit vertex normals, `[x, y, z]` per vertex.
+    pub fn normals(&self) -> Vec<f32> {
+        self.mesh.normals.concat()
+    }
+
+    /// Three vertex indices per triangle, counter-clockwise from outside.
+    pub fn indices(&self) -> Vec<u32> {
+        self.mesh.triangles.concat()
+    }
+
+    /// The mesh as a binary STL file.
+    pub fn to_stl(&self) -> Vec<u8> {
+        self.mesh.to_stl()
+    }
+}
+
+/// The distance field (or occupancy) sampled on a regular grid, for volume
+/// workflows in Blender, Houdini or MagicaVoxel.
+#[wasm_bindgen]
+pub struct VolumeGrid {
+    volume: export::volume::Volume,
+}
+
+#[wasm_bindgen]
+impl VolumeGrid {
+    /// Sample `bounds` (`[min xyz, max xyz]`) at `nx` × `ny` × `nz` voxel
+    /// centers (each 1..=1024). `kind`: 0 signed distance, 1 occupancy.
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        render_params: &[f64],
+        formula_ids: &[u32],
+        bounds: &[f64],
+        nx: u32,
+        ny: u32,
+        nz: u32,
+        kind: u32,
+    ) -> Result<VolumeGrid, JsError> {
+        let params = engine::raymarcher::params_from_buffer(render_params);
+        let formula = cached_formula(render_params, formula_ids, &params);
+        let corner = |i: usize| engine::types::Vec3D {
+            x: bounds.get(i).copied().unwrap_or(f64::NAN),
+            y: bounds.get(i + 1).copied().unwrap_or(f64::NAN),
+            z: bounds.get(i + 2).copied().unwrap_or(f64::NAN),
+        };
+        let kind = export::volume::VolumeKind::from_u32(kind);
+        Ok(VolumeGrid { volume: export::volume::sample(&*formula, corner(0), corner(3), [nx, ny, nz], kind)? })
+    }
+
+    /// JSON header for `raw`: size, bounds, voxel size, kind and sample layout.
+    pub fn header_json(&self) -> String {
+        self.volume.header_json()
+    }
+
+    /// Voxels as little-endian float32 bytes, x fastest.
+    pub fn raw(&self) -> Vec<u8> {
+        self.volume.raw()
+    }
+
+    /// Voxel values, x fastest.
+    pub fn voxels(&self) -> Vec<f32> {
+        self.volume.voxels.clone()
+    }
+
+    /// MagicaVoxel .vox file; throws unless the grid is occupancy of at most 256³.
+    pub fn to_vox(&self) -> Result<Vec<u8>, JsError> {
+        Ok(self.volume.to_vox()?)
+    }
+}
+
+/// Keyframed camera path with animated render parameters.
+#[wasm_bindgen]
+pub struct AnimationTimeline {
+    timeline: engine::animation::Timeline,
+}
+
+#[wasm_bindgen]
+impl AnimationTimeline {
+    /// Parse a timeline description (see `engine::animation`): `fov` in
+    /// degrees, `param_slots` and `keyframes` with `time`, `position`,
+    /// `orientation` or `target`/`up`, `zoom`, `params`, `z0_offset` and
+    /// `easing`.
+    #[wasm_bindgen(constructor)]
+    pub fn new(json: &str) -> Result<AnimationTimeline, JsError> {
+        Ok(AnimationTimeline { timeline: engine::animation::Timeline::from_json(json)? })
+    }
+
+    /// Time of the first keyframe.
+    pub fn start(&self) -> f64 {
+        self.timeline.start()
+    }
+
+    /// Time of the last keyframe.
+    pub fn end(&self) -> f64 {
+        self.timeline.end()
+    }
+
+    /// `render_params` with the camera (slots 2..14) and the animated params
+    /// of time `t` written in.
+    pub fn frame_render_params(&self, t: f64, render_params: &[f64]) -> Vec<f64> {
+        self.timeline.frame_render_params(t, render_params)
+    }
+
+    /// `formula_ids` with the z0 offset of time `t` (unchanged unless a
+    /// keyframe sets `z0_offset`).
+    pub fn frame_formula_ids(&self, t: f64, formula_ids: &[u32]) -> Vec<u32> {
+        self.timeline.frame_formula_ids(t, formula_ids)
+    }
+
+    /// Render the frame at time `t` into `rgba_out`, like `render_quick`
+    /// (without its deprecation warning).
+    pub fn render_animation_frame(
+        &self,
+        t: f64,
+        render_params: &[f64],
+        formula_ids: &[u32],
+        paint_params: &[f64],
+        rgba_out: &mut [u8],
+    ) {
+        quick_from_buffers(
+            &self.timeline.frame_render_params(t, render_params),
+            &self.timeline.frame_formula_ids(t, formula_ids),
+            paint_params,
+            rgba_out,
+        );
+    }
+}
+
+/// Exponential zoom toward a point with march settings that follow it.
+#[wasm_bindgen]
+pub struct ZoomAnimation {
+    zoom: engine::animation::ZoomAnimation,
+}
+
+#[wasm_bindgen]
+impl ZoomAnimation {
+    /// Parse `{ target, zoom_start, zoom_end, detail, step_width_start,
+    /// step_width_end, easing }` (see `engine::animation::ZoomAnimation`).
+    #[wasm_bindgen(constructor)]
+    pub fn new(json: &str) -> Result<ZoomAnimation, JsError> {
+        Ok(ZoomAnimation { zoom: engine::animation::ZoomAnimation::from_json(json)? })
+    }
+
+    /// Zoom factor at `t` in [0, 1].
+    pub fn zoom_at(&self, t: f64) -> f64 {
+        self.zoom.zoom_at(t)
+    }
+
+    /// `render_params` (the view at zoom 1) with the camera position,
+    /// `de_stop`, step width and `max_ray_length` of time `t` written in.
+    pub fn frame_render_params(&self, t: f64, render_params: &[f64]) -> Vec<f64> {
+        self.zoom.frame_render_params(t, render_params)
+    }
+}
+
+/// Morph between two JSON scene descriptions (see `engine::morph`).
+#[wasm_bindgen]
+pub struct SceneMorph {
+    morph: engine::morph::SceneMorph,
+}
+
+#[wasm_bindgen]
+impl SceneMorph {
+    /// `easings` maps JSON pointers into the description to easing names
+    /// (`linear`, `ease_in`, `ease_out`, `ease_in_out`, `hold`); the longest
+    /// matching prefix applies and `""` sets the default. Empty = linear.
+    #[wasm_bindgen(constructor)]
+    pub fn new(from: &str, to: &str, easings: &str) -> Result<SceneMorph, JsError> {
+        Ok(SceneMorph { morph: engine::morph::SceneMorph::from_json(from, to, easings)? })
+    }
+
+    /// JSON scene description at `t` in [0, 1].
+    pub fn scene_json(&self, t: f64) -> String {
+        self.morph.scene(t).to_json()
+    }
+
+    /// Buffers of the scene at `t` in [0, 1].
+    pub fn buffers(&self, t: f64) -> Result<SceneBuffers, JsError> {
+        Ok(SceneBuffers { buffers: self.morph.scene(t).to_buffers()? })
+    }
+
+    /// JSON array of the pointers that are interpolated.
+    pub fn pointers_json(&self) -> String {
+        serde_json::to_string(&self.morph.pointers().collect::<Vec<_>>()).unwrap_or_else(|_| "[]".into())
+    }
+}
+
+/// MB3D-style automatic `de_stop`: `detail` pixels on the focus plane at
+/// distance `1 / zoom` (`fov` horizontal, radians). Set render_params slot
+/// 109 to the detail level instead to have it applied to the camera in use.
+#[wasm_bindgen]
+pub fn auto_de_stop(zoom: f64, fov: f64, width: u32, detail: f64) -> f64 {
+    engine::raymarcher::auto_de_stop(zoom, fov, width, detail)
+}
+
+/// Derive the view ray basis from a look-at camera.
+///
+/// `pos`, `target` and `up` are `[x, y, z]`; `fov` is the vertical field of
+/// view in radians. Returns `[ray_dir_base, ray_dx, ray_dy]` (9 values) for
+/// render_params slots 5..14, so frontends need not duplicate the math.
+#[wasm_bindgen]
+pub fn compute_camera_rays(pos: &[f64], target: &[f64], up: &[f64], fov: f64, width: u32, height: u32) -> Vec<f64> {
+    let vec = |v: &[f64]| engine::types::Vec3D {
+        x: v.first().copied().unwrap_or(0.0),
+        y: v.get(1).copied().unwrap_or(0.0),
+        z: v.get(2).copied().unwrap_or(0.0),
+    };
+    engine::camera::compute_camera_rays(&vec(pos), &vec(target), &vec(up), fov, width, height)
+        .to_array()
+        .to_vec()
+}
@@ -0,0 +1,255 @@
+//! Environment lightmap (image-based lighting) — MB3D's "LightMap" option.
+//!
+//! An equirectangular image around the scene (+Y up, u = 0.5 looking down +Z)
+//! is sampled twice per surface pixel: a pre-convolved irradiance map by the
+//! surface normal for diffuse light, and the image itself by the reflection
+//! vector for specular light. Images are uploaded once into a registry and
+//! referenced by handle from the paint parameters, like height images.
+
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use crate::engine::types::Vec3D;
+use crate::formulas::resources::Registry;
+use crate::math::utils;
+
+/// Size of the diffuse irradiance map (texels, 2:1).
+const IRRADIANCE_WIDTH: u32 = 32;
+const IRRADIANCE_HEIGHT: u32 = 16;
+
+/// Equirectangular RGB texels in linear light.
+#[derive(Clone, Debug)]
+pub struct LatLong {
+    pub width: u32,
+    pub height: u32,
+    pub texels: Vec<[f32; 3]>,
+}
+
+impl LatLong {
+    #[inline]
+    fn at(&self, x: i64, y: i64) -> [f32; 3] {
+        let w = self.width as i64;
+        let x = x.rem_euclid(w);
+        let y = y.clamp(0, self.height as i64 - 1);
+        self.texels[(y * w + x) as usize]
+    }
+
+    /// Bilinear sample in direction `dir` (need not be normalized);
+    /// wraps around horizontally.
+    pub fn sample(&self, dir: &Vec3D, rotation: f64) -> (f64, f64, f64) {
+        let (u, v) = direction_to_uv(dir, rotation);
+        let fx = u * self.width as f64 - 0.5;
+        let fy = v * self.height as f64 - 0.5;
+        let (x0, y0) = (fx.floor() as i64, fy.floor() as i64);
+        let (tx, ty) = ((fx - x0 as f64) as f32, (fy - y0 as f64) as f32);
+        let mut out = [0.0f32; 3];
+        for (c, o) in out.iter_mut().enumerate() {
+            let top = utils::lerpf(self.at(x0, y0)[c], self.at(x0 + 1, y0)[c], tx);
+            let bottom = utils::lerpf(self.at(x0, y0 + 1)[c], self.at(x0 + 1, y0 + 1)[c], tx);
+            *o = utils::lerpf(top, bottom, ty);
+        }
+        (out[0] as f64, out[1] as f64, out[2] as f64)
+    }
+
+    /// Box-filtered copy at a lower resolution.
+    fn downsampled(&self, width: u32, height: u32) -> Self {
+        let mut texels = Vec::with_capacity((width * height) as usize);
+        for y in 0..height {
+            let (y0, y1) = span(y, height, self.height);
+            for x in 0..width {
+                let (x0, x1) = span(x, width, self.width);
+                let mut sum = [0.0f32; 3];
+                for sy in y0..y1 {
+                    for sx in x0..x1 {
+                        let t = self.texels[(sy * self.width + sx) as usize];
+                        sum = [sum[0] + t[0], sum[1] + t[1], sum[2] + t[2]];
+                    }
+                }
+                let n = ((y1 - y0) * (x1 - x0)) as f32;
+                texels.push([sum[0] / n, sum[1] / n, sum[2] / n]);
+            }
+        }
+        Self { width, height, texels }
+    }
+}
+
+/// Source texel range covered by destination texel `i` (at least one texel).
+fn span(i: u32, dst: u32, src: u32) -> (u32, u32) {
+    let start = (i as u64 * src as u64 / dst as u64) as u32;
+    let end = ((i as u64 + 1) * src as u64 / dst as u64) as u32;
+    (start.min(src - 1), end.max(start + 1).min(src))
+}
+
+/// Equirectangular coordinates in [0, 1]² of a direction, rotated about +Y.
+pub fn direction_to_uv(dir: &Vec3D, rotation: f64) -> (f64, f64) {
+    let len = (dir.x * dir.x + dir.y * dir.y + dir.z * dir.z).sqrt();
+    if len == 0.0 {
+        return (0.5, 0.5);
+    }
+    let u = (dir.x.atan2(dir.z) + rotation) / (2.0 * PI) + 0.5;
+    let v = utils::clamp(dir.y / len, -1.0, 1.0).acos() / PI;
+    (u.rem_euclid(1.0), v)
+}
+
+/// Unit direction through the center of equirectangular coordinates (u, v).
+fn uv_to_direction(u: f64, v: f64) -> Vec3D {
+    let phi = (u - 0.5) * 2.0 * PI;
+    let theta = v * PI;
+    Vec3D { x: theta.sin() * phi.sin(), y: theta.cos(), z: theta.sin() * phi.cos() }
+}
+
+/// Environment image with its diffuse convolution.
+#[derive(Clone, Debug)]
+pub struct EnvironmentMap {
+    /// Incoming light by direction (specular lookups)
+    pub radiance: LatLong,
+    /// Cosine-weighted hemisphere average by normal (diffuse lookups);
+    /// a white environment of value 1 gives irradiance 1
+    pub irradiance: LatLong,
+}
+
+impl EnvironmentMap {
+    /// Build from linear RGB texels and precompute the irradiance map.
+    pub fn new(radiance: LatLong) -> Self {
+        let source = radiance.downsampled(IRRADIANCE_WIDTH, IRRADIANCE_HEIGHT);
+        let (w, h) = (source.width, source.height);
+        // Direction and solid angle of every source texel
+        let texel_area = (2.0 * PI / w as f64) * (PI / h as f64);
+        let samples: Vec<(Vec3D, f64, [f32; 3])> = (0..w * h)
+            .map(|i| {
+                let (u, v) = (((i % w) as f64 + 0.5) / w as f64, ((i / w) as f64 + 0.5) / h as f64);
+                let omega = texel_area * (v * PI).sin();
+                (uv_to_direction(u, v), omega, source.texels[i as usize])
+            })
+            .collect();
+
+        let texels = (0..IRRADIANCE_WIDTH * IRRADIANCE_HEIGHT)
+            .map(|i| {
+                let u = ((i % IRRADIANCE_WIDTH) as f64 + 0.5) / IRRADIANCE_WIDTH as f64;
+                let v = ((i / IRRADIANCE_WIDTH) as f64 + 0.5) / IRRADIANCE_HEIGHT as f64;
+                let n = uv_to_direction(u, v);
+                let mut sum = [0.0f64; 3];
+                for (dir, omega, l) in &samples {
+                    let w = (n.x * dir.x + n.y * dir.y + n.z * dir.z).max(0.0) * omega;
+                    sum = [sum[0] + l[0] as f64 * w, sum[1] + l[1] as f64 * w, sum[2] + l[2] as f64 * w];
+                }
+                [(sum[0] / PI) as f32, (sum[1] / PI) as f32, (sum[2] / PI) as f32]
+            })
+            .collect();
+
+        Self {
+            radiance,
+            irradiance: LatLong { width: IRRADIANCE_WIDTH, height: IRRADIANCE_HEIGHT, texels },
+        }
+    }
+
+    /// Build from 8-bit RGBA (LDR, taken as linear) data.
+    pub fn from_rgba8(pixels: &[u8], width: u32, height: u32) -> Option<Self> {
+        let count = (width as usize) * (height as usize);
+        if count == 0 || pixels.len() < count * 4 {
+            return None;
+        }
+        let texels = pixels.chunks_exact(4).take(count)
+            .map(|p| [p[0] as f32 / 255.0, p[1] as f32 / 255.0, p[2] as f32 / 255.0])
+            .collect();
+        Some(Self::new(LatLong { width, height, texels }))
+    }
+
+    /// Build from float RGB triples (HDR radiance, unbounded).
+    pub fn from_rgb_f32(rgb: &[f32], width: u32, height: u32) -> Option<Self> {
+        let count = (width as usize) * (height as usize);
+        if count == 0 || rgb.len() < count * 3 {
+            return None;
+        }
+        let texels = rgb.chunks_exact(3).take(count)
+            .map(|c| [c[0].max(0.0), c[1].max(0.0), c[2].max(0.0)])
+            .collect();
+        Some(Self::new(LatLong { width, height, texels }))
+    }
+}
+
+/// Environment lighting settings of the paint pass.
+#[derive(Clone, Debug)]
+pub struct EnvironmentLighting {
+    pub map: Arc<EnvironmentMap>,
+    /// Diffuse (irradiance) contribution, tinted by the surface color
+    pub diffuse: f64,
+    /// Specular (reflection) contribution
+    pub specular: f64,
+    /// Rotation of the map about +Y in radians
+    pub rotation: f64,
+}
+
+impl EnvironmentLighting {
+    /// Diffuse light arriving at a surface with normal `n`.
+    pub fn diffuse_at(&self, n: &Vec3D) -> (f64, f64, f64) {
+        let (r, g, b) = self.map.irradiance.sample(n, self.rotation);
+        (r * self.diffuse, g * self.diffuse, b * self.diffuse)
+    }
+
+    /// Light reflected toward the viewer along the mirror direction `r`.
+    pub fn specular_at(&self, r: &Vec3D) -> (f64, f64, f64) {
+        let (cr, cg, cb) = self.map.radiance.sample(r, self.rotation);
+        (cr * self.specular, cg * self.specular, cb * self.specular)
+    }
+}
+
+static MAPS: Registry<EnvironmentMap> = Registry::new();
+
+/// Store an environment map in the registry, returning its handle.
+pub fn register_map(map: EnvironmentMap) -> u32 {
+    MAPS.insert(map)
+}
+
+/// Look up a registered environment map.
+pub fn map(handle: u32) -> Option<Arc<EnvironmentMap>> {
+    MAPS.get(handle)
+}
+
+/// Release a registered environment map; its handle may be reused.
+pub fn release_map(handle: u32) {
+    MAPS.remove(handle);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sky(width: u32, height: u32) -> EnvironmentMap {
+        // Bright upper hemisphere, black lower hemisphere
+        let texels = (0..width * height)
+            .map(|i| if i / width < height / 2 { [1.0, 1.0, 1.0] } else { [0.0, 0.0, 0.0] })
+            .collect();
+        EnvironmentMap::new(LatLong { width, height, texels })
+    }
+
+    #[test]
+    fn test_uniform_environment_has_unit_irradiance() {
+        let map = EnvironmentMap::from_rgb_f32(&[1.0; 64 * 32 * 3], 64, 32).unwrap();
+        for dir in [Vec3D { x: 0.0, y: 1.0, z: 0.0 }, Vec3D { x: 1.0, y: 0.0, z: 0.0 }, Vec3D { x: 0.3, y: -0.8, z: 0.5 }] {
+            let (r, _, _) = map.irradiance.sample(&dir, 0.0);
+            assert!((r - 1.0).abs() < 0.02, "irradiance {r}");
+        }
+    }
+
+    #[test]
+    fn test_sky_lights_upward_normals() {
+        let map = sky(64, 32);
+        let up = map.irradiance.sample(&Vec3D { x: 0.0, y: 1.0, z: 0.0 }, 0.0).0;
+        let side = map.irradiance.sample(&Vec3D { x: 1.0, y: 0.0, z: 0.0 }, 0.0).0;
+        let down = map.irradiance.sample(&Vec3D { x: 0.0, y: -1.0, z: 0.0 }, 0.0).0;
+        assert!((up - 1.0).abs() < 0.05 && (side - 0.5).abs() < 0.05 && down < 0.05);
+        // Specular lookups see the image itself
+        assert_eq!(map.radiance.sample(&Vec3D { x: 0.0, y: 1.0, z: 0.2 }, 0.0).0, 1.0);
+        assert_eq!(map.radiance.sample(&Vec3D { x: 0.0, y: -1.0, z: 0.2 }, 0.0).0, 0.0);
+    }
+
+    #[test]
+    fn test_direction_mapping_and_rotation() {
+        let (u, v) = direction_to_uv(&Vec3D { x: 0.0, y: 0.0, z: 1.0 }, 0.0);
+        assert!((u - 0.5).abs() < 1e-12 && (v - 0.5).abs() < 1e-12);
+        let (u, _) = direction_to_uv(&Vec3D { x: 0.0, y: 0.0, z: 1.0 }, PI * 0.5);
+        assert!((u - 0.75).abs() < 1e-12);
+        assert!(EnvironmentMap::from_rgba8(&[0; 8], 4, 4).is_none());
+    }
+}
@@ -17,10 +17,108 @@ pub struct ColorStop {
     pub b: f64,
 }
 
+/// How a second gradient layer combines with the first.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GradientBlend {
+    /// Linear crossfade
+    #[default]
+    Mix,
+    /// Base × layer
+    Multiply,
+    /// Multiply in the darks, screen in the lights of the base
+    Overlay,
+}
+
+impl GradientBlend {
+    pub fn from_u32(v: u32) -> Self {
+        match v {
+            1 => GradientBlend::Multiply,
+            2 => GradientBlend::Overlay,
+            _ => GradientBlend::Mix,
+        }
+    }
+
+    /// Combine `layer` onto `base` with the given weight in [0, 1].
+    pub fn apply(self, base: (f64, f64, f64), layer: (f64, f64, f64), weight: f64) -> (f64, f64, f64) {
+        let blend = |a: f64, b: f64| {
+            let c = match self {
+                GradientBlend::Mix => b,
+                GradientBlend::Multiply => a * b,
+                GradientBlend::Overlay if a < 0.5 => 2.0 * a * b,
+                GradientBlend::Overlay => 1.0 - 2.0 * (1.0 - a) * (1.0 - b),
+            };
+            utils::lerp(a, c, weight)
+        };
+        (blend(base.0, layer.0), blend(base.1, layer.1), blend(base.2, layer.2))
+    }
+}
+
+/// Procedural palette `a + b·cos(2π(c·t + d))` per channel (Iñigo Quilez).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CosinePalette {
+    /// Offset
+    pub a: [f64; 3],
+    /// Amplitude
+    pub b: [f64; 3],
+    /// Frequency
+    pub c: [f64; 3],
+    /// Phase
+    pub d: [f64; 3],
+}
+
+impl CosinePalette {
+    /// Parse `[a rgb, b rgb, c rgb, d rgb]`.
+    pub fn from_values(v: &[f64]) -> Option<Self> {
+        if v.len() < 12 {
+            return None;
+        }
+        let vec3 = |i: usize| [v[i], v[i + 1], v[i + 2]];
+        Some(Self { a: vec3(0), b: vec3(3), c: vec3(6), d: vec3(9) })
+    }
+
+    pub fn sample(&self, t: f64) -> (f64, f64, f64) {
+        let ch = |i: usize| {
+            let v = self.a[i] + self.b[i] * (2.0 * std::f64::consts::PI * (self.c[i] * t + self.d[i])).cos();
+            v.max(0.0)
+        };
+        (ch(0), ch(1), ch(2))
+    }
+}
+
+/// Color space in which neighbouring stops are blended.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Interpolation {
+    /// Per-channel lerp of the stored values (MB3D)
+    #[default]
+    Rgb,
+    /// Perceptually uniform OKLab; keeps lightness and chroma between
+    /// saturated stops
+    OkLab,
+    /// Hue/saturation/value, hue along the shorter arc
+    Hsv,
+}
+
+impl Interpolation {
+    pub fn from_u32(v: u32) -> Self {
+        match v {
+            1 => Interpolation::OkLab,
+            2 => Interpolation::Hsv,
+            _ => Interpolation::Rgb,
+        }
+    }
+}
+
 /// Color gradient with interpolation between stops.
 #[derive(Clone, Debug)]
 pub struct ColorGradient {
     pub stops: Vec<ColorStop>,
+    /// Procedural palette used instead of the stops when set
+    pub cosine: Option<CosinePalette>,
+    /// Blending space between stops
+    pub interpolation: Interpolation,
+    /// Stop colors are linear light (after `linearized`) rather than
+    /// sRGB-encoded; OKLab blending needs to know
+    pub linear: bool,
 }
 
 impl Default for ColorGradient {
@@ -34,17 +132,49 @@ impl Default for ColorGradient {
                 ColorStop { position: 0.75, r: 1.0, g: 0.4, b: 0.0 },    // #ff6600
                 ColorStop { position: 1.0, r: 0.0, g: 0.0, b: 0.0 },     // #000000
             ],
+            cosine: None,
+            interpolation: Interpolation::Rgb,
+            linear: false,
         }
     }
 }
 
 impl ColorGradient {
+    fn with_stops(stops: Vec<ColorStop>) -> Self {
+        Self { stops, cosine: None, interpolation: Interpolation::Rgb, linear: false }
+    }
+
+    /// Procedural gradient from a cosine palette.
+    pub fn cosine(palette: CosinePalette) -> Self {
+        Self { cosine: Some(palette), ..Self::with_stops(Vec::new()) }
+    }
+
     /// Create a gradient from an array of (position, r, g, b) tuples.
     pub fn from_stops(stops: &[(f64, f64, f64, f64)]) -> Self {
         let stops = stops.iter()
             .map(|(pos, r, g, b)| ColorStop { position: *pos, r: *r, g: *g, b: *b })
             .collect();
-        Self { stops }
+        Self::with_stops(stops)
+    }
+
+    /// MB3D's default 4-stop interior gradient (dark blue-violet).
+    pub fn default_interior() -> Self {
+        Self::from_stops(&[
+            (0.0, 0.05, 0.02, 0.15),
+            (0.33, 0.25, 0.08, 0.40),
+            (0.67, 0.60, 0.30, 0.55),
+            (1.0, 0.10, 0.05, 0.20),
+        ])
+    }
+
+    /// Create from 4 RGBA interior colors (evenly spaced, alpha ignored).
+    pub fn from_interior_colors(colors: &[u8; 16]) -> Self {
+        let stops: Vec<_> = colors
+            .chunks_exact(4)
+            .enumerate()
+            .map(|(i, c)| (i as f64 / 3.0, utils::byte_to_float(c[0]), utils::byte_to_float(c[1]), utils::byte_to_float(c[2])))
+            .collect();
+        Self::from_stops(&stops)
     }
 
     /// Create from a flat f64 array: [pos, r, g, b, pos, r, g, b, ...]
@@ -63,7 +193,7 @@ impl ColorGradient {
         if stops.is_empty() {
             return Self::default();
         }
-        Self { stops }
+        Self::with_stops(stops)
     }
 
     /// Sample the gradient at position t (normalized to [0,1]).
@@ -71,6 +201,10 @@ impl ColorGradient {
     pub fn sample(&self, t: f64) -> (f64, f64, f64) {
         let t = utils::clamp(t, 0.0, 1.0);
 
+        if let Some(palette) = &self.cosine {
+            return palette.sample(t);
+        }
+
         if self.stops.is_empty() {
             return (0.0, 0.0, 0.0);
         }
@@ -98,17 +232,270 @@ impl ColorGradient {
             if t >= s0.position && t <= s1.position {
                 let range = s1.position - s0.position;
                 let frac = if range > 1e-10 { (t - s0.position) / range } else { 0.0 };
-                return (
-                    utils::lerp(s0.r, s1.r, frac),
-                    utils::lerp(s0.g, s1.g, frac),
-                    utils::lerp(s0.b, s1.b, frac),
-                );
+                return self.blend((s0.r, s0.g, s0.b), (s1.r, s1.g, s1.b), frac);
             }
         }
 
         let s = &self.stops[last];
         (s.r, s.g, s.b)
     }
+
+    /// Blend two stop colors in the gradient's interpolation space.
+    fn blend(&self, a: (f64, f64, f64), b: (f64, f64, f64), t: f64) -> (f64, f64, f64) {
+        let lerp3 = |a: (f64, f64, f64), b: (f64, f64, f64)| {
+            (utils::lerp(a.0, b.0, t), utils::lerp(a.1, b.1, t), utils::lerp(a.2, b.2, t))
+        };
+        match self.interpolation {
+            Interpolation::Rgb => lerp3(a, b),
+            Interpolation::OkLab => {
+                let linear = self.linear;
+                let decode = |v: f64| if linear { v } else { utils::srgb_to_linear(v) };
+                let encode = |v: f64| if linear { v.max(0.0) } else { utils::linear_to_srgb(v) };
+                let to_lab = |c: (f64, f64, f64)| utils::linear_rgb_to_oklab((decode(c.0), decode(c.1), decode(c.2)));
+                let c = utils::oklab_to_linear_rgb(lerp3(to_lab(a), to_lab(b)));
+                (encode(c.0), encode(c.1), encode(c.2))
+            }
+            Interpolation::Hsv => {
+                let (ha, hb) = (utils::rgb_to_hsv(a), utils::rgb_to_hsv(b));
+                // Gray stops have no hue; take the other one's
+                let h0 = if ha.1 > 0.0 { ha.0 } else { hb.0 };
+                let h1 = if hb.1 > 0.0 { hb.0 } else { h0 };
+                let dh = (h1 - h0 + 0.5).rem_euclid(1.0) - 0.5;
+                utils::hsv_to_rgb((h0 + dh * t, utils::lerp(ha.1, hb.1, t), utils::lerp(ha.2, hb.2, t)))
+            }
+        }
+    }
+
+    /// Flatten to [pos, r, g, b, pos, r, g, b, ...] (inverse of `from_flat`).
+    pub fn to_flat(&self) -> Vec<f64> {
+        self.stops.iter().flat_map(|s| [s.position, s.r, s.g, s.b]).collect()
+    }
+
+    /// Copy with the stop colors decoded from sRGB to linear light
+    /// (a cosine palette is kept as is; it is defined in the working space).
+    pub fn linearized(&self) -> Self {
+        let stops = self.stops.iter()
+            .map(|s| ColorStop {
+                position: s.position,
+                r: utils::srgb_to_linear(s.r),
+                g: utils::srgb_to_linear(s.g),
+                b: utils::srgb_to_linear(s.b),
+            })
+            .collect();
+        Self { stops, cosine: self.cosine, interpolation: self.interpolation, linear: true }
+    }
+
+    /// Keep stops ordered by position, as `sample` expects.
+    fn sort(&mut self) {
+        self.stops.sort_by(|a, b| a.position.total_cmp(&b.position));
+    }
+
+    /// Insert a stop at `t` with the color currently sampled there.
+    /// Returns the index of the new stop.
+    pub fn insert_stop(&mut self, t: f64) -> usize {
+        let position = utils::clamp(t, 0.0, 1.0);
+        let (r, g, b) = self.sample(position);
+        self.sort();
+        let idx = self.stops.partition_point(|s| s.position <= position);
+        self.stops.insert(idx, ColorStop { position, r, g, b });
+        idx
+    }
+
+    /// Remove the stop nearest to `t`. The last remaining stop is kept.
+    /// Returns true if a stop was removed.
+    pub fn remove_nearest(&mut self, t: f64) -> bool {
+        if self.stops.len() <= 1 {
+            return false;
+        }
+        let nearest = self.stops.iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| (a.position - t).abs().total_cmp(&(b.position - t).abs()))
+            .map(|(i, _)| i);
+        match nearest {
+            Some(i) => {
+                self.stops.remove(i);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Space the stops evenly over [0, 1], keeping their order and colors.
+    pub fn distribute_evenly(&mut self) {
+        self.sort();
+        let n = self.stops.len();
+        for (i, stop) in self.stops.iter_mut().enumerate() {
+            stop.position = if n > 1 { i as f64 / (n - 1) as f64 } else { 0.0 };
+        }
+    }
+
+    /// Mirror the gradient (t → 1 − t).
+    pub fn reverse(&mut self) {
+        for stop in &mut self.stops {
+            stop.position = 1.0 - stop.position;
+        }
+        self.stops.reverse();
+    }
+}
+
+/// Palette file formats understood by `ColorGradient::from_palette_file`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PaletteFormat {
+    /// Ultra Fractal gradient collection (.ugr)
+    Ugr,
+    /// Fractint color map (.map)
+    Map,
+    /// GIMP palette (.gpl)
+    Gpl,
+}
+
+impl PaletteFormat {
+    /// Guess the format from the file contents.
+    pub fn detect(text: &str) -> Self {
+        if text.trim_start().starts_with("GIMP Palette") {
+            PaletteFormat::Gpl
+        } else if text.contains("gradient:") {
+            PaletteFormat::Ugr
+        } else {
+            PaletteFormat::Map
+        }
+    }
+}
+
+/// Number of color entries in an Ultra Fractal gradient (index range).
+const UGR_INDEX_RANGE: i64 = 400;
+
+impl ColorGradient {
+    /// Evenly spaced stops from 8-bit colors.
+    fn from_colors(colors: &[(u8, u8, u8)]) -> Option<Self> {
+        if colors.is_empty() {
+            return None;
+        }
+        let last = (colors.len() - 1).max(1) as f64;
+        let stops: Vec<_> = colors
+            .iter()
+            .enumerate()
+            .map(|(i, &(r, g, b))| (i as f64 / last, utils::byte_to_float(r), utils::byte_to_float(g), utils::byte_to_float(b)))
+            .collect();
+        Some(Self::from_stops(&stops))
+    }
+
+    /// Parse a Fractint .map: one "R G B [comment]" line per entry.
+    pub fn from_map(text: &str) -> Option<Self> {
+        let colors: Vec<_> = text.lines().filter_map(parse_rgb_line).collect();
+        Self::from_colors(&colors)
+    }
+
+    /// Parse a GIMP .gpl: header and `Name:`/`Columns:`/`#` lines, then
+    /// "R G B [name]" entries.
+    pub fn from_gpl(text: &str) -> Option<Self> {
+        let colors: Vec<_> = text
+            .lines()
+            .skip_while(|l| !l.trim_start().starts_with("GIMP Palette"))
+            .skip(1)
+            .filter(|l| {
+                let l = l.trim_start();
+                !(l.starts_with('#') || l.starts_with("Name:") || l.starts_with("Columns:"))
+            })
+            .filter_map(parse_rgb_line)
+            .collect();
+        Self::from_colors(&colors)
+    }
+
+    /// Parse every gradient of an Ultra Fractal .ugr collection, with its title.
+    ///
+    /// Nodes are `index=<0..399> color=<0x00BBGGRR as decimal>` pairs in the
+    /// `gradient:` section of each entry; opacity sections are ignored.
+    pub fn from_ugr(text: &str) -> Vec<(String, Self)> {
+        let mut out = Vec::new();
+        let mut name = String::new();
+        let mut stops: Vec<(f64, f64, f64, f64)> = Vec::new();
+        let mut in_gradient = false;
+        let mut index: Option<i64> = None;
+
+        let mut finish = |name: &mut String, stops: &mut Vec<(f64, f64, f64, f64)>| {
+            if !stops.is_empty() {
+                stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+                out.push((std::mem::take(name), Self::from_stops(stops)));
+                stops.clear();
+            }
+        };
+
+        for line in text.lines() {
+            let line = line.trim();
+            if let Some(entry) = line.strip_suffix('{') {
+                finish(&mut name, &mut stops);
+                name = entry.trim().to_string();
+                in_gradient = false;
+                continue;
+            }
+            match line {
+                "gradient:" => { in_gradient = true; continue; }
+                "}" => { finish(&mut name, &mut stops); in_gradient = false; continue; }
+                _ if line.ends_with(':') => { in_gradient = false; continue; }
+                _ => {}
+            }
+            if !in_gradient {
+                continue;
+            }
+            for (key, value) in key_values(line) {
+                match key {
+                    "title" => name = value.to_string(),
+                    "index" => index = value.parse().ok(),
+                    "color" => {
+                        if let (Some(i), Ok(c)) = (index.take(), value.parse::<u32>()) {
+                            let [r, g, b, _] = c.to_le_bytes();
+                            let pos = i.rem_euclid(UGR_INDEX_RANGE) as f64 / UGR_INDEX_RANGE as f64;
+                            stops.push((pos, utils::byte_to_float(r), utils::byte_to_float(g), utils::byte_to_float(b)));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        finish(&mut name, &mut stops);
+        out
+    }
+
+    /// Parse a palette file of any supported format (detected from the
+    /// contents). `entry` picks the gradient of a .ugr collection.
+    pub fn from_palette_file(bytes: &[u8], entry: usize) -> Option<Self> {
+        let text = String::from_utf8_lossy(bytes);
+        match PaletteFormat::detect(&text) {
+            PaletteFormat::Ugr => Self::from_ugr(&text).into_iter().nth(entry).map(|(_, g)| g),
+            PaletteFormat::Map => Self::from_map(&text),
+            PaletteFormat::Gpl => Self::from_gpl(&text),
+        }
+    }
+}
+
+/// First three integers of a line as an 8-bit color.
+fn parse_rgb_line(line: &str) -> Option<(u8, u8, u8)> {
+    let mut it = line.split_whitespace().map(|t| t.parse::<u8>());
+    match (it.next(), it.next(), it.next()) {
+        (Some(Ok(r)), Some(Ok(g)), Some(Ok(b))) => Some((r, g, b)),
+        _ => None,
+    }
+}
+
+/// `key=value` pairs of a .ugr line; values may be double-quoted.
+fn key_values(line: &str) -> Vec<(&str, &str)> {
+    let mut out = Vec::new();
+    let mut rest = line.trim_start();
+    while let Some(eq) = rest.find('=') {
+        let key = rest[..eq].trim();
+        let after = &rest[eq + 1..];
+        let (value, next) = if let Some(quoted) = after.strip_prefix('"') {
+            let end = quoted.find('"').unwrap_or(quoted.len());
+            (&quoted[..end], quoted.get(end + 1..).unwrap_or(""))
+        } else {
+            let end = after.find(char::is_whitespace).unwrap_or(after.len());
+            (&after[..end], &after[end..])
+        };
+        out.push((key, value));
+        rest = next.trim_start();
+    }
+    out
 }
 
 #[cfg(test)]
@@ -126,6 +513,83 @@ mod tests {
         assert!((r - 0.0).abs() < 0.01);
     }
 
+    #[test]
+    fn test_interior_colors_are_evenly_spaced() {
+        let mut colors = [0u8; 16];
+        colors[4..8].copy_from_slice(&[255, 0, 0, 255]);
+        colors[12..16].copy_from_slice(&[0, 0, 255, 255]);
+        let g = ColorGradient::from_interior_colors(&colors);
+        assert_eq!(g.stops.len(), 4);
+        assert_eq!(g.sample(1.0 / 3.0), (1.0, 0.0, 0.0));
+        assert_eq!(g.sample(1.0), (0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn test_palette_files() {
+        let map = "0 0 0 black\n255 0 0\n\n0 0 255 blue\n";
+        let g = ColorGradient::from_palette_file(map.as_bytes(), 0).unwrap();
+        assert_eq!(g.stops.len(), 3);
+        assert_eq!(g.sample(0.5), (1.0, 0.0, 0.0));
+
+        let gpl = "GIMP Palette\nName: Test\nColumns: 2\n#\n  0 255   0\tGreen\n255 255 255\tWhite\n";
+        assert_eq!(PaletteFormat::detect(gpl), PaletteFormat::Gpl);
+        let g = ColorGradient::from_palette_file(gpl.as_bytes(), 0).unwrap();
+        assert_eq!(g.sample(0.0), (0.0, 1.0, 0.0));
+        assert_eq!(g.sample(1.0), (1.0, 1.0, 1.0));
+
+        let ugr = "first {\ngradient:\n  title=\"Red Blue\" smooth=no\n  index=0 color=255\n  index=200 color=16711680\nopacity:\n  smooth=no index=0 opacity=255\n}\n\
+                   second {\ngradient:\n  title=\"Green\"\n  index=-100 color=65280\n}\n";
+        let all = ColorGradient::from_ugr(ugr);
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[0].0, "Red Blue");
+        assert_eq!(all[0].1.sample(0.0), (1.0, 0.0, 0.0));
+        assert_eq!(all[0].1.sample(0.5), (0.0, 0.0, 1.0));
+        assert_eq!(all[1].1.stops[0].position, 0.75);
+        assert!(ColorGradient::from_palette_file(ugr.as_bytes(), 1).is_some());
+        assert!(ColorGradient::from_palette_file(b"not a palette", 0).is_none());
+    }
+
+    #[test]
+    fn test_cosine_palette() {
+        // Classic rainbow: a = b = 0.5, c = 1, d = (0, 1/3, 2/3)
+        let values = [0.5, 0.5, 0.5, 0.5, 0.5, 0.5, 1.0, 1.0, 1.0, 0.0, 1.0 / 3.0, 2.0 / 3.0];
+        let g = ColorGradient::cosine(CosinePalette::from_values(&values).unwrap());
+        let (r, gr, b) = g.sample(0.0);
+        assert!((r - 1.0).abs() < 1e-12 && (gr - 0.25).abs() < 1e-12 && (b - 0.25).abs() < 1e-12);
+        // Periodic with c = 1
+        let (r1, _, _) = g.sample(1.0);
+        assert!((r1 - r).abs() < 1e-12);
+        assert!(CosinePalette::from_values(&values[..11]).is_none());
+    }
+
+    #[test]
+    fn test_interpolation_spaces() {
+        let mut g = ColorGradient::from_stops(&[(0.0, 1.0, 0.0, 0.0), (1.0, 0.0, 1.0, 0.0)]);
+        assert_eq!(g.sample(0.5), (0.5, 0.5, 0.0));
+        // HSV goes through yellow at full value
+        g.interpolation = Interpolation::Hsv;
+        let (r, gr, b) = g.sample(0.5);
+        assert!((r - 1.0).abs() < 1e-12 && (gr - 1.0).abs() < 1e-12 && b.abs() < 1e-12);
+        // OKLab keeps the midpoint brighter than the muddy RGB average
+        g.interpolation = Interpolation::OkLab;
+        let (r, gr, _) = g.sample(0.5);
+        assert!(r > 0.6 && gr > 0.6);
+        assert_eq!(g.sample(0.0), (1.0, 0.0, 0.0));
+        // Linearized stops blend to the same displayed color
+        let lin = g.linearized().sample(0.5);
+        assert!((utils::linear_to_srgb(lin.0) - r).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_blend_modes() {
+        let base = (0.25, 0.5, 1.0);
+        let layer = (0.5, 0.5, 0.5);
+        assert_eq!(GradientBlend::Mix.apply(base, layer, 0.5), (0.375, 0.5, 0.75));
+        assert_eq!(GradientBlend::Multiply.apply(base, layer, 1.0), (0.125, 0.25, 0.5));
+        assert_eq!(GradientBlend::Overlay.apply(base, layer, 1.0), (0.25, 0.5, 1.0));
+        assert_eq!(GradientBlend::Overlay.apply(base, layer, 0.0), base);
+    }
+
     #[test]
     fn test_gradient_midpoint() {
         let g = ColorGradient::default();
@@ -134,4 +598,29 @@ mod tests {
         assert!((g_val - 1.0).abs() < 0.01);
         assert!((b - 1.0).abs() < 0.01);
     }
+
+    #[test]
+    fn test_insert_keeps_sampled_color() {
+        let mut g = ColorGradient::default();
+        let before = g.sample(0.4);
+        let idx = g.insert_stop(0.4);
+        assert_eq!(idx, 2);
+        assert_eq!(g.stops.len(), 6);
+        let after = g.sample(0.4);
+        assert!((before.0 - after.0).abs() < 1e-12 && (before.2 - after.2).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_remove_distribute_reverse() {
+        let mut g = ColorGradient::from_flat(&[0.0, 1.0, 0.0, 0.0, 0.2, 0.0, 1.0, 0.0, 1.0, 0.0, 0.0, 1.0]);
+        g.distribute_evenly();
+        assert!((g.stops[1].position - 0.5).abs() < 1e-12);
+        g.reverse();
+        assert_eq!(g.sample(0.0), (0.0, 0.0, 1.0));
+        assert!(g.remove_nearest(0.45));
+        assert_eq!(g.stops.len(), 2);
+        assert!(g.remove_nearest(0.0));
+        assert!(!g.remove_nearest(0.0));
+        assert_eq!(g.to_flat(), vec![1.0, 1.0, 0.0, 0.0]);
+    }
 }
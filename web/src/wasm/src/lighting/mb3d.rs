@@ -0,0 +1,194 @@
+//! MB3D lighting records (`Light8`, `LightingParas9`) decoded into the paint
+//! configuration.
+//!
+//! MB3D stores global lights as two angles, relative to the view unless the
+//! light is fixed to the object, so decoding needs the camera basis. Ambient
+//! and background colors come as top/bottom pairs; this painter has one of
+//! each and uses their mix. The background pair is MB3D's depth color, which
+//! also tints distant surfaces, so it is the fog color too.
+
+use crate::engine::camera::CameraRays;
+use crate::engine::types::{d7b_to_f64, short_float_to_f64, Light8, LightingParas9, Vec3D};
+use crate::lighting::gradient::ColorGradient;
+use crate::lighting::paint::{LightConfig, LightKind, PaintConfig, PaintView};
+use crate::math::math3d;
+
+/// `0x00BBGGRR` (or RGB bytes) as a [0, 1] color.
+pub(crate) fn color_from_u32(c: u32) -> (f64, f64, f64) {
+    let [r, g, b, _] = c.to_le_bytes();
+    rgb_from_bytes([r, g, b])
+}
+
+pub(crate) fn rgb_from_bytes(c: [u8; 3]) -> (f64, f64, f64) {
+    (c[0] as f64 / 255.0, c[1] as f64 / 255.0, c[2] as f64 / 255.0)
+}
+
+pub(crate) fn mix(a: (f64, f64, f64), b: (f64, f64, f64)) -> (f64, f64, f64) {
+    ((a.0 + b.0) * 0.5, (a.1 + b.1) * 0.5, (a.2 + b.2) * 0.5)
+}
+
+/// World direction toward a global light. Angles are relative to the view
+/// (`rays`) unless the light is fixed to the object.
+pub fn light_direction(light: &Light8, rays: &CameraRays) -> Vec3D {
+    let (ax, ay) = (d7b_to_f64(&{ light.x_pos }), d7b_to_f64(&{ light.y_pos }));
+    // Camera space: x right, y down, z forward
+    let local = math3d::vec3d_normalized(&Vec3D { x: -ax.sin(), y: -ay.sin(), z: -ax.cos() * ay.cos() });
+    if light.option & 0x20 != 0 {
+        return local;
+    }
+    let right = math3d::vec3d_normalized(&rays.dx);
+    let down = math3d::vec3d_normalized(&rays.dy);
+    let world = math3d::vec3d_add(
+        &math3d::vec3d_add(&math3d::vec3d_scale(&right, local.x), &math3d::vec3d_scale(&down, local.y)),
+        &math3d::vec3d_scale(&rays.dir_base, local.z),
+    );
+    math3d::vec3d_normalized(&world)
+}
+
+/// One light. Only lights switched on (option 0) are enabled; lightmap
+/// lights decode as switched off.
+pub fn light_config(light: &Light8, rays: &CameraRays) -> LightConfig {
+    let mut config = LightConfig {
+        direction: light_direction(light, rays),
+        color: rgb_from_bytes(light.color),
+        amplitude: short_float_to_f64(light.amp),
+        specular_size: (8u32 << (light.function & 7)) as f64,
+        enabled: light.option & 3 == 0,
+        ..LightConfig::default()
+    };
+    if light.option & 4 != 0 {
+        config.kind = LightKind::Point;
+        config.position = Vec3D { x: d7b_to_f64(&{ light.x_pos }), y: d7b_to_f64(&{ light.y_pos }), z: d7b_to_f64(&{ light.z_pos }) };
+    }
+    config
+}
+
+/// Lights, ambient, background, fog color and both gradients of an MB3D
+/// scene seen through `view`. Everything else keeps its default.
+pub fn paint_config(lighting: &LightingParas9, view: &PaintView) -> PaintConfig {
+    let rays = CameraRays { dir_base: view.ray_dir_base, dx: view.ray_dx, dy: view.ray_dy };
+    let lights = { lighting.lights };
+    let depth = mix(rgb_from_bytes(lighting.depth_col), rgb_from_bytes(lighting.depth_col2));
+    let tb_pos = { lighting.tb_pos };
+    let lcols = { lighting.lcols };
+    let icols = { lighting.icols };
+    let stops = |stops: Vec<(u16, u32)>| {
+        let stops: Vec<_> = stops
+            .into_iter()
+            .map(|(position, color)| {
+                let (r, g, b) = color_from_u32(color);
+                (position as f64 / 32767.0, r, g, b)
+            })
+            .collect();
+        ColorGradient::from_stops(&stops)
+    };
+    PaintConfig {
+        lights: lights.iter().map(|l| light_config(l, &rays)).collect(),
+        ambient_color: mix(rgb_from_bytes(lighting.amb_col), rgb_from_bytes(lighting.amb_col2)),
+        // Trackbar 8, 0..90
+        ambient_intensity: (tb_pos[5] & 0xFFF) as f64 / 90.0,
+        fog_color: depth,
+        bg_color: depth,
+        view_dir: view.ray_dir_base,
+        gradient: stops(lcols.iter().map(|c| (c.position, c.color_dif)).collect()),
+        interior_gradient: stops(icols.iter().map(|c| (c.position, c.color)).collect()),
+        view: Some(*view),
+        ..PaintConfig::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::types::{f64_to_d7b, f64_to_short_float};
+
+    /// `Light8` from 32 bytes as MB3D writes them.
+    fn light(bytes: [u8; 32]) -> Light8 {
+        // SAFETY: Light8 is packed plain data, every bit pattern is valid
+        unsafe { std::ptr::read_unaligned(bytes.as_ptr() as *const Light8) }
+    }
+
+    fn dump(hex: &str) -> [u8; 32] {
+        let bytes: Vec<u8> = hex.split_whitespace().map(|b| u8::from_str_radix(b, 16).unwrap()).collect();
+        bytes.try_into().unwrap()
+    }
+
+    #[test]
+    fn test_packed_number_dumps() {
+        // 1.0, -2.5 and 0.5 keep all their bits; pi loses the low mantissa byte
+        for (v, hex) in [(1.0, [0, 0, 0, 0, 0, 0xF0, 0x3F]), (-2.5, [0, 0, 0, 0, 0, 0x04, 0xC0]), (0.5, [0, 0, 0, 0, 0, 0xE0, 0x3F])] {
+            assert_eq!(f64_to_d7b(v), hex);
+            assert_eq!(d7b_to_f64(&hex), v);
+        }
+        let pi = [0x2D, 0x44, 0x54, 0xFB, 0x21, 0x09, 0x40];
+        assert_eq!(f64_to_d7b(std::f64::consts::PI), pi);
+        assert_eq!(d7b_to_f64(&pi), f64::from_bits(0x400921FB54442D00));
+        // Short floats: mantissa, decimal exponent
+        for (v, sf) in [(1.5, [15, 0]), (0.8, [80, -1]), (12.0, [12, 1]), (-0.03, [-30, -2])] {
+            assert_eq!(f64_to_short_float(v), sf);
+            assert!((short_float_to_f64(sf) - v).abs() < 1e-15, "{v}");
+        }
+    }
+
+    #[test]
+    fn test_light_dumps() {
+        let rays = CameraRays {
+            dir_base: Vec3D { x: 0.0, y: 0.0, z: 1.0 },
+            dx: Vec3D { x: 0.5, y: 0.0, z: 0.0 },
+            dy: Vec3D { x: 0.0, y: 0.5, z: 0.0 },
+        };
+        // Positional light at (1, -2.5, 0.5): option 4, specular 8 << 2, amplitude 1.5, orange
+        let point = light(dump(
+            "04 02 0F 00 FF 80 00 00 00 00 00 00 00 00 F0 3F 00 00 00 00 00 00 04 C0 00 00 00 00 00 00 E0 3F",
+        ));
+        let c = light_config(&point, &rays);
+        assert_eq!((c.kind, c.enabled), (LightKind::Point, true));
+        assert_eq!(c.position, Vec3D { x: 1.0, y: -2.5, z: 0.5 });
+        assert_eq!((c.amplitude, c.specular_size), (1.5, 32.0));
+        assert_eq!(c.color, (1.0, 128.0 / 255.0, 0.0));
+
+        // Global light at angles (0, 0) shines from the camera: toward it is -forward
+        let global = light(dump(
+            "00 00 0A 00 FF FF FF 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00",
+        ));
+        let c = light_config(&global, &rays);
+        assert_eq!((c.kind, c.amplitude, c.specular_size), (LightKind::Directional, 1.0, 8.0));
+        assert_eq!(c.direction, Vec3D { x: 0.0, y: 0.0, z: -1.0 });
+        // LX = pi/2 is to the left of the view: toward the light is -right
+        let mut side = global;
+        side.x_pos = f64_to_d7b(std::f64::consts::FRAC_PI_2);
+        let d = light_config(&side, &rays).direction;
+        assert!((d.x + 1.0).abs() < 1e-12 && d.y.abs() < 1e-12 && d.z.abs() < 1e-12, "{d:?}");
+
+        // Off (1) and lightmap (2) lights decode switched off
+        for option in [1, 2] {
+            assert!(!light_config(&Light8 { option, ..global }, &rays).enabled);
+        }
+    }
+
+    #[test]
+    fn test_lighting_dump() {
+        let mut lighting = LightingParas9::default();
+        (lighting.amb_col, lighting.amb_col2) = ([255, 0, 0], [0, 0, 255]);
+        (lighting.depth_col, lighting.depth_col2) = ([0, 0, 0], [0, 255, 0]);
+        let mut tb_pos = [0; 9];
+        tb_pos[5] = 0x1000 | 45;
+        lighting.tb_pos = tb_pos;
+        let mut lcols = lighting.lcols;
+        lcols[0].color_dif = 0x00F8851D;
+        lcols[1] = crate::engine::types::LCol8 { position: 32767, color_dif: 0x00FFFFFF, color_spe: 0 };
+        lighting.lcols = lcols;
+        let view = PaintView { ray_dir_base: Vec3D { x: 0.0, y: 0.0, z: 1.0 }, ..Default::default() };
+
+        let config = paint_config(&lighting, &view);
+        assert_eq!(config.lights.len(), 6);
+        assert_eq!(config.ambient_color, (0.5, 0.0, 0.5));
+        assert_eq!(config.ambient_intensity, 0.5);
+        assert_eq!((config.bg_color, config.fog_color), ((0.0, 0.5, 0.0), (0.0, 0.5, 0.0)));
+        // 0x00BBGGRR: red 0x1D, blue 0xF8
+        let first = config.gradient.stops[0];
+        assert_eq!((first.r, first.b), (29.0 / 255.0, 248.0 / 255.0));
+        assert_eq!(config.gradient.stops[1].position, 1.0);
+        assert_eq!(config.interior_gradient.stops.len(), 4);
+    }
+}
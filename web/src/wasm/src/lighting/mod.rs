@@ -1,11 +1,34 @@
 /// Lighting and painting module — port of PaintThread.pas CalcPixelColor2.
 ///
 /// Implements deferred shading on the G-buffer:
-/// - Up to 6 directional/point lights with Phong model
-/// - Ambient occlusion from ray march step count
+/// - Up to 6 directional/point lights with Phong model, each switchable,
+///   with a relight cache for fast light tuning
+/// - Ambient occlusion from ray march step count, plus optional SSAO
 /// - Color gradient mapping from smooth iteration count
-/// - Fog depth blending
-/// - Specular highlights
+/// - Fog depth blending, height fog and fog color gradients
+/// - Specular highlights and rim light
+/// - Environment lightmap (image-based diffuse and specular)
+/// - Optional GGX metallic/roughness shading
+/// - Cel shading with quantized diffuse bands and edge outlines
+/// - Screen-space light shafts
+/// - Orbit-trap driven emission
+/// - Triplanar procedural and image textures, procedural bumps
+/// - Procedural sky gradient and sun disc behind the fractal
+/// - Preetham sun/sky daylight driving the primary light
+/// - Decoding of MB3D lighting records (lights, ambient, gradients)
 
 pub mod paint;
 pub mod gradient;
+pub mod post;
+pub mod overlay;
+pub mod envmap;
+pub mod fog;
+pub mod pbr;
+pub mod relight;
+pub mod shafts;
+pub mod sky;
+pub mod sunsky;
+pub mod ssao;
+pub mod texture;
+pub mod toon;
+pub mod mb3d;
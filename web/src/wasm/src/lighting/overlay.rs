@@ -0,0 +1,130 @@
+//! Framing overlay — aspect-ratio crop guides and safe-area margins.
+//!
+//! Drawn into a separate transparent RGBA buffer that the UI composites over
+//! the render, so framing for prints and video uses the engine's own pixel
+//! coordinates without touching the image itself.
+
+use crate::math::utils;
+
+/// Crop and safe-area guide settings.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SafeRegionSettings {
+    /// Target aspect ratio (width / height) of the crop; 0 = full frame
+    pub aspect: f64,
+    /// Action-safe inset as a fraction of the crop size (0 = hidden)
+    pub action_safe: f64,
+    /// Title-safe inset as a fraction of the crop size (0 = hidden)
+    pub title_safe: f64,
+    /// Opacity of the matte outside the crop [0, 1]
+    pub matte_alpha: f64,
+    /// Guide line color
+    pub color: (f64, f64, f64),
+}
+
+impl Default for SafeRegionSettings {
+    fn default() -> Self {
+        Self { aspect: 0.0, action_safe: 0.05, title_safe: 0.1, matte_alpha: 0.5, color: (1.0, 1.0, 0.0) }
+    }
+}
+
+/// Pixel rectangle [x0, x1) × [y0, y1).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    pub x0: u32,
+    pub y0: u32,
+    pub x1: u32,
+    pub y1: u32,
+}
+
+impl Rect {
+    /// Shrink by `fraction` of the rectangle's size on every side.
+    pub fn inset(&self, fraction: f64) -> Rect {
+        let dx = ((self.x1 - self.x0) as f64 * fraction).round() as u32;
+        let dy = ((self.y1 - self.y0) as f64 * fraction).round() as u32;
+        Rect { x0: self.x0 + dx, y0: self.y0 + dy, x1: self.x1.saturating_sub(dx), y1: self.y1.saturating_sub(dy) }
+    }
+
+    fn contains(&self, x: u32, y: u32) -> bool {
+        x >= self.x0 && x < self.x1 && y >= self.y0 && y < self.y1
+    }
+
+    fn on_border(&self, x: u32, y: u32) -> bool {
+        self.contains(x, y) && (x == self.x0 || x + 1 == self.x1 || y == self.y0 || y + 1 == self.y1)
+    }
+}
+
+/// Largest centred rectangle of the given aspect ratio inside the frame.
+pub fn crop_rect(width: u32, height: u32, aspect: f64) -> Rect {
+    if aspect <= 0.0 || width == 0 || height == 0 {
+        return Rect { x0: 0, y0: 0, x1: width, y1: height };
+    }
+    let frame = width as f64 / height as f64;
+    if frame > aspect {
+        let cw = (height as f64 * aspect).round() as u32;
+        let x0 = (width - cw.min(width)) / 2;
+        Rect { x0, y0: 0, x1: x0 + cw.min(width), y1: height }
+    } else {
+        let ch = (width as f64 / aspect).round() as u32;
+        let y0 = (height - ch.min(height)) / 2;
+        Rect { x0: 0, y0, x1: width, y1: y0 + ch.min(height) }
+    }
+}
+
+/// Draw the guides into `overlay` (RGBA, width * height * 4), clearing it first.
+pub fn draw_safe_regions(overlay: &mut [u8], width: u32, height: u32, settings: &SafeRegionSettings) {
+    let crop = crop_rect(width, height, settings.aspect);
+    let guides: Vec<Rect> = [settings.action_safe, settings.title_safe]
+        .iter()
+        .filter(|&&f| f > 0.0 && f < 0.5)
+        .map(|&f| crop.inset(f))
+        .collect();
+    let line = [
+        utils::float_to_byte(settings.color.0),
+        utils::float_to_byte(settings.color.1),
+        utils::float_to_byte(settings.color.2),
+        255,
+    ];
+    let matte = [0, 0, 0, utils::float_to_byte(settings.matte_alpha)];
+
+    for y in 0..height {
+        for x in 0..width {
+            let i = ((y * width + x) * 4) as usize;
+            let Some(px) = overlay.get_mut(i..i + 4) else { return };
+            let value = if !crop.contains(x, y) {
+                matte
+            } else if crop.on_border(x, y) || guides.iter().any(|g| g.on_border(x, y)) {
+                line
+            } else {
+                [0, 0, 0, 0]
+            };
+            px.copy_from_slice(&value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crop_rect_letterbox_and_pillarbox() {
+        // 2.39:1 inside 16:9 → letterbox
+        let r = crop_rect(1920, 1080, 2.39);
+        assert_eq!((r.x0, r.x1), (0, 1920));
+        assert_eq!(r.y1 - r.y0, 803);
+        // 1:1 inside 16:9 → pillarbox
+        let r = crop_rect(1920, 1080, 1.0);
+        assert_eq!((r.x0, r.x1, r.y0, r.y1), (420, 1500, 0, 1080));
+    }
+
+    #[test]
+    fn test_overlay_matte_and_guides() {
+        let mut overlay = vec![9u8; 20 * 10 * 4];
+        let settings = SafeRegionSettings { aspect: 1.0, ..Default::default() };
+        draw_safe_regions(&mut overlay, 20, 10, &settings);
+        let px = |x: u32, y: u32| &overlay[((y * 20 + x) * 4) as usize..][..4];
+        assert_eq!(px(0, 5)[3], 127); // matte
+        assert_eq!(px(5, 5), &[255, 255, 0, 255]); // crop border
+        assert_eq!(px(10, 5)[3], 0); // clear interior
+    }
+}
@@ -4,14 +4,55 @@
 /// Implements Phong lighting with up to 6 lights, color gradient mapping,
 /// ambient occlusion, fog, and specular highlights.
 
-use crate::engine::types::{SiLight5, Vec3D};
+use std::ops::Range;
+
+use crate::engine::raymarcher::RenderParams;
+use crate::engine::types::{GBufferFormat, SiLight5, SiLight6, Vec3D};
 use crate::math::{math3d, utils};
-use super::gradient::ColorGradient;
+use super::envmap::{self, EnvironmentLighting};
+use super::fog::HeightFog;
+use super::gradient::{ColorGradient, CosinePalette, GradientBlend, Interpolation};
+use super::pbr::{self, PbrMaterial};
+use super::overlay::SafeRegionSettings;
+use super::post::{self, ClipDiagnostics, ClipMarker, ExposureSettings, GlowSettings};
+use super::shafts::{self, ShaftSettings};
+use super::sky::SkySettings;
+use super::sunsky::SunSky;
+use super::ssao::{self, SsaoSettings};
+use super::texture::{self, BumpSettings, SurfaceTexture, TexturePattern};
+use super::toon::ToonSettings;
+
+/// Light source shape.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LightKind {
+    /// Infinitely far away, no falloff
+    #[default]
+    Directional,
+    /// Omnidirectional light at `position`
+    Point,
+    /// Cone of light from `position`, shining along `-direction`
+    Spot,
+}
+
+impl LightKind {
+    pub fn from_u32(v: u32) -> Self {
+        match v {
+            1 => LightKind::Point,
+            2 => LightKind::Spot,
+            _ => LightKind::Directional,
+        }
+    }
+
+    pub fn is_positional(self) -> bool {
+        self != LightKind::Directional
+    }
+}
 
 /// Light source configuration for the paint pass.
 #[derive(Clone, Debug)]
 pub struct LightConfig {
-    /// Light direction (normalized, pointing toward the light)
+    /// Light direction (normalized, pointing toward the light); for spot
+    /// lights the reversed cone axis
     pub direction: Vec3D,
     /// Light color (r, g, b) in [0, 1]
     pub color: (f64, f64, f64),
@@ -21,6 +62,348 @@ pub struct LightConfig {
     pub specular_size: f64,
     /// Specular intensity multiplier
     pub specular_intensity: f64,
+    /// Highlight color (None = the light color)
+    pub specular_color: Option<(f64, f64, f64)>,
+    /// Directional, point or spot light
+    pub kind: LightKind,
+    /// World position of point and spot lights
+    pub position: Vec3D,
+    /// Distance attenuation 1 / (1 + linear·d + quadratic·d²)
+    pub falloff_linear: f64,
+    pub falloff_quadratic: f64,
+    /// Spot cone half-angles in radians: full intensity inside `spot_inner`,
+    /// fading to zero at `spot_outer`
+    pub spot_inner: f64,
+    pub spot_outer: f64,
+    /// Switched-off lights keep their settings but add no light
+    pub enabled: bool,
+}
+
+impl Default for LightConfig {
+    fn default() -> Self {
+        Self {
+            direction: Vec3D { x: 0.577, y: 0.577, z: -0.577 },
+            color: (1.0, 1.0, 1.0),
+            amplitude: 1.0,
+            specular_size: 32.0,
+            specular_intensity: 0.5,
+            specular_color: None,
+            kind: LightKind::Directional,
+            position: Vec3D::default(),
+            falloff_linear: 0.0,
+            falloff_quadratic: 0.0,
+            spot_inner: 0.4,
+            spot_outer: 0.5,
+            enabled: true,
+        }
+    }
+}
+
+impl LightConfig {
+    /// Unit vector from `point` toward the light, the distance to the light
+    /// and the falloff/cone attenuation at `point`.
+    ///
+    /// Directional lights, and positional lights when the surface position is
+    /// unknown, use `direction` at infinite distance without attenuation.
+    pub fn incidence(&self, point: Option<&Vec3D>) -> (Vec3D, f64, f64) {
+        let point = match point {
+            Some(p) if self.kind.is_positional() => p,
+            _ => return (self.direction, f64::INFINITY, 1.0),
+        };
+        let offset = math3d::vec3d_sub(&self.position, point);
+        let distance = math3d::vec3d_length(&offset);
+        if distance <= 0.0 {
+            return (self.direction, 0.0, 1.0);
+        }
+        let to_light = math3d::vec3d_scale(&offset, 1.0 / distance);
+        let mut attenuation =
+            1.0 / (1.0 + self.falloff_linear.max(0.0) * distance + self.falloff_quadratic.max(0.0) * distance * distance);
+        if self.kind == LightKind::Spot {
+            let cos_angle = math3d::vec3d_dot(&to_light, &self.direction);
+            let outer = self.spot_outer.max(0.0);
+            let inner = self.spot_inner.clamp(0.0, outer);
+            attenuation *= if inner < outer {
+                utils::smoothstep(outer.cos(), inner.cos(), cos_angle)
+            } else if cos_angle >= outer.cos() {
+                1.0
+            } else {
+                0.0
+            };
+        }
+        (to_light, distance, attenuation)
+    }
+}
+
+/// Camera of the primary G-buffer, for reconstructing surface positions
+/// from `z_pos` (same mapping as `raymarcher::pixel_direction`).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PaintView {
+    pub camera_pos: Vec3D,
+    pub ray_dir_base: Vec3D,
+    pub ray_dx: Vec3D,
+    pub ray_dy: Vec3D,
+    pub max_ray_length: f64,
+}
+
+impl PaintView {
+    pub fn from_render_params(params: &RenderParams) -> Self {
+        Self {
+            camera_pos: params.camera_pos,
+            ray_dir_base: params.ray_dir_base,
+            ray_dx: params.ray_dx,
+            ray_dy: params.ray_dy,
+            max_ray_length: params.max_ray_length,
+        }
+    }
+
+    /// Unit view ray through (fractional) pixel coordinates.
+    pub fn pixel_direction(&self, fx: f64, fy: f64, width: u32, height: u32) -> Vec3D {
+        let hw = width as f64 * 0.5;
+        let hh = height as f64 * 0.5;
+        let px = (fx - hw) / hw;
+        let py = (fy - hh) / hh;
+        math3d::vec3d_normalized(&Vec3D {
+            x: self.ray_dir_base.x + px * self.ray_dx.x + py * self.ray_dy.x,
+            y: self.ray_dir_base.y + px * self.ray_dx.y + py * self.ray_dy.y,
+            z: self.ray_dir_base.z + px * self.ray_dx.z + py * self.ray_dy.z,
+        })
+    }
+
+    /// World position of a G-buffer hit at (fractional) pixel coordinates.
+    pub fn world_position(&self, fx: f64, fy: f64, width: u32, height: u32, z_pos: u16) -> Vec3D {
+        let dir = self.pixel_direction(fx, fy, width, height);
+        let t = z_pos as f64 / 65535.0 * self.max_ray_length;
+        math3d::vec3d_add(&self.camera_pos, &math3d::vec3d_scale(&dir, t))
+    }
+
+    /// Fractional pixel coordinates that look along `dir` (None when `dir`
+    /// points behind the camera). Inverse of the mapping in `world_position`.
+    pub fn screen_position(&self, dir: &Vec3D, width: u32, height: u32) -> Option<(f64, f64)> {
+        let n = math3d::vec3d_cross(&self.ray_dx, &self.ray_dy);
+        let d_n = math3d::vec3d_dot(dir, &n);
+        if d_n.abs() < 1e-300 {
+            return None;
+        }
+        // dir · s = base + px·dx + py·dy
+        let s = math3d::vec3d_dot(&self.ray_dir_base, &n) / d_n;
+        if s <= 0.0 {
+            return None;
+        }
+        let w = math3d::vec3d_sub(&math3d::vec3d_scale(dir, s), &self.ray_dir_base);
+        let ex = math3d::vec3d_cross(&self.ray_dy, &n);
+        let ey = math3d::vec3d_cross(&n, &self.ray_dx);
+        let px = math3d::vec3d_dot(&w, &ex) / math3d::vec3d_dot(&self.ray_dx, &ex);
+        let py = math3d::vec3d_dot(&w, &ey) / math3d::vec3d_dot(&self.ray_dy, &ey);
+        let (hw, hh) = (width as f64 * 0.5, height as f64 * 0.5);
+        Some((px * hw + hw, py * hh + hh))
+    }
+}
+
+/// Surface reflectance model of the paint pass.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ShadingModel {
+    /// Lambert diffuse + Blinn-Phong specular (MB3D)
+    #[default]
+    Phong,
+    /// GGX metallic/roughness (see `pbr`)
+    Pbr,
+}
+
+/// What drives the surface color.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorSource {
+    /// Smooth iteration count (`color_gradient`)
+    Iteration = 0,
+    /// Orbit trap distance
+    OrbitTrap = 1,
+    /// Normalized depth
+    Depth = 2,
+    /// Ray-march steps (the `ambient` channel; only meaningful with
+    /// hemispheric AO off)
+    Steps = 3,
+    /// World position mapped straight to RGB (needs `view`)
+    Position = 4,
+    /// Surface curvature (`SiLight5::curvature`; crevices low, ridges high)
+    Curvature = 5,
+    /// Second (point) orbit trap of extended G-buffers (`SiLight6`); the
+    /// orbit trap stands in for packed ones
+    OrbitTrap2 = 6,
+}
+
+/// Blend of coloring sources. Scalar sources are averaged by weight into one
+/// gradient position; `Position` is mixed in as a color by its share of the
+/// total weight.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ColoringSettings {
+    /// Weight per `ColorSource` (indexed by its discriminant)
+    pub weights: [f64; 7],
+    /// Color cycles per world unit of the `Position` source
+    pub position_scale: f64,
+}
+
+impl Default for ColoringSettings {
+    fn default() -> Self {
+        Self { weights: [1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0], position_scale: 1.0 }
+    }
+}
+
+impl ColoringSettings {
+    /// Weight of one source (never negative).
+    pub fn weight(&self, source: ColorSource) -> f64 {
+        self.weights[source as usize].max(0.0)
+    }
+
+    /// Surface color of a hit from `gradient`, the world `position` and the
+    /// second trap channel `trap2` of extended G-buffers.
+    pub fn surface_color(
+        &self,
+        pixel: &SiLight5,
+        position: Option<&Vec3D>,
+        trap2: Option<f64>,
+        gradient: &ColorGradient,
+    ) -> (f64, f64, f64) {
+        let scalars = [
+            (ColorSource::Iteration, pixel.gradient_position()),
+            (ColorSource::OrbitTrap, pixel.orbit_trap as f64 / 65535.0),
+            (ColorSource::Depth, pixel.z_pos as f64 / 65535.0),
+            (ColorSource::Steps, pixel.ambient as f64 / 65535.0),
+            (ColorSource::Curvature, 0.5 + 0.5 * pixel.curvature()),
+            (ColorSource::OrbitTrap2, trap2.unwrap_or(pixel.orbit_trap as f64 / 65535.0)),
+        ];
+        let (mut sum, mut total) = (0.0, 0.0);
+        for (source, t) in scalars {
+            let w = self.weight(source);
+            sum += w * t;
+            total += w;
+        }
+        let color = if total > 0.0 { gradient.sample(sum / total) } else { (0.0, 0.0, 0.0) };
+
+        let w_pos = self.weight(ColorSource::Position);
+        match position {
+            Some(p) if w_pos > 0.0 => {
+                let k = self.position_scale * 2.0 * std::f64::consts::PI;
+                let channel = |v: f64| 0.5 + 0.5 * (v * k).cos();
+                let share = w_pos / (w_pos + total);
+                (
+                    utils::lerp(color.0, channel(p.x), share),
+                    utils::lerp(color.1, channel(p.y), share),
+                    utils::lerp(color.2, channel(p.z), share),
+                )
+            }
+            // Only the position was selected but it is unknown here
+            _ if total <= 0.0 => gradient.sample(pixel.gradient_position()),
+            _ => color,
+        }
+    }
+}
+
+/// Second gradient layer with its own coloring sources, blended over the
+/// surface gradient.
+#[derive(Clone, Debug)]
+pub struct GradientLayer {
+    pub gradient: ColorGradient,
+    pub coloring: ColoringSettings,
+    pub blend: GradientBlend,
+    /// Layer opacity [0, 1]
+    pub weight: f64,
+}
+
+impl Default for GradientLayer {
+    fn default() -> Self {
+        Self {
+            gradient: ColorGradient::default(),
+            coloring: ColoringSettings { weights: [0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0], position_scale: 1.0 },
+            blend: GradientBlend::Mix,
+            weight: 0.5,
+        }
+    }
+}
+
+/// Light emitted by surfaces whose orbit trap falls in a range, independent
+/// of the lights (glowing veins and cracks).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EmissionSettings {
+    /// Emitting orbit-trap range, normalized like `ColorSource::OrbitTrap`
+    pub trap_min: f64,
+    pub trap_max: f64,
+    /// Width of the fade at both ends of the range
+    pub softness: f64,
+    pub color: (f64, f64, f64),
+    /// Emitted amount at full weight (0 disables emission)
+    pub intensity: f64,
+}
+
+impl Default for EmissionSettings {
+    fn default() -> Self {
+        Self { trap_min: 0.0, trap_max: 0.1, softness: 0.02, color: (1.0, 0.5, 0.1), intensity: 0.0 }
+    }
+}
+
+impl EmissionSettings {
+    pub fn enabled(&self) -> bool {
+        self.intensity > 0.0
+    }
+
+    /// Emitted light of a surface with normalized orbit trap `trap`.
+    pub fn emitted(&self, trap: f64) -> (f64, f64, f64) {
+        let w = if self.softness > 0.0 {
+            utils::smoothstep(self.trap_min - self.softness, self.trap_min, trap)
+                * (1.0 - utils::smoothstep(self.trap_max, self.trap_max + self.softness, trap))
+        } else if (self.trap_min..=self.trap_max).contains(&trap) {
+            1.0
+        } else {
+            0.0
+        };
+        let k = w * self.intensity;
+        (self.color.0 * k, self.color.1 * k, self.color.2 * k)
+    }
+}
+
+/// Rim (back) light strongest where the surface turns away from the viewer,
+/// so silhouettes read against dark backgrounds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RimSettings {
+    /// Added light at grazing angles (0 disables the rim)
+    pub intensity: f64,
+    /// Higher values confine the rim closer to the silhouette
+    pub exponent: f64,
+    pub color: (f64, f64, f64),
+}
+
+impl Default for RimSettings {
+    fn default() -> Self {
+        Self { intensity: 0.0, exponent: 3.0, color: (1.0, 1.0, 1.0) }
+    }
+}
+
+impl RimSettings {
+    pub fn enabled(&self) -> bool {
+        self.intensity > 0.0
+    }
+
+    /// Rim light for a surface with unit normal `n` seen from `to_eye`.
+    pub fn rim(&self, n: &Vec3D, to_eye: &Vec3D) -> (f64, f64, f64) {
+        let n_dot_v = utils::clamp(math3d::vec3d_dot(n, to_eye), 0.0, 1.0);
+        let k = (1.0 - n_dot_v).powf(self.exponent.max(0.0)) * self.intensity;
+        (self.color.0 * k, self.color.1 * k, self.color.2 * k)
+    }
+}
+
+/// Occlusion from the G-buffer curvature channel: crevices darken, ridges
+/// brighten.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CurvatureShading {
+    /// Darkening of full-strength crevices [0, 1]
+    pub crevice: f64,
+    /// Brightening of full-strength ridges
+    pub ridge: f64,
+}
+
+impl CurvatureShading {
+    /// AO multiplier for normalized curvature `c` in [-1, 1].
+    pub fn factor(&self, c: f64) -> f64 {
+        (1.0 - self.crevice * (-c).max(0.0)).max(0.0) * (1.0 + self.ridge * c.max(0.0))
+    }
 }
 
 /// Full lighting/painting configuration.
@@ -36,38 +419,389 @@ pub struct PaintConfig {
     /// Fog parameters
     pub fog_density: f64,
     pub fog_color: (f64, f64, f64),
+    /// Fog color by normalized depth, replacing `fog_color` (None = flat)
+    pub fog_gradient: Option<ColorGradient>,
+    /// Exponential fog by world height, on top of the depth fog (needs `view`)
+    pub height_fog: HeightFog,
+    /// Screen-space light shafts toward one light (needs `view`)
+    pub shafts: ShaftSettings,
+    /// Orbit-trap driven emission, added after lighting and AO
+    pub emission: EmissionSettings,
+    /// Triplanar texture modulating albedo and roughness (None = off; needs `view`)
+    pub texture: Option<SurfaceTexture>,
+    /// Procedural normal perturbation before lighting (needs `view`)
+    pub bump: BumpSettings,
+    /// Rim light by N·V
+    pub rim: RimSettings,
     /// Background color
     pub bg_color: (f64, f64, f64),
+    /// Procedural sky replacing `bg_color` on misses (None = flat; needs `view`)
+    pub sky: Option<SkySettings>,
+    /// Analytic daylight: sky background and light #0 from the sun position
+    /// (None = off; replaces `sky`)
+    pub sun_sky: Option<SunSky>,
     /// Camera direction (for specular calculation)
     pub view_dir: Vec3D,
     /// AO strength multiplier
     pub ao_strength: f64,
+    /// AO modulation by the curvature channel
+    pub curvature: CurvatureShading,
+    /// Screen-space AO from neighbouring depths and normals
+    pub ssao: SsaoSettings,
+    /// Blend weight of the reflection layer [0, 1]
+    pub reflectivity: f64,
+    /// Reflectivity by material id, replacing `reflectivity` for listed ids
+    pub material_reflectivity: Vec<(u8, f64)>,
+    /// Scale reflectivity per pixel by the smoothness (255 − roughness byte)
+    pub reflect_from_roughness: bool,
+    /// Blend weight of the transmission layer [0, 1]
+    pub transparency: f64,
+    /// Material tint; each channel absorbs (1 − tint) × density per unit of interior path
+    pub glass_color: (f64, f64, f64),
+    pub absorption_density: f64,
+    /// Local exposure map from AO and depth ("adaptive lighting")
+    pub exposure: ExposureSettings,
+    /// Split light between diffuse, specular and reflection (kd + ks + kr = 1)
+    /// instead of adding them
+    pub energy_conserving: bool,
+    /// Which G-buffer data drives the surface gradient
+    pub coloring: ColoringSettings,
+    /// Second gradient blended over the surface color (None = single gradient)
+    pub gradient_layer: Option<GradientLayer>,
+    /// Colors of interior hits (`SiLight5::is_inside`), sampled by orbit trap
+    pub interior_gradient: ColorGradient,
+    /// Surface gradients for composite objects, by material id (id 0 uses `gradient`)
+    pub material_gradients: Vec<(u8, ColorGradient)>,
+    /// Halo around silhouettes from the miss distance (None = off)
+    pub glow: Option<GlowSettings>,
+    /// Mark pixels that clip in any channel (None = off)
+    pub clip_diagnostics: Option<ClipDiagnostics>,
+    /// Crop/safe-area guides for the overlay buffer (None = no overlay)
+    pub safe_regions: Option<SafeRegionSettings>,
+    /// Primary camera; needed to place surfaces for point and spot lights
+    pub view: Option<PaintView>,
+    /// Image-based lighting from an environment map (None = off)
+    pub environment: Option<EnvironmentLighting>,
+    /// Shade in linear light and encode the output as sRGB (false = legacy
+    /// direct mapping of shaded values to bytes)
+    pub linear_workflow: bool,
+    /// Reflectance model
+    pub shading: ShadingModel,
+    /// PBR parameters by material id (id 0 is the fallback for unlisted ids)
+    pub pbr_materials: Vec<(u8, PbrMaterial)>,
+    /// Normal-incidence reflectance for Schlick Fresnel on Phong highlights
+    /// (0 = off, highlights keep the same strength at all angles)
+    pub fresnel_f0: f64,
+    /// Highlight tint by material id (white for unlisted ids)
+    pub specular_tints: Vec<(u8, (f64, f64, f64))>,
+    /// Cel shading: banded Phong diffuse and G-buffer outlines (None = off)
+    pub toon: Option<ToonSettings>,
+    /// Record layout of the G-buffer handed to the wasm paint entry points
+    pub gbuffer_format: GBufferFormat,
+}
+
+/// Secondary G-buffer layers for the paint pass (see `raymarcher::GBufferLayers`).
+#[derive(Clone, Copy, Default)]
+pub struct PaintLayers<'a> {
+    pub reflect: Option<&'a [SiLight5]>,
+    pub transmit: Option<&'a [SiLight5]>,
+    /// Extended records of the primary buffer: exact world positions and
+    /// the second trap channel
+    pub extended: Option<&'a [SiLight6]>,
 }
 
+/// Paint section tag: reflection `[reflectivity, from_roughness]`.
+pub const SECTION_REFLECTION: u32 = 1;
+/// Paint section tag: transparency `[transparency, tint_r, tint_g, tint_b, density]`.
+pub const SECTION_TRANSPARENCY: u32 = 2;
+/// Paint section tag: exposure map `[radius, strength, depth_weight]`.
+pub const SECTION_EXPOSURE: u32 = 3;
+/// Paint section tag: safe-region overlay `[aspect, action_safe, title_safe, matte_alpha, r, g, b]`.
+pub const SECTION_SAFE_REGIONS: u32 = 4;
+/// Paint section tag: material gradient `[material_id, num_stops, (position, r, g, b)*]`.
+pub const SECTION_MATERIAL_GRADIENT: u32 = 5;
+/// Paint section tag: energy-conserving materials `[enabled]`.
+pub const SECTION_ENERGY: u32 = 6;
+/// Paint section tag: clipping diagnostics `[marker (0 zebra, 1 solid), r, g, b]`.
+pub const SECTION_CLIP_DIAGNOSTICS: u32 = 7;
+/// Paint section tag: silhouette glow `[r, g, b, width_px, falloff, intensity]`.
+pub const SECTION_GLOW: u32 = 8;
+/// Paint section tag: primary camera `[pos xyz, dir_base xyz, dx xyz, dy xyz, max_ray_length]`.
+pub const SECTION_VIEW: u32 = 9;
+/// Paint section tag: positional light
+/// `[light_index, kind (0 directional, 1 point, 2 spot), pos xyz, linear, quadratic, inner_rad, outer_rad]`.
+pub const SECTION_LIGHT_SOURCE: u32 = 10;
+/// Paint section tag: environment lightmap `[handle, diffuse, specular, rotation_rad]`.
+pub const SECTION_ENVIRONMENT: u32 = 11;
+/// Paint section tag: color space `[linear_workflow, srgb_inputs]`; with
+/// `srgb_inputs` set, gradient stops and the light, ambient, fog, background,
+/// glass and glow colors are decoded from sRGB on load.
+pub const SECTION_COLOR_SPACE: u32 = 12;
+/// Paint section tag: shading model `[model (0 Phong, 1 PBR)]`.
+pub const SECTION_SHADING_MODEL: u32 = 13;
+/// Paint section tag: PBR material `[material_id, roughness, metallic, reflectance]`.
+pub const SECTION_PBR_MATERIAL: u32 = 14;
+/// Paint section tag: Schlick Fresnel on Phong highlights `[f0]`.
+pub const SECTION_FRESNEL: u32 = 15;
+/// Paint section tag: light highlight color `[light_index, r, g, b]`.
+pub const SECTION_LIGHT_SPECULAR: u32 = 16;
+/// Paint section tag: material highlight tint `[material_id, r, g, b]`.
+pub const SECTION_MATERIAL_SPECULAR: u32 = 17;
+/// Paint section tag: interior gradient `[num_stops, (position, r, g, b)*]`.
+pub const SECTION_INTERIOR_GRADIENT: u32 = 18;
+/// Paint section tag: coloring sources
+/// `[w_iteration, w_orbit_trap, w_depth, w_steps, w_position, position_scale, w_curvature,
+/// w_orbit_trap2]`.
+pub const SECTION_COLORING: u32 = 19;
+/// Paint section tag: second gradient `[num_stops, (position, r, g, b)*]`.
+pub const SECTION_GRADIENT_LAYER: u32 = 20;
+/// Paint section tag: second gradient blending
+/// `[mode (0 mix, 1 multiply, 2 overlay), weight, coloring weights (as SECTION_COLORING)...]`.
+pub const SECTION_GRADIENT_LAYER_BLEND: u32 = 21;
+/// Paint section tag: cosine palette replacing a gradient's stops
+/// `[target (0 surface, 1 interior, 2 second layer, 3 fog), a rgb, b rgb, c rgb, d rgb]`.
+pub const SECTION_COSINE_PALETTE: u32 = 22;
+/// Paint section tag: gradient blending space
+/// `[target (as SECTION_COSINE_PALETTE), space (0 RGB, 1 OKLab, 2 HSV)]`.
+pub const SECTION_GRADIENT_INTERPOLATION: u32 = 23;
+/// Paint section tag: screen-space AO `[radius_px, samples, strength, range, bias]`.
+pub const SECTION_SSAO: u32 = 24;
+/// Paint section tag: cel shading
+/// `[bands, outline_width_px, outline_r, outline_g, outline_b, depth_threshold, normal_threshold]`.
+pub const SECTION_TOON: u32 = 25;
+/// Paint section tag: height fog `[density, base_height, falloff, up_x, up_y, up_z]`.
+pub const SECTION_HEIGHT_FOG: u32 = 26;
+/// Paint section tag: fog color by depth `[num_stops, (position, r, g, b)...]`.
+pub const SECTION_FOG_GRADIENT: u32 = 27;
+/// Paint section tag: light shafts
+/// `[light_index, strength, samples, density, decay, surface_scatter]`.
+pub const SECTION_LIGHT_SHAFTS: u32 = 28;
+/// Paint section tag: orbit-trap emission
+/// `[trap_min, trap_max, softness, r, g, b, intensity]`.
+pub const SECTION_EMISSION: u32 = 29;
+/// Paint section tag: triplanar texture
+/// `[pattern (0 noise, 1 stripes, 2 checker, 3 image), scale, albedo_strength,
+/// roughness_strength, sharpness, image_handle]`.
+pub const SECTION_TEXTURE: u32 = 30;
+/// Paint section tag: procedural bumps `[amplitude, frequency]`.
+pub const SECTION_BUMP: u32 = 31;
+/// Paint section tag: rim light `[intensity, exponent, r, g, b]`.
+pub const SECTION_RIM: u32 = 32;
+/// Paint section tag: light switch `[light_index, enabled]`.
+pub const SECTION_LIGHT_ENABLED: u32 = 33;
+/// Paint section tag: sky background
+/// `[zenith_r, zenith_g, zenith_b, horizon_r, horizon_g, horizon_b, ground_r, ground_g, ground_b,
+/// exponent, fog_to_sky, sun_light (−1 = none), sun_size, sun_intensity, glow_exponent,
+/// glow_strength, up_x, up_y, up_z]`.
+pub const SECTION_SKY: u32 = 34;
+/// Paint section tag: sun/sky model driving light #0
+/// `[elevation, azimuth, turbidity, sky_intensity, sun_intensity, sun_size, disc_intensity,
+/// ground_albedo, fog_to_sky]` (angles in radians).
+pub const SECTION_SUN_SKY: u32 = 35;
+/// Paint section tag: material reflectivity `[material_id, reflectivity]`.
+pub const SECTION_MATERIAL_REFLECTIVITY: u32 = 36;
+/// Paint section tag: curvature occlusion `[crevice, ridge]`.
+pub const SECTION_CURVATURE: u32 = 37;
+/// Paint section tag: G-buffer layout `[format (0 packed SiLight5, 1 extended SiLight6)]`.
+pub const SECTION_GBUFFER_FORMAT: u32 = 38;
+/// Paint section tag: buffer layout stamp `[magic, version]` (see `engine::validate`).
+pub const SECTION_LAYOUT_VERSION: u32 = 39;
+
 impl Default for PaintConfig {
     fn default() -> Self {
         Self {
-            lights: vec![
-                LightConfig {
-                    direction: Vec3D { x: 0.577, y: 0.577, z: -0.577 },
-                    color: (1.0, 1.0, 1.0),
-                    amplitude: 1.0,
-                    specular_size: 32.0,
-                    specular_intensity: 0.5,
-                },
-            ],
+            lights: vec![LightConfig::default()],
             gradient: ColorGradient::default(),
+            coloring: ColoringSettings::default(),
+            gradient_layer: None,
+            interior_gradient: ColorGradient::default_interior(),
             ambient_color: (0.25, 0.25, 0.375),
             ambient_intensity: 0.3,
             fog_density: 0.0,
             fog_color: (0.0, 0.0, 0.0),
+            fog_gradient: None,
+            height_fog: HeightFog::default(),
+            shafts: ShaftSettings::default(),
+            emission: EmissionSettings::default(),
+            texture: None,
+            bump: BumpSettings::default(),
+            rim: RimSettings::default(),
             bg_color: (0.02, 0.02, 0.05),
+            sky: None,
+            sun_sky: None,
             view_dir: Vec3D { x: 0.0, y: 0.0, z: 1.0 },
             ao_strength: 0.5,
+            curvature: CurvatureShading::default(),
+            ssao: SsaoSettings::default(),
+            reflectivity: 0.0,
+            material_reflectivity: Vec::new(),
+            reflect_from_roughness: false,
+            transparency: 0.0,
+            glass_color: (1.0, 1.0, 1.0),
+            absorption_density: 0.0,
+            exposure: ExposureSettings::default(),
+            energy_conserving: false,
+            material_gradients: Vec::new(),
+            glow: None,
+            clip_diagnostics: None,
+            safe_regions: None,
+            view: None,
+            environment: None,
+            linear_workflow: false,
+            shading: ShadingModel::Phong,
+            pbr_materials: Vec::new(),
+            fresnel_f0: 0.0,
+            specular_tints: Vec::new(),
+            toon: None,
+            gbuffer_format: GBufferFormat::Packed,
         }
     }
 }
 
+impl PaintConfig {
+    /// Surface gradient for a material id, falling back to the main gradient.
+    pub fn surface_gradient(&self, material: u8) -> &ColorGradient {
+        self.material_gradients
+            .iter()
+            .find(|(id, _)| *id == material)
+            .map_or(&self.gradient, |(_, g)| g)
+    }
+
+    /// PBR parameters for a material id, falling back to id 0 and then the defaults.
+    pub fn pbr_material(&self, material: u8) -> PbrMaterial {
+        let find = |id: u8| self.pbr_materials.iter().find(|(m, _)| *m == id).map(|(_, p)| *p);
+        find(material).or_else(|| find(0)).unwrap_or_default()
+    }
+
+    /// Gradient addressed by a section target: 0 surface, 1 interior, 2 second layer, 3 fog.
+    fn gradient_target(&mut self, target: u32) -> &mut ColorGradient {
+        match target {
+            1 => &mut self.interior_gradient,
+            2 => &mut self.gradient_layer.get_or_insert_with(GradientLayer::default).gradient,
+            3 => self.fog_gradient.get_or_insert_with(ColorGradient::default),
+            _ => &mut self.gradient,
+        }
+    }
+
+    /// Reflection blend weight for a material id.
+    pub fn reflectivity_for(&self, material: u8) -> f64 {
+        self.material_reflectivity
+            .iter()
+            .find(|(id, _)| *id == material)
+            .map_or(self.reflectivity, |(_, r)| *r)
+    }
+
+    /// Reflection blend weight of a surface entry: its material's reflectivity,
+    /// scaled by smoothness when `reflect_from_roughness` is set.
+    pub fn reflection_weight(&self, pixel: &SiLight5) -> f64 {
+        let mut k = self.reflectivity_for(pixel.material_id());
+        if self.reflect_from_roughness {
+            k *= 1.0 - (pixel.roughness & 0xFF) as f64 / 255.0;
+        }
+        k
+    }
+
+    /// Highlight tint for a material id.
+    pub fn specular_tint(&self, material: u8) -> (f64, f64, f64) {
+        self.specular_tints
+            .iter()
+            .find(|(id, _)| *id == material)
+            .map_or((1.0, 1.0, 1.0), |(_, tint)| *tint)
+    }
+
+    /// Output byte for a shaded channel value.
+    #[inline]
+    pub fn encode(&self, v: f64) -> u8 {
+        if self.linear_workflow {
+            utils::float_to_byte(utils::linear_to_srgb(v))
+        } else {
+            utils::float_to_byte(v)
+        }
+    }
+
+    /// 16-bit output sample for a shaded channel value.
+    #[inline]
+    pub fn encode16(&self, v: f64) -> u16 {
+        let v = if self.linear_workflow { utils::linear_to_srgb(v) } else { v };
+        (v.clamp(0.0, 1.0) * 65535.0).round() as u16
+    }
+
+    /// Shading value of an output byte (inverse of `encode`).
+    #[inline]
+    pub fn decode(&self, b: u8) -> f64 {
+        let v = utils::byte_to_float(b);
+        if self.linear_workflow { utils::srgb_to_linear(v) } else { v }
+    }
+
+    /// Decode every color input from sRGB to linear light.
+    fn linearize_inputs(&mut self) {
+        let lin = |c: (f64, f64, f64)| {
+            (utils::srgb_to_linear(c.0), utils::srgb_to_linear(c.1), utils::srgb_to_linear(c.2))
+        };
+        self.gradient = self.gradient.linearized();
+        self.interior_gradient = self.interior_gradient.linearized();
+        if let Some(layer) = &mut self.gradient_layer {
+            layer.gradient = layer.gradient.linearized();
+        }
+        for (_, g) in &mut self.material_gradients {
+            *g = g.linearized();
+        }
+        if let Some(g) = &mut self.fog_gradient {
+            *g = g.linearized();
+        }
+        for light in &mut self.lights {
+            light.color = lin(light.color);
+            light.specular_color = light.specular_color.map(lin);
+        }
+        for (_, tint) in &mut self.specular_tints {
+            *tint = lin(*tint);
+        }
+        self.ambient_color = lin(self.ambient_color);
+        self.fog_color = lin(self.fog_color);
+        self.bg_color = lin(self.bg_color);
+        self.glass_color = lin(self.glass_color);
+        if let Some(sky) = &mut self.sky {
+            sky.zenith = lin(sky.zenith);
+            sky.horizon = lin(sky.horizon);
+            sky.ground = lin(sky.ground);
+        }
+        if let Some(glow) = &mut self.glow {
+            glow.color = lin(glow.color);
+        }
+        self.emission.color = lin(self.emission.color);
+        self.rim.color = lin(self.rim.color);
+        if let Some(toon) = &mut self.toon {
+            toon.outline_color = lin(toon.outline_color);
+        }
+    }
+
+    /// Whether any light needs surface positions (and so `view`).
+    pub fn has_positional_lights(&self) -> bool {
+        self.lights.iter().any(|l| l.kind.is_positional())
+    }
+
+    /// Whether painting needs the camera: positional lights, height fog,
+    /// light shafts, surface textures, bumps or a sky.
+    pub fn needs_view(&self) -> bool {
+        self.has_positional_lights()
+            || self.height_fog.enabled()
+            || self.shafts.enabled()
+            || self.texture.is_some()
+            || self.bump.enabled()
+            || self.sky.is_some()
+            || self.sun_sky.is_some()
+    }
+
+    /// Let the sun/sky model set light #0 (adding it if there are no lights).
+    pub fn apply_sun_sky(&mut self) {
+        let Some(sun_sky) = self.sun_sky else { return };
+        if self.lights.is_empty() {
+            self.lights.push(LightConfig::default());
+        }
+        sun_sky.drive_light(&mut self.lights[0]);
+    }
+}
+
 /// Paint the complete G-buffer into RGBA output.
 ///
 /// Port of the PaintThread.CalcPixelColor2 deferred shading pass.
@@ -78,24 +812,382 @@ pub fn paint_gbuffer(
     height: u32,
     config: &PaintConfig,
 ) {
-    let total = (width * height) as usize;
+    paint_gbuffer_layers(gbuffer, PaintLayers::default(), rgba_out, width, height, config);
+}
 
-    for i in 0..total {
-        let pixel = &gbuffer[i];
-        let ri = i * 4;
+/// Paint with optional reflection and transmission layers, blended in by
+/// `config.reflectivity` and `config.transparency`.
+pub fn paint_gbuffer_layers(
+    gbuffer: &[SiLight5],
+    layers: PaintLayers,
+    rgba_out: &mut [u8],
+    width: u32,
+    height: u32,
+    config: &PaintConfig,
+) {
+    paint_gbuffer_range(gbuffer, layers, rgba_out, width, height, 0..height, config);
+}
 
-        if ri + 3 >= rgba_out.len() { break; }
+/// Paint an extended G-buffer: surfaces are placed at their stored world
+/// positions and the second trap channel feeds `ColorSource::OrbitTrap2`.
+pub fn paint_gbuffer_extended(
+    records: &[SiLight6],
+    rgba_out: &mut [u8],
+    width: u32,
+    height: u32,
+    config: &PaintConfig,
+) {
+    let gbuffer: Vec<SiLight5> = records.iter().map(|r| r.base).collect();
+    let layers = PaintLayers { extended: Some(records), ..Default::default() };
+    paint_gbuffer_layers(&gbuffer, layers, rgba_out, width, height, config);
+}
+
+/// Repaint only the image rows in `rows` of a full-size `rgba_out`, e.g. a
+/// dirty region, or one worker's share of a parallel repaint. Neighbourhood
+/// passes (SSAO, outlines, exposure) still see the whole G-buffer, so the
+/// rows match a full repaint exactly.
+pub fn paint_gbuffer_range(
+    gbuffer: &[SiLight5],
+    layers: PaintLayers,
+    rgba_out: &mut [u8],
+    width: u32,
+    height: u32,
+    rows: Range<u32>,
+    config: &PaintConfig,
+) {
+    let pixels = rgba_out.len() / 4;
+    paint_pixels(gbuffer, layers, width, height, rows, config, pixels, |i, color| {
+        let out = &mut rgba_out[i * 4..i * 4 + 4];
+        out[0] = config.encode(color.0);
+        out[1] = config.encode(color.1);
+        out[2] = config.encode(color.2);
+        out[3] = 255;
+    });
+}
+
+/// Like `paint_gbuffer_layers` with unquantized float RGBA output: linear
+/// light under `config.linear_workflow`, unclamped, alpha 1.
+pub fn paint_gbuffer_f32(
+    gbuffer: &[SiLight5],
+    layers: PaintLayers,
+    rgba_out: &mut [f32],
+    width: u32,
+    height: u32,
+    config: &PaintConfig,
+) {
+    let pixels = rgba_out.len() / 4;
+    paint_pixels(gbuffer, layers, width, height, 0..height, config, pixels, |i, color| {
+        rgba_out[i * 4..i * 4 + 4].copy_from_slice(&[color.0 as f32, color.1 as f32, color.2 as f32, 1.0]);
+    });
+}
+
+/// Shade the G-buffer entries of the image rows in `rows` (and below
+/// `pixels`), handing each final color to `write`.
+#[allow(clippy::too_many_arguments)]
+fn paint_pixels(
+    gbuffer: &[SiLight5],
+    layers: PaintLayers,
+    width: u32,
+    height: u32,
+    rows: Range<u32>,
+    config: &PaintConfig,
+    pixels: usize,
+    mut write: impl FnMut(usize, (f64, f64, f64)),
+) {
+    let start = (rows.start.min(height) * width) as usize;
+    let end = ((rows.end.min(height) * width) as usize).min(pixels);
+    let painter = PixelPainter::new(gbuffer, layers, width, height, rows, config);
+
+    for (i, pixel) in gbuffer.iter().enumerate().take(end).skip(start) {
+        let (x, y) = ((i as u32 % width) as f64, (i as u32 / width) as f64);
+        write(i, painter.color(i, x, y, pixel));
+    }
+}
+
+/// The per-pixel body of the paint pass, shared by full paints and the
+/// antialiasing pass so that resampled edges are shaded like their
+/// neighbours.
+pub(crate) struct PixelPainter<'a> {
+    layers: PaintLayers<'a>,
+    maps: PaintMaps,
+    shaft_color: Option<(f64, f64, f64)>,
+    width: u32,
+    height: u32,
+    config: &'a PaintConfig,
+}
+
+impl<'a> PixelPainter<'a> {
+    /// Painter with neighbourhood maps complete for the image rows in `rows`.
+    pub(crate) fn new(
+        gbuffer: &[SiLight5],
+        layers: PaintLayers<'a>,
+        width: u32,
+        height: u32,
+        rows: Range<u32>,
+        config: &'a PaintConfig,
+    ) -> Self {
+        Self {
+            layers,
+            maps: PaintMaps::new(gbuffer, width, height, rows, config),
+            shaft_color: shaft_color(config),
+            width,
+            height,
+            config,
+        }
+    }
+
+    /// Light-shaft scattering of pixel `i` added to `color`.
+    fn add_shafts(&self, color: (f64, f64, f64), i: usize) -> (f64, f64, f64) {
+        match self.shaft_color {
+            Some(c) => {
+                let s = self.maps.shaft(i);
+                (color.0 + c.0 * s, color.1 + c.1 * s, color.2 + c.2 * s)
+            }
+            None => color,
+        }
+    }
+
+    /// Final color of `pixel` seen through (fractional) pixel coordinates
+    /// (x, y). Secondary layers and neighbourhood maps are sampled at pixel
+    /// index `i`.
+    pub(crate) fn color(&self, i: usize, x: f64, y: f64, pixel: &SiLight5) -> (f64, f64, f64) {
+        let (config, maps, layers) = (self.config, &self.maps, &self.layers);
+        let (width, height) = (self.width, self.height);
+
+        // Outlines are drawn flat over both sides of an edge
+        if let (Some(toon), true) = (&config.toon, maps.outline(i)) {
+            return toon.outline_color;
+        }
 
         // Check if this pixel hit the surface (z_pos < 65535 means hit)
         if pixel.z_pos >= 65534 {
-            // Background pixel
-            rgba_out[ri] = utils::float_to_byte(config.bg_color.0);
-            rgba_out[ri + 1] = utils::float_to_byte(config.bg_color.1);
-            rgba_out[ri + 2] = utils::float_to_byte(config.bg_color.2);
-            rgba_out[ri + 3] = 255;
-            continue;
+            // Background pixel, with the silhouette halo if enabled
+            let bg = background(&view_direction(x, y, width, height, config), config);
+            let bg = match &config.glow {
+                Some(glow) => glow.apply(bg, pixel),
+                None => bg,
+            };
+            return self.add_shafts(bg, i);
+        }
+
+        let extended = layers.extended.and_then(|records| records.get(i));
+        let position = match extended {
+            Some(record) => Some(record.world_position()),
+            None => config.view.map(|view| view.world_position(x, y, width, height, pixel.z_pos)),
+        };
+        let reflect = layers.reflect.and_then(|layer| layer.get(i));
+        let mut color = shade_surface(pixel, position.as_ref(), extended.map(SiLight6::trap2), reflect.is_some(), config);
+        let k = maps.ssao(i);
+        // Secondary rays leave along (transmit, approximately) or mirrored
+        // about (reflect) the view ray; their misses show the sky there
+        let incident = match &config.view {
+            Some(view) => view.pixel_direction(x, y, width, height),
+            None => config.view_dir,
+        };
+        color = (color.0 * k, color.1 * k, color.2 * k);
+
+        // Blend what is seen through the surface, attenuated by Beer–Lambert absorption
+        if let Some(trans) = layers.transmit.and_then(|layer| layer.get(i)) {
+            if config.transparency > 0.0 {
+                let seen = shade_ray(&SiLight5 { ambient: 0, ..*trans }, &incident, config);
+                let path = trans.interior_path() * config.absorption_density;
+                let k = config.transparency;
+                color = (
+                    utils::lerp(color.0, seen.0 * (-(1.0 - config.glass_color.0) * path).exp(), k),
+                    utils::lerp(color.1, seen.1 * (-(1.0 - config.glass_color.1) * path).exp(), k),
+                    utils::lerp(color.2, seen.2 * (-(1.0 - config.glass_color.2) * path).exp(), k),
+                );
+            }
+        }
+
+        // Blend the reflection bounce, which is shaded (and fogged) on its own
+        if let Some(refl) = reflect {
+            let k = config.reflection_weight(pixel);
+            if k > 0.0 {
+                let normal = Vec3D { x: pixel.sn_x as f64, y: pixel.sn_y as f64, z: pixel.sn_z as f64 };
+                let mirrored = math3d::vec3d_reflect(&incident, &math3d::vec3d_normalized(&normal));
+                let reflected = shade_ray(refl, &mirrored, config);
+                color = if config.energy_conserving {
+                    // The lights already leave the reflection its share
+                    (color.0 + reflected.0 * k, color.1 + reflected.1 * k, color.2 + reflected.2 * k)
+                } else {
+                    (
+                        utils::lerp(color.0, reflected.0, k),
+                        utils::lerp(color.1, reflected.1, k),
+                        utils::lerp(color.2, reflected.2, k),
+                    )
+                };
+            }
+        }
+
+        // Decode depth (0–1 range)
+        let depth = pixel.z_pos as f64 / 65535.0;
+        let (mut final_r, mut final_g, mut final_b) = self.add_shafts(apply_fog(color, depth, position.as_ref(), config), i);
+
+        // Local exposure
+        let gain = maps.exposure(i);
+        final_r *= gain;
+        final_g *= gain;
+        final_b *= gain;
+
+        // Overexposure diagnostics
+        if let Some(diag) = &config.clip_diagnostics {
+            let (px, py) = (i as u32 % width, i as u32 / width);
+            (final_r, final_g, final_b) = diag.mark(px, py, (final_r, final_g, final_b));
         }
 
+        (final_r, final_g, final_b)
+    }
+}
+
+/// Neighbourhood passes over the whole G-buffer, shared by painting and relighting.
+pub(crate) struct PaintMaps {
+    exposure: Option<Vec<f32>>,
+    ssao: Option<Vec<f32>>,
+    outlines: Option<Vec<bool>>,
+    shafts: Option<Vec<f32>>,
+}
+
+impl PaintMaps {
+    /// Maps needed by `config`, complete for the image rows in `rows`.
+    pub(crate) fn new(gbuffer: &[SiLight5], width: u32, height: u32, rows: Range<u32>, config: &PaintConfig) -> Self {
+        let shaft_light = config.lights.get(config.shafts.light).filter(|_| config.shafts.enabled());
+        Self {
+            exposure: config.exposure.enabled()
+                .then(|| post::exposure_map(gbuffer, width, height, &config.exposure)),
+            ssao: config.ssao.enabled()
+                .then(|| ssao::ssao_map(gbuffer, width, height, rows.clone(), &config.ssao, config.view.as_ref())),
+            outlines: config.toon.as_ref().map(|toon| toon.outline_mask(gbuffer, width, height, rows.clone())),
+            shafts: shaft_light.zip(config.view.as_ref()).map(|(light, view)| {
+                shafts::shaft_map(gbuffer, width, height, rows, &config.shafts, light, view)
+            }),
+        }
+    }
+
+    /// Local exposure gain of pixel `i`.
+    pub(crate) fn exposure(&self, i: usize) -> f64 {
+        self.exposure.as_ref().and_then(|m| m.get(i)).map_or(1.0, |&g| g as f64)
+    }
+
+    /// SSAO multiplier of pixel `i`.
+    pub(crate) fn ssao(&self, i: usize) -> f64 {
+        self.ssao.as_ref().and_then(|m| m.get(i)).map_or(1.0, |&k| k as f64)
+    }
+
+    /// Whether pixel `i` is covered by a cel-shading outline.
+    pub(crate) fn outline(&self, i: usize) -> bool {
+        self.outlines.as_ref().and_then(|m| m.get(i).copied()).unwrap_or(false)
+    }
+
+    /// Light-shaft scattering at pixel `i`.
+    pub(crate) fn shaft(&self, i: usize) -> f64 {
+        self.shafts.as_ref().and_then(|m| m.get(i)).map_or(0.0, |&s| s as f64)
+    }
+}
+
+/// Color added per unit of light-shaft scattering (None when shafts are off
+/// or their light is missing or switched off).
+pub(crate) fn shaft_color(config: &PaintConfig) -> Option<(f64, f64, f64)> {
+    if !config.shafts.enabled() || config.view.is_none() {
+        return None;
+    }
+    let light = config.lights.get(config.shafts.light).filter(|l| l.enabled)?;
+    let k = light.amplitude * config.shafts.strength;
+    Some((light.color.0 * k, light.color.1 * k, light.color.2 * k))
+}
+
+/// View ray through (fractional) pixel coordinates (x, y) for the
+/// background. Without a view the sky is spread over the image rows.
+pub(crate) fn view_direction(x: f64, y: f64, width: u32, height: u32, config: &PaintConfig) -> Vec3D {
+    let (x, y) = (x + 0.5, y + 0.5);
+    match &config.view {
+        Some(view) => view.pixel_direction(x, y, width, height),
+        None => Vec3D { x: 0.0, y: 1.0 - 2.0 * y / height as f64, z: 1.0 },
+    }
+}
+
+/// Background seen by a miss along `dir`: the sky in that direction, or the
+/// flat background color. Primary, reflected, transmitted and supersampled
+/// misses all end here.
+pub(crate) fn background(dir: &Vec3D, config: &PaintConfig) -> (f64, f64, f64) {
+    if config.sky.is_none() && config.sun_sky.is_none() {
+        return config.bg_color;
+    }
+    let sun_light = match (&config.sun_sky, &config.sky) {
+        (Some(_), _) => Some(0),
+        (None, Some(sky)) => sky.sun_light,
+        (None, None) => None,
+    };
+    let sun = sun_light
+        .and_then(|index| config.lights.get(index))
+        .filter(|light| light.enabled)
+        .map(|light| {
+            let toward = match (&config.view, light.kind.is_positional()) {
+                (Some(view), true) => math3d::vec3d_sub(&light.position, &view.camera_pos),
+                _ => light.direction,
+            };
+            let a = light.amplitude;
+            (math3d::vec3d_normalized(&toward), (light.color.0 * a, light.color.1 * a, light.color.2 * a))
+        });
+    match (&config.sun_sky, &config.sky) {
+        (Some(sun_sky), _) => sun_sky.color(dir, sun),
+        (None, Some(sky)) => sky.color(dir, sun),
+        (None, None) => config.bg_color,
+    }
+}
+
+/// Final color of a single G-buffer entry (background, or shaded surface with fog).
+pub fn shade_pixel(pixel: &SiLight5, config: &PaintConfig) -> (f64, f64, f64) {
+    shade_ray(pixel, &config.view_dir, config)
+}
+
+/// Like `shade_pixel` for an entry seen along `dir`, which places the
+/// background behind a miss.
+pub fn shade_ray(pixel: &SiLight5, dir: &Vec3D, config: &PaintConfig) -> (f64, f64, f64) {
+    if pixel.z_pos >= 65534 {
+        return background(dir, config);
+    }
+    apply_fog(shade_surface(pixel, None, None, false, config), pixel.z_pos as f64 / 65535.0, None, config)
+}
+
+/// Shade a surface hit (ambient + Phong lights + AO), before fog.
+/// `reflected` says whether a reflection bounce is blended in afterwards.
+fn shade_surface(
+    pixel: &SiLight5,
+    position: Option<&Vec3D>,
+    trap2: Option<f64>,
+    reflected: bool,
+    config: &PaintConfig,
+) -> (f64, f64, f64) {
+    let surface = SurfacePoint::new(pixel, position, trap2, config);
+    let (mut final_r, mut final_g, mut final_b) = surface.unlit(config);
+    for (li, light) in config.lights.iter().enumerate() {
+        if !light.enabled || light.amplitude < 0.001 { continue; }
+        let (d, s) = surface.light_terms(li, light, reflected, config);
+        let highlight = light.specular_color.unwrap_or(light.color);
+        let k = light.amplitude * surface.ao;
+        final_r += (d.0 * light.color.0 + s.0 * highlight.0) * k;
+        final_g += (d.1 * light.color.1 + s.1 * highlight.1) * k;
+        final_b += (d.2 * light.color.2 + s.2 * highlight.2) * k;
+    }
+    (final_r, final_g, final_b)
+}
+
+/// Decoded G-buffer surface with everything the light loop needs.
+pub(crate) struct SurfacePoint<'a> {
+    pixel: &'a SiLight5,
+    position: Option<&'a Vec3D>,
+    normal: Vec3D,
+    incident: Vec3D,
+    to_eye: Vec3D,
+    albedo: (f64, f64, f64),
+    pbr: Option<(PbrMaterial, f64)>,
+    /// Step-count ambient occlusion factor
+    pub ao: f64,
+}
+
+impl<'a> SurfacePoint<'a> {
+    pub(crate) fn new(pixel: &'a SiLight5, position: Option<&'a Vec3D>, trap2: Option<f64>, config: &PaintConfig) -> Self {
         // Decode surface normal from G-buffer (i16 → f64)
         let nx = pixel.sn_x as f64 / 32767.0;
         let ny = pixel.sn_y as f64 / 32767.0;
@@ -103,63 +1195,244 @@ pub fn paint_gbuffer(
         let mut normal = Vec3D { x: nx, y: ny, z: nz };
         math3d::vec3d_normalize(&mut normal);
 
-        // Decode depth (0–1 range)
-        let depth = pixel.z_pos as f64 / 65535.0;
-
         // Decode AO from step count
         let ao_raw = pixel.ambient as f64 / 65535.0;
-        let ao = 1.0 - ao_raw * config.ao_strength;
+        let ao = (1.0 - ao_raw * config.ao_strength) * config.curvature.factor(pixel.curvature());
+
+        // Sample the surface color from the gradient; interior hits all reach the
+        // iteration limit, so their color follows the orbit trap instead
+        let surface = if pixel.is_inside() {
+            config.interior_gradient.sample(pixel.orbit_trap as f64 / 65535.0)
+        } else {
+            let gradient = config.surface_gradient(pixel.material_id());
+            let base = config.coloring.surface_color(pixel, position, trap2, gradient);
+            match &config.gradient_layer {
+                Some(layer) => {
+                    let color = layer.coloring.surface_color(pixel, position, trap2, &layer.gradient);
+                    layer.blend.apply(base, color, layer.weight)
+                }
+                None => base,
+            }
+        };
+        // Triplanar texture at the world position
+        let texel = config.texture.as_ref().zip(position).map(|(tex, p)| (tex, tex.sample(p, &normal)));
+        let albedo = match texel {
+            Some((tex, t)) => tex.modulate_albedo(surface, t),
+            None => surface,
+        };
+        // Micro-detail: lighting sees the bumped normal
+        if let (true, Some(p)) = (config.bump.enabled(), position) {
+            normal = config.bump.perturb(&normal, p);
+        }
+
+        // View ray direction at the surface
+        let incident = match (position, &config.view) {
+            (Some(p), Some(view)) => math3d::vec3d_normalized(&math3d::vec3d_sub(p, &view.camera_pos)),
+            _ => config.view_dir,
+        };
+        let to_eye = math3d::vec3d_scale(&incident, -1.0);
+        let pbr = (config.shading == ShadingModel::Pbr).then(|| {
+            let material = config.pbr_material(pixel.material_id());
+            let mut roughness = material.roughness_for((pixel.roughness & 0xFF) as u8);
+            if let Some((tex, t)) = texel {
+                roughness = utils::clamp(tex.modulate_roughness(roughness, t), pbr::MIN_ROUGHNESS, 1.0);
+            }
+            (material, roughness)
+        });
+
+        Self { pixel, position, normal, incident, to_eye, albedo, pbr, ao }
+    }
 
-        // Sample the surface color from the gradient
-        let grad_t = pixel.color_gradient as f64 / 65535.0;
-        let (surf_r, surf_g, surf_b) = config.gradient.sample(grad_t);
+    /// Everything but the direct lights: ambient, environment and rim light
+    /// scaled by AO, plus emission.
+    pub(crate) fn unlit(&self, config: &PaintConfig) -> (f64, f64, f64) {
+        let (surf_r, surf_g, surf_b) = self.albedo;
+        let normal = &self.normal;
 
         // Start with ambient lighting
         let mut final_r = config.ambient_color.0 * config.ambient_intensity * surf_r;
         let mut final_g = config.ambient_color.1 * config.ambient_intensity * surf_g;
         let mut final_b = config.ambient_color.2 * config.ambient_intensity * surf_b;
 
-        // Accumulate contribution from each light (Phong model)
-        for light in &config.lights {
-            if light.amplitude < 0.001 { continue; }
+        // Environment lightmap: irradiance by normal, radiance by reflection vector
+        if let Some(env) = &config.environment {
+            let (mut dr, mut dg, mut db) = env.diffuse_at(normal);
+            let (mut sr, mut sg, mut sb) = env.specular_at(&math3d::vec3d_reflect(&self.incident, normal));
+            if let Some((material, _)) = &self.pbr {
+                // Metals have no diffuse part; reflections follow Fresnel
+                let kd = 1.0 - utils::clamp(material.metallic, 0.0, 1.0);
+                (dr, dg, db) = (dr * kd, dg * kd, db * kd);
+                let n_dot_v = math3d::vec3d_dot(normal, &self.to_eye);
+                let f0 = material.f0(self.albedo);
+                sr *= pbr::fresnel_schlick(n_dot_v, f0.0);
+                sg *= pbr::fresnel_schlick(n_dot_v, f0.1);
+                sb *= pbr::fresnel_schlick(n_dot_v, f0.2);
+            }
+            final_r += dr * surf_r + sr;
+            final_g += dg * surf_g + sg;
+            final_b += db * surf_b + sb;
+        }
 
-            // Diffuse (Lambert)
-            let n_dot_l = math3d::vec3d_dot(&normal, &light.direction).max(0.0);
-            let diffuse = n_dot_l * light.amplitude;
+        // Rim light around silhouettes
+        if config.rim.enabled() {
+            let (rr, rg, rb) = config.rim.rim(normal, &self.to_eye);
+            final_r += rr;
+            final_g += rg;
+            final_b += rb;
+        }
+
+        // Apply ambient occlusion; emitted light is not occluded
+        let (er, eg, eb) = if config.emission.enabled() {
+            config.emission.emitted(self.pixel.orbit_trap as f64 / 65535.0)
+        } else {
+            (0.0, 0.0, 0.0)
+        };
+        (final_r * self.ao + er, final_g * self.ao + eg, final_b * self.ao + eb)
+    }
 
-            // Specular (Blinn-Phong)
-            let half_vec = math3d::vec3d_normalized(&Vec3D {
-                x: light.direction.x + config.view_dir.x,
-                y: light.direction.y + config.view_dir.y,
-                z: light.direction.z + config.view_dir.z,
-            });
-            let n_dot_h = math3d::vec3d_dot(&normal, &half_vec).max(0.0);
-            let specular = n_dot_h.powf(light.specular_size) * light.specular_intensity * light.amplitude;
+    /// Diffuse and specular light from light `li` per unit of amplitude,
+    /// before AO and before multiplying by the light color (diffuse) and
+    /// highlight color (specular). `reflected` says whether a reflection
+    /// bounce is blended in, which takes its share in energy-conserving mode.
+    pub(crate) fn light_terms(
+        &self,
+        li: usize,
+        light: &LightConfig,
+        reflected: bool,
+        config: &PaintConfig,
+    ) -> ((f64, f64, f64), (f64, f64, f64)) {
+        const DARK: ((f64, f64, f64), (f64, f64, f64)) = ((0.0, 0.0, 0.0), (0.0, 0.0, 0.0));
+        if self.pixel.in_shadow(li) { return DARK; }
+        let (to_light, _, attenuation) = light.incidence(self.position);
+        if attenuation <= 0.0 { return DARK; }
+        let normal = &self.normal;
+        let (surf_r, surf_g, surf_b) = self.albedo;
+        let tint = config.specular_tint(self.pixel.material_id());
 
-            final_r += (diffuse * surf_r + specular) * light.color.0;
-            final_g += (diffuse * surf_g + specular) * light.color.1;
-            final_b += (diffuse * surf_b + specular) * light.color.2;
+        if let Some((material, roughness)) = &self.pbr {
+            let (d, s) = pbr::reflected(normal, &self.to_eye, &to_light, self.albedo, material, *roughness);
+            let a = attenuation;
+            return ((d.0 * a, d.1 * a, d.2 * a), (s.0 * tint.0 * a, s.1 * tint.1 * a, s.2 * tint.2 * a));
         }
 
-        // Apply ambient occlusion
-        final_r *= ao;
-        final_g *= ao;
-        final_b *= ao;
+        // Legacy mode adds full diffuse and specular; energy-conserving mode
+        // splits the light between them and the reflection bounce so the
+        // surface never reflects more than arrives
+        let (kd, ks) = if config.energy_conserving {
+            let kr = if reflected { utils::clamp(config.reflection_weight(self.pixel), 0.0, 1.0) } else { 0.0 };
+            let ks = utils::clamp(light.specular_intensity, 0.0, 1.0) * (1.0 - kr);
+            (1.0 - kr - ks, ks)
+        } else {
+            (1.0, light.specular_intensity)
+        };
 
-        // Apply fog
-        if config.fog_density > 0.0 {
-            let fog_factor = (-depth * config.fog_density * 10.0).exp();
-            final_r = utils::lerp(config.fog_color.0, final_r, fog_factor);
-            final_g = utils::lerp(config.fog_color.1, final_g, fog_factor);
-            final_b = utils::lerp(config.fog_color.2, final_b, fog_factor);
+        // Diffuse (Lambert)
+        let n_dot_l = math3d::vec3d_dot(normal, &to_light).max(0.0);
+        let banded = config.toon.as_ref().map_or(n_dot_l, |toon| toon.quantize(n_dot_l));
+        let diffuse = banded * attenuation * kd;
+
+        // Specular (Blinn-Phong), halfway between the light and this pixel's eye ray
+        let half_vec = math3d::vec3d_normalized(&math3d::vec3d_add(&to_light, &self.to_eye));
+        let n_dot_h = math3d::vec3d_dot(normal, &half_vec).max(0.0);
+        let mut specular = n_dot_h.powf(light.specular_size) * ks * attenuation;
+        if config.energy_conserving && n_dot_l <= 0.0 {
+            specular = 0.0; // no highlights from lights behind the surface
+        }
+        // Highlights strengthen toward grazing angles
+        if config.fresnel_f0 > 0.0 {
+            specular *= pbr::fresnel_schlick(math3d::vec3d_dot(&self.to_eye, &half_vec), config.fresnel_f0);
         }
 
-        // Write RGBA output
-        rgba_out[ri] = utils::float_to_byte(final_r);
-        rgba_out[ri + 1] = utils::float_to_byte(final_g);
-        rgba_out[ri + 2] = utils::float_to_byte(final_b);
-        rgba_out[ri + 3] = 255;
+        (
+            (diffuse * surf_r, diffuse * surf_g, diffuse * surf_b),
+            (specular * tint.0, specular * tint.1, specular * tint.2),
+        )
+    }
+}
+
+/// Blend toward the fog color by normalized depth, and by height when the
+/// surface position is known.
+pub(crate) fn apply_fog(color: (f64, f64, f64), depth: f64, position: Option<&Vec3D>, config: &PaintConfig) -> (f64, f64, f64) {
+    let (fog_factor, fog_color) = fog_terms(depth, position, config);
+    if fog_factor >= 1.0 {
+        return color;
+    }
+    (
+        utils::lerp(fog_color.0, color.0, fog_factor),
+        utils::lerp(fog_color.1, color.1, fog_factor),
+        utils::lerp(fog_color.2, color.2, fog_factor),
+    )
+}
+
+/// Share of the surface color that survives the fog, and the fog color.
+pub(crate) fn fog_terms(depth: f64, position: Option<&Vec3D>, config: &PaintConfig) -> (f64, (f64, f64, f64)) {
+    let mut fog_factor = 1.0;
+    if config.fog_density > 0.0 {
+        fog_factor *= (-depth * config.fog_density * 10.0).exp();
+    }
+    if let (true, Some(view), Some(p)) = (config.height_fog.enabled(), &config.view, position) {
+        fog_factor *= config.height_fog.transmittance(&view.camera_pos, p);
+    }
+    if fog_factor >= 1.0 {
+        return (1.0, config.fog_color);
+    }
+    // Fade into the sky behind the surface
+    if let (Some(view), Some(p)) = (&config.view, position) {
+        let ray = math3d::vec3d_sub(p, &view.camera_pos);
+        match (&config.sun_sky, &config.sky) {
+            (Some(sun_sky), _) if sun_sky.fog_to_sky => return (fog_factor, sun_sky.radiance(&ray)),
+            (None, Some(sky)) if sky.fog_to_sky => return (fog_factor, sky.gradient(&ray)),
+            _ => {}
+        }
+    }
+    (fog_factor, config.fog_gradient.as_ref().map_or(config.fog_color, |g| g.sample(depth)))
+}
+
+/// Red/cyan anaglyph encodings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnaglyphMode {
+    /// Left red channel, right green/blue (full color, most retinal rivalry)
+    Color,
+    /// Luminance of each eye (no color, least rivalry)
+    Gray,
+    /// Left luminance in red, right color in green/blue
+    HalfColor,
+}
+
+impl AnaglyphMode {
+    pub fn from_u32(v: u32) -> Self {
+        match v {
+            1 => AnaglyphMode::Gray,
+            2 => AnaglyphMode::HalfColor,
+            _ => AnaglyphMode::Color,
+        }
+    }
+}
+
+/// Combine painted left/right eye images (RGBA) into a red/cyan anaglyph.
+pub fn anaglyph(left: &[u8], right: &[u8], rgba_out: &mut [u8], mode: AnaglyphMode) {
+    let luma = |p: &[u8]| (0.299 * p[0] as f64 + 0.587 * p[1] as f64 + 0.114 * p[2] as f64).round() as u8;
+    for ((l, r), out) in left.chunks_exact(4).zip(right.chunks_exact(4)).zip(rgba_out.chunks_exact_mut(4)) {
+        let (red, green, blue) = match mode {
+            AnaglyphMode::Color => (l[0], r[1], r[2]),
+            AnaglyphMode::Gray => (luma(l), luma(r), luma(r)),
+            AnaglyphMode::HalfColor => (luma(l), r[1], r[2]),
+        };
+        out.copy_from_slice(&[red, green, blue, 255]);
+    }
+}
+
+/// Parse `[w_iteration, w_orbit_trap, w_depth, w_steps, w_position, position_scale]`.
+fn coloring_from_values(values: &[f64]) -> ColoringSettings {
+    let mut weights = [0.0; 7];
+    for (w, v) in weights.iter_mut().zip(values.iter().take(5)) {
+        *w = v.max(0.0);
     }
+    // Sources added after position_scale follow it
+    for (source, k) in [(ColorSource::Curvature, 6), (ColorSource::OrbitTrap2, 7)] {
+        weights[source as usize] = values.get(k).copied().unwrap_or(0.0).max(0.0);
+    }
+    ColoringSettings { weights, position_scale: values.get(5).copied().unwrap_or(1.0) }
 }
 
 /// Build PaintConfig from a flat f64 parameter array.
@@ -171,7 +1444,8 @@ pub fn paint_gbuffer(
 ///   view_dir_x, view_dir_y, view_dir_z,
 ///   ao_strength,
 ///   num_gradient_stops,
-///   for each stop: [position, r, g, b]]
+///   for each stop: [position, r, g, b],
+///   tagged sections: [tag, count, values...]*]
 pub fn paint_config_from_buffer(data: &[f64]) -> PaintConfig {
     let mut config = PaintConfig::default();
     if data.is_empty() {
@@ -179,6 +1453,7 @@ pub fn paint_config_from_buffer(data: &[f64]) -> PaintConfig {
     }
 
     let mut idx = 0;
+    let mut srgb_inputs = false;
 
     // Read lights
     let num_lights = data[idx] as usize;
@@ -194,6 +1469,7 @@ pub fn paint_config_from_buffer(data: &[f64]) -> PaintConfig {
             amplitude: data[idx + 6],
             specular_size: data[idx + 7],
             specular_intensity: data[idx + 8],
+            ..LightConfig::default()
         });
         idx += 9;
     }
@@ -247,5 +1523,573 @@ pub fn paint_config_from_buffer(data: &[f64]) -> PaintConfig {
         }
     }
 
+    // Optional tagged sections; unknown tags are skipped
+    while idx + 1 < data.len() {
+        let tag = data[idx] as u32;
+        let count = data[idx + 1] as usize;
+        idx += 2;
+        let end = idx.saturating_add(count).min(data.len());
+        let values = &data[idx..end];
+        idx = end;
+        match tag {
+            SECTION_REFLECTION if !values.is_empty() => {
+                config.reflectivity = utils::clamp(values[0], 0.0, 1.0);
+                config.reflect_from_roughness = values.get(1).is_some_and(|&v| v != 0.0);
+            }
+            SECTION_TRANSPARENCY if values.len() >= 5 => {
+                config.transparency = utils::clamp(values[0], 0.0, 1.0);
+                config.glass_color = (values[1], values[2], values[3]);
+                config.absorption_density = values[4].max(0.0);
+            }
+            SECTION_EXPOSURE if values.len() >= 2 => {
+                config.exposure = ExposureSettings {
+                    radius: values[0].max(0.0) as u32,
+                    strength: values[1],
+                    depth_weight: values.get(2).copied().unwrap_or(0.0),
+                };
+            }
+            SECTION_SAFE_REGIONS if !values.is_empty() => {
+                let d = SafeRegionSettings::default();
+                let get = |i: usize, default: f64| values.get(i).copied().unwrap_or(default);
+                config.safe_regions = Some(SafeRegionSettings {
+                    aspect: values[0].max(0.0),
+                    action_safe: get(1, d.action_safe),
+                    title_safe: get(2, d.title_safe),
+                    matte_alpha: utils::clamp(get(3, d.matte_alpha), 0.0, 1.0),
+                    color: (get(4, d.color.0), get(5, d.color.1), get(6, d.color.2)),
+                });
+            }
+            SECTION_GLOW if values.len() >= 4 => {
+                let d = GlowSettings::default();
+                config.glow = Some(GlowSettings {
+                    color: (values[0], values[1], values[2]),
+                    width: values[3].max(0.0),
+                    falloff: values.get(4).copied().unwrap_or(d.falloff),
+                    intensity: values.get(5).copied().unwrap_or(d.intensity),
+                });
+            }
+            SECTION_CLIP_DIAGNOSTICS if !values.is_empty() => {
+                let d = ClipDiagnostics::default();
+                config.clip_diagnostics = Some(ClipDiagnostics {
+                    marker: if values[0] == 1.0 { ClipMarker::Solid } else { ClipMarker::Zebra },
+                    color: if values.len() >= 4 { (values[1], values[2], values[3]) } else { d.color },
+                });
+            }
+            SECTION_VIEW if values.len() >= 13 => {
+                let v = |i: usize| Vec3D { x: values[i], y: values[i + 1], z: values[i + 2] };
+                config.view = Some(PaintView {
+                    camera_pos: v(0),
+                    ray_dir_base: v(3),
+                    ray_dx: v(6),
+                    ray_dy: v(9),
+                    max_ray_length: values[12],
+                });
+            }
+            SECTION_LIGHT_SOURCE if values.len() >= 5 => {
+                if let Some(light) = config.lights.get_mut(values[0] as usize) {
+                    let get = |i: usize, default: f64| values.get(i).copied().unwrap_or(default);
+                    light.kind = LightKind::from_u32(values[1] as u32);
+                    light.position = Vec3D { x: values[2], y: values[3], z: values[4] };
+                    light.falloff_linear = get(5, 0.0).max(0.0);
+                    light.falloff_quadratic = get(6, 0.0).max(0.0);
+                    light.spot_outer = get(8, light.spot_outer).max(0.0);
+                    light.spot_inner = get(7, light.spot_inner).clamp(0.0, light.spot_outer);
+                }
+            }
+            SECTION_LIGHT_ENABLED if values.len() >= 2 => {
+                if let Some(light) = config.lights.get_mut(values[0] as usize) {
+                    light.enabled = values[1] != 0.0;
+                }
+            }
+            SECTION_ENVIRONMENT if !values.is_empty() => {
+                config.environment = envmap::map(values[0] as u32).map(|map| EnvironmentLighting {
+                    map,
+                    diffuse: values.get(1).copied().unwrap_or(1.0).max(0.0),
+                    specular: values.get(2).copied().unwrap_or(0.0).max(0.0),
+                    rotation: values.get(3).copied().unwrap_or(0.0),
+                });
+            }
+            SECTION_COLOR_SPACE if !values.is_empty() => {
+                config.linear_workflow = values[0] != 0.0;
+                srgb_inputs = values.get(1).is_some_and(|&v| v != 0.0);
+            }
+            SECTION_SHADING_MODEL if !values.is_empty() => {
+                config.shading = if values[0] == 1.0 { ShadingModel::Pbr } else { ShadingModel::Phong };
+            }
+            SECTION_PBR_MATERIAL if values.len() >= 3 => {
+                let id = values[0] as u8;
+                let material = PbrMaterial {
+                    roughness: utils::clamp(values[1], 0.0, 1.0),
+                    metallic: utils::clamp(values[2], 0.0, 1.0),
+                    reflectance: values.get(3).map_or(PbrMaterial::default().reflectance, |&v| utils::clamp(v, 0.0, 1.0)),
+                };
+                config.pbr_materials.retain(|(m, _)| *m != id);
+                config.pbr_materials.push((id, material));
+            }
+            SECTION_FRESNEL if !values.is_empty() => {
+                config.fresnel_f0 = utils::clamp(values[0], 0.0, 1.0);
+            }
+            SECTION_LIGHT_SPECULAR if values.len() >= 4 => {
+                if let Some(light) = config.lights.get_mut(values[0] as usize) {
+                    light.specular_color = Some((values[1], values[2], values[3]));
+                }
+            }
+            SECTION_MATERIAL_SPECULAR if values.len() >= 4 => {
+                let id = values[0] as u8;
+                config.specular_tints.retain(|(m, _)| *m != id);
+                config.specular_tints.push((id, (values[1], values[2], values[3])));
+            }
+            SECTION_GBUFFER_FORMAT if !values.is_empty() => {
+                config.gbuffer_format = GBufferFormat::from_u32(values[0] as u32);
+            }
+            SECTION_CURVATURE if !values.is_empty() => {
+                config.curvature = CurvatureShading {
+                    crevice: utils::clamp(values[0], 0.0, 1.0),
+                    ridge: values.get(1).copied().unwrap_or(0.0).max(0.0),
+                };
+            }
+            SECTION_MATERIAL_REFLECTIVITY if values.len() >= 2 => {
+                let id = values[0] as u8;
+                config.material_reflectivity.retain(|(m, _)| *m != id);
+                config.material_reflectivity.push((id, utils::clamp(values[1], 0.0, 1.0)));
+            }
+            SECTION_INTERIOR_GRADIENT if !values.is_empty() => {
+                let stops: Vec<_> = values[1..]
+                    .chunks_exact(4)
+                    .take(values[0] as usize)
+                    .map(|c| (c[0], c[1], c[2], c[3]))
+                    .collect();
+                if !stops.is_empty() {
+                    config.interior_gradient = ColorGradient::from_stops(&stops);
+                }
+            }
+            SECTION_COLORING if !values.is_empty() => {
+                config.coloring = coloring_from_values(values);
+            }
+            SECTION_GRADIENT_LAYER if !values.is_empty() => {
+                let stops: Vec<_> = values[1..]
+                    .chunks_exact(4)
+                    .take(values[0] as usize)
+                    .map(|c| (c[0], c[1], c[2], c[3]))
+                    .collect();
+                if !stops.is_empty() {
+                    config.gradient_layer.get_or_insert_with(GradientLayer::default).gradient =
+                        ColorGradient::from_stops(&stops);
+                }
+            }
+            SECTION_GRADIENT_LAYER_BLEND if values.len() >= 2 => {
+                let layer = config.gradient_layer.get_or_insert_with(GradientLayer::default);
+                layer.blend = GradientBlend::from_u32(values[0] as u32);
+                layer.weight = utils::clamp(values[1], 0.0, 1.0);
+                if values.len() > 2 {
+                    layer.coloring = coloring_from_values(&values[2..]);
+                }
+            }
+            SECTION_COSINE_PALETTE if values.len() >= 13 => {
+                if let Some(palette) = CosinePalette::from_values(&values[1..]) {
+                    config.gradient_target(values[0] as u32).cosine = Some(palette);
+                }
+            }
+            SECTION_GRADIENT_INTERPOLATION if values.len() >= 2 => {
+                config.gradient_target(values[0] as u32).interpolation = Interpolation::from_u32(values[1] as u32);
+            }
+            SECTION_SSAO if values.len() >= 3 => {
+                let d = SsaoSettings::default();
+                config.ssao = SsaoSettings {
+                    radius: values[0].max(0.0) as u32,
+                    samples: values[1].clamp(0.0, 64.0) as u32,
+                    strength: utils::clamp(values[2], 0.0, 1.0),
+                    range: values.get(3).copied().unwrap_or(d.range),
+                    bias: values.get(4).copied().unwrap_or(d.bias),
+                };
+            }
+            SECTION_TOON if values.len() >= 2 => {
+                let d = ToonSettings::default();
+                let color = match values.get(2..5) {
+                    Some(c) => (c[0], c[1], c[2]),
+                    None => d.outline_color,
+                };
+                config.toon = Some(ToonSettings {
+                    bands: values[0].clamp(0.0, 64.0) as u32,
+                    outline_width: values[1].clamp(0.0, 16.0) as u32,
+                    outline_color: color,
+                    depth_threshold: values.get(5).copied().unwrap_or(d.depth_threshold),
+                    normal_threshold: values.get(6).copied().unwrap_or(d.normal_threshold),
+                });
+            }
+            SECTION_HEIGHT_FOG if values.len() >= 3 => {
+                let d = HeightFog::default();
+                config.height_fog = HeightFog {
+                    density: values[0].max(0.0),
+                    base_height: values[1],
+                    falloff: values[2].max(0.0),
+                    up: match values.get(3..6) {
+                        Some(u) if u.iter().any(|&c| c != 0.0) => Vec3D { x: u[0], y: u[1], z: u[2] },
+                        _ => d.up,
+                    },
+                };
+            }
+            SECTION_FOG_GRADIENT if !values.is_empty() => {
+                let stops: Vec<_> = values[1..]
+                    .chunks_exact(4)
+                    .take(values[0] as usize)
+                    .map(|c| (c[0], c[1], c[2], c[3]))
+                    .collect();
+                if !stops.is_empty() {
+                    config.fog_gradient = Some(ColorGradient::from_stops(&stops));
+                }
+            }
+            SECTION_LIGHT_SHAFTS if values.len() >= 2 => {
+                let d = ShaftSettings::default();
+                config.shafts = ShaftSettings {
+                    light: values[0].clamp(0.0, 5.0) as usize,
+                    strength: values[1].max(0.0),
+                    samples: values.get(2).map_or(d.samples, |&v| v.clamp(0.0, 256.0) as u32),
+                    density: values.get(3).copied().unwrap_or(d.density),
+                    decay: values.get(4).copied().unwrap_or(d.decay),
+                    surface_scatter: values.get(5).copied().unwrap_or(d.surface_scatter),
+                };
+            }
+            SECTION_EMISSION if values.len() >= 7 => {
+                config.emission = EmissionSettings {
+                    trap_min: values[0],
+                    trap_max: values[1],
+                    softness: values[2].max(0.0),
+                    color: (values[3], values[4], values[5]),
+                    intensity: values[6].max(0.0),
+                };
+            }
+            SECTION_TEXTURE if values.len() >= 2 => {
+                let d = SurfaceTexture::default();
+                let pattern = match values[0] as u32 {
+                    1 => Some(TexturePattern::Stripes),
+                    2 => Some(TexturePattern::Checker),
+                    3 => values.get(5).and_then(|&h| texture::texture(h as u32)).map(TexturePattern::Image),
+                    _ => Some(TexturePattern::Noise),
+                };
+                config.texture = pattern.map(|pattern| SurfaceTexture {
+                    pattern,
+                    scale: values[1],
+                    albedo_strength: values.get(2).copied().unwrap_or(d.albedo_strength),
+                    roughness_strength: values.get(3).copied().unwrap_or(d.roughness_strength),
+                    sharpness: values.get(4).copied().unwrap_or(d.sharpness),
+                });
+            }
+            SECTION_BUMP if !values.is_empty() => {
+                config.bump = BumpSettings {
+                    amplitude: values[0].max(0.0),
+                    frequency: values.get(1).copied().unwrap_or(BumpSettings::default().frequency),
+                };
+            }
+            SECTION_RIM if !values.is_empty() => {
+                let d = RimSettings::default();
+                config.rim = RimSettings {
+                    intensity: values[0].max(0.0),
+                    exponent: values.get(1).copied().unwrap_or(d.exponent),
+                    color: match values.get(2..5) {
+                        Some(c) => (c[0], c[1], c[2]),
+                        None => d.color,
+                    },
+                };
+            }
+            SECTION_SKY if values.len() >= 9 => {
+                let d = SkySettings::default();
+                let get = |k: usize, default: f64| values.get(k).copied().unwrap_or(default);
+                let sun = get(11, -1.0);
+                config.sky = Some(SkySettings {
+                    zenith: (values[0], values[1], values[2]),
+                    horizon: (values[3], values[4], values[5]),
+                    ground: (values[6], values[7], values[8]),
+                    exponent: get(9, d.exponent).max(0.0),
+                    fog_to_sky: get(10, 1.0) != 0.0,
+                    sun_light: if sun >= 0.0 { Some(sun as usize) } else { None },
+                    sun_size: get(12, d.sun_size).max(0.0),
+                    sun_intensity: get(13, d.sun_intensity).max(0.0),
+                    glow_exponent: get(14, d.glow_exponent),
+                    glow_strength: get(15, d.glow_strength).max(0.0),
+                    up: match values.get(16..19) {
+                        Some(u) if u.iter().any(|&c| c != 0.0) => Vec3D { x: u[0], y: u[1], z: u[2] },
+                        _ => d.up,
+                    },
+                });
+            }
+            SECTION_SUN_SKY if values.len() >= 2 => {
+                let d = SunSky::default();
+                let get = |k: usize, default: f64| values.get(k).copied().unwrap_or(default);
+                config.sun_sky = Some(SunSky {
+                    elevation: values[0],
+                    azimuth: values[1],
+                    turbidity: get(2, d.turbidity).clamp(1.0, 20.0),
+                    sky_intensity: get(3, d.sky_intensity).max(0.0),
+                    sun_intensity: get(4, d.sun_intensity).max(0.0),
+                    sun_size: get(5, d.sun_size).max(0.0),
+                    disc_intensity: get(6, d.disc_intensity).max(0.0),
+                    ground_albedo: get(7, d.ground_albedo).max(0.0),
+                    fog_to_sky: get(8, 1.0) != 0.0,
+                });
+            }
+            SECTION_ENERGY if !values.is_empty() => {
+                config.energy_conserving = values[0] != 0.0;
+            }
+            SECTION_MATERIAL_GRADIENT if values.len() >= 2 => {
+                let id = values[0] as u8;
+                let stops: Vec<_> = values[2..]
+                    .chunks_exact(4)
+                    .take(values[1] as usize)
+                    .map(|c| (c[0], c[1], c[2], c[3]))
+                    .collect();
+                if id != 0 && !stops.is_empty() {
+                    config.material_gradients.retain(|(m, _)| *m != id);
+                    config.material_gradients.push((id, ColorGradient::from_stops(&stops)));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if srgb_inputs {
+        config.linearize_inputs();
+    }
+    // The model's colors are already linear
+    config.apply_sun_sky();
     config
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hit(roughness: u16) -> SiLight5 {
+        SiLight5 { sn_z: -32767, z_pos: 20000, ambient: 4000, color_gradient: 20000, roughness, ..Default::default() }
+    }
+
+    fn paint_f32(gbuffer: &[SiLight5], layers: PaintLayers, config: &PaintConfig) -> Vec<f32> {
+        let mut out = vec![0.0; gbuffer.len() * 4];
+        paint_gbuffer_f32(gbuffer, layers, &mut out, gbuffer.len() as u32, 1, config);
+        out
+    }
+
+    #[test]
+    fn test_malformed_section_count_is_clamped() {
+        // No lights, ambient, fog, background, view, AO strength, no gradient stops
+        let mut data = vec![0.0, 0.3, 0.3, 0.3, 1.0, 0.0, 0.0, 0.0, 0.0, 0.1, 0.2, 0.3, 0.0, 0.0, 1.0, 0.5, 0.0];
+        assert_eq!(data.len(), 17);
+        for count in [f64::INFINITY, 1e300, usize::MAX as f64] {
+            data.truncate(17);
+            data.extend_from_slice(&[SECTION_REFLECTION as f64, count, 0.75]);
+            let config = paint_config_from_buffer(&data);
+            assert_eq!(config.reflectivity, 0.75);
+            assert_eq!(config.bg_color, (0.1, 0.2, 0.3));
+        }
+        // A tag without its count is ignored
+        data.truncate(17);
+        data.push(SECTION_REFLECTION as f64);
+        assert_eq!(paint_config_from_buffer(&data).reflectivity, 0.0);
+    }
+
+    #[test]
+    fn test_srgb_inputs_linearize_glass_color() {
+        let mut data = vec![0.0, 0.3, 0.3, 0.3, 1.0, 0.0, 0.0, 0.0, 0.0, 0.1, 0.2, 0.3, 0.0, 0.0, 1.0, 0.5, 0.0];
+        data.extend_from_slice(&[SECTION_TRANSPARENCY as f64, 5.0, 1.0, 0.5, 0.25, 1.0, 1.0]);
+        data.extend_from_slice(&[SECTION_COLOR_SPACE as f64, 2.0, 1.0, 1.0]);
+        let config = paint_config_from_buffer(&data);
+        let lin = utils::srgb_to_linear;
+        assert_eq!(config.glass_color, (lin(0.5), lin(0.25), 1.0));
+        assert_eq!(config.bg_color, (lin(0.1), lin(0.2), lin(0.3)));
+    }
+
+    #[test]
+    fn test_head_on_highlight_follows_fresnel() {
+        // Light behind the eye, surface facing the camera
+        let light = LightConfig { direction: Vec3D { x: 0.0, y: 0.0, z: -1.0 }, ..Default::default() };
+        let specular = |fresnel_f0: f64| {
+            let config = PaintConfig { lights: vec![light.clone()], fresnel_f0, ..Default::default() };
+            let pixel = hit(0);
+            let surface = SurfacePoint::new(&pixel, None, None, &config);
+            surface.light_terms(0, &config.lights[0], false, &config).1 .0
+        };
+        // Without Fresnel the full default intensity
+        assert!((specular(0.0) - 0.5).abs() < 1e-6);
+        assert!(specular(0.8) > specular(0.2));
+        assert!(specular(0.2) > 0.0);
+    }
+
+    #[test]
+    fn test_reflection_layer_blend() {
+        let gbuffer = [hit(0), hit(0x0300), hit(0x00FF)];
+        let mirror = SiLight5 { sn_x: 32767, color_gradient: 60000, ..hit(0) };
+        let reflect = [mirror; 3];
+        let layers = PaintLayers { reflect: Some(&reflect), ..Default::default() };
+        let config = PaintConfig {
+            reflectivity: 0.5,
+            material_reflectivity: vec![(3, 1.0)],
+            reflect_from_roughness: true,
+            ..Default::default()
+        };
+        let plain = paint_f32(&gbuffer, PaintLayers::default(), &config);
+        let blended = paint_f32(&gbuffer, layers, &config);
+        let seen = shade_pixel(&mirror, &config);
+        let seen = [seen.0 as f32, seen.1 as f32, seen.2 as f32];
+        for c in 0..3 {
+            // Smooth: half way to the reflection
+            assert!((blended[c] - (plain[c] + seen[c]) * 0.5).abs() < 1e-6);
+            // Material 3 is a perfect mirror
+            assert!((blended[4 + c] - seen[c]).abs() < 1e-6);
+            // Fully rough: no reflection
+            assert_eq!(blended[8 + c], plain[8 + c]);
+        }
+        assert_ne!(plain[..3], seen[..]);
+    }
+
+    #[test]
+    fn test_energy_conserving_stays_within_the_light() {
+        let white = ColorGradient::from_stops(&[(0.0, 1.0, 1.0, 1.0), (1.0, 1.0, 1.0, 1.0)]);
+        let light = LightConfig {
+            direction: Vec3D { x: 0.0, y: 0.0, z: -1.0 },
+            specular_intensity: 0.0,
+            ..Default::default()
+        };
+        // Head-on light and no AO: additive reflection would reach 1.5
+        let config = PaintConfig {
+            lights: vec![light],
+            ambient_intensity: 0.0,
+            ao_strength: 0.0,
+            gradient: white,
+            reflectivity: 0.5,
+            energy_conserving: true,
+            ..Default::default()
+        };
+        let gbuffer = [hit(0)];
+        let reflect = [hit(0)];
+        let out = paint_f32(&gbuffer, PaintLayers { reflect: Some(&reflect), ..Default::default() }, &config);
+        for c in &out[..3] {
+            assert!(*c <= 1.0 + 1e-6, "{out:?}");
+            assert!(*c >= 0.5, "{out:?}");
+        }
+    }
+
+    #[test]
+    fn test_energy_conserving_reserves_only_for_a_reflection() {
+        let white = ColorGradient::from_stops(&[(0.0, 1.0, 1.0, 1.0), (1.0, 1.0, 1.0, 1.0)]);
+        let light = LightConfig {
+            direction: Vec3D { x: 0.0, y: 0.0, z: -1.0 },
+            specular_intensity: 0.0,
+            ..Default::default()
+        };
+        let config = PaintConfig {
+            lights: vec![light],
+            ambient_intensity: 0.0,
+            ao_strength: 0.0,
+            gradient: white,
+            bg_color: (0.0, 0.0, 0.0),
+            reflectivity: 0.5,
+            energy_conserving: true,
+            ..Default::default()
+        };
+        let gbuffer = [hit(0)];
+        // Without a reflect layer the lights keep the full share
+        let out = paint_f32(&gbuffer, PaintLayers::default(), &config);
+        assert!((out[0] - 1.0).abs() < 1e-3, "{out:?}");
+        // A reflection of black leaves only the lights' half
+        let miss = [SiLight5 { z_pos: 65535, ..Default::default() }];
+        let out = paint_f32(&gbuffer, PaintLayers { reflect: Some(&miss), ..Default::default() }, &config);
+        assert!((out[0] - 0.5).abs() < 1e-3, "{out:?}");
+    }
+
+    #[test]
+    fn test_secondary_misses_show_the_sky() {
+        // Normal at 45° between −y and −z; the view ray looks along +z
+        let gbuffer = [SiLight5 { sn_y: -32767, ..hit(0x0300) }];
+        let miss = [SiLight5 { z_pos: 65535, ..Default::default() }];
+        let config = PaintConfig {
+            sky: Some(SkySettings { sun_light: None, ..Default::default() }),
+            material_reflectivity: vec![(3, 1.0)],
+            transparency: 1.0,
+            ..Default::default()
+        };
+        let down = background(&Vec3D { x: 0.0, y: -1.0, z: 0.0 }, &config);
+        let ahead = background(&config.view_dir, &config);
+        assert_ne!(down, config.bg_color);
+        assert_ne!(ahead, config.bg_color);
+
+        // The mirrored ray points straight down into the ground color
+        let reflected = paint_f32(&gbuffer, PaintLayers { reflect: Some(&miss), ..Default::default() }, &config);
+        // The transmitted ray keeps going along the view ray
+        let seen = paint_f32(&gbuffer, PaintLayers { transmit: Some(&miss), ..Default::default() }, &config);
+        for (c, (d, a)) in [(down.0, ahead.0), (down.1, ahead.1), (down.2, ahead.2)].into_iter().enumerate() {
+            assert!((reflected[c] as f64 - d).abs() < 1e-6, "{reflected:?} {down:?}");
+            assert!((seen[c] as f64 - a).abs() < 1e-6, "{seen:?} {ahead:?}");
+        }
+    }
+
+    #[test]
+    fn test_material_behind_glass() {
+        let gbuffer = [hit(0)];
+        let mut seen = hit(0x05C0);
+        // World units, not a fraction of the ray length
+        seen.set_interior_path(3.0);
+        assert_eq!(seen.interior_path(), 3.0);
+        let transmit = [seen];
+        let layers = PaintLayers { transmit: Some(&transmit), ..Default::default() };
+        let red = ColorGradient::from_stops(&[(0.0, 1.0, 0.0, 0.0), (1.0, 1.0, 0.0, 0.0)]);
+        let config = PaintConfig {
+            transparency: 1.0,
+            glass_color: (1.0, 0.5, 1.0),
+            absorption_density: 2.0,
+            material_gradients: vec![(5, red)],
+            ..Default::default()
+        };
+        assert_eq!(seen.material_id(), 5);
+
+        // Material 5 (and its roughness) shade the seen surface; green is absorbed
+        let out = paint_f32(&gbuffer, layers, &config);
+        let expected = shade_pixel(&SiLight5 { ambient: 0, ..seen }, &config);
+        let green = (-(1.0 - 0.5) * 3.0 * 2.0f64).exp();
+        assert!((out[0] as f64 - expected.0).abs() < 1e-6);
+        assert!((out[1] as f64 - expected.1 * green).abs() < 1e-6);
+        assert!(expected.0 > 2.0 * expected.2, "{expected:?}");
+    }
+
+    #[test]
+    fn test_range_paint_matches_full_paint() {
+        use crate::engine::raymarcher;
+        use crate::formulas::{hybrid::HybridFormula, hybrid::HybridMode, FormulaId};
+
+        let (width, height) = (24, 24);
+        let params = RenderParams { width, height, ..Default::default() };
+        let formula = HybridFormula::new(&[(FormulaId::MandelbulbPower8, 1)], HybridMode::Alternating, 8, 16.0);
+        let mut gbuffer = vec![SiLight5::default(); (width * height) as usize];
+        raymarcher::render_scanlines(&params, &formula, &mut gbuffer, 0, 1);
+
+        // Neighbourhood passes read rows outside the painted range
+        let light = LightConfig { direction: Vec3D { x: 0.1, y: 0.1, z: 1.0 }, ..Default::default() };
+        let config = PaintConfig {
+            lights: vec![light],
+            view: Some(PaintView::from_render_params(&params)),
+            ssao: SsaoSettings { strength: 0.8, radius: 4, ..Default::default() },
+            toon: Some(ToonSettings::default()),
+            shafts: ShaftSettings { strength: 1.0, ..Default::default() },
+            ..Default::default()
+        };
+        let paint = |config: &PaintConfig, rows: Range<u32>| {
+            let mut out = vec![0u8; gbuffer.len() * 4];
+            paint_gbuffer_range(&gbuffer, PaintLayers::default(), &mut out, width, height, rows, config);
+            out
+        };
+        let full = paint(&config, 0..height);
+        let rows = 9..15;
+        let part = paint(&config, rows.clone());
+        let span = (rows.start * width * 4) as usize..(rows.end * width * 4) as usize;
+        assert_eq!(full[span.clone()], part[span.clone()]);
+        assert!(part[..span.start].iter().chain(&part[span.end..]).all(|b| *b == 0));
+
+        // Every pass contributes to the compared rows
+        for plain in [
+            PaintConfig { ssao: SsaoSettings::default(), ..config.clone() },
+            PaintConfig { toon: None, ..config.clone() },
+            PaintConfig { shafts: ShaftSettings::default(), ..config.clone() },
+        ] {
+            assert_ne!(paint(&plain, rows.clone())[span.clone()], part[span.clone()]);
+        }
+    }
+}
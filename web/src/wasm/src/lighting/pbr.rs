@@ -0,0 +1,151 @@
+//! Physically based shading — GGX microfacet specular with a
+//! metallic/roughness workflow.
+//!
+//! Used by the paint pass when `PaintConfig::shading` is `ShadingModel::Pbr`.
+//! Light amplitudes keep the Phong convention (a white Lambert surface facing
+//! a light of amplitude 1 reflects 1), so switching models does not change the
+//! overall exposure of a scene.
+
+use std::f64::consts::PI;
+
+use crate::engine::types::Vec3D;
+use crate::math::{math3d, utils};
+
+/// Lowest roughness used for evaluation; smoother surfaces give single-pixel
+/// highlights that alias badly.
+pub const MIN_ROUGHNESS: f64 = 0.03;
+
+/// Surface parameters of one material id.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PbrMaterial {
+    /// Perceptual roughness [0, 1]; overridden per pixel by a nonzero
+    /// G-buffer roughness byte
+    pub roughness: f64,
+    /// 0 = dielectric, 1 = metal (tints reflections by the base color)
+    pub metallic: f64,
+    /// Normal-incidence reflectance of the dielectric part (0.04 for most
+    /// non-metals)
+    pub reflectance: f64,
+}
+
+impl Default for PbrMaterial {
+    fn default() -> Self {
+        Self { roughness: 0.5, metallic: 0.0, reflectance: 0.04 }
+    }
+}
+
+impl PbrMaterial {
+    /// Roughness for a G-buffer roughness byte (0 = use the material's own).
+    pub fn roughness_for(&self, byte: u8) -> f64 {
+        let r = if byte > 0 { byte as f64 / 255.0 } else { self.roughness };
+        utils::clamp(r, MIN_ROUGHNESS, 1.0)
+    }
+
+    /// Normal-incidence reflectance per channel for a base color.
+    pub fn f0(&self, base: (f64, f64, f64)) -> (f64, f64, f64) {
+        let m = utils::clamp(self.metallic, 0.0, 1.0);
+        let d = self.reflectance;
+        (utils::lerp(d, base.0, m), utils::lerp(d, base.1, m), utils::lerp(d, base.2, m))
+    }
+}
+
+/// GGX / Trowbridge-Reitz normal distribution for perceptual roughness `r`.
+pub fn ggx_distribution(n_dot_h: f64, r: f64) -> f64 {
+    let a2 = (r * r) * (r * r);
+    let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    a2 / (PI * d * d)
+}
+
+/// Smith geometry term with the Schlick-GGX approximation for direct light.
+pub fn smith_geometry(n_dot_v: f64, n_dot_l: f64, r: f64) -> f64 {
+    let k = (r + 1.0) * (r + 1.0) / 8.0;
+    let g1 = |x: f64| x / (x * (1.0 - k) + k);
+    g1(n_dot_v) * g1(n_dot_l)
+}
+
+/// Schlick's approximation of the Fresnel reflectance.
+#[inline]
+pub fn fresnel_schlick(cos_theta: f64, f0: f64) -> f64 {
+    f0 + (1.0 - f0) * (1.0 - utils::clamp(cos_theta, 0.0, 1.0)).powi(5)
+}
+
+/// Diffuse and specular light reflected toward `v` from a light in direction
+/// `l` (both unit vectors away from the surface), per unit of light amplitude
+/// and already multiplied by n·l.
+pub fn reflected(
+    n: &Vec3D,
+    v: &Vec3D,
+    l: &Vec3D,
+    base: (f64, f64, f64),
+    material: &PbrMaterial,
+    roughness: f64,
+) -> ((f64, f64, f64), (f64, f64, f64)) {
+    let n_dot_l = math3d::vec3d_dot(n, l);
+    if n_dot_l <= 0.0 {
+        return ((0.0, 0.0, 0.0), (0.0, 0.0, 0.0));
+    }
+    let n_dot_v = math3d::vec3d_dot(n, v).max(1e-4);
+    let h = math3d::vec3d_normalized(&math3d::vec3d_add(v, l));
+    let n_dot_h = math3d::vec3d_dot(n, &h).max(0.0);
+    let v_dot_h = math3d::vec3d_dot(v, &h).max(0.0);
+
+    // Specular lobe; the π matches the Lambert = 1 amplitude convention
+    let spec = ggx_distribution(n_dot_h, roughness) * smith_geometry(n_dot_v, n_dot_l, roughness)
+        / (4.0 * n_dot_v * n_dot_l) * PI;
+    let f0 = material.f0(base);
+    let diffuse = 1.0 - utils::clamp(material.metallic, 0.0, 1.0);
+    let channel = |f0: f64, base: f64| {
+        let f = fresnel_schlick(v_dot_h, f0);
+        ((1.0 - f) * diffuse * base * n_dot_l, f * spec * n_dot_l)
+    };
+    let (r, g, b) = (channel(f0.0, base.0), channel(f0.1, base.1), channel(f0.2, base.2));
+    ((r.0, g.0, b.0), (r.1, g.1, b.1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ggx_is_normalized() {
+        // ∫ D(h) (n·h) dω over the hemisphere = 1
+        for r in [0.2, 0.5, 1.0] {
+            let steps = 20000;
+            let mut sum = 0.0;
+            for i in 0..steps {
+                let theta = (i as f64 + 0.5) / steps as f64 * PI * 0.5;
+                let c = theta.cos();
+                sum += ggx_distribution(c, r) * c * theta.sin() * 2.0 * PI * (PI * 0.5 / steps as f64);
+            }
+            assert!((sum - 1.0).abs() < 1e-3, "r = {r}: {sum}");
+        }
+    }
+
+    #[test]
+    fn test_fresnel_limits() {
+        assert!((fresnel_schlick(1.0, 0.04) - 0.04).abs() < 1e-12);
+        assert!((fresnel_schlick(0.0, 0.04) - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_metals_have_no_diffuse_and_tinted_highlights() {
+        let n = Vec3D { x: 0.0, y: 0.0, z: 1.0 };
+        let l = math3d::vec3d_normalized(&Vec3D { x: 1.0, y: 0.0, z: 1.0 });
+        let off_mirror = math3d::vec3d_normalized(&Vec3D { x: 1.0, y: 0.0, z: 0.2 });
+        let gold = (1.0, 0.7, 0.3);
+        let metal = PbrMaterial { roughness: 0.2, metallic: 1.0, ..Default::default() };
+        let plastic = PbrMaterial { roughness: 0.2, ..Default::default() };
+
+        // Far from the mirror direction only the diffuse part remains
+        let (m_diffuse, m_spec) = reflected(&n, &off_mirror, &l, gold, &metal, 0.2);
+        let (p_diffuse, _) = reflected(&n, &off_mirror, &l, gold, &plastic, 0.2);
+        assert!(m_diffuse.0 == 0.0 && m_spec.0 < 0.05 && p_diffuse.0 > 0.5);
+
+        // In the mirror direction the metal highlight takes the base color
+        let mirror = Vec3D { x: -l.x, y: 0.0, z: l.z };
+        let (_, h) = reflected(&n, &mirror, &l, gold, &metal, 0.2);
+        assert!(h.0 > h.1 && h.1 > h.2);
+        let (_, behind) = reflected(&n, &mirror, &Vec3D { x: 0.0, y: 0.0, z: -1.0 }, gold, &metal, 0.2);
+        assert_eq!(behind, (0.0, 0.0, 0.0));
+    }
+}
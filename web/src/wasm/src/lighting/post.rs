@@ -0,0 +1,273 @@
+//! Post-processing helpers for the paint pass.
+//!
+//! Adaptive lighting: a local exposure map built from the G-buffer AO and
+//! depth channels, blurred so that whole crevices (not single pixels) get
+//! brighter — the poor-man's tone mapping MB3D artists otherwise fake by hand.
+//!
+//! Silhouette glow: brighten background pixels by the closest-approach
+//! distance stored in miss pixels (`MissEncoding::ClosestApproach`).
+//!
+//! Clipping diagnostics: mark pixels that exceed the displayable range in any
+//! channel, so light amplitudes and exposure can be tuned without guesswork.
+//!
+//! External layer compositing: merge an RGBA + depth layer rendered elsewhere
+//! (characters, props) into the painted image by depth, fogged like the fractal.
+
+use crate::engine::types::SiLight5;
+use crate::math::utils;
+use super::paint::{self, PaintConfig};
+
+/// Exposure map settings.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ExposureSettings {
+    /// Blur radius in pixels
+    pub radius: u32,
+    /// Exposure boost in stops at full occlusion (0 disables the map)
+    pub strength: f64,
+    /// Share of depth (vs. AO) in the map [0, 1]
+    pub depth_weight: f64,
+}
+
+impl Default for ExposureSettings {
+    fn default() -> Self {
+        Self { radius: 8, strength: 0.0, depth_weight: 0.0 }
+    }
+}
+
+impl ExposureSettings {
+    pub fn enabled(&self) -> bool {
+        self.strength != 0.0
+    }
+}
+
+/// Box blur of `values` restricted to pixels where `mask` is set, so the
+/// background does not bleed into the surface. Separable, O(width × height).
+fn masked_box_blur(values: &[f32], mask: &[f32], width: usize, height: usize, radius: usize) -> Vec<f32> {
+    let pass = |v: &[f32], horizontal: bool| -> Vec<f32> {
+        let (lines, len) = if horizontal { (height, width) } else { (width, height) };
+        let at = |line: usize, i: usize| if horizontal { line * width + i } else { i * width + line };
+        let mut out = vec![0.0f32; v.len()];
+        for line in 0..lines {
+            // Running sum over the window [i − r, i + r]
+            let mut sum = 0.0f32;
+            for i in 0..radius.min(len) {
+                sum += v[at(line, i)];
+            }
+            for i in 0..len {
+                if i + radius < len {
+                    sum += v[at(line, i + radius)];
+                }
+                if i > radius {
+                    sum -= v[at(line, i - radius - 1)];
+                }
+                out[at(line, i)] = sum;
+            }
+        }
+        out
+    };
+    let weighted: Vec<f32> = values.iter().zip(mask).map(|(v, m)| v * m).collect();
+    let num = pass(&pass(&weighted, true), false);
+    let den = pass(&pass(mask, true), false);
+    num.iter().zip(&den).map(|(n, d)| if *d > 0.0 { n / d } else { 0.0 }).collect()
+}
+
+/// Per-pixel exposure gain (multiplier) for the painted colors.
+pub fn exposure_map(gbuffer: &[SiLight5], width: u32, height: u32, settings: &ExposureSettings) -> Vec<f32> {
+    let (w, h) = (width as usize, height as usize);
+    let total = (w * h).min(gbuffer.len());
+    let mut values = vec![0.0f32; w * h];
+    let mut mask = vec![0.0f32; w * h];
+    let dw = settings.depth_weight.clamp(0.0, 1.0);
+    for (i, px) in gbuffer.iter().take(total).enumerate() {
+        if px.z_pos < 65534 {
+            let ao = px.ambient as f64 / 65535.0;
+            let depth = px.z_pos as f64 / 65535.0;
+            values[i] = (ao * (1.0 - dw) + depth * dw) as f32;
+            mask[i] = 1.0;
+        }
+    }
+    let blurred = masked_box_blur(&values, &mask, w, h, settings.radius as usize);
+    blurred.iter().map(|&v| (settings.strength * v as f64).exp2() as f32).collect()
+}
+
+/// Halo around silhouettes, painted onto background pixels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GlowSettings {
+    pub color: (f64, f64, f64),
+    /// Halo width in pixels
+    pub width: f64,
+    /// Falloff exponent (1 = linear, higher = tighter)
+    pub falloff: f64,
+    pub intensity: f64,
+}
+
+impl Default for GlowSettings {
+    fn default() -> Self {
+        Self { color: (1.0, 0.8, 0.5), width: 16.0, falloff: 2.0, intensity: 1.0 }
+    }
+}
+
+impl GlowSettings {
+    /// Glow weight for a miss that came within `distance` pixels of the surface.
+    pub fn weight(&self, distance: f64) -> f64 {
+        if self.width <= 0.0 || distance >= self.width {
+            return 0.0;
+        }
+        self.intensity * (1.0 - distance.max(0.0) / self.width).powf(self.falloff.max(0.0))
+    }
+
+    /// Background color with the glow of a miss pixel added.
+    pub fn apply(&self, background: (f64, f64, f64), pixel: &SiLight5) -> (f64, f64, f64) {
+        let k = self.weight(pixel.miss_distance());
+        (
+            background.0 + self.color.0 * k,
+            background.1 + self.color.1 * k,
+            background.2 + self.color.2 * k,
+        )
+    }
+}
+
+/// How clipped pixels are marked.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClipMarker {
+    /// Diagonal stripes over the image
+    Zebra,
+    /// Flat marker color
+    Solid,
+}
+
+/// Overexposure diagnostic overlay settings.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClipDiagnostics {
+    pub marker: ClipMarker,
+    pub color: (f64, f64, f64),
+}
+
+impl Default for ClipDiagnostics {
+    fn default() -> Self {
+        Self { marker: ClipMarker::Zebra, color: (1.0, 0.0, 1.0) }
+    }
+}
+
+impl ClipDiagnostics {
+    /// Stripe width in pixels for the zebra marker.
+    const STRIPE: u32 = 4;
+
+    /// Color to show at (x, y) for an unclamped color: the marker where a
+    /// channel clips, otherwise the color itself.
+    pub fn mark(&self, x: u32, y: u32, color: (f64, f64, f64)) -> (f64, f64, f64) {
+        let clipped = [color.0, color.1, color.2].iter().any(|&c| !(0.0..=1.0).contains(&c));
+        let on_stripe = match self.marker {
+            ClipMarker::Solid => true,
+            ClipMarker::Zebra => ((x + y) / Self::STRIPE).is_multiple_of(2),
+        };
+        if clipped && on_stripe { self.color } else { color }
+    }
+}
+
+/// Externally rendered color and depth for `composite_external`.
+pub struct ExternalLayer<'a> {
+    /// Straight (non-premultiplied) RGBA, width * height * 4
+    pub rgba: &'a [u8],
+    /// Depth per pixel normalized like `z_pos` (ray distance / max_ray_length)
+    pub depth: &'a [f32],
+}
+
+/// Merge an external layer into the painted image `rgba_out` by depth.
+///
+/// Where the layer is nearer than the fractal surface (or over background) it
+/// is fogged at its own depth and alpha-blended over the painted color.
+/// Returns the number of pixels the layer covered.
+pub fn composite_external(
+    gbuffer: &[SiLight5],
+    external: &ExternalLayer,
+    rgba_out: &mut [u8],
+    config: &PaintConfig,
+) -> u32 {
+    let mut covered = 0;
+    for (i, pixel) in gbuffer.iter().enumerate() {
+        let ri = i * 4;
+        let (Some(src), Some(&depth)) = (external.rgba.get(ri..ri + 4), external.depth.get(i)) else { break };
+        let Some(dst) = rgba_out.get_mut(ri..ri + 4) else { break };
+        let alpha = src[3] as f64 / 255.0;
+        let surface_depth = if pixel.z_pos >= 65534 { f64::INFINITY } else { pixel.z_pos as f64 / 65535.0 };
+        if alpha <= 0.0 || depth as f64 >= surface_depth {
+            continue;
+        }
+        let color = (config.decode(src[0]), config.decode(src[1]), config.decode(src[2]));
+        let fogged = paint::apply_fog(color, depth as f64, None, config);
+        for (c, v) in dst.iter_mut().zip([fogged.0, fogged.1, fogged.2]) {
+            *c = config.encode(utils::lerp(config.decode(*c), v, alpha));
+        }
+        dst[3] = 255;
+        covered += 1;
+    }
+    covered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blur_ignores_masked_pixels() {
+        let values = [0.5, 0.5, 9.0, 0.5];
+        let mask = [1.0, 1.0, 0.0, 1.0];
+        let out = masked_box_blur(&values, &mask, 4, 1, 2);
+        assert!(out.iter().all(|v| (v - 0.5).abs() < 1e-6));
+    }
+
+    #[test]
+    fn test_occluded_pixels_get_brighter() {
+        let open = SiLight5 { z_pos: 100, ambient: 0, ..Default::default() };
+        let crevice = SiLight5 { z_pos: 100, ambient: 65535, ..Default::default() };
+        let settings = ExposureSettings { radius: 0, strength: 1.0, depth_weight: 0.0 };
+        let map = exposure_map(&[open, crevice], 2, 1, &settings);
+        assert!((map[0] - 1.0).abs() < 1e-6);
+        assert!((map[1] - 2.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_external_layer_respects_depth() {
+        let gbuffer = [
+            SiLight5 { z_pos: 32768, ..Default::default() }, // surface at 0.5
+            SiLight5 { z_pos: 32768, ..Default::default() },
+            SiLight5 { z_pos: 65535, ..Default::default() }, // background
+        ];
+        let ext_rgba = [255, 0, 0, 255, 255, 0, 0, 255, 0, 0, 255, 128];
+        let ext_depth = [0.25, 0.75, 0.9];
+        let mut rgba = [10u8; 12];
+        let external = ExternalLayer { rgba: &ext_rgba, depth: &ext_depth };
+        let covered = composite_external(&gbuffer, &external, &mut rgba, &PaintConfig::default());
+        assert_eq!(covered, 2);
+        assert_eq!(&rgba[0..4], &[255, 0, 0, 255]); // in front
+        assert_eq!(&rgba[4..8], &[10, 10, 10, 10]); // hidden behind the surface
+        assert!(rgba[10] > 100 && rgba[8] < 20); // half-transparent over background
+    }
+
+    #[test]
+    fn test_clip_markers() {
+        let zebra = ClipDiagnostics::default();
+        let hot = (1.5, 0.5, 0.5);
+        assert_eq!(zebra.mark(0, 0, hot), zebra.color);
+        assert_eq!(zebra.mark(4, 0, hot), hot); // between stripes
+        assert_eq!(zebra.mark(0, 0, (0.9, 0.9, 0.9)), (0.9, 0.9, 0.9));
+        let solid = ClipDiagnostics { marker: ClipMarker::Solid, ..zebra };
+        assert_eq!(solid.mark(4, 0, hot), solid.color);
+    }
+
+    #[test]
+    fn test_glow_falls_off_with_distance() {
+        let glow = GlowSettings { width: 10.0, falloff: 1.0, ..Default::default() };
+        assert_eq!(glow.weight(0.0), 1.0);
+        assert_eq!(glow.weight(5.0), 0.5);
+        assert_eq!(glow.weight(10.0), 0.0);
+
+        let mut near = SiLight5 { z_pos: 65535, ..Default::default() };
+        near.set_miss_distance(5.0);
+        assert_eq!(glow.apply((0.0, 0.0, 0.0), &near), (0.5, 0.4, 0.25));
+        // Sentinel-only misses get no glow
+        let plain = SiLight5 { z_pos: 65535, ..Default::default() };
+        assert_eq!(glow.apply((0.1, 0.1, 0.1), &plain), (0.1, 0.1, 0.1));
+    }
+}
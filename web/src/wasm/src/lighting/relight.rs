@@ -0,0 +1,255 @@
+//! Relight cache — fast repaints while tuning lights.
+//!
+//! Painting is linear in each light's diffuse and specular terms, so a
+//! build pass stores, per pixel, everything that does not depend on the
+//! lights (ambient, environment, emission and fog, already scaled by AO and
+//! exposure) plus each light's terms before its color and amplitude. A
+//! repaint is then a weighted sum: switching a light on or off, recoloring
+//! it or changing its amplitude costs no shading at all, and moving or
+//! reshaping one light recomputes only that light.
+//!
+//! Reflection and transmission layers are not cached; the cache reproduces
+//! `paint::paint_gbuffer` on the primary G-buffer. Memory is six floats per
+//! light and pixel.
+
+use crate::engine::types::{SiLight5, Vec3D};
+use super::paint::{self, PaintConfig, PaintMaps, SurfacePoint};
+
+type Rgb = [f32; 3];
+
+/// Per-light terms at unit amplitude (see `SurfacePoint::light_terms`).
+#[derive(Clone, Debug, Default)]
+struct LightTerms {
+    diffuse: Vec<Rgb>,
+    specular: Vec<Rgb>,
+}
+
+/// Cached lighting decomposition of one G-buffer.
+#[derive(Clone, Debug)]
+pub struct RelightCache {
+    width: u32,
+    height: u32,
+    /// Final color without direct lights and light shafts
+    rest: Vec<Rgb>,
+    /// Final color per unit of light term (AO · SSAO · fog · exposure)
+    weight: Vec<f32>,
+    /// Final color per unit of light-shaft color
+    shaft: Vec<f32>,
+    /// Shaded surface (not background or outline)
+    surface: Vec<bool>,
+    lights: Vec<LightTerms>,
+}
+
+#[inline]
+fn rgb(c: (f64, f64, f64)) -> Rgb {
+    [c.0 as f32, c.1 as f32, c.2 as f32]
+}
+
+impl RelightCache {
+    /// Shade everything once and store the decomposition.
+    pub fn build(gbuffer: &[SiLight5], width: u32, height: u32, config: &PaintConfig) -> Self {
+        let total = ((width * height) as usize).min(gbuffer.len());
+        let maps = PaintMaps::new(gbuffer, width, height, 0..height, config);
+        let mut cache = Self {
+            width,
+            height,
+            rest: vec![[0.0; 3]; total],
+            weight: vec![0.0; total],
+            shaft: vec![0.0; total],
+            surface: vec![false; total],
+            lights: vec![LightTerms::default(); config.lights.len()],
+        };
+
+        for (i, pixel) in gbuffer.iter().enumerate().take(total) {
+            if let (Some(toon), true) = (&config.toon, maps.outline(i)) {
+                cache.rest[i] = rgb(toon.outline_color);
+                continue;
+            }
+            if pixel.z_pos >= 65534 {
+                let (x, y) = ((i as u32 % width) as f64, (i as u32 / width) as f64);
+                let bg = paint::background(&paint::view_direction(x, y, width, height, config), config);
+                let bg = match &config.glow {
+                    Some(glow) => glow.apply(bg, pixel),
+                    None => bg,
+                };
+                cache.rest[i] = rgb(bg);
+                cache.shaft[i] = maps.shaft(i) as f32;
+                continue;
+            }
+
+            let position = cache.position(pixel, i, config);
+            let surface = SurfacePoint::new(pixel, position.as_ref(), None, config);
+            let unlit = surface.unlit(config);
+            let occlusion = maps.ssao(i);
+            let (fog, fog_color) = paint::fog_terms(pixel.z_pos as f64 / 65535.0, position.as_ref(), config);
+            let gain = maps.exposure(i);
+            let rest = |c: f64, u: f64| gain * (c * (1.0 - fog) + u * occlusion * fog);
+            cache.rest[i] = rgb((rest(fog_color.0, unlit.0), rest(fog_color.1, unlit.1), rest(fog_color.2, unlit.2)));
+            cache.weight[i] = (gain * fog * occlusion * surface.ao) as f32;
+            cache.shaft[i] = (gain * maps.shaft(i)) as f32;
+            cache.surface[i] = true;
+        }
+        for index in 0..config.lights.len() {
+            cache.update_light(gbuffer, index, config);
+        }
+        cache
+    }
+
+    /// World position of pixel `i` for positional lights.
+    fn position(&self, pixel: &SiLight5, i: usize, config: &PaintConfig) -> Option<Vec3D> {
+        config.view.map(|view| {
+            let (x, y) = (i as u32 % self.width, i as u32 / self.width);
+            view.world_position(x as f64, y as f64, self.width, self.height, pixel.z_pos)
+        })
+    }
+
+    /// Recompute light `index` after changing anything but its switch,
+    /// color, highlight color or amplitude (direction, position, falloff,
+    /// cone, highlight size or strength). Adds cache slots for new lights.
+    pub fn update_light(&mut self, gbuffer: &[SiLight5], index: usize, config: &PaintConfig) {
+        let Some(light) = config.lights.get(index) else { return };
+        if self.lights.len() <= index {
+            self.lights.resize(index + 1, LightTerms::default());
+        }
+        let total = self.rest.len();
+        let mut terms = LightTerms { diffuse: vec![[0.0; 3]; total], specular: vec![[0.0; 3]; total] };
+        for (i, pixel) in gbuffer.iter().enumerate().take(total) {
+            if !self.surface[i] || self.weight[i] == 0.0 {
+                continue; // lights do not reach the output
+            }
+            let position = self.position(pixel, i, config);
+            let surface = SurfacePoint::new(pixel, position.as_ref(), None, config);
+            // Reflection layers are not cached, so nothing is reserved for them
+            let (d, s) = surface.light_terms(index, light, false, config);
+            terms.diffuse[i] = rgb(d);
+            terms.specular[i] = rgb(s);
+        }
+        self.lights[index] = terms;
+    }
+
+    /// Paint from the cache with the light switches, colors and amplitudes
+    /// of `config`.
+    pub fn paint(&self, rgba_out: &mut [u8], config: &PaintConfig) {
+        // Per light: diffuse and specular multipliers
+        let lights: Vec<(usize, Rgb, Rgb)> = config.lights.iter().enumerate()
+            .filter(|(index, light)| light.enabled && light.amplitude >= 0.001 && *index < self.lights.len())
+            .map(|(index, light)| {
+                let a = light.amplitude;
+                let highlight = light.specular_color.unwrap_or(light.color);
+                (index, rgb((light.color.0 * a, light.color.1 * a, light.color.2 * a)),
+                    rgb((highlight.0 * a, highlight.1 * a, highlight.2 * a)))
+            })
+            .collect();
+        let shaft_color = paint::shaft_color(config).map(rgb).unwrap_or_default();
+
+        for (i, out) in rgba_out.chunks_exact_mut(4).enumerate().take(self.rest.len()) {
+            let mut c = self.rest[i];
+            let w = self.weight[i];
+            if w > 0.0 {
+                for (index, dc, sc) in &lights {
+                    let terms = &self.lights[*index];
+                    let (d, s) = (terms.diffuse[i], terms.specular[i]);
+                    for k in 0..3 {
+                        c[k] += w * (d[k] * dc[k] + s[k] * sc[k]);
+                    }
+                }
+            }
+            for k in 0..3 {
+                c[k] += self.shaft[i] * shaft_color[k];
+            }
+            let mut color = (c[0] as f64, c[1] as f64, c[2] as f64);
+            if let (Some(diag), true) = (&config.clip_diagnostics, self.surface[i]) {
+                color = diag.mark(i as u32 % self.width, i as u32 / self.width, color);
+            }
+            out[0] = config.encode(color.0);
+            out[1] = config.encode(color.1);
+            out[2] = config.encode(color.2);
+            out[3] = 255;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lighting::paint::LightConfig;
+    use crate::math::math3d;
+
+    /// Hemisphere-ish patch: normals tilting across a 16×16 image, with a
+    /// band of background rows.
+    fn gbuffer() -> Vec<SiLight5> {
+        (0..16 * 16)
+            .map(|i| {
+                let (x, y) = (i % 16, i / 16);
+                if y >= 13 {
+                    return SiLight5 { z_pos: 65535, ..Default::default() };
+                }
+                let n = math3d::vec3d_normalized(&Vec3D { x: x as f64 - 7.5, y: y as f64 - 6.0, z: -8.0 });
+                SiLight5 {
+                    sn_x: (n.x * 32767.0) as i16,
+                    sn_y: (n.y * 32767.0) as i16,
+                    sn_z: (n.z * 32767.0) as i16,
+                    z_pos: 20000 + (x * 100) as u16,
+                    ambient: 8000,
+                    color_gradient: (y * 2000) as u16,
+                    ..Default::default()
+                }
+            })
+            .collect()
+    }
+    fn config() -> PaintConfig {
+        PaintConfig {
+            lights: vec![
+                LightConfig::default(),
+                LightConfig { direction: Vec3D { x: -0.6, y: 0.0, z: -0.8 }, color: (0.2, 0.4, 1.0), ..Default::default() },
+            ],
+            fog_density: 0.3,
+            ..Default::default()
+        }
+    }
+
+    fn painted(config: &PaintConfig) -> Vec<u8> {
+        let mut out = vec![0u8; 16 * 16 * 4];
+        paint::paint_gbuffer(&gbuffer(), &mut out, 16, 16, config);
+        out
+    }
+
+    fn assert_close(a: &[u8], b: &[u8]) {
+        for (i, (x, y)) in a.iter().zip(b).enumerate() {
+            assert!((*x as i32 - *y as i32).abs() <= 1, "byte {i}: {x} vs {y}");
+        }
+    }
+
+    #[test]
+    fn test_cache_matches_full_paint() {
+        let config = config();
+        let cache = RelightCache::build(&gbuffer(), 16, 16, &config);
+        let mut out = vec![0u8; 16 * 16 * 4];
+        cache.paint(&mut out, &config);
+        assert_close(&out, &painted(&config));
+    }
+
+    #[test]
+    fn test_toggle_and_recolor_without_rebuild() {
+        let mut config = config();
+        let cache = RelightCache::build(&gbuffer(), 16, 16, &config);
+        config.lights[0].enabled = false;
+        config.lights[1].color = (1.0, 0.5, 0.0);
+        config.lights[1].amplitude = 1.5;
+        let mut out = vec![0u8; 16 * 16 * 4];
+        cache.paint(&mut out, &config);
+        assert_close(&out, &painted(&config));
+    }
+
+    #[test]
+    fn test_moved_light_recomputes_only_itself() {
+        let mut config = config();
+        let g = gbuffer();
+        let mut cache = RelightCache::build(&g, 16, 16, &config);
+        config.lights[1].direction = math3d::vec3d_normalized(&Vec3D { x: 0.5, y: -0.5, z: -0.7 });
+        cache.update_light(&g, 1, &config);
+        let mut out = vec![0u8; 16 * 16 * 4];
+        cache.paint(&mut out, &config);
+        assert_close(&out, &painted(&config));
+    }
+}
@@ -0,0 +1,114 @@
+//! Procedural sky background — zenith/horizon/ground gradient with an
+//! optional sun disc.
+//!
+//! Miss pixels take the sky color along their view ray instead of the flat
+//! background color. The sun sits in the direction of a chosen light, so the
+//! disc and its glow always agree with the lighting. With `fog_to_sky`, fog
+//! fades surfaces toward the sky color along the same ray, so distant
+//! geometry dissolves into the horizon instead of a flat fog color.
+
+use crate::engine::types::Vec3D;
+use crate::math::{math3d, utils};
+
+/// Sky settings.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SkySettings {
+    pub zenith: (f64, f64, f64),
+    pub horizon: (f64, f64, f64),
+    /// Color below the horizon
+    pub ground: (f64, f64, f64),
+    /// Shape of the gradient away from the horizon (< 1 keeps the horizon band narrow)
+    pub exponent: f64,
+    /// World up direction
+    pub up: Vec3D,
+    /// Fog takes the sky color along the view ray instead of the fog color
+    pub fog_to_sky: bool,
+    /// Light whose direction and color place the sun (None = no sun)
+    pub sun_light: Option<usize>,
+    /// Angular radius of the disc in radians
+    pub sun_size: f64,
+    /// Disc brightness, times the light color
+    pub sun_intensity: f64,
+    /// Halo around the disc: sharpness (cosine exponent) and strength
+    pub glow_exponent: f64,
+    pub glow_strength: f64,
+}
+
+impl Default for SkySettings {
+    fn default() -> Self {
+        Self {
+            zenith: (0.15, 0.3, 0.65),
+            horizon: (0.7, 0.8, 0.9),
+            ground: (0.25, 0.22, 0.2),
+            exponent: 0.5,
+            up: Vec3D { x: 0.0, y: 1.0, z: 0.0 },
+            fog_to_sky: true,
+            sun_light: None,
+            sun_size: 0.02,
+            sun_intensity: 4.0,
+            glow_exponent: 64.0,
+            glow_strength: 0.5,
+        }
+    }
+}
+
+impl SkySettings {
+    /// Gradient color along `dir` (need not be normalized), without the sun.
+    pub fn gradient(&self, dir: &Vec3D) -> (f64, f64, f64) {
+        let e = math3d::vec3d_dot(&math3d::vec3d_normalized(dir), &math3d::vec3d_normalized(&self.up));
+        let (target, t) = if e >= 0.0 { (self.zenith, e) } else { (self.ground, -e) };
+        let t = utils::clamp(t, 0.0, 1.0).powf(self.exponent.max(1e-3));
+        (
+            utils::lerp(self.horizon.0, target.0, t),
+            utils::lerp(self.horizon.1, target.1, t),
+            utils::lerp(self.horizon.2, target.2, t),
+        )
+    }
+
+    /// Sky color along `dir` with the sun toward unit vector `sun` of color
+    /// `sun_color`.
+    pub fn color(&self, dir: &Vec3D, sun: Option<(Vec3D, (f64, f64, f64))>) -> (f64, f64, f64) {
+        let mut c = self.gradient(dir);
+        if let Some((sun_dir, sun_color)) = sun {
+            let cos = math3d::vec3d_dot(&math3d::vec3d_normalized(dir), &sun_dir);
+            // Disc with a soft edge a quarter of its radius wide
+            let r = self.sun_size.max(0.0);
+            let disc = utils::smoothstep((r * 1.25).cos(), r.cos(), cos) * self.sun_intensity;
+            let glow = cos.max(0.0).powf(self.glow_exponent.max(1.0)) * self.glow_strength;
+            let k = disc + glow;
+            c = (c.0 + sun_color.0 * k, c.1 + sun_color.1 * k, c.2 + sun_color.2 * k);
+        }
+        c
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: (f64, f64, f64), b: (f64, f64, f64)) -> bool {
+        (a.0 - b.0).abs() < 1e-12 && (a.1 - b.1).abs() < 1e-12 && (a.2 - b.2).abs() < 1e-12
+    }
+
+    #[test]
+    fn test_gradient_spans_ground_horizon_zenith() {
+        let sky = SkySettings::default();
+        assert!(close(sky.gradient(&Vec3D { x: 0.0, y: 1.0, z: 0.0 }), sky.zenith));
+        assert!(close(sky.gradient(&Vec3D { x: 0.0, y: 0.0, z: 1.0 }), sky.horizon));
+        assert!(close(sky.gradient(&Vec3D { x: 0.0, y: -2.0, z: 0.0 }), sky.ground));
+    }
+
+    #[test]
+    fn test_sun_disc_and_glow() {
+        let sky = SkySettings { glow_strength: 0.0, ..Default::default() };
+        let sun = math3d::vec3d_normalized(&Vec3D { x: 0.0, y: 0.5, z: 1.0 });
+        let lit = sky.color(&sun, Some((sun, (1.0, 0.9, 0.8))));
+        let base = sky.gradient(&sun);
+        assert!((lit.0 - base.0 - sky.sun_intensity).abs() < 1e-9);
+        // Just outside the disc only the glow remains
+        let off = math3d::vec3d_normalized(&Vec3D { x: 0.1, y: 0.5, z: 1.0 });
+        assert!(close(sky.color(&off, Some((sun, (1.0, 0.9, 0.8)))), sky.gradient(&off)));
+        let glowing = SkySettings::default();
+        assert!(glowing.color(&off, Some((sun, (1.0, 1.0, 1.0)))).0 > glowing.gradient(&off).0);
+    }
+}
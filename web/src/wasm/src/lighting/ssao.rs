@@ -0,0 +1,158 @@
+//! Screen-space ambient occlusion from G-buffer depth and normals.
+//!
+//! Complements the ray-march ambient (step count or hemispheric AO) with the
+//! occlusion neighbouring pixels cast on each other: every surface pixel
+//! looks at a few pixels within a screen radius and counts how far they rise
+//! above its tangent plane. Positions come from the paint view when it is
+//! known; otherwise a view-independent approximation (pixel coordinates in
+//! image widths, depth in units of the max ray length) is used.
+
+use std::ops::Range;
+
+use crate::engine::types::{SiLight5, Vec3D};
+use crate::math::{math3d, utils};
+use super::paint::PaintView;
+
+/// SSAO settings.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SsaoSettings {
+    /// Sampling radius in pixels
+    pub radius: u32,
+    /// Neighbours sampled per pixel
+    pub samples: u32,
+    /// Darkening at full occlusion [0, 1] (0 disables the pass)
+    pub strength: f64,
+    /// Distance at which an occluder's influence has halved (position units)
+    pub range: f64,
+    /// Ignore occluders below this angle cosine above the tangent plane
+    /// (suppresses self-occlusion on curved surfaces)
+    pub bias: f64,
+}
+
+impl Default for SsaoSettings {
+    fn default() -> Self {
+        Self { radius: 8, samples: 12, strength: 0.0, range: 0.05, bias: 0.1 }
+    }
+}
+
+impl SsaoSettings {
+    pub fn enabled(&self) -> bool {
+        self.strength > 0.0 && self.samples > 0 && self.radius > 0
+    }
+}
+
+/// Surface position of pixel (x, y) for occlusion tests.
+fn position(view: Option<&PaintView>, px: &SiLight5, x: u32, y: u32, width: u32, height: u32) -> Vec3D {
+    match view {
+        Some(view) => view.world_position(x as f64, y as f64, width, height, px.z_pos),
+        None => Vec3D {
+            x: x as f64 / width as f64,
+            y: y as f64 / width as f64,
+            z: px.z_pos as f64 / 65535.0,
+        },
+    }
+}
+
+/// Per-pixel ambient multiplier in [1 − strength, 1] (1 for background),
+/// computed for the image rows in `rows` (1 elsewhere).
+pub fn ssao_map(
+    gbuffer: &[SiLight5],
+    width: u32,
+    height: u32,
+    rows: Range<u32>,
+    settings: &SsaoSettings,
+    view: Option<&PaintView>,
+) -> Vec<f32> {
+    let total = (width * height) as usize;
+    let mut map = vec![1.0f32; total];
+    if !settings.enabled() {
+        return map;
+    }
+    // Golden-angle spiral of sample offsets, rotated per pixel
+    let golden = std::f64::consts::PI * (3.0 - 5.0f64.sqrt());
+    let range = settings.range.max(1e-12);
+
+    let start = (rows.start.min(height) * width) as usize;
+    let end = (rows.end.min(height) * width) as usize;
+    for (i, px) in gbuffer.iter().enumerate().take(end).skip(start) {
+        if px.z_pos >= 65534 {
+            continue;
+        }
+        let (x, y) = (i as u32 % width, i as u32 / width);
+        let p = position(view, px, x, y, width, height);
+        let n = math3d::vec3d_normalized(&Vec3D {
+            x: px.sn_x as f64 / 32767.0,
+            y: px.sn_y as f64 / 32767.0,
+            z: px.sn_z as f64 / 32767.0,
+        });
+        // Interleaved gradient noise decorrelates neighbouring spirals
+        let rotation = (52.982919 * (0.06711056 * x as f64 + 0.00583715 * y as f64).fract()).fract() * std::f64::consts::TAU;
+
+        let mut occlusion = 0.0;
+        for s in 0..settings.samples {
+            let k = (s as f64 + 0.5) / settings.samples as f64;
+            let r = k.sqrt() * settings.radius as f64;
+            let a = s as f64 * golden + rotation;
+            let sx = x as i64 + (r * a.cos()).round() as i64;
+            let sy = y as i64 + (r * a.sin()).round() as i64;
+            if sx < 0 || sy < 0 || sx >= width as i64 || sy >= height as i64 {
+                continue;
+            }
+            let (sx, sy) = (sx as u32, sy as u32);
+            let Some(q) = gbuffer.get((sy * width + sx) as usize) else { continue };
+            if q.z_pos >= 65534 {
+                continue;
+            }
+            let v = math3d::vec3d_sub(&position(view, q, sx, sy, width, height), &p);
+            let d = math3d::vec3d_length(&v);
+            if d <= 1e-12 {
+                continue;
+            }
+            let rise = math3d::vec3d_dot(&n, &v) / d - settings.bias;
+            if rise > 0.0 {
+                let falloff = 1.0 / (1.0 + (d / range) * (d / range));
+                occlusion += rise * falloff;
+            }
+        }
+        let occlusion = occlusion / settings.samples as f64;
+        map[i] = (1.0 - utils::clamp(settings.strength, 0.0, 1.0) * utils::clamp(occlusion, 0.0, 1.0)) as f32;
+    }
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Surface facing the camera (−Z) at the given normalized depth.
+    fn facing(depth: f64) -> SiLight5 {
+        SiLight5 { sn_z: -32767, z_pos: (depth * 65535.0) as u16, ..Default::default() }
+    }
+
+    fn settings() -> SsaoSettings {
+        SsaoSettings { radius: 3, samples: 16, strength: 1.0, range: 1.0, bias: 0.0 }
+    }
+
+    #[test]
+    fn test_flat_surface_is_unoccluded() {
+        let gbuffer = vec![facing(0.5); 16 * 16];
+        let map = ssao_map(&gbuffer, 16, 16, 0..16, &settings(), None);
+        assert!(map.iter().all(|&v| (v - 1.0).abs() < 1e-6));
+    }
+
+    #[test]
+    fn test_pit_is_occluded() {
+        // Center pixel lies behind its neighbours
+        let mut gbuffer = vec![facing(0.5); 16 * 16];
+        gbuffer[8 * 16 + 8] = facing(0.6);
+        gbuffer[0] = SiLight5 { z_pos: 65535, ..Default::default() };
+        let map = ssao_map(&gbuffer, 16, 16, 0..16, &settings(), None);
+        assert!(map[8 * 16 + 8] < 0.9, "{}", map[8 * 16 + 8]);
+        assert_eq!(map[0], 1.0);
+        // Disabled pass leaves everything lit
+        let off = SsaoSettings { strength: 0.0, ..settings() };
+        assert!(ssao_map(&gbuffer, 16, 16, 0..16, &off, None).iter().all(|&v| v == 1.0));
+        // Rows outside the range are left unoccluded
+        assert_eq!(ssao_map(&gbuffer, 16, 16, 0..8, &settings(), None)[8 * 16 + 8], 1.0);
+    }
+}
@@ -0,0 +1,223 @@
+//! Analytic daylight — Preetham sun/sky model.
+//!
+//! One control set (sun elevation, azimuth and atmospheric turbidity)
+//! drives both the sky background and the primary light: light #0 becomes a
+//! directional sun pointing at the sky's sun, colored by the atmosphere it
+//! shines through, so low suns are dim and orange and hazy skies are pale.
+//!
+//! The sky follows Preetham, Shirley and Smits, "A Practical Analytic Model
+//! for Daylight" (1999): a Perez luminance distribution scaled by the zenith
+//! luminance and chromaticity. The frame matches `envmap`: +Y is up and
+//! azimuth is measured from +Z toward +X. Results are linear radiance.
+
+use crate::engine::types::Vec3D;
+use crate::math::{math3d, utils};
+use super::paint::{LightConfig, LightKind};
+
+/// Sun/sky settings.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SunSky {
+    /// Sun height above the horizon in radians
+    pub elevation: f64,
+    /// Sun azimuth in radians, from +Z toward +X
+    pub azimuth: f64,
+    /// Haziness, 2 (clear) to 10 (hazy)
+    pub turbidity: f64,
+    /// Sky radiance per kcd/m² of model luminance
+    pub sky_intensity: f64,
+    /// Amplitude of light #0 for an unattenuated sun
+    pub sun_intensity: f64,
+    /// Angular radius of the disc in radians
+    pub sun_size: f64,
+    /// Disc brightness, times the sun light color and amplitude
+    pub disc_intensity: f64,
+    /// Share of the horizon radiance reflected by the ground below it
+    pub ground_albedo: f64,
+    /// Fog takes the sky color along the view ray instead of the fog color
+    pub fog_to_sky: bool,
+}
+
+impl Default for SunSky {
+    fn default() -> Self {
+        Self {
+            elevation: 0.6,
+            azimuth: 0.5,
+            turbidity: 3.0,
+            sky_intensity: 0.05,
+            sun_intensity: 1.2,
+            sun_size: 0.02,
+            disc_intensity: 4.0,
+            ground_albedo: 0.3,
+            fog_to_sky: true,
+        }
+    }
+}
+
+/// Perez distribution coefficients A–E.
+type Perez = [f64; 5];
+
+/// F(θ, γ) for view zenith angle θ and angle γ to the sun.
+fn perez(c: &Perez, cos_theta: f64, gamma: f64) -> f64 {
+    (1.0 + c[0] * (c[1] / cos_theta.max(0.01)).exp())
+        * (1.0 + c[2] * (c[3] * gamma).exp() + c[4] * gamma.cos() * gamma.cos())
+}
+
+/// Linear sRGB from CIE xyY.
+fn xyy_to_rgb(x: f64, y: f64, lum: f64) -> (f64, f64, f64) {
+    if y <= 0.0 {
+        return (0.0, 0.0, 0.0);
+    }
+    let cx = x / y * lum;
+    let cz = (1.0 - x - y) / y * lum;
+    (
+        (3.2406 * cx - 1.5372 * lum - 0.4986 * cz).max(0.0),
+        (-0.9689 * cx + 1.8758 * lum + 0.0415 * cz).max(0.0),
+        (0.0557 * cx - 0.2040 * lum + 1.0570 * cz).max(0.0),
+    )
+}
+
+impl SunSky {
+    /// Unit vector toward the sun.
+    pub fn sun_direction(&self) -> Vec3D {
+        let (se, ce) = self.elevation.sin_cos();
+        Vec3D { x: ce * self.azimuth.sin(), y: se, z: ce * self.azimuth.cos() }
+    }
+
+    /// Sun zenith angle, kept just above the horizon where the model holds.
+    fn theta_s(&self) -> f64 {
+        (std::f64::consts::FRAC_PI_2 - self.elevation).clamp(0.0, std::f64::consts::FRAC_PI_2 - 0.01)
+    }
+
+    fn coefficients(&self) -> (Perez, Perez, Perez) {
+        let t = self.turbidity.clamp(1.0, 20.0);
+        (
+            [0.1787 * t - 1.4630, -0.3554 * t + 0.4275, -0.0227 * t + 5.3251, 0.1206 * t - 2.5771, -0.0670 * t + 0.3703],
+            [-0.0193 * t - 0.2592, -0.0665 * t + 0.0008, -0.0004 * t + 0.2125, -0.0641 * t - 0.8989, -0.0033 * t + 0.0452],
+            [-0.0167 * t - 0.2608, -0.0950 * t + 0.0092, -0.0079 * t + 0.2102, -0.0441 * t - 1.6537, -0.0109 * t + 0.0529],
+        )
+    }
+
+    /// Zenith luminance (kcd/m²) and chromaticity.
+    fn zenith(&self) -> (f64, f64, f64) {
+        let t = self.turbidity.clamp(1.0, 20.0);
+        let ts = self.theta_s();
+        let chi = (4.0 / 9.0 - t / 120.0) * (std::f64::consts::PI - 2.0 * ts);
+        let lum = ((4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192).max(0.0);
+        let th = [ts * ts * ts, ts * ts, ts, 1.0];
+        let row = |m: [[f64; 4]; 3]| {
+            let dot = |r: [f64; 4]| r.iter().zip(&th).map(|(a, b)| a * b).sum::<f64>();
+            t * t * dot(m[0]) + t * dot(m[1]) + dot(m[2])
+        };
+        let x = row([
+            [0.00166, -0.00375, 0.00209, 0.0],
+            [-0.02903, 0.06377, -0.03202, 0.00394],
+            [0.11693, -0.21196, 0.06052, 0.25886],
+        ]);
+        let y = row([
+            [0.00275, -0.00610, 0.00317, 0.0],
+            [-0.04214, 0.08970, -0.04153, 0.00516],
+            [0.15346, -0.26756, 0.06670, 0.26688],
+        ]);
+        (lum, x, y)
+    }
+
+    /// Sky radiance along `dir` (need not be normalized), without the sun
+    /// disc. Below the horizon the ground reflects the horizon radiance.
+    pub fn radiance(&self, dir: &Vec3D) -> (f64, f64, f64) {
+        let d = math3d::vec3d_normalized(dir);
+        let below = d.y < 0.0;
+        // Below the horizon, look at the horizon instead
+        let d = if below {
+            math3d::vec3d_normalized(&Vec3D { x: d.x, y: 0.0, z: d.z })
+        } else {
+            d
+        };
+        let cos_theta = d.y.max(0.0);
+        let sun = self.sun_direction();
+        let gamma = utils::clamp(math3d::vec3d_dot(&d, &sun), -1.0, 1.0).acos();
+        let ts = self.theta_s();
+        let (cy, cx, cyy) = self.coefficients();
+        let (zl, zx, zy) = self.zenith();
+        let ratio = |c: &Perez| perez(c, cos_theta, gamma) / perez(c, 1.0, ts);
+        let c = xyy_to_rgb(zx * ratio(&cx), zy * ratio(&cyy), zl * ratio(&cy));
+        let k = self.sky_intensity * if below { self.ground_albedo } else { 1.0 };
+        (c.0 * k, c.1 * k, c.2 * k)
+    }
+
+    /// Sunlight color after the atmosphere (per unit of unattenuated sun):
+    /// Rayleigh and Ångström aerosol extinction over the Kasten–Young air
+    /// mass, faded out as the sun sets.
+    pub fn sun_transmittance(&self) -> (f64, f64, f64) {
+        let ts = self.theta_s();
+        let deg = ts.to_degrees();
+        let air_mass = 1.0 / (ts.cos() + 0.50572 * (96.07995 - deg).powf(-1.6364));
+        let beta = 0.04608 * self.turbidity.clamp(1.0, 20.0) - 0.04586;
+        let channel = |lambda_um: f64| {
+            let rayleigh = 0.008735 * lambda_um.powf(-4.08);
+            let aerosol = beta * lambda_um.powf(-1.3);
+            (-air_mass * (rayleigh + aerosol)).exp()
+        };
+        let set = utils::smoothstep(-0.02, 0.02, self.elevation);
+        (channel(0.68) * set, channel(0.55) * set, channel(0.44) * set)
+    }
+
+    /// Point `light` at the sun and give it the sun's color and strength.
+    pub fn drive_light(&self, light: &mut LightConfig) {
+        let t = self.sun_transmittance();
+        let peak = t.0.max(t.1).max(t.2);
+        light.kind = LightKind::Directional;
+        light.direction = self.sun_direction();
+        light.color = if peak > 0.0 { (t.0 / peak, t.1 / peak, t.2 / peak) } else { (1.0, 1.0, 1.0) };
+        light.specular_color = None;
+        light.amplitude = self.sun_intensity * peak;
+    }
+
+    /// Background along `dir`: sky radiance plus the disc of the sun light
+    /// (direction `sun`, color times amplitude `sun_color`).
+    pub fn color(&self, dir: &Vec3D, sun: Option<(Vec3D, (f64, f64, f64))>) -> (f64, f64, f64) {
+        let mut c = self.radiance(dir);
+        if let Some((sun_dir, sun_color)) = sun {
+            let cos = math3d::vec3d_dot(&math3d::vec3d_normalized(dir), &sun_dir);
+            let r = self.sun_size.max(0.0);
+            let k = utils::smoothstep((r * 1.25).cos(), r.cos(), cos) * self.disc_intensity;
+            c = (c.0 + sun_color.0 * k, c.1 + sun_color.1 * k, c.2 + sun_color.2 * k);
+        }
+        c
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sky_is_blue_and_brightest_near_the_sun() {
+        let sky = SunSky::default();
+        let zenith = sky.radiance(&Vec3D { x: 0.0, y: 1.0, z: 0.0 });
+        assert!(zenith.2 > zenith.0, "{zenith:?}");
+        let sun = sky.sun_direction();
+        let near = sky.radiance(&math3d::vec3d_add(&sun, &Vec3D { x: 0.0, y: 0.05, z: 0.0 }));
+        let away = sky.radiance(&Vec3D { x: -sun.x, y: sun.y, z: -sun.z });
+        assert!(near.1 > away.1, "{near:?} vs {away:?}");
+        let ground = sky.radiance(&Vec3D { x: 0.0, y: -1.0, z: 1.0 });
+        let horizon = sky.radiance(&Vec3D { x: 0.0, y: 0.0, z: 1.0 });
+        assert!((ground.1 - horizon.1 * sky.ground_albedo).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_low_sun_drives_a_dim_warm_light() {
+        let mut light = LightConfig { kind: LightKind::Point, ..Default::default() };
+        let noon = SunSky { elevation: 1.2, ..Default::default() };
+        noon.drive_light(&mut light);
+        assert_eq!(light.kind, LightKind::Directional);
+        assert!((math3d::vec3d_dot(&light.direction, &noon.sun_direction()) - 1.0).abs() < 1e-12);
+        let (noon_amplitude, noon_color) = (light.amplitude, light.color);
+
+        SunSky { elevation: 0.05, ..Default::default() }.drive_light(&mut light);
+        assert!(light.amplitude < noon_amplitude);
+        assert!(light.color.2 / light.color.0 < noon_color.2 / noon_color.0);
+
+        SunSky { elevation: -0.1, ..Default::default() }.drive_light(&mut light);
+        assert_eq!(light.amplitude, 0.0);
+    }
+}
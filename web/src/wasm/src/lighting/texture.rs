@@ -0,0 +1,327 @@
+//! Triplanar surface textures for the paint pass.
+//!
+//! A pattern is evaluated on the three axis-aligned planes through the
+//! reconstructed world position and blended by the surface normal, so
+//! fractal surfaces of any orientation get undistorted detail without UVs.
+//! Procedural patterns give a gray value; uploaded images (registered once,
+//! referenced by handle) give color. The result modulates the albedo and,
+//! under PBR shading, the roughness.
+//!
+//! Bump mapping perturbs the shading normal by the gradient of 3D noise at
+//! the same position, for micro-detail below the march resolution.
+
+use std::sync::Arc;
+
+use crate::engine::types::Vec3D;
+use crate::formulas::resources::Registry;
+use crate::math::{math3d, utils};
+
+/// RGB image for texturing, tiled in both directions.
+#[derive(Clone, Debug)]
+pub struct TextureImage {
+    pub width: u32,
+    pub height: u32,
+    pub texels: Vec<[f32; 3]>,
+}
+
+impl TextureImage {
+    /// Build from 8-bit RGBA data (alpha ignored).
+    pub fn from_rgba8(pixels: &[u8], width: u32, height: u32) -> Option<Self> {
+        let count = (width as usize) * (height as usize);
+        if count == 0 || pixels.len() < count * 4 {
+            return None;
+        }
+        let texels = pixels.chunks_exact(4).take(count)
+            .map(|p| [p[0] as f32 / 255.0, p[1] as f32 / 255.0, p[2] as f32 / 255.0])
+            .collect();
+        Some(Self { width, height, texels })
+    }
+
+    #[inline]
+    fn at(&self, x: i64, y: i64) -> [f32; 3] {
+        let x = x.rem_euclid(self.width as i64);
+        let y = y.rem_euclid(self.height as i64);
+        self.texels[(y * self.width as i64 + x) as usize]
+    }
+
+    /// Bilinear sample at texture coordinates (u, v), one tile per unit.
+    pub fn sample(&self, u: f64, v: f64) -> (f64, f64, f64) {
+        let fx = u * self.width as f64 - 0.5;
+        let fy = v * self.height as f64 - 0.5;
+        let (x0, y0) = (fx.floor() as i64, fy.floor() as i64);
+        let (tx, ty) = ((fx - x0 as f64) as f32, (fy - y0 as f64) as f32);
+        let mut out = [0.0f32; 3];
+        for (c, o) in out.iter_mut().enumerate() {
+            let top = utils::lerpf(self.at(x0, y0)[c], self.at(x0 + 1, y0)[c], tx);
+            let bottom = utils::lerpf(self.at(x0, y0 + 1)[c], self.at(x0 + 1, y0 + 1)[c], tx);
+            *o = utils::lerpf(top, bottom, ty);
+        }
+        (out[0] as f64, out[1] as f64, out[2] as f64)
+    }
+}
+
+/// Texture source.
+#[derive(Clone, Debug)]
+pub enum TexturePattern {
+    /// Fractal value noise
+    Noise,
+    /// Soft stripes across the plane's first axis
+    Stripes,
+    /// Alternating unit squares
+    Checker,
+    /// Uploaded image
+    Image(Arc<TextureImage>),
+}
+
+/// Surface texture settings.
+#[derive(Clone, Debug)]
+pub struct SurfaceTexture {
+    pub pattern: TexturePattern,
+    /// Texture repeats per world unit
+    pub scale: f64,
+    /// Exponent on the normal weights; higher gives sharper plane seams
+    pub sharpness: f64,
+    /// How much the texture darkens/tints the albedo [0, 1]
+    pub albedo_strength: f64,
+    /// How far the texture luminance replaces the roughness [0, 1] (PBR only)
+    pub roughness_strength: f64,
+}
+
+impl Default for SurfaceTexture {
+    fn default() -> Self {
+        Self { pattern: TexturePattern::Noise, scale: 1.0, sharpness: 4.0, albedo_strength: 0.5, roughness_strength: 0.0 }
+    }
+}
+
+impl SurfaceTexture {
+    /// Planar sample at 2D coordinates.
+    fn planar(&self, u: f64, v: f64) -> (f64, f64, f64) {
+        let gray = |t: f64| (t, t, t);
+        match &self.pattern {
+            TexturePattern::Noise => gray(fbm(u, v)),
+            TexturePattern::Stripes => gray(0.5 + 0.5 * (u * std::f64::consts::TAU).sin()),
+            TexturePattern::Checker => gray(((u.floor() + v.floor()) as i64).rem_euclid(2) as f64),
+            TexturePattern::Image(image) => image.sample(u, v),
+        }
+    }
+
+    /// Triplanar sample at world position `p` with unit normal `n`.
+    pub fn sample(&self, p: &Vec3D, n: &Vec3D) -> (f64, f64, f64) {
+        let k = self.sharpness.max(1.0);
+        let w = (n.x.abs().powf(k), n.y.abs().powf(k), n.z.abs().powf(k));
+        let total = w.0 + w.1 + w.2;
+        if total <= 0.0 {
+            return (1.0, 1.0, 1.0);
+        }
+        let s = self.scale;
+        let (x, y, z) = (p.x * s, p.y * s, p.z * s);
+        let mut out = (0.0, 0.0, 0.0);
+        for (weight, (u, v)) in [(w.0, (y, z)), (w.1, (x, z)), (w.2, (x, y))] {
+            if weight > 0.0 {
+                let c = self.planar(u, v);
+                let k = weight / total;
+                out = (out.0 + c.0 * k, out.1 + c.1 * k, out.2 + c.2 * k);
+            }
+        }
+        out
+    }
+
+    /// Albedo after modulation by a texture sample.
+    pub fn modulate_albedo(&self, albedo: (f64, f64, f64), texel: (f64, f64, f64)) -> (f64, f64, f64) {
+        let k = utils::clamp(self.albedo_strength, 0.0, 1.0);
+        (
+            albedo.0 * utils::lerp(1.0, texel.0, k),
+            albedo.1 * utils::lerp(1.0, texel.1, k),
+            albedo.2 * utils::lerp(1.0, texel.2, k),
+        )
+    }
+
+    /// Roughness after modulation by a texture sample.
+    pub fn modulate_roughness(&self, roughness: f64, texel: (f64, f64, f64)) -> f64 {
+        let luminance = 0.2126 * texel.0 + 0.7152 * texel.1 + 0.0722 * texel.2;
+        utils::lerp(roughness, luminance, utils::clamp(self.roughness_strength, 0.0, 1.0))
+    }
+}
+
+/// Procedural normal perturbation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BumpSettings {
+    /// Tilt of the normal per unit of noise slope (0 disables bumps)
+    pub amplitude: f64,
+    /// Noise features per world unit
+    pub frequency: f64,
+}
+
+impl Default for BumpSettings {
+    fn default() -> Self {
+        Self { amplitude: 0.0, frequency: 20.0 }
+    }
+}
+
+impl BumpSettings {
+    pub fn enabled(&self) -> bool {
+        self.amplitude > 0.0 && self.frequency > 0.0
+    }
+
+    /// Unit normal `n` tilted against the tangential noise gradient at `p`.
+    pub fn perturb(&self, n: &Vec3D, p: &Vec3D) -> Vec3D {
+        let f = self.frequency;
+        let (x, y, z) = (p.x * f, p.y * f, p.z * f);
+        let h = 1e-3;
+        let c = fbm3(x, y, z);
+        let g = Vec3D {
+            x: (fbm3(x + h, y, z) - c) / h,
+            y: (fbm3(x, y + h, z) - c) / h,
+            z: (fbm3(x, y, z + h) - c) / h,
+        };
+        // Only the gradient along the surface tilts the normal
+        let along = math3d::vec3d_dot(&g, n);
+        let tangent = math3d::vec3d_sub(&g, &math3d::vec3d_scale(n, along));
+        math3d::vec3d_normalized(&math3d::vec3d_sub(n, &math3d::vec3d_scale(&tangent, self.amplitude)))
+    }
+}
+
+/// Lattice hash in [0, 1).
+#[inline]
+fn hash(x: i64, y: i64) -> f64 {
+    hash3(x, y, 0)
+}
+
+/// 3D lattice hash in [0, 1).
+#[inline]
+fn hash3(x: i64, y: i64, z: i64) -> f64 {
+    let mut h = (x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ (y as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+        ^ (z as u64).wrapping_mul(0x1656_67B1_9E37_79F9);
+    h ^= h >> 29;
+    h = h.wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    h ^= h >> 32;
+    (h >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Smoothly interpolated value noise in [0, 1].
+fn value_noise(u: f64, v: f64) -> f64 {
+    let (x0, y0) = (u.floor(), v.floor());
+    let (tx, ty) = (utils::smoothstep(0.0, 1.0, u - x0), utils::smoothstep(0.0, 1.0, v - y0));
+    let (x0, y0) = (x0 as i64, y0 as i64);
+    let top = utils::lerp(hash(x0, y0), hash(x0 + 1, y0), tx);
+    let bottom = utils::lerp(hash(x0, y0 + 1), hash(x0 + 1, y0 + 1), tx);
+    utils::lerp(top, bottom, ty)
+}
+
+/// Smoothly interpolated 3D value noise in [0, 1].
+fn value_noise3(x: f64, y: f64, z: f64) -> f64 {
+    let (x0, y0, z0) = (x.floor(), y.floor(), z.floor());
+    let t = (
+        utils::smoothstep(0.0, 1.0, x - x0),
+        utils::smoothstep(0.0, 1.0, y - y0),
+        utils::smoothstep(0.0, 1.0, z - z0),
+    );
+    let (x0, y0, z0) = (x0 as i64, y0 as i64, z0 as i64);
+    let layer = |z: i64| {
+        let top = utils::lerp(hash3(x0, y0, z), hash3(x0 + 1, y0, z), t.0);
+        let bottom = utils::lerp(hash3(x0, y0 + 1, z), hash3(x0 + 1, y0 + 1, z), t.0);
+        utils::lerp(top, bottom, t.1)
+    };
+    utils::lerp(layer(z0), layer(z0 + 1), t.2)
+}
+
+/// Three octaves of 3D value noise in [0, 1].
+fn fbm3(x: f64, y: f64, z: f64) -> f64 {
+    let (mut sum, mut amplitude, mut frequency, mut norm) = (0.0, 0.5, 1.0, 0.0);
+    for _ in 0..3 {
+        sum += value_noise3(x * frequency, y * frequency, z * frequency) * amplitude;
+        norm += amplitude;
+        amplitude *= 0.5;
+        frequency *= 2.0;
+    }
+    sum / norm
+}
+
+/// Four octaves of value noise in [0, 1].
+fn fbm(u: f64, v: f64) -> f64 {
+    let (mut sum, mut amplitude, mut frequency, mut norm) = (0.0, 0.5, 1.0, 0.0);
+    for _ in 0..4 {
+        sum += value_noise(u * frequency, v * frequency) * amplitude;
+        norm += amplitude;
+        amplitude *= 0.5;
+        frequency *= 2.0;
+    }
+    sum / norm
+}
+
+static TEXTURES: Registry<TextureImage> = Registry::new();
+
+/// Store a texture image in the registry, returning its handle.
+pub fn register_texture(image: TextureImage) -> u32 {
+    TEXTURES.insert(image)
+}
+
+/// Look up a registered texture image.
+pub fn texture(handle: u32) -> Option<Arc<TextureImage>> {
+    TEXTURES.get(handle)
+}
+
+/// Release a registered texture image; its handle may be reused.
+pub fn release_texture(handle: u32) {
+    TEXTURES.remove(handle);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checker_follows_the_facing_plane() {
+        let tex = SurfaceTexture { pattern: TexturePattern::Checker, sharpness: 8.0, ..Default::default() };
+        let up = Vec3D { x: 0.0, y: 1.0, z: 0.0 };
+        // On a floor the XZ plane decides: (0.5, 0.5) and (1.5, 0.5) differ
+        assert_eq!(tex.sample(&Vec3D { x: 0.5, y: 7.3, z: 0.5 }, &up).0, 0.0);
+        assert_eq!(tex.sample(&Vec3D { x: 1.5, y: 7.3, z: 0.5 }, &up).0, 1.0);
+        // Diagonal normals blend the planes
+        let diag = Vec3D { x: 0.0, y: 0.5f64.sqrt(), z: 0.5f64.sqrt() };
+        let v = tex.sample(&Vec3D { x: 0.5, y: 1.5, z: 0.5 }, &diag).0;
+        assert!((v - 0.5).abs() < 1e-9, "{v}");
+    }
+
+    #[test]
+    fn test_noise_is_bounded_and_continuous() {
+        for i in 0..200 {
+            let u = i as f64 * 0.173;
+            let a = fbm(u, u * 0.7);
+            let b = fbm(u + 1e-6, u * 0.7);
+            assert!((0.0..=1.0).contains(&a));
+            assert!((a - b).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_bumps_tilt_but_keep_the_hemisphere() {
+        let n = Vec3D { x: 0.0, y: 0.0, z: -1.0 };
+        let bump = BumpSettings { amplitude: 0.3, frequency: 5.0 };
+        let mut tilted = 0;
+        for i in 0..50 {
+            let p = Vec3D { x: i as f64 * 0.137, y: i as f64 * 0.071, z: 0.0 };
+            let b = bump.perturb(&n, &p);
+            assert!((math3d::vec3d_length(&b) - 1.0).abs() < 1e-9);
+            assert!(math3d::vec3d_dot(&b, &n) > 0.5);
+            if math3d::vec3d_dot(&b, &n) < 0.9999 {
+                tilted += 1;
+            }
+        }
+        assert!(tilted > 40);
+        assert!(!BumpSettings::default().enabled());
+    }
+
+    #[test]
+    fn test_image_texture_tiles_and_modulates() {
+        // 2×1 image: red, blue
+        let image = TextureImage::from_rgba8(&[255, 0, 0, 255, 0, 0, 255, 255], 2, 1).unwrap();
+        assert_eq!(image.sample(0.25, 0.5), (1.0, 0.0, 0.0));
+        assert_eq!(image.sample(1.75, -3.5), (0.0, 0.0, 1.0));
+        let tex = SurfaceTexture { albedo_strength: 0.5, roughness_strength: 1.0, ..Default::default() };
+        assert_eq!(tex.modulate_albedo((1.0, 1.0, 1.0), (0.0, 1.0, 1.0)), (0.5, 1.0, 1.0));
+        assert!((tex.modulate_roughness(0.2, (1.0, 1.0, 1.0)) - 1.0).abs() < 1e-12);
+        assert!(TextureImage::from_rgba8(&[0; 4], 2, 1).is_none());
+    }
+}
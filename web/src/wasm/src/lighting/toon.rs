@@ -0,0 +1,113 @@
+//! Stylized (cel) shading: quantized diffuse bands and G-buffer outlines.
+//!
+//! Edges are found with the same depth / normal / silhouette test the
+//! adaptive antialiasing uses, then widened to the requested line width and
+//! painted over both the surface and the background side.
+
+use std::ops::Range;
+
+use crate::engine::antialias::{self, AaSettings};
+use crate::engine::types::SiLight5;
+
+/// Cel shading settings.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ToonSettings {
+    /// Diffuse brightness levels (0 = smooth shading)
+    pub bands: u32,
+    /// Outline width in pixels (0 = no outlines)
+    pub outline_width: u32,
+    pub outline_color: (f64, f64, f64),
+    /// Normalized depth difference that counts as an edge
+    pub depth_threshold: f64,
+    /// Minimum cosine between neighbour normals before it counts as an edge
+    pub normal_threshold: f64,
+}
+
+impl Default for ToonSettings {
+    fn default() -> Self {
+        Self {
+            bands: 3,
+            outline_width: 1,
+            outline_color: (0.0, 0.0, 0.0),
+            depth_threshold: 0.002,
+            normal_threshold: 0.7,
+        }
+    }
+}
+
+impl ToonSettings {
+    /// Quantize a diffuse term in [0, 1] to `bands` levels; any light at all
+    /// reaches the first band.
+    pub fn quantize(&self, v: f64) -> f64 {
+        if self.bands == 0 {
+            return v;
+        }
+        let b = self.bands as f64;
+        (v.clamp(0.0, 1.0) * b).ceil() / b
+    }
+
+    /// Pixels covered by outlines, complete for the image rows in `rows`.
+    pub fn outline_mask(&self, gbuffer: &[SiLight5], width: u32, height: u32, rows: Range<u32>) -> Vec<bool> {
+        let total = (width * height) as usize;
+        if self.outline_width == 0 {
+            return vec![false; total];
+        }
+        let aa = AaSettings {
+            depth_threshold: self.depth_threshold,
+            normal_threshold: self.normal_threshold,
+            ..Default::default()
+        };
+        // Edges up to a line width outside the rows still reach into them
+        let r = (self.outline_width - 1) as i64;
+        let first = (rows.start as i64 - r).max(0) as u32;
+        let last = (rows.end as i64 + r).min(height as i64) as u32;
+        let mut edges = vec![false; total];
+        for y in first..last {
+            for x in 0..width {
+                edges[(y * width + x) as usize] = antialias::is_edge(gbuffer, width, height, x, y, &aa);
+            }
+        }
+
+        // Widen the one-pixel edges to the line width
+        if r == 0 {
+            return edges;
+        }
+        let mut mask = vec![false; total];
+        for (i, _) in edges.iter().enumerate().filter(|(_, &e)| e) {
+            let (x, y) = ((i as u32 % width) as i64, (i as u32 / width) as i64);
+            for ny in (y - r).max(0)..=(y + r).min(height as i64 - 1) {
+                for nx in (x - r).max(0)..=(x + r).min(width as i64 - 1) {
+                    mask[(ny * width as i64 + nx) as usize] = true;
+                }
+            }
+        }
+        mask
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quantized_bands() {
+        let toon = ToonSettings { bands: 4, ..Default::default() };
+        assert_eq!(toon.quantize(0.0), 0.0);
+        assert_eq!(toon.quantize(0.1), 0.25);
+        assert_eq!(toon.quantize(0.6), 0.75);
+        assert_eq!(toon.quantize(1.0), 1.0);
+        assert_eq!(ToonSettings { bands: 0, ..toon }.quantize(0.6), 0.6);
+    }
+
+    #[test]
+    fn test_outlines_follow_silhouettes() {
+        // 8×1 row: surface on the left half, background on the right
+        let hit = SiLight5 { z_pos: 1000, sn_z: -32767, ..Default::default() };
+        let miss = SiLight5 { z_pos: 65535, ..Default::default() };
+        let gbuffer: Vec<_> = (0..8).map(|x| if x < 4 { hit } else { miss }).collect();
+        let thin = ToonSettings::default().outline_mask(&gbuffer, 8, 1, 0..1);
+        assert_eq!(thin, [false, false, false, true, true, false, false, false]);
+        let wide = ToonSettings { outline_width: 2, ..Default::default() }.outline_mask(&gbuffer, 8, 1, 0..1);
+        assert_eq!(wide, [false, false, true, true, true, true, false, false]);
+    }
+}
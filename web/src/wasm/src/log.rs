@@ -0,0 +1,50 @@
+//! Log bridge to the browser console, with one-time deprecation warnings.
+//!
+//! On WASM messages go to `console.warn`; native builds (tests, tools) write
+//! to stderr. `deprecated` is for entry points of the positional buffer
+//! protocol that are kept as adapters once a replacement API exists: each one
+//! warns on first use only, so worker loops calling it every frame do not
+//! flood the console.
+
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+/// Emit a warning.
+pub fn warn(message: &str) {
+    #[cfg(target_arch = "wasm32")]
+    web_sys::console::warn_1(&message.into());
+    #[cfg(not(target_arch = "wasm32"))]
+    eprintln!("{message}");
+}
+
+static WARNED: Mutex<Option<HashSet<&'static str>>> = Mutex::new(None);
+
+/// Emit `message` the first time `key` is seen. Returns whether it was emitted.
+pub fn warn_once(key: &'static str, message: &str) -> bool {
+    let first = WARNED
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get_or_insert_with(HashSet::new)
+        .insert(key);
+    if first {
+        warn(message);
+    }
+    first
+}
+
+/// One-time deprecation warning for entry point `name`, pointing at `replacement`.
+pub fn deprecated(name: &'static str, replacement: &str) -> bool {
+    warn_once(name, &format!("mb3d-wasm: `{name}` is deprecated and will be removed; use `{replacement}` instead"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deprecation_warns_once_per_entry_point() {
+        assert!(deprecated("test_entry_a", "scene API"));
+        assert!(!deprecated("test_entry_a", "scene API"));
+        assert!(deprecated("test_entry_b", "scene API"));
+    }
+}
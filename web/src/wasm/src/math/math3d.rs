@@ -4,6 +4,7 @@
 /// WASM SIMD optimizations will be added incrementally.
 
 use crate::engine::types::{Matrix3, Vec3D};
+use super::strict;
 
 // ─── Vector operations ───────────────────────────────────────
 
@@ -76,6 +77,39 @@ pub fn vec3d_normalized(v: &Vec3D) -> Vec3D {
     result
 }
 
+/// Build two unit tangents `(t, b)` so that `(t, b, n)` is an orthonormal basis.
+/// `n` must be normalized.
+#[inline]
+pub fn vec3d_orthonormal_basis(n: &Vec3D) -> (Vec3D, Vec3D) {
+    let helper = if n.x.abs() < 0.9 {
+        Vec3D { x: 1.0, y: 0.0, z: 0.0 }
+    } else {
+        Vec3D { x: 0.0, y: 1.0, z: 0.0 }
+    };
+    let t = vec3d_normalized(&vec3d_cross(&helper, n));
+    let b = vec3d_cross(n, &t);
+    (t, b)
+}
+
+/// Mirror direction `d` about the surface with normal `n`.
+#[inline]
+pub fn vec3d_reflect(d: &Vec3D, n: &Vec3D) -> Vec3D {
+    vec3d_sub(d, &vec3d_scale(n, 2.0 * vec3d_dot(d, n)))
+}
+
+/// Refract unit direction `d` through a surface with unit normal `n` facing
+/// the incident side, with `eta` = n_incident / n_transmitted.
+/// Returns `None` on total internal reflection.
+#[inline]
+pub fn vec3d_refract(d: &Vec3D, n: &Vec3D, eta: f64) -> Option<Vec3D> {
+    let cos_i = -vec3d_dot(d, n);
+    let k = 1.0 - eta * eta * (1.0 - cos_i * cos_i);
+    if k < 0.0 {
+        return None;
+    }
+    Some(vec3d_add(&vec3d_scale(d, eta), &vec3d_scale(n, eta * cos_i - k.sqrt())))
+}
+
 // ─── Matrix operations ───────────────────────────────────────
 
 /// Multiply matrix × vector: result = M * v
@@ -126,9 +160,9 @@ pub fn mat3_identity() -> Matrix3 {
 /// Build rotation matrix from Euler angles (in radians).
 /// Matching Math3D.pas RotateMatrixXYZ convention.
 pub fn mat3_from_euler(rx: f64, ry: f64, rz: f64) -> Matrix3 {
-    let (sx, cx) = rx.sin_cos();
-    let (sy, cy) = ry.sin_cos();
-    let (sz, cz) = rz.sin_cos();
+    let (sx, cx) = strict::sin_cos(rx);
+    let (sy, cy) = strict::sin_cos(ry);
+    let (sz, cz) = strict::sin_cos(rz);
 
     Matrix3 {
         m: [
@@ -200,14 +234,14 @@ impl Quaternion {
         // Clamp dot to valid range for acos
         let dot = dot.min(1.0);
 
-        let theta = dot.acos();
+        let theta = strict::acos(dot);
         if theta.abs() < 1e-10 {
             return *self; // Quaternions are nearly identical
         }
 
-        let sin_theta = theta.sin();
-        let s0 = ((1.0 - t) * theta).sin() / sin_theta;
-        let s1 = (t * theta).sin() / sin_theta;
+        let sin_theta = strict::sin(theta);
+        let s0 = strict::sin((1.0 - t) * theta) / sin_theta;
+        let s1 = strict::sin(t * theta) / sin_theta;
 
         Quaternion {
             w: s0 * self.w + s1 * other.w,
@@ -320,6 +354,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn test_quaternion_slerp_endpoints() {
         let a = Quaternion::identity();
         let b = Quaternion { w: 0.707107, x: 0.707107, y: 0.0, z: 0.0 };
@@ -344,4 +379,17 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_refract_snell() {
+        // 45° incidence into glass: sin θt = sin 45° / 1.5
+        let d = vec3d_normalized(&Vec3D { x: 1.0, y: -1.0, z: 0.0 });
+        let n = Vec3D { x: 0.0, y: 1.0, z: 0.0 };
+        let t = vec3d_refract(&d, &n, 1.0 / 1.5).unwrap();
+        assert!((vec3d_length(&t) - 1.0).abs() < 1e-12);
+        assert!((t.x - std::f64::consts::FRAC_1_SQRT_2 / 1.5).abs() < 1e-12);
+        // Leaving glass at a grazing angle → total internal reflection
+        let g = vec3d_normalized(&Vec3D { x: 1.0, y: 0.2, z: 0.0 });
+        assert!(vec3d_refract(&g, &Vec3D { x: 0.0, y: -1.0, z: 0.0 }, 1.5).is_none());
+    }
 }
@@ -1,2 +1,4 @@
 pub mod math3d;
+pub mod rng;
+pub mod strict;
 pub mod utils;
@@ -0,0 +1,96 @@
+//! Transcendental functions routed through one place for deterministic output.
+//!
+//! Network rendering verifies tiles by checksumming their G-buffers, so a
+//! tile rendered natively must match the same tile rendered in a browser bit
+//! for bit. Arithmetic is already safe: Rust never contracts `a * b + c` into
+//! an FMA on its own, and the renderer has no parallel reductions (rows are
+//! independent and every per-pixel sum runs in a fixed order). What differs
+//! between targets are `sin`, `ln`, `powf` and friends, which defer to the
+//! platform libm.
+//!
+//! With the `strict-determinism` feature these wrappers call the pure-Rust
+//! `libm` crate instead, which gives the same results on every target. Without
+//! it they are the std methods and cost nothing. `sqrt` is correctly rounded
+//! by IEEE 754 and needs no wrapper.
+
+#[cfg(feature = "strict-determinism")]
+mod imp {
+    #[inline]
+    pub fn sin(x: f64) -> f64 { libm::sin(x) }
+    #[inline]
+    pub fn cos(x: f64) -> f64 { libm::cos(x) }
+    #[inline]
+    pub fn tan(x: f64) -> f64 { libm::tan(x) }
+    #[inline]
+    pub fn asin(x: f64) -> f64 { libm::asin(x) }
+    #[inline]
+    pub fn acos(x: f64) -> f64 { libm::acos(x) }
+    #[inline]
+    pub fn atan(x: f64) -> f64 { libm::atan(x) }
+    #[inline]
+    pub fn atan2(y: f64, x: f64) -> f64 { libm::atan2(y, x) }
+    #[inline]
+    pub fn exp(x: f64) -> f64 { libm::exp(x) }
+    #[inline]
+    pub fn ln(x: f64) -> f64 { libm::log(x) }
+    #[inline]
+    pub fn powf(x: f64, y: f64) -> f64 { libm::pow(x, y) }
+    #[inline]
+    pub fn powi(x: f64, n: i32) -> f64 { libm::pow(x, n as f64) }
+    #[inline]
+    pub fn sin_cos(x: f64) -> (f64, f64) { libm::sincos(x) }
+}
+
+#[cfg(not(feature = "strict-determinism"))]
+mod imp {
+    #[inline(always)]
+    pub fn sin(x: f64) -> f64 { x.sin() }
+    #[inline(always)]
+    pub fn cos(x: f64) -> f64 { x.cos() }
+    #[inline(always)]
+    pub fn tan(x: f64) -> f64 { x.tan() }
+    #[inline(always)]
+    pub fn asin(x: f64) -> f64 { x.asin() }
+    #[inline(always)]
+    pub fn acos(x: f64) -> f64 { x.acos() }
+    #[inline(always)]
+    pub fn atan(x: f64) -> f64 { x.atan() }
+    #[inline(always)]
+    pub fn atan2(y: f64, x: f64) -> f64 { y.atan2(x) }
+    #[inline(always)]
+    pub fn exp(x: f64) -> f64 { x.exp() }
+    #[inline(always)]
+    pub fn ln(x: f64) -> f64 { x.ln() }
+    #[inline(always)]
+    pub fn powf(x: f64, y: f64) -> f64 { x.powf(y) }
+    #[inline(always)]
+    pub fn powi(x: f64, n: i32) -> f64 { x.powi(n) }
+    #[inline(always)]
+    pub fn sin_cos(x: f64) -> (f64, f64) { x.sin_cos() }
+}
+
+pub use imp::*;
+
+/// True when the crate was built with `strict-determinism`.
+pub const STRICT: bool = cfg!(feature = "strict-determinism");
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrappers_agree_with_std() {
+        for &x in &[0.1, 0.5, 1.3, 2.0, 7.25] {
+            let close = |a: f64, b: f64| (a - b).abs() <= 1e-14 * b.abs().max(1.0);
+            assert!(close(sin(x), x.sin()));
+            assert!(close(cos(x), x.cos()));
+            assert!(close(acos(x / 8.0), (x / 8.0).acos()));
+            assert!(close(atan2(x, -1.0), x.atan2(-1.0)));
+            assert!(close(ln(x), x.ln()));
+            assert!(close(powf(x, 7.0), x.powf(7.0)));
+            assert!(close(powi(x, 2), x * x));
+            let (s, c) = sin_cos(x);
+            assert!(close(s, x.sin()) && close(c, x.cos()));
+        }
+    }
+}
@@ -4,6 +4,7 @@
 /// utilities used throughout the rendering pipeline.
 
 use crate::engine::types::Vec3D;
+use super::strict;
 
 /// Clamp a value to [min, max] range.
 #[inline(always)]
@@ -83,18 +84,18 @@ pub fn smoothstep(edge0: f64, edge1: f64, x: f64) -> f64 {
 #[inline(always)]
 pub fn sin_cos_d(angle_deg: f64) -> (f64, f64) {
     let rad = angle_deg * std::f64::consts::PI / 180.0;
-    (rad.sin(), rad.cos())
+    (strict::sin(rad), strict::cos(rad))
 }
 
 /// Convert spherical coordinates to Cartesian unit vector.
 /// theta = polar angle from Z axis, phi = azimuthal angle from X axis.
 #[inline]
 pub fn spherical_to_cartesian(theta: f64, phi: f64) -> Vec3D {
-    let st = theta.sin();
+    let st = strict::sin(theta);
     Vec3D {
-        x: st * phi.cos(),
-        y: st * phi.sin(),
-        z: theta.cos(),
+        x: st * strict::cos(phi),
+        y: st * strict::sin(phi),
+        z: strict::cos(theta),
     }
 }
 
@@ -121,6 +122,75 @@ pub fn byte_to_float(v: u8) -> f64 {
     v as f64 / 255.0
 }
 
+/// Decode an sRGB-encoded channel value in [0, 1] to linear light.
+#[inline]
+pub fn srgb_to_linear(v: f64) -> f64 {
+    let v = clamp(v, 0.0, 1.0);
+    if v <= 0.04045 { v / 12.92 } else { ((v + 0.055) / 1.055).powf(2.4) }
+}
+
+/// Encode a linear-light channel value as sRGB (clamped to [0, 1]).
+#[inline]
+pub fn linear_to_srgb(v: f64) -> f64 {
+    let v = if v.is_nan() { 0.0 } else { clamp(v, 0.0, 1.0) };
+    if v <= 0.0031308 { v * 12.92 } else { 1.055 * v.powf(1.0 / 2.4) - 0.055 }
+}
+
+/// Linear sRGB → OKLab (L, a, b).
+pub fn linear_rgb_to_oklab(c: (f64, f64, f64)) -> (f64, f64, f64) {
+    let l = 0.4122214708 * c.0 + 0.5363325363 * c.1 + 0.0514459929 * c.2;
+    let m = 0.2119034982 * c.0 + 0.6806995451 * c.1 + 0.1073969566 * c.2;
+    let s = 0.0883024619 * c.0 + 0.2817188376 * c.1 + 0.6299787005 * c.2;
+    let (l, m, s) = (l.cbrt(), m.cbrt(), s.cbrt());
+    (
+        0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s,
+        1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s,
+        0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s,
+    )
+}
+
+/// OKLab (L, a, b) → linear sRGB.
+pub fn oklab_to_linear_rgb(c: (f64, f64, f64)) -> (f64, f64, f64) {
+    let l = c.0 + 0.3963377774 * c.1 + 0.2158037573 * c.2;
+    let m = c.0 - 0.1055613458 * c.1 - 0.0638541728 * c.2;
+    let s = c.0 - 0.0894841775 * c.1 - 1.2914855480 * c.2;
+    let (l, m, s) = (l * l * l, m * m * m, s * s * s);
+    (
+        4.0767416621 * l - 3.3077115913 * m + 0.2309699292 * s,
+        -1.2684380046 * l + 2.6097574011 * m - 0.3413193965 * s,
+        -0.0041960863 * l - 0.7034186147 * m + 1.7076147010 * s,
+    )
+}
+
+/// RGB → HSV with hue in [0, 1).
+pub fn rgb_to_hsv(c: (f64, f64, f64)) -> (f64, f64, f64) {
+    let max = c.0.max(c.1).max(c.2);
+    let min = c.0.min(c.1).min(c.2);
+    let d = max - min;
+    let h = if d <= 0.0 {
+        0.0
+    } else if max == c.0 {
+        ((c.1 - c.2) / d).rem_euclid(6.0) / 6.0
+    } else if max == c.1 {
+        ((c.2 - c.0) / d + 2.0) / 6.0
+    } else {
+        ((c.0 - c.1) / d + 4.0) / 6.0
+    };
+    let s = if max > 0.0 { d / max } else { 0.0 };
+    (h, s, max)
+}
+
+/// HSV (hue in turns) → RGB.
+pub fn hsv_to_rgb(c: (f64, f64, f64)) -> (f64, f64, f64) {
+    let (h, s, v) = c;
+    let h = h.rem_euclid(1.0) * 6.0;
+    let f = |n: f64| {
+        let k = (n + h) % 6.0;
+        v - v * s * k.min(4.0 - k).clamp(0.0, 1.0)
+    };
+    (f(5.0), f(3.0), f(1.0))
+}
+
 /// Parse a CSS hex color string "#RRGGBB" to (r, g, b) as f64 in [0, 1].
 pub fn parse_hex_color(hex: &str) -> (f64, f64, f64) {
     let hex = hex.trim_start_matches('#');
@@ -156,6 +226,30 @@ mod tests {
         assert!((lerp(0.0, 10.0, 1.0) - 10.0).abs() < 1e-10);
     }
 
+    #[test]
+    fn test_srgb_round_trip() {
+        assert!((srgb_to_linear(0.5) - 0.21404).abs() < 1e-5);
+        assert!((linear_to_srgb(0.18) - 0.46135).abs() < 1e-5);
+        for i in 0..=255 {
+            let v = i as f64 / 255.0;
+            assert!((linear_to_srgb(srgb_to_linear(v)) - v).abs() < 1e-12);
+        }
+        assert!((linear_to_srgb(2.0) - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_color_space_round_trips() {
+        for c in [(1.0, 0.0, 0.0), (0.2, 0.7, 0.4), (0.0, 0.0, 1.0), (0.5, 0.5, 0.5)] {
+            let back = oklab_to_linear_rgb(linear_rgb_to_oklab(c));
+            assert!((back.0 - c.0).abs() < 1e-6 && (back.1 - c.1).abs() < 1e-6 && (back.2 - c.2).abs() < 1e-6);
+            let back = hsv_to_rgb(rgb_to_hsv(c));
+            assert!((back.0 - c.0).abs() < 1e-12 && (back.1 - c.1).abs() < 1e-12 && (back.2 - c.2).abs() < 1e-12);
+        }
+        let white = linear_rgb_to_oklab((1.0, 1.0, 1.0));
+        assert!((white.0 - 1.0).abs() < 1e-6 && white.1.abs() < 1e-6);
+        assert!((rgb_to_hsv((0.0, 1.0, 0.0)).0 - 1.0 / 3.0).abs() < 1e-12);
+    }
+
     #[test]
     fn test_parse_hex_color() {
         let (r, g, b) = parse_hex_color("#ff8040");